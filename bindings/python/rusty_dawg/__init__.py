@@ -1,4 +1,5 @@
 from .rusty_dawg import *  # This Python library wraps the built Rust library.
+from .rusty_dawg import __version__
 from transformers.tokenization_utils import PreTrainedTokenizer
 from typing import Dict
 
@@ -0,0 +1,17 @@
+from typing import Any, Dict, List
+
+from .rusty_dawg import *
+from .rusty_dawg import Dawg, __version__
+
+class PyDawg:
+    dawg: Dawg
+    tokenizer: Any
+    def __init__(self, dawg: Dawg, tokenizer: Any) -> None: ...
+    def get_suffix_context(self, query: str) -> Dict[str, List[int]]: ...
+    def get_matching_substrings(
+        self, query: str, min_length: int = ..., remove_redundant: bool = ...
+    ) -> Dict[str, Any]: ...
+    @staticmethod
+    def remove_redundant_substrings(
+        matching_substrings: List[Dict[str, Any]]
+    ) -> List[Dict[str, Any]]: ...
@@ -0,0 +1,155 @@
+"""
+asyncio-compatible wrappers for querying (C)DAWGs without stalling the event loop.
+
+The Rust index classes are not thread-safe (`Cdawg` and `DiskCdawg` are `unsendable`), so each index
+has to be created and queried on a single thread. The wrappers here own a pool of worker threads,
+each with its own copy of the index: the index is loaded on every worker, queries are submitted to
+them, and the results come back as awaitables. With one worker, the default, queries are answered
+in the order they were submitted. With more, batched queries are split across the workers. The
+index's `batch_*` methods release the GIL, so calls to them on different workers run in parallel.
+"""
+
+import asyncio
+from concurrent.futures import ThreadPoolExecutor
+from typing import Any, Callable, List, Sequence, Tuple, TypeVar
+
+T = TypeVar("T")
+
+
+class _Worker:
+    """
+    A single thread holding its own copy of an index.
+    """
+
+    def __init__(self, factory: Callable[[], Any], name: str):
+        self.executor = ThreadPoolExecutor(max_workers=1, thread_name_prefix=name)
+        self.holder = {}
+        self.executor.submit(self._create, factory).result()
+
+    def _create(self, factory: Callable[[], Any]):
+        self.holder["index"] = factory()
+
+    def call(self, fn: Callable[[Any], T]) -> T:
+        return fn(self.holder["index"])
+
+    def close(self):
+        self.executor.submit(self.holder.clear).result()
+        self.executor.shutdown()
+
+
+class AsyncIndex:
+    """
+    Runs calls against an index on a pool of worker threads.
+    """
+
+    def __init__(self, factory: Callable[[], Any], n_workers: int = 1):
+        """
+        `factory` is called on each worker thread to create or load that worker's index, e.g.
+        `lambda: DiskCdawg.load(tokens_path, cdawg_path)`. Each of the `n_workers` threads holds
+        its own index, so a RAM index takes `n_workers` times the memory.
+        """
+        if n_workers < 1:
+            raise ValueError(f"n_workers must be at least 1, not {n_workers}")
+        self._workers = [_Worker(factory, f"rusty_dawg_{idx}") for idx in range(n_workers)]
+        self._next_worker = 0
+
+    async def _run_on(self, worker: _Worker, fn: Callable[[Any], T]) -> T:
+        loop = asyncio.get_running_loop()
+        return await loop.run_in_executor(worker.executor, worker.call, fn)
+
+    async def run(self, fn: Callable[[Any], T]) -> T:
+        """
+        Apply `fn` to the index on the next worker thread, taking turns between them.
+        """
+        worker = self._workers[self._next_worker]
+        self._next_worker = (self._next_worker + 1) % len(self._workers)
+        return await self._run_on(worker, fn)
+
+    async def map(self, fn: Callable[[Any, Any], T], queries: Sequence[Any]) -> List[T]:
+        """
+        Apply `fn(index, query)` to each query, split into contiguous chunks across the worker
+        threads, preserving order.
+        """
+        chunk_size = max(1, -(-len(queries) // len(self._workers)))
+        chunks = [queries[idx : idx + chunk_size] for idx in range(0, len(queries), chunk_size)]
+        results = await asyncio.gather(
+            *(
+                self._run_on(worker, lambda index, chunk=chunk: [fn(index, q) for q in chunk])
+                for worker, chunk in zip(self._workers, chunks)
+            )
+        )
+        return [result for chunk_results in results for result in chunk_results]
+
+    def close(self):
+        """
+        Drop each index on the thread that created it and shut down the workers.
+        """
+        for worker in self._workers:
+            worker.close()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc_info):
+        self.close()
+
+
+def _cdawg_suffix_context(cdawg, tokens: Sequence[int]) -> Tuple[List[int], List[int]]:
+    cs = cdawg.get_initial()
+    lengths, counts = [], []
+    for token in tokens:
+        cs = cdawg.transition_and_count(cs, token)
+        lengths.append(cs.get_length())
+        counts.append(cdawg.get_suffix_count(cs))
+    return lengths, counts
+
+
+def _cdawg_next_tokens(cdawg, tokens: Sequence[int]) -> List[Tuple[int, float]]:
+    cs = cdawg.get_initial()
+    for token in tokens:
+        cs = cdawg.transition_and_count(cs, token)
+    return cdawg.get_next_tokens(cs)
+
+
+def _dawg_suffix_context(dawg, tokens: Sequence[int]) -> Tuple[List[int], List[int]]:
+    state, length = dawg.get_initial(), 0
+    lengths, counts = [], []
+    for token in tokens:
+        state, length = dawg.transition_and_count(state, token, length)
+        lengths.append(length)
+        counts.append(dawg.get_count(state))
+    return lengths, counts
+
+
+class AsyncCdawg(AsyncIndex):
+    """
+    Async batched queries over a `Cdawg` or `DiskCdawg`.
+    """
+
+    async def suffix_contexts(
+        self, queries: Sequence[Sequence[int]]
+    ) -> List[Tuple[List[int], List[int]]]:
+        """
+        For each query, return the matched suffix length and its count at every position.
+        """
+        return await self.map(_cdawg_suffix_context, queries)
+
+    async def next_tokens(self, queries: Sequence[Sequence[int]]) -> List[List[Tuple[int, float]]]:
+        """
+        For each query, return the next-token distribution after matching the whole query.
+        """
+        return await self.map(_cdawg_next_tokens, queries)
+
+
+class AsyncDawg(AsyncIndex):
+    """
+    Async batched queries over a `Dawg` or `DiskDawg`.
+    """
+
+    async def suffix_contexts(
+        self, queries: Sequence[Sequence[int]]
+    ) -> List[Tuple[List[int], List[int]]]:
+        """
+        For each query, return the matched suffix length and its count at every position.
+        """
+        return await self.map(_dawg_suffix_context, queries)
@@ -0,0 +1,617 @@
+# Type stubs for the compiled Rust extension module.
+# Keep these in sync with the #[pymethods] blocks in bindings/python/src.
+
+from typing import Any, Callable, Dict, Iterator, List, Optional, Tuple, Union, overload
+
+import numpy as np
+import numpy.typing as npt
+
+__version__: str
+
+Span = Tuple[int, int]
+# A DAWG walker position: (state, length).
+Position = Tuple[int, int]
+# A raw string, tokenized with the tokenizer in the index manifest, or token IDs.
+Query = Union[str, List[int]]
+# Called during builds with n_tokens, n_nodes, n_edges, elapsed_secs, tokens_per_sec and
+# cache_hit_rate (None if nothing was read through a cache).
+TelemetryCallback = Callable[[Dict[str, Any]], None]
+# CSR arrays keyed as in rusty_dawg.transitions, plus "shape" as a (rows, vocab) tuple.
+TransitionArrays = Dict[str, Any]
+
+class CdawgState:
+    def get_length(self) -> int: ...
+    def get_state_and_gamma(self) -> Tuple[Optional[int], Span]: ...
+
+class CdawgStateIx64:
+    def get_length(self) -> int: ...
+    def get_state_and_gamma(self) -> Tuple[Optional[int], Span]: ...
+
+class Dawg:
+    def __init__(self) -> None: ...
+    @classmethod
+    def load(cls, path: str) -> "Dawg": ...
+    def build(
+        self,
+        text: List[int],
+        callback: Optional[TelemetryCallback] = None,
+        every: int = 100000,
+    ) -> None: ...
+    def build_from_tokens(self, tokens: List[int]) -> None: ...
+    def extend_token(self, token: int) -> int: ...
+    def end_document(self, doc_id: int) -> None: ...
+    def get_last(self) -> Tuple[int, int]: ...
+    def transition_matrix(
+        self, max_states: int, vocab_size: Optional[int] = None
+    ) -> TransitionArrays: ...
+    def get_initial(self) -> int: ...
+    def transition(self, state: int, token: int, use_failures: bool) -> Optional[int]: ...
+    def transition_and_count(
+        self, state: int, token: int, length: int
+    ) -> Tuple[Optional[int], int]: ...
+    def get_count(self, state: int) -> int: ...
+    # Deprecated: use the walker methods below.
+    def get_edges(self, state: int) -> List[Tuple[int, int]]: ...
+    def walker_start(self) -> Position: ...
+    def enter(self, position: Position, token: int) -> Optional[Position]: ...
+    def fail(self, position: Position) -> Optional[Position]: ...
+    def counts(self, position: Position) -> int: ...
+    def emit_state_id(self, position: Position) -> int: ...
+    def recompute_lengths(self) -> None: ...
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def get_failure(self, state: int) -> Optional[int]: ...
+    def get_length(self, state: int) -> int: ...
+    def set_tokenizer(self, name: str) -> None: ...
+    def tokenize(self, text: str) -> Tuple[List[int], List[Span]]: ...
+    def get_ngram_count(self, query: Query) -> int: ...
+    def get_suffix_length_profile(self, query: Query) -> List[int]: ...
+
+class DiskDawg:
+    @classmethod
+    def load(cls, path: str) -> "DiskDawg": ...
+    def build(
+        self,
+        text: List[int],
+        callback: Optional[TelemetryCallback] = None,
+        every: int = 100000,
+    ) -> None: ...
+    def transition_matrix(
+        self, max_states: int, vocab_size: Optional[int] = None
+    ) -> TransitionArrays: ...
+    def get_initial(self) -> int: ...
+    def transition(self, state: int, token: int, use_failures: bool) -> Optional[int]: ...
+    def transition_and_count(
+        self, state: int, token: int, length: int
+    ) -> Tuple[Optional[int], int]: ...
+    def get_count(self, state: int) -> int: ...
+    # Deprecated: use the walker methods below.
+    def get_edges(self, state: int) -> List[Tuple[int, int]]: ...
+    def walker_start(self) -> Position: ...
+    def enter(self, position: Position, token: int) -> Optional[Position]: ...
+    def fail(self, position: Position) -> Optional[Position]: ...
+    def counts(self, position: Position) -> int: ...
+    def emit_state_id(self, position: Position) -> int: ...
+    def recompute_lengths(self) -> None: ...
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def get_failure(self, state: int) -> Optional[int]: ...
+    def get_length(self, state: int) -> int: ...
+    def set_tokenizer(self, name: str) -> None: ...
+    def tokenize(self, text: str) -> Tuple[List[int], List[Span]]: ...
+    def get_ngram_count(self, query: Query) -> int: ...
+    def get_suffix_length_profile(self, query: Query) -> List[int]: ...
+
+class Cdawg:
+    EOS: int
+    def __init__(self, tokens: List[int]) -> None: ...
+    def build(
+        self, callback: Optional[TelemetryCallback] = None, every: int = 100000
+    ) -> None: ...
+    def update(self, in_state: int, start: int, end: int) -> Tuple[int, int]: ...
+    def fill_counts(self) -> None: ...
+    def fill_counts_ram(self) -> None: ...
+    def fill_doc_frequencies(self) -> None: ...
+    def fill_domain_counts(self, max_exact_domains: int = 64) -> None: ...
+    def traverse_arities(self, capacity: int) -> List[int]: ...
+    def freeze(self) -> FrozenCdawg: ...
+    def get_source(self) -> int: ...
+    def get_initial(self) -> CdawgState: ...
+    def transition_and_count(self, cs: CdawgState, token: int) -> CdawgState: ...
+    def get_edge_by_token(self, state: int, token: int) -> Optional[int]: ...
+    def get_start_end_target(self, edge_idx: int) -> Tuple[int, int, int]: ...
+    def resolve_span(self, edge_idx: int) -> List[int]: ...
+    def iter_edges(self, state: int) -> List[Tuple[int, int, int, List[int]]]: ...
+    def get_count(self, state: int) -> int: ...
+    def get_doc_frequency(self, state: int) -> int: ...
+    def get_domain_counts(self, state: int) -> List[Tuple[int, int]]: ...
+    def implicitly_fail(self, state: int, gamma: Span) -> CdawgState: ...
+    # Deprecated: use the walker methods below.
+    def get_length(self, state: int) -> int: ...
+    def neighbors(self, state: int) -> List[int]: ...
+    def enter(self, cs: CdawgState, token: int) -> Optional[CdawgState]: ...
+    def fail(self, cs: CdawgState) -> Optional[CdawgState]: ...
+    def counts(self, cs: CdawgState) -> int: ...
+    def emit_state_id(self, cs: CdawgState) -> int: ...
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def set_tokenizer(self, name: str) -> None: ...
+    def tokenize(self, text: str) -> Tuple[List[int], List[Span]]: ...
+    def get_suffix_count(self, cs: CdawgState, kind: str = "occurrences") -> int: ...
+    def get_ngram_count(self, query: Query) -> int: ...
+    def get_suffix_length_profile(self, query: Query) -> List[int]: ...
+    def next_token_strs(self, query: Query) -> List[Tuple[str, float]]: ...
+    def probability(self, query: Query) -> float: ...
+    def log_probability(self, query: Query) -> float: ...
+    def get_matching_doc_ids(self, cs: CdawgState, limit: int = 100) -> List[int]: ...
+    def delete_document(self, doc_id: int) -> int: ...
+    def get_matching_doc_spans(
+        self, cs: CdawgState, limit: int = 100
+    ) -> List[Tuple[int, int, int]]: ...
+    def get_document_span(
+        self, doc_id: int
+    ) -> Optional[Tuple[int, int, Optional[int]]]: ...
+    def get_document_count(self) -> int: ...
+    def get_entropy(self, cs: CdawgState) -> float: ...
+    def get_next_tokens(
+        self, cs: CdawgState, kind: str = "occurrences"
+    ) -> List[Tuple[int, float]]: ...
+    def get_top_k_continuations(
+        self, cs: CdawgState, k: int, m: int
+    ) -> List[Tuple[List[int], float]]: ...
+    def batch_suffix_lengths(
+        self, batch: List[List[int]]
+    ) -> List[npt.NDArray[np.uint64]]: ...
+    def batch_next_token_probs(
+        self, batch: List[List[int]], kind: str = "occurrences"
+    ) -> List[Tuple[npt.NDArray[np.uint16], npt.NDArray[np.float64]]]: ...
+    def iter_frequent_spans(self, min_count: int) -> Iterator[Tuple[List[int], int]]: ...
+
+class Cdawg32:
+    EOS: int
+    def __init__(self, tokens: List[int]) -> None: ...
+    def build(
+        self, callback: Optional[TelemetryCallback] = None, every: int = 100000
+    ) -> None: ...
+    def update(self, in_state: int, start: int, end: int) -> Tuple[int, int]: ...
+    def fill_counts(self) -> None: ...
+    def fill_counts_ram(self) -> None: ...
+    def fill_doc_frequencies(self) -> None: ...
+    def fill_domain_counts(self, max_exact_domains: int = 64) -> None: ...
+    def traverse_arities(self, capacity: int) -> List[int]: ...
+    def freeze(self) -> FrozenCdawg32: ...
+    def get_source(self) -> int: ...
+    def get_initial(self) -> CdawgState: ...
+    def transition_and_count(self, cs: CdawgState, token: int) -> CdawgState: ...
+    def get_edge_by_token(self, state: int, token: int) -> Optional[int]: ...
+    def get_start_end_target(self, edge_idx: int) -> Tuple[int, int, int]: ...
+    def resolve_span(self, edge_idx: int) -> List[int]: ...
+    def iter_edges(self, state: int) -> List[Tuple[int, int, int, List[int]]]: ...
+    def get_count(self, state: int) -> int: ...
+    def get_doc_frequency(self, state: int) -> int: ...
+    def get_domain_counts(self, state: int) -> List[Tuple[int, int]]: ...
+    def implicitly_fail(self, state: int, gamma: Span) -> CdawgState: ...
+    # Deprecated: use the walker methods below.
+    def get_length(self, state: int) -> int: ...
+    def neighbors(self, state: int) -> List[int]: ...
+    def enter(self, cs: CdawgState, token: int) -> Optional[CdawgState]: ...
+    def fail(self, cs: CdawgState) -> Optional[CdawgState]: ...
+    def counts(self, cs: CdawgState) -> int: ...
+    def emit_state_id(self, cs: CdawgState) -> int: ...
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def set_tokenizer(self, name: str) -> None: ...
+    def tokenize(self, text: str) -> Tuple[List[int], List[Span]]: ...
+    def get_suffix_count(self, cs: CdawgState, kind: str = "occurrences") -> int: ...
+    def get_ngram_count(self, query: Query) -> int: ...
+    def get_suffix_length_profile(self, query: Query) -> List[int]: ...
+    def next_token_strs(self, query: Query) -> List[Tuple[str, float]]: ...
+    def probability(self, query: Query) -> float: ...
+    def log_probability(self, query: Query) -> float: ...
+    def get_matching_doc_ids(self, cs: CdawgState, limit: int = 100) -> List[int]: ...
+    def delete_document(self, doc_id: int) -> int: ...
+    def get_matching_doc_spans(
+        self, cs: CdawgState, limit: int = 100
+    ) -> List[Tuple[int, int, int]]: ...
+    def get_document_span(
+        self, doc_id: int
+    ) -> Optional[Tuple[int, int, Optional[int]]]: ...
+    def get_document_count(self) -> int: ...
+    def get_entropy(self, cs: CdawgState) -> float: ...
+    def get_next_tokens(
+        self, cs: CdawgState, kind: str = "occurrences"
+    ) -> List[Tuple[int, float]]: ...
+    def get_top_k_continuations(
+        self, cs: CdawgState, k: int, m: int
+    ) -> List[Tuple[List[int], float]]: ...
+    def batch_suffix_lengths(
+        self, batch: List[List[int]]
+    ) -> List[npt.NDArray[np.uint64]]: ...
+    def batch_next_token_probs(
+        self, batch: List[List[int]], kind: str = "occurrences"
+    ) -> List[Tuple[npt.NDArray[np.uint32], npt.NDArray[np.float64]]]: ...
+    def iter_frequent_spans(self, min_count: int) -> Iterator[Tuple[List[int], int]]: ...
+
+class DiskCdawg:
+    EOS: int
+    def __init__(
+        self, tokens_path: str, mb_path: str, n_nodes: int, n_edges: int
+    ) -> None: ...
+    @classmethod
+    def load(cls, tokens_path: str, mb_path: str) -> "DiskCdawg": ...
+    @classmethod
+    def load_shared(cls, mb_path: str) -> "DiskCdawg": ...
+    def build(
+        self, callback: Optional[TelemetryCallback] = None, every: int = 100000
+    ) -> None: ...
+    def update(self, in_state: int, start: int, end: int) -> Tuple[int, int]: ...
+    def fill_counts(self, stack_path: str, capacity: int) -> None: ...
+    def fill_counts_ram(self) -> None: ...
+    def fill_doc_frequencies(self) -> None: ...
+    def fill_domain_counts(self, max_exact_domains: int = 64) -> None: ...
+    def traverse_arities(self, capacity: int) -> List[int]: ...
+    def freeze(self) -> FrozenCdawg: ...
+    def get_source(self) -> int: ...
+    def get_initial(self) -> CdawgState: ...
+    def transition_and_count(self, cs: CdawgState, token: int) -> CdawgState: ...
+    def get_edge_by_token(self, state: int, token: int) -> Optional[int]: ...
+    def get_start_end_target(self, edge_idx: int) -> Tuple[int, int, int]: ...
+    def resolve_span(self, edge_idx: int) -> List[int]: ...
+    def iter_edges(self, state: int) -> List[Tuple[int, int, int, List[int]]]: ...
+    def get_count(self, state: int) -> int: ...
+    def get_doc_frequency(self, state: int) -> int: ...
+    def get_domain_counts(self, state: int) -> List[Tuple[int, int]]: ...
+    def implicitly_fail(self, state: int, gamma: Span) -> CdawgState: ...
+    # Deprecated: use the walker methods below.
+    def get_length(self, state: int) -> int: ...
+    def neighbors(self, state: int) -> List[int]: ...
+    def enter(self, cs: CdawgState, token: int) -> Optional[CdawgState]: ...
+    def fail(self, cs: CdawgState) -> Optional[CdawgState]: ...
+    def counts(self, cs: CdawgState) -> int: ...
+    def emit_state_id(self, cs: CdawgState) -> int: ...
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def set_tokenizer(self, name: str) -> None: ...
+    def tokenize(self, text: str) -> Tuple[List[int], List[Span]]: ...
+    def get_suffix_count(self, cs: CdawgState, kind: str = "occurrences") -> int: ...
+    def get_ngram_count(self, query: Query) -> int: ...
+    def get_suffix_length_profile(self, query: Query) -> List[int]: ...
+    def next_token_strs(self, query: Query) -> List[Tuple[str, float]]: ...
+    def probability(self, query: Query) -> float: ...
+    def log_probability(self, query: Query) -> float: ...
+    def get_matching_doc_ids(self, cs: CdawgState, limit: int = 100) -> List[int]: ...
+    def delete_document(self, doc_id: int) -> int: ...
+    def save_metadata(self, path: str) -> None: ...
+    def get_matching_doc_spans(
+        self, cs: CdawgState, limit: int = 100
+    ) -> List[Tuple[int, int, int]]: ...
+    def get_document_span(
+        self, doc_id: int
+    ) -> Optional[Tuple[int, int, Optional[int]]]: ...
+    def get_document_count(self) -> int: ...
+    def get_entropy(self, cs: CdawgState) -> float: ...
+    def get_next_tokens(
+        self, cs: CdawgState, kind: str = "occurrences"
+    ) -> List[Tuple[int, float]]: ...
+    def get_top_k_continuations(
+        self, cs: CdawgState, k: int, m: int
+    ) -> List[Tuple[List[int], float]]: ...
+    def batch_suffix_lengths(
+        self, batch: List[List[int]]
+    ) -> List[npt.NDArray[np.uint64]]: ...
+    def batch_next_token_probs(
+        self, batch: List[List[int]], kind: str = "occurrences"
+    ) -> List[Tuple[npt.NDArray[np.uint16], npt.NDArray[np.float64]]]: ...
+    def iter_frequent_spans(self, min_count: int) -> Iterator[Tuple[List[int], int]]: ...
+
+class DiskCdawgIx64:
+    EOS: int
+    def __init__(
+        self, tokens_path: str, mb_path: str, n_nodes: int, n_edges: int
+    ) -> None: ...
+    @classmethod
+    def load(cls, tokens_path: str, mb_path: str) -> "DiskCdawgIx64": ...
+    @classmethod
+    def load_shared(cls, mb_path: str) -> "DiskCdawgIx64": ...
+    def build(
+        self, callback: Optional[TelemetryCallback] = None, every: int = 100000
+    ) -> None: ...
+    def update(self, in_state: int, start: int, end: int) -> Tuple[int, int]: ...
+    def fill_counts(self, stack_path: str, capacity: int) -> None: ...
+    def fill_counts_ram(self) -> None: ...
+    def fill_doc_frequencies(self) -> None: ...
+    def fill_domain_counts(self, max_exact_domains: int = 64) -> None: ...
+    def traverse_arities(self, capacity: int) -> List[int]: ...
+    def freeze(self) -> FrozenCdawgIx64: ...
+    def get_source(self) -> int: ...
+    def get_initial(self) -> CdawgStateIx64: ...
+    def transition_and_count(self, cs: CdawgStateIx64, token: int) -> CdawgStateIx64: ...
+    def get_edge_by_token(self, state: int, token: int) -> Optional[int]: ...
+    def get_start_end_target(self, edge_idx: int) -> Tuple[int, int, int]: ...
+    def resolve_span(self, edge_idx: int) -> List[int]: ...
+    def iter_edges(self, state: int) -> List[Tuple[int, int, int, List[int]]]: ...
+    def get_count(self, state: int) -> int: ...
+    def get_doc_frequency(self, state: int) -> int: ...
+    def get_domain_counts(self, state: int) -> List[Tuple[int, int]]: ...
+    def implicitly_fail(self, state: int, gamma: Span) -> CdawgStateIx64: ...
+    # Deprecated: use the walker methods below.
+    def get_length(self, state: int) -> int: ...
+    def neighbors(self, state: int) -> List[int]: ...
+    def enter(self, cs: CdawgStateIx64, token: int) -> Optional[CdawgStateIx64]: ...
+    def fail(self, cs: CdawgStateIx64) -> Optional[CdawgStateIx64]: ...
+    def counts(self, cs: CdawgStateIx64) -> int: ...
+    def emit_state_id(self, cs: CdawgStateIx64) -> int: ...
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def set_tokenizer(self, name: str) -> None: ...
+    def tokenize(self, text: str) -> Tuple[List[int], List[Span]]: ...
+    def get_suffix_count(self, cs: CdawgStateIx64, kind: str = "occurrences") -> int: ...
+    def get_ngram_count(self, query: Query) -> int: ...
+    def get_suffix_length_profile(self, query: Query) -> List[int]: ...
+    def next_token_strs(self, query: Query) -> List[Tuple[str, float]]: ...
+    def probability(self, query: Query) -> float: ...
+    def log_probability(self, query: Query) -> float: ...
+    def get_matching_doc_ids(self, cs: CdawgStateIx64, limit: int = 100) -> List[int]: ...
+    def delete_document(self, doc_id: int) -> int: ...
+    def save_metadata(self, path: str) -> None: ...
+    def get_matching_doc_spans(
+        self, cs: CdawgStateIx64, limit: int = 100
+    ) -> List[Tuple[int, int, int]]: ...
+    def get_document_span(
+        self, doc_id: int
+    ) -> Optional[Tuple[int, int, Optional[int]]]: ...
+    def get_document_count(self) -> int: ...
+    def get_entropy(self, cs: CdawgStateIx64) -> float: ...
+    def get_next_tokens(
+        self, cs: CdawgStateIx64, kind: str = "occurrences"
+    ) -> List[Tuple[int, float]]: ...
+    def get_top_k_continuations(
+        self, cs: CdawgStateIx64, k: int, m: int
+    ) -> List[Tuple[List[int], float]]: ...
+    def batch_suffix_lengths(
+        self, batch: List[List[int]]
+    ) -> List[npt.NDArray[np.uint64]]: ...
+    def batch_next_token_probs(
+        self, batch: List[List[int]], kind: str = "occurrences"
+    ) -> List[Tuple[npt.NDArray[np.uint16], npt.NDArray[np.float64]]]: ...
+    def iter_frequent_spans(self, min_count: int) -> Iterator[Tuple[List[int], int]]: ...
+
+class DiskCdawg32:
+    EOS: int
+    def __init__(
+        self, tokens_path: str, mb_path: str, n_nodes: int, n_edges: int
+    ) -> None: ...
+    @classmethod
+    def load(cls, tokens_path: str, mb_path: str) -> "DiskCdawg32": ...
+    @classmethod
+    def load_shared(cls, mb_path: str) -> "DiskCdawg32": ...
+    def build(
+        self, callback: Optional[TelemetryCallback] = None, every: int = 100000
+    ) -> None: ...
+    def update(self, in_state: int, start: int, end: int) -> Tuple[int, int]: ...
+    def fill_counts(self, stack_path: str, capacity: int) -> None: ...
+    def fill_counts_ram(self) -> None: ...
+    def fill_doc_frequencies(self) -> None: ...
+    def fill_domain_counts(self, max_exact_domains: int = 64) -> None: ...
+    def traverse_arities(self, capacity: int) -> List[int]: ...
+    def freeze(self) -> FrozenCdawg32: ...
+    def get_source(self) -> int: ...
+    def get_initial(self) -> CdawgState: ...
+    def transition_and_count(self, cs: CdawgState, token: int) -> CdawgState: ...
+    def get_edge_by_token(self, state: int, token: int) -> Optional[int]: ...
+    def get_start_end_target(self, edge_idx: int) -> Tuple[int, int, int]: ...
+    def resolve_span(self, edge_idx: int) -> List[int]: ...
+    def iter_edges(self, state: int) -> List[Tuple[int, int, int, List[int]]]: ...
+    def get_count(self, state: int) -> int: ...
+    def get_doc_frequency(self, state: int) -> int: ...
+    def get_domain_counts(self, state: int) -> List[Tuple[int, int]]: ...
+    def implicitly_fail(self, state: int, gamma: Span) -> CdawgState: ...
+    # Deprecated: use the walker methods below.
+    def get_length(self, state: int) -> int: ...
+    def neighbors(self, state: int) -> List[int]: ...
+    def enter(self, cs: CdawgState, token: int) -> Optional[CdawgState]: ...
+    def fail(self, cs: CdawgState) -> Optional[CdawgState]: ...
+    def counts(self, cs: CdawgState) -> int: ...
+    def emit_state_id(self, cs: CdawgState) -> int: ...
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def set_tokenizer(self, name: str) -> None: ...
+    def tokenize(self, text: str) -> Tuple[List[int], List[Span]]: ...
+    def get_suffix_count(self, cs: CdawgState, kind: str = "occurrences") -> int: ...
+    def get_ngram_count(self, query: Query) -> int: ...
+    def get_suffix_length_profile(self, query: Query) -> List[int]: ...
+    def next_token_strs(self, query: Query) -> List[Tuple[str, float]]: ...
+    def probability(self, query: Query) -> float: ...
+    def log_probability(self, query: Query) -> float: ...
+    def get_matching_doc_ids(self, cs: CdawgState, limit: int = 100) -> List[int]: ...
+    def delete_document(self, doc_id: int) -> int: ...
+    def save_metadata(self, path: str) -> None: ...
+    def get_matching_doc_spans(
+        self, cs: CdawgState, limit: int = 100
+    ) -> List[Tuple[int, int, int]]: ...
+    def get_document_span(
+        self, doc_id: int
+    ) -> Optional[Tuple[int, int, Optional[int]]]: ...
+    def get_document_count(self) -> int: ...
+    def get_entropy(self, cs: CdawgState) -> float: ...
+    def get_next_tokens(
+        self, cs: CdawgState, kind: str = "occurrences"
+    ) -> List[Tuple[int, float]]: ...
+    def get_top_k_continuations(
+        self, cs: CdawgState, k: int, m: int
+    ) -> List[Tuple[List[int], float]]: ...
+    def batch_suffix_lengths(
+        self, batch: List[List[int]]
+    ) -> List[npt.NDArray[np.uint64]]: ...
+    def batch_next_token_probs(
+        self, batch: List[List[int]], kind: str = "occurrences"
+    ) -> List[Tuple[npt.NDArray[np.uint32], npt.NDArray[np.float64]]]: ...
+    def iter_frequent_spans(self, min_count: int) -> Iterator[Tuple[List[int], int]]: ...
+
+class DiskCdawg32Ix64:
+    EOS: int
+    def __init__(
+        self, tokens_path: str, mb_path: str, n_nodes: int, n_edges: int
+    ) -> None: ...
+    @classmethod
+    def load(cls, tokens_path: str, mb_path: str) -> "DiskCdawg32Ix64": ...
+    @classmethod
+    def load_shared(cls, mb_path: str) -> "DiskCdawg32Ix64": ...
+    def build(
+        self, callback: Optional[TelemetryCallback] = None, every: int = 100000
+    ) -> None: ...
+    def update(self, in_state: int, start: int, end: int) -> Tuple[int, int]: ...
+    def fill_counts(self, stack_path: str, capacity: int) -> None: ...
+    def fill_counts_ram(self) -> None: ...
+    def fill_doc_frequencies(self) -> None: ...
+    def fill_domain_counts(self, max_exact_domains: int = 64) -> None: ...
+    def traverse_arities(self, capacity: int) -> List[int]: ...
+    def freeze(self) -> FrozenCdawg32Ix64: ...
+    def get_source(self) -> int: ...
+    def get_initial(self) -> CdawgStateIx64: ...
+    def transition_and_count(self, cs: CdawgStateIx64, token: int) -> CdawgStateIx64: ...
+    def get_edge_by_token(self, state: int, token: int) -> Optional[int]: ...
+    def get_start_end_target(self, edge_idx: int) -> Tuple[int, int, int]: ...
+    def resolve_span(self, edge_idx: int) -> List[int]: ...
+    def iter_edges(self, state: int) -> List[Tuple[int, int, int, List[int]]]: ...
+    def get_count(self, state: int) -> int: ...
+    def get_doc_frequency(self, state: int) -> int: ...
+    def get_domain_counts(self, state: int) -> List[Tuple[int, int]]: ...
+    def implicitly_fail(self, state: int, gamma: Span) -> CdawgStateIx64: ...
+    # Deprecated: use the walker methods below.
+    def get_length(self, state: int) -> int: ...
+    def neighbors(self, state: int) -> List[int]: ...
+    def enter(self, cs: CdawgStateIx64, token: int) -> Optional[CdawgStateIx64]: ...
+    def fail(self, cs: CdawgStateIx64) -> Optional[CdawgStateIx64]: ...
+    def counts(self, cs: CdawgStateIx64) -> int: ...
+    def emit_state_id(self, cs: CdawgStateIx64) -> int: ...
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def set_tokenizer(self, name: str) -> None: ...
+    def tokenize(self, text: str) -> Tuple[List[int], List[Span]]: ...
+    def get_suffix_count(self, cs: CdawgStateIx64, kind: str = "occurrences") -> int: ...
+    def get_ngram_count(self, query: Query) -> int: ...
+    def get_suffix_length_profile(self, query: Query) -> List[int]: ...
+    def next_token_strs(self, query: Query) -> List[Tuple[str, float]]: ...
+    def probability(self, query: Query) -> float: ...
+    def log_probability(self, query: Query) -> float: ...
+    def get_matching_doc_ids(self, cs: CdawgStateIx64, limit: int = 100) -> List[int]: ...
+    def delete_document(self, doc_id: int) -> int: ...
+    def save_metadata(self, path: str) -> None: ...
+    def get_matching_doc_spans(
+        self, cs: CdawgStateIx64, limit: int = 100
+    ) -> List[Tuple[int, int, int]]: ...
+    def get_document_span(
+        self, doc_id: int
+    ) -> Optional[Tuple[int, int, Optional[int]]]: ...
+    def get_document_count(self) -> int: ...
+    def get_entropy(self, cs: CdawgStateIx64) -> float: ...
+    def get_next_tokens(
+        self, cs: CdawgStateIx64, kind: str = "occurrences"
+    ) -> List[Tuple[int, float]]: ...
+    def get_top_k_continuations(
+        self, cs: CdawgStateIx64, k: int, m: int
+    ) -> List[Tuple[List[int], float]]: ...
+    def batch_suffix_lengths(
+        self, batch: List[List[int]]
+    ) -> List[npt.NDArray[np.uint64]]: ...
+    def batch_next_token_probs(
+        self, batch: List[List[int]], kind: str = "occurrences"
+    ) -> List[Tuple[npt.NDArray[np.uint32], npt.NDArray[np.float64]]]: ...
+    def iter_frequent_spans(self, min_count: int) -> Iterator[Tuple[List[int], int]]: ...
+
+class FrozenCdawg:
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def get_source(self) -> int: ...
+    def get_count(self, state: int) -> int: ...
+    def get_initial(self) -> CdawgState: ...
+    def transition_and_count(self, cs: CdawgState, token: int) -> CdawgState: ...
+    def get_suffix_count(self, cs: CdawgState) -> int: ...
+    def get_ngram_count(self, ngram: List[int]) -> int: ...
+    def get_suffix_length_profile(self, tokens: List[int]) -> List[int]: ...
+    def get_suffix_counts(self, tokens: List[int]) -> List[int]: ...
+    def batch_suffix_lengths(
+        self, batch: List[List[int]]
+    ) -> List[npt.NDArray[np.uint64]]: ...
+    def batch_next_token_probs(
+        self, batch: List[List[int]]
+    ) -> List[Tuple[npt.NDArray[np.uint16], npt.NDArray[np.float64]]]: ...
+
+class FrozenCdawgIx64:
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def get_source(self) -> int: ...
+    def get_count(self, state: int) -> int: ...
+    def get_initial(self) -> CdawgStateIx64: ...
+    def transition_and_count(self, cs: CdawgStateIx64, token: int) -> CdawgStateIx64: ...
+    def get_suffix_count(self, cs: CdawgStateIx64) -> int: ...
+    def get_ngram_count(self, ngram: List[int]) -> int: ...
+    def get_suffix_length_profile(self, tokens: List[int]) -> List[int]: ...
+    def get_suffix_counts(self, tokens: List[int]) -> List[int]: ...
+    def batch_suffix_lengths(
+        self, batch: List[List[int]]
+    ) -> List[npt.NDArray[np.uint64]]: ...
+    def batch_next_token_probs(
+        self, batch: List[List[int]]
+    ) -> List[Tuple[npt.NDArray[np.uint16], npt.NDArray[np.float64]]]: ...
+
+class FrozenCdawg32:
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def get_source(self) -> int: ...
+    def get_count(self, state: int) -> int: ...
+    def get_initial(self) -> CdawgState: ...
+    def transition_and_count(self, cs: CdawgState, token: int) -> CdawgState: ...
+    def get_suffix_count(self, cs: CdawgState) -> int: ...
+    def get_ngram_count(self, ngram: List[int]) -> int: ...
+    def get_suffix_length_profile(self, tokens: List[int]) -> List[int]: ...
+    def get_suffix_counts(self, tokens: List[int]) -> List[int]: ...
+    def batch_suffix_lengths(
+        self, batch: List[List[int]]
+    ) -> List[npt.NDArray[np.uint64]]: ...
+    def batch_next_token_probs(
+        self, batch: List[List[int]]
+    ) -> List[Tuple[npt.NDArray[np.uint32], npt.NDArray[np.float64]]]: ...
+
+class FrozenCdawg32Ix64:
+    def node_count(self) -> int: ...
+    def edge_count(self) -> int: ...
+    def get_source(self) -> int: ...
+    def get_count(self, state: int) -> int: ...
+    def get_initial(self) -> CdawgStateIx64: ...
+    def transition_and_count(self, cs: CdawgStateIx64, token: int) -> CdawgStateIx64: ...
+    def get_suffix_count(self, cs: CdawgStateIx64) -> int: ...
+    def get_ngram_count(self, ngram: List[int]) -> int: ...
+    def get_suffix_length_profile(self, tokens: List[int]) -> List[int]: ...
+    def get_suffix_counts(self, tokens: List[int]) -> List[int]: ...
+    def batch_suffix_lengths(
+        self, batch: List[List[int]]
+    ) -> List[npt.NDArray[np.uint64]]: ...
+    def batch_next_token_probs(
+        self, batch: List[List[int]]
+    ) -> List[Tuple[npt.NDArray[np.uint32], npt.NDArray[np.float64]]]: ...
+
+class TokenVec:
+    EOS: int
+    @classmethod
+    def open(cls, path: str) -> "TokenVec": ...
+    def __len__(self) -> int: ...
+    @overload
+    def __getitem__(self, key: int) -> int: ...
+    @overload
+    def __getitem__(self, key: slice) -> npt.NDArray[np.uint16]: ...
+    def slice(self, start: int, end: int) -> npt.NDArray[np.uint16]: ...
+    def decode(self, start: int, end: int, tokenizer: Any) -> str: ...
+
+class TokenVec32:
+    EOS: int
+    @classmethod
+    def open(cls, path: str) -> "TokenVec32": ...
+    def __len__(self) -> int: ...
+    @overload
+    def __getitem__(self, key: int) -> int: ...
+    @overload
+    def __getitem__(self, key: slice) -> npt.NDArray[np.uint32]: ...
+    def slice(self, start: int, end: int) -> npt.NDArray[np.uint32]: ...
+    def decode(self, start: int, end: int, tokenizer: Any) -> str: ...
@@ -0,0 +1,37 @@
+"""
+Sparse transition matrices of DAWGs, for training neural approximations of the automaton.
+
+The arrays come from `Dawg.transition_matrix`, `DiskDawg.transition_matrix`, or a directory written
+by `rusty-dawg export-transitions`. Row `r` is the state `states[r]`, among the states with the
+highest counts, and its transitions are in `indptr[r]:indptr[r + 1]` of `indices` (tokens),
+`next_states` and `counts` (the count of each next state). scipy is only needed for `to_csr`.
+"""
+
+import os
+from typing import Any, Dict, Tuple
+
+import numpy as np
+
+ARRAYS = ["states", "state_counts", "indptr", "indices", "next_states", "counts"]
+
+
+def load_transitions(path: str) -> Dict[str, np.ndarray]:
+    """
+    Load the arrays written by `rusty-dawg export-transitions` to the directory `path`.
+    """
+    arrays = {name: np.load(os.path.join(path, f"{name}.npy")) for name in ARRAYS}
+    arrays["shape"] = tuple(int(dim) for dim in np.load(os.path.join(path, "shape.npy")))
+    return arrays
+
+
+def to_csr(arrays: Dict[str, np.ndarray]) -> Tuple[Any, Any]:
+    """
+    Build scipy state x vocab CSR matrices of next states and of their counts. Missing transitions
+    are implicit zeros. State 0 is the initial state, which no transition leads to.
+    """
+    from scipy.sparse import csr_matrix
+
+    structure = (arrays["indices"], arrays["indptr"])
+    next_states = csr_matrix((arrays["next_states"], *structure), shape=arrays["shape"])
+    counts = csr_matrix((arrays["counts"], *structure), shape=arrays["shape"])
+    return next_states, counts
@@ -0,0 +1,85 @@
+// Helpers for batched queries, which loop over tokens in Rust to avoid a Python call per token.
+//
+// The CDAWG classes differ in their backing, so they pass in their own transition function.
+
+use std::thread;
+
+use rusty_dawg::cdawg::cdawg_state::CdawgState;
+use rusty_dawg::graph::indexing::IndexType;
+
+/// Length of the longest suffix that occurs after each token of `tokens`.
+pub fn suffix_lengths<Ix, T, F>(tokens: &[T], initial: CdawgState<Ix>, transition: F) -> Vec<u64>
+where
+    Ix: IndexType,
+    T: Copy,
+    F: Fn(CdawgState<Ix>, T) -> CdawgState<Ix>,
+{
+    let mut cs = initial;
+    tokens
+        .iter()
+        .map(|token| {
+            cs = transition(cs, *token);
+            cs.length
+        })
+        .collect()
+}
+
+/// State for the longest suffix of `tokens` that occurs, backing off to `initial` if the last token
+/// never occurs.
+pub fn match_suffix<Ix, T, F>(
+    tokens: &[T],
+    initial: CdawgState<Ix>,
+    transition: F,
+) -> CdawgState<Ix>
+where
+    Ix: IndexType,
+    T: Copy,
+    F: Fn(CdawgState<Ix>, T) -> CdawgState<Ix>,
+{
+    let cs = tokens
+        .iter()
+        .fold(initial, |cs, token| transition(cs, *token));
+    match cs.target {
+        Some(_) => cs,
+        None => initial,
+    }
+}
+
+/// Lets an unsendable class use its index inside `Python::allow_threads`, so other Python threads
+/// can run during a long batch. The closure runs on the calling thread, and pyo3 only lets the
+/// thread that created an unsendable class touch it, so the index is never shared across threads.
+pub struct AssumeSend<T>(T);
+
+unsafe impl<T> Send for AssumeSend<T> {}
+
+impl<T> AssumeSend<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn get(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Map `f` over `items` on all available cores, keeping the order.
+pub fn map_parallel<I, O, F>(items: &[I], f: F) -> Vec<O>
+where
+    I: Sync,
+    O: Send,
+    F: Fn(&I) -> O + Sync,
+{
+    let n_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = usize::max(1, items.len().div_ceil(n_threads));
+    let f = &f;
+    thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    })
+}
@@ -1,124 +1,447 @@
+use numpy::{IntoPyArray, PyArray1};
 use pyo3::prelude::*;
 
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::batch;
 use crate::cdawg_state::CdawgState;
+use crate::count_kind::parse_count_kind;
+use crate::frozen_cdawg::{FrozenCdawg, FrozenCdawg32};
+use crate::query::{Offsets, Query, QueryTokenizer};
+use crate::telemetry::{Telemetry, DEFAULT_EVERY};
+use crate::{to_py_err, warn_graph_access};
 
 use rusty_dawg::cdawg;
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::frequent_spans::FrequentSpans;
+use rusty_dawg::cdawg::frozen;
 use rusty_dawg::graph::indexing::{DefaultIx, EdgeIndex, NodeIndex};
 use rusty_dawg::graph::NodeRef;
+use rusty_dawg::memory_backing::RamBacking;
+use rusty_dawg::walker::Walk;
 use rusty_dawg::weight::DefaultWeight;
 
-#[pyclass(unsendable)]
-pub struct Cdawg {
-    cdawg: cdawg::Cdawg<DefaultWeight, DefaultIx>,
-}
+type Mb = RamBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
 
-// Wrap the normal Dawg class with a Python interface.
-#[pymethods]
-impl Cdawg {
-    #[classattr]
-    const EOS: u16 = u16::MAX;
-
-    #[new]
-    pub fn new(tokens: Vec<u16>) -> Self {
-        let tokens_rc = Rc::new(RefCell::new(tokens));
-        Self {
-            cdawg: cdawg::Cdawg::new(tokens_rc),
-        }
-    }
-
-    pub fn build(&mut self) {
-        self.cdawg.build();
-    }
-
-    /// Build CDAWG incrementally. Use Rust build() at scale rather than calling through Python!
-    pub fn update(&mut self, in_state: usize, start: usize, end: usize) -> (usize, usize) {
-        let (new_state, new_start) = self.cdawg.update(NodeIndex::new(in_state), start, end);
-        (new_state.index(), new_start)
-    }
-
-    pub fn fill_counts(&mut self) {
-        let mut counter = cdawg::TopologicalCounter::new_ram();
-        counter.fill_counts(&mut self.cdawg);
-    }
-
-    pub fn get_source(&self) -> usize {
-        self.cdawg.get_source().index()
-    }
-
-    pub fn get_initial(&self) -> CdawgState {
-        CdawgState {
-            cs: self.cdawg.get_initial(),
+// Pyclasses can't be generic, so a class is generated for each supported token type.
+macro_rules! cdawg_class {
+    ($name:ident, $spans:ident, $frozen:ident, $token:ty) => {
+        #[pyclass(unsendable)]
+        pub struct $name {
+            cdawg: cdawg::Cdawg<DefaultWeight, DefaultIx, Mb, $token>,
+            tokenizer: QueryTokenizer,
         }
-    }
 
-    pub fn transition_and_count(&self, cs: CdawgState, token: u16) -> CdawgState {
-        CdawgState {
-            cs: self.cdawg.transition_and_count(cs.cs, token),
+        // Wrap the normal Dawg class with a Python interface.
+        #[pymethods]
+        impl $name {
+            #[classattr]
+            const EOS: $token = <$token>::MAX;
+
+            #[new]
+            pub fn new(tokens: Vec<$token>) -> Self {
+                let tokens_rc = Rc::new(RefCell::new(tokens));
+                Self {
+                    cdawg: cdawg::Cdawg::new(tokens_rc),
+                    tokenizer: QueryTokenizer::default(),
+                }
+            }
+
+            #[pyo3(signature = (callback=None, every=DEFAULT_EVERY))]
+            pub fn build(&mut self, callback: Option<PyObject>, every: usize) -> PyResult<()> {
+                let mut telemetry = match callback {
+                    Some(callback) => Telemetry::new(callback),
+                    None => {
+                        self.cdawg.build();
+                        return Ok(());
+                    }
+                };
+                let result = self.cdawg.build_with_callback(every, |cdawg, n_tokens| {
+                    telemetry.report(n_tokens, cdawg.node_count(), cdawg.edge_count())
+                });
+                telemetry.finish(result)
+            }
+
+            /// Build CDAWG incrementally. Use Rust build() at scale rather than calling through Python!
+            pub fn update(&mut self, in_state: usize, start: usize, end: usize) -> (usize, usize) {
+                let (new_state, new_start) =
+                    self.cdawg.update(NodeIndex::new(in_state), start, end);
+                (new_state.index(), new_start)
+            }
+
+            pub fn fill_counts(&mut self) {
+                let mut counter = cdawg::TopologicalCounter::new_ram();
+                counter.fill_counts(&mut self.cdawg);
+            }
+
+            /// Same as fill_counts. Provided so that Cdawg and DiskCdawg share a method surface.
+            pub fn fill_counts_ram(&mut self) {
+                self.fill_counts();
+            }
+
+            /// Fill the number of documents that each state occurs in, for get_doc_frequency and
+            /// "documents" counts.
+            pub fn fill_doc_frequencies(&mut self) {
+                let mut counter = cdawg::TopologicalCounter::new_ram();
+                counter.fill_doc_frequencies(&mut self.cdawg);
+            }
+
+            /// Fill the count of each state in each domain, for get_domain_counts. Up to
+            /// `max_exact_domains` domains are counted exactly, and the counts of more are sketched.
+            #[pyo3(signature = (max_exact_domains=cdawg::domain_counts::DEFAULT_MAX_EXACT_DOMAINS))]
+            pub fn fill_domain_counts(&mut self, max_exact_domains: usize) {
+                let mut counter = cdawg::TopologicalCounter::new_ram();
+                counter.fill_domain_counts(&mut self.cdawg, max_exact_domains);
+            }
+
+            /// Iterate over (tokens, count) for every state counted at least `min_count` times, with
+            /// the longest span of tokens it represents. Counts must be filled first.
+            pub fn iter_frequent_spans(slf: Py<Self>, py: Python<'_>, min_count: usize) -> $spans {
+                let spans = FrequentSpans::new(&slf.borrow(py).cdawg, min_count);
+                $spans { owner: slf, spans }
+            }
+
+            /// Get list of arities for all nodes in CDAWG.
+            pub fn traverse_arities(&mut self, capacity: usize) -> Vec<usize> {
+                let mut traverser = cdawg::traverse_arity::TraverseArity::new_ram(capacity);
+                traverser.traverse_arity(&mut self.cdawg)
+            }
+
+            /// Copy into a read-only FrozenCdawg that can be queried from several threads at once.
+            /// Counts should be filled first.
+            pub fn freeze(&self) -> $frozen {
+                $frozen {
+                    cdawg: frozen::FrozenCdawg::new(&self.cdawg),
+                }
+            }
+
+            pub fn get_source(&self) -> usize {
+                self.cdawg.get_source().index()
+            }
+
+            pub fn get_initial(&self) -> CdawgState {
+                CdawgState {
+                    cs: self.cdawg.get_initial(),
+                }
+            }
+
+            pub fn transition_and_count(&self, cs: CdawgState, token: $token) -> CdawgState {
+                CdawgState {
+                    cs: self.cdawg.transition_and_count(cs.cs, token),
+                }
+            }
+
+            pub fn get_edge_by_token(&self, state: usize, token: $token) -> Option<usize> {
+                let node_idx = NodeIndex::new(state);
+                let edge_idx = self.cdawg.get_edge_by_token(node_idx, token);
+                match edge_idx {
+                    Some(e) => Some(e.index()),
+                    None => None,
+                }
+            }
+
+            pub fn get_start_end_target(&self, edge_idx: usize) -> (usize, usize, usize) {
+                let (start, end, target) =
+                    self.cdawg.get_start_end_target(EdgeIndex::new(edge_idx));
+                // Adjust back to 0-indexed start for inference time.
+                (start - 1, end, target.index())
+            }
+
+            /// Get the tokens on an edge, resolving open-ended spans.
+            pub fn resolve_span(&self, edge_idx: usize) -> Vec<$token> {
+                self.cdawg.resolve_span(EdgeIndex::new(edge_idx))
+            }
+
+            /// Get (first_token, span_len, target, tokens) for each edge out of a state, leaving out
+            /// the self-loops that label sinks.
+            pub fn iter_edges(&self, state: usize) -> Vec<($token, usize, usize, Vec<$token>)> {
+                self.cdawg
+                    .iter_edges(NodeIndex::new(state))
+                    .map(|edge| {
+                        (
+                            edge.first_token,
+                            edge.span_len,
+                            edge.target.index(),
+                            edge.tokens,
+                        )
+                    })
+                    .collect()
+            }
+
+            pub fn get_count(&self, state: usize) -> usize {
+                self.cdawg.get_count(NodeIndex::new(state))
+            }
+
+            pub fn get_doc_frequency(&self, state: usize) -> PyResult<usize> {
+                self.cdawg
+                    .get_doc_frequency(NodeIndex::new(state))
+                    .map_err(to_py_err)
+            }
+
+            /// Get (domain, count) for each domain that the strings in a state occur in.
+            pub fn get_domain_counts(&self, state: usize) -> PyResult<Vec<(usize, usize)>> {
+                self.cdawg
+                    .get_domain_counts(NodeIndex::new(state))
+                    .map_err(to_py_err)
+            }
+
+            /// gamma here is 0-indexed.
+            pub fn implicitly_fail(&self, state: usize, gamma: (usize, usize)) -> CdawgState {
+                CdawgState {
+                    cs: self.cdawg.implicitly_fail(NodeIndex::new(state), gamma),
+                }
+            }
+
+            /// Return the length associated with a node.
+            pub fn get_length(&self, py: Python<'_>, state: usize) -> PyResult<u64> {
+                warn_graph_access(py, "get_length")?;
+                Ok(self
+                    .cdawg
+                    .get_graph()
+                    .get_node(NodeIndex::new(state))
+                    .get_length())
+            }
+
+            /// Get list of states that a state connects to. Useful for graph traversal.
+            pub fn neighbors(&self, py: Python<'_>, state: usize) -> PyResult<Vec<usize>> {
+                warn_graph_access(py, "neighbors")?;
+                let node = NodeIndex::new(state);
+                Ok(self
+                    .cdawg
+                    .get_graph()
+                    .neighbors(node)
+                    .map(|x| x.index())
+                    .collect())
+            }
+
+            /// Position after `token`, or None if the extended string doesn't occur. Start
+            /// walking from `get_initial()`.
+            pub fn enter(&self, cs: CdawgState, token: $token) -> Option<CdawgState> {
+                Walk::enter(&self.cdawg, cs.cs, token).map(|cs| CdawgState { cs })
+            }
+
+            /// Position of the longest suffix in a different state, or None at the start.
+            pub fn fail(&self, cs: CdawgState) -> Option<CdawgState> {
+                Walk::fail(&self.cdawg, cs.cs).map(|cs| CdawgState { cs })
+            }
+
+            pub fn counts(&self, cs: CdawgState) -> usize {
+                Walk::count(&self.cdawg, cs.cs)
+            }
+
+            pub fn emit_state_id(&self, cs: CdawgState) -> usize {
+                Walk::state_id(&self.cdawg, cs.cs)
+            }
+
+            pub fn node_count(&self) -> usize {
+                self.cdawg.node_count()
+            }
+
+            pub fn edge_count(&self) -> usize {
+                self.cdawg.edge_count()
+            }
+
+            // Methods for inference time.
+
+            /// Use the pretrained tokenizer `name` for raw string queries, e.g. if the index has no
+            /// manifest.
+            pub fn set_tokenizer(&mut self, name: String) {
+                self.tokenizer.set_name(name);
+            }
+
+            /// Tokenize a query with the index's tokenizer. Returns the token IDs and the character span of
+            /// each token.
+            pub fn tokenize(&self, text: &str) -> PyResult<(Vec<$token>, Offsets)> {
+                self.tokenizer.tokenize(text)
+            }
+
+            #[pyo3(signature = (cs, kind="occurrences"))]
+            pub fn get_suffix_count(&self, cs: CdawgState, kind: &str) -> PyResult<usize> {
+                let kind = parse_count_kind(kind)?;
+                self.cdawg
+                    .get_suffix_count_by_kind(cs.cs, kind)
+                    .map_err(to_py_err)
+            }
+
+            /// Count the occurrences of a query, given as a string or token IDs.
+            pub fn get_ngram_count(&self, query: Query) -> PyResult<usize> {
+                let query = self.tokenizer.resolve(query)?;
+                Ok(self.cdawg.get_ngram_count(&query))
+            }
+
+            /// Possible next tokens after the longest matching suffix of a query, decoded with the
+            /// index's tokenizer, as (text, probability) pairs, most likely first.
+            pub fn next_token_strs(&self, query: Query) -> PyResult<Vec<(String, f64)>> {
+                self.tokenizer.next_token_strs(&self.cdawg, query)
+            }
+
+            /// Length of the longest suffix of a query that occurs in the corpus, at each token.
+            pub fn get_suffix_length_profile(&self, query: Query) -> PyResult<Vec<u64>> {
+                let query = self.tokenizer.resolve(query)?;
+                Ok(self.cdawg.get_suffix_length_profile(&query))
+            }
+
+            pub fn probability(&self, query: Query) -> PyResult<f64> {
+                let query = self.tokenizer.resolve(query)?;
+                self.cdawg.probability(&query).map_err(to_py_err)
+            }
+
+            pub fn log_probability(&self, query: Query) -> PyResult<f64> {
+                let query = self.tokenizer.resolve(query)?;
+                self.cdawg.log_probability(&query).map_err(to_py_err)
+            }
+
+            /// IDs of up to `limit` documents containing the suffix matched by `cs`.
+            #[pyo3(signature = (cs, limit=100))]
+            pub fn get_matching_doc_ids(&self, cs: CdawgState, limit: usize) -> Vec<usize> {
+                self.cdawg.get_matching_doc_ids(cs.cs, limit)
+            }
+
+            /// Delete every document labelled `doc_id`, subtracting its occurrences from the counts
+            /// and leaving it out of document queries. Returns the number of documents deleted.
+            pub fn delete_document(&mut self, doc_id: usize) -> PyResult<usize> {
+                self.cdawg.delete_document(doc_id).map_err(to_py_err)
+            }
+
+            /// Like `get_matching_doc_ids`, but as (doc_id, start, end) token spans. Requires an index
+            /// built with document spans.
+            #[pyo3(signature = (cs, limit=100))]
+            pub fn get_matching_doc_spans(
+                &self,
+                cs: CdawgState,
+                limit: usize,
+            ) -> PyResult<Vec<(usize, usize, usize)>> {
+                let spans = self
+                    .cdawg
+                    .get_matching_doc_spans(cs.cs, limit)
+                    .map_err(to_py_err)?;
+                Ok(spans
+                    .into_iter()
+                    .map(|span| (span.doc_id, span.start, span.end))
+                    .collect())
+            }
+
+            /// Get (start, end, sink) for the first document labelled `doc_id` that hasn't been
+            /// deleted. The sink is None if it was pruned. Requires an index built with document spans.
+            pub fn get_document_span(
+                &self,
+                doc_id: usize,
+            ) -> PyResult<Option<(usize, usize, Option<usize>)>> {
+                let span = self.cdawg.get_document_span(doc_id).map_err(to_py_err)?;
+                Ok(span.map(|span| (span.start, span.end, span.sink)))
+            }
+
+            pub fn get_document_count(&self) -> PyResult<usize> {
+                self.cdawg.get_document_count().map_err(to_py_err)
+            }
+
+            pub fn get_entropy(&self, cs: CdawgState) -> f64 {
+                self.cdawg.get_entropy(cs.cs)
+            }
+
+            #[pyo3(signature = (cs, kind="occurrences"))]
+            pub fn get_next_tokens(
+                &self,
+                cs: CdawgState,
+                kind: &str,
+            ) -> PyResult<Vec<($token, f64)>> {
+                let kind = parse_count_kind(kind)?;
+                self.cdawg
+                    .get_next_tokens_by_kind(cs.cs, kind)
+                    .map_err(to_py_err)
+            }
+
+            /// Length of the longest matching suffix after each token, for each sequence in `batch`.
+            pub fn batch_suffix_lengths<'py>(
+                &self,
+                py: Python<'py>,
+                batch: Vec<Vec<$token>>,
+            ) -> Vec<&'py PyArray1<u64>> {
+                let cdawg = batch::AssumeSend::new(&self.cdawg);
+                let lengths: Vec<_> = py.allow_threads(move || {
+                    let cdawg = cdawg.get();
+                    batch
+                        .iter()
+                        .map(|tokens| {
+                            batch::suffix_lengths(tokens, cdawg.get_initial(), |cs, token| {
+                                cdawg.transition_and_count(cs, token)
+                            })
+                        })
+                        .collect()
+                });
+                lengths.into_iter().map(|l| l.into_pyarray(py)).collect()
+            }
+
+            /// Next-token distribution after the longest matching suffix of each sequence in
+            /// `batch`, as a pair of token and probability arrays.
+            #[pyo3(signature = (batch, kind="occurrences"))]
+            pub fn batch_next_token_probs<'py>(
+                &self,
+                py: Python<'py>,
+                batch: Vec<Vec<$token>>,
+                kind: &str,
+            ) -> PyResult<Vec<(&'py PyArray1<$token>, &'py PyArray1<f64>)>> {
+                let kind = parse_count_kind(kind)?;
+                let cdawg = batch::AssumeSend::new(&self.cdawg);
+                let distributions: anyhow::Result<Vec<_>> = py.allow_threads(move || {
+                    let cdawg = cdawg.get();
+                    batch
+                        .iter()
+                        .map(|tokens| {
+                            let cs =
+                                batch::match_suffix(tokens, cdawg.get_initial(), |cs, token| {
+                                    cdawg.transition_and_count(cs, token)
+                                });
+                            Ok(cdawg
+                                .get_next_tokens_by_kind(cs, kind)?
+                                .into_iter()
+                                .unzip::<_, _, Vec<_>, Vec<_>>())
+                        })
+                        .collect()
+                });
+                Ok(distributions
+                    .map_err(to_py_err)?
+                    .into_iter()
+                    .map(|(tokens, probs)| (tokens.into_pyarray(py), probs.into_pyarray(py)))
+                    .collect())
+            }
+
+            /// The `k` most likely continuations of `m` tokens after `cs`, with their probabilities.
+            pub fn get_top_k_continuations(
+                &self,
+                cs: CdawgState,
+                k: usize,
+                m: usize,
+            ) -> Vec<(Vec<$token>, f64)> {
+                self.cdawg.get_top_k_continuations(cs.cs, k, m)
+            }
         }
-    }
-
-    pub fn get_edge_by_token(&self, state: usize, token: u16) -> Option<usize> {
-        let node_idx = NodeIndex::new(state);
-        let edge_idx = self.cdawg.get_edge_by_token(node_idx, token);
-        match edge_idx {
-            Some(e) => Some(e.index()),
-            None => None,
+
+        // Lazy iterator returned by `iter_frequent_spans`, which borrows the CDAWG at each step.
+        #[pyclass(unsendable)]
+        pub struct $spans {
+            owner: Py<$name>,
+            spans: FrequentSpans<DefaultIx, $token>,
         }
-    }
-
-    pub fn get_start_end_target(&self, edge_idx: usize) -> (usize, usize, usize) {
-        let (start, end, target) = self.cdawg.get_start_end_target(EdgeIndex::new(edge_idx));
-        // Adjust back to 0-indexed start for inference time.
-        (start - 1, end, target.index())
-    }
-
-    pub fn get_count(&self, state: usize) -> usize {
-        self.cdawg.get_count(NodeIndex::new(state))
-    }
-
-    /// gamma here is 0-indexed.
-    pub fn implicitly_fail(&self, state: usize, gamma: (usize, usize)) -> CdawgState {
-        CdawgState {
-            cs: self.cdawg.implicitly_fail(NodeIndex::new(state), gamma),
+
+        #[pymethods]
+        impl $spans {
+            fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+                slf
+            }
+
+            fn __next__(
+                mut slf: PyRefMut<'_, Self>,
+                py: Python<'_>,
+            ) -> Option<(Vec<$token>, usize)> {
+                let owner = slf.owner.clone_ref(py);
+                let owner = owner.borrow(py);
+                slf.spans.next_span(&owner.cdawg)
+            }
         }
-    }
-
-    /// Return the length associated with a node.
-    pub fn get_length(&self, state: usize) -> u64 {
-        self.cdawg
-            .get_graph()
-            .get_node(NodeIndex::new(state))
-            .get_length()
-    }
-
-    /// Get list of states that a state connects to. Useful for graph traversal.
-    pub fn neighbors(&self, state: usize) -> Vec<usize> {
-        let node = NodeIndex::new(state);
-        self.cdawg.get_graph().neighbors(node).map(|x| x.index()).collect()
-    }
-
-    pub fn node_count(&self) -> usize {
-        self.cdawg.node_count()
-    }
-
-    pub fn edge_count(&self) -> usize {
-        self.cdawg.edge_count()
-    }
-
-    // Methods for inference time.
-
-    pub fn get_suffix_count(&self, cs: CdawgState) -> usize {
-        self.cdawg.get_suffix_count(cs.cs)
-    }
-
-    pub fn get_entropy(&self, cs: CdawgState) -> f64 {
-        self.cdawg.get_entropy(cs.cs)
-    }
-
-    pub fn get_next_tokens(&self, cs: CdawgState) -> Vec<(u16, f64)> {
-        self.cdawg.get_next_tokens(cs.cs)
-    }
+    };
 }
+
+cdawg_class!(Cdawg, CdawgFrequentSpans, FrozenCdawg, u16);
+cdawg_class!(Cdawg32, Cdawg32FrequentSpans, FrozenCdawg32, u32);
@@ -3,23 +3,31 @@ use pyo3::prelude::*;
 use rusty_dawg::cdawg::cdawg_state;
 use rusty_dawg::graph::indexing::DefaultIx;
 
-#[pyclass]
-#[derive(Clone)]
-pub struct CdawgState {
-    pub cs: cdawg_state::CdawgState<DefaultIx>,
-}
+// A state class is generated for each supported index width.
+macro_rules! cdawg_state_class {
+    ($name:ident, $ix:ty) => {
+        #[pyclass]
+        #[derive(Clone)]
+        pub struct $name {
+            pub cs: cdawg_state::CdawgState<$ix>,
+        }
 
-#[pymethods]
-impl CdawgState {
-    pub fn get_length(&self) -> u64 {
-        self.cs.length
-    }
+        #[pymethods]
+        impl $name {
+            pub fn get_length(&self) -> u64 {
+                self.cs.length
+            }
 
-    pub fn get_state_and_gamma(&self) -> (Option<usize>, (usize, usize)) {
-        let (state, gamma) = self.cs.get_state_and_gamma();
-        match state {
-            Some(q) => (Some(q.index()), gamma),
-            None => (None, gamma),
+            pub fn get_state_and_gamma(&self) -> (Option<usize>, (usize, usize)) {
+                let (state, gamma) = self.cs.get_state_and_gamma();
+                match state {
+                    Some(q) => (Some(q.index()), gamma),
+                    None => (None, gamma),
+                }
+            }
         }
-    }
+    };
 }
+
+cdawg_state_class!(CdawgState, DefaultIx);
+cdawg_state_class!(CdawgStateIx64, u64);
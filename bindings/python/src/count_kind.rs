@@ -0,0 +1,10 @@
+use pyo3::prelude::*;
+
+use crate::to_py_err;
+
+use rusty_dawg::cdawg::count_kind::CountKind;
+
+// Parse the `kind` argument of count queries ("occurrences", "documents", or "weighted").
+pub fn parse_count_kind(kind: &str) -> PyResult<CountKind> {
+    kind.parse().map_err(to_py_err)
+}
@@ -1,16 +1,36 @@
+use pyo3::exceptions::{PyIndexError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyType;
+use pyo3::types::{PyDict, PyType};
+use std::path::Path;
+
+use crate::query::{Offsets, Query, QueryTokenizer};
+use crate::telemetry::{Telemetry, DEFAULT_EVERY};
+use crate::transitions;
+use crate::{to_py_err, warn_graph_access};
 
 use rusty_dawg::dawg;
+use rusty_dawg::error::{check_exists, RustyDawgError};
 use rusty_dawg::graph::indexing::NodeIndex;
 use rusty_dawg::graph::{EdgeRef, NodeRef};
 use rusty_dawg::io::load::Load;
 use rusty_dawg::memory_backing::CacheConfig;
+use rusty_dawg::tokenize::end::End;
+use rusty_dawg::walker::{DawgPosition, Walk};
 use rusty_dawg::weight::DefaultWeight;
 
 #[pyclass]
 pub struct Dawg {
     dawg: dawg::Dawg<u16, DefaultWeight>,
+    tokenizer: QueryTokenizer,
+    // State and length of the string added with `extend_token`, which the next token extends.
+    last: NodeIndex,
+    length: u64,
+}
+
+impl Default for Dawg {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // Wrap the normal Dawg class with a Python interface.
@@ -18,22 +38,92 @@ pub struct Dawg {
 impl Dawg {
     #[new]
     pub fn new() -> Self {
+        let dawg = dawg::Dawg::new();
         Self {
-            dawg: dawg::Dawg::new(),
+            last: dawg.get_initial(),
+            length: 0,
+            dawg,
+            tokenizer: QueryTokenizer::default(),
         }
     }
 
     #[classmethod]
     pub fn load(_cls: &PyType, path: String) -> PyResult<Self> {
-        // let file = fs::OpenOptions::new().read(true).open(&path)?;
+        check_exists(Path::new(&path)).map_err(to_py_err)?;
         let wrapped_dawg =
             <dawg::Dawg<u16, DefaultWeight> as Load>::load(&path, CacheConfig::none())
-                .expect("Failed to deserialize");
-        Ok(Self { dawg: wrapped_dawg })
+                .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(Self {
+            last: wrapped_dawg.get_initial(),
+            length: 0,
+            dawg: wrapped_dawg,
+            tokenizer: QueryTokenizer::from_manifest(&path)?,
+        })
+    }
+
+    /// Add each of `tokens` to the end of the string built with `extend_token`.
+    pub fn build_from_tokens(&mut self, tokens: Vec<u16>) -> PyResult<()> {
+        for token in tokens {
+            self.extend_token(token)?;
+        }
+        Ok(())
+    }
+
+    /// Add `token` to the end of the string built so far, starting from the empty string. Returns
+    /// the state of the whole string. The reserved end-of-document token can't be added.
+    pub fn extend_token(&mut self, token: u16) -> PyResult<usize> {
+        if token == u16::end() {
+            return Err(to_py_err(
+                RustyDawgError::TokenOverflow(token as u64).into(),
+            ));
+        }
+        (self.last, self.length) = self.dawg.extend(token, self.last, self.length);
+        Ok(self.last.index())
+    }
+
+    /// End the string built with `extend_token` as document `doc_id`, so that the next token
+    /// starts a new document and matches don't span the two.
+    pub fn end_document(&mut self, doc_id: u64) {
+        (self.last, self.length) = self.dawg.end_document(self.last, u16::end(), doc_id);
     }
 
-    pub fn build(&mut self, text: Vec<u16>) {
-        self.dawg.build(&text);
+    /// State and length of the string built with `extend_token` since the last document ended.
+    pub fn get_last(&self) -> (usize, u64) {
+        (self.last.index(), self.length)
+    }
+
+    #[pyo3(signature = (text, callback=None, every=DEFAULT_EVERY))]
+    pub fn build(
+        &mut self,
+        text: Vec<u16>,
+        callback: Option<PyObject>,
+        every: usize,
+    ) -> PyResult<()> {
+        let mut telemetry = match callback {
+            Some(callback) => Telemetry::new(callback),
+            None => {
+                self.dawg.build(&text);
+                return Ok(());
+            }
+        };
+        let result = self
+            .dawg
+            .build_with_callback(&text, every, |dawg, n_tokens| {
+                telemetry.report(n_tokens, dawg.node_count(), dawg.edge_count())
+            });
+        telemetry.finish(result)
+    }
+
+    /// Transitions of the `max_states` states with the highest counts, as a dict of numpy arrays
+    /// in CSR form (see `rusty_dawg.transitions`).
+    #[pyo3(signature = (max_states, vocab_size=None))]
+    pub fn transition_matrix<'py>(
+        &self,
+        py: Python<'py>,
+        max_states: usize,
+        vocab_size: Option<usize>,
+    ) -> PyResult<&'py PyDict> {
+        transitions::to_py_dict(py, self.dawg.transition_matrix(max_states, vocab_size))
     }
 
     pub fn get_initial(&self) -> usize {
@@ -42,10 +132,9 @@ impl Dawg {
 
     pub fn transition(&self, state: usize, token: u16, use_failures: bool) -> Option<usize> {
         let state_index = NodeIndex::new(state);
-        match self.dawg.transition(state_index, token, use_failures) {
-            Some(q) => Some(q.index()),
-            None => None,
-        }
+        self.dawg
+            .transition(state_index, token, use_failures)
+            .map(|q| q.index())
     }
 
     pub fn transition_and_count(
@@ -62,19 +151,43 @@ impl Dawg {
         }
     }
 
-    pub fn get_count(&self, state: usize) -> usize {
-        let state_index = NodeIndex::new(state);
-        self.dawg.get_node(state_index).get_count()
+    pub fn get_count(&self, state: usize) -> PyResult<usize> {
+        let state_index = self.check_state(state)?;
+        Ok(self.dawg.get_node(state_index).get_count())
     }
 
     // Returns (State, TokenId)
-    pub fn get_edges(&self, state: usize) -> Vec<(usize, u16)> {
+    pub fn get_edges(&self, py: Python<'_>, state: usize) -> PyResult<Vec<(usize, u16)>> {
+        warn_graph_access(py, "get_edges")?;
         let state_index = NodeIndex::new(state);
         let graph = self.dawg.get_graph();
-        graph
+        Ok(graph
             .edges(state_index)
             .map(|edge| (edge.get_target().index(), edge.get_weight()))
-            .collect()
+            .collect())
+    }
+
+    /// Position of the empty string, as (state, length), to walk with `enter` and `fail`.
+    pub fn walker_start(&self) -> (usize, u64) {
+        from_position(Walk::start(&self.dawg))
+    }
+
+    /// Position after `token`, or None if the extended string doesn't occur.
+    pub fn enter(&self, position: (usize, u64), token: u16) -> Option<(usize, u64)> {
+        Walk::enter(&self.dawg, to_position(position), token).map(from_position)
+    }
+
+    /// Position of the longest suffix in a different state, or None at the start.
+    pub fn fail(&self, position: (usize, u64)) -> Option<(usize, u64)> {
+        Walk::fail(&self.dawg, to_position(position)).map(from_position)
+    }
+
+    pub fn counts(&self, position: (usize, u64)) -> usize {
+        Walk::count(&self.dawg, to_position(position))
+    }
+
+    pub fn emit_state_id(&self, position: (usize, u64)) -> usize {
+        Walk::state_id(&self.dawg, to_position(position))
     }
 
     pub fn recompute_lengths(&mut self) {
@@ -89,17 +202,42 @@ impl Dawg {
         self.dawg.edge_count()
     }
 
-    pub fn get_failure(&self, state: usize) -> Option<usize> {
-        let state_node = NodeIndex::new(state);
-        match self.dawg.get_node(state_node).get_failure() {
-            Some(phi) => Some(phi.index()),
-            None => None,
-        }
+    pub fn get_failure(&self, state: usize) -> PyResult<Option<usize>> {
+        let state_node = self.check_state(state)?;
+        Ok(self
+            .dawg
+            .get_node(state_node)
+            .get_failure()
+            .map(|phi| phi.index()))
+    }
+
+    pub fn get_length(&self, state: usize) -> PyResult<u64> {
+        let state_node = self.check_state(state)?;
+        Ok(self.dawg.get_node(state_node).get_length())
     }
 
-    pub fn get_length(&self, state: usize) -> u64 {
-        let state_node = NodeIndex::new(state);
-        self.dawg.get_node(state_node).get_length()
+    /// Use the pretrained tokenizer `name` for raw string queries, e.g. if the index has no
+    /// manifest.
+    pub fn set_tokenizer(&mut self, name: String) {
+        self.tokenizer.set_name(name);
+    }
+
+    /// Tokenize a query with the index's tokenizer. Returns the token IDs and the character span of
+    /// each token.
+    pub fn tokenize(&self, text: &str) -> PyResult<(Vec<u16>, Offsets)> {
+        self.tokenizer.tokenize(text)
+    }
+
+    /// Count the occurrences of a query, given as a string or token IDs.
+    pub fn get_ngram_count(&self, query: Query) -> PyResult<usize> {
+        let query = self.tokenizer.resolve(query)?;
+        Ok(self.dawg.get_ngram_count(&query))
+    }
+
+    /// Length of the longest suffix of a query that occurs in the corpus, at each token.
+    pub fn get_suffix_length_profile(&self, query: Query) -> PyResult<Vec<u64>> {
+        let query = self.tokenizer.resolve(query)?;
+        Ok(self.dawg.get_suffix_length_profile(&query))
     }
 }
 
@@ -107,4 +245,27 @@ impl Dawg {
     pub fn get_dawg(&self) -> &dawg::Dawg<u16, DefaultWeight> {
         &self.dawg
     }
+
+    // Raise IndexError rather than panicking on a state that doesn't exist.
+    fn check_state(&self, state: usize) -> PyResult<NodeIndex> {
+        if state >= self.dawg.node_count() {
+            return Err(PyIndexError::new_err(format!(
+                "State {} out of range for a DAWG with {} states",
+                state,
+                self.dawg.node_count()
+            )));
+        }
+        Ok(NodeIndex::new(state))
+    }
+}
+
+fn to_position((state, length): (usize, u64)) -> DawgPosition {
+    DawgPosition {
+        state: NodeIndex::new(state),
+        length,
+    }
+}
+
+fn from_position(position: DawgPosition) -> (usize, u64) {
+    (position.state.index(), position.length)
 }
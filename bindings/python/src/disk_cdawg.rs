@@ -1,130 +1,527 @@
+use numpy::{IntoPyArray, PyArray1};
 use pyo3::prelude::*;
 use pyo3::types::PyType;
 
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::cdawg_state::CdawgState;
+use crate::batch;
+use crate::cdawg_state::{CdawgState, CdawgStateIx64};
+use crate::count_kind::parse_count_kind;
+use crate::frozen_cdawg::{FrozenCdawg, FrozenCdawg32, FrozenCdawg32Ix64, FrozenCdawgIx64};
+use crate::query::{Offsets, Query, QueryTokenizer};
+use crate::telemetry::{Telemetry, DEFAULT_EVERY};
+use crate::{to_py_err, warn_graph_access};
 
 use rusty_dawg::cdawg;
 use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::frequent_spans::FrequentSpans;
+use rusty_dawg::cdawg::frozen;
 use rusty_dawg::graph::indexing::{DefaultIx, EdgeIndex, NodeIndex};
+use rusty_dawg::graph::NodeRef;
 use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
-use rusty_dawg::weight::DefaultWeight;
+use rusty_dawg::walker::Walk;
+use rusty_dawg::weight::{DefaultWeight, Weight64};
 
-type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+// Pyclasses can't be generic, so a class is generated for each supported token type and index
+// width. The 64-bit classes load indexes built with `--index-width 64`.
+macro_rules! disk_cdawg_class {
+    ($name:ident, $spans:ident, $frozen:ident, $state:ident, $weight:ty, $ix:ty, $token:ty) => {
+        #[pyclass(unsendable)]
+        pub struct $name {
+            cdawg:
+                cdawg::Cdawg<$weight, $ix, DiskBacking<$weight, CdawgEdgeWeight<$ix>, $ix>, $token>,
+            tokenizer: QueryTokenizer,
+        }
 
-#[pyclass(unsendable)]
-pub struct DiskCdawg {
-    cdawg: cdawg::Cdawg<DefaultWeight, DefaultIx, Mb>,
-}
+        // Wrap the normal Dawg class with a Python interface.
+        #[pymethods]
+        impl $name {
+            #[classattr]
+            const EOS: $token = <$token>::MAX;
 
-// Wrap the normal Dawg class with a Python interface.
-#[pymethods]
-impl DiskCdawg {
-    #[classattr]
-    const EOS: u16 = u16::MAX;
-
-    // Assumes that tokens_path is a DiskVec already populated with the tokens we want to build on.
-    #[new]
-    pub fn new(tokens_path: String, mb_path: String, n_nodes: usize, n_edges: usize) -> Self {
-        let tokens_vec = DiskVec::load(tokens_path).unwrap();
-        let tokens_rc = Rc::new(RefCell::new(tokens_vec));
-        let mb = DiskBacking::new(mb_path);
-        let cache_config = CacheConfig::none();
-        Self {
-            cdawg: cdawg::Cdawg::with_capacity_mb(tokens_rc, mb, n_nodes, n_edges, cache_config),
-        }
-    }
-
-    // Load a DiskCdawg that has already been built.
-    #[classmethod]
-    pub fn load(_cls: &PyType, tokens_path: String, mb_path: String) -> Self {
-        let tokens_vec = DiskVec::load(tokens_path).unwrap();
-        let tokens_rc = Rc::new(RefCell::new(tokens_vec));
-        let cache_config = CacheConfig::none();
-        Self {
-            cdawg: cdawg::Cdawg::load(tokens_rc, mb_path, cache_config).unwrap(),
-        }
-    }
-
-    pub fn build(&mut self) {
-        self.cdawg.build();
-    }
-
-    pub fn fill_counts(&mut self, stack_path: String, capacity: usize) {
-        let mut counter = cdawg::TopologicalCounter::new_disk(stack_path, capacity).unwrap();
-        counter.fill_counts(&mut self.cdawg);
-    }
-
-    // TODO: Merge with above, adding default argument or TopologicalCounter object.
-    pub fn fill_counts_ram(&mut self) {
-        let mut counter = cdawg::TopologicalCounter::new_ram();
-        counter.fill_counts(&mut self.cdawg);
-    }
-
-    /// Get list of arities for all nodes in CDAWG.
-    pub fn traverse_arities(&mut self, capacity: usize) -> Vec<usize> {
-        let mut traverser = cdawg::traverse_arity::TraverseArity::new_ram(capacity);
-        traverser.traverse_arity(&mut self.cdawg)
-    }
-
-    pub fn get_initial(&self) -> CdawgState {
-        CdawgState {
-            cs: self.cdawg.get_initial(),
-        }
-    }
+            // Assumes that tokens_path is a DiskVec already populated with the tokens we want to build on.
+            #[new]
+            pub fn new(
+                tokens_path: String,
+                mb_path: String,
+                n_nodes: usize,
+                n_edges: usize,
+            ) -> PyResult<Self> {
+                let tokens_vec = DiskVec::load(tokens_path).map_err(to_py_err)?;
+                let tokens_rc = Rc::new(RefCell::new(tokens_vec));
+                let mb = DiskBacking::new(mb_path);
+                let cache_config = CacheConfig::none();
+                Ok(Self {
+                    cdawg: cdawg::Cdawg::with_capacity_mb(
+                        tokens_rc,
+                        mb,
+                        n_nodes,
+                        n_edges,
+                        cache_config,
+                    ),
+                    tokenizer: QueryTokenizer::default(),
+                })
+            }
 
-    pub fn transition_and_count(&self, cs: CdawgState, token: u16) -> CdawgState {
-        CdawgState {
-            cs: self.cdawg.transition_and_count(cs.cs, token),
-        }
-    }
-
-    pub fn get_edge_by_token(&self, state: usize, token: u16) -> Option<usize> {
-        let node_idx = NodeIndex::new(state);
-        let edge_idx = self.cdawg.get_edge_by_token(node_idx, token);
-        match edge_idx {
-            Some(e) => Some(e.index()),
-            None => None,
-        }
-    }
+            // Load a DiskCdawg that has already been built.
+            #[classmethod]
+            pub fn load(_cls: &PyType, tokens_path: String, mb_path: String) -> PyResult<Self> {
+                let tokens_vec = DiskVec::load(tokens_path).map_err(to_py_err)?;
+                let tokens_rc = Rc::new(RefCell::new(tokens_vec));
+                let cache_config = CacheConfig::none();
+                let tokenizer = QueryTokenizer::from_manifest(&mb_path)?;
+                Ok(Self {
+                    cdawg: cdawg::Cdawg::load(tokens_rc, mb_path, cache_config)
+                        .map_err(to_py_err)?,
+                    tokenizer,
+                })
+            }
+
+            // Load a DiskCdawg whose metadata references a shared tokens DiskVec.
+            #[classmethod]
+            pub fn load_shared(_cls: &PyType, mb_path: String) -> PyResult<Self> {
+                let cache_config = CacheConfig::none();
+                let tokenizer = QueryTokenizer::from_manifest(&mb_path)?;
+                Ok(Self {
+                    cdawg: cdawg::Cdawg::load_shared(mb_path, cache_config).map_err(to_py_err)?,
+                    tokenizer,
+                })
+            }
+
+            #[pyo3(signature = (callback=None, every=DEFAULT_EVERY))]
+            pub fn build(&mut self, callback: Option<PyObject>, every: usize) -> PyResult<()> {
+                let mut telemetry = match callback {
+                    Some(callback) => Telemetry::new(callback),
+                    None => {
+                        self.cdawg.build();
+                        return Ok(());
+                    }
+                };
+                let result = self.cdawg.build_with_callback(every, |cdawg, n_tokens| {
+                    telemetry.report(n_tokens, cdawg.node_count(), cdawg.edge_count())
+                });
+                telemetry.finish(result)
+            }
+
+            /// Build CDAWG incrementally. Use Rust build() at scale rather than calling through Python!
+            pub fn update(&mut self, in_state: usize, start: usize, end: usize) -> (usize, usize) {
+                let (new_state, new_start) =
+                    self.cdawg.update(NodeIndex::new(in_state), start, end);
+                (new_state.index(), new_start)
+            }
+
+            pub fn fill_counts(&mut self, stack_path: String, capacity: usize) -> PyResult<()> {
+                let mut counter =
+                    cdawg::TopologicalCounter::new_disk(stack_path, capacity).map_err(to_py_err)?;
+                counter.fill_counts(&mut self.cdawg);
+                Ok(())
+            }
+
+            // TODO: Merge with above, adding default argument or TopologicalCounter object.
+            pub fn fill_counts_ram(&mut self) {
+                let mut counter = cdawg::TopologicalCounter::new_ram();
+                counter.fill_counts(&mut self.cdawg);
+            }
+
+            /// Fill the number of documents that each state occurs in, for get_doc_frequency and
+            /// "documents" counts.
+            pub fn fill_doc_frequencies(&mut self) {
+                let mut counter = cdawg::TopologicalCounter::new_ram();
+                counter.fill_doc_frequencies(&mut self.cdawg);
+            }
+
+            /// Fill the count of each state in each domain, for get_domain_counts. Up to
+            /// `max_exact_domains` domains are counted exactly, and the counts of more are sketched.
+            #[pyo3(signature = (max_exact_domains=cdawg::domain_counts::DEFAULT_MAX_EXACT_DOMAINS))]
+            pub fn fill_domain_counts(&mut self, max_exact_domains: usize) {
+                let mut counter = cdawg::TopologicalCounter::new_ram();
+                counter.fill_domain_counts(&mut self.cdawg, max_exact_domains);
+            }
+
+            /// Iterate over (tokens, count) for every state counted at least `min_count` times, with
+            /// the longest span of tokens it represents. Counts must be filled first.
+            pub fn iter_frequent_spans(slf: Py<Self>, py: Python<'_>, min_count: usize) -> $spans {
+                let spans = FrequentSpans::new(&slf.borrow(py).cdawg, min_count);
+                $spans { owner: slf, spans }
+            }
+
+            /// Get list of arities for all nodes in CDAWG.
+            pub fn traverse_arities(&mut self, capacity: usize) -> Vec<usize> {
+                let mut traverser = cdawg::traverse_arity::TraverseArity::new_ram(capacity);
+                traverser.traverse_arity(&mut self.cdawg)
+            }
+
+            /// Copy into a read-only FrozenCdawg that can be queried from several threads at once.
+            /// Counts should be filled first.
+            pub fn freeze(&self) -> $frozen {
+                $frozen {
+                    cdawg: frozen::FrozenCdawg::new(&self.cdawg),
+                }
+            }
+
+            pub fn get_source(&self) -> usize {
+                self.cdawg.get_source().index()
+            }
+
+            pub fn get_initial(&self) -> $state {
+                $state {
+                    cs: self.cdawg.get_initial(),
+                }
+            }
+
+            pub fn transition_and_count(&self, cs: $state, token: $token) -> $state {
+                $state {
+                    cs: self.cdawg.transition_and_count(cs.cs, token),
+                }
+            }
+
+            pub fn get_edge_by_token(&self, state: usize, token: $token) -> Option<usize> {
+                let node_idx = NodeIndex::new(state);
+                let edge_idx = self.cdawg.get_edge_by_token(node_idx, token);
+                match edge_idx {
+                    Some(e) => Some(e.index()),
+                    None => None,
+                }
+            }
+
+            pub fn get_start_end_target(&self, edge_idx: usize) -> (usize, usize, usize) {
+                let (start, end, target) =
+                    self.cdawg.get_start_end_target(EdgeIndex::new(edge_idx));
+                // Adjust back to 0-indexed start for inference time.
+                (start - 1, end, target.index())
+            }
+
+            /// Get the tokens on an edge, resolving open-ended spans.
+            pub fn resolve_span(&self, edge_idx: usize) -> Vec<$token> {
+                self.cdawg.resolve_span(EdgeIndex::new(edge_idx))
+            }
+
+            /// Get (first_token, span_len, target, tokens) for each edge out of a state, leaving out
+            /// the self-loops that label sinks.
+            pub fn iter_edges(&self, state: usize) -> Vec<($token, usize, usize, Vec<$token>)> {
+                self.cdawg
+                    .iter_edges(NodeIndex::new(state))
+                    .map(|edge| {
+                        (
+                            edge.first_token,
+                            edge.span_len,
+                            edge.target.index(),
+                            edge.tokens,
+                        )
+                    })
+                    .collect()
+            }
+
+            pub fn get_count(&self, state: usize) -> usize {
+                self.cdawg.get_count(NodeIndex::new(state))
+            }
+
+            pub fn get_doc_frequency(&self, state: usize) -> PyResult<usize> {
+                self.cdawg
+                    .get_doc_frequency(NodeIndex::new(state))
+                    .map_err(to_py_err)
+            }
+
+            /// Get (domain, count) for each domain that the strings in a state occur in.
+            pub fn get_domain_counts(&self, state: usize) -> PyResult<Vec<(usize, usize)>> {
+                self.cdawg
+                    .get_domain_counts(NodeIndex::new(state))
+                    .map_err(to_py_err)
+            }
 
-    pub fn get_start_end_target(&self, edge_idx: usize) -> (usize, usize, usize) {
-        let (start, end, target) = self.cdawg.get_start_end_target(EdgeIndex::new(edge_idx));
-        // Adjust back to 0-indexed start for inference time.
-        (start - 1, end, target.index())
-    }
+            /// gamma here is 0-indexed.
+            pub fn implicitly_fail(&self, state: usize, gamma: (usize, usize)) -> $state {
+                $state {
+                    cs: self.cdawg.implicitly_fail(NodeIndex::new(state), gamma),
+                }
+            }
 
-    pub fn get_count(&self, state: usize) -> usize {
-        self.cdawg.get_count(NodeIndex::new(state))
-    }
+            /// Return the length associated with a node.
+            pub fn get_length(&self, py: Python<'_>, state: usize) -> PyResult<u64> {
+                warn_graph_access(py, "get_length")?;
+                Ok(self
+                    .cdawg
+                    .get_graph()
+                    .get_node(NodeIndex::new(state))
+                    .get_length())
+            }
 
-    /// Get list of states that a state connects to. Useful for graph traversal.
-    pub fn neighbors(&self, state: usize) -> Vec<usize> {
-        let node = NodeIndex::new(state);
-        self.cdawg.get_graph().neighbors(node).map(|x| x.index()).collect()
-    }
+            /// Get list of states that a state connects to. Useful for graph traversal.
+            pub fn neighbors(&self, py: Python<'_>, state: usize) -> PyResult<Vec<usize>> {
+                warn_graph_access(py, "neighbors")?;
+                let node = NodeIndex::new(state);
+                Ok(self
+                    .cdawg
+                    .get_graph()
+                    .neighbors(node)
+                    .map(|x| x.index())
+                    .collect())
+            }
 
-    pub fn node_count(&self) -> usize {
-        self.cdawg.node_count()
-    }
+            /// Position after `token`, or None if the extended string doesn't occur. Start
+            /// walking from `get_initial()`.
+            pub fn enter(&self, cs: $state, token: $token) -> Option<$state> {
+                Walk::enter(&self.cdawg, cs.cs, token).map(|cs| $state { cs })
+            }
 
-    pub fn edge_count(&self) -> usize {
-        self.cdawg.edge_count()
-    }
+            /// Position of the longest suffix in a different state, or None at the start.
+            pub fn fail(&self, cs: $state) -> Option<$state> {
+                Walk::fail(&self.cdawg, cs.cs).map(|cs| $state { cs })
+            }
 
-    // Methods for inference time.
+            pub fn counts(&self, cs: $state) -> usize {
+                Walk::count(&self.cdawg, cs.cs)
+            }
 
-    pub fn get_suffix_count(&self, cs: CdawgState) -> usize {
-        self.cdawg.get_suffix_count(cs.cs)
-    }
+            pub fn emit_state_id(&self, cs: $state) -> usize {
+                Walk::state_id(&self.cdawg, cs.cs)
+            }
 
-    pub fn get_entropy(&self, cs: CdawgState) -> f64 {
-        self.cdawg.get_entropy(cs.cs)
-    }
+            pub fn node_count(&self) -> usize {
+                self.cdawg.node_count()
+            }
 
-    pub fn get_next_tokens(&self, cs: CdawgState) -> Vec<(u16, f64)> {
-        self.cdawg.get_next_tokens(cs.cs)
-    }
+            pub fn edge_count(&self) -> usize {
+                self.cdawg.edge_count()
+            }
+
+            // Methods for inference time.
+
+            /// Use the pretrained tokenizer `name` for raw string queries, e.g. if the index has no
+            /// manifest.
+            pub fn set_tokenizer(&mut self, name: String) {
+                self.tokenizer.set_name(name);
+            }
+
+            /// Tokenize a query with the index's tokenizer. Returns the token IDs and the character span of
+            /// each token.
+            pub fn tokenize(&self, text: &str) -> PyResult<(Vec<$token>, Offsets)> {
+                self.tokenizer.tokenize(text)
+            }
+
+            #[pyo3(signature = (cs, kind="occurrences"))]
+            pub fn get_suffix_count(&self, cs: $state, kind: &str) -> PyResult<usize> {
+                let kind = parse_count_kind(kind)?;
+                self.cdawg
+                    .get_suffix_count_by_kind(cs.cs, kind)
+                    .map_err(to_py_err)
+            }
+
+            /// Count the occurrences of a query, given as a string or token IDs.
+            pub fn get_ngram_count(&self, query: Query) -> PyResult<usize> {
+                let query = self.tokenizer.resolve(query)?;
+                Ok(self.cdawg.get_ngram_count(&query))
+            }
+
+            /// Possible next tokens after the longest matching suffix of a query, decoded with the
+            /// index's tokenizer, as (text, probability) pairs, most likely first.
+            pub fn next_token_strs(&self, query: Query) -> PyResult<Vec<(String, f64)>> {
+                self.tokenizer.next_token_strs(&self.cdawg, query)
+            }
+
+            /// Length of the longest suffix of a query that occurs in the corpus, at each token.
+            pub fn get_suffix_length_profile(&self, query: Query) -> PyResult<Vec<u64>> {
+                let query = self.tokenizer.resolve(query)?;
+                Ok(self.cdawg.get_suffix_length_profile(&query))
+            }
+
+            pub fn probability(&self, query: Query) -> PyResult<f64> {
+                let query = self.tokenizer.resolve(query)?;
+                self.cdawg.probability(&query).map_err(to_py_err)
+            }
+
+            pub fn log_probability(&self, query: Query) -> PyResult<f64> {
+                let query = self.tokenizer.resolve(query)?;
+                self.cdawg.log_probability(&query).map_err(to_py_err)
+            }
+
+            /// IDs of up to `limit` documents containing the suffix matched by `cs`.
+            #[pyo3(signature = (cs, limit=100))]
+            pub fn get_matching_doc_ids(&self, cs: $state, limit: usize) -> Vec<usize> {
+                self.cdawg.get_matching_doc_ids(cs.cs, limit)
+            }
+
+            /// Delete every document labelled `doc_id`, subtracting its occurrences from the counts
+            /// and leaving it out of document queries. Returns the number of documents deleted.
+            pub fn delete_document(&mut self, doc_id: usize) -> PyResult<usize> {
+                self.cdawg.delete_document(doc_id).map_err(to_py_err)
+            }
+
+            /// Save the metadata, including deleted documents, to the index directory `path`.
+            pub fn save_metadata(&self, path: String) -> PyResult<()> {
+                self.cdawg.flush()?;
+                Ok(self.cdawg.save_metadata(path)?)
+            }
+
+            /// Like `get_matching_doc_ids`, but as (doc_id, start, end) token spans. Requires an index
+            /// built with document spans.
+            #[pyo3(signature = (cs, limit=100))]
+            pub fn get_matching_doc_spans(
+                &self,
+                cs: $state,
+                limit: usize,
+            ) -> PyResult<Vec<(usize, usize, usize)>> {
+                let spans = self
+                    .cdawg
+                    .get_matching_doc_spans(cs.cs, limit)
+                    .map_err(to_py_err)?;
+                Ok(spans
+                    .into_iter()
+                    .map(|span| (span.doc_id, span.start, span.end))
+                    .collect())
+            }
+
+            /// Get (start, end, sink) for the first document labelled `doc_id` that hasn't been
+            /// deleted. The sink is None if it was pruned. Requires an index built with document spans.
+            pub fn get_document_span(
+                &self,
+                doc_id: usize,
+            ) -> PyResult<Option<(usize, usize, Option<usize>)>> {
+                let span = self.cdawg.get_document_span(doc_id).map_err(to_py_err)?;
+                Ok(span.map(|span| (span.start, span.end, span.sink)))
+            }
+
+            pub fn get_document_count(&self) -> PyResult<usize> {
+                self.cdawg.get_document_count().map_err(to_py_err)
+            }
+
+            pub fn get_entropy(&self, cs: $state) -> f64 {
+                self.cdawg.get_entropy(cs.cs)
+            }
+
+            #[pyo3(signature = (cs, kind="occurrences"))]
+            pub fn get_next_tokens(&self, cs: $state, kind: &str) -> PyResult<Vec<($token, f64)>> {
+                let kind = parse_count_kind(kind)?;
+                self.cdawg
+                    .get_next_tokens_by_kind(cs.cs, kind)
+                    .map_err(to_py_err)
+            }
+
+            /// Length of the longest matching suffix after each token, for each sequence in `batch`.
+            pub fn batch_suffix_lengths<'py>(
+                &self,
+                py: Python<'py>,
+                batch: Vec<Vec<$token>>,
+            ) -> Vec<&'py PyArray1<u64>> {
+                let cdawg = batch::AssumeSend::new(&self.cdawg);
+                let lengths: Vec<_> = py.allow_threads(move || {
+                    let cdawg = cdawg.get();
+                    batch
+                        .iter()
+                        .map(|tokens| {
+                            batch::suffix_lengths(tokens, cdawg.get_initial(), |cs, token| {
+                                cdawg.transition_and_count(cs, token)
+                            })
+                        })
+                        .collect()
+                });
+                lengths.into_iter().map(|l| l.into_pyarray(py)).collect()
+            }
+
+            /// Next-token distribution after the longest matching suffix of each sequence in
+            /// `batch`, as a pair of token and probability arrays.
+            #[pyo3(signature = (batch, kind="occurrences"))]
+            pub fn batch_next_token_probs<'py>(
+                &self,
+                py: Python<'py>,
+                batch: Vec<Vec<$token>>,
+                kind: &str,
+            ) -> PyResult<Vec<(&'py PyArray1<$token>, &'py PyArray1<f64>)>> {
+                let kind = parse_count_kind(kind)?;
+                let cdawg = batch::AssumeSend::new(&self.cdawg);
+                let distributions: anyhow::Result<Vec<_>> = py.allow_threads(move || {
+                    let cdawg = cdawg.get();
+                    batch
+                        .iter()
+                        .map(|tokens| {
+                            let cs =
+                                batch::match_suffix(tokens, cdawg.get_initial(), |cs, token| {
+                                    cdawg.transition_and_count(cs, token)
+                                });
+                            Ok(cdawg
+                                .get_next_tokens_by_kind(cs, kind)?
+                                .into_iter()
+                                .unzip::<_, _, Vec<_>, Vec<_>>())
+                        })
+                        .collect()
+                });
+                Ok(distributions
+                    .map_err(to_py_err)?
+                    .into_iter()
+                    .map(|(tokens, probs)| (tokens.into_pyarray(py), probs.into_pyarray(py)))
+                    .collect())
+            }
+
+            /// The `k` most likely continuations of `m` tokens after `cs`, with their probabilities.
+            pub fn get_top_k_continuations(
+                &self,
+                cs: $state,
+                k: usize,
+                m: usize,
+            ) -> Vec<(Vec<$token>, f64)> {
+                self.cdawg.get_top_k_continuations(cs.cs, k, m)
+            }
+        }
+
+        // Lazy iterator returned by `iter_frequent_spans`, which borrows the CDAWG at each step.
+        #[pyclass(unsendable)]
+        pub struct $spans {
+            owner: Py<$name>,
+            spans: FrequentSpans<$ix, $token>,
+        }
+
+        #[pymethods]
+        impl $spans {
+            fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+                slf
+            }
+
+            fn __next__(
+                mut slf: PyRefMut<'_, Self>,
+                py: Python<'_>,
+            ) -> Option<(Vec<$token>, usize)> {
+                let owner = slf.owner.clone_ref(py);
+                let owner = owner.borrow(py);
+                slf.spans.next_span(&owner.cdawg)
+            }
+        }
+    };
 }
+
+disk_cdawg_class!(
+    DiskCdawg,
+    DiskCdawgFrequentSpans,
+    FrozenCdawg,
+    CdawgState,
+    DefaultWeight,
+    DefaultIx,
+    u16
+);
+disk_cdawg_class!(
+    DiskCdawg32,
+    DiskCdawg32FrequentSpans,
+    FrozenCdawg32,
+    CdawgState,
+    DefaultWeight,
+    DefaultIx,
+    u32
+);
+disk_cdawg_class!(
+    DiskCdawgIx64,
+    DiskCdawgIx64FrequentSpans,
+    FrozenCdawgIx64,
+    CdawgStateIx64,
+    Weight64,
+    u64,
+    u16
+);
+disk_cdawg_class!(
+    DiskCdawg32Ix64,
+    DiskCdawg32Ix64FrequentSpans,
+    FrozenCdawg32Ix64,
+    CdawgStateIx64,
+    Weight64,
+    u64,
+    u32
+);
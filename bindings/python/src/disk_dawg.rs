@@ -1,11 +1,17 @@
 use pyo3::prelude::*;
-use pyo3::types::PyType;
+use pyo3::types::{PyDict, PyType};
+
+use crate::query::{Offsets, Query, QueryTokenizer};
+use crate::telemetry::{Telemetry, DEFAULT_EVERY};
+use crate::transitions;
+use crate::{to_py_err, warn_graph_access};
 
 use rusty_dawg::dawg;
 use rusty_dawg::graph::indexing::DefaultIx;
 use rusty_dawg::graph::indexing::NodeIndex;
 use rusty_dawg::graph::{EdgeRef, NodeRef};
 use rusty_dawg::memory_backing::{CacheConfig, DiskBacking};
+use rusty_dawg::walker::{DawgPosition, Walk};
 use rusty_dawg::weight::DefaultWeight;
 
 type Mb = DiskBacking<DefaultWeight, u16, DefaultIx>;
@@ -14,6 +20,7 @@ type Mb = DiskBacking<DefaultWeight, u16, DefaultIx>;
 // #[pyclass(unsendable)]
 pub struct DiskDawg {
     dawg: dawg::Dawg<u16, DefaultWeight, DefaultIx, Mb>,
+    tokenizer: QueryTokenizer,
 }
 
 // Wrap the normal Dawg class with a Python interface.
@@ -23,12 +30,43 @@ impl DiskDawg {
     // #[pyo3(signature = (path, **kwargs))]
     pub fn load(_cls: &PyType, path: String) -> PyResult<Self> {
         Ok(Self {
-            dawg: dawg::Dawg::load(&path, CacheConfig::none()).expect("Failed to deserialize"),
+            dawg: dawg::Dawg::load(&path, CacheConfig::none()).map_err(to_py_err)?,
+            tokenizer: QueryTokenizer::from_manifest(&path)?,
         })
     }
 
-    pub fn build(&mut self, text: Vec<u16>) {
-        self.dawg.build(&text);
+    #[pyo3(signature = (text, callback=None, every=DEFAULT_EVERY))]
+    pub fn build(
+        &mut self,
+        text: Vec<u16>,
+        callback: Option<PyObject>,
+        every: usize,
+    ) -> PyResult<()> {
+        let mut telemetry = match callback {
+            Some(callback) => Telemetry::new(callback),
+            None => {
+                self.dawg.build(&text);
+                return Ok(());
+            }
+        };
+        let result = self
+            .dawg
+            .build_with_callback(&text, every, |dawg, n_tokens| {
+                telemetry.report(n_tokens, dawg.node_count(), dawg.edge_count())
+            });
+        telemetry.finish(result)
+    }
+
+    /// Transitions of the `max_states` states with the highest counts, as a dict of numpy arrays
+    /// in CSR form (see `rusty_dawg.transitions`).
+    #[pyo3(signature = (max_states, vocab_size=None))]
+    pub fn transition_matrix<'py>(
+        &self,
+        py: Python<'py>,
+        max_states: usize,
+        vocab_size: Option<usize>,
+    ) -> PyResult<&'py PyDict> {
+        transitions::to_py_dict(py, self.dawg.transition_matrix(max_states, vocab_size))
     }
 
     pub fn get_initial(&self) -> usize {
@@ -37,10 +75,9 @@ impl DiskDawg {
 
     pub fn transition(&self, state: usize, token: u16, use_failures: bool) -> Option<usize> {
         let state_index = NodeIndex::new(state);
-        match self.dawg.transition(state_index, token, use_failures) {
-            Some(q) => Some(q.index()),
-            None => None,
-        }
+        self.dawg
+            .transition(state_index, token, use_failures)
+            .map(|q| q.index())
     }
 
     pub fn transition_and_count(
@@ -63,13 +100,37 @@ impl DiskDawg {
     }
 
     // Returns (State, TokenId)
-    pub fn get_edges(&self, state: usize) -> Vec<(usize, u16)> {
+    pub fn get_edges(&self, py: Python<'_>, state: usize) -> PyResult<Vec<(usize, u16)>> {
+        warn_graph_access(py, "get_edges")?;
         let state_index = NodeIndex::new(state);
         let graph = self.dawg.get_graph();
-        graph
+        Ok(graph
             .edges(state_index)
             .map(|edge| (edge.get_target().index(), edge.get_weight()))
-            .collect()
+            .collect())
+    }
+
+    /// Position of the empty string, as (state, length), to walk with `enter` and `fail`.
+    pub fn walker_start(&self) -> (usize, u64) {
+        from_position(Walk::start(&self.dawg))
+    }
+
+    /// Position after `token`, or None if the extended string doesn't occur.
+    pub fn enter(&self, position: (usize, u64), token: u16) -> Option<(usize, u64)> {
+        Walk::enter(&self.dawg, to_position(position), token).map(from_position)
+    }
+
+    /// Position of the longest suffix in a different state, or None at the start.
+    pub fn fail(&self, position: (usize, u64)) -> Option<(usize, u64)> {
+        Walk::fail(&self.dawg, to_position(position)).map(from_position)
+    }
+
+    pub fn counts(&self, position: (usize, u64)) -> usize {
+        Walk::count(&self.dawg, to_position(position))
+    }
+
+    pub fn emit_state_id(&self, position: (usize, u64)) -> usize {
+        Walk::state_id(&self.dawg, to_position(position))
     }
 
     pub fn recompute_lengths(&mut self) {
@@ -86,16 +147,40 @@ impl DiskDawg {
 
     pub fn get_failure(&self, state: usize) -> Option<usize> {
         let state_node = NodeIndex::new(state);
-        match self.dawg.get_node(state_node).get_failure() {
-            Some(phi) => Some(phi.index()),
-            None => None,
-        }
+        self.dawg
+            .get_node(state_node)
+            .get_failure()
+            .map(|phi| phi.index())
     }
 
     pub fn get_length(&self, state: usize) -> u64 {
         let state_node = NodeIndex::new(state);
         self.dawg.get_node(state_node).get_length()
     }
+
+    /// Use the pretrained tokenizer `name` for raw string queries, e.g. if the index has no
+    /// manifest.
+    pub fn set_tokenizer(&mut self, name: String) {
+        self.tokenizer.set_name(name);
+    }
+
+    /// Tokenize a query with the index's tokenizer. Returns the token IDs and the character span of
+    /// each token.
+    pub fn tokenize(&self, text: &str) -> PyResult<(Vec<u16>, Offsets)> {
+        self.tokenizer.tokenize(text)
+    }
+
+    /// Count the occurrences of a query, given as a string or token IDs.
+    pub fn get_ngram_count(&self, query: Query) -> PyResult<usize> {
+        let query = self.tokenizer.resolve(query)?;
+        Ok(self.dawg.get_ngram_count(&query))
+    }
+
+    /// Length of the longest suffix of a query that occurs in the corpus, at each token.
+    pub fn get_suffix_length_profile(&self, query: Query) -> PyResult<Vec<u64>> {
+        let query = self.tokenizer.resolve(query)?;
+        Ok(self.dawg.get_suffix_length_profile(&query))
+    }
 }
 
 impl DiskDawg {
@@ -103,3 +188,14 @@ impl DiskDawg {
         &self.dawg
     }
 }
+
+fn to_position((state, length): (usize, u64)) -> DawgPosition {
+    DawgPosition {
+        state: NodeIndex::new(state),
+        length,
+    }
+}
+
+fn from_position(position: DawgPosition) -> (usize, u64) {
+    (position.state.index(), position.length)
+}
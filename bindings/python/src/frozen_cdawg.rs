@@ -0,0 +1,131 @@
+use numpy::{IntoPyArray, PyArray1};
+use pyo3::prelude::*;
+
+use crate::batch;
+use crate::cdawg_state::{CdawgState, CdawgStateIx64};
+
+use rusty_dawg::cdawg::frozen;
+use rusty_dawg::graph::indexing::{DefaultIx, NodeIndex};
+use rusty_dawg::weight::{DefaultWeight, Weight64};
+
+// Unlike Cdawg and DiskCdawg, these classes are Send, so queries release the GIL and can run on
+// several Python threads at once.
+macro_rules! frozen_cdawg_class {
+    ($name:ident, $state:ident, $weight:ty, $ix:ty, $token:ty) => {
+        #[pyclass]
+        #[derive(Clone)]
+        pub struct $name {
+            pub cdawg: frozen::FrozenCdawg<$weight, $ix, $token>,
+        }
+
+        #[pymethods]
+        impl $name {
+            pub fn node_count(&self) -> usize {
+                self.cdawg.node_count()
+            }
+
+            pub fn edge_count(&self) -> usize {
+                self.cdawg.edge_count()
+            }
+
+            pub fn get_source(&self) -> usize {
+                self.cdawg.get_source().index()
+            }
+
+            pub fn get_count(&self, state: usize) -> usize {
+                self.cdawg.get_count(NodeIndex::new(state))
+            }
+
+            pub fn get_initial(&self) -> $state {
+                $state {
+                    cs: self.cdawg.get_initial(),
+                }
+            }
+
+            pub fn transition_and_count(&self, cs: $state, token: $token) -> $state {
+                $state {
+                    cs: self.cdawg.transition_and_count(cs.cs, token),
+                }
+            }
+
+            pub fn get_suffix_count(&self, cs: $state) -> usize {
+                self.cdawg.get_suffix_count(cs.cs)
+            }
+
+            pub fn get_ngram_count(&self, py: Python<'_>, ngram: Vec<$token>) -> usize {
+                py.allow_threads(|| self.cdawg.get_ngram_count(&ngram))
+            }
+
+            /// Length of the longest matching suffix after each token of `tokens`.
+            pub fn get_suffix_length_profile(
+                &self,
+                py: Python<'_>,
+                tokens: Vec<$token>,
+            ) -> Vec<u64> {
+                py.allow_threads(|| self.cdawg.get_suffix_length_profile(&tokens))
+            }
+
+            /// Suffix count after each token of `tokens`, starting from the initial state.
+            pub fn get_suffix_counts(&self, py: Python<'_>, tokens: Vec<$token>) -> Vec<usize> {
+                py.allow_threads(|| {
+                    let mut cs = self.cdawg.get_initial();
+                    tokens
+                        .iter()
+                        .map(|token| {
+                            cs = self.cdawg.transition_and_count(cs, *token);
+                            self.cdawg.get_suffix_count(cs)
+                        })
+                        .collect()
+                })
+            }
+
+            /// Length of the longest matching suffix after each token, for each sequence in `batch`.
+            /// Sequences are split across all cores.
+            pub fn batch_suffix_lengths<'py>(
+                &self,
+                py: Python<'py>,
+                batch: Vec<Vec<$token>>,
+            ) -> Vec<&'py PyArray1<u64>> {
+                let lengths = py.allow_threads(|| {
+                    batch::map_parallel(&batch, |tokens| {
+                        batch::suffix_lengths(tokens, self.cdawg.get_initial(), |cs, token| {
+                            self.cdawg.transition_and_count(cs, token)
+                        })
+                    })
+                });
+                lengths.into_iter().map(|l| l.into_pyarray(py)).collect()
+            }
+
+            /// Next-token distribution after the longest matching suffix of each sequence in
+            /// `batch`, as a pair of token and probability arrays. Sequences are split across all
+            /// cores.
+            pub fn batch_next_token_probs<'py>(
+                &self,
+                py: Python<'py>,
+                batch: Vec<Vec<$token>>,
+            ) -> Vec<(&'py PyArray1<$token>, &'py PyArray1<f64>)> {
+                let distributions = py.allow_threads(|| {
+                    batch::map_parallel(&batch, |tokens| {
+                        let cs =
+                            batch::match_suffix(tokens, self.cdawg.get_initial(), |cs, token| {
+                                self.cdawg.transition_and_count(cs, token)
+                            });
+                        self.cdawg
+                            .get_next_tokens(cs)
+                            .into_iter()
+                            .unzip::<_, _, Vec<_>, Vec<_>>()
+                    })
+                });
+                distributions
+                    .into_iter()
+                    .map(|(tokens, probs)| (tokens.into_pyarray(py), probs.into_pyarray(py)))
+                    .collect()
+            }
+        }
+    };
+}
+
+frozen_cdawg_class!(FrozenCdawg, CdawgState, DefaultWeight, DefaultIx, u16);
+frozen_cdawg_class!(FrozenCdawg32, CdawgState, DefaultWeight, DefaultIx, u32);
+frozen_cdawg_class!(FrozenCdawgIx64, CdawgStateIx64, Weight64, u64, u16);
+frozen_cdawg_class!(FrozenCdawg32Ix64, CdawgStateIx64, Weight64, u64, u32);
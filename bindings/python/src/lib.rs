@@ -1,24 +1,81 @@
+// The impls that pyo3 0.19 generates for #[pymethods] are nested in functions, which newer
+// compilers warn about.
+#![allow(non_local_definitions)]
+
+use ::rusty_dawg::error::RustyDawgError;
+use pyo3::exceptions::{
+    PyDeprecationWarning, PyFileNotFoundError, PyIOError, PyOverflowError, PyValueError,
+};
 use pyo3::prelude::*;
 
+pub mod batch;
 pub mod cdawg;
 pub mod cdawg_state;
+pub mod count_kind;
 pub mod dawg;
 pub mod disk_cdawg;
 pub mod disk_dawg;
+pub mod frozen_cdawg;
+pub mod query;
+pub mod telemetry;
+pub mod token_vec;
+pub mod transitions;
 
-use cdawg::Cdawg;
-use cdawg_state::CdawgState;
+use cdawg::{Cdawg, Cdawg32};
+use cdawg_state::{CdawgState, CdawgStateIx64};
 use dawg::Dawg;
-use disk_cdawg::DiskCdawg;
+use disk_cdawg::{DiskCdawg, DiskCdawg32, DiskCdawg32Ix64, DiskCdawgIx64};
 use disk_dawg::DiskDawg;
+use frozen_cdawg::{FrozenCdawg, FrozenCdawg32, FrozenCdawg32Ix64, FrozenCdawgIx64};
+use token_vec::{TokenVec, TokenVec32};
+
+// Direct graph access depends on the memory backing, so traversals should use the walker methods.
+pub(crate) fn warn_graph_access(py: Python<'_>, method: &str) -> PyResult<()> {
+    let message = format!(
+        "{} reads the graph directly and is deprecated; use enter, fail, counts and emit_state_id",
+        method
+    );
+    PyErr::warn(py, py.get_type::<PyDeprecationWarning>(), &message, 1)
+}
+
+// Map library errors to the matching Python exception, so callers can catch e.g. FileNotFoundError.
+pub(crate) fn to_py_err(err: anyhow::Error) -> PyErr {
+    let message = err.to_string();
+    if let Some(err) = err.downcast_ref::<RustyDawgError>() {
+        return match err {
+            RustyDawgError::MissingFile(_) => PyFileNotFoundError::new_err(message),
+            RustyDawgError::TokenOverflow(_) => PyOverflowError::new_err(message),
+            _ => PyValueError::new_err(message),
+        };
+    }
+    if let Some(err) = err.downcast_ref::<std::io::Error>() {
+        return match err.kind() {
+            std::io::ErrorKind::NotFound => PyFileNotFoundError::new_err(message),
+            _ => PyIOError::new_err(message),
+        };
+    }
+    PyValueError::new_err(message)
+}
 
 /// A Python module implemented in Rust.
 #[pymodule]
 fn rusty_dawg(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
     m.add_class::<Cdawg>()?;
+    m.add_class::<Cdawg32>()?;
     m.add_class::<CdawgState>()?;
+    m.add_class::<CdawgStateIx64>()?;
     m.add_class::<Dawg>()?;
     m.add_class::<DiskCdawg>()?;
+    m.add_class::<DiskCdawg32>()?;
+    m.add_class::<DiskCdawgIx64>()?;
+    m.add_class::<DiskCdawg32Ix64>()?;
     m.add_class::<DiskDawg>()?;
+    m.add_class::<FrozenCdawg>()?;
+    m.add_class::<FrozenCdawg32>()?;
+    m.add_class::<FrozenCdawgIx64>()?;
+    m.add_class::<FrozenCdawg32Ix64>()?;
+    m.add_class::<TokenVec>()?;
+    m.add_class::<TokenVec32>()?;
     Ok(())
 }
@@ -0,0 +1,125 @@
+// Queries passed from Python as either raw strings or token ID sequences.
+//
+// Raw strings are tokenized in Rust with the tokenizer recorded in the index manifest, so that
+// queries are always tokenized the same way as the corpus was. Token IDs are passed through as-is,
+// after checking that they fit in the index's token type.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::{RefCell, RefMut};
+use std::path::Path;
+
+use crate::to_py_err;
+
+use rusty_dawg::build_manifest::BuildManifest;
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::text_query::get_next_token_strs;
+use rusty_dawg::cdawg::token_backing::CdawgToken;
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::graph::indexing::IndexType;
+use rusty_dawg::memory_backing::MemoryBacking;
+use rusty_dawg::tokenize::PretrainedTokenizer;
+use rusty_dawg::weight::Weight;
+
+/// Character span of each token of a tokenized query.
+pub type Offsets = Vec<(usize, usize)>;
+
+#[derive(FromPyObject)]
+pub enum Query {
+    #[pyo3(transparent)]
+    Text(String),
+    #[pyo3(transparent)]
+    Tokens(Vec<u64>),
+}
+
+/// Tokenizer for raw string queries. It is loaded on first use, since loading it may download it.
+#[derive(Default)]
+pub struct QueryTokenizer {
+    name: Option<String>,
+    tokenizer: RefCell<Option<PretrainedTokenizer>>,
+}
+
+impl QueryTokenizer {
+    /// Use the tokenizer from the manifest saved next to the index at `path`, if there is one.
+    pub fn from_manifest<P: AsRef<Path>>(path: P) -> PyResult<Self> {
+        let manifest = BuildManifest::load_next_to(path).map_err(to_py_err)?;
+        Ok(Self {
+            name: manifest.map(|manifest| manifest.tokenizer),
+            tokenizer: RefCell::new(None),
+        })
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = Some(name);
+        self.tokenizer = RefCell::new(None);
+    }
+
+    // The tokenizer, loaded on first use.
+    fn get_tokenizer(&self) -> PyResult<RefMut<'_, PretrainedTokenizer>> {
+        let Some(ref name) = self.name else {
+            return Err(PyValueError::new_err(
+                "This index has no manifest recording its tokenizer; pass token IDs or call \
+                 set_tokenizer()",
+            ));
+        };
+        let mut tokenizer = self.tokenizer.borrow_mut();
+        if tokenizer.is_none() {
+            *tokenizer = Some(PretrainedTokenizer::try_new(name).map_err(to_py_err)?);
+        }
+        Ok(RefMut::map(tokenizer, |tokenizer| {
+            tokenizer.as_mut().unwrap()
+        }))
+    }
+
+    /// Tokenize `text`, returning the token IDs and the character span of each token.
+    pub fn tokenize<T: CdawgToken>(&self, text: &str) -> PyResult<(Vec<T>, Offsets)> {
+        let (ids, offsets) = self
+            .get_tokenizer()?
+            .encode_with_offsets(text)
+            .map_err(to_py_err)?;
+        let ids = ids
+            .into_iter()
+            .map(|id| match to_token(id.into()) {
+                Ok(token) if token != T::end() => Ok(token),
+                _ => Err(PyValueError::new_err(format!(
+                    "Token ID {} exceeds {}",
+                    id,
+                    T::NAME
+                ))),
+            })
+            .collect::<PyResult<_>>()?;
+        Ok((ids, offsets))
+    }
+
+    pub fn resolve<T: CdawgToken>(&self, query: Query) -> PyResult<Vec<T>> {
+        match query {
+            Query::Text(text) => Ok(self.tokenize(&text)?.0),
+            Query::Tokens(tokens) => tokens.into_iter().map(to_token).collect(),
+        }
+    }
+
+    /// Decoded next tokens after the longest suffix of `query` that occurs in `cdawg`, most likely
+    /// first.
+    pub fn next_token_strs<W, Ix, Mb, T>(
+        &self,
+        cdawg: &Cdawg<W, Ix, Mb, T>,
+        query: Query,
+    ) -> PyResult<Vec<(String, f64)>>
+    where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Ix: IndexType,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        Mb::EdgeRef: Copy,
+        T: CdawgToken + TryFrom<u32>,
+    {
+        let query = self.resolve(query)?;
+        let tokenizer = self.get_tokenizer()?;
+        Ok(get_next_token_strs(cdawg, &query, &*tokenizer))
+    }
+}
+
+fn to_token<T: CdawgToken>(id: u64) -> PyResult<T> {
+    T::try_from(id)
+        .map_err(|_| PyValueError::new_err(format!("Token ID {} exceeds {}", id, T::NAME)))
+}
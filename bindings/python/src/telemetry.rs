@@ -0,0 +1,80 @@
+// Live build statistics passed to a Python callback.
+//
+// Builds without a callback take the plain Rust build path, so telemetry costs nothing unless it is
+// requested. While a callback is registered, the process-wide metrics are enabled so that cache
+// hits and misses are counted.
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::time::Instant;
+
+use rusty_dawg::metrics;
+
+pub const DEFAULT_EVERY: usize = 100_000;
+
+pub struct Telemetry {
+    callback: PyObject,
+    start: Instant,
+    metrics_were_enabled: bool,
+    error: Option<PyErr>,
+}
+
+impl Telemetry {
+    pub fn new(callback: PyObject) -> Self {
+        let metrics_were_enabled = metrics::is_enabled();
+        metrics::enable();
+        Self {
+            callback,
+            start: Instant::now(),
+            metrics_were_enabled,
+            error: None,
+        }
+    }
+
+    /// Call the callback with a dict of stats. If it raises, the error is kept for `finish` and
+    /// the build is stopped.
+    pub fn report(
+        &mut self,
+        n_tokens: usize,
+        n_nodes: usize,
+        n_edges: usize,
+    ) -> anyhow::Result<()> {
+        let result = Python::with_gil(|py| {
+            let elapsed = self.start.elapsed().as_secs_f64();
+            let snapshot = metrics::snapshot();
+            let stats = PyDict::new(py);
+            stats.set_item("n_tokens", n_tokens)?;
+            stats.set_item("n_nodes", n_nodes)?;
+            stats.set_item("n_edges", n_edges)?;
+            stats.set_item("elapsed_secs", elapsed)?;
+            stats.set_item("tokens_per_sec", n_tokens as f64 / elapsed)?;
+            if snapshot.cache_hits + snapshot.cache_misses > 0 {
+                stats.set_item("cache_hit_rate", snapshot.cache_hit_rate())?;
+            } else {
+                stats.set_item("cache_hit_rate", py.None())?;
+            }
+            self.callback.call1(py, (stats,))?;
+            Ok(())
+        });
+        result.map_err(|err: PyErr| {
+            self.error = Some(err);
+            anyhow::anyhow!("Telemetry callback raised an exception")
+        })
+    }
+
+    /// Convert the result of a build, re-raising the callback's exception if it raised one.
+    pub fn finish(mut self, result: anyhow::Result<()>) -> PyResult<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        Ok(result?)
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if !self.metrics_were_enabled {
+            metrics::disable();
+        }
+    }
+}
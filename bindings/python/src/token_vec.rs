@@ -0,0 +1,105 @@
+use numpy::{IntoPyArray, PyArray1};
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PySlice, PyType};
+
+use rusty_dawg::memory_backing::DiskVec;
+
+use crate::to_py_err;
+
+macro_rules! token_vec_class {
+    ($name:ident, $token:ty) => {
+        // Read-only view of the token DiskVec written by `--train-vec-path`.
+        #[pyclass]
+        pub struct $name {
+            tokens: DiskVec<$token>,
+        }
+
+        impl $name {
+            fn get_range(&self, start: usize, end: usize) -> PyResult<Vec<$token>> {
+                let end = end.min(self.tokens.len());
+                (start..end)
+                    .map(|idx| self.tokens.get(idx))
+                    .collect::<anyhow::Result<_>>()
+                    .map_err(to_py_err)
+            }
+        }
+
+        #[pymethods]
+        impl $name {
+            #[classattr]
+            const EOS: $token = <$token>::MAX;
+
+            #[classmethod]
+            pub fn open(_cls: &PyType, path: String) -> PyResult<Self> {
+                let tokens = DiskVec::load(path).map_err(to_py_err)?;
+                Ok(Self { tokens })
+            }
+
+            pub fn __len__(&self) -> usize {
+                self.tokens.len()
+            }
+
+            // Index with an int to get a token, or with a slice (step 1) to get a numpy array.
+            pub fn __getitem__(&self, py: Python, key: &PyAny) -> PyResult<PyObject> {
+                if let Ok(slice) = key.downcast::<PySlice>() {
+                    let indices = slice.indices(self.tokens.len() as std::os::raw::c_long)?;
+                    if indices.step != 1 {
+                        return Err(PyValueError::new_err(concat!(
+                            stringify!($name),
+                            " slices must have step 1"
+                        )));
+                    }
+                    let (start, end) = (
+                        indices.start as usize,
+                        indices.stop.max(indices.start) as usize,
+                    );
+                    return Ok(self.slice(py, start, end)?.to_object(py));
+                }
+
+                let mut idx: isize = key.extract()?;
+                if idx < 0 {
+                    idx += self.tokens.len() as isize;
+                }
+                if idx < 0 || idx as usize >= self.tokens.len() {
+                    return Err(PyIndexError::new_err(concat!(
+                        stringify!($name),
+                        " index out of range"
+                    )));
+                }
+                let token = self.tokens.get(idx as usize).map_err(to_py_err)?;
+                Ok(token.to_object(py))
+            }
+
+            /// Tokens in [start, end) as a numpy array.
+            pub fn slice<'py>(
+                &self,
+                py: Python<'py>,
+                start: usize,
+                end: usize,
+            ) -> PyResult<&'py PyArray1<$token>> {
+                Ok(self.get_range(start, end)?.into_pyarray(py))
+            }
+
+            /// Decode tokens in [start, end) with any tokenizer that has a `decode(list[int])` method,
+            /// e.g. a HuggingFace tokenizer. End-of-document tokens are dropped.
+            pub fn decode(
+                &self,
+                py: Python,
+                start: usize,
+                end: usize,
+                tokenizer: &PyAny,
+            ) -> PyResult<PyObject> {
+                let tokens: Vec<$token> = self
+                    .get_range(start, end)?
+                    .into_iter()
+                    .filter(|token| *token != <$token>::MAX)
+                    .collect();
+                Ok(tokenizer.call_method1("decode", (tokens,))?.to_object(py))
+            }
+        }
+    };
+}
+
+token_vec_class!(TokenVec, u16);
+token_vec_class!(TokenVec32, u32);
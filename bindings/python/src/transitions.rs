@@ -0,0 +1,22 @@
+// Transition matrices of DAWGs as dicts of numpy arrays, with the keys of the .npy files written
+// by `rusty-dawg export-transitions`. `rusty_dawg.transitions.to_csr` turns them into scipy sparse
+// matrices.
+
+use numpy::IntoPyArray;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use rusty_dawg::dawg::transitions::TransitionMatrix;
+
+pub fn to_py_dict(py: Python<'_>, matrix: TransitionMatrix) -> PyResult<&PyDict> {
+    let (n_rows, n_cols) = matrix.shape();
+    let arrays = PyDict::new(py);
+    arrays.set_item("states", matrix.states.into_pyarray(py))?;
+    arrays.set_item("state_counts", matrix.state_counts.into_pyarray(py))?;
+    arrays.set_item("indptr", matrix.indptr.into_pyarray(py))?;
+    arrays.set_item("indices", matrix.indices.into_pyarray(py))?;
+    arrays.set_item("next_states", matrix.next_states.into_pyarray(py))?;
+    arrays.set_item("counts", matrix.counts.into_pyarray(py))?;
+    arrays.set_item("shape", (n_rows, n_cols))?;
+    Ok(arrays)
+}
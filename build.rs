@@ -0,0 +1,6 @@
+// Generates the gRPC service in `rusty_dawg::grpc` from proto/cdawg.proto.
+
+fn main() {
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/cdawg.proto").unwrap();
+}
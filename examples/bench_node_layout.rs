@@ -0,0 +1,86 @@
+// Compare query speed of the array-of-structs (DiskBacking) and struct-of-arrays (ColumnarBacking)
+// node layouts on a synthetic corpus.
+//
+// Usage: cargo run --release --example bench_node_layout [N_TOKENS] [N_QUERIES]
+
+use std::env;
+use std::error::Error;
+use std::time::Instant;
+
+use rusty_dawg::prelude::*;
+use tempfile::tempdir;
+
+const VOCAB_SIZE: u64 = 64;
+const QUERY_LENGTH: usize = 8;
+
+// Deterministic pseudo-random tokens, so runs are comparable.
+fn generate_tokens(n_tokens: usize, mut seed: u64) -> Vec<u16> {
+    let mut tokens = Vec::with_capacity(n_tokens);
+    for _ in 0..n_tokens {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        // Square the uniform draw so that the distribution is skewed like natural text.
+        let uniform = (seed >> 33) % VOCAB_SIZE;
+        tokens.push((uniform * uniform / VOCAB_SIZE) as u16);
+    }
+    tokens
+}
+
+fn bench<Mb>(name: &str, dawg: &Dawg<u16, DefaultWeight, DefaultIx, Mb>, queries: &[u16])
+where
+    Mb: MemoryBacking<DefaultWeight, u16, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    // Lookups only follow edges, so they read first_edge but no other node fields.
+    let start = Instant::now();
+    let mut n_found = 0;
+    for query in queries.chunks(QUERY_LENGTH) {
+        let mut state = Some(dawg.get_initial());
+        for token in query {
+            state = state.and_then(|q| dawg.transition(q, *token, false));
+        }
+        n_found += state.is_some() as usize;
+    }
+    let lookup_secs = start.elapsed().as_secs_f64();
+
+    // Scoring additionally reads counts.
+    let start = Instant::now();
+    let mut total_count = 0;
+    for query in queries.chunks(QUERY_LENGTH) {
+        total_count += dawg.get_ngram_count(query);
+    }
+    let count_secs = start.elapsed().as_secs_f64();
+
+    println!(
+        "{:>10}: lookup {:.3}s ({} found), count {:.3}s (total {})",
+        name, lookup_secs, n_found, count_secs, total_count
+    );
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let n_tokens: usize = args.next().map_or(Ok(1_000_000), |s| s.parse())?;
+    let n_queries: usize = args.next().map_or(Ok(100_000), |s| s.parse())?;
+    let tokens = generate_tokens(n_tokens, 0);
+    let queries = generate_tokens(n_queries * QUERY_LENGTH, 1);
+
+    let disk_dir = tempdir()?;
+    let mut disk_dawg: Dawg<u16, DefaultWeight, DefaultIx, DiskBacking<_, _, _>> =
+        Dawg::new_mb(DiskBacking::new(disk_dir.path()), None);
+    disk_dawg.build(&tokens);
+
+    let columnar_dir = tempdir()?;
+    let mut columnar_dawg: Dawg<u16, DefaultWeight, DefaultIx, ColumnarBacking<_, _, _>> =
+        Dawg::new_mb(ColumnarBacking::new(columnar_dir.path()), None);
+    columnar_dawg.build(&tokens);
+
+    println!(
+        "# tokens: {}, # states: {}",
+        n_tokens,
+        disk_dawg.node_count()
+    );
+    bench("disk", &disk_dawg, &queries);
+    bench("columnar", &columnar_dawg, &queries);
+    Ok(())
+}
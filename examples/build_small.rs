@@ -0,0 +1,38 @@
+// Build a DAWG on a tiny bundled corpus and save it to disk.
+//
+// Usage: cargo run --example build_small [SAVE_PATH]
+
+use std::env;
+use std::error::Error;
+
+use rusty_dawg::prelude::*;
+
+const CORPUS: &str = include_str!("data/tiny.txt");
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let save_path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "/tmp/tiny.dawg".to_string());
+
+    let mut index: TokenIndex<u16> = TokenIndex::new();
+    let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+
+    // Each line is a document. Documents are separated by a special end-of-document transition.
+    let (mut last, mut length) = (dawg.get_initial(), 0);
+    for (doc_id, line) in CORPUS.lines().enumerate() {
+        index.build(line);
+        for token in index.tokenize(line) {
+            (last, length) = dawg.extend(token, last, length);
+        }
+        (last, length) = dawg.end_document(last, u16::MAX, doc_id as u64);
+    }
+
+    println!("# documents: {}", CORPUS.lines().count());
+    println!("# types: {}", index.get_count());
+    println!("# states: {}", dawg.node_count());
+    println!("# edges: {}", dawg.edge_count());
+
+    dawg.save(&save_path)?;
+    println!("Saved DAWG to {}", save_path);
+    Ok(())
+}
@@ -0,0 +1,25 @@
+// Serve a disk CDAWG over gRPC. See proto/cdawg.proto for the service.
+//
+// Usage: cargo run --release --features grpc --example grpc_server DISK_PATH [ADDR]
+//
+// The CDAWG must reference shared tokens (built with --share-tokens). It is copied into RAM before
+// serving.
+
+use std::env;
+
+use rusty_dawg::grpc;
+use rusty_dawg::prelude::*;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = env::args().skip(1);
+    let path = args.next().expect("Usage: grpc_server DISK_PATH [ADDR]");
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:50051".to_string());
+
+    let cdawg: Cdawg<DefaultWeight, DefaultIx, DiskBacking<_, _, _>> =
+        Cdawg::load_shared(path, CacheConfig::none())?;
+    let frozen = FrozenCdawg::new(&cdawg);
+    drop(cdawg);
+    println!("Serving {} states on {}", frozen.node_count(), addr);
+    grpc::serve(frozen, addr.parse()?).await
+}
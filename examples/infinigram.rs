@@ -0,0 +1,55 @@
+// Use a CDAWG as an unbounded-order n-gram model ("infini-gram") on a tiny bundled corpus.
+//
+// Usage: cargo run --example infinigram ["CONTEXT"]
+
+use std::cell::RefCell;
+use std::env;
+use std::rc::Rc;
+
+use rusty_dawg::prelude::*;
+
+const CORPUS: &str = include_str!("data/tiny.txt");
+
+fn main() {
+    let context = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "the cat sat on".to_string());
+
+    // The CDAWG indexes a token vector in which every document ends with u16::MAX.
+    let mut index: TokenIndex<u16> = TokenIndex::new();
+    let mut tokens: Vec<u16> = Vec::new();
+    for line in CORPUS.lines() {
+        index.build(line);
+        tokens.extend(index.tokenize(line));
+        tokens.push(u16::MAX);
+    }
+    println!("# tokens: {}", tokens.len());
+
+    let tokens: Rc<RefCell<dyn TokenBacking<u16>>> = Rc::new(RefCell::new(tokens));
+    let mut cdawg: Cdawg = Cdawg::new(tokens);
+    cdawg.build();
+    TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+
+    // Match the longest suffix of the context, then read off the next-token distribution.
+    let mut cs = cdawg.get_initial();
+    for word in context.split_whitespace() {
+        cs = cdawg.transition_and_count(cs, index.index(word));
+    }
+    if cs.target.is_none() {
+        // The last word never occurs in the corpus, so back off to the empty context.
+        cs = cdawg.get_initial();
+    }
+    println!(
+        "Matched suffix of length {} occurring {} times",
+        cs.length,
+        cdawg.get_suffix_count(cs)
+    );
+    for (token, prob) in cdawg.get_next_tokens(cs) {
+        let word = if token == u16::MAX {
+            "</doc>"
+        } else {
+            index.token(token)
+        };
+        println!("  P({} | ...) = {:.3}", word, prob);
+    }
+}
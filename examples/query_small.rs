@@ -0,0 +1,37 @@
+// Build a DAWG on a tiny bundled corpus, then query suffix lengths and counts.
+//
+// Usage: cargo run --example query_small ["QUERY TEXT"]
+
+use std::env;
+
+use rusty_dawg::prelude::*;
+
+const CORPUS: &str = include_str!("data/tiny.txt");
+
+fn main() {
+    let query = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "the dog sat on the mat".to_string());
+
+    let mut index: TokenIndex<u16> = TokenIndex::new();
+    index.build(CORPUS);
+    let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+    dawg.build(&index.tokenize(CORPUS));
+
+    // Track the longest suffix of the query prefix that occurs in the corpus, like the Evaluator.
+    let (mut state, mut length) = (dawg.get_initial(), 0);
+    for word in query.split_whitespace() {
+        let token = index.index(word);
+        let (opt_state, new_length) = dawg.transition_and_count(state, token, length);
+        state = opt_state.unwrap();
+        length = new_length;
+        let count = dawg.get_node(state).get_count();
+        println!("{:>10}  suffix length: {}  count: {}", word, length, count);
+    }
+
+    let max_length = dawg.get_max_factor_length(index.tokenize(&query));
+    println!(
+        "Longest substring of the query in the corpus: {} tokens",
+        max_length
+    );
+}
@@ -0,0 +1,83 @@
+#ifndef RUSTY_DAWG_H
+#define RUSTY_DAWG_H
+
+/* Generated by cbindgen from src/ffi.rs. Don't edit by hand; run `make header`. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * A CDAWG opened for querying.
+ */
+typedef struct RustyDawgIndex RustyDawgIndex;
+
+/**
+ * Open the disk CDAWG at `index_path`, with the token vector it was built on at `tokens_path`, and
+ * copy it into RAM. `tokens_path` may be NULL if the CDAWG references shared tokens. Returns NULL
+ * on failure. The index must be closed with `rusty_dawg_close_index`.
+ *
+ * # Safety
+ *
+ * `index_path` must be a NUL-terminated string, and so must `tokens_path` unless it is NULL.
+ */
+RustyDawgIndex *rusty_dawg_open_index(const char *index_path, const char *tokens_path);
+
+/**
+ * Free an index returned by `rusty_dawg_open_index`. Does nothing if `index` is NULL.
+ *
+ * # Safety
+ *
+ * `index` must have come from `rusty_dawg_open_index` and not have been closed already.
+ */
+void rusty_dawg_close_index(RustyDawgIndex *index);
+
+/**
+ * The reason the last call on this thread failed, or NULL if it succeeded. The string is owned by
+ * the library and stays valid until the next call on this thread.
+ */
+const char *rusty_dawg_last_error(void);
+
+/**
+ * The number of occurrences of the `n_tokens` tokens at `tokens` in the corpus. Returns 0 on
+ * failure.
+ *
+ * # Safety
+ *
+ * `index` must be an open index or NULL, and `tokens` must point to `n_tokens` tokens, or may be
+ * NULL if `n_tokens` is 0.
+ */
+uint64_t rusty_dawg_count(const RustyDawgIndex *index, const uint16_t *tokens, size_t n_tokens);
+
+/**
+ * The length of the longest suffix of the query that occurs in the corpus. Returns 0 on failure.
+ *
+ * # Safety
+ *
+ * Same as `rusty_dawg_count`.
+ */
+uint64_t rusty_dawg_suffix_len(const RustyDawgIndex *index,
+                               const uint16_t *tokens,
+                               size_t n_tokens);
+
+/**
+ * The distribution over the token following the longest suffix of the query that occurs in the
+ * corpus. Writes up to `capacity` tokens and their probabilities to `out_tokens` and `out_probs`,
+ * most likely first, and returns the total number of next tokens, which may exceed `capacity`.
+ * Returns 0 on failure.
+ *
+ * # Safety
+ *
+ * Same as `rusty_dawg_count`. `out_tokens` and `out_probs` must each have room for `capacity`
+ * items, or may be NULL if `capacity` is 0.
+ */
+size_t rusty_dawg_next_tokens(const RustyDawgIndex *index,
+                              const uint16_t *tokens,
+                              size_t n_tokens,
+                              uint16_t *out_tokens,
+                              double *out_probs,
+                              size_t capacity);
+
+#endif  /* RUSTY_DAWG_H */
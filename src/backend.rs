@@ -0,0 +1,371 @@
+// Runtime selection of the types that a build is monomorphized over.
+//
+// The build loops (`run_rusty_dawg` and `build_cdawg`) are generic, so their inner loops stay
+// monomorphic. A `Backend` records the choices made from the command-line arguments, and running it
+// resolves one choice per function: the token type picks `E`, the index width of a CDAWG and the
+// weight type pick `Ix` and the node weight, and then the backing picks `Mb`. A new token type,
+// index width, or backing is added with one match arm, rather than one arm per combination.
+
+use serde::{Deserialize, Serialize};
+use std::convert::{TryFrom, TryInto};
+use std::error::Error;
+use std::fmt;
+use std::fmt::Debug;
+
+use super::{run_rusty_dawg, Args, N};
+use crate::build_cdawg::{build_cdawg, resume_cdawg};
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::token_backing::CdawgToken;
+use rusty_dawg::graph::avl_graph::binary::BinaryLabel;
+use rusty_dawg::graph::indexing::{DefaultIx, IndexType, IndexWidth};
+use rusty_dawg::memory_backing::{DiskBacking, RamBacking};
+use rusty_dawg::tokenize::end::End;
+use rusty_dawg::tokenize::token_type::TokenType;
+use rusty_dawg::weight::{CountMode, Weight, Weight32, Weight64, WeightApprox, WeightType};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backing {
+    Ram,
+    Disk(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    Dawg {
+        token_type: TokenType,
+        weight_type: WeightType,
+        backing: Backing,
+    },
+    // CDAWGs support u16 and u32 tokens, and 40- or 64-bit indices.
+    Cdawg {
+        token_type: TokenType,
+        index_width: IndexWidth,
+        weight_type: WeightType,
+        backing: Backing,
+    },
+}
+
+impl Backend {
+    pub fn select(
+        args: &Args,
+        token_type: TokenType,
+        index_width: IndexWidth,
+    ) -> Result<Self, Box<dyn Error>> {
+        if args.resume && (!args.cdawg || args.ram || args.disk_path.is_none()) {
+            return Err("--resume only supports CDAWGs built on disk at --disk-path".into());
+        }
+        if args.resume_from_checkpoint && (!args.cdawg || args.ram || args.disk_path.is_none()) {
+            return Err(
+                "--resume-from-checkpoint only supports CDAWGs built on disk at --disk-path".into(),
+            );
+        }
+        if args.checkpoint_every.is_some() && (!args.cdawg || args.ram || args.disk_path.is_none())
+        {
+            return Err(
+                "--checkpoint-every only supports CDAWGs built on disk at --disk-path".into(),
+            );
+        }
+        if args.token_frame_size.is_some() {
+            if !args.cdawg || args.train_vec_path.is_none() {
+                return Err("--token-frame-size only supports CDAWGs with --train-vec-path".into());
+            }
+            if args.resume || args.resume_from_checkpoint || args.checkpoint_every.is_some() {
+                return Err("Compressed tokens can't be resumed or checkpointed".into());
+            }
+            if args.share_tokens {
+                return Err("Compressed tokens can't be shared with --share-tokens".into());
+            }
+        }
+        let checkpointing = args.checkpoint_every.is_some() || args.resume_from_checkpoint;
+        if checkpointing && args.max_doc_tokens.is_some() {
+            return Err(
+                "Checkpoints don't support splitting documents with --max-doc-tokens".into(),
+            );
+        }
+        if let Some(min_count) = args.prune_min_count {
+            if args.save_path.is_empty() {
+                return Err("--prune-min-count requires --save-path".into());
+            }
+            if args.disk_path.as_deref() == Some(args.save_path.as_str()) {
+                return Err("--prune-min-count can't save over the graph at --disk-path".into());
+            }
+            if args.cdawg && args.no_counts {
+                return Err("--prune-min-count requires counts, but --no-counts was set".into());
+            }
+            if min_count == 0 {
+                return Err("--prune-min-count must be positive".into());
+            }
+        }
+        match args.stats.as_deref() {
+            None => {}
+            Some("good-turing") if args.cdawg => {
+                return Err("--stats good-turing only supports DAWGs".into());
+            }
+            Some("good-turing") => {}
+            Some(stats) => return Err(format!("Unknown --stats option {}", stats).into()),
+        }
+        // Without the reserved separator, documents don't exist in the graph.
+        let ends_documents = match args.doc_separator.as_str() {
+            "reserved" => !args.single_string,
+            "eos" | "none" => false,
+            name => return Err(format!("Unknown --doc-separator {}", name).into()),
+        };
+        if !ends_documents {
+            if checkpointing {
+                return Err("Checkpoints require --doc-separator reserved".into());
+            }
+            if args.doc_spans {
+                return Err("--doc-spans requires --doc-separator reserved".into());
+            }
+            if !args.cdawg && args.num_threads > 1 {
+                return Err("--num-threads requires --doc-separator reserved for DAWGs".into());
+            }
+        }
+        let weight_type = select_weight_type(args, index_width)?;
+        if args.cdawg {
+            if token_type == TokenType::Usize {
+                return Err(format!(
+                    "CDAWGs only support u16 and u32 tokens, but utype {} was selected",
+                    token_type
+                )
+                .into());
+            }
+            // With --ram, a CDAWG is built in RAM even if it will be saved on disk.
+            let backing = match args.disk_path {
+                Some(ref path) if !args.ram => Backing::Disk(path.clone()),
+                _ => Backing::Ram,
+            };
+            return Ok(Backend::Cdawg {
+                token_type,
+                index_width,
+                weight_type,
+                backing,
+            });
+        }
+        if index_width != IndexWidth::Bits40 {
+            return Err(format!("DAWGs only support 40-bit indices, not {}", index_width).into());
+        }
+        let backing = match args.disk_path {
+            Some(ref path) => Backing::Disk(path.clone()),
+            None => Backing::Ram,
+        };
+        Ok(Backend::Dawg {
+            token_type,
+            weight_type,
+            backing,
+        })
+    }
+
+    pub fn run(self, args: Args) -> Result<(), Box<dyn Error>> {
+        println!("Backend: {}", self);
+        match self {
+            Backend::Dawg {
+                token_type,
+                weight_type,
+                backing,
+            } => match token_type {
+                TokenType::U16 => run_dawg::<u16>(args, weight_type, backing),
+                TokenType::U32 => run_dawg::<u32>(args, weight_type, backing),
+                TokenType::Usize => run_dawg::<usize>(args, weight_type, backing),
+            },
+            Backend::Cdawg {
+                token_type,
+                index_width,
+                weight_type,
+                backing,
+            } => match token_type {
+                TokenType::U16 => run_cdawg::<u16>(args, index_width, weight_type, backing),
+                TokenType::U32 => run_cdawg::<u32>(args, index_width, weight_type, backing),
+                TokenType::Usize => unreachable!("rejected by Backend::select"),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, token_type, index_width, weight_type, backing) = match self {
+            Backend::Dawg {
+                token_type,
+                weight_type,
+                backing,
+            } => ("DAWG", token_type, IndexWidth::Bits40, weight_type, backing),
+            Backend::Cdawg {
+                token_type,
+                index_width,
+                weight_type,
+                backing,
+            } => ("CDAWG", token_type, *index_width, weight_type, backing),
+        };
+        write!(f, "{}<{}>", name, token_type)?;
+        if index_width != IndexWidth::Bits40 {
+            write!(f, " with {} indices", index_width)?;
+        }
+        match weight_type {
+            WeightType::Approx => write!(f, " with approximate counts")?,
+            _ if *weight_type != WeightType::default_for(index_width.bits(), CountMode::Exact) => {
+                write!(f, " with {}-bit weights", weight_type)?
+            }
+            _ => {}
+        }
+        match backing {
+            Backing::Ram => write!(f, " in RAM"),
+            Backing::Disk(path) => write!(f, " on disk: {}", path),
+        }
+    }
+}
+
+// The weight type chosen with `--weight-type`, or else the default for the index width and count
+// mode, checked against the options that need a wider weight or counts.
+fn select_weight_type(args: &Args, index_width: IndexWidth) -> Result<WeightType, Box<dyn Error>> {
+    let default = WeightType::default_for(index_width.bits(), args.count_mode);
+    let weight_type = args.weight_type.unwrap_or(default);
+    if args.count_mode == CountMode::Approx && weight_type != WeightType::Approx {
+        return Err(format!(
+            "--count-mode approx can't be combined with --weight-type {}",
+            weight_type
+        )
+        .into());
+    }
+    if index_width == IndexWidth::Bits64 && weight_type != WeightType::W64 {
+        return Err(format!("--weight-type {} only supports 40-bit indices", weight_type).into());
+    }
+    if !weight_type.has_counts() {
+        if args.cdawg && !args.no_counts {
+            return Err(format!(
+                "--weight-type {} stores no counts, so pass --no-counts",
+                weight_type
+            )
+            .into());
+        }
+        let uses_counts = args.prune_min_count.is_some()
+            || args.doc_frequencies
+            || args.domain_counts
+            || !args.test_path.is_empty()
+            || args.stats.is_some()
+            || args.detailed_stats
+            || args.stats_held_out.is_some();
+        if uses_counts {
+            return Err(format!(
+                "--weight-type {} stores no counts, so it can't be used with evaluation, pruning, or count statistics",
+                weight_type
+            )
+            .into());
+        }
+    }
+    if weight_type == WeightType::W32 {
+        // Lengths have 31 bits, and the max failure is reserved for None.
+        let n_nodes = (args.nodes_ratio * (args.n_tokens as f64)).ceil() as usize;
+        if args.n_tokens >= 1 << 31 || n_nodes >= u32::MAX as usize {
+            return Err("--weight-type 32 supports up to 2^31 tokens and 2^32 nodes".into());
+        }
+    }
+    Ok(weight_type)
+}
+
+fn run_dawg<E>(args: Args, weight_type: WeightType, backing: Backing) -> Result<(), Box<dyn Error>>
+where
+    E: Eq
+        + Ord
+        + Serialize
+        + for<'a> Deserialize<'a>
+        + Copy
+        + Default
+        + Debug
+        + TryInto<usize>
+        + TryFrom<usize>
+        + Send
+        + 'static
+        + TryInto<u32>
+        + TryFrom<u32>
+        + End
+        + BinaryLabel,
+    usize: TryFrom<E>,
+    u64: TryFrom<E>,
+    <E as TryFrom<usize>>::Error: Debug,
+{
+    match weight_type {
+        WeightType::W32 => run_dawg_w::<E, Weight32>(args, backing),
+        WeightType::W40 => run_dawg_w::<E, N>(args, backing),
+        WeightType::W64 => run_dawg_w::<E, Weight64>(args, backing),
+        WeightType::Approx => run_dawg_w::<E, WeightApprox>(args, backing),
+    }
+}
+
+fn run_dawg_w<E, W>(args: Args, backing: Backing) -> Result<(), Box<dyn Error>>
+where
+    E: Eq
+        + Ord
+        + Serialize
+        + for<'a> Deserialize<'a>
+        + Copy
+        + Default
+        + Debug
+        + TryInto<usize>
+        + TryFrom<usize>
+        + Send
+        + 'static
+        + TryInto<u32>
+        + TryFrom<u32>
+        + End
+        + BinaryLabel,
+    usize: TryFrom<E>,
+    u64: TryFrom<E>,
+    <E as TryFrom<usize>>::Error: Debug,
+    W: Weight + Copy + Serialize + for<'a> Deserialize<'a> + Clone + Default + Send + 'static,
+{
+    match backing {
+        Backing::Ram => run_rusty_dawg::<E, W, _>(args, RamBacking::<W, E, DefaultIx>::default()),
+        Backing::Disk(path) => {
+            run_rusty_dawg::<E, W, _>(args, DiskBacking::<W, E, DefaultIx>::new(path))
+        }
+    }
+}
+
+fn run_cdawg<T>(
+    args: Args,
+    index_width: IndexWidth,
+    weight_type: WeightType,
+    backing: Backing,
+) -> Result<(), Box<dyn Error>>
+where
+    T: CdawgToken + TryFrom<u32> + TryInto<usize> + TryFrom<usize>,
+    usize: TryFrom<T>,
+{
+    match (index_width, weight_type) {
+        (IndexWidth::Bits40, WeightType::W32) => {
+            run_cdawg_ix::<T, Weight32, DefaultIx>(args, backing)
+        }
+        (IndexWidth::Bits40, WeightType::W40) => run_cdawg_ix::<T, N, DefaultIx>(args, backing),
+        (IndexWidth::Bits40, WeightType::W64) => {
+            run_cdawg_ix::<T, Weight64, DefaultIx>(args, backing)
+        }
+        (IndexWidth::Bits40, WeightType::Approx) => {
+            run_cdawg_ix::<T, WeightApprox, DefaultIx>(args, backing)
+        }
+        (IndexWidth::Bits64, WeightType::W64) => run_cdawg_ix::<T, Weight64, u64>(args, backing),
+        (IndexWidth::Bits64, _) => unreachable!("rejected by Backend::select"),
+    }
+}
+
+fn run_cdawg_ix<T, W, Ix>(args: Args, backing: Backing) -> Result<(), Box<dyn Error>>
+where
+    T: CdawgToken + TryFrom<u32> + TryInto<usize> + TryFrom<usize>,
+    usize: TryFrom<T>,
+    W: Weight + Copy + Serialize + for<'a> Deserialize<'a> + Clone + Default,
+    Ix: IndexType + Serialize + for<'a> Deserialize<'a>,
+{
+    match backing {
+        Backing::Ram => Ok(build_cdawg::<W, Ix, _, T>(
+            args,
+            RamBacking::<W, CdawgEdgeWeight<Ix>, Ix>::default(),
+        )?),
+        Backing::Disk(path) if args.resume || args.resume_from_checkpoint => {
+            Ok(resume_cdawg::<W, Ix, T>(args, path)?)
+        }
+        Backing::Disk(path) => Ok(build_cdawg::<W, Ix, _, T>(
+            args,
+            DiskBacking::<W, CdawgEdgeWeight<Ix>, Ix>::new(path),
+        )?),
+    }
+}
@@ -4,7 +4,6 @@
 use anyhow::Result;
 
 use std::cell::RefCell;
-use std::cmp::min;
 
 use std::convert::TryInto;
 
@@ -12,84 +11,50 @@ use std::rc::Rc;
 
 use std::fs;
 use std::mem::size_of;
+use std::path::{Path, PathBuf};
 
-use kdam::{tqdm, BarExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 use super::Args;
 
-use crate::build_stats::BuildStats;
-use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
-use crate::cdawg::token_backing::TokenBacking;
-use crate::cdawg::Cdawg;
-use crate::cdawg::TopologicalCounter;
-use crate::data_reader::{DataReader, JsonlReader, PileReader, TxtReader};
-use crate::graph::avl_graph::edge::Edge;
-use crate::graph::avl_graph::node::Node;
-use crate::graph::indexing::DefaultIx;
-use crate::io;
-use crate::io::Save;
-use crate::memory_backing::{DiskVec, MemoryBacking};
-use crate::tokenize::{NullTokenIndex, PretrainedTokenizer, TokenIndex, Tokenize};
-
-type N = super::N;
-type E = CdawgEdgeWeight<DefaultIx>;
-
-pub fn build_cdawg<Mb>(args: Args, mb: Mb) -> Result<()>
-where
-    Mb: MemoryBacking<N, CdawgEdgeWeight<DefaultIx>, DefaultIx>,
-    Cdawg<N, DefaultIx, Mb>: io::Save,
-{
-    // TODO: Support token types with more bits?
-    let mut index: Box<dyn Tokenize<u16>> = if args.tokenizer == "whitespace" {
-        Box::new(TokenIndex::new())
-    } else if args.tokenizer == "null" {
-        Box::new(NullTokenIndex::new())
-    } else {
-        let mut pt = PretrainedTokenizer::new(&args.tokenizer);
-        pt.add_eos = true;
-        Box::new(pt)
-    };
-
-    println!("==========");
-    println!("Sizes");
-    println!("==========");
-    println!("  Ix: {}B", size_of::<DefaultIx>());
-    println!("  N: {}B", size_of::<N>());
-    println!("  E: {}B", size_of::<E>());
-    println!("  Node: {}B", size_of::<Node<N, DefaultIx>>());
-    println!("  Edge: {}B", size_of::<Edge<E, DefaultIx>>());
-    println!();
+use rusty_dawg::build_observer::BuildObserver;
+use rusty_dawg::cdawg::cached_tokens::CachedTokens;
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::checkpoint::{restore_checkpoint, save_checkpoint, BuildProgress};
+use rusty_dawg::cdawg::compressed_tokens::CompressedTokens;
+use rusty_dawg::cdawg::metadata::CdawgMetadata;
+use rusty_dawg::cdawg::stats::{StatsConfig, StatsTracker};
+use rusty_dawg::cdawg::token_backing::{CdawgToken, TokenBacking};
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::cdawg::TopologicalCounter;
+use rusty_dawg::data_reader::DocPiece;
+use rusty_dawg::evaluator::MultiEvaluator;
+use rusty_dawg::graph::avl_graph::edge::Edge;
+use rusty_dawg::graph::avl_graph::node::Node;
+use rusty_dawg::graph::indexing::IndexType;
+use rusty_dawg::io;
+use rusty_dawg::io::Save;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec, MemoryBacking};
+use rusty_dawg::tokenize::{load_tokenizer, Tokenize};
+use rusty_dawg::weight::Weight;
 
-    println!("Opening train file...");
-    let train_file = fs::File::open(args.train_path.as_str())?;
-    let n_bytes = train_file.metadata().unwrap().len();
-    let buf_size: usize = min(n_bytes.try_into().unwrap(), args.buf_size);
-    println!("Buffer size: {}B", args.buf_size);
-
-    let reader: Box<DataReader> = if args.data_reader == "pile" {
-        Box::new(PileReader::new(args.train_path.clone()).unwrap())
-    } else if args.data_reader == "jsonl" {
-        Box::new(JsonlReader::new(args.train_path.clone(), "text".to_string(), None).unwrap())
-    } else {
-        Box::new(TxtReader::new(
-            train_file,
-            buf_size,
-            args.split_token.clone(),
-        ))
-    };
-
-    let test_raw: String = if args.test_path.is_empty() {
-        "".to_string()
-    } else {
-        let path = args.test_path.as_str();
-        fs::read_to_string(path).unwrap_or_else(|_| panic!("Could not load test from {}", path))
-    };
-    index.build(&test_raw); // Either the tokenizer must be pretrained or test must contain all tokens!
+type TokenCache<T> = Rc<RefCell<CachedTokens<DiskVec<T>, T>>>;
+type Tokens<T> = Rc<RefCell<dyn TokenBacking<T>>>;
 
+pub fn build_cdawg<N, Ix, Mb, T>(args: Args, mb: Mb) -> Result<()>
+where
+    N: Weight + Serialize + DeserializeOwned + Clone + Default,
+    Ix: IndexType + Serialize + DeserializeOwned,
+    Mb: MemoryBacking<N, CdawgEdgeWeight<Ix>, Ix>,
+    Cdawg<N, Ix, Mb, T>: io::Save,
+    T: CdawgToken + TryFrom<u32> + TryInto<usize> + TryFrom<usize>,
+    usize: TryFrom<T>,
+{
     println!("Cache size: {}", args.cache_size);
     let n_nodes = (args.nodes_ratio * (args.n_tokens as f64)).ceil() as usize;
     let n_edges = (args.edges_ratio * (args.n_tokens as f64)).ceil() as usize;
-    let cache_config = args.get_cache_config();
+    let cache_config = get_cache_config::<N, Ix, T>(&args)?;
     let _max_length: Option<u64> = if !args.max_state_length.is_negative() {
         Some(args.max_state_length.try_into().unwrap())
     } else {
@@ -99,54 +64,292 @@ where
     // Maintain a DiskVec that we update incrementally (whenever we read a token, set it).
     println!("# tokens: {}", args.n_tokens);
     println!("Creating train vector...");
-    let train_vec: Rc<RefCell<dyn TokenBacking<u16>>> = match &args.train_vec_path {
+    let (train_vec, token_cache) = match &args.train_vec_path {
+        Some(ref train_vec_path) if args.token_frame_size.is_some() => {
+            let frame_size = args.token_frame_size.unwrap();
+            let cache_frames = usize::max(args.token_cache_size / usize::max(frame_size, 1), 1);
+            println!(
+                "Compressing train vector in frames of {} tokens",
+                frame_size
+            );
+            let tokens: CompressedTokens<T> =
+                CompressedTokens::new(train_vec_path, frame_size, cache_frames)?;
+            let tokens: Rc<RefCell<dyn TokenBacking<T>>> = Rc::new(RefCell::new(tokens));
+            (tokens, None)
+        }
         Some(ref train_vec_path) => {
             let disk_vec = DiskVec::new(train_vec_path, args.n_tokens)?;
-            Rc::new(RefCell::new(disk_vec))
+            wrap_tokens(disk_vec, &args)?
         }
         None => {
             println!("Storing tokens vector in RAM!");
-            let vec = Vec::with_capacity(args.n_tokens);
-            Rc::new(RefCell::new(vec))
+            let vec: Rc<RefCell<dyn TokenBacking<T>>> =
+                Rc::new(RefCell::new(Vec::with_capacity(args.n_tokens)));
+            (vec, None)
         }
     };
 
     println!("Allocating CDAWG...");
-    let mut cdawg: Cdawg<N, DefaultIx, Mb> =
+    let cdawg: Cdawg<N, Ix, Mb, T> =
         Cdawg::with_capacity_mb(train_vec.clone(), mb, n_nodes, n_edges, cache_config);
+    extend_cdawg(args, cdawg, train_vec, token_cache, 0)
+}
 
-    let mut idx: usize = 0;
-    let mut pbar = tqdm!(total = args.n_tokens);
-    let (mut state, mut start) = (cdawg.get_source(), 1);
-    for (doc_id, doc) in reader {
-        let tokens = index.tokenize(doc.as_str());
-        for token in &tokens {
-            idx += 1;
-            train_vec.borrow_mut().push(*token);
-            (state, start) = cdawg.update(state, start, idx);
-            if *token == u16::MAX {
-                (state, start) = cdawg.end_document(idx, doc_id);
-            }
-            let _ = pbar.update(1);
-
-            if let Some(stats_threshold) = args.stats_threshold {
-                if (idx + 1) % stats_threshold == 0 {
-                    let stats = BuildStats::from_cdawg(&cdawg, idx, n_bytes, pbar.elapsed_time());
-                    let npt = stats.get_nodes_per_token();
-                    let ept = stats.get_edges_per_token();
-                    pbar.set_description(format!("n/t: {:.2}, e/t: {:.2}", npt, ept));
-                    if let Some(ref stats_path) = args.stats_path {
-                        stats.append_to_jsonl(stats_path)?;
+/// Reopen the CDAWG built on disk at `path` and add the documents in `train_path` to it. With
+/// --resume-from-checkpoint, it is first rolled back to its last checkpoint, and the documents that
+/// were already added are skipped.
+pub fn resume_cdawg<N, Ix, T>(args: Args, path: String) -> Result<()>
+where
+    N: Weight + Copy + Serialize + DeserializeOwned + Clone + Default,
+    Ix: IndexType + Serialize + DeserializeOwned,
+    T: CdawgToken + TryFrom<u32> + TryInto<usize> + TryFrom<usize>,
+    usize: TryFrom<T>,
+{
+    let mut skip_docs = 0;
+    if args.resume_from_checkpoint {
+        println!("Restoring checkpoint in {}...", path);
+        let progress = restore_checkpoint::<N, Ix, _>(&path)?;
+        if progress.train_path != args.train_path {
+            anyhow::bail!(
+                "Checkpoint in {} was taken while reading {}, not {}",
+                path,
+                progress.train_path,
+                args.train_path
+            );
+        }
+        println!("Skipping {} documents...", progress.n_docs);
+        skip_docs = progress.n_docs;
+    }
+    let metadata = CdawgMetadata::load_json(Path::new(&path).join("metadata.json"))?;
+    let train_vec_path = match (&args.train_vec_path, &metadata.tokens) {
+        (Some(ref train_vec_path), _) => PathBuf::from(train_vec_path),
+        (None, Some(ref shared_tokens)) => shared_tokens.resolve(&path).path,
+        (None, None) => anyhow::bail!("--resume requires --train-vec-path"),
+    };
+    println!("Reopening train vector {:?}...", train_vec_path);
+    let disk_vec = DiskVec::open_mut(&train_vec_path, metadata.end_position)?;
+    let (train_vec, token_cache) = wrap_tokens(disk_vec, &args)?;
+
+    println!(
+        "Resuming CDAWG at {} from token {}...",
+        path, metadata.end_position
+    );
+    let cdawg: Cdawg<N, Ix, DiskBacking<N, CdawgEdgeWeight<Ix>, Ix>, T> = Cdawg::resume_from_disk(
+        train_vec.clone(),
+        &path,
+        get_cache_config::<N, Ix, T>(&args)?,
+    )?;
+    extend_cdawg(args, cdawg, train_vec, token_cache, skip_docs)
+}
+
+// Cache sizes for the nodes and edges of the CDAWG, leaving room in --memory-budget for the tokens
+// cached while building.
+fn get_cache_config<N, Ix: IndexType, T>(args: &Args) -> Result<CacheConfig> {
+    let cached_tokens = match (&args.train_vec_path, args.token_frame_size) {
+        (None, _) => 0,
+        (Some(_), Some(frame_size)) => usize::max(args.token_cache_size, frame_size),
+        (Some(_), None) => args.token_cache_size,
+    };
+    args.get_cache_config::<Node<N, Ix>, Edge<CdawgEdgeWeight<Ix>, Ix>, Ix>(
+        cached_tokens * size_of::<T>(),
+    )
+}
+
+// Read tokens through a block cache if --token-cache-size is set.
+fn wrap_tokens<T: CdawgToken>(
+    disk_vec: DiskVec<T>,
+    args: &Args,
+) -> Result<(Tokens<T>, Option<TokenCache<T>>)> {
+    if args.token_cache_size == 0 {
+        return Ok((Rc::new(RefCell::new(disk_vec)), None));
+    }
+    let cached = CachedTokens::new(disk_vec, args.token_block_size, args.token_cache_size)?;
+    let cached = Rc::new(RefCell::new(cached));
+    Ok((cached.clone(), Some(cached)))
+}
+
+// Add the documents in `train_path` after the first `skip_docs` to `cdawg`, then fill counts and
+// save it.
+fn extend_cdawg<N, Ix, Mb, T>(
+    args: Args,
+    mut cdawg: Cdawg<N, Ix, Mb, T>,
+    train_vec: Tokens<T>,
+    token_cache: Option<TokenCache<T>>,
+    skip_docs: usize,
+) -> Result<()>
+where
+    N: Weight + Serialize + DeserializeOwned + Clone + Default,
+    Ix: IndexType + Serialize + DeserializeOwned,
+    Mb: MemoryBacking<N, CdawgEdgeWeight<Ix>, Ix>,
+    Cdawg<N, Ix, Mb, T>: io::Save,
+    T: CdawgToken + TryFrom<u32> + TryInto<usize> + TryFrom<usize>,
+    usize: TryFrom<T>,
+{
+    let mut index: Box<dyn Tokenize<T>> = load_tokenizer(&args.tokenizer)?;
+
+    println!("==========");
+    println!("Sizes");
+    println!("==========");
+    println!("  Ix: {}B", size_of::<Ix>());
+    println!("  N: {}B", size_of::<N>());
+    println!("  E: {}B", size_of::<CdawgEdgeWeight<Ix>>());
+    println!("  Node: {}B", size_of::<Node<N, Ix>>());
+    println!("  Edge: {}B", size_of::<Edge<CdawgEdgeWeight<Ix>, Ix>>());
+    println!();
+
+    println!("Opening train file...");
+    let n_bytes = args.get_train_bytes()?;
+    println!("Buffer size: {}B", args.buf_size);
+    let reader = args.get_data_reader()?;
+
+    let mut tests_raw: Vec<(String, String)> = Vec::new();
+    for path in args.test_path.iter() {
+        if tests_raw.iter().any(|(other, _)| other == path) {
+            anyhow::bail!("Test path {} was passed more than once", path);
+        }
+        let test_raw = fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("Could not load test from {}", path));
+        index.try_build(&test_raw)?; // Either the tokenizer must be pretrained or test must contain all tokens!
+        tests_raw.push((path.clone(), test_raw));
+    }
+    let mut tests: Vec<(String, Vec<T>)> = Vec::new();
+    for (path, test_raw) in tests_raw.iter() {
+        let mut test: Vec<T> = index.try_tokenize(test_raw)?;
+        let old_test_len = test.len();
+        if args.truncate_test > 0 && test.len() > args.truncate_test {
+            test.truncate(args.truncate_test);
+        }
+        println!("#(test) {}: {}/{}", path, test.len(), old_test_len);
+        tests.push((path.clone(), test));
+    }
+    let mut evaluator = MultiEvaluator::new(&tests, args.max_length);
+    let mut results_writer = args.get_results_writer()?;
+    let eval_threshold = args.n_tokens.checked_div(args.n_eval).unwrap_or(0);
+    let mut eval_schedule = args.get_eval_schedule(eval_threshold)?;
+
+    let stats_schedule = args.get_eval_schedule(args.stats_threshold.unwrap_or(0))?;
+    let mut stats_config = StatsConfig::new(stats_schedule, n_bytes);
+    if let Some(ref stats_path) = args.stats_path {
+        stats_config = stats_config.with_path(stats_path);
+    }
+    if args.detailed_stats {
+        stats_config = stats_config.with_detail();
+    }
+    if let Some(ref held_out_path) = args.stats_held_out {
+        let held_out_raw = fs::read_to_string(held_out_path)
+            .map_err(|err| anyhow::anyhow!("Could not load {}: {}", held_out_path, err))?;
+        index.try_build(&held_out_raw)?;
+        let held_out: Vec<T> = index.try_tokenize(&held_out_raw)?;
+        println!("#(held out) {}: {}", held_out_path, held_out.len());
+        stats_config = stats_config.with_held_out(held_out);
+    }
+    let mut stats_tracker = StatsTracker::new(stats_config);
+    let mut splitter = args.get_doc_splitter()?;
+    // The Pile reader, and the JSONL reader with a domain key, return the domain of each document
+    // as its ID.
+    let track_domains = args.data_reader == "pile"
+        || (args.data_reader == "jsonl" && args.jsonl_domain_key.is_some());
+    if args.domain_counts && !track_domains {
+        anyhow::bail!("--domain-counts requires the pile reader, or jsonl with --jsonl-domain-key");
+    }
+    let (mut state, mut start) = cdawg.get_resume_point();
+    let separator = args.get_separator()?;
+    if start > 1 && separator != cdawg.get_separator() {
+        anyhow::bail!(
+            "The CDAWG was built with document separator {}, not {}",
+            cdawg.get_separator(),
+            separator
+        );
+    }
+    cdawg.set_separator(separator);
+    let separator_token: Option<T> = separator.get_token()?;
+    if args.doc_spans && cdawg.get_doc_spans().is_none() {
+        if start > 1 {
+            anyhow::bail!("--doc-spans can't be added to a CDAWG that was built without them");
+        }
+        cdawg.track_documents();
+    }
+    let mut idx: usize = start - 1;
+    let mut last_checkpoint = idx;
+    let mut observer = args.get_build_observer()?;
+    for (n_docs, doc) in (skip_docs + 1..).zip(reader.skip(skip_docs)) {
+        let (doc_id, doc) = doc?;
+        let tokens = index.try_tokenize(doc.as_str())?;
+        // Split the document, then end each piece with the separator.
+        let pieces = match splitter {
+            Some(ref mut splitter) => splitter.split(doc_id, tokens.len())?,
+            None => vec![DocPiece::whole(doc_id, tokens.len())],
+        };
+        for piece in pieces {
+            let piece_tokens = tokens[piece.start..piece.end].iter();
+            for token in piece_tokens.chain(separator_token.as_ref()) {
+                idx += 1;
+                train_vec.borrow_mut().push(*token);
+                (state, start) = cdawg.update(state, start, idx);
+                if *token == T::end() {
+                    (state, start) = cdawg.end_document(idx, piece.doc_id);
+                }
+                observer.on_token(idx)?;
+
+                if let Some(snapshot_every) = args.snapshot_every {
+                    if idx.is_multiple_of(snapshot_every) {
+                        cdawg.take_snapshot();
                     }
                 }
+
+                // Counts are only filled after building, so fill them temporarily to evaluate.
+                if !evaluator.is_empty() && eval_schedule.should_eval(idx) {
+                    TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+                    evaluator.evaluate_cdawg(&cdawg, idx);
+                    cdawg.reset_counts();
+                    if let Some(ref mut writer) = results_writer {
+                        writer.write(&evaluator)?;
+                    }
+                }
+
+                if let Some(stats) = stats_tracker.observe(&mut cdawg, idx)? {
+                    observer.on_node_stats(&stats)?;
+                }
+            }
+        }
+        if track_domains {
+            if let Some(totals) = cdawg.get_totals_mut() {
+                totals.add_domain_document(doc_id, tokens.len());
+            }
+        }
+        observer.on_document(n_docs, idx)?;
+
+        // Checkpoints can only be taken between documents, which only exist in the graph if they
+        // end with the reserved token.
+        if let (Some(checkpoint_every), Some(ref disk_path)) =
+            (args.checkpoint_every, &args.disk_path)
+        {
+            if separator.ends_documents() && idx - last_checkpoint >= checkpoint_every {
+                let progress = BuildProgress {
+                    train_path: args.train_path.clone(),
+                    n_docs,
+                };
+                save_checkpoint(&cdawg, disk_path, &progress)?;
+                observer.on_checkpoint(idx)?;
+                last_checkpoint = idx;
             }
         }
     }
+    if let Some(ref mut splitter) = splitter {
+        splitter.flush()?;
+    }
     eprintln!();
+    if let Some(writer) = results_writer {
+        writer.finish(&evaluator)?;
+    }
 
     println!("\nFilling counts...");
     if !args.no_counts {
         match args.count_path {
+            _ if args.num_threads > 1 => {
+                let mut counter = TopologicalCounter::new_ram();
+                counter.fill_counts_parallel(&mut cdawg, args.num_threads);
+            }
             Some(ref count_path) => {
                 let mut counter = TopologicalCounter::new_disk(count_path, idx)?;
                 counter.fill_counts(&mut cdawg);
@@ -157,31 +360,76 @@ where
             }
         }
     }
-
-    let stats = BuildStats::from_cdawg(&cdawg, idx, n_bytes, pbar.elapsed_time());
-    if let Some(ref stats_path) = args.stats_path {
-        stats.append_to_jsonl(stats_path)?;
+    if args.doc_frequencies {
+        println!("Filling document frequencies...");
+        TopologicalCounter::new_ram().fill_doc_frequencies(&mut cdawg);
+    }
+    if args.domain_counts {
+        println!("Filling domain counts...");
+        TopologicalCounter::new_ram().fill_domain_counts(&mut cdawg, args.max_exact_domains);
     }
+
+    let stats = stats_tracker.finish(&mut cdawg, idx)?;
+    observer.on_node_stats(&stats)?;
     println!();
     println!("==========");
     println!("Completed!");
     println!("==========");
     println!("  # tokens: {}", idx);
+    if let Some(totals) = cdawg.get_totals() {
+        println!("  # documents: {}", totals.n_docs);
+    }
     println!("  # nodes: {}", stats.n_nodes);
     println!("  # edges: {}", stats.n_edges);
     println!("  tokens/byte: {:.2}", stats.get_tokens_per_byte());
     println!("  nodes/token: {:.2}", stats.get_nodes_per_token());
     println!("  edge/token: {:.2}", stats.get_edges_per_token());
     println!("  balance ratio: {:.2}", stats.balance_ratio);
+    println!("  counts: {}", stats.describe_counts());
+    if let Some(ref detail) = stats.detail {
+        println!("  mean state entropy: {:.2} bits", detail.entropy_mean);
+        if let Some(suffix_length) = detail.held_out_suffix_length {
+            println!("  held-out suffix length: {:.2}", suffix_length);
+        }
+    }
+    if let Some(ref token_cache) = token_cache {
+        let cache_stats = token_cache.borrow().get_stats();
+        println!("  token cache hit rate: {:.2}", cache_stats.hit_rate());
+    }
     println!();
 
+    // A resumed CDAWG that shares its tokens has to rehash them, since they were appended to.
+    let shared_path = cdawg.get_shared_tokens().map(|shared| shared.path.clone());
+    if args.share_tokens {
+        match args.train_vec_path {
+            Some(ref train_vec_path) => cdawg.share_tokens(train_vec_path),
+            None => anyhow::bail!("--share-tokens requires --train-vec-path"),
+        }
+    } else if let Some(shared_path) = shared_path {
+        cdawg.share_tokens(shared_path);
+    }
+
     // TODO: Simplify this logic and the associated flags.
-    if !args.save_path.is_empty() {
+    if let Some(min_count) = args.prune_min_count {
+        println!("Saving DAWG pruned to count >= {}...", min_count);
+        let pruned = cdawg.prune_by_count(min_count);
+        println!("  # nodes: {}", pruned.node_count());
+        println!("  # edges: {}", pruned.edge_count());
+        pruned
+            .save(&args.save_path)
+            .map_err(|err| anyhow::anyhow!("{}", err))?;
+        args.get_manifest(separator)?
+            .save_next_to(&args.save_path)?;
+        println!("Successfully saved DAWG to {}!", &args.save_path);
+    } else if !args.save_path.is_empty() {
         println!("Saving DAWG...");
         let _ = cdawg.save(&args.save_path);
+        args.get_manifest(separator)?
+            .save_next_to(&args.save_path)?;
         println!("Successfully saved DAWG to {}!", &args.save_path);
-    } else if let Some(disk_path) = args.disk_path {
+    } else if let Some(ref disk_path) = args.disk_path {
         let _ = cdawg.save(disk_path.as_str());
+        args.get_manifest(separator)?.save_next_to(disk_path)?;
     }
     Ok(())
 }
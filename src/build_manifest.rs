@@ -0,0 +1,127 @@
+// Provenance for a built DAWG or CDAWG: where the training data came from and how it was read.
+//
+// The manifest is written next to the saved index. For an index saved as a directory, it is
+// `<dir>/manifest.json`; for an index saved as a single file, it is `<file>.manifest.json`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::data_reader::{DocumentFilter, FileDocs};
+use crate::tokenize::DocumentSeparator;
+
+#[derive(Deserialize, Serialize)]
+pub struct BuildManifest {
+    pub train_path: String,
+    pub data_reader: String,
+    pub tokenizer: String,
+    #[serde(default)]
+    pub vocab_hash: Option<u64>, // Hash of a pretrained tokenizer's vocabulary, to detect changes.
+    pub filters: Vec<DocumentFilter>, // Serialized in command-line syntax, e.g. "meta.source=web".
+    #[serde(default)]
+    pub separator: DocumentSeparator,
+    #[serde(default)]
+    pub files: Vec<FileDocs>, // Documents read from each file that `train_path` expanded to.
+}
+
+impl BuildManifest {
+    pub fn get_path<P: AsRef<Path>>(save_path: P) -> PathBuf {
+        let save_path = save_path.as_ref();
+        if save_path.is_dir() {
+            save_path.join("manifest.json")
+        } else {
+            let mut path = save_path.as_os_str().to_owned();
+            path.push(".manifest.json");
+            PathBuf::from(path)
+        }
+    }
+
+    /// The training file that the document at position `doc` in reading order came from, or None
+    /// if files weren't recorded.
+    pub fn get_file(&self, doc: usize) -> Option<&str> {
+        let idx = self.files.partition_point(|file| file.docs.1 <= doc);
+        self.files
+            .get(idx)
+            .filter(|file| file.docs.0 <= doc)
+            .map(|file| file.path.as_str())
+    }
+
+    pub fn save_json<P: AsRef<Path>>(&self, file_path: P) -> Result<()> {
+        let json_data = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(json_data.as_bytes())?;
+        Ok(())
+    }
+
+    /// Save the manifest next to an index saved at `save_path`.
+    pub fn save_next_to<P: AsRef<Path>>(&self, save_path: P) -> Result<()> {
+        self.save_json(Self::get_path(save_path))
+    }
+
+    /// Load the manifest saved next to an index at `save_path`, or None if there isn't one.
+    pub fn load_next_to<P: AsRef<Path>>(save_path: P) -> Result<Option<Self>> {
+        let path = Self::get_path(save_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_next_to() {
+        let manifest = BuildManifest {
+            train_path: "train.jsonl".to_string(),
+            data_reader: "jsonl".to_string(),
+            tokenizer: "gpt2".to_string(),
+            vocab_hash: Some(0xabc),
+            filters: vec!["meta.source~^web".parse().unwrap()],
+            separator: DocumentSeparator::Eos(50256),
+            files: vec![
+                FileDocs {
+                    path: "a.jsonl".to_string(),
+                    docs: (0, 2),
+                },
+                FileDocs {
+                    path: "b.jsonl".to_string(),
+                    docs: (2, 2),
+                },
+                FileDocs {
+                    path: "c.jsonl".to_string(),
+                    docs: (2, 5),
+                },
+            ],
+        };
+        assert_eq!(manifest.get_file(1), Some("a.jsonl"));
+        assert_eq!(manifest.get_file(2), Some("c.jsonl"));
+        assert_eq!(manifest.get_file(5), None);
+
+        let dir = tempdir().unwrap();
+        manifest.save_next_to(dir.path()).unwrap();
+        let text = std::fs::read_to_string(dir.path().join("manifest.json")).unwrap();
+        let blob: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(blob["filters"][0], "meta.source~^web");
+
+        let loaded = BuildManifest::load_next_to(dir.path()).unwrap().unwrap();
+        assert_eq!(loaded.tokenizer, "gpt2");
+        assert_eq!(loaded.vocab_hash, Some(0xabc));
+        assert_eq!(loaded.filters[0].to_string(), "meta.source~^web");
+        assert_eq!(loaded.separator, DocumentSeparator::Eos(50256));
+        assert_eq!(loaded.files, manifest.files);
+
+        let file_path = dir.path().join("dawg.bin");
+        manifest.save_next_to(&file_path).unwrap();
+        assert!(dir.path().join("dawg.bin.manifest.json").exists());
+        assert!(BuildManifest::load_next_to(dir.path().join("other.bin"))
+            .unwrap()
+            .is_none());
+    }
+}
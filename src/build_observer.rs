@@ -0,0 +1,206 @@
+// Hooks for reporting progress while a DAWG or CDAWG is built.
+//
+// The build loops in `main.rs`, `build_cdawg.rs` and `builder.rs` call into a `BuildObserver`, so
+// that library users embedding the builder can track progress programmatically. Every hook has a
+// default no-op implementation, so observers only implement the ones they need.
+//
+// Several observers can be combined in a `Vec<Box<dyn BuildObserver>>`, which forwards each call
+// to all of them in order.
+
+use anyhow::Result;
+use kdam::{tqdm, Bar, BarExt};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::build_stats::BuildStats;
+
+pub trait BuildObserver {
+    /// Called after each token is added, with the number of tokens added so far.
+    fn on_token(&mut self, _n_tokens: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after each document is added, with the numbers of documents and tokens so far.
+    fn on_document(&mut self, _n_docs: usize, _n_tokens: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after a checkpoint of the first `n_tokens` tokens is saved.
+    fn on_checkpoint(&mut self, _n_tokens: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called whenever the size of the graph is measured.
+    fn on_node_stats(&mut self, _stats: &BuildStats) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Ignores every event.
+#[derive(Default)]
+pub struct NoopObserver;
+
+impl BuildObserver for NoopObserver {}
+
+/// Shows a progress bar over the expected number of tokens, with the nodes and edges per token.
+pub struct ProgressBarObserver {
+    pbar: Bar,
+}
+
+impl ProgressBarObserver {
+    pub fn new(n_tokens: usize) -> Self {
+        Self {
+            pbar: tqdm!(total = n_tokens),
+        }
+    }
+}
+
+impl BuildObserver for ProgressBarObserver {
+    fn on_token(&mut self, n_tokens: usize) -> Result<()> {
+        let _ = self.pbar.update_to(n_tokens);
+        Ok(())
+    }
+
+    fn on_node_stats(&mut self, stats: &BuildStats) -> Result<()> {
+        let npt = stats.get_nodes_per_token();
+        let ept = stats.get_edges_per_token();
+        self.pbar
+            .set_description(format!("n/t: {:.2}, e/t: {:.2}", npt, ept));
+        Ok(())
+    }
+}
+
+/// Writes a CSV row each time the size of the graph is measured.
+pub struct CsvMetricsObserver<W: Write> {
+    writer: W,
+    n_docs: usize,
+}
+
+impl CsvMetricsObserver<BufWriter<File>> {
+    /// Create (or truncate) the CSV file at `path` and write its header.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> CsvMetricsObserver<W> {
+    pub fn new(mut writer: W) -> Result<Self> {
+        writeln!(
+            writer,
+            "n_tokens,n_docs,n_nodes,n_edges,nodes_per_token,edges_per_token,balance_ratio,elapsed_time"
+        )?;
+        Ok(Self { writer, n_docs: 0 })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> BuildObserver for CsvMetricsObserver<W> {
+    fn on_document(&mut self, n_docs: usize, _n_tokens: usize) -> Result<()> {
+        self.n_docs = n_docs;
+        Ok(())
+    }
+
+    fn on_checkpoint(&mut self, _n_tokens: usize) -> Result<()> {
+        // Keep the log in step with the checkpoint, in case the build is resumed from it.
+        Ok(self.writer.flush()?)
+    }
+
+    fn on_node_stats(&mut self, stats: &BuildStats) -> Result<()> {
+        writeln!(
+            self.writer,
+            "{},{},{},{},{:.4},{:.4},{:.4},{:.2}",
+            stats.n_tokens,
+            self.n_docs,
+            stats.n_nodes,
+            stats.n_edges,
+            stats.get_nodes_per_token(),
+            stats.get_edges_per_token(),
+            stats.balance_ratio,
+            stats.elapsed_time
+        )?;
+        Ok(self.writer.flush()?)
+    }
+}
+
+impl BuildObserver for Vec<Box<dyn BuildObserver>> {
+    fn on_token(&mut self, n_tokens: usize) -> Result<()> {
+        self.iter_mut()
+            .try_for_each(|observer| observer.on_token(n_tokens))
+    }
+
+    fn on_document(&mut self, n_docs: usize, n_tokens: usize) -> Result<()> {
+        self.iter_mut()
+            .try_for_each(|observer| observer.on_document(n_docs, n_tokens))
+    }
+
+    fn on_checkpoint(&mut self, n_tokens: usize) -> Result<()> {
+        self.iter_mut()
+            .try_for_each(|observer| observer.on_checkpoint(n_tokens))
+    }
+
+    fn on_node_stats(&mut self, stats: &BuildStats) -> Result<()> {
+        self.iter_mut()
+            .try_for_each(|observer| observer.on_node_stats(stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{channel, Sender};
+
+    fn get_stats(n_tokens: usize, n_nodes: usize, n_edges: usize) -> BuildStats {
+        BuildStats {
+            n_tokens,
+            n_nodes,
+            n_edges,
+            n_bytes: 0,
+            balance_ratio: 1.,
+            elapsed_time: 0.5,
+            count_error: 0.,
+            has_counts: true,
+            detail: None,
+        }
+    }
+
+    #[test]
+    fn test_csv_metrics() {
+        let mut observer = CsvMetricsObserver::new(Vec::new()).unwrap();
+        observer.on_document(3, 4).unwrap();
+        observer.on_node_stats(&get_stats(4, 6, 8)).unwrap();
+        let text = String::from_utf8(observer.into_inner()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("n_tokens,n_docs,n_nodes,n_edges"));
+        assert_eq!(lines[1], "4,3,6,8,1.5000,2.0000,1.0000,0.50");
+    }
+
+    // Sends each token count back to the test.
+    struct SendingObserver(Sender<usize>);
+
+    impl BuildObserver for SendingObserver {
+        fn on_token(&mut self, n_tokens: usize) -> Result<()> {
+            Ok(self.0.send(n_tokens)?)
+        }
+    }
+
+    #[test]
+    fn test_vec_forwards_events() {
+        let (tx1, rx1) = channel();
+        let (tx2, rx2) = channel();
+        let mut observers: Vec<Box<dyn BuildObserver>> = vec![
+            Box::new(SendingObserver(tx1)),
+            Box::new(NoopObserver),
+            Box::new(SendingObserver(tx2)),
+        ];
+        for n_tokens in 1..=3 {
+            observers.on_token(n_tokens).unwrap();
+        }
+        assert_eq!(rx1.try_iter().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(rx2.try_iter().collect::<Vec<_>>(), [1, 2, 3]);
+    }
+}
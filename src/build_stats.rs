@@ -2,13 +2,17 @@
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::path::Path;
 
 use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::stats::DetailedStats;
+use crate::cdawg::token_backing::CdawgToken;
 use crate::cdawg::Cdawg;
-use crate::graph::indexing::IndexType;
+use crate::dawg::Dawg;
+use crate::graph::indexing::{DefaultIx, IndexType};
 use crate::memory_backing::MemoryBacking;
 use crate::weight::Weight;
 
@@ -20,11 +24,21 @@ pub struct BuildStats {
     pub n_bytes: u64,
     pub balance_ratio: f64,
     pub elapsed_time: f32,
+    #[serde(default)]
+    pub count_error: f64, // Relative standard error of counts, or 0 if they are exact.
+    #[serde(default = "has_counts_default")]
+    pub has_counts: bool, // False for node weights that don't store counts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<DetailedStats>, // Only computed for CDAWGs, if asked for.
+}
+
+fn has_counts_default() -> bool {
+    true
 }
 
 impl BuildStats {
-    pub fn from_cdawg<W, Ix, Mb>(
-        cdawg: &Cdawg<W, Ix, Mb>,
+    pub fn from_cdawg<W, Ix, Mb, T>(
+        cdawg: &Cdawg<W, Ix, Mb, T>,
         n_tokens: usize,
         n_bytes: u64,
         elapsed_time: f32,
@@ -33,6 +47,7 @@ impl BuildStats {
         W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
         Ix: IndexType,
         Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        T: CdawgToken,
     {
         Self {
             n_tokens,
@@ -41,6 +56,34 @@ impl BuildStats {
             n_bytes,
             balance_ratio: cdawg.balance_ratio(1),
             elapsed_time,
+            count_error: W::count_error(),
+            has_counts: W::has_counts(),
+            detail: None,
+        }
+    }
+
+    pub fn from_dawg<E, W, Mb>(
+        dawg: &Dawg<E, W, DefaultIx, Mb>,
+        n_tokens: usize,
+        n_bytes: u64,
+        elapsed_time: f32,
+    ) -> Self
+    where
+        E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, E, DefaultIx>,
+        Mb::EdgeRef: Copy,
+    {
+        Self {
+            n_tokens,
+            n_nodes: dawg.node_count(),
+            n_edges: dawg.edge_count(),
+            n_bytes,
+            balance_ratio: dawg.balance_ratio(1),
+            elapsed_time,
+            count_error: W::count_error(),
+            has_counts: W::has_counts(),
+            detail: None,
         }
     }
 
@@ -56,6 +99,22 @@ impl BuildStats {
         (self.n_tokens as f64) / (self.n_bytes as f64)
     }
 
+    /// Describe how accurate counts are, for the summary printed after building.
+    pub fn describe_counts(&self) -> String {
+        if !self.has_counts {
+            return "not stored".to_string();
+        }
+        if self.count_error == 0. {
+            return "exact".to_string();
+        }
+        // Estimates are unbiased, so 95% of counts are within two standard errors.
+        format!(
+            "approximate, relative std. error {:.1}% (95% within {:.1}%)",
+            100. * self.count_error,
+            200. * self.count_error
+        )
+    }
+
     pub fn append_to_jsonl<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let blob = serde_json::to_string(self)?;
 
@@ -0,0 +1,416 @@
+// Builders that configure a DAWG or CDAWG and build it from a data reader, so that library users
+// don't have to copy the build loops from `main.rs` and `build_cdawg.rs`.
+//
+// Both builders start out building in RAM. `disk_path` switches to a `DiskBacking` at that path,
+// which changes the type of the builder, since the backing is a type parameter of the graph.
+//
+// Each document read is added to the tokenizer's vocabulary (a no-op for pretrained tokenizers),
+// tokenized, and ended with an end-of-document token, `End::end()`. The tokenizer is returned along
+// with the graph, so that queries can be tokenized the same way.
+//
+// An optional `BuildObserver` is told about each token and document added.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::convert::{TryFrom, TryInto};
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::build_observer::{BuildObserver, NoopObserver};
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::token_backing::{CdawgToken, TokenBacking};
+use crate::cdawg::{Cdawg, TopologicalCounter};
+use crate::data_reader::DataReader;
+use crate::dawg::Dawg;
+use crate::graph::indexing::DefaultIx;
+use crate::memory_backing::{CacheConfig, DiskBacking, DiskVec, MemoryBacking, RamBacking};
+use crate::tokenize::end::End;
+use crate::tokenize::{TokenIndex, Tokenize};
+use crate::weight::DefaultWeight;
+
+type CdawgRamBacking = RamBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+// How much to allocate up front. The backings grow past this if needed.
+struct Capacity {
+    n_tokens: usize,
+    nodes_ratio: f64,
+    edges_ratio: f64,
+    cache_config: CacheConfig,
+}
+
+impl Default for Capacity {
+    fn default() -> Self {
+        Self {
+            n_tokens: 1024,
+            nodes_ratio: 2.,
+            edges_ratio: 3.,
+            cache_config: CacheConfig::none(),
+        }
+    }
+}
+
+impl Capacity {
+    fn n_nodes(&self) -> usize {
+        (self.nodes_ratio * (self.n_tokens as f64)).ceil() as usize
+    }
+
+    fn n_edges(&self) -> usize {
+        (self.edges_ratio * (self.n_tokens as f64)).ceil() as usize
+    }
+}
+
+pub struct DawgBuilder<E, Mb = RamBacking<DefaultWeight, E, DefaultIx>>
+where
+    E: Eq + Serialize + Copy + Debug,
+{
+    mb: Mb,
+    max_length: Option<u64>,
+    capacity: Capacity,
+    tokenizer: Option<Box<dyn Tokenize<E>>>,
+    observer: Box<dyn BuildObserver>,
+}
+
+impl<E> DawgBuilder<E>
+where
+    E: Eq + Serialize + Copy + Debug,
+{
+    pub fn new() -> Self {
+        Self {
+            mb: RamBacking::default(),
+            max_length: None,
+            capacity: Capacity::default(),
+            tokenizer: None,
+            observer: Box::new(NoopObserver),
+        }
+    }
+}
+
+impl<E> Default for DawgBuilder<E>
+where
+    E: Eq + Serialize + Copy + Debug,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E, Mb> DawgBuilder<E, Mb>
+where
+    E: Eq
+        + Ord
+        + Serialize
+        + for<'de> Deserialize<'de>
+        + Copy
+        + Debug
+        + End
+        + TryInto<usize>
+        + TryFrom<usize>
+        + 'static,
+    usize: TryFrom<E>,
+    Mb: MemoryBacking<DefaultWeight, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    /// Build on disk in the directory at `path`.
+    pub fn disk_path<P>(self, path: P) -> DawgBuilder<E, DiskBacking<DefaultWeight, E, DefaultIx>>
+    where
+        P: AsRef<Path> + Clone + Debug,
+    {
+        DawgBuilder {
+            mb: DiskBacking::new(path),
+            max_length: self.max_length,
+            capacity: self.capacity,
+            tokenizer: self.tokenizer,
+            observer: self.observer,
+        }
+    }
+
+    /// Only keep states for strings up to `max_length` tokens long.
+    pub fn max_length(mut self, max_length: u64) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    pub fn cache_config(mut self, cache_config: CacheConfig) -> Self {
+        self.capacity.cache_config = cache_config;
+        self
+    }
+
+    /// Estimate of the number of tokens, used to preallocate the graph.
+    pub fn n_tokens(mut self, n_tokens: usize) -> Self {
+        self.capacity.n_tokens = n_tokens;
+        self
+    }
+
+    /// Nodes and edges to preallocate per token.
+    pub fn ratios(mut self, nodes_ratio: f64, edges_ratio: f64) -> Self {
+        self.capacity.nodes_ratio = nodes_ratio;
+        self.capacity.edges_ratio = edges_ratio;
+        self
+    }
+
+    /// Tokenizer for documents. Defaults to a `TokenIndex` over whitespace-separated tokens.
+    pub fn tokenizer(mut self, tokenizer: Box<dyn Tokenize<E>>) -> Self {
+        self.tokenizer = Some(tokenizer);
+        self
+    }
+
+    /// Report progress to `observer` while building.
+    pub fn observer(mut self, observer: Box<dyn BuildObserver>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Build a DAWG on every document in `reader`, and return it with the tokenizer.
+    #[allow(clippy::type_complexity)]
+    pub fn build_from_reader(
+        self,
+        reader: Box<DataReader>,
+    ) -> Result<(Dawg<E, DefaultWeight, DefaultIx, Mb>, Box<dyn Tokenize<E>>)> {
+        let mut tokenizer = self
+            .tokenizer
+            .unwrap_or_else(|| Box::new(TokenIndex::new()));
+        let mut dawg = Dawg::with_capacity_mb(
+            self.mb,
+            self.max_length,
+            self.capacity.n_nodes(),
+            self.capacity.n_edges(),
+            self.capacity.cache_config,
+        );
+        let mut observer = self.observer;
+        let (mut last, mut length) = (dawg.get_initial(), 0);
+        let mut idx = 0;
+        for (n_docs, doc) in (1..).zip(reader) {
+            let (doc_id, doc) = doc?;
+            tokenizer.try_build(doc.as_str())?;
+            for token in tokenizer.try_tokenize(doc.as_str())? {
+                (last, length) = dawg.extend(token, last, length);
+                idx += 1;
+                observer.on_token(idx)?;
+            }
+            (last, length) = dawg.end_document(last, E::end(), doc_id as u64);
+            observer.on_document(n_docs, idx)?;
+        }
+        Ok((dawg, tokenizer))
+    }
+}
+
+pub struct CdawgBuilder<T, Mb = CdawgRamBacking>
+where
+    T: CdawgToken,
+{
+    mb: Mb,
+    capacity: Capacity,
+    tokenizer: Option<Box<dyn Tokenize<T>>>,
+    train_vec_path: Option<PathBuf>,
+    observer: Box<dyn BuildObserver>,
+}
+
+impl<T> CdawgBuilder<T>
+where
+    T: CdawgToken,
+{
+    pub fn new() -> Self {
+        Self {
+            mb: RamBacking::default(),
+            capacity: Capacity::default(),
+            tokenizer: None,
+            train_vec_path: None,
+            observer: Box::new(NoopObserver),
+        }
+    }
+}
+
+impl<T> Default for CdawgBuilder<T>
+where
+    T: CdawgToken,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, Mb> CdawgBuilder<T, Mb>
+where
+    T: CdawgToken + TryInto<usize> + TryFrom<usize>,
+    usize: TryFrom<T>,
+    Mb: MemoryBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    /// Build on disk in the directory at `path`. Call `save_metadata` on the result so that it can
+    /// be loaded again.
+    pub fn disk_path<P>(
+        self,
+        path: P,
+    ) -> CdawgBuilder<T, DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>>
+    where
+        P: AsRef<Path> + Clone + Debug,
+    {
+        CdawgBuilder {
+            mb: DiskBacking::new(path),
+            capacity: self.capacity,
+            tokenizer: self.tokenizer,
+            train_vec_path: self.train_vec_path,
+            observer: self.observer,
+        }
+    }
+
+    /// Store the tokens in a `DiskVec` at `path` instead of in RAM.
+    pub fn train_vec_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.train_vec_path = Some(path.into());
+        self
+    }
+
+    pub fn cache_config(mut self, cache_config: CacheConfig) -> Self {
+        self.capacity.cache_config = cache_config;
+        self
+    }
+
+    /// Estimate of the number of tokens, used to preallocate the graph and tokens.
+    pub fn n_tokens(mut self, n_tokens: usize) -> Self {
+        self.capacity.n_tokens = n_tokens;
+        self
+    }
+
+    /// Nodes and edges to preallocate per token.
+    pub fn ratios(mut self, nodes_ratio: f64, edges_ratio: f64) -> Self {
+        self.capacity.nodes_ratio = nodes_ratio;
+        self.capacity.edges_ratio = edges_ratio;
+        self
+    }
+
+    /// Tokenizer for documents. Defaults to a `TokenIndex` over whitespace-separated tokens.
+    pub fn tokenizer(mut self, tokenizer: Box<dyn Tokenize<T>>) -> Self {
+        self.tokenizer = Some(tokenizer);
+        self
+    }
+
+    /// Report progress to `observer` while building.
+    pub fn observer(mut self, observer: Box<dyn BuildObserver>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Build a CDAWG on every document in `reader` and fill its counts, and return it with the
+    /// tokenizer. The document IDs from the reader label the sinks.
+    #[allow(clippy::type_complexity)]
+    pub fn build_from_reader(
+        self,
+        reader: Box<DataReader>,
+    ) -> Result<(Cdawg<DefaultWeight, DefaultIx, Mb, T>, Box<dyn Tokenize<T>>)> {
+        let mut tokenizer = self
+            .tokenizer
+            .unwrap_or_else(|| Box::new(TokenIndex::new()));
+        let tokens: Rc<RefCell<dyn TokenBacking<T>>> = match self.train_vec_path {
+            Some(ref path) => Rc::new(RefCell::new(DiskVec::new(path, self.capacity.n_tokens)?)),
+            None => Rc::new(RefCell::new(Vec::with_capacity(self.capacity.n_tokens))),
+        };
+        let mut cdawg = Cdawg::with_capacity_mb(
+            tokens.clone(),
+            self.mb,
+            self.capacity.n_nodes(),
+            self.capacity.n_edges(),
+            self.capacity.cache_config,
+        );
+
+        let mut observer = self.observer;
+        let (mut state, mut start) = cdawg.get_resume_point();
+        let mut idx = start - 1;
+        for (n_docs, doc) in (1..).zip(reader) {
+            let (doc_id, doc) = doc?;
+            tokenizer.try_build(doc.as_str())?;
+            let mut doc_tokens = tokenizer.try_tokenize(doc.as_str())?;
+            // Pretrained tokenizers may already end documents.
+            if doc_tokens.last() != Some(&T::end()) {
+                doc_tokens.push(T::end());
+            }
+            for token in doc_tokens {
+                idx += 1;
+                tokens.borrow_mut().push(token);
+                (state, start) = cdawg.update(state, start, idx);
+                if token == T::end() {
+                    (state, start) = cdawg.end_document(idx, doc_id);
+                }
+                observer.on_token(idx)?;
+            }
+            observer.on_document(n_docs, idx)?;
+        }
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        Ok((cdawg, tokenizer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{channel, Sender};
+    use tempfile::tempdir;
+
+    fn get_reader(docs: &[&str]) -> Box<DataReader> {
+        let docs: Vec<_> = docs
+            .iter()
+            .map(|doc| Rc::new(doc.to_string()))
+            .enumerate()
+            .map(Ok)
+            .collect();
+        Box::new(docs.into_iter())
+    }
+
+    #[test]
+    fn test_dawg_builder() {
+        let reader = get_reader(&["a b c", "b c d"]);
+        let (dawg, mut tokenizer) = DawgBuilder::<u16>::new()
+            .max_length(2)
+            .build_from_reader(reader)
+            .unwrap();
+        assert_eq!(dawg.get_ngram_count(&tokenizer.tokenize("b c")), 2);
+        assert_eq!(dawg.get_ngram_count(&tokenizer.tokenize("c d")), 1);
+    }
+
+    #[test]
+    fn test_cdawg_builder() {
+        let reader = get_reader(&["a b c", "b c d"]);
+        let (cdawg, mut tokenizer) = CdawgBuilder::<u16>::new()
+            .build_from_reader(reader)
+            .unwrap();
+        assert_eq!(cdawg.get_ngram_count(&tokenizer.tokenize("b c")), 2);
+        assert_eq!(cdawg.get_ngram_count(&tokenizer.tokenize("a b c")), 1);
+        assert_eq!(cdawg.get_ngram_count(&tokenizer.tokenize("c b")), 0);
+    }
+
+    #[test]
+    fn test_cdawg_builder_disk() {
+        let tmp_dir = tempdir().unwrap();
+        let reader = get_reader(&["a b c", "b c d"]);
+        let (cdawg, mut tokenizer) = CdawgBuilder::<u16>::new()
+            .n_tokens(16)
+            .train_vec_path(tmp_dir.path().join("tokens.vec"))
+            .disk_path(tmp_dir.path().join("cdawg"))
+            .build_from_reader(reader)
+            .unwrap();
+        assert_eq!(cdawg.get_ngram_count(&tokenizer.tokenize("b c")), 2);
+        assert_eq!(cdawg.get_ngram_count(&tokenizer.tokenize("d")), 1);
+    }
+
+    // Sends (n_docs, n_tokens) back to the test after each document.
+    struct DocObserver(Sender<(usize, usize)>);
+
+    impl BuildObserver for DocObserver {
+        fn on_document(&mut self, n_docs: usize, n_tokens: usize) -> Result<()> {
+            Ok(self.0.send((n_docs, n_tokens))?)
+        }
+    }
+
+    #[test]
+    fn test_cdawg_builder_observer() {
+        let (tx, rx) = channel();
+        let reader = get_reader(&["a b c", "b c d"]);
+        CdawgBuilder::<u16>::new()
+            .observer(Box::new(DocObserver(tx)))
+            .build_from_reader(reader)
+            .unwrap();
+        // Each document ends with an end-of-document token.
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [(1, 4), (2, 8)]);
+    }
+}
@@ -0,0 +1,151 @@
+// A smoothed n-gram language model read directly off a CDAWG's counts.
+//
+// Probabilities use interpolated absolute discounting, where the history is the suffix matched by
+// a CdawgState and the backoff chain is its chain of failures (as taken by `implicitly_fail`):
+//
+//   P(w | h) = max(c(hw) - D, 0) / c(h) + D * N(h) / c(h) * P(w | h')
+//
+// where N(h) is the number of distinct tokens following h and h' is the longest suffix of h in a
+// different state. Strings in the same state share their counts and continuations, so intermediate
+// suffixes would give the same terms. Below the source, P is uniform over the vocabulary.
+//
+// This isn't Kneser-Ney: that would replace the counts at lower orders with continuation counts,
+// the number of distinct tokens preceding a string, which aren't stored in the graph.
+// End-of-document tokens count as continuations, but can't be predicted.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::cdawg_state::CdawgState;
+use crate::cdawg::inenaga::Cdawg;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::graph::indexing::IndexType;
+use crate::graph::EdgeRef;
+use crate::memory_backing::MemoryBacking;
+use crate::weight::Weight;
+
+pub struct AbsoluteDiscounting<'a, W, Ix, Mb, T>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+{
+    cdawg: &'a Cdawg<W, Ix, Mb, T>,
+    discount: f64,
+    vocab_size: usize,
+}
+
+impl<'a, W, Ix, Mb, T> AbsoluteDiscounting<'a, W, Ix, Mb, T>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    /// Wrap `cdawg`, whose counts must be filled. `discount` must be in (0, 1].
+    pub fn new(cdawg: &'a Cdawg<W, Ix, Mb, T>, discount: f64, vocab_size: usize) -> Result<Self> {
+        if !(discount > 0. && discount <= 1.) {
+            bail!("Discount must be in (0, 1], got {}", discount);
+        }
+        if vocab_size == 0 {
+            bail!("Vocabulary size must be positive");
+        }
+        Ok(Self {
+            cdawg,
+            discount,
+            vocab_size,
+        })
+    }
+
+    /// Probability that `token` follows the suffix matched by `cs`.
+    pub fn get_probability(&self, cs: CdawgState<Ix>, token: T) -> f64 {
+        let mut cs = cs;
+        let mut prob = 0.;
+        let mut weight = 1.; // Mass left for the backoff distribution.
+        while let Some(target) = cs.target {
+            let count = self.cdawg.get_count(target) as f64;
+            let (next_count, n_types, backoff) = if cs.start == cs.end {
+                let next_count = match self.cdawg.get_edge_by_token(target, token) {
+                    Some(edge_idx) => {
+                        let edge = self.cdawg.get_graph().get_edge(edge_idx);
+                        self.cdawg.get_count(edge.get_target())
+                    }
+                    None => 0,
+                };
+                let n_types = self.cdawg.get_graph().n_edges(target);
+                let backoff = self.cdawg.implicitly_fail(target, (cs.end, cs.end));
+                (next_count, n_types, backoff)
+            } else {
+                // In the middle of an edge, the only continuation is the next token on it.
+                let next_count = if self.cdawg.get_token(cs.start) == token {
+                    self.cdawg.get_count(target)
+                } else {
+                    0
+                };
+                let backoff = self
+                    .cdawg
+                    .implicitly_fail(cs.state, (cs.edge_start, cs.start));
+                (next_count, 1, backoff)
+            };
+
+            // Counts may be missing for a state that is still being built.
+            if count > 0. {
+                prob += weight * f64::max(next_count as f64 - self.discount, 0.) / count;
+                weight *= self.discount * n_types as f64 / count;
+            }
+            cs = backoff;
+        }
+        prob + weight / self.vocab_size as f64
+    }
+
+    /// Natural log of `get_probability`.
+    pub fn get_log_probability(&self, cs: CdawgState<Ix>, token: T) -> f64 {
+        self.get_probability(cs, token).ln()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_absolute_discounting() {
+        let (a, b, c, d) = (0, 1, 2, 3);
+        let tokens = vec![a, b, c, a, b, d, b, c, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        let lm = AbsoluteDiscounting::new(&cdawg, 0.5, 4).unwrap();
+
+        // "a" only occurs before "b", so it interpolates with P(b) using half a count of mass.
+        let initial = cdawg.get_initial();
+        let cs = cdawg.transition_and_count(initial, a);
+        let expected = 1.5 / 2. + 0.5 / 2. * lm.get_probability(initial, b);
+        assert!((lm.get_probability(cs, b) - expected).abs() < 1e-9);
+
+        // Each distribution is proper, up to the mass reserved for end-of-document tokens.
+        for history in [vec![], vec![a], vec![a, b], vec![b, c], vec![d, d]] {
+            let mut cs = cdawg.get_initial();
+            for token in history {
+                cs = cdawg.transition_and_count(cs, token);
+            }
+            let probs: Vec<_> = [a, b, c, d]
+                .iter()
+                .map(|token| lm.get_probability(cs, *token))
+                .collect();
+            assert!(probs.iter().all(|prob| *prob > 0.));
+            assert!(probs.iter().sum::<f64>() <= 1. + 1e-9);
+        }
+
+        // Seen continuations are more likely than unseen ones.
+        let cs = cdawg.transition_and_count(cdawg.transition_and_count(initial, a), b);
+        assert!(lm.get_probability(cs, c) > lm.get_probability(cs, a));
+        assert!(AbsoluteDiscounting::new(&cdawg, 1.5, 4).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+// A read-only CDAWG on disk whose queries are async, built with the `async` feature.
+//
+// `Cdawg` and `FrozenCdawg` block on every read, which stalls an async server whenever the index is
+// on disk. `AsyncCdawg` reads the nodes, edges and tokens of a saved CDAWG through `AsyncDiskVec`s,
+// so a query awaits its reads instead. It is `Send + Sync`, and cloning it is cheap.
+//
+// Queries follow the ones on `FrozenCdawg` exactly, and return the same `CdawgState`s.
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use std::cmp::Ordering;
+use std::path::Path;
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::cdawg_state::CdawgState;
+use crate::cdawg::metadata::CdawgMetadata;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::graph::avl_graph::edge::Edge;
+use crate::graph::avl_graph::node::Node;
+use crate::graph::indexing::{DefaultIx, EdgeIndex, IndexType, NodeIndex};
+use crate::memory_backing::AsyncDiskVec;
+use crate::weight::{DefaultWeight, Weight};
+
+pub struct AsyncCdawg<W = DefaultWeight, Ix: IndexType = DefaultIx, T = u16> {
+    nodes: AsyncDiskVec<Node<W, Ix>>,
+    edges: AsyncDiskVec<Edge<CdawgEdgeWeight<Ix>, Ix>>,
+    tokens: AsyncDiskVec<T>,
+    source: NodeIndex<Ix>,
+    end_position: usize,
+}
+
+// Derived Clone would require W, Ix and T to be Clone.
+impl<W, Ix, T> Clone for AsyncCdawg<W, Ix, T>
+where
+    Ix: IndexType,
+{
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            edges: self.edges.clone(),
+            tokens: self.tokens.clone(),
+            source: self.source,
+            end_position: self.end_position,
+        }
+    }
+}
+
+impl<W, Ix, T> AsyncCdawg<W, Ix, T>
+where
+    Ix: IndexType + DeserializeOwned + Send + Sync + 'static,
+    W: Weight + DeserializeOwned + Send + Sync + 'static,
+    T: CdawgToken + Send + Sync,
+{
+    /// Open the disk CDAWG saved at `path`, whose counts should be filled, and the token DiskVec at
+    /// `tokens_path` that it was built on.
+    pub fn load<P, Q>(tokens_path: P, path: Q) -> Result<Self>
+    where
+        P: AsRef<Path> + std::fmt::Debug,
+        Q: AsRef<Path>,
+    {
+        let config = CdawgMetadata::load_json(path.as_ref().join("metadata.json"))?;
+        config.validate_utype::<T>()?;
+        config.validate_index_width::<Ix>()?;
+        config.validate_weight::<W>()?;
+        Ok(Self {
+            nodes: AsyncDiskVec::load(path.as_ref().join("nodes.vec"))?,
+            edges: AsyncDiskVec::load(path.as_ref().join("edges.vec"))?,
+            tokens: AsyncDiskVec::load(tokens_path)?,
+            source: NodeIndex::new(config.source),
+            end_position: config.end_position,
+        })
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn get_source(&self) -> NodeIndex<Ix> {
+        self.source
+    }
+
+    pub async fn get_count(&self, state: NodeIndex<Ix>) -> Result<usize> {
+        Ok(self.nodes.get(state.index()).await?.weight.get_count())
+    }
+
+    pub fn get_initial(&self) -> CdawgState<Ix> {
+        CdawgState {
+            state: self.source,
+            edge_start: 0,
+            start: 0,
+            end: 0,
+            target: Some(self.source),
+            length: 0,
+        }
+    }
+
+    /// Same as `FrozenCdawg::transition_and_count`.
+    pub async fn transition_and_count(
+        &self,
+        mut cs: CdawgState<Ix>,
+        token: T,
+    ) -> Result<CdawgState<Ix>> {
+        // Failing and retrying is a loop here, since async fns can't recurse without boxing.
+        loop {
+            let Some(state) = cs.target else {
+                return Ok(self.get_initial());
+            };
+            if cs.start == cs.end {
+                if let Some(edge) = self.get_edge_by_token(state, token).await? {
+                    let (start, end) = self.get_span(edge.weight, edge.target).await?;
+                    return Ok(CdawgState {
+                        state,
+                        edge_start: start - 1,
+                        start,
+                        end,
+                        target: Some(edge.target),
+                        length: cs.length + 1,
+                    });
+                }
+                cs = self.implicitly_fail(state, (cs.end, cs.end)).await?;
+            } else if token == self.tokens.get(cs.start).await? {
+                cs.start += 1;
+                cs.length += 1;
+                return Ok(cs);
+            } else {
+                cs = self
+                    .implicitly_fail(cs.state, (cs.edge_start, cs.start))
+                    .await?;
+            }
+        }
+    }
+
+    pub async fn get_suffix_count(&self, cs: CdawgState<Ix>) -> Result<usize> {
+        self.get_count(cs.target.unwrap()).await
+    }
+
+    /// Same as `FrozenCdawg::get_suffix_length_profile`.
+    pub async fn get_suffix_length_profile(&self, query: &[T]) -> Result<Vec<u64>> {
+        let mut cs = self.get_initial();
+        let mut lengths = Vec::with_capacity(query.len());
+        for token in query.iter() {
+            cs = self.transition_and_count(cs, *token).await?;
+            lengths.push(cs.length);
+        }
+        Ok(lengths)
+    }
+
+    /// Count the occurrences of `ngram` in the corpus by exact match, or 0 if it doesn't occur.
+    pub async fn get_ngram_count(&self, ngram: &[T]) -> Result<usize> {
+        let mut cs = self.get_initial();
+        for token in ngram.iter() {
+            let old_length = cs.length;
+            cs = self.transition_and_count(cs, *token).await?;
+            if cs.length != old_length + 1 {
+                return Ok(0);
+            }
+        }
+        self.get_suffix_count(cs).await
+    }
+
+    /// Same as `FrozenCdawg::implicitly_fail`. `gamma` is 0-indexed.
+    pub async fn implicitly_fail(
+        &self,
+        state: NodeIndex<Ix>,
+        gamma: (usize, usize),
+    ) -> Result<CdawgState<Ix>> {
+        let (start, end) = gamma;
+        let fstate = self
+            .nodes
+            .get(state.index())
+            .await?
+            .weight
+            .get_failure()
+            .map(|q| NodeIndex::new(q.index()));
+        let (opt_state, new_start, opt_target, found_start, found_end) =
+            self.inference_canonize(fstate, (start + 1, end)).await?;
+        let (new_start, found_start) = (new_start - 1, found_start - 1);
+        Ok(match opt_state {
+            Some(q) if new_start == end => CdawgState {
+                state: q,
+                edge_start: found_start,
+                start: found_end,
+                end: found_end,
+                target: opt_state,
+                length: self.nodes.get(q.index()).await?.weight.get_length(),
+            },
+            Some(q) => {
+                let progress = end - new_start;
+                CdawgState {
+                    state: q,
+                    edge_start: found_start,
+                    start: found_start + progress,
+                    end: found_end,
+                    target: opt_target,
+                    length: self.nodes.get(q.index()).await?.weight.get_length() + progress as u64,
+                }
+            }
+            None => CdawgState {
+                state: self.source,
+                edge_start: 0,
+                start: 0,
+                end: 0,
+                target: None,
+                length: 0,
+            },
+        })
+    }
+
+    // Same as `FrozenCdawg::inference_canonize`. `gamma` is 1-indexed.
+    #[allow(clippy::type_complexity)]
+    async fn inference_canonize(
+        &self,
+        mut state: Option<NodeIndex<Ix>>,
+        gamma: (usize, usize),
+    ) -> Result<(
+        Option<NodeIndex<Ix>>,
+        usize,
+        Option<NodeIndex<Ix>>,
+        usize,
+        usize,
+    )> {
+        let (mut start, end) = gamma;
+        if start > end {
+            return Ok((state, start, state, start, end));
+        }
+
+        let (mut found_start, mut found_end, mut found_state) = match state {
+            Some(q) => {
+                let token = self.tokens.get(start - 1).await?;
+                self.get_start_end_target(q, token).await?
+            }
+            None => (1, 1, self.source),
+        };
+        while found_end + start <= end + found_start {
+            start += found_end + 1 - found_start;
+            state = Some(found_state);
+            if start <= end {
+                let token = self.tokens.get(start - 1).await?;
+                (found_start, found_end, found_state) =
+                    self.get_start_end_target(found_state, token).await?;
+            }
+        }
+        Ok((state, start, Some(found_state), found_start, found_end))
+    }
+
+    // The 1-indexed span and target of the edge out of `state` starting with `token`, which must
+    // exist.
+    async fn get_start_end_target(
+        &self,
+        state: NodeIndex<Ix>,
+        token: T,
+    ) -> Result<(usize, usize, NodeIndex<Ix>)> {
+        let edge = self.get_edge_by_token(state, token).await?.unwrap();
+        let (start, end) = self.get_span(edge.weight, edge.target).await?;
+        Ok((start, end, edge.target))
+    }
+
+    // Search the AVL tree of edges out of `state`, like `FrozenCdawg::get_edge_by_token`.
+    async fn get_edge_by_token(
+        &self,
+        state: NodeIndex<Ix>,
+        token: T,
+    ) -> Result<Option<Edge<CdawgEdgeWeight<Ix>, Ix>>> {
+        if token == T::end() {
+            return Ok(None);
+        }
+        let mut edge_idx = self.nodes.get(state.index()).await?.first_edge;
+        while edge_idx != EdgeIndex::end() {
+            let edge = self.edges.get(edge_idx.index()).await?;
+            let edge_token = self.tokens.get(edge.weight.start.index()).await?;
+            edge_idx = match token.cmp(&edge_token) {
+                Ordering::Equal => return Ok(Some(edge)),
+                Ordering::Less => edge.left,
+                Ordering::Greater => edge.right,
+            };
+        }
+        Ok(None)
+    }
+
+    // Same as `FrozenCdawg::get_span`: the 1-indexed span of an edge, resolving open ends.
+    async fn get_span(
+        &self,
+        weight: CdawgEdgeWeight<Ix>,
+        target: NodeIndex<Ix>,
+    ) -> Result<(usize, usize)> {
+        let (start, end) = weight.get_span();
+        if end < Ix::max_value().index() {
+            return Ok((start + 1, end));
+        }
+        let edge_idx = self.nodes.get(target.index()).await?.first_edge;
+        if edge_idx == EdgeIndex::end() {
+            Ok((start + 1, self.end_position))
+        } else {
+            let (e, _) = self.edges.get(edge_idx.index()).await?.weight.get_span();
+            Ok((start + 1, e + 1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::{Cdawg, TopologicalCounter};
+    use crate::memory_backing::DiskVec;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_async_matches_cdawg() {
+        let (a, b, c, d) = (0, 1, 2, 3);
+        let tokens = vec![a, b, c, a, b, u16::MAX, b, c, d, a, b, c, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("cdawg");
+        let tokens_path = tmp_dir.path().join("tokens.vec");
+        cdawg.get_graph().save_to_disk(&path).unwrap();
+        cdawg.save_metadata(&path).unwrap();
+        DiskVec::from_vec(&tokens, &tokens_path).unwrap();
+        let async_cdawg: AsyncCdawg = AsyncCdawg::load(&tokens_path, &path).unwrap();
+        assert_eq!(async_cdawg.node_count(), cdawg.node_count());
+
+        let query = [b, c, a, b, d, a, b, c, c, d];
+        assert_eq!(
+            async_cdawg.get_suffix_length_profile(&query).await.unwrap(),
+            cdawg.get_suffix_length_profile(&query)
+        );
+        let (mut cs, mut async_cs) = (cdawg.get_initial(), async_cdawg.get_initial());
+        for token in query {
+            cs = cdawg.transition_and_count(cs, token);
+            async_cs = async_cdawg
+                .transition_and_count(async_cs, token)
+                .await
+                .unwrap();
+            assert_eq!(async_cs.length, cs.length);
+            assert_eq!(async_cs.target, cs.target);
+            assert_eq!(
+                async_cdawg.get_suffix_count(async_cs).await.unwrap(),
+                cdawg.get_suffix_count(cs)
+            );
+        }
+        assert_eq!(async_cdawg.get_ngram_count(&[a, b]).await.unwrap(), 3);
+        assert_eq!(async_cdawg.get_ngram_count(&[d, d]).await.unwrap(), 0);
+    }
+}
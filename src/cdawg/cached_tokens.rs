@@ -0,0 +1,146 @@
+// Block cache for reading a token backing, e.g. the token DiskVec while building a CDAWG.
+//
+// Span comparisons during a build read tokens at scattered positions, but usually read several
+// nearby tokens at once, so tokens are cached in fixed-size blocks evicted in LRU order. The cache
+// is separate from the node and edge caches and keeps its own hit and miss counts.
+
+use anyhow::{bail, Result};
+use lru::LruCache;
+use std::cell::{Cell, RefCell};
+use std::num::NonZeroUsize;
+
+use crate::cdawg::token_backing::TokenBacking;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TokenCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl TokenCacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.;
+        }
+        self.hits as f64 / total as f64
+    }
+}
+
+pub struct CachedTokens<B, T = u16> {
+    inner: B,
+    block_size: usize,
+    blocks: RefCell<LruCache<usize, Vec<T>>>,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+}
+
+impl<B, T> CachedTokens<B, T>
+where
+    B: TokenBacking<T>,
+    T: Copy,
+{
+    /// Cache up to `cache_size` tokens of `inner`, in blocks of `block_size` tokens.
+    pub fn new(inner: B, block_size: usize, cache_size: usize) -> Result<Self> {
+        let n_blocks = cache_size / usize::max(block_size, 1);
+        if block_size == 0 || n_blocks == 0 {
+            bail!(
+                "Token cache of {} tokens can't hold a block of {} tokens",
+                cache_size,
+                block_size
+            );
+        }
+        Ok(Self {
+            inner,
+            block_size,
+            blocks: RefCell::new(LruCache::new(NonZeroUsize::new(n_blocks).unwrap())),
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        })
+    }
+
+    pub fn get_stats(&self) -> TokenCacheStats {
+        TokenCacheStats {
+            hits: self.hits.get(),
+            misses: self.misses.get(),
+        }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn read_block(&self, block: usize) -> Vec<T> {
+        let start = block * self.block_size;
+        let end = usize::min(start + self.block_size, self.inner.len());
+        (start..end).map(|idx| self.inner.get(idx)).collect()
+    }
+}
+
+impl<B, T> TokenBacking<T> for CachedTokens<B, T>
+where
+    B: TokenBacking<T>,
+    T: Copy,
+{
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn get(&self, index: usize) -> T {
+        let (block, offset) = (index / self.block_size, index % self.block_size);
+        let mut blocks = self.blocks.borrow_mut();
+        if let Some(tokens) = blocks.get(&block) {
+            self.hits.set(self.hits.get() + 1);
+            return tokens[offset];
+        }
+        self.misses.set(self.misses.get() + 1);
+        let tokens = self.read_block(block);
+        let token = tokens[offset];
+        blocks.put(block, tokens);
+        token
+    }
+
+    fn push(&mut self, value: T) {
+        let index = self.inner.len();
+        self.inner.push(value);
+        // The last block may be cached while partially filled, so keep it up to date.
+        if let Some(tokens) = self.blocks.get_mut().peek_mut(&(index / self.block_size)) {
+            tokens.push(value);
+        }
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_push() {
+        let mut tokens = CachedTokens::new(vec![0_u16, 1, 2, 3, 4], 2, 4).unwrap();
+        assert_eq!(tokens.get(4), 4);
+        assert_eq!(tokens.get(1), 1);
+        assert_eq!(tokens.get(0), 0);
+        assert_eq!(tokens.get_stats(), TokenCacheStats { hits: 1, misses: 2 });
+
+        // Block 2 is cached with one token, and pushing fills it.
+        tokens.push(5);
+        assert_eq!(tokens.get(5), 5);
+        assert_eq!(tokens.get_stats().hits, 2);
+
+        // Reading blocks 1 and 0 evicts block 2.
+        assert_eq!(tokens.get(3), 3);
+        assert_eq!(tokens.get(0), 0);
+        assert_eq!(tokens.get(5), 5);
+        assert_eq!(tokens.get_stats(), TokenCacheStats { hits: 2, misses: 5 });
+        assert_eq!(tokens.into_inner(), vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_too_small() {
+        assert!(CachedTokens::new(Vec::<u16>::new(), 8, 4).is_err());
+    }
+}
@@ -0,0 +1,15 @@
+// An edge of a CDAWG with its token span decoded, for inspecting the graph without dealing in the
+// 1-indexed, open-ended spans stored in edge weights.
+
+use crate::graph::indexing::{IndexType, NodeIndex};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CdawgEdge<Ix, T>
+where
+    Ix: IndexType,
+{
+    pub first_token: T,        // Token that the edge is looked up by.
+    pub span_len: usize,       // Number of tokens on the edge.
+    pub target: NodeIndex<Ix>, // State the edge leads to.
+    pub tokens: Vec<T>,        // All tokens on the edge, starting with `first_token`.
+}
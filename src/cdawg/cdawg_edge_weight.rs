@@ -4,7 +4,8 @@ use crate::graph::indexing::{DefaultIx, IndexType};
 
 // TODO: Can simply remove this type and use (Ix, Ix)
 
-#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize)]
+// Ordered by span, so that graphs can be compared structurally.
+#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CdawgEdgeWeight<Ix: IndexType = DefaultIx> {
     #[serde(bound(serialize = "Ix: Serialize", deserialize = "Ix: Deserialize<'de>",))]
     pub start: Ix,
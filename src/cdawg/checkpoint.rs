@@ -0,0 +1,245 @@
+// Checkpoints of a CDAWG being built on disk, so that a long build can be restarted after a crash.
+//
+// Building modifies existing nodes and edges as well as appending new ones, so the graph files can't
+// just be truncated back to where they were. Instead, the header and used prefix of `nodes.vec` and
+// `edges.vec` are copied into `<path>/checkpoint/` along with the metadata, and copied back over the
+// live files to restore it. Tokens are only ever appended, so they aren't copied: resuming just
+// ignores any tokens past the checkpoint's end position.
+//
+// Checkpoints are only taken at the end of a document, where the active point is always the source
+// with an empty span, so the metadata is enough to continue the build.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::cdawg::Cdawg;
+use crate::graph::avl_graph::edge::Edge;
+use crate::graph::avl_graph::node::Node;
+use crate::graph::indexing::IndexType;
+use crate::memory_backing::vec_backing::format::HEADER_LEN;
+use crate::memory_backing::{DiskBacking, MemoryBacking};
+use crate::weight::Weight;
+
+const CHECKPOINT_DIR: &str = "checkpoint";
+const OLD_CHECKPOINT_DIR: &str = "checkpoint.old";
+const TMP_CHECKPOINT_DIR: &str = "checkpoint.tmp";
+
+/// How far through the training data a checkpoint was taken.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct BuildProgress {
+    pub train_path: String,
+    pub n_docs: usize, // Documents read from `train_path`.
+}
+
+/// Checkpoint `cdawg`, which is being built on disk at `path`. Must be called at the end of a
+/// document.
+pub fn save_checkpoint<W, Ix, Mb, T, P>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    path: P,
+    progress: &BuildProgress,
+) -> Result<()>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    Mb::EdgeRef: Copy,
+    T: CdawgToken,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    cdawg.flush()?;
+    let tmp_dir = path.join(TMP_CHECKPOINT_DIR);
+    if tmp_dir.exists() {
+        fs::remove_dir_all(&tmp_dir)?;
+    }
+    fs::create_dir(&tmp_dir)?;
+
+    let mb: DiskBacking<W, CdawgEdgeWeight<Ix>, Ix> = DiskBacking::new(path);
+    let nodes_path = mb.get_nodes_path();
+    let edges_path = mb.get_edges_path();
+    copy_prefix(
+        &nodes_path,
+        &tmp_dir.join(file_name(&nodes_path)),
+        HEADER_LEN + cdawg.node_count() * size_of::<Node<W, Ix>>(),
+    )?;
+    copy_prefix(
+        &edges_path,
+        &tmp_dir.join(file_name(&edges_path)),
+        HEADER_LEN + cdawg.edge_count() * size_of::<Edge<CdawgEdgeWeight<Ix>, Ix>>(),
+    )?;
+    cdawg.save_metadata(&tmp_dir)?;
+    fs::write(
+        tmp_dir.join("progress.json"),
+        serde_json::to_string(progress)?,
+    )?;
+
+    // Keep the previous checkpoint until the new one is in place.
+    let dir = path.join(CHECKPOINT_DIR);
+    let old_dir = path.join(OLD_CHECKPOINT_DIR);
+    if dir.exists() {
+        if old_dir.exists() {
+            fs::remove_dir_all(&old_dir)?;
+        }
+        fs::rename(&dir, &old_dir)?;
+    }
+    fs::rename(&tmp_dir, &dir)?;
+    if old_dir.exists() {
+        fs::remove_dir_all(&old_dir)?;
+    }
+    Ok(())
+}
+
+/// Roll the CDAWG being built on disk at `path` back to its last checkpoint, so that it can be
+/// reopened with `Cdawg::resume_from_disk`.
+pub fn restore_checkpoint<W, Ix, P>(path: P) -> Result<BuildProgress>
+where
+    Ix: IndexType,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    // A checkpoint is only renamed to `checkpoint.old` while a newer one is being moved into place.
+    let Some(dir) = [CHECKPOINT_DIR, OLD_CHECKPOINT_DIR]
+        .into_iter()
+        .map(|name| path.join(name))
+        .find(|dir| dir.join("progress.json").is_file())
+    else {
+        bail!("No checkpoint found in {:?}", path);
+    };
+
+    // Overwrite the live files in place, so that the capacity allocated for the build is kept.
+    let mb: DiskBacking<W, CdawgEdgeWeight<Ix>, Ix> = DiskBacking::new(path);
+    for live_path in [mb.get_nodes_path(), mb.get_edges_path()] {
+        let mut src = File::open(dir.join(file_name(&live_path)))?;
+        let mut dst = File::options().write(true).open(&live_path)?;
+        io::copy(&mut src, &mut dst)?;
+        dst.sync_all()?;
+    }
+    fs::copy(dir.join("metadata.json"), path.join("metadata.json"))?;
+    Ok(serde_json::from_str(&fs::read_to_string(
+        dir.join("progress.json"),
+    )?)?)
+}
+
+fn copy_prefix(src_path: &Path, dst_path: &Path, n_bytes: usize) -> Result<()> {
+    let src = File::open(src_path)?;
+    let mut dst = File::create(dst_path)?;
+    let n_copied = io::copy(&mut io::Read::take(src, n_bytes as u64), &mut dst)?;
+    if n_copied != n_bytes as u64 {
+        bail!(
+            "{:?} holds {} bytes, but {} were expected",
+            src_path,
+            n_copied,
+            n_bytes
+        );
+    }
+    dst.sync_all()?;
+    Ok(())
+}
+
+fn file_name(path: &Path) -> PathBuf {
+    PathBuf::from(path.file_name().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::token_backing::TokenBacking;
+    use crate::cdawg::TopologicalCounter;
+    use crate::graph::indexing::{DefaultIx, NodeIndex};
+    use crate::memory_backing::{CacheConfig, DiskVec};
+    use crate::weight::DefaultWeight;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use tempfile::tempdir;
+
+    type DiskCdawg = Cdawg<
+        DefaultWeight,
+        DefaultIx,
+        DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>,
+    >;
+
+    fn add_tokens(
+        cdawg: &mut DiskCdawg,
+        tokens: &Rc<RefCell<dyn TokenBacking<u16>>>,
+        new_tokens: &[u16],
+    ) {
+        let (mut state, mut start) = cdawg.get_resume_point();
+        let mut idx = tokens.borrow().len();
+        for token in new_tokens.iter() {
+            idx += 1;
+            tokens.borrow_mut().push(*token);
+            (state, start) = cdawg.update(state, start, idx);
+            if *token == u16::MAX {
+                (state, start) = cdawg.end_document(idx, idx);
+            }
+        }
+    }
+
+    #[test]
+    fn test_restore_checkpoint() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        let tokens1 = vec![c, o, c, o, a, u16::MAX];
+        let tokens2 = vec![c, o, l, a, u16::MAX];
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("cdawg");
+        let tokens_path = tmp_dir.path().join("tokens.vec");
+
+        let disk_vec = DiskVec::<u16>::new(&tokens_path, 32).unwrap();
+        let tokens: Rc<RefCell<dyn TokenBacking<u16>>> = Rc::new(RefCell::new(disk_vec));
+        let mb = DiskBacking::new(&path);
+        let mut cdawg: DiskCdawg =
+            Cdawg::with_capacity_mb(tokens.clone(), mb, 32, 32, CacheConfig::none());
+        add_tokens(&mut cdawg, &tokens, &tokens1);
+        let progress = BuildProgress {
+            train_path: "train.txt".to_string(),
+            n_docs: 1,
+        };
+        save_checkpoint(&cdawg, &path, &progress).unwrap();
+        let n_nodes = cdawg.node_count();
+
+        // Keep building past the checkpoint, then crash.
+        add_tokens(&mut cdawg, &tokens, &tokens2);
+        assert!(cdawg.node_count() > n_nodes);
+        drop(cdawg);
+        drop(tokens);
+
+        assert_eq!(
+            restore_checkpoint::<DefaultWeight, DefaultIx, _>(&path).unwrap(),
+            progress
+        );
+        let disk_vec = DiskVec::<u16>::open_mut(&tokens_path, tokens1.len()).unwrap();
+        let tokens: Rc<RefCell<dyn TokenBacking<u16>>> = Rc::new(RefCell::new(disk_vec));
+        let mut resumed: DiskCdawg =
+            Cdawg::resume_from_disk(tokens.clone(), &path, CacheConfig::none()).unwrap();
+        assert_eq!(resumed.node_count(), n_nodes);
+        assert_eq!(
+            resumed.get_resume_point(),
+            (NodeIndex::new(0), tokens1.len() + 1)
+        );
+        add_tokens(&mut resumed, &tokens, &tokens2);
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut resumed);
+
+        let all_tokens: Vec<u16> = tokens1.iter().chain(tokens2.iter()).copied().collect();
+        let mut full: Cdawg = Cdawg::new(Rc::new(RefCell::new(all_tokens)));
+        full.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut full);
+        assert_eq!(resumed.node_count(), full.node_count());
+        for ngram in [&[c][..], &[c, o], &[o, c, o], &[l, a], &[a], &[o, l, a]] {
+            assert_eq!(resumed.get_ngram_count(ngram), full.get_ngram_count(ngram));
+        }
+    }
+
+    #[test]
+    fn test_restore_missing_checkpoint() {
+        let tmp_dir = tempdir().unwrap();
+        assert!(restore_checkpoint::<DefaultWeight, DefaultIx, _>(tmp_dir.path()).is_err());
+    }
+}
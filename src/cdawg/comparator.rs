@@ -6,25 +6,26 @@ use std::cmp::Ordering;
 use std::rc::Rc;
 
 use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
-use crate::cdawg::token_backing::TokenBacking;
+use crate::cdawg::token_backing::{CdawgToken, TokenBacking};
 use crate::graph::indexing::IndexType;
 
-const END: u16 = u16::MAX;
-
-pub struct CdawgComparator {
-    tokens: Rc<RefCell<dyn TokenBacking<u16>>>,
-    token1: Option<u16>, // If token is provided, it is assumed to be the token for e1.
+pub struct CdawgComparator<T = u16> {
+    tokens: Rc<RefCell<dyn TokenBacking<T>>>,
+    token1: Option<T>, // If token is provided, it is assumed to be the token for e1.
 }
 
-impl CdawgComparator {
-    pub fn new(tokens: Rc<RefCell<dyn TokenBacking<u16>>>) -> Self {
+impl<T> CdawgComparator<T>
+where
+    T: CdawgToken,
+{
+    pub fn new(tokens: Rc<RefCell<dyn TokenBacking<T>>>) -> Self {
         Self {
             tokens,
             token1: None,
         }
     }
 
-    pub fn new_with_token(tokens: Rc<RefCell<dyn TokenBacking<u16>>>, token: u16) -> Self {
+    pub fn new_with_token(tokens: Rc<RefCell<dyn TokenBacking<T>>>, token: T) -> Self {
         Self {
             tokens,
             token1: Some(token),
@@ -32,9 +33,10 @@ impl CdawgComparator {
     }
 }
 
-impl<Ix> Comparator<CdawgEdgeWeight<Ix>> for CdawgComparator
+impl<Ix, T> Comparator<CdawgEdgeWeight<Ix>> for CdawgComparator<T>
 where
     Ix: IndexType,
+    T: CdawgToken,
 {
     fn compare(&self, e1: &CdawgEdgeWeight<Ix>, e2: &CdawgEdgeWeight<Ix>) -> Ordering {
         let token1 = match self.token1 {
@@ -43,7 +45,7 @@ where
         };
         let token2 = self.tokens.borrow().get(e2.start.index());
 
-        if token1 == END && token2 == END {
+        if token1 == T::end() && token2 == T::end() {
             // The start index of an open node represents doc_id
             e1.start.cmp(&e2.start)
         } else if token1 == token2 {
@@ -63,12 +65,14 @@ mod tests {
     use super::*;
     use crate::graph::indexing::DefaultIx;
 
+    const END: u16 = u16::MAX;
+
     type E = CdawgEdgeWeight<DefaultIx>;
 
     #[test]
     fn test_compare_no_token() {
         let tokens = Rc::new(RefCell::new(vec![2, 1, 0, 1, 2, END, END]));
-        let cmp = CdawgComparator::new(tokens);
+        let cmp: CdawgComparator = CdawgComparator::new(tokens);
 
         assert_eq!(cmp.compare(&E::new(0, 5), &E::new(4, 5)), Ordering::Equal);
         assert_eq!(cmp.compare(&E::new(0, 5), &E::new(1, 5)), Ordering::Greater);
@@ -83,7 +87,7 @@ mod tests {
     #[test]
     fn test_compare_token() {
         let tokens = Rc::new(RefCell::new(vec![2, 1, 0, 1, 2]));
-        let cmp = CdawgComparator::new_with_token(tokens, 1);
+        let cmp: CdawgComparator = CdawgComparator::new_with_token(tokens, 1);
 
         assert_eq!(cmp.compare(&E::new(0, 5), &E::new(0, 5)), Ordering::Less);
         assert_eq!(cmp.compare(&E::new(0, 5), &E::new(1, 5)), Ordering::Equal);
@@ -93,7 +97,7 @@ mod tests {
     #[test]
     fn test_compare_end() {
         let tokens = Rc::new(RefCell::new(vec![2, 1, END, 1, END]));
-        let cmp = CdawgComparator::new_with_token(tokens, END);
+        let cmp: CdawgComparator = CdawgComparator::new_with_token(tokens, END);
 
         assert_eq!(cmp.compare(&E::new(2, 3), &E::new(4, 5)), Ordering::Less);
         assert_eq!(cmp.compare(&E::new(4, 5), &E::new(4, 5)), Ordering::Equal);
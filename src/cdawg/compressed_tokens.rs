@@ -0,0 +1,249 @@
+// Token backing stored on disk in compressed frames, for corpora whose token vector is too large to
+// keep uncompressed.
+//
+// Tokens are grouped into fixed-size frames, each deflated on its own and appended to the data file
+// at `path`. The byte offset of each frame, along with the tokens of the last, partially filled
+// frame, are kept in `<path>.index` and rewritten on every flush. Random access decompresses the
+// frame containing a token, so decompressed frames are kept in an LRU cache, as in `CachedTokens`.
+
+use anyhow::{bail, Result};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use lru::LruCache;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use crate::cdawg::cached_tokens::TokenCacheStats;
+use crate::cdawg::token_backing::TokenBacking;
+
+#[derive(Serialize, Deserialize)]
+struct FrameIndex<T> {
+    frame_size: usize,
+    offsets: Vec<u64>, // Start of each frame in the data file, followed by its end.
+    tail: Vec<T>,      // Tokens after the last full frame, not yet compressed.
+}
+
+fn get_index_path(path: &Path) -> PathBuf {
+    let mut index_path = path.as_os_str().to_owned();
+    index_path.push(".index");
+    PathBuf::from(index_path)
+}
+
+pub struct CompressedTokens<T = u16> {
+    path: PathBuf,
+    file: RefCell<File>,
+    index: FrameIndex<T>,
+    frames: RefCell<LruCache<usize, Vec<T>>>,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+}
+
+impl<T> CompressedTokens<T>
+where
+    T: Copy + Serialize + DeserializeOwned,
+{
+    /// Create an empty backing at `path`, compressing `frame_size` tokens per frame and caching up
+    /// to `cache_frames` decompressed frames. Fails if `path` already exists.
+    pub fn new<P: AsRef<Path>>(path: P, frame_size: usize, cache_frames: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            bail!("{:?} already exists!", path);
+        }
+        if frame_size == 0 {
+            bail!("Compressed token frames must hold at least one token");
+        }
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let index = FrameIndex {
+            frame_size,
+            offsets: vec![0],
+            tail: Vec::with_capacity(frame_size),
+        };
+        let tokens = Self::with_index(path, file, index, cache_frames)?;
+        tokens.flush()?;
+        Ok(tokens)
+    }
+
+    /// Open the backing previously flushed at `path`, for reading and appending.
+    pub fn open<P: AsRef<Path>>(path: P, cache_frames: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let index_file = File::open(get_index_path(&path))?;
+        let index: FrameIndex<T> = bincode::deserialize_from(index_file)?;
+        let file = File::options().read(true).write(true).open(&path)?;
+        // Frames written after the last flush aren't in the index, so drop them.
+        file.set_len(*index.offsets.last().unwrap())?;
+        Self::with_index(path, file, index, cache_frames)
+    }
+
+    fn with_index(
+        path: PathBuf,
+        file: File,
+        index: FrameIndex<T>,
+        cache_frames: usize,
+    ) -> Result<Self> {
+        let cache_frames = match NonZeroUsize::new(cache_frames) {
+            Some(cache_frames) => cache_frames,
+            None => bail!("Compressed tokens need a cache of at least one frame"),
+        };
+        Ok(Self {
+            path,
+            file: RefCell::new(file),
+            index,
+            frames: RefCell::new(LruCache::new(cache_frames)),
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        })
+    }
+
+    pub fn get_frame_size(&self) -> usize {
+        self.index.frame_size
+    }
+
+    /// Size of the compressed frames on disk, in bytes.
+    pub fn n_compressed_bytes(&self) -> u64 {
+        *self.index.offsets.last().unwrap()
+    }
+
+    pub fn get_stats(&self) -> TokenCacheStats {
+        TokenCacheStats {
+            hits: self.hits.get(),
+            misses: self.misses.get(),
+        }
+    }
+
+    fn n_frames(&self) -> usize {
+        self.index.offsets.len() - 1
+    }
+
+    fn read_frame(&self, frame: usize) -> Result<Vec<T>> {
+        let (start, end) = (self.index.offsets[frame], self.index.offsets[frame + 1]);
+        let mut compressed = vec![0; (end - start) as usize];
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut compressed)?;
+        let mut bytes = Vec::new();
+        DeflateDecoder::new(compressed.as_slice()).read_to_end(&mut bytes)?;
+        Ok(bincode::deserialize(&bytes)?)
+    }
+
+    fn write_frame(&mut self) -> Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&bincode::serialize(&self.index.tail)?)?;
+        let compressed = encoder.finish()?;
+        let start = self.n_compressed_bytes();
+        let file = self.file.get_mut();
+        file.seek(SeekFrom::Start(start))?;
+        file.write_all(&compressed)?;
+        let end = start + compressed.len() as u64;
+        self.index.offsets.push(end);
+        self.index.tail.clear();
+        Ok(())
+    }
+}
+
+impl<T> TokenBacking<T> for CompressedTokens<T>
+where
+    T: Copy + Serialize + DeserializeOwned,
+{
+    fn len(&self) -> usize {
+        self.n_frames() * self.index.frame_size + self.index.tail.len()
+    }
+
+    fn get(&self, index: usize) -> T {
+        let frame_size = self.index.frame_size;
+        let (frame, offset) = (index / frame_size, index % frame_size);
+        if frame == self.n_frames() {
+            return self.index.tail[offset];
+        }
+        let mut frames = self.frames.borrow_mut();
+        if let Some(tokens) = frames.get(&frame) {
+            self.hits.set(self.hits.get() + 1);
+            return tokens[offset];
+        }
+        self.misses.set(self.misses.get() + 1);
+        let tokens = self.read_frame(frame).unwrap();
+        let token = tokens[offset];
+        frames.put(frame, tokens);
+        token
+    }
+
+    fn push(&mut self, value: T) {
+        self.index.tail.push(value);
+        if self.index.tail.len() == self.index.frame_size {
+            self.write_frame().unwrap();
+        }
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.file.borrow().sync_data()?;
+        let index_path = get_index_path(&self.path);
+        let mut tmp_path = index_path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        bincode::serialize_into(File::create(&tmp_path)?, &self.index)?;
+        fs::rename(&tmp_path, &index_path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_push_get_reopen() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("tokens");
+        let mut tokens: CompressedTokens<u16> = CompressedTokens::new(&path, 4, 2).unwrap();
+        for token in 0..10 {
+            tokens.push(token);
+        }
+        assert_eq!(tokens.len(), 10);
+        assert!(tokens.n_compressed_bytes() > 0);
+
+        // Frames 0 and 1 are compressed, and 8 and 9 are still in the tail.
+        assert_eq!(tokens.get(9), 9);
+        assert_eq!(tokens.get(5), 5);
+        assert_eq!(tokens.get(1), 1);
+        assert_eq!(tokens.get(2), 2);
+        assert_eq!(tokens.get_stats(), TokenCacheStats { hits: 1, misses: 2 });
+        tokens.flush().unwrap();
+        assert!(CompressedTokens::<u16>::new(&path, 4, 2).is_err());
+
+        let mut reopened: CompressedTokens<u16> = CompressedTokens::open(&path, 1).unwrap();
+        assert_eq!(reopened.get_frame_size(), 4);
+        reopened.push(10);
+        reopened.push(11);
+        let all: Vec<_> = (0..reopened.len()).map(|idx| reopened.get(idx)).collect();
+        assert_eq!(all, (0..12).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_cdawg_on_compressed_tokens() {
+        use crate::cdawg::{Cdawg, TopologicalCounter};
+        use std::rc::Rc;
+
+        let tmp_dir = tempdir().unwrap();
+        let mut tokens: CompressedTokens<u16> =
+            CompressedTokens::new(tmp_dir.path().join("tokens"), 3, 2).unwrap();
+        for token in [0, 1, 2, 0, 1, 3, 1, 2, u16::MAX] {
+            tokens.push(token);
+        }
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        assert_eq!(cdawg.get_ngram_count(&[0, 1]), 2);
+        assert_eq!(cdawg.get_ngram_count(&[1, 2]), 2);
+        assert_eq!(cdawg.get_ngram_count(&[1, 3, 1, 2]), 1);
+        assert_eq!(cdawg.get_ngram_count(&[2, 1]), 0);
+    }
+}
@@ -0,0 +1,98 @@
+// Check whether every substring of a query token stream occurs in the corpus indexed by a CDAWG.
+//
+// The query stream is matched against the CDAWG left-to-right, as in the Evaluator. A query document
+// is fully contained iff the matched suffix length grows by one at every position. Whenever it does
+// not, the suffix ending at that position that is one token longer than the match is missing.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::inenaga::Cdawg;
+use crate::cdawg::token_backing::{CdawgToken, TokenBacking};
+use crate::graph::indexing::IndexType;
+use crate::memory_backing::MemoryBacking;
+use crate::metrics;
+use crate::weight::Weight;
+
+#[derive(Debug, Default, Serialize)]
+pub struct ContainmentReport {
+    pub n_tokens: usize, // Number of query tokens checked (not counting document boundaries).
+    pub n_missing: usize, // Number of positions where some substring ending there is missing.
+    pub counterexamples: Vec<(usize, usize)>, // First missing spans, as [start, end) in the query.
+}
+
+impl ContainmentReport {
+    pub fn is_contained(&self) -> bool {
+        self.n_missing == 0
+    }
+}
+
+/// Stream `query` through `cdawg` and report (up to `max_counterexamples`) minimal missing spans.
+pub fn check_containment<W, Ix, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    query: &dyn TokenBacking<T>,
+    max_counterexamples: usize,
+) -> ContainmentReport
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    let _timer = metrics::time_query();
+    let mut report = ContainmentReport::default();
+    let mut cs = cdawg.get_initial();
+    for idx in 0..query.len() {
+        let token = query.get(idx);
+        if token == T::end() {
+            // Substrings spanning document boundaries are not meaningful, so restart matching.
+            cs = cdawg.get_initial();
+            continue;
+        }
+
+        let old_length = cs.length;
+        cs = cdawg.transition_and_count(cs, token);
+        report.n_tokens += 1;
+        if cs.length != old_length + 1 {
+            report.n_missing += 1;
+            if report.counterexamples.len() < max_counterexamples {
+                let length = cs.length as usize;
+                report.counterexamples.push((idx - length, idx + 1));
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+#[allow(unused_variables)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_check_containment_cocoa() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![c, o, c, o, a, u16::MAX])));
+        cdawg.build();
+
+        let query = vec![o, c, o, u16::MAX, c, o, a];
+        let report = check_containment(&cdawg, &query, 10);
+        assert!(report.is_contained());
+        assert_eq!(report.n_tokens, 6);
+
+        let query = vec![c, o, l, a, u16::MAX, o, a, c];
+        let report = check_containment(&cdawg, &query, 10);
+        assert!(!report.is_contained());
+        // "l" is missing, then "ac" is missing.
+        assert_eq!(report.counterexamples, vec![(2, 3), (6, 8)]);
+
+        let report = check_containment(&cdawg, &query, 1);
+        assert_eq!(report.n_missing, 2);
+        assert_eq!(report.counterexamples, vec![(2, 3)]);
+    }
+}
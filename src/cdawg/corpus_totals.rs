@@ -0,0 +1,91 @@
+// Size of the corpus a CDAWG was built on, saved in its metadata so that counts can be normalized
+// into probabilities at query time.
+//
+// Totals only cover completed documents: tokens after the last end-of-document token aren't
+// counted. End-of-document tokens themselves are counted in `n_docs`, not `n_tokens`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DomainTotals {
+    pub n_tokens: usize,
+    pub n_docs: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CorpusTotals {
+    pub n_tokens: usize,
+    pub n_docs: usize,
+    // Keyed by the domain ID assigned by the data reader, e.g. the index of `pile_set_name` for the
+    // Pile. Empty if the data reader doesn't assign domains.
+    #[serde(default)]
+    pub domains: BTreeMap<usize, DomainTotals>,
+    #[serde(default)]
+    pub deleted: DomainTotals, // Documents deleted after they were added.
+}
+
+impl CorpusTotals {
+    /// Record a document that ends at position `idx` (1-indexed, including its end-of-document
+    /// token) of the token backing.
+    pub fn end_document(&mut self, idx: usize) {
+        self.n_docs += 1;
+        let n_ended = self.n_docs + self.deleted.n_docs;
+        self.n_tokens = idx - n_ended - self.deleted.n_tokens;
+    }
+
+    /// Remove a deleted document with `n_tokens` tokens, not counting its end-of-document token.
+    pub fn delete_document(&mut self, n_tokens: usize) {
+        self.n_docs -= 1;
+        self.n_tokens -= n_tokens;
+        self.deleted.n_docs += 1;
+        self.deleted.n_tokens += n_tokens;
+    }
+
+    pub fn add_domain_document(&mut self, domain: usize, n_tokens: usize) {
+        let totals = self.domains.entry(domain).or_default();
+        totals.n_tokens += n_tokens;
+        totals.n_docs += 1;
+    }
+
+    /// Fraction of token positions at which an occurrence counted `count` times starts.
+    pub fn normalize(&self, count: usize) -> f64 {
+        count as f64 / self.n_tokens as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_end_document() {
+        // Two documents, "ab$" and "c$".
+        let mut totals = CorpusTotals::default();
+        totals.end_document(3);
+        totals.end_document(5);
+        assert_eq!(totals.n_docs, 2);
+        assert_eq!(totals.n_tokens, 3);
+        assert_eq!(totals.normalize(1), 1. / 3.);
+
+        // Deleting "ab$" leaves "c$", and the next document "de$" is counted after it.
+        totals.delete_document(2);
+        assert_eq!((totals.n_docs, totals.n_tokens), (1, 1));
+        totals.end_document(8);
+        assert_eq!((totals.n_docs, totals.n_tokens), (2, 3));
+    }
+
+    #[test]
+    fn test_serde_domains() {
+        let mut totals = CorpusTotals::default();
+        totals.add_domain_document(6, 10);
+        totals.add_domain_document(6, 5);
+        let json = serde_json::to_string(&totals).unwrap();
+        let totals2: CorpusTotals = serde_json::from_str(&json).unwrap();
+        assert_eq!(totals2.domains[&6].n_tokens, 15);
+        assert_eq!(totals2.domains[&6].n_docs, 2);
+
+        let old: CorpusTotals = serde_json::from_str(r#"{"n_tokens": 3, "n_docs": 1}"#).unwrap();
+        assert!(old.domains.is_empty());
+    }
+}
@@ -0,0 +1,59 @@
+// Which statistic a count or probability query should be answered with.
+//
+// An index is always built with occurrence counts. Other statistics are only available if the
+// index was built with them, and querying a missing one is an error rather than a silent fallback.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CountKind {
+    #[default]
+    Occurrences, // Number of times the string occurs in the corpus.
+    Documents, // Number of documents the string occurs in.
+    Weighted,  // Sum of per-document weights over occurrences.
+}
+
+impl fmt::Display for CountKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CountKind::Occurrences => "occurrences",
+            CountKind::Documents => "documents",
+            CountKind::Weighted => "weighted",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for CountKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "occurrences" => Ok(CountKind::Occurrences),
+            "documents" => Ok(CountKind::Documents),
+            "weighted" => Ok(CountKind::Weighted),
+            _ => bail!("Unknown count kind: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_display_round_trip() {
+        for kind in [
+            CountKind::Occurrences,
+            CountKind::Documents,
+            CountKind::Weighted,
+        ] {
+            assert_eq!(kind.to_string().parse::<CountKind>().unwrap(), kind);
+        }
+        assert!("bytes".parse::<CountKind>().is_err());
+    }
+}
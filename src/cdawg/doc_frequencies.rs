@@ -0,0 +1,67 @@
+// Document frequency of each state of a CDAWG: the number of documents that its strings occur in.
+//
+// They are filled by `TopologicalCounter::fill_doc_frequencies` and kept in a vector parallel to the
+// nodes, which is saved as `doc_freqs.vec` next to the graph. A CDAWG loaded from disk maps the file
+// rather than reading it into RAM.
+
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::memory_backing::DiskVec;
+
+const FILE_NAME: &str = "doc_freqs.vec";
+
+pub enum DocFrequencies {
+    Ram(Vec<usize>),
+    Disk(DiskVec<usize>, PathBuf), // Loaded from the file at the path.
+}
+
+impl DocFrequencies {
+    pub fn get(&self, state: usize) -> usize {
+        match self {
+            DocFrequencies::Ram(doc_freqs) => doc_freqs[state],
+            DocFrequencies::Disk(doc_freqs, _) => doc_freqs.get(state).unwrap(),
+        }
+    }
+
+    pub fn get_path<P: AsRef<Path>>(dir: P) -> PathBuf {
+        dir.as_ref().join(FILE_NAME)
+    }
+
+    /// Load the document frequencies saved in `dir`, if there are any.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Option<Self>> {
+        let path = Self::get_path(dir);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let doc_freqs = DiskVec::load(&path)?;
+        Ok(Some(DocFrequencies::Disk(doc_freqs, path)))
+    }
+
+    /// Save to `dir`, replacing any document frequencies saved there before.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let path = Self::get_path(dir);
+        match self {
+            DocFrequencies::Disk(_, loaded_path) if *loaded_path == path => return Ok(()),
+            _ => Self::remove(&path)?,
+        }
+        match self {
+            DocFrequencies::Ram(doc_freqs) => {
+                DiskVec::from_vec(doc_freqs, &path)?;
+            }
+            DocFrequencies::Disk(_, loaded_path) => {
+                fs::copy(loaded_path, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove document frequencies saved at `path`, which no longer match the graph.
+    pub fn remove(path: &Path) -> Result<()> {
+        if path.is_file() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
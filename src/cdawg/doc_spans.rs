@@ -0,0 +1,115 @@
+// Token spans of the documents a CDAWG was built on, saved in its metadata so that matches can be
+// attributed to the documents they occur in.
+//
+// Sinks already label their document through the self-loop added by `Cdawg::end_document`, which
+// records the document ID and the position of its end-of-document token. Document IDs need not be
+// unique (e.g. the Pile reader uses the domain), so spans are looked up by that position instead.
+// Each span also records its document's sink, so that a document's state can be found by ID without
+// scanning the graph for self-loops.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocSpan {
+    pub doc_id: usize,
+    pub start: usize, // Position of the first token (0-indexed).
+    pub end: usize,   // Position of the end-of-document token (0-indexed).
+    #[serde(default)]
+    pub sink: Option<usize>, // None if recorded by an older version, or pruned.
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocSpans {
+    spans: Vec<DocSpan>, // Sorted by position, since documents are added in order.
+}
+
+impl DocSpans {
+    /// Record a document whose end-of-document token is at position `idx` (1-indexed), and whose
+    /// sink is the state `sink`.
+    pub fn end_document(&mut self, idx: usize, doc_id: usize, sink: usize) {
+        let start = self.spans.last().map_or(0, |span| span.end + 1);
+        self.spans.push(DocSpan {
+            doc_id,
+            start,
+            end: idx - 1,
+            sink: Some(sink),
+        });
+    }
+
+    /// Find the document whose end-of-document token is at position `end` (0-indexed).
+    pub fn get_by_end(&self, end: usize) -> Option<DocSpan> {
+        self.spans
+            .binary_search_by_key(&end, |span| span.end)
+            .ok()
+            .map(|idx| self.spans[idx])
+    }
+
+    /// Iterate over the documents labelled `doc_id`, in order. IDs need not be unique.
+    pub fn get_by_id(&self, doc_id: usize) -> impl Iterator<Item = DocSpan> + '_ {
+        self.spans
+            .iter()
+            .filter(move |span| span.doc_id == doc_id)
+            .copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = DocSpan> + '_ {
+        self.spans.iter().copied()
+    }
+
+    /// Replace each sink with its state in a copy of the graph, or None if it wasn't copied.
+    pub fn remap_sinks<F>(&mut self, new_state: F)
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        for span in self.spans.iter_mut() {
+            span.sink = span.sink.and_then(&new_state);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_end_document() {
+        // Two documents, "ab$" and "c$".
+        let mut docs = DocSpans::default();
+        docs.end_document(3, 7, 1);
+        docs.end_document(5, 7, 4);
+        assert_eq!(docs.len(), 2);
+        assert_eq!(
+            docs.get_by_end(2),
+            Some(DocSpan {
+                doc_id: 7,
+                start: 0,
+                end: 2,
+                sink: Some(1),
+            })
+        );
+        assert_eq!(
+            docs.get_by_end(4),
+            Some(DocSpan {
+                doc_id: 7,
+                start: 3,
+                end: 4,
+                sink: Some(4),
+            })
+        );
+        assert_eq!(docs.get_by_end(3), None);
+        assert_eq!(docs.get_by_id(7).count(), 2);
+        assert_eq!(docs.get_by_id(8).next(), None);
+
+        docs.remap_sinks(|sink| if sink == 4 { Some(2) } else { None });
+        let sinks: Vec<_> = docs.iter().map(|span| span.sink).collect();
+        assert_eq!(sinks, vec![None, Some(2)]);
+    }
+}
@@ -0,0 +1,250 @@
+// Count of each state of a CDAWG in each domain of the corpus, for attributing memorized strings to
+// the sub-corpora they come from. Domains are the IDs that the data reader gives documents, like the
+// index of `pile_set_name` for the Pile, and are read from the self-loops that label the sinks.
+//
+// With few enough domains, every state stores the exact count of each. With more, each state keeps
+// a count-min sketch instead, of `SKETCH_DEPTH` rows taking as much space as the exact counts of
+// `max_exact` domains. Its estimates are never too low. The counts of each state are a row of a
+// vector parallel to the nodes, saved as `domain_counts.vec` next to the graph, with the domains and
+// layout saved in `domain_counts.json`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::memory_backing::DiskVec;
+
+const FILE_NAME: &str = "domain_counts.vec";
+const LAYOUT_FILE_NAME: &str = "domain_counts.json";
+
+/// Up to this many domains are counted exactly, unless set otherwise.
+pub const DEFAULT_MAX_EXACT_DOMAINS: usize = 64;
+const SKETCH_DEPTH: usize = 4;
+
+/// The domains that are counted, and how their counts are laid out in each state's row.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainLayout {
+    pub domains: Vec<usize>,         // Sorted.
+    pub sketch_width: Option<usize>, // Columns of each sketch row, or None if counts are exact.
+}
+
+impl DomainLayout {
+    pub fn new(mut domains: Vec<usize>, max_exact: usize) -> Self {
+        domains.sort_unstable();
+        domains.dedup();
+        let sketch_width = if domains.len() <= max_exact {
+            None
+        } else {
+            Some((max_exact / SKETCH_DEPTH).max(1))
+        };
+        Self {
+            domains,
+            sketch_width,
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.sketch_width.is_none()
+    }
+
+    /// Number of counts stored for each state.
+    pub fn row_len(&self) -> usize {
+        match self.sketch_width {
+            Some(width) => width * SKETCH_DEPTH,
+            None => self.domains.len(),
+        }
+    }
+
+    /// The cells of a state's row that count `domain`, or none if it isn't a known domain.
+    pub fn cells(&self, domain: usize) -> Vec<usize> {
+        match self.sketch_width {
+            Some(width) => (0..SKETCH_DEPTH)
+                .map(|row| row * width + hash(domain, row) % width)
+                .collect(),
+            None => self.domains.binary_search(&domain).into_iter().collect(),
+        }
+    }
+}
+
+// SplitMix64 of the domain, seeded differently for each sketch row.
+fn hash(domain: usize, row: usize) -> usize {
+    let mut x = (domain as u64)
+        .wrapping_mul(SKETCH_DEPTH as u64)
+        .wrapping_add(row as u64)
+        .wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (x ^ (x >> 31)) as usize
+}
+
+enum DomainValues {
+    Ram(Vec<usize>),
+    Disk(DiskVec<usize>, PathBuf), // Loaded from the file at the path.
+}
+
+pub struct DomainCounts {
+    layout: DomainLayout,
+    values: DomainValues,
+}
+
+impl DomainCounts {
+    /// Wrap `values`, which holds the row of each state in turn.
+    pub fn new(layout: DomainLayout, values: Vec<usize>) -> Self {
+        Self {
+            layout,
+            values: DomainValues::Ram(values),
+        }
+    }
+
+    pub fn get_layout(&self) -> &DomainLayout {
+        &self.layout
+    }
+
+    fn get_value(&self, idx: usize) -> usize {
+        match self.values {
+            DomainValues::Ram(ref values) => values[idx],
+            DomainValues::Disk(ref values, _) => values.get(idx).unwrap(),
+        }
+    }
+
+    /// The counts stored for `state`, laid out as described by the layout.
+    pub fn get_row(&self, state: usize) -> Vec<usize> {
+        let row_len = self.layout.row_len();
+        (0..row_len)
+            .map(|cell| self.get_value(state * row_len + cell))
+            .collect()
+    }
+
+    /// Count of `state` in `domain`, which is an upper bound if the counts are sketched.
+    pub fn get(&self, state: usize, domain: usize) -> usize {
+        let row_start = state * self.layout.row_len();
+        self.layout
+            .cells(domain)
+            .into_iter()
+            .map(|cell| self.get_value(row_start + cell))
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Every domain that `state` occurs in, with its count.
+    pub fn get_all(&self, state: usize) -> Vec<(usize, usize)> {
+        self.layout
+            .domains
+            .iter()
+            .map(|domain| (*domain, self.get(state, *domain)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    pub fn get_path<P: AsRef<Path>>(dir: P) -> PathBuf {
+        dir.as_ref().join(FILE_NAME)
+    }
+
+    fn get_layout_path<P: AsRef<Path>>(dir: P) -> PathBuf {
+        dir.as_ref().join(LAYOUT_FILE_NAME)
+    }
+
+    /// Load the domain counts saved in `dir`, if there are any.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Option<Self>> {
+        let path = Self::get_path(&dir);
+        let layout_path = Self::get_layout_path(&dir);
+        if !path.is_file() || !layout_path.is_file() {
+            return Ok(None);
+        }
+        let layout = serde_json::from_str(&fs::read_to_string(layout_path)?)?;
+        let values = DiskVec::load(&path)?;
+        Ok(Some(Self {
+            layout,
+            values: DomainValues::Disk(values, path),
+        }))
+    }
+
+    /// Save to `dir`, replacing any domain counts saved there before.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let path = Self::get_path(&dir);
+        match self.values {
+            DomainValues::Disk(_, ref loaded_path) if *loaded_path == path => return Ok(()),
+            _ => Self::remove(dir.as_ref())?,
+        }
+        fs::write(
+            Self::get_layout_path(&dir),
+            serde_json::to_string(&self.layout)?,
+        )?;
+        match self.values {
+            DomainValues::Ram(ref values) => {
+                DiskVec::from_vec(values, &path)?;
+            }
+            DomainValues::Disk(_, ref loaded_path) => {
+                fs::copy(loaded_path, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove domain counts saved in `dir`, which no longer match the graph.
+    pub fn remove(dir: &Path) -> Result<()> {
+        for path in [Self::get_path(dir), Self::get_layout_path(dir)] {
+            if path.is_file() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_exact_and_sketched() {
+        let exact = DomainLayout::new(vec![7, 3, 7], 4);
+        assert!(exact.is_exact());
+        assert_eq!(exact.domains, vec![3, 7]);
+        assert_eq!(exact.cells(7), vec![1]);
+        assert!(exact.cells(5).is_empty());
+
+        // Two states, counted in domains 3 and 7.
+        let counts = DomainCounts::new(exact, vec![2, 1, 0, 4]);
+        assert_eq!(counts.get(0, 3), 2);
+        assert_eq!(counts.get(1, 7), 4);
+        assert_eq!(counts.get(1, 5), 0);
+        assert_eq!(counts.get_all(1), vec![(7, 4)]);
+
+        let sketched = DomainLayout::new((0..10).collect(), 8);
+        assert_eq!(sketched.sketch_width, Some(2));
+        assert_eq!(sketched.row_len(), 8);
+        let mut values = vec![0; sketched.row_len()];
+        for (domain, count) in [(0, 5), (4, 1), (9, 2)] {
+            for cell in sketched.cells(domain) {
+                values[cell] += count;
+            }
+        }
+        let counts = DomainCounts::new(sketched, values);
+        assert!(counts.get(0, 0) >= 5);
+        assert!(counts.get(0, 4) >= 1);
+        assert!(counts.get(0, 9) >= 2);
+        assert!(counts.get(0, 0) <= 8);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let tmp_dir = tempdir().unwrap();
+        let layout = DomainLayout::new(vec![1, 2], 64);
+        let counts = DomainCounts::new(layout.clone(), vec![3, 0, 1, 1]);
+        counts.save(tmp_dir.path()).unwrap();
+
+        let loaded = DomainCounts::load(tmp_dir.path()).unwrap().unwrap();
+        assert_eq!(*loaded.get_layout(), layout);
+        assert_eq!(loaded.get_row(1), vec![1, 1]);
+        assert_eq!(loaded.get_all(0), vec![(1, 3)]);
+        // Saving to where it was loaded from keeps the file.
+        loaded.save(tmp_dir.path()).unwrap();
+        assert_eq!(loaded.get(0, 1), 3);
+
+        DomainCounts::remove(tmp_dir.path()).unwrap();
+        assert!(DomainCounts::load(tmp_dir.path()).unwrap().is_none());
+    }
+}
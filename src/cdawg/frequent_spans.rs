@@ -0,0 +1,140 @@
+// Enumerate the substrings of the corpus that occur at least `min_count` times, with their counts.
+//
+// Every substring of the corpus can be extended to the right, without changing its count, to a string
+// represented by some state. So it's enough to list each state once, with the longest string it
+// represents.
+// That string is spelled by the path of primary edges from the source: edges where the target's
+// length is the source's length plus the length of the label. Primary edges form a spanning tree, so
+// a DFS over them visits each state exactly once. Counts never increase along a path, so subtrees
+// below `min_count` are pruned. Edges whose labels contain an end-of-document token are skipped,
+// since they only lead to sinks.
+//
+// The traversal keeps no reference to the CDAWG between steps, so that it can be driven lazily from
+// Python.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::inenaga::Cdawg;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::graph::indexing::{IndexType, NodeIndex};
+use crate::graph::{EdgeRef, NodeRef};
+use crate::memory_backing::MemoryBacking;
+use crate::weight::Weight;
+
+pub struct FrequentSpans<Ix, T> {
+    min_count: usize,
+    stack: Vec<(NodeIndex<Ix>, usize, (usize, usize))>, // State, length of its parent, edge label.
+    span: Vec<T>, // Longest string of the last state returned.
+}
+
+impl<Ix, T> FrequentSpans<Ix, T>
+where
+    Ix: IndexType,
+    T: CdawgToken,
+{
+    /// Start a traversal of `cdawg`, whose counts must be filled.
+    pub fn new<W, Mb>(cdawg: &Cdawg<W, Ix, Mb, T>, min_count: usize) -> Self
+    where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        Mb::EdgeRef: Copy,
+    {
+        let mut spans = Self {
+            min_count,
+            stack: Vec::new(),
+            span: Vec::new(),
+        };
+        spans.push_children(cdawg, cdawg.get_source());
+        spans
+    }
+
+    /// The longest string of the next state counted at least `min_count` times, and its count.
+    pub fn next_span<W, Mb>(&mut self, cdawg: &Cdawg<W, Ix, Mb, T>) -> Option<(Vec<T>, usize)>
+    where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        Mb::EdgeRef: Copy,
+    {
+        let (state, parent_length, (start, end)) = self.stack.pop()?;
+        self.span.truncate(parent_length);
+        self.span
+            .extend((start - 1..end).map(|idx| cdawg.get_token(idx)));
+        self.push_children(cdawg, state);
+        Some((self.span.clone(), cdawg.get_count(state)))
+    }
+
+    /// Iterate over the remaining strings and counts.
+    pub fn iter<'a, W, Mb>(
+        mut self,
+        cdawg: &'a Cdawg<W, Ix, Mb, T>,
+    ) -> impl Iterator<Item = (Vec<T>, usize)> + 'a
+    where
+        Ix: 'a,
+        T: 'a,
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        Mb::EdgeRef: Copy,
+    {
+        std::iter::from_fn(move || self.next_span(cdawg))
+    }
+
+    fn push_children<W, Mb>(&mut self, cdawg: &Cdawg<W, Ix, Mb, T>, state: NodeIndex<Ix>)
+    where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        Mb::EdgeRef: Copy,
+    {
+        let graph = cdawg.get_graph();
+        let length = graph.get_node(state).get_length() as usize;
+        for edge in graph.edges(state) {
+            let target = edge.get_target();
+            if target == state || cdawg.get_count(target) < self.min_count {
+                continue;
+            }
+            let (start, end) = cdawg.get_span(edge.get_weight(), target);
+            let is_primary =
+                length + (end + 1 - start) == graph.get_node(target).get_length() as usize;
+            if is_primary && (start - 1..end).all(|idx| cdawg.get_token(idx) != T::end()) {
+                self.stack.push((target, length, (start, end)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn get_spans(tokens: Vec<u16>, min_count: usize) -> Vec<(Vec<u16>, usize)> {
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+        let mut spans: Vec<_> = FrequentSpans::new(&cdawg, min_count).iter(&cdawg).collect();
+        spans.sort();
+        spans
+    }
+
+    #[test]
+    fn test_frequent_spans_cocoa() {
+        let (c, o, a) = (0, 1, 2);
+        let tokens = vec![c, o, c, o, a, u16::MAX];
+        assert_eq!(get_spans(tokens.clone(), 1), vec![(vec![c, o], 2)]);
+        assert_eq!(get_spans(tokens, 3), vec![]);
+    }
+
+    #[test]
+    fn test_frequent_spans_abcabcaba() {
+        let (a, b, c) = (0, 1, 2);
+        let tokens = vec![a, b, c, a, b, c, a, b, a, u16::MAX];
+        assert_eq!(
+            get_spans(tokens.clone(), 2),
+            vec![(vec![a], 4), (vec![a, b], 3), (vec![a, b, c, a, b], 2)]
+        );
+        assert_eq!(get_spans(tokens, 3), vec![(vec![a], 4), (vec![a, b], 3)]);
+    }
+}
@@ -0,0 +1,559 @@
+// Conversion of a built token DAWG into the CDAWG of the same tokens, without rebuilding it.
+//
+// A CDAWG is a DAWG whose states that can only be continued one way have been merged into the edges
+// through them, which become spans of the token vector. So the states kept are the initial state
+// (the source), the states that can be continued in at least two ways, and a sink for each
+// document. As in `Cdawg::build`, each document that a state is a suffix of counts as a separate way
+// to continue it, with its own end-of-document edge. States that can only continue by ending a
+// single document are merged into the sink of that document, and states that can't be continued at
+// all (the suffixes of an unfinished last document) into the sink of the next document.
+//
+// Edges are labelled with the first occurrence of their tokens, which ends where the state they
+// lead to first occurs. These first end positions are found by walking the tokens through the DAWG,
+// which also checks that the DAWG was built on them, and then taking the minimum over the longer
+// strings that fail to each state. With several documents, `Cdawg::build` sometimes labels an edge
+// with a later occurrence, so the spans can differ from a CDAWG built from the tokens, but the
+// states, the tokens on each edge and the counts are the same.
+//
+// The walk also checks that each document prefix is the longest string of its state. `Dawg::extend`
+// splits states so that this holds, but DAWGs saved by older versions didn't when a document started
+// with tokens that only occurred earlier after the same context. Such DAWGs can't be converted, and
+// the CDAWG has to be built from the tokens instead.
+//
+// As after `Cdawg::build`, sinks count 1 and everything else 0 until the counts are filled.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::cmp::{min, Reverse};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::comparator::CdawgComparator;
+use crate::cdawg::corpus_totals::CorpusTotals;
+use crate::cdawg::inenaga::Cdawg;
+use crate::cdawg::token_backing::{CdawgToken, TokenBacking};
+use crate::dawg::Dawg;
+use crate::graph::avl_graph::node::NodeMutRef;
+use crate::graph::avl_graph::AvlGraph;
+use crate::graph::indexing::{DefaultIx, IndexType, NodeIndex};
+use crate::graph::{EdgeRef, NodeRef};
+use crate::memory_backing::{CacheConfig, MemoryBacking};
+use crate::weight::Weight;
+
+// What a DAWG state becomes in the CDAWG.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Role {
+    State,
+    Chain,       // Continues one way, so it's merged into the edges through it.
+    Sink(usize), // Merged into the sink of a document, or of the next one if it's out of range.
+    DocLabel,    // Target of an end-of-document edge, whose length is the document ID.
+}
+
+// The results of walking the tokens through the DAWG.
+struct Walk {
+    first_ends: Vec<usize>,        // Where each state first occurs, 1-indexed.
+    doc_ends: Vec<(usize, usize)>, // Position of the end-of-document token and ID of each document.
+    suffixes: Vec<(usize, usize)>, // (state, document) for each document a state is a suffix of.
+    is_label: Vec<bool>,
+}
+
+impl<W, Ix, Mb, T> Cdawg<W, Ix, Mb, T>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    Mb::EdgeRef: Copy,
+    T: CdawgToken,
+{
+    /// Convert `dawg` into a CDAWG over the first `n_tokens` of `tokens`, which must be the tokens it
+    /// was built on, with an end-of-document token after each document that was ended in the DAWG.
+    pub fn from_dawg<Wd, Mbd>(
+        dawg: &Dawg<T, Wd, DefaultIx, Mbd>,
+        tokens: Rc<RefCell<dyn TokenBacking<T>>>,
+        n_tokens: usize,
+        mb: Mb,
+        cache_config: CacheConfig,
+    ) -> Result<Self>
+    where
+        Wd: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mbd: MemoryBacking<Wd, T, DefaultIx>,
+        Mbd::EdgeRef: Copy,
+    {
+        if !Wd::has_failures() {
+            bail!(
+                "Can't convert a DAWG without failures; its weight type is {}",
+                Wd::name()
+            );
+        }
+        if n_tokens > tokens.borrow().len() {
+            bail!(
+                "Can't convert {} tokens from a token vector of length {}",
+                n_tokens,
+                tokens.borrow().len()
+            );
+        }
+        let walk = walk_tokens(dawg, &*tokens.borrow(), n_tokens)?;
+        let n_docs = walk.doc_ends.len();
+        let roles = get_roles(dawg, &walk);
+        let chain_ends = get_chain_ends(dawg, &roles);
+        // The state that the edge into `state` leads to in the CDAWG, and how many tokens it spans.
+        let follow = |state: usize| match roles[state] {
+            Role::Chain => (chain_ends[state].0, chain_ends[state].1 + 1),
+            _ => (state, 1),
+        };
+
+        let is_kept = |idx: usize| roles[idx] == Role::State;
+        let n_states = (0..dawg.node_count()).filter(|&idx| is_kept(idx)).count();
+        let n_edges = (0..dawg.node_count())
+            .filter(|&idx| is_kept(idx))
+            .map(|idx| count_next(dawg, NodeIndex::new(idx)))
+            .sum::<usize>()
+            + walk.suffixes.len()
+            + n_docs;
+        let mut graph: AvlGraph<W, CdawgEdgeWeight<Ix>, Ix, Mb> =
+            AvlGraph::with_capacity_mb(mb, n_states + n_docs + 1, n_edges, cache_config);
+
+        // The source and sinks come first, as in `build`.
+        let initial = dawg.get_initial().index();
+        let source = graph.add_node(W::new(0, None, 0));
+        let mut sinks = Vec::with_capacity(n_docs + 1);
+        let mut doc_start = 0;
+        let doc_ends = walk.doc_ends.iter().map(|&(end, _)| end);
+        for end in doc_ends.chain([n_tokens]) {
+            let length = (end - doc_start) as u64;
+            let failure = Some(NodeIndex::new(source.index()));
+            sinks.push(graph.add_node(W::new(length, failure, 1)));
+            doc_start = end;
+        }
+        let mut new_states: Vec<Option<NodeIndex<Ix>>> = vec![None; dawg.node_count()];
+        for (idx, new_state) in new_states.iter_mut().enumerate() {
+            if idx == initial {
+                *new_state = Some(source);
+            } else if is_kept(idx) {
+                let length = dawg.get_node(NodeIndex::new(idx)).get_length();
+                *new_state = Some(graph.add_node(W::new(length, None, 0)));
+            }
+        }
+
+        let mut totals = CorpusTotals::default();
+        for (doc, &(end, doc_id)) in walk.doc_ends.iter().enumerate() {
+            // Stored like the weights of `end_document`: the span start is the document's end.
+            let weight = CdawgEdgeWeight::new(end - 1, doc_id);
+            add_edge(&mut graph, &tokens, (sinks[doc], sinks[doc]), weight);
+            totals.end_document(end);
+        }
+
+        let open = Ix::max_value().index();
+        for (idx, new_state) in new_states.iter().enumerate() {
+            let Some(new_state) = *new_state else {
+                continue;
+            };
+            let state = NodeIndex::new(idx);
+            let failure = dawg
+                .get_node(state)
+                .get_failure()
+                .and_then(|failure| new_states[failure.index()])
+                .map(|failure| NodeIndex::new(failure.index()));
+            graph.get_node_mut(new_state).set_failure(failure);
+
+            for edge in dawg.get_graph().edges(state) {
+                if edge.get_weight() == T::end() {
+                    continue;
+                }
+                let (target, n_spanned) = follow(edge.get_target().index());
+                let end = walk.first_ends[target];
+                let (new_target, end) = match roles[target] {
+                    Role::State => (new_states[target].unwrap(), end),
+                    Role::Sink(doc) => (sinks[doc], open),
+                    _ => unreachable!("Chains end in states or sinks"),
+                };
+                let weight = CdawgEdgeWeight::new(walk.first_ends[target] - n_spanned, end);
+                add_edge(&mut graph, &tokens, (new_state, new_target), weight);
+            }
+            for &(_, doc) in suffixes_of(&walk.suffixes, idx) {
+                let weight = CdawgEdgeWeight::new(walk.doc_ends[doc].0 - 1, open);
+                add_edge(&mut graph, &tokens, (new_state, sinks[doc]), weight);
+            }
+        }
+
+        Ok(Self::from_parts(
+            tokens,
+            graph,
+            (source, sinks[n_docs]),
+            n_tokens,
+            totals,
+        ))
+    }
+}
+
+fn walk_tokens<T, W, Mb>(
+    dawg: &Dawg<T, W, DefaultIx, Mb>,
+    tokens: &dyn TokenBacking<T>,
+    n_tokens: usize,
+) -> Result<Walk>
+where
+    T: CdawgToken,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, T, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    let mut walk = Walk {
+        first_ends: vec![usize::MAX; dawg.node_count()],
+        doc_ends: Vec::new(),
+        suffixes: Vec::new(),
+        is_label: vec![false; dawg.node_count()],
+    };
+    // Documents ending in the same state chain their labels off each other, in order.
+    let mut last_labels: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+    let (mut state, mut length) = (dawg.get_initial(), 0);
+    for idx in 0..n_tokens {
+        let token = tokens.get(idx);
+        let position = idx + 1;
+        if token == T::end() {
+            let doc = walk.doc_ends.len();
+            let mut opt_state = Some(state);
+            while let Some(suffix) = opt_state {
+                walk.suffixes.push((suffix.index(), doc));
+                opt_state = dawg.get_node(suffix).get_failure();
+            }
+            // States split off from a document's state share its labels, so skip those that
+            // belong to earlier documents, as `end_document` did.
+            let mut from = last_labels.get(&state).copied().unwrap_or(state);
+            let label = loop {
+                let Some(label) = dawg.transition(from, token, false) else {
+                    bail!(
+                        "The document ending at position {} wasn't ended in the DAWG",
+                        position
+                    );
+                };
+                if !walk.is_label[label.index()] {
+                    break label;
+                }
+                from = label;
+            };
+            last_labels.insert(state, label);
+            walk.is_label[label.index()] = true;
+            let doc_id = dawg.get_node(label).get_length() as usize;
+            walk.doc_ends.push((position, doc_id));
+            (state, length) = (dawg.get_initial(), 0);
+            continue;
+        }
+
+        let Some(next) = dawg.transition(state, token, false) else {
+            bail!(
+                "Token {} at position {} isn't in the DAWG; was it built on these tokens?",
+                token,
+                position
+            );
+        };
+        length += 1;
+        if dawg.get_node(next).get_length() != length {
+            bail!(
+                "The prefix of length {} of the document at position {} shares a DAWG state with \
+                 longer strings, so the DAWG can't be converted; build the CDAWG from the tokens",
+                length,
+                position
+            );
+        }
+        walk.first_ends[next.index()] = min(walk.first_ends[next.index()], position);
+        state = next;
+    }
+    walk.suffixes.sort_unstable();
+
+    // Every string in a state ends wherever the longer strings that fail to it do.
+    let mut by_length: Vec<usize> = (0..dawg.node_count())
+        .filter(|&idx| !walk.is_label[idx])
+        .collect();
+    by_length.sort_unstable_by_key(|&idx| Reverse(dawg.get_node(NodeIndex::new(idx)).get_length()));
+    for idx in by_length {
+        if let Some(failure) = dawg.get_node(NodeIndex::new(idx)).get_failure() {
+            let first_end = walk.first_ends[idx];
+            let failure_end = &mut walk.first_ends[failure.index()];
+            *failure_end = min(*failure_end, first_end);
+        }
+    }
+    let initial = dawg.get_initial().index();
+    if let Some(idx) = (0..dawg.node_count())
+        .find(|&idx| idx != initial && !walk.is_label[idx] && walk.first_ends[idx] == usize::MAX)
+    {
+        bail!(
+            "State {} of the DAWG doesn't occur in the tokens; was it built on these tokens?",
+            idx
+        );
+    }
+    Ok(walk)
+}
+
+fn get_roles<T, W, Mb>(dawg: &Dawg<T, W, DefaultIx, Mb>, walk: &Walk) -> Vec<Role>
+where
+    T: CdawgToken,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, T, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    let initial = dawg.get_initial().index();
+    (0..dawg.node_count())
+        .map(|idx| {
+            if walk.is_label[idx] {
+                return Role::DocLabel;
+            }
+            let docs = suffixes_of(&walk.suffixes, idx);
+            match (count_next(dawg, NodeIndex::new(idx)), docs) {
+                _ if idx == initial => Role::State,
+                (1, []) => Role::Chain,
+                (0, [(_, doc)]) => Role::Sink(*doc),
+                (0, []) => Role::Sink(walk.doc_ends.len()),
+                _ => Role::State,
+            }
+        })
+        .collect()
+}
+
+// For each chain state, the state or sink that following it leads to, and the number of edges.
+fn get_chain_ends<T, W, Mb>(dawg: &Dawg<T, W, DefaultIx, Mb>, roles: &[Role]) -> Vec<(usize, usize)>
+where
+    T: CdawgToken,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, T, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    let next = |idx: usize| {
+        dawg.get_graph()
+            .edges(NodeIndex::new(idx))
+            .find(|edge| edge.get_weight() != T::end())
+            .unwrap()
+            .get_target()
+            .index()
+    };
+    let mut chain_ends = vec![(usize::MAX, 0); roles.len()];
+    let mut path = Vec::new();
+    for idx in 0..roles.len() {
+        let mut state = idx;
+        while roles[state] == Role::Chain && chain_ends[state].0 == usize::MAX {
+            path.push(state);
+            state = next(state);
+        }
+        let (end, mut n_edges) = match roles[state] {
+            Role::Chain => chain_ends[state],
+            _ => (state, 0),
+        };
+        for state in path.drain(..).rev() {
+            n_edges += 1;
+            chain_ends[state] = (end, n_edges);
+        }
+    }
+    chain_ends
+}
+
+// Number of tokens that `state` can be continued by, not counting ending a document.
+fn count_next<T, W, Mb>(dawg: &Dawg<T, W, DefaultIx, Mb>, state: NodeIndex) -> usize
+where
+    T: CdawgToken,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, T, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    dawg.get_graph()
+        .edges(state)
+        .filter(|edge| edge.get_weight() != T::end())
+        .count()
+}
+
+fn suffixes_of(suffixes: &[(usize, usize)], state: usize) -> &[(usize, usize)] {
+    let start = suffixes.partition_point(|&(idx, _)| idx < state);
+    let end = suffixes.partition_point(|&(idx, _)| idx <= state);
+    &suffixes[start..end]
+}
+
+fn add_edge<W, Ix, Mb, T>(
+    graph: &mut AvlGraph<W, CdawgEdgeWeight<Ix>, Ix, Mb>,
+    tokens: &Rc<RefCell<dyn TokenBacking<T>>>,
+    (state, target): (NodeIndex<Ix>, NodeIndex<Ix>),
+    weight: CdawgEdgeWeight<Ix>,
+) where
+    Ix: IndexType,
+    W: Weight + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+{
+    let token = tokens.borrow().get(weight.get_span().0);
+    let cmp = CdawgComparator::new_with_token(tokens.clone(), token);
+    graph.add_balanced_edge_cmp(state, target, weight, Box::new(cmp));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::sampling::XorShiftRng;
+    use crate::cdawg::TopologicalCounter;
+    use crate::weight::DefaultWeight;
+
+    const END: u16 = u16::MAX;
+
+    // Build a DAWG the way the binary does, ending documents at end-of-document tokens and
+    // labelling them with their end positions, as `Cdawg::build` does.
+    fn build_dawg(tokens: &[u16]) -> Dawg<u16, DefaultWeight> {
+        let mut dawg = Dawg::new();
+        let (mut last, mut length) = (dawg.get_initial(), 0);
+        for (idx, &token) in tokens.iter().enumerate() {
+            (last, length) = match token {
+                END => dawg.end_document(last, END, idx as u64 + 1),
+                token => dawg.extend(token, last, length),
+            };
+        }
+        dawg
+    }
+
+    fn convert(tokens: &[u16]) -> Result<Cdawg> {
+        let dawg = build_dawg(tokens);
+        let n_tokens = tokens.len();
+        let tokens = Rc::new(RefCell::new(tokens.to_vec()));
+        let mut cdawg: Cdawg = Cdawg::from_dawg(
+            &dawg,
+            tokens,
+            n_tokens,
+            Default::default(),
+            CacheConfig::none(),
+        )?;
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        Ok(cdawg)
+    }
+
+    // A state reached from the source, with its edges labelled by their tokens.
+    #[derive(Debug, PartialEq)]
+    struct Summary {
+        length: u64,
+        count: usize,
+        terminal: bool,
+        failure: Option<usize>,
+        edges: Vec<(Vec<u16>, usize)>,
+        doc_loops: Vec<CdawgEdgeWeight>,
+    }
+
+    // Summarize the states reachable from the source in breadth-first order, visiting edges in
+    // order of their tokens. With several documents, `build` can label an edge with another
+    // occurrence of the same tokens, so edges are compared by their tokens rather than their spans.
+    // End-of-document edges are told apart by the position of the end-of-document token.
+    fn summarize(cdawg: &Cdawg) -> Vec<Summary> {
+        let mut ids = HashMap::from([(cdawg.get_source(), 0)]);
+        let mut order = vec![cdawg.get_source()];
+        let mut summaries = Vec::new();
+        while summaries.len() < order.len() {
+            let state = order[summaries.len()];
+            let mut edges = Vec::new();
+            let mut doc_loops = Vec::new();
+            for edge in cdawg.get_graph().edges(state) {
+                let (weight, target) = (edge.get_weight(), edge.get_target());
+                if target == state {
+                    doc_loops.push(weight);
+                    continue;
+                }
+                let (start, end) = cdawg.get_span(weight, target);
+                let label: Vec<u16> = (start - 1..end).map(|idx| cdawg.get_token(idx)).collect();
+                let key = if label[0] == END { start } else { 0 };
+                edges.push((label, key, target));
+            }
+            edges.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+            let edges = edges
+                .into_iter()
+                .map(|(label, _, target)| {
+                    let n_ids = ids.len();
+                    let id = *ids.entry(target).or_insert(n_ids);
+                    if id == n_ids {
+                        order.push(target);
+                    }
+                    (label, id)
+                })
+                .collect();
+            let node = cdawg.get_graph().get_node(state);
+            summaries.push(Summary {
+                length: node.get_length(),
+                count: node.get_count(),
+                terminal: node.is_terminal(),
+                failure: node.get_failure().map(|failure| failure.index()),
+                edges,
+                doc_loops,
+            });
+        }
+        // Failures can point anywhere, so they're renumbered once every state has an ID.
+        for summary in summaries.iter_mut() {
+            summary.failure = summary.failure.map(|failure| ids[&NodeIndex::new(failure)]);
+        }
+        summaries
+    }
+
+    fn check_same_as_build(tokens: Vec<u16>) {
+        let converted = convert(&tokens).unwrap();
+        let mut built: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        built.build();
+        TopologicalCounter::new_ram().fill_counts(&mut built);
+
+        assert_eq!(converted.node_count(), built.node_count());
+        assert_eq!(converted.edge_count(), built.edge_count());
+        assert_eq!(summarize(&converted), summarize(&built));
+        assert_eq!(converted.get_totals(), built.get_totals());
+        for doc in tokens.split(|&token| token == END) {
+            for start in 0..doc.len() {
+                for end in start + 1..doc.len() + 1 {
+                    let ngram = &doc[start..end];
+                    assert_eq!(
+                        converted.get_ngram_count(ngram),
+                        built.get_ngram_count(ngram)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_dawg_one_document() {
+        let (a, b, c) = (0, 1, 2);
+        check_same_as_build(vec![a, b, c, a, b, c, a, b, a, END]);
+        check_same_as_build(vec![a, a, a, a, END]);
+        // Without an end-of-document token, the suffixes are left implicit.
+        check_same_as_build(vec![a, b, c, a, b, c, a, b, a]);
+    }
+
+    #[test]
+    fn test_from_dawg_documents() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        check_same_as_build(vec![c, o, c, o, a, END, c, o, l, a, END]);
+        check_same_as_build(vec![0, END, 1, END]);
+        // Repeated documents get sinks of their own.
+        check_same_as_build(vec![c, o, l, a, END, c, o, l, a, END, c, o, END]);
+        check_same_as_build(vec![c, o, c, o, a, END, c, o, l, a]);
+    }
+
+    #[test]
+    fn test_from_dawg_random() {
+        let mut rng = XorShiftRng::new(0);
+        for _ in 0..200 {
+            let tokens: Vec<u16> = (0..20)
+                .map(|_| match rng.next_u64() % 4 {
+                    3 if rng.next_u64().is_multiple_of(2) => END,
+                    token => token as u16,
+                })
+                .collect();
+            check_same_as_build(tokens);
+        }
+    }
+
+    #[test]
+    fn test_from_dawg_split_prefix() {
+        // "b" starts the second document but only occurred after "a" before, so it was split off.
+        check_same_as_build(vec![0, 1, END, 1, END]);
+        // Tokens that the DAWG wasn't built on.
+        let dawg = build_dawg(&[0, 1, END]);
+        let tokens = Rc::new(RefCell::new(vec![0, 2, END]));
+        let result: Result<Cdawg> =
+            Cdawg::from_dawg(&dawg, tokens, 3, Default::default(), CacheConfig::none());
+        assert!(result.is_err());
+        // Padding after the last document is left out.
+        let tokens = Rc::new(RefCell::new(vec![0, 1, END, 0, 0]));
+        let result: Result<Cdawg> =
+            Cdawg::from_dawg(&dawg, tokens, 3, Default::default(), CacheConfig::none());
+        assert_eq!(result.unwrap().get_totals().unwrap().n_tokens, 2);
+    }
+}
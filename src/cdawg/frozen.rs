@@ -0,0 +1,395 @@
+// A read-only copy of a CDAWG that many threads can query at once.
+//
+// `Cdawg` reads tokens through an `Rc<RefCell<..>>`, and its backings aren't thread-safe, so it can't
+// be shared between threads. `FrozenCdawg` copies the nodes, edges and tokens of a built CDAWG into
+// immutable arrays behind `Arc`s. It is `Send + Sync`, and cloning it is cheap, so a query server can
+// hand a clone to each worker.
+//
+// Queries follow the ones on `Cdawg` exactly, and return the same `CdawgState`s. Only tokens up to
+// the end of the last document are copied, and documents can't be added afterwards.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::cdawg_state::CdawgState;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::cdawg::Cdawg;
+use crate::graph::avl_graph::edge::Edge;
+use crate::graph::avl_graph::node::Node;
+use crate::graph::indexing::{DefaultIx, EdgeIndex, IndexType, NodeIndex};
+use crate::graph::{EdgeRef, NodeRef};
+use crate::memory_backing::MemoryBacking;
+use crate::metrics;
+use crate::weight::{DefaultWeight, Weight};
+
+pub struct FrozenCdawg<W = DefaultWeight, Ix: IndexType = DefaultIx, T = u16> {
+    nodes: Arc<[Node<W, Ix>]>,
+    edges: Arc<[Edge<CdawgEdgeWeight<Ix>, Ix>]>,
+    tokens: Arc<[T]>,
+    source: NodeIndex<Ix>,
+    end_position: usize,
+}
+
+// Derived Clone would require W, Ix and T to be Clone.
+impl<W, Ix, T> Clone for FrozenCdawg<W, Ix, T>
+where
+    Ix: IndexType,
+{
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            edges: self.edges.clone(),
+            tokens: self.tokens.clone(),
+            source: self.source,
+            end_position: self.end_position,
+        }
+    }
+}
+
+impl<W, Ix, T> FrozenCdawg<W, Ix, T>
+where
+    Ix: IndexType,
+    W: Weight + Clone,
+    T: CdawgToken,
+{
+    /// Copy `cdawg`, whose counts should be filled, into RAM.
+    pub fn new<Mb>(cdawg: &Cdawg<W, Ix, Mb, T>) -> Self
+    where
+        W: Serialize + for<'de> Deserialize<'de>,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        Mb::EdgeRef: Copy,
+    {
+        let graph = cdawg.get_graph();
+        let nodes: Vec<_> = (0..cdawg.node_count())
+            .map(|idx| Node {
+                weight: graph.get_node(NodeIndex::new(idx)).get_weight(),
+                first_edge: graph.get_node(NodeIndex::new(idx)).get_first_edge(),
+            })
+            .collect();
+        let edges: Vec<_> = (0..cdawg.edge_count())
+            .map(|idx| {
+                let edge = graph.get_edge(EdgeIndex::new(idx));
+                Edge {
+                    weight: edge.get_weight(),
+                    target: edge.get_target(),
+                    left: edge.get_left(),
+                    right: edge.get_right(),
+                    balance_factor: edge.get_balance_factor(),
+                }
+            })
+            .collect();
+        let (source, start) = cdawg.get_resume_point();
+        let end_position = start - 1;
+        let tokens: Vec<_> = (0..end_position).map(|idx| cdawg.get_token(idx)).collect();
+        Self {
+            nodes: nodes.into(),
+            edges: edges.into(),
+            tokens: tokens.into(),
+            source,
+            end_position,
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn get_source(&self) -> NodeIndex<Ix> {
+        self.source
+    }
+
+    pub fn get_count(&self, state: NodeIndex<Ix>) -> usize {
+        self.nodes[state.index()].weight.get_count()
+    }
+
+    pub fn get_initial(&self) -> CdawgState<Ix> {
+        CdawgState {
+            state: self.source,
+            edge_start: 0,
+            start: 0,
+            end: 0,
+            target: Some(self.source),
+            length: 0,
+        }
+    }
+
+    /// Same as `Cdawg::transition_and_count`.
+    pub fn transition_and_count(&self, mut cs: CdawgState<Ix>, token: T) -> CdawgState<Ix> {
+        let Some(state) = cs.target else {
+            return self.get_initial();
+        };
+        if cs.start == cs.end {
+            if let Some(edge) = self.get_edge_by_token(state, token) {
+                let (start, end) = self.get_span(edge.weight, edge.target);
+                return CdawgState {
+                    state,
+                    edge_start: start - 1,
+                    start,
+                    end,
+                    target: Some(edge.target),
+                    length: cs.length + 1,
+                };
+            }
+            let fail_cs = self.implicitly_fail(state, (cs.end, cs.end));
+            self.transition_and_count(fail_cs, token)
+        } else if token == self.tokens[cs.start] {
+            cs.start += 1;
+            cs.length += 1;
+            cs
+        } else {
+            let fail_cs = self.implicitly_fail(cs.state, (cs.edge_start, cs.start));
+            self.transition_and_count(fail_cs, token)
+        }
+    }
+
+    pub fn get_suffix_count(&self, cs: CdawgState<Ix>) -> usize {
+        self.get_count(cs.target.unwrap())
+    }
+
+    /// Same as `Cdawg::get_suffix_length_profile`.
+    pub fn get_suffix_length_profile(&self, query: &[T]) -> Vec<u64> {
+        let _timer = metrics::time_query();
+        let mut cs = self.get_initial();
+        query
+            .iter()
+            .map(|token| {
+                cs = self.transition_and_count(cs, *token);
+                cs.length
+            })
+            .collect()
+    }
+
+    /// Count the occurrences of `ngram` in the corpus by exact match, or 0 if it doesn't occur.
+    pub fn get_ngram_count(&self, ngram: &[T]) -> usize {
+        let _timer = metrics::time_query();
+        let mut cs = self.get_initial();
+        for token in ngram.iter() {
+            let old_length = cs.length;
+            cs = self.transition_and_count(cs, *token);
+            if cs.length != old_length + 1 {
+                return 0;
+            }
+        }
+        self.get_suffix_count(cs)
+    }
+
+    /// Same as `Cdawg::get_next_tokens`, though the tokens may come in a different order.
+    pub fn get_next_tokens(&self, cs: CdawgState<Ix>) -> Vec<(T, f64)> {
+        let (state, gamma) = cs.get_state_and_gamma();
+        if gamma.0 != gamma.1 {
+            return vec![(self.tokens[gamma.1], 1.)];
+        }
+
+        let q = state.unwrap();
+        let denom = self.get_count(q) as f64;
+        let mut tokens = Vec::new();
+        let mut stack = vec![self.nodes[q.index()].first_edge];
+        while let Some(edge_idx) = stack.pop() {
+            if edge_idx == EdgeIndex::end() {
+                continue;
+            }
+            let edge = &self.edges[edge_idx.index()];
+            let token = self.tokens[edge.weight.start.index()];
+            tokens.push((token, self.get_count(edge.target) as f64 / denom));
+            stack.push(edge.left);
+            stack.push(edge.right);
+        }
+        tokens
+    }
+
+    /// Same as `Cdawg::implicitly_fail`. `gamma` is 0-indexed.
+    pub fn implicitly_fail(&self, state: NodeIndex<Ix>, gamma: (usize, usize)) -> CdawgState<Ix> {
+        let (start, end) = gamma;
+        let fstate = self.nodes[state.index()]
+            .weight
+            .get_failure()
+            .map(|q| NodeIndex::new(q.index()));
+        let (opt_state, new_start, opt_target, found_start, found_end) =
+            self.inference_canonize(fstate, (start + 1, end));
+        let (new_start, found_start) = (new_start - 1, found_start - 1);
+        match opt_state {
+            Some(q) if new_start == end => CdawgState {
+                state: q,
+                edge_start: found_start,
+                start: found_end,
+                end: found_end,
+                target: opt_state,
+                length: self.nodes[q.index()].weight.get_length(),
+            },
+            Some(q) => {
+                let progress = end - new_start;
+                CdawgState {
+                    state: q,
+                    edge_start: found_start,
+                    start: found_start + progress,
+                    end: found_end,
+                    target: opt_target,
+                    length: self.nodes[q.index()].weight.get_length() + progress as u64,
+                }
+            }
+            None => CdawgState {
+                state: self.source,
+                edge_start: 0,
+                start: 0,
+                end: 0,
+                target: None,
+                length: 0,
+            },
+        }
+    }
+
+    // Same as `Cdawg::inference_canonize`. `gamma` is 1-indexed.
+    #[allow(clippy::type_complexity)]
+    fn inference_canonize(
+        &self,
+        mut state: Option<NodeIndex<Ix>>,
+        gamma: (usize, usize),
+    ) -> (
+        Option<NodeIndex<Ix>>,
+        usize,
+        Option<NodeIndex<Ix>>,
+        usize,
+        usize,
+    ) {
+        let (mut start, end) = gamma;
+        if start > end {
+            return (state, start, state, start, end);
+        }
+
+        let (mut found_start, mut found_end, mut found_state) = match state {
+            Some(q) => self.get_start_end_target(q, self.tokens[start - 1]),
+            None => (1, 1, self.source),
+        };
+        while found_end + start <= end + found_start {
+            start += found_end + 1 - found_start;
+            state = Some(found_state);
+            if start <= end {
+                (found_start, found_end, found_state) =
+                    self.get_start_end_target(found_state, self.tokens[start - 1]);
+            }
+        }
+        (state, start, Some(found_state), found_start, found_end)
+    }
+
+    // The 1-indexed span and target of the edge out of `state` starting with `token`, which must
+    // exist.
+    fn get_start_end_target(
+        &self,
+        state: NodeIndex<Ix>,
+        token: T,
+    ) -> (usize, usize, NodeIndex<Ix>) {
+        let edge = self.get_edge_by_token(state, token).unwrap();
+        let (start, end) = self.get_span(edge.weight, edge.target);
+        (start, end, edge.target)
+    }
+
+    // Search the AVL tree of edges out of `state`, like `Cdawg::get_edge_by_token`.
+    fn get_edge_by_token(
+        &self,
+        state: NodeIndex<Ix>,
+        token: T,
+    ) -> Option<&Edge<CdawgEdgeWeight<Ix>, Ix>> {
+        if token == T::end() {
+            return None;
+        }
+        let mut edge_idx = self.nodes[state.index()].first_edge;
+        while edge_idx != EdgeIndex::end() {
+            let edge = &self.edges[edge_idx.index()];
+            edge_idx = match token.cmp(&self.tokens[edge.weight.start.index()]) {
+                Ordering::Equal => return Some(edge),
+                Ordering::Less => edge.left,
+                Ordering::Greater => edge.right,
+            };
+        }
+        None
+    }
+
+    // Same as `Cdawg::get_span`: the 1-indexed span of an edge, resolving open ends.
+    fn get_span(&self, weight: CdawgEdgeWeight<Ix>, target: NodeIndex<Ix>) -> (usize, usize) {
+        let (start, end) = weight.get_span();
+        if end < Ix::max_value().index() {
+            return (start + 1, end);
+        }
+        let edge_idx = self.nodes[target.index()].first_edge;
+        if edge_idx == EdgeIndex::end() {
+            (start + 1, self.end_position)
+        } else {
+            let (e, _) = self.edges[edge_idx.index()].weight.get_span();
+            (start + 1, e + 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::thread;
+
+    #[test]
+    fn test_frozen_matches_cdawg() {
+        let (a, b, c, d) = (0, 1, 2, 3);
+        let tokens = vec![a, b, c, a, b, u16::MAX, b, c, d, a, b, c, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        let frozen = FrozenCdawg::new(&cdawg);
+        assert_eq!(frozen.node_count(), cdawg.node_count());
+
+        let query = [b, c, a, b, d, a, b, c, c, d];
+        assert_eq!(
+            frozen.get_suffix_length_profile(&query),
+            cdawg.get_suffix_length_profile(&query)
+        );
+        let (mut cs, mut frozen_cs) = (cdawg.get_initial(), frozen.get_initial());
+        for token in query {
+            cs = cdawg.transition_and_count(cs, token);
+            frozen_cs = frozen.transition_and_count(frozen_cs, token);
+            assert_eq!(frozen_cs.length, cs.length);
+            assert_eq!(frozen_cs.target, cs.target);
+            assert_eq!(
+                frozen.get_suffix_count(frozen_cs),
+                cdawg.get_suffix_count(cs)
+            );
+        }
+
+        for cs in [frozen.get_initial(), frozen_cs] {
+            let mut next_tokens = frozen.get_next_tokens(cs);
+            let mut expected = cdawg.get_next_tokens(cs);
+            next_tokens.sort_by_key(|(token, _)| *token);
+            expected.sort_by_key(|(token, _)| *token);
+            assert_eq!(next_tokens, expected);
+        }
+
+        let ngrams = [
+            vec![a, b],
+            vec![b, c],
+            vec![a, b, c],
+            vec![c, d, a],
+            vec![d, d],
+        ];
+        let expected: Vec<_> = ngrams
+            .iter()
+            .map(|ngram| cdawg.get_ngram_count(ngram))
+            .collect();
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let counts: Vec<_> = ngrams
+                        .iter()
+                        .map(|ngram| frozen.get_ngram_count(ngram))
+                        .collect();
+                    assert_eq!(counts, expected);
+                });
+            }
+        });
+    }
+}
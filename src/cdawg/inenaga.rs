@@ -22,78 +22,260 @@
 // counts can only be computed efficiently after building has finished, and this is the format that
 // the second step expects.
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::convert::TryInto;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+use crate::cdawg::cdawg_edge::CdawgEdge;
 use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
 use crate::cdawg::cdawg_state::CdawgState;
 use crate::cdawg::comparator::CdawgComparator;
+use crate::cdawg::corpus_totals::CorpusTotals;
+use crate::cdawg::count_kind::CountKind;
+use crate::cdawg::doc_frequencies::DocFrequencies;
+use crate::cdawg::doc_spans::{DocSpan, DocSpans};
+use crate::cdawg::domain_counts::DomainCounts;
 use crate::cdawg::metadata::CdawgMetadata;
-use crate::cdawg::token_backing::TokenBacking;
+use crate::cdawg::sampling::{WeightedChoice, XorShiftRng};
+use crate::cdawg::shared_tokens::SharedTokens;
+use crate::cdawg::snapshot::Snapshot;
+use crate::cdawg::token_backing::{CdawgToken, TokenBacking};
+use crate::cdawg::tombstones::Tombstones;
+use crate::cdawg::top_substrings;
+use crate::cdawg::topological_counter::{count_doc_frequencies, count_domains};
 use crate::graph::avl_graph::edge::EdgeMutRef;
 use crate::graph::avl_graph::node::NodeMutRef;
 use crate::graph::avl_graph::AvlGraph;
-use crate::graph::indexing::{DefaultIx, EdgeIndex, IndexType, NodeIndex};
+use crate::graph::dot;
+use crate::graph::indexing::{index_bits, DefaultIx, EdgeIndex, IndexType, NodeIndex};
 use crate::graph::{EdgeRef, NodeRef};
-use crate::memory_backing::{CacheConfig, DiskBacking, MemoryBacking, RamBacking};
+use crate::memory_backing::{
+    CacheConfig, DiskBacking, DiskVec, MemoryBacking, MmapBacking, RamBacking, TierPolicy,
+    TieredBacking,
+};
+use crate::metrics;
+use crate::tokenize::DocumentSeparator;
 use crate::weight::{DefaultWeight, Weight};
 
 // TODO: Add TokenBacking for tokens
 
-pub struct Cdawg<W = DefaultWeight, Ix = DefaultIx, Mb = RamBacking<W, CdawgEdgeWeight<Ix>, Ix>>
-where
+pub struct Cdawg<
+    W = DefaultWeight,
+    Ix = DefaultIx,
+    Mb = RamBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T = u16,
+> where
     Ix: IndexType,
     W: Weight + Clone,
     Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
 {
-    tokens: Rc<RefCell<dyn TokenBacking<u16>>>,
+    tokens: Rc<RefCell<dyn TokenBacking<T>>>,
     graph: AvlGraph<W, CdawgEdgeWeight<Ix>, Ix, Mb>,
     source: NodeIndex<Ix>,
     sink: NodeIndex<Ix>,
     end_position: usize, // End position of current document.
+    snapshots: Vec<Snapshot>,
+    shared_tokens: Option<SharedTokens>, // Set if tokens are stored outside the index directory.
+    totals: Option<CorpusTotals>,        // None if loaded from metadata that didn't record them.
+    docs: Option<DocSpans>,              // None unless document spans are being tracked.
+    separator: DocumentSeparator,
+    tombstones: Tombstones, // Deleted documents, which are skipped by document queries.
+    doc_freqs: Option<DocFrequencies>, // None unless filled, or loaded from a saved file.
+    domain_counts: Option<DomainCounts>, // Likewise.
 }
 
-impl<W, Ix> Cdawg<W, Ix>
+impl<W, Ix, T> Cdawg<W, Ix, RamBacking<W, CdawgEdgeWeight<Ix>, Ix>, T>
 where
     Ix: IndexType,
     W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    T: CdawgToken,
 {
-    pub fn new(tokens: Rc<RefCell<dyn TokenBacking<u16>>>) -> Self {
+    pub fn new(tokens: Rc<RefCell<dyn TokenBacking<T>>>) -> Self {
         let mb: RamBacking<W, CdawgEdgeWeight<Ix>, Ix> = RamBacking::default();
         Self::new_mb(tokens, mb)
     }
 }
 
-impl<W, Ix> Cdawg<W, Ix, DiskBacking<W, CdawgEdgeWeight<Ix>, Ix>>
+impl<W, Ix, T> Cdawg<W, Ix, DiskBacking<W, CdawgEdgeWeight<Ix>, Ix>, T>
 where
     Ix: IndexType + Serialize + for<'de> serde::Deserialize<'de>,
     W: Weight + Copy + Serialize + for<'de> Deserialize<'de> + Clone + Default,
     CdawgEdgeWeight<Ix>: Serialize + for<'de> Deserialize<'de>,
+    T: CdawgToken,
 {
     pub fn load<P: AsRef<Path> + Clone + std::fmt::Debug>(
-        tokens: Rc<RefCell<dyn TokenBacking<u16>>>,
+        tokens: Rc<RefCell<dyn TokenBacking<T>>>,
+        path: P,
+        cache_config: CacheConfig,
+    ) -> Result<Self> {
+        let graph = AvlGraph::load(path.clone(), cache_config)?;
+        Self::from_graph(tokens, graph, path)
+    }
+
+    /// Load a CDAWG whose metadata references a shared token backing, checking its length.
+    pub fn load_shared<P: AsRef<Path> + Clone + std::fmt::Debug>(
+        path: P,
+        cache_config: CacheConfig,
+    ) -> Result<Self> {
+        let mut config_path = path.as_ref().to_path_buf();
+        config_path.push("metadata.json");
+        let config = CdawgMetadata::load_json(config_path)?;
+        let Some(shared_tokens) = config.tokens else {
+            bail!(
+                "CDAWG at {:?} does not reference a shared token backing",
+                path
+            );
+        };
+        let tokens: DiskVec<T> = DiskVec::load(shared_tokens.resolve(&path).path)?;
+        Self::load(Rc::new(RefCell::new(tokens)), path, cache_config)
+    }
+
+    /// Reopen a CDAWG built on disk so that more documents can be added, continuing from
+    /// `get_resume_point()`. `tokens` must be the tokens it was built on, and be able to grow. The
+    /// build must have stopped at the end of a document. Counts are reset to how they are during a
+    /// build, so they have to be filled again afterwards.
+    pub fn resume_from_disk<P: AsRef<Path> + Clone + std::fmt::Debug>(
+        tokens: Rc<RefCell<dyn TokenBacking<T>>>,
+        path: P,
+        cache_config: CacheConfig,
+    ) -> Result<Self> {
+        let mut config_path = path.as_ref().to_path_buf();
+        config_path.push("metadata.json");
+        let config = CdawgMetadata::load_json(config_path)?;
+        config.validate_utype::<T>()?;
+        config.validate_index_width::<Ix>()?;
+        config.validate_weight::<W>()?;
+        let shared_tokens = config.tokens.map(|shared| shared.resolve(&path));
+        let (Some(n_nodes), Some(n_edges)) = (config.n_nodes, config.n_edges) else {
+            bail!(
+                "CDAWG at {:?} was saved without its graph sizes, so it can't be resumed",
+                path
+            );
+        };
+        {
+            let tokens = tokens.borrow();
+            if tokens.len() != config.end_position {
+                bail!(
+                    "Token backing has {} tokens, but the CDAWG at {:?} was built on {}",
+                    tokens.len(),
+                    path,
+                    config.end_position
+                );
+            }
+            let ends_document = tokens.is_empty() || tokens.get(tokens.len() - 1) == T::end();
+            if config.separator.ends_documents() && !ends_document {
+                bail!(
+                    "CDAWG at {:?} stopped partway through a document, so it can't be resumed",
+                    path
+                );
+            }
+            if let Some(ref shared_tokens) = shared_tokens {
+                shared_tokens.check_len(&*tokens)?;
+            }
+        }
+
+        let graph = AvlGraph::open_mut(path, n_nodes, n_edges, cache_config)?;
+        let mut cdawg = Self {
+            tokens,
+            graph,
+            source: NodeIndex::new(config.source),
+            sink: NodeIndex::new(config.sink),
+            end_position: config.end_position,
+            snapshots: config.snapshots,
+            shared_tokens,
+            totals: config.totals,
+            docs: config.docs,
+            separator: config.separator,
+            tombstones: config.tombstones,
+            doc_freqs: None,
+            domain_counts: None,
+        };
+        cdawg.reset_counts();
+        Ok(cdawg)
+    }
+}
+
+impl<W, Ix, T> Cdawg<W, Ix, MmapBacking<W, CdawgEdgeWeight<Ix>, Ix>, T>
+where
+    Ix: IndexType + Serialize + for<'de> serde::Deserialize<'de> + 'static,
+    W: Weight + Copy + Serialize + for<'de> Deserialize<'de> + Clone + Default + 'static,
+    CdawgEdgeWeight<Ix>: Serialize + for<'de> Deserialize<'de>,
+    T: CdawgToken,
+{
+    /// Map a CDAWG saved on disk into memory, read-only.
+    pub fn load_mmap<P: AsRef<Path>>(
+        tokens: Rc<RefCell<dyn TokenBacking<T>>>,
+        path: P,
+    ) -> Result<Self> {
+        let graph = AvlGraph::load_mmap(&path)?;
+        Self::from_graph(tokens, graph, path)
+    }
+}
+
+impl<W, Ix, T> Cdawg<W, Ix, TieredBacking<W, CdawgEdgeWeight<Ix>, Ix>, T>
+where
+    Ix: IndexType + Serialize + for<'de> serde::Deserialize<'de>,
+    W: Weight + Copy + Serialize + for<'de> Deserialize<'de> + Clone + Default,
+    CdawgEdgeWeight<Ix>: Serialize + for<'de> Deserialize<'de>,
+    T: CdawgToken,
+{
+    /// Load a CDAWG saved on disk, with the states selected by `policy` pinned in RAM.
+    pub fn load_tiered<P: AsRef<Path> + Clone + std::fmt::Debug>(
+        tokens: Rc<RefCell<dyn TokenBacking<T>>>,
         path: P,
+        policy: TierPolicy,
         cache_config: CacheConfig,
     ) -> Result<Self> {
-        // Load source/sink from config file if it exists.
-        let path2 = path.clone();
-        let graph = AvlGraph::load(path, cache_config)?;
+        let graph = AvlGraph::load_tiered(path.clone(), policy, cache_config)?;
+        Self::from_graph(tokens, graph, path)
+    }
+}
 
-        let mut config_path = path2.as_ref().to_path_buf();
+impl<W, Ix, Mb, T> Cdawg<W, Ix, Mb, T>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    Mb::EdgeRef: Copy,
+    T: CdawgToken,
+{
+    // Attach the metadata saved at `path`, if there is any, to a loaded graph.
+    fn from_graph<P: AsRef<Path>>(
+        tokens: Rc<RefCell<dyn TokenBacking<T>>>,
+        graph: AvlGraph<W, CdawgEdgeWeight<Ix>, Ix, Mb>,
+        path: P,
+    ) -> Result<Self> {
+        let mut config_path = path.as_ref().to_path_buf();
         config_path.push("metadata.json");
         if config_path.exists() {
-            // FIXME(#98): This will fail silently if config file exists but is empty.
             let config = CdawgMetadata::load_json(config_path)?;
+            config.validate_utype::<T>()?;
+            config.validate_index_width::<Ix>()?;
+            config.validate_weight::<W>()?;
+            let shared_tokens = config.tokens.map(|shared| shared.resolve(&path));
+            if let Some(ref shared_tokens) = shared_tokens {
+                shared_tokens.check_len(&*tokens.borrow())?;
+            }
             Ok(Self {
                 tokens,
                 graph,
                 source: NodeIndex::new(config.source),
                 sink: NodeIndex::new(config.sink),
                 end_position: config.end_position,
+                snapshots: config.snapshots,
+                shared_tokens,
+                totals: config.totals,
+                docs: config.docs,
+                separator: config.separator,
+                tombstones: config.tombstones,
+                doc_freqs: DocFrequencies::load(&path)?,
+                domain_counts: DomainCounts::load(&path)?,
             })
         } else {
             Ok(Self {
@@ -102,19 +284,45 @@ where
                 source: NodeIndex::new(0),
                 sink: NodeIndex::new(1),
                 end_position: 0,
+                snapshots: Vec::new(),
+                shared_tokens: None,
+                totals: None,
+                docs: None,
+                separator: DocumentSeparator::default(),
+                tombstones: Tombstones::default(),
+                doc_freqs: None,
+                domain_counts: None,
             })
         }
     }
-}
 
-impl<W, Ix, Mb> Cdawg<W, Ix, Mb>
-where
-    Ix: IndexType,
-    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
-    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
-    Mb::EdgeRef: Copy,
-{
-    pub fn new_mb(tokens: Rc<RefCell<dyn TokenBacking<u16>>>, mb: Mb) -> Cdawg<W, Ix, Mb> {
+    // Wrap a graph built other than by `update`, e.g. converted from a DAWG. Its sinks, document
+    // self-loops and counts must be as `build` leaves them, with `sink` the sink of the next document.
+    pub(crate) fn from_parts(
+        tokens: Rc<RefCell<dyn TokenBacking<T>>>,
+        graph: AvlGraph<W, CdawgEdgeWeight<Ix>, Ix, Mb>,
+        (source, sink): (NodeIndex<Ix>, NodeIndex<Ix>),
+        end_position: usize,
+        totals: CorpusTotals,
+    ) -> Self {
+        Self {
+            tokens,
+            graph,
+            source,
+            sink,
+            end_position,
+            snapshots: Vec::new(),
+            shared_tokens: None,
+            totals: Some(totals),
+            docs: None,
+            separator: DocumentSeparator::default(),
+            tombstones: Tombstones::default(),
+            doc_freqs: None,
+            domain_counts: None,
+        }
+    }
+
+    pub fn new_mb(tokens: Rc<RefCell<dyn TokenBacking<T>>>, mb: Mb) -> Cdawg<W, Ix, Mb, T> {
         let mut graph: AvlGraph<W, CdawgEdgeWeight<Ix>, Ix, Mb> = AvlGraph::new_mb(mb);
         let source = graph.add_node(W::new(0, None, 0));
         // FIXME: Hacky type conversion for sink failure.
@@ -125,16 +333,24 @@ where
             source,
             sink,
             end_position: 0,
+            snapshots: Vec::new(),
+            shared_tokens: None,
+            totals: Some(CorpusTotals::default()),
+            docs: None,
+            separator: DocumentSeparator::default(),
+            tombstones: Tombstones::default(),
+            doc_freqs: None,
+            domain_counts: None,
         }
     }
 
     pub fn with_capacity_mb(
-        tokens: Rc<RefCell<dyn TokenBacking<u16>>>,
+        tokens: Rc<RefCell<dyn TokenBacking<T>>>,
         mb: Mb,
         n_nodes: usize,
         n_edges: usize,
         cache_config: CacheConfig,
-    ) -> Cdawg<W, Ix, Mb> {
+    ) -> Cdawg<W, Ix, Mb, T> {
         let mut graph: AvlGraph<W, CdawgEdgeWeight<Ix>, Ix, Mb> =
             AvlGraph::with_capacity_mb(mb, n_nodes, n_edges, cache_config);
         let source = graph.add_node(W::new(0, None, 0));
@@ -146,6 +362,14 @@ where
             source,
             sink,
             end_position: 0,
+            snapshots: Vec::new(),
+            shared_tokens: None,
+            totals: Some(CorpusTotals::default()),
+            docs: None,
+            separator: DocumentSeparator::default(),
+            tombstones: Tombstones::default(),
+            doc_freqs: None,
+            domain_counts: None,
         }
     }
 
@@ -155,10 +379,30 @@ where
         let length = self.tokens.borrow().len();
         for idx in 1..length + 1 {
             (state, start) = self.update(state, start, idx);
-            if self.tokens.borrow().get(idx - 1) == u16::MAX {
+            if self.tokens.borrow().get(idx - 1) == T::end() {
+                (state, start) = self.end_document(idx, idx);
+            }
+        }
+    }
+
+    /// Like `build`, but calls `callback` with the number of tokens added every `every` tokens.
+    /// Stops early if the callback fails.
+    pub fn build_with_callback<F>(&mut self, every: usize, mut callback: F) -> Result<()>
+    where
+        F: FnMut(&Self, usize) -> Result<()>,
+    {
+        let (mut state, mut start) = (self.source, 1);
+        let length = self.tokens.borrow().len();
+        for idx in 1..length + 1 {
+            (state, start) = self.update(state, start, idx);
+            if self.tokens.borrow().get(idx - 1) == T::end() {
                 (state, start) = self.end_document(idx, idx);
             }
+            if every != 0 && idx % every == 0 {
+                callback(self, idx)?;
+            }
         }
+        Ok(())
     }
 
     pub fn update(
@@ -202,7 +446,7 @@ where
             }
 
             // 1) Add a new OPEN edge from r to sink (that can grow via pointer).
-            // Should work correctly when tokens[end - 1] is u16::MAX.
+            // Should work correctly when tokens[end - 1] is the end-of-document token.
             self.add_balanced_edge(r, self.sink, (end, Ix::max_value().index()));
 
             // 2) Set failure transition.
@@ -234,6 +478,13 @@ where
         let weight = (idx, doc_id); // doc_id is basically a label for node
         self.add_balanced_edge(self.sink, self.sink, weight);
 
+        if let Some(ref mut totals) = self.totals {
+            totals.end_document(idx);
+        }
+        if let Some(ref mut docs) = self.docs {
+            docs.end_document(idx, doc_id, self.sink.index());
+        }
+
         let source = NodeIndex::new(self.source.index());
         self.sink = self.graph.add_node(W::new(0, Some(source), 1));
         (self.source, idx + 1)
@@ -417,7 +668,7 @@ where
         &self,
         state: Option<NodeIndex<Ix>>,
         gamma: (usize, usize),
-        token: u16,
+        token: T,
     ) -> bool {
         let (start, end) = gamma;
         if start <= end {
@@ -434,7 +685,7 @@ where
 
             // No +1 because 0-indexed.
             let existing_token = self.tokens.borrow().get(found_start + end - start);
-            if token != u16::MAX || existing_token != u16::MAX {
+            if token != T::end() || existing_token != T::end() {
                 token == existing_token
             } else {
                 // Compare based on whether these are the same end-of-text tokens.
@@ -445,7 +696,7 @@ where
                 Some(phi) => {
                     // token == tokens[end]
                     // let edge_idx = self.get_edge_by_token(phi, token);
-                    let edge_idx = if token != u16::MAX {
+                    let edge_idx = if token != T::end() {
                         self.get_edge_by_token(phi, token)
                     } else {
                         self.get_edge_by_token_index(phi, end)
@@ -473,7 +724,11 @@ where
 
     // Get the Inenaga-indexed span associated with an edge.
     // Maybe make this a macro?
-    fn get_span(&self, weight: CdawgEdgeWeight<Ix>, target: NodeIndex<Ix>) -> (usize, usize) {
+    pub(crate) fn get_span(
+        &self,
+        weight: CdawgEdgeWeight<Ix>,
+        target: NodeIndex<Ix>,
+    ) -> (usize, usize) {
         let (start, end) = weight.get_span();
         // Shift to 1-indexed and retrieve value of end pointer.
         if end < Ix::max_value().index() {
@@ -504,8 +759,75 @@ where
         (span.0, span.1, target)
     }
 
+    /// The tokens on an edge, with open-ended spans resolved to where their document ends.
+    pub fn resolve_span(&self, edge_idx: EdgeIndex<Ix>) -> Vec<T> {
+        let (start, end, _) = self.get_start_end_target(edge_idx);
+        let tokens = self.tokens.borrow();
+        (start - 1..end).map(|pos| tokens.get(pos)).collect()
+    }
+
+    /// The edges out of `state` with their tokens decoded. The document self-loops on sinks are
+    /// left out, as in `to_dot`.
+    pub fn iter_edges(&self, state: NodeIndex<Ix>) -> impl Iterator<Item = CdawgEdge<Ix, T>> + '_ {
+        self.graph
+            .edges(state)
+            .filter(move |edge| edge.get_target() != state)
+            .map(move |edge| {
+                let target = edge.get_target();
+                let (start, end) = self.get_span(edge.get_weight(), target);
+                let tokens: Vec<T> = {
+                    let backing = self.tokens.borrow();
+                    (start - 1..end).map(|pos| backing.get(pos)).collect()
+                };
+                CdawgEdge {
+                    first_token: tokens[0],
+                    span_len: tokens.len(),
+                    target,
+                    tokens,
+                }
+            })
+    }
+
+    /// Render the CDAWG in GraphViz's DOT language. Edges are labelled with their token spans,
+    /// decoded with `token_decoder`. The document self-loops on sinks are left out.
+    pub fn to_dot<Wr, D>(&self, writer: &mut Wr, token_decoder: D) -> Result<()>
+    where
+        Wr: Write,
+        D: Fn(T) -> String,
+    {
+        dot::write_header(writer)?;
+        for idx in 0..self.node_count() {
+            let state = NodeIndex::new(idx);
+            let length = self.graph.get_node(state).get_length();
+            dot::write_state(writer, idx, length, self.graph.get_node(state).get_count())?;
+        }
+        let tokens = self.tokens.borrow();
+        for idx in 0..self.node_count() {
+            let state = NodeIndex::new(idx);
+            for edge in self.graph.edges(state) {
+                let target = edge.get_target();
+                if target == state {
+                    continue;
+                }
+                let (start, end) = self.get_span(edge.get_weight(), target);
+                let label: Vec<String> = (start - 1..end)
+                    .map(|pos| match tokens.get(pos) {
+                        token if token == T::end() => "<eos>".to_string(),
+                        token => token_decoder(token),
+                    })
+                    .collect();
+                dot::write_edge(writer, idx, target.index(), &label.join(" "))?;
+            }
+            if let Some(failure) = self.graph.get_node(state).get_failure() {
+                dot::write_failure(writer, idx, failure.index())?;
+            }
+        }
+        dot::write_footer(writer)
+    }
+
     // Convenience methods.
 
+    /// The underlying graph, whose layout may change. Prefer `walker()` for custom traversals.
     pub fn get_graph(&self) -> &AvlGraph<W, CdawgEdgeWeight<Ix>, Ix, Mb> {
         &self.graph
     }
@@ -534,10 +856,11 @@ where
     }
 
     // Only well-defined when token is not end-of-text.
-    pub fn get_edge_by_token(&self, state: NodeIndex<Ix>, token: u16) -> Option<EdgeIndex<Ix>> {
-        if token != u16::MAX {
+    pub fn get_edge_by_token(&self, state: NodeIndex<Ix>, token: T) -> Option<EdgeIndex<Ix>> {
+        if token != T::end() {
             let weight = CdawgEdgeWeight::new(0, 0); // Doesn't matter.
-            let cmp = CdawgComparator::new_with_token(self.tokens.clone(), token);
+            let cmp: CdawgComparator<T> =
+                CdawgComparator::new_with_token(self.tokens.clone(), token);
             self.graph
                 .get_edge_by_weight_cmp(state, weight, Box::new(cmp))
         } else {
@@ -553,7 +876,7 @@ where
     ) -> Option<EdgeIndex<Ix>> {
         let weight = CdawgEdgeWeight::new(token_idx, token_idx + 1);
         let token = self.tokens.borrow().get(token_idx);
-        let cmp = CdawgComparator::new_with_token(self.tokens.clone(), token);
+        let cmp: CdawgComparator<T> = CdawgComparator::new_with_token(self.tokens.clone(), token);
         self.graph
             .get_edge_by_weight_cmp(state, weight, Box::new(cmp))
     }
@@ -567,7 +890,7 @@ where
         // We should have gamma.0 <= gamma.1
         let weight = self._new_edge_weight(gamma.0, gamma.1);
         let token = self.tokens.borrow().get(gamma.0 - 1); // Map to 0-indexed
-        let cmp = CdawgComparator::new_with_token(self.tokens.clone(), token);
+        let cmp: CdawgComparator<T> = CdawgComparator::new_with_token(self.tokens.clone(), token);
         self.graph
             .add_balanced_edge_cmp(state, target, weight, Box::new(cmp))
     }
@@ -587,18 +910,19 @@ where
     }
 
     // Transition and track length analogously to the DAWG.
-    pub fn transition_and_count(&self, mut cs: CdawgState<Ix>, token: u16) -> CdawgState<Ix> {
-        if cs.target.is_none() {
+    pub fn transition_and_count(&self, mut cs: CdawgState<Ix>, token: T) -> CdawgState<Ix> {
+        let Some(target) = cs.target else {
             // Corresponds to the case where we are in the null state after failing.
-            self.get_initial()
-        } else if cs.start == cs.end {
+            return self.get_initial();
+        };
+        if cs.start == cs.end {
             // We are at a state. Analogous to DAWG case.
-            let e = self.get_edge_by_token(cs.target.unwrap(), token);
+            let e = self.get_edge_by_token(target, token);
             if let Some(e_val) = e {
                 let edge = self.graph.get_edge(e_val);
                 let gamma = self.get_span(edge.get_weight(), edge.get_target());
                 return CdawgState {
-                    state: cs.target.unwrap(),
+                    state: target,
                     edge_start: gamma.0 - 1, // -1 for 0-indexing
                     start: gamma.0,          // -1 for 0-indexing, +1 to increment
                     end: gamma.1,
@@ -606,7 +930,7 @@ where
                     length: cs.length + 1,
                 };
             }
-            let fail_cs = self.implicitly_fail(cs.target.unwrap(), (cs.end, cs.end));
+            let fail_cs = self.implicitly_fail(target, (cs.end, cs.end));
             self.transition_and_count(fail_cs, token)
         } else {
             // We are on an edge.
@@ -621,6 +945,26 @@ where
         }
     }
 
+    /// Transition like `transition_and_count`, but only accept matches that are visible in the
+    /// first `snapshot.end_position` tokens. This is an approximation: see `cdawg::snapshot`.
+    pub fn transition_and_count_at(
+        &self,
+        cs: CdawgState<Ix>,
+        token: T,
+        snapshot: &Snapshot,
+    ) -> CdawgState<Ix> {
+        let mut cs = self.transition_and_count(cs, token);
+        // cs.start is the exclusive end of the occurrence on the current edge label.
+        while cs.length > 0 && cs.start > snapshot.end_position {
+            cs = if cs.start == cs.end {
+                self.implicitly_fail(cs.target.unwrap(), (cs.end, cs.end))
+            } else {
+                self.implicitly_fail(cs.state, (cs.edge_start, cs.start))
+            };
+        }
+        cs
+    }
+
     // Inference-time version of canonize. Crucially:
     //   1. returns target state.
     fn inference_canonize(
@@ -718,164 +1062,867 @@ where
         }
     }
 
-    pub fn get_count(&self, state: NodeIndex<Ix>) -> usize {
-        self.graph.get_node(state).get_count()
+    /// Record the current size of the CDAWG as a snapshot marker.
+    pub fn take_snapshot(&mut self) -> Snapshot {
+        let snapshot = Snapshot {
+            end_position: self.end_position,
+            n_nodes: self.node_count(),
+            n_edges: self.edge_count(),
+        };
+        self.snapshots.push(snapshot);
+        snapshot
     }
 
-    pub fn set_count(&mut self, state: NodeIndex<Ix>, count: usize) {
-        self.graph.get_node_mut(state).set_count(count);
+    pub fn get_snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
     }
 
-    ///Save metadata
-    pub fn save_metadata<P: AsRef<Path> + Clone>(&self, path: P) -> Result<()> {
-        let mut config_path = path.as_ref().to_path_buf();
-        config_path.push("metadata.json");
-        let config = CdawgMetadata {
-            source: self.source.index(),
-            sink: self.sink.index(),
-            end_position: self.end_position,
-        };
-        config.save_json(config_path)
+    /// Reference the token backing at `path` from the metadata instead of requiring callers to
+    /// pass it in. Call once the tokens are fully written, since this hashes them.
+    pub fn share_tokens<P: Into<PathBuf>>(&mut self, path: P) {
+        self.shared_tokens = Some(SharedTokens::new(path, &*self.tokens.borrow()));
     }
 
-    // TODO(#100): Refactor these into an Infinigram class that wraps a Cdawg
+    pub fn get_shared_tokens(&self) -> Option<&SharedTokens> {
+        self.shared_tokens.as_ref()
+    }
 
-    /// Get the count of the suffix matched by a CdawgState.
-    pub fn get_suffix_count(&self, cs: CdawgState<Ix>) -> usize {
-        self.get_count(cs.target.unwrap())
+    /// The state and start position to continue building from, as returned by `end_document`.
+    pub fn get_resume_point(&self) -> (NodeIndex<Ix>, usize) {
+        (self.source, self.end_position + 1)
     }
 
-    /// Get the entropy of a CDAWG state in bits.
-    pub fn get_entropy(&self, cs: CdawgState<Ix>) -> f64 {
-        let (state, gamma) = cs.get_state_and_gamma();
-        if gamma.0 != gamma.1 {
-            return 0.;
+    /// Undo filling counts, so that building can continue: sinks, whose only edges are document
+    /// self-loops, count 1 unless their document was deleted, and all other states count 0.
+    pub fn reset_counts(&mut self) {
+        for idx in 0..self.node_count() {
+            let state = NodeIndex::new(idx);
+            let is_sink = state != self.source && self.graph.neighbors(state).all(|q| q == state);
+            let count = is_sink && !self.is_deleted_sink(state);
+            self.set_count(state, count as usize);
         }
+    }
 
-        let q = state.unwrap();
-        let denom = self.get_count(q);
-        let mut sum = 0.;
-        for next_state in self.get_graph().neighbors(q) {
-            let prob = (self.get_count(next_state) as f64) / (denom as f64);
-            sum -= prob * f64::log2(prob);
+    /// Copy the states with count at least `min_count` into a new CDAWG in RAM that shares this
+    /// one's tokens. Counts must be filled. Every implicit state on an edge has the same count as the
+    /// edge's target, so dropping the edges into pruned states drops exactly the infrequent
+    /// substrings. The source and the current sink are always kept, so that the copy is well formed.
+    pub fn prune_by_count(
+        &self,
+        min_count: usize,
+    ) -> Cdawg<W, Ix, RamBacking<W, CdawgEdgeWeight<Ix>, Ix>, T> {
+        let mut graph: AvlGraph<
+            W,
+            CdawgEdgeWeight<Ix>,
+            Ix,
+            RamBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        > = AvlGraph::new_mb(RamBacking::default());
+        let mut new_states: Vec<Option<NodeIndex<Ix>>> = vec![None; self.node_count()];
+        for (idx, new_state) in new_states.iter_mut().enumerate() {
+            let state = NodeIndex::new(idx);
+            if state == self.source || state == self.sink || self.get_count(state) >= min_count {
+                *new_state = Some(graph.add_node(W::new(0, None, 0)));
+            }
         }
-        sum
-    }
 
-    pub fn get_next_tokens(&self, cs: CdawgState<Ix>) -> Vec<(u16, f64)> {
-        let (state, gamma) = cs.get_state_and_gamma();
-        if gamma.0 != gamma.1 {
-            let token = self.tokens.borrow().get(gamma.1);
-            return vec![(token, 1.)];
+        for (idx, new_state) in new_states.iter().enumerate() {
+            let Some(new_state) = *new_state else {
+                continue;
+            };
+            let state = NodeIndex::new(idx);
+            let node = self.graph.get_node(state).get_weight();
+            // Failures represent suffixes, which are at least as frequent.
+            let failure = node
+                .get_failure()
+                .and_then(|failure| new_states[failure.index()]);
+            graph.get_node_mut(new_state).set_length(node.get_length());
+            graph.get_node_mut(new_state).set_failure(failure);
+            graph.get_node_mut(new_state).set_count(node.get_count());
+            graph
+                .get_node_mut(new_state)
+                .set_terminal(node.is_terminal());
+            for edge in self.graph.edges(state) {
+                let Some(new_target) = new_states[edge.get_target().index()] else {
+                    continue;
+                };
+                let weight = edge.get_weight();
+                let token = self.tokens.borrow().get(weight.get_span().0);
+                let cmp = CdawgComparator::new_with_token(self.tokens.clone(), token);
+                graph.add_balanced_edge_cmp(new_state, new_target, weight, Box::new(cmp));
+            }
         }
 
-        let q = state.unwrap();
-        let denom = self.get_count(q);
-        let mut tokens = Vec::new();
-        for edge in self.get_graph().edges(q) {
-            // let edge_ref = self.graph.get_edge(edge_idx);
-            let next_state = edge.get_target();
-            let span = self.get_span(edge.get_weight(), next_state);
-            let token = self.tokens.borrow().get(span.0 - 1); // Shift to 0 indexing.
-            let prob = (self.get_count(next_state) as f64) / (denom as f64);
-            tokens.push((token, prob));
+        let doc_freqs = self.doc_freqs.as_ref().map(|doc_freqs| {
+            let mut new_doc_freqs = vec![0; graph.node_count()];
+            for (idx, new_state) in new_states.iter().enumerate() {
+                if let Some(new_state) = new_state {
+                    new_doc_freqs[new_state.index()] = doc_freqs.get(idx);
+                }
+            }
+            DocFrequencies::Ram(new_doc_freqs)
+        });
+        let domain_counts = self.domain_counts.as_ref().map(|domain_counts| {
+            let layout = domain_counts.get_layout().clone();
+            let mut new_values = vec![0; graph.node_count() * layout.row_len()];
+            for (idx, new_state) in new_states.iter().enumerate() {
+                if let Some(new_state) = new_state {
+                    let row_start = new_state.index() * layout.row_len();
+                    for (cell, count) in domain_counts.get_row(idx).into_iter().enumerate() {
+                        new_values[row_start + cell] = count;
+                    }
+                }
+            }
+            DomainCounts::new(layout, new_values)
+        });
+        let docs = self.docs.clone().map(|mut docs| {
+            docs.remap_sinks(|sink| new_states[sink].map(|state| state.index()));
+            docs
+        });
+        Cdawg {
+            tokens: self.tokens.clone(),
+            graph,
+            source: new_states[self.source.index()].unwrap(),
+            sink: new_states[self.sink.index()].unwrap(),
+            end_position: self.end_position,
+            snapshots: Vec::new(), // Snapshots record sizes of the unpruned graph.
+            shared_tokens: self.shared_tokens.clone(),
+            totals: self.totals.clone(),
+            docs,
+            separator: self.separator,
+            tombstones: self.tombstones.clone(),
+            doc_freqs,
+            domain_counts,
         }
-        tokens
     }
-}
 
-#[cfg(test)]
-#[allow(unused_variables)]
-#[allow(unused_imports)]
-#[allow(unused_assignments)]
-mod tests {
-    use super::*;
-    use crate::cdawg::TopologicalCounter;
-    use crate::memory_backing::DiskVec;
-    use tempfile::tempdir;
+    /// Record the token span of each document added from now on. Call before building.
+    pub fn track_documents(&mut self) {
+        self.docs.get_or_insert_with(DocSpans::default);
+    }
 
-    macro_rules! get_edge {
-        // `()` indicates that the macro takes no argument.
-        ($c:expr, $q:expr, $w:expr) => {
-            $c.graph.get_edge($c.get_edge_by_token($q, $w).unwrap())
-        };
+    pub fn get_doc_spans(&self) -> Option<&DocSpans> {
+        self.docs.as_ref()
     }
 
-    macro_rules! get_span {
-        ($c:expr, $e:expr) => {
-            $c.get_span($e.get_weight(), $e.get_target())
+    /// The span and sink of the first document labelled `doc_id` that hasn't been deleted, or None
+    /// if there is none. Requires the CDAWG to have been built with `track_documents`.
+    pub fn get_document_span(&self, doc_id: usize) -> Result<Option<DocSpan>> {
+        let Some(ref docs) = self.docs else {
+            bail!("This CDAWG was not built with document spans");
         };
+        Ok(docs
+            .get_by_id(doc_id)
+            .find(|span| !self.tombstones.contains(span.end)))
     }
 
-    type Span = (usize, usize);
+    /// Number of completed documents that haven't been deleted.
+    pub fn get_document_count(&self) -> Result<usize> {
+        match (&self.docs, &self.totals) {
+            (Some(docs), _) => Ok(docs
+                .iter()
+                .filter(|span| !self.tombstones.contains(span.end))
+                .count()),
+            (None, Some(totals)) => Ok(totals.n_docs),
+            (None, None) => bail!("This CDAWG's metadata records neither documents nor totals"),
+        }
+    }
 
-    fn to_inenaga(gamma: Span) -> Span {
-        (gamma.0 + 1, gamma.1)
+    pub fn get_separator(&self) -> DocumentSeparator {
+        self.separator
     }
 
-    #[test]
-    fn test_canonize() {
-        // Test canonize, which uses 1-indexing!
-        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![0, 1, 2])));
-        let q = cdawg
-            .graph
-            .add_node(DefaultWeight::new(1, Some(cdawg.source), 0));
-        cdawg.add_balanced_edge(cdawg.source, q, (1, 1));
-        cdawg.add_balanced_edge(q, cdawg.sink, (2, 3));
+    /// Record how documents are separated in the tokens. Call before building.
+    pub fn set_separator(&mut self, separator: DocumentSeparator) {
+        self.separator = separator;
+    }
 
-        let (mut state, mut start) = cdawg.canonize(None, (1, 1));
-        assert_eq!(state.unwrap().index(), cdawg.source.index());
-        assert_eq!(start, 2);
+    /// Whether `token` marks the end of a document: either the reserved end-of-document token, or
+    /// the EOS token if the CDAWG was built with `DocumentSeparator::Eos`.
+    pub fn is_document_end(&self, token: T) -> bool {
+        token == T::end() || self.separator.is_boundary(token)
+    }
 
-        (state, start) = cdawg.canonize(None, (1, 0));
-        assert_eq!(state, None);
-        assert_eq!(start, 1);
+    pub fn get_count(&self, state: NodeIndex<Ix>) -> usize {
+        self.graph.get_node(state).get_count()
+    }
 
-        (state, start) = cdawg.canonize(Some(cdawg.source), (1, 0));
-        assert_eq!(state.unwrap().index(), cdawg.source.index());
-        assert_eq!(start, 1);
+    /// Get the token at 0-indexed position `idx` in the corpus.
+    pub fn get_token(&self, idx: usize) -> T {
+        self.tokens.borrow().get(idx)
+    }
 
-        (state, start) = cdawg.canonize(Some(cdawg.source), (1, 1));
-        assert_eq!(state.unwrap().index(), q.index());
-        assert_eq!(start, 2); // No tokens remaining.
+    // Whether an edge's label starts with an end-of-document token.
+    pub fn is_sentinel_edge(&self, weight: CdawgEdgeWeight<Ix>) -> bool {
+        let (start, _) = weight.get_span();
+        self.tokens.borrow().get(start) == T::end()
+    }
 
-        (state, start) = cdawg.canonize(Some(cdawg.source), (1, 2));
-        assert_eq!(state.unwrap().index(), q.index());
-        assert_eq!(start, 2); // One token remaining.
+    /// Whether some string in the state is a document suffix. Set when counts are filled.
+    pub fn is_terminal(&self, state: NodeIndex<Ix>) -> bool {
+        self.graph.get_node(state).is_terminal()
+    }
 
-        (state, start) = cdawg.canonize(Some(cdawg.source), (1, 3));
-        assert_eq!(state.unwrap().index(), cdawg.sink.index());
-        assert_eq!(start, 4); // No tokens remaining.
+    pub fn set_terminal(&mut self, state: NodeIndex<Ix>, terminal: bool) {
+        self.graph.get_node_mut(state).set_terminal(terminal);
+    }
 
-        (state, start) = cdawg.canonize(Some(q), (2, 2));
-        assert_eq!(state.unwrap().index(), q.index());
-        assert_eq!(start, 2); // One token remaining.
+    /// Number of documents that end with the strings in the state.
+    pub fn terminal_count(&self, state: NodeIndex<Ix>) -> usize {
+        if !self.is_terminal(state) {
+            return 0;
+        }
+        // Each end-of-document edge leads to the sink of a distinct document. Sinks also have a
+        // self-loop labelling their document, which is skipped.
+        self.graph
+            .edges(state)
+            .filter(|edge| edge.get_target() != state && self.is_sentinel_edge(edge.get_weight()))
+            .filter(|edge| !self.is_deleted_sink(edge.get_target()))
+            .count()
+    }
 
-        (state, start) = cdawg.canonize(Some(q), (2, 3));
-        assert_eq!(state.unwrap().index(), cdawg.sink.index());
-        assert_eq!(start, 4); // No tokens remaining.
+    /// Whether the self-loop `weight` of a sink labels a deleted document.
+    pub fn is_deleted_loop(&self, weight: CdawgEdgeWeight<Ix>) -> bool {
+        let (end, _) = weight.get_span();
+        self.tombstones.contains(end)
     }
 
-    #[test]
-    fn test_check_end_point() {
-        // This is 1-indexed!
-        let mut cdawg: Cdawg =
-            Cdawg::new(Rc::new(RefCell::new(vec![0, 1, 2, u16::MAX, 2, u16::MAX])));
-        cdawg.add_balanced_edge(cdawg.source, cdawg.sink, (1, 4));
-        assert!(cdawg.check_end_point(Some(cdawg.source), to_inenaga((0, 0)), 0));
-        assert!(!cdawg.check_end_point(Some(cdawg.source), to_inenaga((0, 0)), 1));
-        assert!(cdawg.check_end_point(Some(cdawg.source), to_inenaga((0, 1)), 1));
-        assert!(!cdawg.check_end_point(Some(cdawg.source), to_inenaga((0, 1)), 0));
+    // Whether `state` is the sink of a deleted document.
+    fn is_deleted_sink(&self, state: NodeIndex<Ix>) -> bool {
+        !self.tombstones.is_empty()
+            && self
+                .graph
+                .edges(state)
+                .any(|edge| edge.get_target() == state && self.is_deleted_loop(edge.get_weight()))
+    }
 
-        // From the null state, anything should return true.
-        assert!(cdawg.check_end_point(None, to_inenaga((0, 0)), 0));
-        assert!(cdawg.check_end_point(None, to_inenaga((0, 0)), 1));
-        assert!(cdawg.check_end_point(None, to_inenaga((0, 0)), 2));
+    pub fn get_tombstones(&self) -> &Tombstones {
+        &self.tombstones
+    }
 
-        // Check with end-of-text tokens on edge.
-        assert!(cdawg.check_end_point(Some(cdawg.source), (1, 3), u16::MAX));
-        // Has not been inserted yet.
-        assert!(!cdawg.check_end_point(Some(cdawg.source), (1, 5), u16::MAX));
+    /// Delete every completed document labelled `doc_id`, returning how many were deleted. IDs need
+    /// not be unique, so with the Pile reader this deletes a whole domain. Counts must be filled.
+    ///
+    /// The occurrences in deleted documents are subtracted from the counts exactly, and the
+    /// documents are left out of `locate` and document queries from then on. Their tokens and
+    /// states are kept, so this takes one pass over the graph rather than a rebuild. Save the
+    /// metadata afterwards to keep the deletion.
+    pub fn delete_document(&mut self, doc_id: usize) -> Result<usize> {
+        if self.get_count(self.source) == 0 {
+            bail!("Counts must be filled before deleting documents");
+        }
+
+        // The number of occurrences of a state's strings in the deleted documents is its number of
+        // paths to their sinks. States are visited in postorder, like in TopologicalCounter.
+        let mut paths: Vec<Option<usize>> = vec![None; self.node_count()];
+        let mut deleted = Vec::new(); // End position and sink length of each deleted document.
+        let mut stack = vec![(self.source, true)];
+        while let Some((state, open)) = stack.pop() {
+            if open {
+                if paths[state.index()].is_some() {
+                    continue;
+                }
+                paths[state.index()] = Some(0);
+                stack.push((state, false));
+                for next_state in self.graph.neighbors(state) {
+                    if next_state != state {
+                        stack.push((next_state, true));
+                    }
+                }
+            } else {
+                let mut n_paths = 0;
+                for edge in self.graph.edges(state) {
+                    let next_state = edge.get_target();
+                    if next_state != state {
+                        n_paths += paths[next_state.index()].unwrap_or(0);
+                        continue;
+                    }
+                    let (end, id) = edge.get_weight().get_span();
+                    if id == doc_id && !self.tombstones.contains(end) {
+                        let length = self.graph.get_node(state).get_length();
+                        deleted.push((end, length as usize));
+                        n_paths += 1;
+                    }
+                }
+                paths[state.index()] = Some(n_paths);
+            }
+        }
+
+        for (idx, n_paths) in paths.into_iter().enumerate() {
+            let n_paths = n_paths.unwrap_or(0);
+            if n_paths > 0 {
+                let state = NodeIndex::new(idx);
+                self.set_count(state, self.get_count(state) - n_paths);
+            }
+        }
+        for (end, length) in deleted.iter() {
+            self.tombstones.insert(*end);
+            if let Some(ref mut totals) = self.totals {
+                // The sink's length covers the whole document, including its end-of-document token.
+                totals.delete_document(length - 1);
+            }
+        }
+        if !deleted.is_empty() && self.doc_freqs.is_some() {
+            self.set_doc_frequencies(count_doc_frequencies(self));
+        }
+        if !deleted.is_empty() {
+            if let Some(layout) = self
+                .domain_counts
+                .as_ref()
+                .map(|dc| dc.get_layout().clone())
+            {
+                self.set_domain_counts(count_domains(self, layout));
+            }
+        }
+        Ok(deleted.len())
+    }
+
+    /// Statistics this CDAWG can answer count queries with.
+    pub fn get_count_kinds(&self) -> Vec<CountKind> {
+        let mut kinds = vec![CountKind::Occurrences];
+        if self.doc_freqs.is_some() {
+            kinds.push(CountKind::Documents);
+        }
+        kinds
+    }
+
+    /// Get the count of a state under the statistic selected by `kind`.
+    pub fn get_count_by_kind(&self, state: NodeIndex<Ix>, kind: CountKind) -> Result<usize> {
+        match kind {
+            CountKind::Occurrences => Ok(self.get_count(state)),
+            CountKind::Documents if self.doc_freqs.is_some() => self.get_doc_frequency(state),
+            _ => bail!("This CDAWG was not built with {} counts", kind),
+        }
+    }
+
+    /// Number of live documents that the strings in the state occur in. Document frequencies must
+    /// have been filled with `TopologicalCounter::fill_doc_frequencies`, or saved with the graph.
+    pub fn get_doc_frequency(&self, state: NodeIndex<Ix>) -> Result<usize> {
+        let Some(ref doc_freqs) = self.doc_freqs else {
+            bail!("Document frequencies have not been filled");
+        };
+        Ok(doc_freqs.get(state.index()))
+    }
+
+    pub fn set_doc_frequencies(&mut self, doc_freqs: Vec<usize>) {
+        self.doc_freqs = Some(DocFrequencies::Ram(doc_freqs));
+    }
+
+    // Save the document frequencies next to the graph at `path`, or remove stale ones saved there
+    // before if there are none.
+    fn save_doc_frequencies<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        match self.doc_freqs {
+            Some(ref doc_freqs) => doc_freqs.save(path),
+            None => DocFrequencies::remove(&DocFrequencies::get_path(path)),
+        }
+    }
+
+    /// Count of the strings in the state in each domain that they occur in, as (domain, count)
+    /// pairs sorted by domain. Domains are the document IDs assigned by the data reader, and domain
+    /// counts must have been filled with `TopologicalCounter::fill_domain_counts`, or saved with
+    /// the graph. If they were sketched, counts may be too high but never too low.
+    pub fn get_domain_counts(&self, state: NodeIndex<Ix>) -> Result<Vec<(usize, usize)>> {
+        let Some(ref domain_counts) = self.domain_counts else {
+            bail!("Domain counts have not been filled");
+        };
+        Ok(domain_counts.get_all(state.index()))
+    }
+
+    /// Like `get_domain_counts`, but only the count in `domain`.
+    pub fn get_domain_count(&self, state: NodeIndex<Ix>, domain: usize) -> Result<usize> {
+        let Some(ref domain_counts) = self.domain_counts else {
+            bail!("Domain counts have not been filled");
+        };
+        Ok(domain_counts.get(state.index(), domain))
+    }
+
+    /// Whether every domain is counted exactly, or None if domain counts haven't been filled.
+    pub fn has_exact_domain_counts(&self) -> Option<bool> {
+        self.domain_counts
+            .as_ref()
+            .map(|domain_counts| domain_counts.get_layout().is_exact())
+    }
+
+    pub fn set_domain_counts(&mut self, domain_counts: DomainCounts) {
+        self.domain_counts = Some(domain_counts);
+    }
+
+    // Like `save_doc_frequencies`, for the domain counts.
+    fn save_domain_counts<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        match self.domain_counts {
+            Some(ref domain_counts) => domain_counts.save(path),
+            None => DomainCounts::remove(path.as_ref()),
+        }
+    }
+
+    pub fn set_count(&mut self, state: NodeIndex<Ix>, count: usize) {
+        self.graph.get_node_mut(state).set_count(count);
+    }
+
+    /// Write the graph and tokens through to disk, for disk-backed CDAWGs.
+    pub fn flush(&self) -> Result<()> {
+        self.graph.flush()?;
+        self.tokens.borrow().flush()
+    }
+
+    /// Save metadata, and the document frequencies and domain counts if they were filled.
+    pub fn save_metadata<P: AsRef<Path> + Clone>(&self, path: P) -> Result<()> {
+        let mut config_path = path.as_ref().to_path_buf();
+        config_path.push("metadata.json");
+        let config = CdawgMetadata {
+            source: self.source.index(),
+            sink: self.sink.index(),
+            end_position: self.end_position,
+            snapshots: self.snapshots.clone(),
+            tokens: match self.shared_tokens {
+                Some(ref shared) => Some(shared.relative_to(&path)?),
+                None => None,
+            },
+            totals: self.totals.clone(),
+            n_nodes: Some(self.node_count()),
+            n_edges: Some(self.edge_count()),
+            docs: self.docs.clone(),
+            utype: Some(T::NAME.to_string()),
+            separator: self.separator,
+            tombstones: self.tombstones.clone(),
+            index_width: Some(index_bits::<Ix>()),
+            weight: Some(W::name().to_string()),
+        };
+        config.save_json(config_path)?;
+        self.save_doc_frequencies(&path)?;
+        self.save_domain_counts(path)
+    }
+
+    // TODO(#100): Refactor these into an Infinigram class that wraps a Cdawg
+
+    /// Get the count of the suffix matched by a CdawgState.
+    pub fn get_suffix_count(&self, cs: CdawgState<Ix>) -> usize {
+        self.get_count(cs.target.unwrap())
+    }
+
+    /// Length of the longest suffix that occurs in the corpus at each position of `query`.
+    pub fn get_suffix_length_profile(&self, query: &[T]) -> Vec<u64> {
+        let _timer = metrics::time_query();
+        let mut cs = self.get_initial();
+        query
+            .iter()
+            .map(|token| {
+                cs = self.transition_and_count(cs, *token);
+                cs.length
+            })
+            .collect()
+    }
+
+    /// Count the occurrences of `ngram` in the corpus by exact match, or 0 if it doesn't occur.
+    pub fn get_ngram_count(&self, ngram: &[T]) -> usize {
+        let _timer = metrics::time_query();
+        let mut cs = self.get_initial();
+        for token in ngram.iter() {
+            let old_length = cs.length;
+            cs = self.transition_and_count(cs, *token);
+            if cs.length != old_length + 1 {
+                return 0;
+            }
+        }
+        self.get_suffix_count(cs)
+    }
+
+    /// Get the starting offsets (0-indexed) in the corpus of up to `max_results` occurrences of
+    /// `query`, in no particular order. Each occurrence is a path from the state matched by `query`
+    /// to a sink, which fixes where it ends. Like `get_matching_doc_ids`, only completed documents
+    /// are searched.
+    pub fn locate(&self, query: &[T], max_results: usize) -> Vec<usize> {
+        let _timer = metrics::time_query();
+        let mut cs = self.get_initial();
+        for token in query.iter() {
+            let old_length = cs.length;
+            cs = self.transition_and_count(cs, *token);
+            if cs.length != old_length + 1 {
+                return Vec::new();
+            }
+        }
+
+        // Each state is paired with the number of tokens read after the query to reach it.
+        let mut positions = Vec::new();
+        let mut stack = vec![(cs.target.unwrap(), cs.end - cs.start)];
+        while let Some((state, suffix_length)) = stack.pop() {
+            for edge in self.graph.edges(state) {
+                let next = edge.get_target();
+                if next == state {
+                    // The self-loop of a sink starts at its document's end-of-document token.
+                    if self.is_deleted_loop(edge.get_weight()) {
+                        continue;
+                    }
+                    let (doc_end, _) = edge.get_weight().get_span();
+                    positions.push(doc_end + 1 - suffix_length - query.len());
+                    if positions.len() >= max_results {
+                        return positions;
+                    }
+                } else {
+                    let (start, end) = self.get_span(edge.get_weight(), next);
+                    stack.push((next, suffix_length + end + 1 - start));
+                }
+            }
+        }
+        positions
+    }
+
+    /// Probability that `query` starts at a uniformly random token position of the corpus, i.e.,
+    /// its count divided by the number of tokens. Requires totals in the metadata.
+    pub fn probability(&self, query: &[T]) -> Result<f64> {
+        let totals = self.require_totals()?;
+        if query.is_empty() {
+            return Ok(1.);
+        }
+        Ok(totals.normalize(self.get_ngram_count(query)))
+    }
+
+    /// Natural log of `probability`, which is negative infinity if `query` doesn't occur.
+    pub fn log_probability(&self, query: &[T]) -> Result<f64> {
+        Ok(self.probability(query)?.ln())
+    }
+
+    pub fn get_totals(&self) -> Option<&CorpusTotals> {
+        self.totals.as_ref()
+    }
+
+    /// Used by builders to record per-domain totals, which the CDAWG can't infer itself.
+    pub fn get_totals_mut(&mut self) -> Option<&mut CorpusTotals> {
+        self.totals.as_mut()
+    }
+
+    fn require_totals(&self) -> Result<&CorpusTotals> {
+        match self.totals {
+            Some(ref totals) if totals.n_tokens > 0 => Ok(totals),
+            Some(_) => bail!("Can't normalize counts of a CDAWG without complete documents"),
+            None => bail!("This CDAWG's metadata doesn't record corpus totals; rebuild it"),
+        }
+    }
+
+    /// Number of documents ending with the suffix matched by a CdawgState.
+    pub fn get_suffix_terminal_count(&self, cs: CdawgState<Ix>) -> usize {
+        if cs.start == cs.end {
+            return self.terminal_count(cs.target.unwrap());
+        }
+        // In the middle of an edge, the string is a document suffix iff the edge continues with an
+        // end-of-document token. All occurrences then end documents.
+        if self.tokens.borrow().get(cs.start) == T::end() {
+            return self.get_count(cs.target.unwrap());
+        }
+        0
+    }
+
+    /// Get the count of the suffix matched by a CdawgState under the statistic `kind`.
+    pub fn get_suffix_count_by_kind(&self, cs: CdawgState<Ix>, kind: CountKind) -> Result<usize> {
+        self.get_count_by_kind(cs.target.unwrap(), kind)
+    }
+
+    // Find the self-loops of up to `limit` completed documents containing the suffix matched by
+    // `cs`. These are the sinks reachable from its target.
+    fn matching_doc_loops(&self, cs: CdawgState<Ix>, limit: usize) -> Vec<CdawgEdgeWeight<Ix>> {
+        let mut loops = Vec::new();
+        let Some(target) = cs.target else {
+            return loops;
+        };
+        let mut visited = HashSet::new();
+        let mut stack = vec![target];
+        visited.insert(target);
+        while let Some(state) = stack.pop() {
+            for edge in self.graph.edges(state) {
+                let next = edge.get_target();
+                if next == state {
+                    // Sinks have no other edges than the self-loop labelling their document.
+                    if self.is_deleted_loop(edge.get_weight()) {
+                        continue;
+                    }
+                    loops.push(edge.get_weight());
+                    if loops.len() >= limit {
+                        return loops;
+                    }
+                } else if visited.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        loops
+    }
+
+    /// Get the IDs of up to `limit` documents containing the suffix matched by `cs`. IDs are
+    /// those passed to `end_document`, and a document still being built is never returned.
+    pub fn get_matching_doc_ids(&self, cs: CdawgState<Ix>, limit: usize) -> Vec<usize> {
+        self.matching_doc_loops(cs, limit)
+            .into_iter()
+            .map(|weight| weight.get_span().1)
+            .collect()
+    }
+
+    /// Like `get_matching_doc_ids`, but with the token span of each document. Requires the CDAWG
+    /// to have been built with `track_documents`.
+    pub fn get_matching_doc_spans(&self, cs: CdawgState<Ix>, limit: usize) -> Result<Vec<DocSpan>> {
+        let Some(ref docs) = self.docs else {
+            bail!("This CDAWG was not built with document spans");
+        };
+        self.matching_doc_loops(cs, limit)
+            .into_iter()
+            .map(|weight| {
+                let (end, _) = weight.get_span();
+                match docs.get_by_end(end) {
+                    Some(span) => Ok(span),
+                    None => bail!("No document span ends at position {}", end),
+                }
+            })
+            .collect()
+    }
+
+    /// Get the entropy of a CDAWG state in bits.
+    pub fn get_entropy(&self, cs: CdawgState<Ix>) -> f64 {
+        let (state, gamma) = cs.get_state_and_gamma();
+        if gamma.0 != gamma.1 {
+            return 0.;
+        }
+
+        let q = state.unwrap();
+        let denom = self.get_count(q);
+        let mut sum = 0.;
+        for next_state in self.get_graph().neighbors(q) {
+            let prob = (self.get_count(next_state) as f64) / (denom as f64);
+            sum -= prob * f64::log2(prob);
+        }
+        sum
+    }
+
+    pub fn get_next_tokens(&self, cs: CdawgState<Ix>) -> Vec<(T, f64)> {
+        self.get_next_tokens_by_kind(cs, CountKind::Occurrences)
+            .unwrap()
+    }
+
+    /// Get the `k` most likely continuations of `m` tokens after the suffix matched by `cs`, with
+    /// their probabilities, most likely first. This is a beam search of width `k` over
+    /// `get_next_tokens`, and continuations that would run into an end-of-document token are
+    /// dropped.
+    pub fn get_top_k_continuations(
+        &self,
+        cs: CdawgState<Ix>,
+        k: usize,
+        m: usize,
+    ) -> Vec<(Vec<T>, f64)> {
+        if cs.target.is_none() {
+            return Vec::new();
+        }
+        let mut beam = vec![(Vec::new(), 1., cs)];
+        for _ in 0..m {
+            let mut candidates = Vec::new();
+            for (tokens, prob, cs) in beam {
+                for (token, next_prob) in self.get_next_tokens(cs) {
+                    if self.is_document_end(token) {
+                        continue;
+                    }
+                    let mut next_tokens = tokens.clone();
+                    next_tokens.push(token);
+                    let next_cs = self.transition_and_count(cs, token);
+                    candidates.push((next_tokens, prob * next_prob, next_cs));
+                }
+            }
+            candidates.sort_by(|c1, c2| c2.1.partial_cmp(&c1.1).unwrap());
+            candidates.truncate(k);
+            beam = candidates;
+        }
+        beam.into_iter()
+            .map(|(tokens, prob, _)| (tokens, prob))
+            .collect()
+    }
+
+    /// The `k` most frequent substrings of length `n` that don't span documents, with their counts,
+    /// most frequent first. Counts must be filled.
+    pub fn top_substrings(&self, n: usize, k: usize) -> Vec<(Vec<T>, usize)> {
+        top_substrings::top_substrings(self, n, k)
+    }
+
+    /// Next-token distribution where probabilities are ratios of counts of kind `kind`.
+    pub fn get_next_tokens_by_kind(
+        &self,
+        cs: CdawgState<Ix>,
+        kind: CountKind,
+    ) -> Result<Vec<(T, f64)>> {
+        let (state, gamma) = cs.get_state_and_gamma();
+        if gamma.0 != gamma.1 {
+            let token = self.tokens.borrow().get(gamma.1);
+            return Ok(vec![(token, 1.)]);
+        }
+
+        let q = state.unwrap();
+        let denom = self.get_count_by_kind(q, kind)?;
+        let mut tokens = Vec::new();
+        for edge in self.get_graph().edges(q) {
+            // let edge_ref = self.graph.get_edge(edge_idx);
+            let next_state = edge.get_target();
+            let span = self.get_span(edge.get_weight(), next_state);
+            let token = self.tokens.borrow().get(span.0 - 1); // Shift to 0 indexing.
+            let prob = (self.get_count_by_kind(next_state, kind)? as f64) / (denom as f64);
+            tokens.push((token, prob));
+        }
+        Ok(tokens)
+    }
+
+    /// Sample the token following the suffix matched by `cs`, with probabilities proportional to
+    /// counts raised to `1 / temperature`. A temperature of 0 picks the most frequent token.
+    /// Returns None if the document ends instead, i.e. an end-of-document token is sampled, or if
+    /// nothing follows in the corpus. Counts must be filled.
+    pub fn sample_next_token(
+        &self,
+        cs: CdawgState<Ix>,
+        rng: &mut XorShiftRng,
+        temperature: f64,
+    ) -> Option<T> {
+        let (state, gamma) = cs.get_state_and_gamma();
+        if gamma.0 != gamma.1 {
+            // Partway through an edge, only one token can follow.
+            let token = self.tokens.borrow().get(gamma.1);
+            return if self.is_document_end(token) {
+                None
+            } else {
+                Some(token)
+            };
+        }
+
+        // Stream over the edges rather than collecting the distribution.
+        let q = state?;
+        let mut choice = WeightedChoice::default();
+        let mut best: Option<(usize, T)> = None;
+        for edge in self.graph.edges(q) {
+            let next_state = edge.get_target();
+            if next_state == q {
+                continue; // Document self-loop of a sink.
+            }
+            let span = self.get_span(edge.get_weight(), next_state);
+            let token = self.tokens.borrow().get(span.0 - 1); // Shift to 0 indexing.
+            let count = self.get_count(next_state);
+            if temperature > 0. {
+                choice.add(token, (count as f64).powf(1. / temperature), rng);
+            } else {
+                match best {
+                    Some((best_count, _)) if best_count >= count => {}
+                    _ => best = Some((count, token)),
+                }
+            }
+        }
+        let token = if temperature > 0. {
+            choice.get()
+        } else {
+            best.map(|(_, token)| token)
+        };
+        token.filter(|token| !self.is_document_end(*token))
+    }
+
+    /// Like `sample_next_token`, for a batch of states that share one RNG.
+    pub fn sample_next_tokens(
+        &self,
+        states: &[CdawgState<Ix>],
+        rng: &mut XorShiftRng,
+        temperature: f64,
+    ) -> Vec<Option<T>> {
+        states
+            .iter()
+            .map(|cs| self.sample_next_token(*cs, rng, temperature))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_variables)]
+#[allow(unused_imports)]
+#[allow(unused_assignments)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use crate::io::Save;
+    use crate::memory_backing::DiskVec;
+    use crate::weight::Weight64;
+    use tempfile::tempdir;
+
+    macro_rules! get_edge {
+        // `()` indicates that the macro takes no argument.
+        ($c:expr, $q:expr, $w:expr) => {
+            $c.graph.get_edge($c.get_edge_by_token($q, $w).unwrap())
+        };
+    }
+
+    macro_rules! get_span {
+        ($c:expr, $e:expr) => {
+            $c.get_span($e.get_weight(), $e.get_target())
+        };
+    }
+
+    type Span = (usize, usize);
+
+    fn to_inenaga(gamma: Span) -> Span {
+        (gamma.0 + 1, gamma.1)
+    }
+
+    #[test]
+    fn test_canonize() {
+        // Test canonize, which uses 1-indexing!
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![0, 1, 2])));
+        let q = cdawg.graph.add_node(DefaultWeight::new(
+            1,
+            Some(NodeIndex::new(cdawg.source.index())),
+            0,
+        ));
+        cdawg.add_balanced_edge(cdawg.source, q, (1, 1));
+        cdawg.add_balanced_edge(q, cdawg.sink, (2, 3));
+
+        let (mut state, mut start) = cdawg.canonize(None, (1, 1));
+        assert_eq!(state.unwrap().index(), cdawg.source.index());
+        assert_eq!(start, 2);
+
+        (state, start) = cdawg.canonize(None, (1, 0));
+        assert_eq!(state, None);
+        assert_eq!(start, 1);
+
+        (state, start) = cdawg.canonize(Some(cdawg.source), (1, 0));
+        assert_eq!(state.unwrap().index(), cdawg.source.index());
+        assert_eq!(start, 1);
+
+        (state, start) = cdawg.canonize(Some(cdawg.source), (1, 1));
+        assert_eq!(state.unwrap().index(), q.index());
+        assert_eq!(start, 2); // No tokens remaining.
+
+        (state, start) = cdawg.canonize(Some(cdawg.source), (1, 2));
+        assert_eq!(state.unwrap().index(), q.index());
+        assert_eq!(start, 2); // One token remaining.
+
+        (state, start) = cdawg.canonize(Some(cdawg.source), (1, 3));
+        assert_eq!(state.unwrap().index(), cdawg.sink.index());
+        assert_eq!(start, 4); // No tokens remaining.
+
+        (state, start) = cdawg.canonize(Some(q), (2, 2));
+        assert_eq!(state.unwrap().index(), q.index());
+        assert_eq!(start, 2); // One token remaining.
+
+        (state, start) = cdawg.canonize(Some(q), (2, 3));
+        assert_eq!(state.unwrap().index(), cdawg.sink.index());
+        assert_eq!(start, 4); // No tokens remaining.
+    }
+
+    #[test]
+    fn test_check_end_point() {
+        // This is 1-indexed!
+        let mut cdawg: Cdawg =
+            Cdawg::new(Rc::new(RefCell::new(vec![0, 1, 2, u16::MAX, 2, u16::MAX])));
+        cdawg.add_balanced_edge(cdawg.source, cdawg.sink, (1, 4));
+        assert!(cdawg.check_end_point(Some(cdawg.source), to_inenaga((0, 0)), 0));
+        assert!(!cdawg.check_end_point(Some(cdawg.source), to_inenaga((0, 0)), 1));
+        assert!(cdawg.check_end_point(Some(cdawg.source), to_inenaga((0, 1)), 1));
+        assert!(!cdawg.check_end_point(Some(cdawg.source), to_inenaga((0, 1)), 0));
+
+        // From the null state, anything should return true.
+        assert!(cdawg.check_end_point(None, to_inenaga((0, 0)), 0));
+        assert!(cdawg.check_end_point(None, to_inenaga((0, 0)), 1));
+        assert!(cdawg.check_end_point(None, to_inenaga((0, 0)), 2));
+
+        // Check with end-of-text tokens on edge.
+        assert!(cdawg.check_end_point(Some(cdawg.source), (1, 3), u16::MAX));
+        // Has not been inserted yet.
+        assert!(!cdawg.check_end_point(Some(cdawg.source), (1, 5), u16::MAX));
 
         // Check with end-of-text tokens at state.
         // here, end must be the token index (when 0-indexed)
@@ -931,9 +1978,11 @@ mod tests {
     #[test]
     fn test_separate_node_null() {
         let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![0, 1, 2])));
-        let c = cdawg
-            .graph
-            .add_node(DefaultWeight::new(1, Some(cdawg.source), 0));
+        let c = cdawg.graph.add_node(DefaultWeight::new(
+            1,
+            Some(NodeIndex::new(cdawg.source.index())),
+            0,
+        ));
         cdawg.add_balanced_edge(cdawg.source, c, (1, 1));
 
         // First step of cocoa should go back to initial state.
@@ -1197,6 +2246,7 @@ mod tests {
     type DiskW = DefaultWeight;
     type DiskE = CdawgEdgeWeight<DefaultIx>;
     type DiskCdawg = Cdawg<DiskW, DefaultIx, DiskBacking<DiskW, DiskE, DefaultIx>>;
+    type DiskCdawg32 = Cdawg<DiskW, DefaultIx, DiskBacking<DiskW, DiskE, DefaultIx>, u32>;
 
     #[test]
     fn test_save_metadata_load_null() {
@@ -1220,6 +2270,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_save_load_index64() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path();
+        type E64 = CdawgEdgeWeight<u64>;
+        type DiskCdawg64 = Cdawg<Weight64, u64, DiskBacking<Weight64, E64, u64>>;
+
+        let (a, b, c) = (0, 1, 2);
+        let tokens = Rc::new(RefCell::new(vec![a, b, c, a, b, u16::MAX]));
+        let mut cdawg: DiskCdawg64 = Cdawg::new_mb(tokens.clone(), DiskBacking::new(path));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        cdawg.save_metadata(path).unwrap();
+        drop(cdawg);
+
+        let cdawg: DiskCdawg64 = Cdawg::load(tokens.clone(), path, CacheConfig::none()).unwrap();
+        assert_eq!(cdawg.get_ngram_count(&[a, b]), 2);
+        assert_eq!(cdawg.get_ngram_count(&[b, c, a]), 1);
+        assert!(DiskCdawg::load(tokens, path, CacheConfig::none()).is_err());
+    }
+
+    #[test]
+    fn test_load_shared_after_move() {
+        let (a, b, c) = (0, 1, 2);
+        let tokens = vec![a, b, c, a, b, u16::MAX];
+        let tmp_dir = tempdir().unwrap();
+        let old_dir = tmp_dir.path().join("old");
+        let tokens_path = old_dir.join("tokens.vec");
+        std::fs::create_dir(&old_dir).unwrap();
+        let disk_vec = DiskVec::from_vec(&tokens, &tokens_path).unwrap();
+        let mut cdawg: DiskCdawg = Cdawg::new_mb(
+            Rc::new(RefCell::new(disk_vec)),
+            DiskBacking::new(old_dir.join("index")),
+        );
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        cdawg.share_tokens(&tokens_path);
+        cdawg.save_metadata(old_dir.join("index")).unwrap();
+        drop(cdawg);
+
+        // The tokens are found relative to the index, so both can be moved together.
+        let new_dir = tmp_dir.path().join("new");
+        std::fs::rename(&old_dir, &new_dir).unwrap();
+        let index_path = new_dir.join("index");
+        let cdawg: DiskCdawg = Cdawg::load_shared(&index_path, CacheConfig::none()).unwrap();
+        assert_eq!(cdawg.get_ngram_count(&[a, b]), 2);
+        let shared = cdawg.get_shared_tokens().unwrap();
+        assert_eq!(shared.path, index_path.join("../tokens.vec"));
+        let tokens = DiskVec::<u16>::load(&shared.path).unwrap();
+        assert!(shared.verify(&tokens).is_ok());
+    }
+
     #[test]
     fn test_tokens_disk_vec() {
         // Perform step 1 of cocoa on a DiskVec.
@@ -1267,6 +2369,7 @@ mod tests {
             lengths.push(cs.length);
         }
         assert_eq!(lengths, vec![1, 2, 3, 3, 0]);
+        assert_eq!(cdawg.get_suffix_length_profile(&[a, b, c, a, d]), lengths);
     }
 
     #[test]
@@ -1310,6 +2413,33 @@ mod tests {
         assert_eq!(lengths, vec![1, 2, 2]);
     }
 
+    #[test]
+    fn test_transition_and_count_at_snapshot() {
+        let (a, b, c, d) = (0, 1, 2, 3);
+        let train = Rc::new(RefCell::new(vec![a, b, c, b, d]));
+        let mut cdawg: Cdawg = Cdawg::new(train);
+        let (mut state, mut start) = (cdawg.get_source(), 1);
+        let mut snapshot = None;
+        for idx in 1..6 {
+            (state, start) = cdawg.update(state, start, idx);
+            if idx == 3 {
+                snapshot = Some(cdawg.take_snapshot());
+            }
+        }
+        let snapshot = snapshot.unwrap();
+        assert_eq!(snapshot.end_position, 3);
+        assert_eq!(cdawg.get_snapshots(), &[snapshot]);
+
+        // "b d" only occurs after the first three tokens.
+        let mut lengths = Vec::new();
+        let mut cs = cdawg.get_initial();
+        for token in [b, d].iter() {
+            cs = cdawg.transition_and_count_at(cs, *token, &snapshot);
+            lengths.push(cs.length);
+        }
+        assert_eq!(lengths, vec![1, 0]);
+    }
+
     #[test]
     fn test_multidoc_abc_bcd() {
         let (a, b, c, d) = (0, 1, 2, 3);
@@ -1331,14 +2461,14 @@ mod tests {
         assert_eq!(starts, vec![5, 9]);
 
         // Check that the documents are encoded correctly with edges from source to their sink node.
-        let cmp0 = CdawgComparator::new(train.clone());
+        let cmp0: CdawgComparator = CdawgComparator::new(train.clone());
         let doc0 = cdawg.graph.get_edge_by_weight_cmp(
             cdawg.source,
             CdawgEdgeWeight::new(3, 0),
             Box::new(cmp0),
         );
         assert_eq!(cdawg.graph.get_edge(doc0.unwrap()).get_target().index(), 1);
-        let cmp1 = CdawgComparator::new(train.clone());
+        let cmp1: CdawgComparator = CdawgComparator::new(train.clone());
         let doc1 = cdawg.graph.get_edge_by_weight_cmp(
             cdawg.source,
             CdawgEdgeWeight::new(7, 0),
@@ -1408,7 +2538,7 @@ mod tests {
         ); // 1-indexed
 
         // Test the sink edges.
-        let cmp0 = CdawgComparator::new(train.clone());
+        let cmp0: CdawgComparator = CdawgComparator::new(train.clone());
         let doc0 = cdawg.graph.get_edge_by_weight_cmp(
             cdawg.source,
             CdawgEdgeWeight::new(1, 2),
@@ -1418,7 +2548,7 @@ mod tests {
             cdawg.graph.get_edge(doc0.unwrap()).get_target(),
             NodeIndex::new(1)
         );
-        let cmp1 = CdawgComparator::new(train.clone());
+        let cmp1: CdawgComparator = CdawgComparator::new(train.clone());
         let doc1 = cdawg.graph.get_edge_by_weight_cmp(
             cdawg.source,
             CdawgEdgeWeight::new(3, 4),
@@ -1484,6 +2614,431 @@ mod tests {
         assert_eq!(cdawg.get_count(q2), 0);
     }
 
+    #[test]
+    fn test_u32_tokens() {
+        // Token IDs past u16::MAX, as with 128k vocabularies.
+        let (c, o, a) = (70000, 70001, 5);
+        let train: Vec<u32> = vec![c, o, c, o, a, u32::MAX, o, a, u32::MAX];
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path();
+        let mb = DiskBacking::new(path);
+        let mut cdawg: DiskCdawg32 = Cdawg::new_mb(Rc::new(RefCell::new(train.clone())), mb);
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+        assert_eq!(cdawg.get_ngram_count(&[c, o]), 2);
+        assert_eq!(cdawg.get_ngram_count(&[o, a]), 2);
+        assert_eq!(cdawg.get_ngram_count(&[a, c]), 0);
+        cdawg.save_metadata(path).unwrap();
+
+        let loaded: DiskCdawg32 =
+            Cdawg::load(Rc::new(RefCell::new(train)), path, CacheConfig::none()).unwrap();
+        assert_eq!(loaded.get_ngram_count(&[o, c, o, a]), 1);
+        let wrong_utype: Result<DiskCdawg> = Cdawg::load(
+            Rc::new(RefCell::new(vec![0_u16; 9])),
+            path,
+            CacheConfig::none(),
+        );
+        assert!(wrong_utype.is_err());
+    }
+
+    #[test]
+    fn test_get_ngram_count_cocoa() {
+        let (c, o, a) = (0, 1, 2);
+        let train = Rc::new(RefCell::new(vec![c, o, c, o, a, u16::MAX]));
+        let mut cdawg: Cdawg = Cdawg::new(train);
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+
+        assert_eq!(cdawg.get_ngram_count(&[c]), 2);
+        assert_eq!(cdawg.get_ngram_count(&[c, o]), 2);
+        assert_eq!(cdawg.get_ngram_count(&[o, c, o, a]), 1);
+        assert_eq!(cdawg.get_ngram_count(&[a]), 1);
+        assert_eq!(cdawg.get_ngram_count(&[c, a]), 0);
+        assert_eq!(cdawg.get_ngram_count(&[a, c]), 0);
+    }
+
+    #[test]
+    fn test_locate() {
+        let (a, b, c, d, e) = (0, 1, 2, 3, 4);
+        let tokens = vec![a, b, c, u16::MAX, b, c, d, u16::MAX, a, b, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        cdawg.build();
+
+        let locate = |query: &[u16]| {
+            let mut positions = cdawg.locate(query, 100);
+            positions.sort();
+            positions
+        };
+        assert_eq!(locate(&[b, c]), vec![1, 4]);
+        assert_eq!(locate(&[a, b]), vec![0, 8]);
+        assert_eq!(locate(&[b]), vec![1, 4, 9]);
+        assert_eq!(locate(&[c, d]), vec![5]);
+        assert_eq!(locate(&[a, b, c]), vec![0]);
+        assert_eq!(locate(&[e]), Vec::<usize>::new());
+        assert_eq!(locate(&[c, a]), Vec::<usize>::new());
+        for query in [&[b][..], &[a, b], &[c, d]] {
+            for position in locate(query) {
+                assert_eq!(&tokens[position..position + query.len()], query);
+            }
+        }
+        assert_eq!(cdawg.locate(&[b], 2).len(), 2);
+    }
+
+    #[test]
+    fn test_prune_by_count() {
+        let (a, b, c) = (0, 1, 2);
+        let tokens = vec![a, b, c, a, b, c, a, b, a, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+
+        let pruned = cdawg.prune_by_count(2);
+        assert!(pruned.node_count() < cdawg.node_count());
+        for ngram in [
+            &[a][..],
+            &[a, b],
+            &[b, c, a],
+            &[c, a, b],
+            &[b, a],
+            &[a, b, a],
+        ] {
+            let count = cdawg.get_ngram_count(ngram);
+            let expected = if count >= 2 { count } else { 0 };
+            assert_eq!(pruned.get_ngram_count(ngram), expected);
+        }
+        assert_eq!(cdawg.prune_by_count(1).node_count(), cdawg.node_count());
+    }
+
+    #[test]
+    fn test_get_matching_doc_ids() {
+        let (a, b, c, d) = (0, 1, 2, 3);
+        let train = Rc::new(RefCell::new(vec![
+            a,
+            b,
+            c,
+            u16::MAX,
+            b,
+            c,
+            d,
+            u16::MAX,
+            a,
+            b,
+            u16::MAX,
+        ]));
+        let mut cdawg: Cdawg = Cdawg::new(train.clone());
+        cdawg.track_documents();
+        let (mut state, mut start) = (cdawg.source, 1);
+        let mut doc_id = 10;
+        for idx in 1..train.borrow().len() + 1 {
+            (state, start) = cdawg.update(state, start, idx);
+            if train.borrow().get(idx - 1) == u16::MAX {
+                (state, start) = cdawg.end_document(idx, doc_id);
+                doc_id += 1;
+            }
+        }
+
+        let get_ids = |query: &[u16], limit: usize| {
+            let mut cs = cdawg.get_initial();
+            for token in query {
+                cs = cdawg.transition_and_count(cs, *token);
+            }
+            let mut ids = cdawg.get_matching_doc_ids(cs, limit);
+            ids.sort();
+            ids
+        };
+        assert_eq!(get_ids(&[b, c], 10), vec![10, 11]);
+        assert_eq!(get_ids(&[a, b], 10), vec![10, 12]);
+        assert_eq!(get_ids(&[d], 10), vec![11]);
+        assert_eq!(get_ids(&[b], 10), vec![10, 11, 12]);
+        assert_eq!(get_ids(&[b], 2).len(), 2);
+
+        let mut cs = cdawg.get_initial();
+        for token in [c, d] {
+            cs = cdawg.transition_and_count(cs, token);
+        }
+        let spans = cdawg.get_matching_doc_spans(cs, 10).unwrap();
+        assert_eq!(
+            spans,
+            vec![DocSpan {
+                doc_id: 11,
+                start: 4,
+                end: 7,
+                sink: Some(2),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_delete_document() {
+        let (a, b, c, d) = (0, 1, 2, 3);
+        let end = u16::MAX;
+        let train = vec![a, b, c, end, b, c, d, end, a, b, end];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(train)));
+        cdawg.build(); // Documents are labelled by their end position: 4, 8 and 11.
+        assert!(cdawg.delete_document(4).is_err());
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        assert_eq!(cdawg.get_ngram_count(&[b]), 3);
+
+        assert_eq!(cdawg.delete_document(4).unwrap(), 1);
+        assert_eq!(cdawg.delete_document(4).unwrap(), 0);
+        assert_eq!(cdawg.get_tombstones().len(), 1);
+        assert_eq!(cdawg.get_ngram_count(&[b]), 2);
+        assert_eq!(cdawg.get_ngram_count(&[a, b]), 1);
+        assert_eq!(cdawg.get_ngram_count(&[a, b, c]), 0);
+        assert_eq!(cdawg.get_ngram_count(&[b, c]), 1);
+        assert_eq!(cdawg.locate(&[a, b], 10), vec![8]);
+
+        let mut cs = cdawg.get_initial();
+        cs = cdawg.transition_and_count(cs, b);
+        let mut ids = cdawg.get_matching_doc_ids(cs, 10);
+        ids.sort();
+        assert_eq!(ids, vec![8, 11]);
+        let totals = cdawg.get_totals().unwrap();
+        assert_eq!((totals.n_docs, totals.n_tokens), (2, 5));
+
+        // Counting again from scratch gives the same counts.
+        let counts: Vec<_> = (0..cdawg.node_count())
+            .map(|idx| cdawg.get_count(NodeIndex::new(idx)))
+            .collect();
+        cdawg.reset_counts();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        for (idx, count) in counts.into_iter().enumerate() {
+            assert_eq!(cdawg.get_count(NodeIndex::new(idx)), count);
+        }
+    }
+
+    #[test]
+    fn test_get_matching_doc_spans_untracked() {
+        let train = Rc::new(RefCell::new(vec![0, 1, u16::MAX]));
+        let mut cdawg: Cdawg = Cdawg::new(train);
+        cdawg.build();
+        let cs = cdawg.transition_and_count(cdawg.get_initial(), 0);
+        assert_eq!(cdawg.get_matching_doc_ids(cs, 10), vec![3]);
+        assert!(cdawg.get_matching_doc_spans(cs, 10).is_err());
+        assert!(cdawg.get_document_span(3).is_err());
+        assert_eq!(cdawg.get_document_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_get_document_span() {
+        let (a, b, c) = (0, 1, 2);
+        let end = u16::MAX;
+        let train = Rc::new(RefCell::new(vec![a, b, end, c, end, a, end]));
+        let mut cdawg: Cdawg = Cdawg::new(train.clone());
+        cdawg.track_documents();
+        let (mut state, mut start) = (cdawg.source, 1);
+        let mut doc_id = 10;
+        for idx in 1..train.borrow().len() + 1 {
+            (state, start) = cdawg.update(state, start, idx);
+            if train.borrow().get(idx - 1) == end {
+                (state, start) = cdawg.end_document(idx, doc_id);
+                doc_id += 1;
+            }
+        }
+        assert_eq!(cdawg.get_document_count().unwrap(), 3);
+        let span = cdawg.get_document_span(11).unwrap().unwrap();
+        assert_eq!((span.doc_id, span.start, span.end), (11, 3, 4));
+        // The sink is labelled by the document's self-loop.
+        let sink = NodeIndex::new(span.sink.unwrap());
+        let labels: Vec<_> = cdawg
+            .get_graph()
+            .edges(sink)
+            .filter(|edge| edge.get_target() == sink)
+            .map(|edge| edge.get_weight().get_span())
+            .collect();
+        assert_eq!(labels, vec![(4, 11)]);
+        assert_eq!(cdawg.get_document_span(13).unwrap(), None);
+
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        cdawg.delete_document(11).unwrap();
+        assert_eq!(cdawg.get_document_span(11).unwrap(), None);
+        assert_eq!(cdawg.get_document_count().unwrap(), 2);
+
+        // Pruning drops the sinks of documents, which are counted once.
+        let pruned = cdawg.prune_by_count(2);
+        assert_eq!(pruned.get_document_span(10).unwrap().unwrap().sink, None);
+    }
+
+    #[test]
+    fn test_probability_saved_totals() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        let tokens = vec![c, o, c, o, a, u16::MAX, c, o, l, a, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+
+        let totals = cdawg.get_totals().unwrap();
+        assert_eq!((totals.n_tokens, totals.n_docs), (9, 2));
+        assert_eq!(cdawg.probability(&[c, o]).unwrap(), 3. / 9.);
+        assert_eq!(cdawg.log_probability(&[a]).unwrap(), (2. / 9_f64).ln());
+        assert_eq!(cdawg.log_probability(&[a, c]).unwrap(), f64::NEG_INFINITY);
+
+        let tmp_dir = tempdir().unwrap();
+        cdawg.save(tmp_dir.path().to_str().unwrap()).unwrap();
+        let loaded: DiskCdawg = Cdawg::load(
+            Rc::new(RefCell::new(tokens)),
+            tmp_dir.path(),
+            CacheConfig::none(),
+        )
+        .unwrap();
+        assert_eq!(loaded.get_totals(), cdawg.get_totals());
+        assert_eq!(loaded.probability(&[c, o]).unwrap(), 3. / 9.);
+    }
+
+    #[test]
+    fn test_resume_from_disk() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        let tokens1 = vec![c, o, c, o, a, u16::MAX];
+        let tokens2 = [c, o, l, a, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens1.clone())));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path();
+        cdawg.save(path.to_str().unwrap()).unwrap();
+
+        let tokens_dir = tempdir().unwrap();
+        let tokens_path = tokens_dir.path().join("tokens.vec");
+        DiskVec::from_vec(&tokens1, &tokens_path).unwrap();
+        let disk_vec = DiskVec::<u16>::open_mut(&tokens_path, tokens1.len()).unwrap();
+        let tokens: Rc<RefCell<dyn TokenBacking<u16>>> = Rc::new(RefCell::new(disk_vec));
+        let mut resumed: DiskCdawg =
+            Cdawg::resume_from_disk(tokens.clone(), path, CacheConfig::none()).unwrap();
+        let (mut state, mut start) = resumed.get_resume_point();
+        let mut idx = tokens1.len();
+        for token in tokens2.iter() {
+            idx += 1;
+            tokens.borrow_mut().push(*token);
+            (state, start) = resumed.update(state, start, idx);
+            if *token == u16::MAX {
+                (state, start) = resumed.end_document(idx, idx);
+            }
+        }
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut resumed);
+
+        let all_tokens: Vec<u16> = tokens1.iter().chain(tokens2.iter()).copied().collect();
+        let mut full: Cdawg = Cdawg::new(Rc::new(RefCell::new(all_tokens)));
+        full.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut full);
+        assert_eq!(resumed.node_count(), full.node_count());
+        assert_eq!(resumed.get_totals(), full.get_totals());
+        for ngram in [&[c][..], &[c, o], &[o, c, o], &[l, a], &[a], &[o, l, a]] {
+            assert_eq!(resumed.get_ngram_count(ngram), full.get_ngram_count(ngram));
+        }
+    }
+
+    #[test]
+    fn test_load_tiered() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        let tokens = vec![c, o, c, o, a, u16::MAX, c, o, l, a, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+        let tmp_dir = tempdir().unwrap();
+        cdawg.save(tmp_dir.path().to_str().unwrap()).unwrap();
+
+        type TieredCdawg = Cdawg<DiskW, DefaultIx, TieredBacking<DiskW, DiskE, DefaultIx>>;
+        let tiered: TieredCdawg = Cdawg::load_tiered(
+            Rc::new(RefCell::new(tokens)),
+            tmp_dir.path(),
+            TierPolicy::MinCount(3),
+            CacheConfig::none(),
+        )
+        .unwrap();
+        assert!(tiered.get_graph().n_pinned().0 > 0);
+        for ngram in [&[c][..], &[c, o], &[o, c, o], &[l, a], &[a, c]] {
+            assert_eq!(tiered.get_ngram_count(ngram), cdawg.get_ngram_count(ngram));
+        }
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let (c, o, a) = (0, 1, 2);
+        let tokens = vec![c, o, c, o, a, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        let mut out = Vec::new();
+        cdawg
+            .to_dot(&mut out, |token| {
+                ["c", "o", "a"][token as usize].to_string()
+            })
+            .unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(dot.starts_with("digraph {"));
+        assert!(dot.contains("q0 -> q1 [label=\"a <eos>\"];"));
+        assert!(!dot.contains("q1 -> q1"));
+    }
+
+    #[test]
+    fn test_iter_edges() {
+        let (c, o, a) = (0, 1, 2);
+        let tokens = vec![c, o, c, o, a, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        let source = cdawg.get_source();
+        let mut edges: Vec<_> = cdawg.iter_edges(source).collect();
+        edges.sort_by_key(|edge| edge.first_token);
+        assert_eq!(edges.len(), 4);
+        assert_eq!(edges[0].tokens, vec![c, o]);
+        assert_eq!(edges[2].first_token, a);
+        assert_eq!(edges[2].tokens, vec![a, u16::MAX]);
+        assert_eq!(edges[2].span_len, 2);
+        assert_eq!(edges[2].target, NodeIndex::new(1));
+        // Edges starting with the end-of-document token aren't looked up by token.
+        for edge in edges[..3].iter() {
+            let edge_idx = cdawg.get_edge_by_token(source, edge.first_token).unwrap();
+            assert_eq!(cdawg.resolve_span(edge_idx), edge.tokens);
+        }
+        // The self-loop labelling the sink is left out.
+        assert_eq!(cdawg.iter_edges(NodeIndex::new(1)).count(), 0);
+    }
+
+    #[test]
+    fn test_load_mmap() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        let tokens = vec![c, o, c, o, a, u16::MAX, c, o, l, a, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+        let tmp_dir = tempdir().unwrap();
+        cdawg.save(tmp_dir.path().to_str().unwrap()).unwrap();
+
+        type MmapCdawg = Cdawg<DiskW, DefaultIx, MmapBacking<DiskW, DiskE, DefaultIx>>;
+        for _ in 0..2 {
+            let mapped: MmapCdawg =
+                Cdawg::load_mmap(Rc::new(RefCell::new(tokens.clone())), tmp_dir.path()).unwrap();
+            assert_eq!(mapped.node_count(), cdawg.node_count());
+            for ngram in [&[c][..], &[c, o], &[o, c, o], &[l, a], &[a, c]] {
+                assert_eq!(mapped.get_ngram_count(ngram), cdawg.get_ngram_count(ngram));
+            }
+        }
+        assert!(tmp_dir.path().join("nodes.pod").is_file());
+    }
+
+    #[test]
+    fn test_resume_from_disk_mid_document() {
+        let tokens = vec![0, 1, u16::MAX, 2];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        cdawg.build();
+        let tmp_dir = tempdir().unwrap();
+        cdawg.save(tmp_dir.path().to_str().unwrap()).unwrap();
+        let result: Result<DiskCdawg> = Cdawg::resume_from_disk(
+            Rc::new(RefCell::new(tokens)),
+            tmp_dir.path(),
+            CacheConfig::none(),
+        );
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_get_count_abcabcaba() {
         // Test counts incrementally.
@@ -1541,6 +3096,38 @@ mod tests {
         assert_eq!(entropies, vec![1., 0., 0., 1.9182958340544896, 1.]);
     }
 
+    #[test]
+    fn test_get_top_k_continuations() {
+        let (a, b, c) = (0, 1, 2);
+        let train = Rc::new(RefCell::new(vec![a, b, a, b, a, c, u16::MAX]));
+        let mut cdawg: Cdawg = Cdawg::new(train);
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+
+        let cs = cdawg.transition_and_count(cdawg.get_initial(), a);
+        assert_eq!(
+            cdawg.get_top_k_continuations(cs, 2, 1),
+            vec![(vec![b], 2. / 3.), (vec![c], 1. / 3.)]
+        );
+        assert_eq!(
+            cdawg.get_top_k_continuations(cs, 1, 1),
+            vec![(vec![b], 2. / 3.)]
+        );
+        // Continuing with c runs into the end of the document.
+        assert_eq!(
+            cdawg.get_top_k_continuations(cs, 2, 2),
+            vec![(vec![b, a], 2. / 3.)]
+        );
+        let mut continuations = cdawg.get_top_k_continuations(cs, 3, 3);
+        continuations.sort_by(|c1, c2| c1.0.cmp(&c2.0));
+        assert_eq!(
+            continuations,
+            vec![(vec![b, a, b], 1. / 3.), (vec![b, a, c], 1. / 3.)]
+        );
+        assert_eq!(cdawg.get_top_k_continuations(cs, 2, 0), vec![(vec![], 1.)]);
+    }
+
     #[test]
     fn test_get_next_tokens() {
         // Test counts incrementally.
@@ -1556,7 +3143,7 @@ mod tests {
         for token in [a, b, a, d, c].iter() {
             cs = cdawg.transition_and_count(cs, *token);
             let mut tokens = cdawg.get_next_tokens(cs);
-            tokens.sort_by(|tup1, tup2| tup1.0.cmp(&tup2.0));
+            tokens.sort_by_key(|tup| tup.0);
             next_tokens.push(tokens);
         }
 
@@ -1576,4 +3163,210 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_sample_next_token() {
+        let (a, b, c, d) = (0, 1, 2, 3);
+        let train = Rc::new(RefCell::new(vec![c, a, b, a, c, u16::MAX]));
+        let mut cdawg: Cdawg = Cdawg::new(train);
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+        let mut rng = XorShiftRng::new(0);
+
+        let mut cs = cdawg.get_initial();
+        for token in [a, b, a] {
+            cs = cdawg.transition_and_count(cs, token);
+        }
+        assert_eq!(cdawg.sample_next_token(cs, &mut rng, 1.), Some(c));
+
+        // After failing on d, the match is "c", which ends the document half of the time.
+        let mut cs = cdawg.get_initial();
+        for token in [a, d, c] {
+            cs = cdawg.transition_and_count(cs, token);
+        }
+        let samples = cdawg.sample_next_tokens(&[cs; 1000], &mut rng, 1.);
+        let n_ends = samples.iter().filter(|token| token.is_none()).count();
+        assert!((400..600).contains(&n_ends));
+        assert!(samples.iter().flatten().all(|token| *token == a));
+    }
+
+    #[test]
+    fn test_sample_next_token_greedy() {
+        let (a, b) = (0, 1);
+        let train = Rc::new(RefCell::new(vec![a, a, b, u16::MAX]));
+        let mut cdawg: Cdawg = Cdawg::new(train);
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+        let mut rng = XorShiftRng::new(0);
+
+        let cs = cdawg.get_initial();
+        assert_eq!(cdawg.sample_next_token(cs, &mut rng, 0.), Some(a));
+        let cs = cdawg.transition_and_count(cs, b);
+        assert_eq!(cdawg.sample_next_token(cs, &mut rng, 0.), None);
+    }
+
+    #[test]
+    fn test_get_count_by_kind() {
+        let (a, b) = (0, 1);
+        let train = Rc::new(RefCell::new(vec![a, b, a, u16::MAX]));
+        let mut cdawg: Cdawg = Cdawg::new(train);
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+
+        let cs = cdawg.transition_and_count(cdawg.get_initial(), a);
+        let count = cdawg.get_suffix_count_by_kind(cs, CountKind::Occurrences);
+        assert_eq!(count.unwrap(), 2);
+        assert!(cdawg
+            .get_suffix_count_by_kind(cs, CountKind::Documents)
+            .is_err());
+        assert!(cdawg
+            .get_next_tokens_by_kind(cs, CountKind::Weighted)
+            .is_err());
+
+        counter.fill_doc_frequencies(&mut cdawg);
+        assert_eq!(
+            cdawg.get_count_kinds(),
+            vec![CountKind::Occurrences, CountKind::Documents]
+        );
+        let count = cdawg.get_suffix_count_by_kind(cs, CountKind::Documents);
+        assert_eq!(count.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_doc_frequencies() {
+        let (a, b, c) = (0, 1, 2);
+        let end = u16::MAX;
+        let tokens = vec![a, b, a, b, end, b, c, end, a, b, c, end];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        cdawg.build(); // Documents are labelled by their end position: 5, 8 and 12.
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+        assert!(cdawg.get_doc_frequency(cdawg.get_source()).is_err());
+        counter.fill_doc_frequencies(&mut cdawg);
+
+        let doc_frequency = |cdawg: &Cdawg, query: &[u16]| {
+            let mut cs = cdawg.get_initial();
+            for token in query {
+                cs = cdawg.transition_and_count(cs, *token);
+            }
+            cdawg
+                .get_suffix_count_by_kind(cs, CountKind::Documents)
+                .unwrap()
+        };
+        assert_eq!(cdawg.get_doc_frequency(cdawg.get_source()).unwrap(), 3);
+        assert_eq!(doc_frequency(&cdawg, &[a, b]), 2);
+        assert_eq!(cdawg.get_ngram_count(&[a, b]), 3);
+        assert_eq!(doc_frequency(&cdawg, &[b]), 3);
+        assert_eq!(doc_frequency(&cdawg, &[b, c]), 2);
+        assert_eq!(doc_frequency(&cdawg, &[b, a]), 1);
+
+        // Deleting a document updates the frequencies.
+        assert_eq!(cdawg.delete_document(12).unwrap(), 1);
+        assert_eq!(doc_frequency(&cdawg, &[a, b]), 1);
+        assert_eq!(doc_frequency(&cdawg, &[b, c]), 1);
+        assert_eq!(doc_frequency(&cdawg, &[b]), 2);
+
+        // They are saved with the graph, and removed when saving without them.
+        let tmp_dir = tempdir().unwrap();
+        cdawg.save(tmp_dir.path().to_str().unwrap()).unwrap();
+        let loaded: DiskCdawg = Cdawg::load(
+            Rc::new(RefCell::new(tokens.clone())),
+            tmp_dir.path(),
+            CacheConfig::none(),
+        )
+        .unwrap();
+        for idx in 0..cdawg.node_count() {
+            let state = NodeIndex::new(idx);
+            assert_eq!(
+                loaded.get_doc_frequency(state).unwrap(),
+                cdawg.get_doc_frequency(state).unwrap()
+            );
+        }
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        cdawg.save_metadata(tmp_dir.path()).unwrap();
+        assert!(!DocFrequencies::get_path(tmp_dir.path()).exists());
+    }
+
+    #[test]
+    fn test_domain_counts() {
+        let (a, b, c) = (0, 1, 2);
+        let end = u16::MAX;
+        let tokens = vec![a, b, end, b, c, end, a, b, c, end];
+        let domains = [5, 5, 9];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        let (mut state, mut start) = (cdawg.source, 1);
+        let mut n_docs = 0;
+        for idx in 1..tokens.len() + 1 {
+            (state, start) = cdawg.update(state, start, idx);
+            if tokens[idx - 1] == end {
+                (state, start) = cdawg.end_document(idx, domains[n_docs]);
+                n_docs += 1;
+            }
+        }
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+        assert!(cdawg.get_domain_counts(cdawg.get_source()).is_err());
+        counter.fill_domain_counts(&mut cdawg, 64);
+        assert_eq!(cdawg.has_exact_domain_counts(), Some(true));
+
+        let target = |cdawg: &Cdawg, query: &[u16]| {
+            let mut cs = cdawg.get_initial();
+            for token in query {
+                cs = cdawg.transition_and_count(cs, *token);
+            }
+            cs.target.unwrap()
+        };
+        let ab = target(&cdawg, &[a, b]);
+        assert_eq!(cdawg.get_domain_counts(ab).unwrap(), vec![(5, 1), (9, 1)]);
+        let b_state = target(&cdawg, &[b]);
+        assert_eq!(
+            cdawg.get_domain_counts(b_state).unwrap(),
+            vec![(5, 2), (9, 1)]
+        );
+        assert_eq!(cdawg.get_domain_count(b_state, 7).unwrap(), 0);
+        // Exact domain counts add up to the total count, except in the empty sink of the next
+        // document, which is counted as one occurrence.
+        for idx in 0..cdawg.node_count() {
+            let state = NodeIndex::new(idx);
+            if cdawg.get_graph().edges(state).next().is_none() {
+                continue;
+            }
+            let total: usize = cdawg
+                .get_domain_counts(state)
+                .unwrap()
+                .iter()
+                .map(|(_, count)| count)
+                .sum();
+            assert_eq!(total, cdawg.get_count(state));
+        }
+
+        // Saved with the graph.
+        let tmp_dir = tempdir().unwrap();
+        cdawg.save(tmp_dir.path().to_str().unwrap()).unwrap();
+        let loaded: DiskCdawg = Cdawg::load(
+            Rc::new(RefCell::new(tokens.clone())),
+            tmp_dir.path(),
+            CacheConfig::none(),
+        )
+        .unwrap();
+        assert_eq!(
+            loaded
+                .get_domain_counts(NodeIndex::new(ab.index()))
+                .unwrap(),
+            vec![(5, 1), (9, 1)]
+        );
+
+        // Deleting a domain updates the counts.
+        assert_eq!(cdawg.delete_document(9).unwrap(), 1);
+        assert_eq!(cdawg.get_domain_counts(ab).unwrap(), vec![(5, 1)]);
+
+        // With too many domains to count exactly, sketched counts are never too low.
+        counter.fill_domain_counts(&mut cdawg, 0);
+        assert_eq!(cdawg.has_exact_domain_counts(), Some(false));
+        assert!(cdawg.get_domain_count(b_state, 5).unwrap() >= 2);
+    }
 }
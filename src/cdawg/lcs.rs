@@ -0,0 +1,141 @@
+// Longest common substring between a query corpus B and the corpus A indexed by a CDAWG.
+//
+// B is streamed through the CDAWG left-to-right, as in the Evaluator. The matched length at each
+// position is the longest suffix of B ending there that also occurs in A, so the longest common
+// substring ends wherever it is largest. Matching restarts at document separators in B.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::inenaga::Cdawg;
+use crate::cdawg::token_backing::{CdawgToken, TokenBacking};
+use crate::graph::indexing::IndexType;
+use crate::memory_backing::MemoryBacking;
+use crate::metrics;
+use crate::weight::Weight;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CommonSubstring {
+    pub start: usize, // Span of the first longest common substring in B, as [start, end).
+    pub end: usize,
+    pub count: usize, // Count of the substring in A, or 0 if no token of B occurs in A.
+}
+
+impl CommonSubstring {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Find the longest substring of `query` that occurs in the corpus indexed by `cdawg`.
+pub fn longest_common_substring<W, Ix, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    query: &dyn TokenBacking<T>,
+) -> CommonSubstring
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    scan(cdawg, query, |_| {})
+}
+
+/// Like `longest_common_substring`, but also return the overlap profile: the matched length at
+/// each position of `query`, which is 0 at document separators.
+pub fn overlap_profile<W, Ix, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    query: &dyn TokenBacking<T>,
+) -> (CommonSubstring, Vec<u64>)
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    let mut profile = Vec::with_capacity(query.len());
+    let lcs = scan(cdawg, query, |length| profile.push(length));
+    (lcs, profile)
+}
+
+// Stream `query` through `cdawg`, calling `visit` with the matched length at each position.
+fn scan<W, Ix, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    query: &dyn TokenBacking<T>,
+    mut visit: impl FnMut(u64),
+) -> CommonSubstring
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    let _timer = metrics::time_query();
+    let mut lcs = CommonSubstring::default();
+    let mut cs = cdawg.get_initial();
+    for idx in 0..query.len() {
+        let token = query.get(idx);
+        if token == T::end() {
+            cs = cdawg.get_initial();
+            visit(0);
+            continue;
+        }
+        cs = cdawg.transition_and_count(cs, token);
+        let length = cs.length as usize;
+        if length > lcs.len() {
+            lcs = CommonSubstring {
+                start: idx + 1 - length,
+                end: idx + 1,
+                count: cdawg.get_suffix_count(cs),
+            };
+        }
+        visit(cs.length);
+    }
+    lcs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_longest_common_substring() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        let end = u16::MAX;
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![c, o, c, o, a, end])));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+
+        let query = vec![l, o, c, o, end, c, o, a, l];
+        let lcs = longest_common_substring(&cdawg, &query);
+        assert_eq!(
+            lcs,
+            CommonSubstring {
+                start: 1,
+                end: 4,
+                count: 1
+            }
+        );
+        assert_eq!(lcs.len(), 3);
+
+        let (profile_lcs, profile) = overlap_profile(&cdawg, &query);
+        assert_eq!(profile_lcs, lcs);
+        // Matching restarts after the separator.
+        assert_eq!(profile, vec![0, 1, 2, 3, 0, 1, 2, 3, 0]);
+
+        let query = vec![l, l];
+        let lcs = longest_common_substring(&cdawg, &query);
+        assert!(lcs.is_empty());
+        assert_eq!(lcs.count, 0);
+    }
+}
@@ -1,22 +1,61 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 use std::convert::AsRef;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
 
+use crate::cdawg::corpus_totals::CorpusTotals;
+use crate::cdawg::doc_spans::DocSpans;
+use crate::cdawg::shared_tokens::SharedTokens;
+use crate::cdawg::snapshot::Snapshot;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::cdawg::tombstones::Tombstones;
+use crate::graph::indexing::{index_bits, DefaultIx, IndexType};
+use crate::memory_backing::vec_backing::format::FormatError;
+use crate::tokenize::DocumentSeparator;
+use crate::weight::{CountMode, Weight, WeightType};
+
 #[derive(Deserialize, Serialize)]
 pub struct CdawgMetadata {
     pub source: usize,       // Index of source node.
     pub sink: usize,         // Index of sink node.
     pub end_position: usize, // End position of active document.
+    #[serde(default)]
+    pub snapshots: Vec<Snapshot>, // Markers taken during the build, oldest first.
+    #[serde(default)]
+    pub tokens: Option<SharedTokens>, // Shared token backing, relative to the index directory.
+    #[serde(default)]
+    pub totals: Option<CorpusTotals>, // Missing for CDAWGs saved before totals were recorded.
+    #[serde(default)]
+    pub n_nodes: Option<usize>, // Graph sizes, needed to resume a build on disk.
+    #[serde(default)]
+    pub n_edges: Option<usize>,
+    #[serde(default)]
+    pub docs: Option<DocSpans>, // Only recorded if the build tracked documents.
+    #[serde(default)]
+    pub utype: Option<String>, // Token type, or None for CDAWGs saved before it was recorded (u16).
+    #[serde(default)]
+    pub separator: DocumentSeparator, // Reserved for CDAWGs saved before it was recorded.
+    #[serde(default)]
+    pub tombstones: Tombstones,
+    #[serde(default)]
+    pub index_width: Option<usize>, // Bits per node and edge index, or None for 40 (DefaultIx).
+    #[serde(default)]
+    pub weight: Option<String>, // Node weight type, or None for the default of the index width.
 }
 
 impl CdawgMetadata {
     pub fn load_json<P: AsRef<Path>>(file_path: P) -> Result<Self> {
-        let mut file = File::open(file_path)?;
+        let mut file = File::open(&file_path)?;
         let mut data = String::new();
         file.read_to_string(&mut data)?;
+        if data.trim().is_empty() {
+            bail!(FormatError::Truncated {
+                path: file_path.as_ref().to_path_buf(),
+                len: data.len() as u64,
+            });
+        }
         Ok(serde_json::from_str(&data)?)
     }
 
@@ -26,12 +65,57 @@ impl CdawgMetadata {
         file.write_all(json_data.as_bytes())?;
         Ok(())
     }
+
+    /// Check that the CDAWG was built on tokens of type `T`.
+    pub fn validate_utype<T: CdawgToken>(&self) -> Result<()> {
+        let utype = self.utype.as_deref().unwrap_or(u16::NAME);
+        if utype != T::NAME {
+            bail!(
+                "CDAWG was built on {} tokens, but is being loaded with {}",
+                utype,
+                T::NAME
+            );
+        }
+        Ok(())
+    }
+
+    /// Check that the CDAWG was built with node and edge indices of type `Ix`.
+    pub fn validate_index_width<Ix: IndexType>(&self) -> Result<()> {
+        let bits = self.index_width.unwrap_or(index_bits::<DefaultIx>());
+        if bits != index_bits::<Ix>() {
+            bail!(
+                "CDAWG was built with {}-bit indices, but is being loaded with {}-bit indices",
+                bits,
+                index_bits::<Ix>()
+            );
+        }
+        Ok(())
+    }
+    /// Check that the CDAWG was built with node weights of type `W`.
+    pub fn validate_weight<W: Weight>(&self) -> Result<()> {
+        let weight = match self.weight {
+            Some(ref weight) => weight.clone(),
+            None => {
+                let bits = self.index_width.unwrap_or(index_bits::<DefaultIx>());
+                WeightType::default_for(bits, CountMode::Exact).to_string()
+            }
+        };
+        if weight != W::name() {
+            bail!(
+                "CDAWG was built with {} node weights, but is being loaded with {}",
+                weight,
+                W::name()
+            );
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 #[allow(unused_imports)]
 mod tests {
     use super::*;
+    use crate::weight::{DefaultWeight, Weight32};
     use tempfile::NamedTempFile;
 
     #[test]
@@ -42,6 +126,17 @@ mod tests {
             source: 42,
             sink: 35,
             end_position: 54,
+            snapshots: Vec::new(),
+            tokens: None,
+            totals: Some(CorpusTotals::default()),
+            n_nodes: Some(7),
+            n_edges: None,
+            docs: None,
+            utype: None,
+            separator: DocumentSeparator::Concatenate,
+            tombstones: Tombstones::default(),
+            index_width: None,
+            weight: None,
         };
         blob.save_json(path).unwrap();
 
@@ -49,5 +144,24 @@ mod tests {
         assert_eq!(blob2.source, 42);
         assert_eq!(blob2.sink, 35);
         assert_eq!(blob2.end_position, 54);
+        assert_eq!(blob2.totals, Some(CorpusTotals::default()));
+        assert_eq!(blob2.separator, DocumentSeparator::Concatenate);
+        assert_eq!(blob2.n_nodes, Some(7));
+        assert!(blob2.validate_utype::<u16>().is_ok());
+        assert!(blob2.validate_utype::<u32>().is_err());
+        assert!(blob2.validate_index_width::<DefaultIx>().is_ok());
+        assert!(blob2.validate_index_width::<u64>().is_err());
+        assert!(blob2.validate_weight::<DefaultWeight>().is_ok());
+        assert!(blob2.validate_weight::<Weight32>().is_err());
+    }
+
+    #[test]
+    fn test_load_empty_json() {
+        let file = NamedTempFile::new().expect("Failed to create file");
+        let err = CdawgMetadata::load_json(file.path()).err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<FormatError>(),
+            Some(FormatError::Truncated { len: 0, .. })
+        ));
     }
 }
@@ -2,12 +2,38 @@ pub mod cdawg_edge_weight; // Refered to in higher level types.
 mod topological_counter; // Traverses a built CDAWG to add counts to the states.
 pub mod traverse_arity;
 
+pub mod absolute_discounting; // Smoothed n-gram probabilities from the counts and failures.
+#[cfg(feature = "async")]
+pub mod async_cdawg; // Read-only CDAWG on disk with async queries, for servers.
+pub mod cached_tokens; // Block cache for reading tokens from disk while building.
+pub mod cdawg_edge; // Edge with its span decoded into tokens, for inspecting the graph.
 pub mod cdawg_state;
+pub mod checkpoint; // Copies of a disk build at document boundaries, for restarting it.
 pub mod comparator;
+pub mod compressed_tokens; // Token vector stored on disk in deflated frames.
+pub mod containment; // Check that a token stream is fully contained in the indexed corpus.
+pub mod corpus_totals; // Corpus size saved in the metadata, for normalizing counts.
+pub mod count_kind; // Selects occurrence, document or weighted counts at query time.
+pub mod doc_frequencies; // Number of documents that each state occurs in, for DF counts.
+pub mod doc_spans; // Token span of each document, for attributing matches.
+pub mod domain_counts; // Count of each state in each domain, exact or sketched.
+pub mod frequent_spans; // Substrings counted at least some number of times, for corpus analysis.
+pub mod from_dawg; // Converts a built DAWG and its tokens into a CDAWG without rebuilding.
+pub mod frozen; // Read-only copy that can be queried from many threads.
 mod inenaga; // Algo from "On-line construction of compact directed acyclic word graphs"
-mod metadata;
+pub mod lcs; // Longest substring shared with a query corpus, and the matched length at each position.
+pub mod metadata; // Saved next to a built CDAWG, describing its tokens, documents and build.
+pub mod novelty; // Fraction of a query corpus's n-grams that don't occur in the indexed corpus.
+pub mod overlap; // Longest substring shared with the indexed corpus, per query document.
+pub mod sampling; // Seeded RNG and one-pass weighted choice for sampling next tokens.
+pub mod shared_tokens; // Lets several indices reference one token vector, checked by hash.
+pub mod snapshot; // Cheap markers for querying the index as of an earlier token position.
 mod stack;
+pub mod stats; // Periodic size statistics while building.
+pub mod text_query; // String queries with decoded continuations, using the corpus tokenizer.
 pub mod token_backing;
+pub mod tombstones; // Documents deleted after building, skipped by document queries.
+pub mod top_substrings; // Most frequent substrings of a given length, for corpus analysis.
 
 // We will use the Inenaga implementation of the build algorithm.
 pub use self::inenaga::Cdawg;
@@ -0,0 +1,123 @@
+// N-gram novelty of a query corpus B, e.g. LM generations, against the corpus A indexed by a CDAWG.
+//
+// B is streamed through the CDAWG, as in the Evaluator. The n-gram ending at a position occurs in A
+// iff the matched length there is at least n, so one pass counts the novel n-grams for every n at
+// once. N-grams spanning document separators in B are not counted.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::inenaga::Cdawg;
+use crate::cdawg::token_backing::{CdawgToken, TokenBacking};
+use crate::graph::indexing::IndexType;
+use crate::memory_backing::MemoryBacking;
+use crate::metrics;
+use crate::weight::Weight;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NgramNovelty {
+    pub n: usize,
+    pub n_ngrams: usize, // Number of n-grams in B, counting repeats.
+    pub n_novel: usize,  // How many of them don't occur in A.
+}
+
+impl NgramNovelty {
+    pub fn n_duplicated(&self) -> usize {
+        self.n_ngrams - self.n_novel
+    }
+
+    /// Fraction of the n-grams that don't occur in A, or 0 if B has none.
+    pub fn novel_fraction(&self) -> f64 {
+        if self.n_ngrams == 0 {
+            return 0.;
+        }
+        self.n_novel as f64 / self.n_ngrams as f64
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct NoveltyReport {
+    pub n_tokens: usize, // Number of query tokens (not counting document separators).
+    pub ngrams: Vec<NgramNovelty>, // One entry for each n from 1 to the maximum.
+}
+
+/// Count the novel n-grams of `query` for n = 1..=`max_n`.
+pub fn ngram_novelty<W, Ix, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    query: &dyn TokenBacking<T>,
+    max_n: usize,
+) -> NoveltyReport
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    let _timer = metrics::time_query();
+    let mut report = NoveltyReport {
+        n_tokens: 0,
+        ngrams: (1..=max_n)
+            .map(|n| NgramNovelty {
+                n,
+                ..Default::default()
+            })
+            .collect(),
+    };
+    let mut cs = cdawg.get_initial();
+    let mut doc_length = 0;
+    for idx in 0..query.len() {
+        let token = query.get(idx);
+        if token == T::end() {
+            cs = cdawg.get_initial();
+            doc_length = 0;
+            continue;
+        }
+        cs = cdawg.transition_and_count(cs, token);
+        report.n_tokens += 1;
+        doc_length += 1;
+        // The n-grams ending here are novel for every n longer than the match.
+        let matched = cs.length as usize;
+        for ngram in report.ngrams.iter_mut().take(doc_length) {
+            ngram.n_ngrams += 1;
+            if ngram.n > matched {
+                ngram.n_novel += 1;
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_ngram_novelty() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        let end = u16::MAX;
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![c, o, c, o, a, end])));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+
+        // Matched lengths are 1, 2, 0 in the first document and 1, 2 in the second.
+        let query = vec![c, o, l, end, o, a];
+        let report = ngram_novelty(&cdawg, &query, 3);
+        assert_eq!(report.n_tokens, 5);
+        let counts: Vec<_> = report
+            .ngrams
+            .iter()
+            .map(|ngram| (ngram.n, ngram.n_ngrams, ngram.n_novel))
+            .collect();
+        assert_eq!(counts, vec![(1, 5, 1), (2, 3, 1), (3, 1, 1)]);
+        assert_eq!(report.ngrams[0].n_duplicated(), 4);
+        assert_eq!(report.ngrams[2].novel_fraction(), 1.);
+
+        let empty: Vec<u16> = Vec::new();
+        let report = ngram_novelty(&cdawg, &empty, 2);
+        assert_eq!(report.ngrams[1].novel_fraction(), 0.);
+    }
+}
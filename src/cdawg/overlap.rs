@@ -0,0 +1,158 @@
+// Per-document overlap between a query corpus B and the corpus A indexed by a CDAWG.
+//
+// For each document in B (delimited by T::end() in the token stream), we find the longest substring
+// that also occurs in A, along with its count in A. Documents are matched independently, so they
+// can be scored in parallel with one CDAWG handle per worker.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::inenaga::Cdawg;
+use crate::cdawg::token_backing::{CdawgToken, TokenBacking};
+use crate::graph::indexing::IndexType;
+use crate::memory_backing::MemoryBacking;
+use crate::weight::Weight;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DocumentOverlap {
+    pub doc_idx: usize,    // Index of the document in B.
+    pub start: usize,      // Position of the document's first token in B.
+    pub n_tokens: usize,   // Length of the document.
+    pub max_length: usize, // Length of the longest substring also in A.
+    pub max_count: usize,  // Count of that substring in A, or 0 if no token matched.
+    pub match_end: usize,  // End of the longest match, as an offset into the document.
+}
+
+/// Split a token stream into [start, end) spans of documents, excluding the end-of-document separators.
+pub fn document_spans<T, B>(tokens: &B) -> Vec<(usize, usize)>
+where
+    T: CdawgToken,
+    B: TokenBacking<T> + ?Sized,
+{
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for idx in 0..tokens.len() {
+        if tokens.get(idx) == T::end() {
+            spans.push((start, idx));
+            start = idx + 1;
+        }
+    }
+    if start < tokens.len() {
+        spans.push((start, tokens.len()));
+    }
+    spans
+}
+
+/// Score the document `query[start..end]` against `cdawg`.
+pub fn score_document<W, Ix, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    query: &dyn TokenBacking<T>,
+    doc_idx: usize,
+    (start, end): (usize, usize),
+) -> DocumentOverlap
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    let mut overlap = DocumentOverlap {
+        doc_idx,
+        start,
+        n_tokens: end - start,
+        ..Default::default()
+    };
+    let mut cs = cdawg.get_initial();
+    for idx in start..end {
+        cs = cdawg.transition_and_count(cs, query.get(idx));
+        let length = cs.length as usize;
+        if length > overlap.max_length {
+            overlap.max_length = length;
+            overlap.max_count = cdawg.get_suffix_count(cs);
+            overlap.match_end = idx + 1 - start;
+        }
+    }
+    overlap
+}
+
+/// Matched length and count at each position of the document `query[start..end]`: the longest
+/// substring of A ending there and its count in A, or 0 if no token matched.
+pub fn match_document<W, Ix, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    query: &dyn TokenBacking<T>,
+    (start, end): (usize, usize),
+) -> (Vec<u64>, Vec<usize>)
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    let mut lengths = Vec::with_capacity(end - start);
+    let mut counts = Vec::with_capacity(end - start);
+    let mut cs = cdawg.get_initial();
+    for idx in start..end {
+        cs = cdawg.transition_and_count(cs, query.get(idx));
+        lengths.push(cs.length);
+        counts.push(if cs.length > 0 {
+            cdawg.get_suffix_count(cs)
+        } else {
+            0
+        });
+    }
+    (lengths, counts)
+}
+
+#[cfg(test)]
+#[allow(unused_variables)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_score_documents_cocoa() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![c, o, c, o, a, u16::MAX])));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+
+        let query = vec![l, c, o, l, u16::MAX, o, c, o, a, u16::MAX, l];
+        let spans = document_spans(&query);
+        assert_eq!(spans, vec![(0, 4), (5, 9), (10, 11)]);
+
+        let overlaps: Vec<_> = spans
+            .iter()
+            .enumerate()
+            .map(|(doc_idx, span)| score_document(&cdawg, &query, doc_idx, *span))
+            .collect();
+        // "co" occurs twice in "cocoa".
+        assert_eq!((overlaps[0].max_length, overlaps[0].max_count), (2, 2));
+        assert_eq!(overlaps[0].match_end, 3);
+        // "ocoa" occurs once.
+        assert_eq!((overlaps[1].max_length, overlaps[1].max_count), (4, 1));
+        assert_eq!((overlaps[2].max_length, overlaps[2].max_count), (0, 0));
+    }
+
+    #[test]
+    fn test_match_document_cocoa() {
+        let (c, o, a, l) = (0, 1, 2, 3);
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![c, o, c, o, a, u16::MAX])));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+
+        let query = vec![l, c, o, l, u16::MAX, o, c, o, a];
+        let (lengths, counts) = match_document(&cdawg, &query, (0, 4));
+        assert_eq!(lengths, vec![0, 1, 2, 0]);
+        assert_eq!(counts, vec![0, 2, 2, 0]);
+        let (lengths, counts) = match_document(&cdawg, &query, (5, 9));
+        assert_eq!(lengths, vec![1, 2, 3, 4]);
+        assert_eq!(counts, vec![2, 1, 1, 1]);
+    }
+}
@@ -0,0 +1,99 @@
+// Randomness for sampling from a CDAWG, without depending on an RNG crate.
+//
+// `XorShiftRng` is a small seeded generator (xorshift64* with a mixed-in constant, so that a seed
+// of zero works). `WeightedChoice` picks one of a stream of weighted items in a single pass, so a
+// next token can be sampled without collecting a state's edges first.
+
+#[derive(Debug, Clone)]
+pub struct XorShiftRng {
+    state: u64,
+}
+
+impl XorShiftRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        // Zero is a fixed point of xorshift, so mix in a constant.
+        let mut x = self.state ^ 0x9E3779B97F4A7C15;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x ^ 0x9E3779B97F4A7C15;
+        x.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    /// Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Sample an item with probability proportional to its weight, seeing each item once.
+pub struct WeightedChoice<T> {
+    total: f64,
+    choice: Option<T>,
+}
+
+impl<T> Default for WeightedChoice<T> {
+    fn default() -> Self {
+        Self {
+            total: 0.,
+            choice: None,
+        }
+    }
+}
+
+impl<T> WeightedChoice<T> {
+    pub fn add(&mut self, item: T, weight: f64, rng: &mut XorShiftRng) {
+        if weight <= 0. {
+            return;
+        }
+        // Replacing with probability weight / total keeps each item's probability proportional to
+        // its weight among those seen so far.
+        self.total += weight;
+        if rng.next_f64() * self.total < weight {
+            self.choice = Some(item);
+        }
+    }
+
+    pub fn get(self) -> Option<T> {
+        self.choice
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_f64() {
+        let mut rng = XorShiftRng::new(0);
+        for _ in 0..1000 {
+            let x = rng.next_f64();
+            assert!((0. ..1.).contains(&x));
+        }
+        assert_ne!(
+            XorShiftRng::new(1).next_u64(),
+            XorShiftRng::new(2).next_u64()
+        );
+    }
+
+    #[test]
+    fn test_weighted_choice() {
+        let mut rng = XorShiftRng::new(42);
+        let mut n_heads = 0;
+        for _ in 0..10000 {
+            let mut choice = WeightedChoice::default();
+            choice.add("heads", 3., &mut rng);
+            choice.add("tails", 1., &mut rng);
+            choice.add("edge", 0., &mut rng);
+            if choice.get() == Some("heads") {
+                n_heads += 1;
+            }
+        }
+        assert!((7000..8000).contains(&n_heads));
+        assert_eq!(WeightedChoice::<u16>::default().get(), None);
+    }
+}
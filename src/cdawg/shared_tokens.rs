@@ -0,0 +1,179 @@
+// Reference to a token vector shared by several CDAWGs, e.g. pruned, per-domain and full indices
+// over the same corpus.
+//
+// Rather than each index directory keeping its own copy of the tokens, the metadata records the
+// path of a shared DiskVec, relative to the index directory, along with its length and a content
+// hash. Only the length is checked when the index is loaded, since hashing takes a pass over every
+// token. The `verify` command checks the hash, to catch an index paired with a modified token file.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::mem::size_of;
+use std::path::{Component, Path, PathBuf};
+
+use crate::cdawg::token_backing::{CdawgToken, TokenBacking};
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+pub(crate) const FNV_PRIME: u64 = 0x100000001b3;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SharedTokens {
+    pub path: PathBuf,
+    pub len: usize,
+    pub hash: u64,
+}
+
+impl SharedTokens {
+    pub fn new<P, T, B>(path: P, tokens: &B) -> Self
+    where
+        P: Into<PathBuf>,
+        T: CdawgToken,
+        B: TokenBacking<T> + ?Sized,
+    {
+        Self {
+            path: path.into(),
+            len: tokens.len(),
+            hash: hash_tokens(tokens),
+        }
+    }
+
+    /// Check that `tokens` have the length this reference was created with. This is cheap enough to
+    /// do on every load.
+    pub fn check_len<T, B>(&self, tokens: &B) -> Result<()>
+    where
+        T: CdawgToken,
+        B: TokenBacking<T> + ?Sized,
+    {
+        if tokens.len() != self.len {
+            bail!(
+                "Token backing {:?} has {} tokens, but the index expects {}",
+                self.path,
+                tokens.len(),
+                self.len
+            );
+        }
+        Ok(())
+    }
+
+    /// Check that `tokens` are the ones this reference was created from, by length and hash.
+    pub fn verify<T, B>(&self, tokens: &B) -> Result<()>
+    where
+        T: CdawgToken,
+        B: TokenBacking<T> + ?Sized,
+    {
+        self.check_len(tokens)?;
+        let hash = hash_tokens(tokens);
+        if hash != self.hash {
+            bail!(
+                "Token backing {:?} has hash {:x}, but the index expects {:x}",
+                self.path,
+                hash,
+                self.hash
+            );
+        }
+        Ok(())
+    }
+
+    /// This reference with its path resolved against the index directory `dir`, as it is stored in
+    /// metadata. Absolute paths, as older indices stored, are kept as they are.
+    pub fn resolve<P: AsRef<Path>>(&self, dir: P) -> Self {
+        Self {
+            path: dir.as_ref().join(&self.path),
+            ..self.clone()
+        }
+    }
+
+    /// This reference with its path relative to the index directory `dir`, so that the index and
+    /// tokens can be moved together. Both must exist.
+    pub fn relative_to<P: AsRef<Path>>(&self, dir: P) -> Result<Self> {
+        Ok(Self {
+            path: relative_path(&self.path, dir.as_ref())?,
+            ..self.clone()
+        })
+    }
+}
+
+// The path from the directory `base` to `path`, e.g. `../tokens.vec`.
+fn relative_path(path: &Path, base: &Path) -> Result<PathBuf> {
+    let path = path.canonicalize()?;
+    let base = base.canonicalize()?;
+    let n_common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative: PathBuf = base
+        .components()
+        .skip(n_common)
+        .map(|_| Component::ParentDir)
+        .collect();
+    relative.extend(path.components().skip(n_common));
+    Ok(relative)
+}
+
+/// FNV-1a hash of the tokens as little-endian bytes. This is stable across platforms and Rust
+/// versions, unlike `std::hash`, so it can be stored in metadata. It takes one pass over the tokens.
+pub fn hash_tokens<T, B>(tokens: &B) -> u64
+where
+    T: CdawgToken,
+    B: TokenBacking<T> + ?Sized,
+{
+    let mut hash = FNV_OFFSET;
+    let width = size_of::<T>();
+    for idx in 0..tokens.len() {
+        let token: u64 = tokens.get(idx).into();
+        for &byte in &token.to_le_bytes()[..width] {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check_len() {
+        let tokens: Vec<u16> = vec![1, 2, 3, u16::MAX];
+        let shared = SharedTokens::new("tokens.vec", &tokens);
+        let swapped: Vec<u16> = vec![2, 1, 3, u16::MAX];
+        assert!(shared.check_len(&swapped).is_ok());
+        let truncated: Vec<u16> = vec![1, 2, 3];
+        assert!(shared.check_len(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let tokens: Vec<u16> = vec![1, 2, 3, u16::MAX];
+        let shared = SharedTokens::new("tokens.vec", &tokens);
+        assert!(shared.verify(&tokens).is_ok());
+
+        let swapped: Vec<u16> = vec![2, 1, 3, u16::MAX];
+        assert!(shared.verify(&swapped).is_err());
+        let truncated: Vec<u16> = vec![1, 2, 3];
+        assert!(shared.verify(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let tmp_dir = tempdir().unwrap();
+        let tokens_path = tmp_dir.path().join("tokens.vec");
+        let index_dir = tmp_dir.path().join("indices").join("full");
+        std::fs::write(&tokens_path, []).unwrap();
+        std::fs::create_dir_all(&index_dir).unwrap();
+
+        let tokens: Vec<u16> = vec![];
+        let shared = SharedTokens::new(&tokens_path, &tokens);
+        let relative = shared.relative_to(&index_dir).unwrap();
+        assert_eq!(relative.path, Path::new("../../tokens.vec"));
+        assert_eq!(
+            relative.resolve(&index_dir).path.canonicalize().unwrap(),
+            tokens_path.canonicalize().unwrap()
+        );
+        assert_eq!(shared.resolve(&index_dir).path, tokens_path);
+    }
+}
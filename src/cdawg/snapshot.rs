@@ -0,0 +1,49 @@
+// Snapshot markers recorded while a CDAWG is built online.
+//
+// A snapshot is cheap metadata describing the index after the first `end_position` tokens. It is
+// used to answer "as of token T" queries approximately (see `Cdawg::transition_and_count_at`):
+// matches are only accepted if the occurrence recorded on the CDAWG edge they end on lies within
+// the first T tokens. Edge labels point to a single occurrence of each edge, so a suffix that did
+// occur before T can occasionally be cut short, and counts are always those of the full corpus.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub end_position: usize, // Number of tokens consumed when the snapshot was taken.
+    pub n_nodes: usize,
+    pub n_edges: usize,
+}
+
+/// Find the latest snapshot taken at or before `end_position`.
+pub fn latest_before(snapshots: &[Snapshot], end_position: usize) -> Option<Snapshot> {
+    snapshots
+        .iter()
+        .filter(|snapshot| snapshot.end_position <= end_position)
+        .max_by_key(|snapshot| snapshot.end_position)
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_before() {
+        let snapshots = vec![
+            Snapshot {
+                end_position: 10,
+                n_nodes: 5,
+                n_edges: 7,
+            },
+            Snapshot {
+                end_position: 20,
+                n_nodes: 9,
+                n_edges: 14,
+            },
+        ];
+        assert_eq!(latest_before(&snapshots, 5), None);
+        assert_eq!(latest_before(&snapshots, 15), Some(snapshots[0]));
+        assert_eq!(latest_before(&snapshots, 20), Some(snapshots[1]));
+    }
+}
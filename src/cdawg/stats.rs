@@ -0,0 +1,312 @@
+// Statistics about the size of a CDAWG, computed periodically while it is built.
+//
+// A `StatsTracker` is called after each token is added. When its `EvalSchedule` fires, it computes
+// `BuildStats`, appends them to a JSONL file if one is configured, and returns them so the caller
+// can also report them elsewhere (e.g., in a progress bar).
+//
+// With `with_detail`, each computation also takes a pass over the graph for the distribution of
+// next-token entropies over states and the growth in size since the last computation, and with
+// `with_held_out`, a pass over a held-out token stream for the average length of the longest
+// suffix matched at each token. Entropies need counts, so they are filled temporarily if they
+// haven't been yet, which makes detailed stats about as expensive as an evaluation.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::build_stats::BuildStats;
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::cdawg::{Cdawg, TopologicalCounter};
+use crate::eval_schedule::EvalSchedule;
+use crate::graph::indexing::{IndexType, NodeIndex};
+use crate::graph::EdgeRef;
+use crate::memory_backing::MemoryBacking;
+use crate::weight::Weight;
+
+pub struct StatsConfig<T> {
+    pub schedule: EvalSchedule,
+    pub n_bytes: u64,          // Size of the training data, used for tokens/byte.
+    pub path: Option<PathBuf>, // JSONL file that stats are appended to.
+    pub detail: bool,          // Whether to compute `DetailedStats`.
+    pub held_out: Vec<T>,      // Tokens to measure suffix lengths on, if any.
+}
+
+impl<T> StatsConfig<T> {
+    pub fn new(schedule: EvalSchedule, n_bytes: u64) -> Self {
+        Self {
+            schedule,
+            n_bytes,
+            path: None,
+            detail: false,
+            held_out: Vec::new(),
+        }
+    }
+
+    pub fn with_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_detail(mut self) -> Self {
+        self.detail = true;
+        self
+    }
+
+    /// Also measure suffix lengths on `held_out`, which implies `with_detail`.
+    pub fn with_held_out(mut self, held_out: Vec<T>) -> Self {
+        self.detail = true;
+        self.held_out = held_out;
+        self
+    }
+}
+
+/// Stats that take a pass over the graph, saved as the `detail` of `BuildStats`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DetailedStats {
+    pub node_growth: f64, // Nodes added per token since the last stats, or since the start.
+    pub edge_growth: f64, // Edges added per token, likewise.
+    pub entropy_mean: f64, // Mean next-token entropy in bits, over states with transitions.
+    pub entropy_histogram: Vec<usize>, // Number of states with entropy in [i, i + 1) bits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub held_out_suffix_length: Option<f64>, // Mean suffix length matched at each held-out token.
+}
+
+/// Entropy in bits of the next token after each state with transitions, weighted by the counts of
+/// the targets. The self-loops that label sinks aren't transitions.
+pub fn state_entropies<W, Ix, Mb, T>(cdawg: &Cdawg<W, Ix, Mb, T>) -> Vec<f64>
+where
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Ix: IndexType,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+{
+    let mut entropies = Vec::new();
+    for idx in 0..cdawg.node_count() {
+        let state = NodeIndex::new(idx);
+        let counts: Vec<f64> = cdawg
+            .get_graph()
+            .edges(state)
+            .map(|edge| edge.get_target())
+            .filter(|target| *target != state)
+            .map(|target| cdawg.get_count(target) as f64)
+            .collect();
+        let total: f64 = counts.iter().sum();
+        if total == 0. {
+            continue;
+        }
+        let entropy = counts
+            .iter()
+            .filter(|count| **count > 0.)
+            .map(|count| -(count / total) * (count / total).log2())
+            .sum();
+        entropies.push(entropy);
+    }
+    entropies
+}
+
+/// Mean length of the longest suffix of `tokens` matched in `cdawg` after each token.
+pub fn mean_suffix_length<W, Ix, Mb, T>(cdawg: &Cdawg<W, Ix, Mb, T>, tokens: &[T]) -> f64
+where
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Ix: IndexType,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+{
+    if tokens.is_empty() {
+        return 0.;
+    }
+    let mut cs = cdawg.get_initial();
+    let mut total = 0;
+    for token in tokens {
+        cs = cdawg.transition_and_count(cs, *token);
+        total += cs.length;
+    }
+    total as f64 / tokens.len() as f64
+}
+
+pub struct StatsTracker<T> {
+    config: StatsConfig<T>,
+    start: Instant,
+    last: (usize, usize, usize), // Tokens, nodes and edges when stats were last computed.
+}
+
+impl<T> StatsTracker<T>
+where
+    T: CdawgToken,
+{
+    /// Create a tracker. Elapsed times are measured from this call.
+    pub fn new(config: StatsConfig<T>) -> Self {
+        Self {
+            config,
+            start: Instant::now(),
+            last: (0, 0, 0),
+        }
+    }
+
+    /// Compute stats for `cdawg` after `n_tokens` tokens, without saving them. Counts are filled
+    /// and reset again if detailed stats need them.
+    pub fn compute<W, Ix, Mb>(
+        &mut self,
+        cdawg: &mut Cdawg<W, Ix, Mb, T>,
+        n_tokens: usize,
+    ) -> BuildStats
+    where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Ix: IndexType + Serialize + for<'de> Deserialize<'de>,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    {
+        let elapsed_time = self.start.elapsed().as_secs_f32();
+        let mut stats = BuildStats::from_cdawg(cdawg, n_tokens, self.config.n_bytes, elapsed_time);
+        if self.config.detail {
+            stats.detail = Some(self.compute_detail(cdawg, &stats));
+        }
+        self.last = (n_tokens, stats.n_nodes, stats.n_edges);
+        stats
+    }
+
+    fn compute_detail<W, Ix, Mb>(
+        &self,
+        cdawg: &mut Cdawg<W, Ix, Mb, T>,
+        stats: &BuildStats,
+    ) -> DetailedStats
+    where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Ix: IndexType + Serialize + for<'de> Deserialize<'de>,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    {
+        let (last_tokens, last_nodes, last_edges) = self.last;
+        let n_new = stats.n_tokens.saturating_sub(last_tokens).max(1) as f64;
+        let counts_filled = cdawg.get_count(cdawg.get_source()) > 0;
+        if !counts_filled {
+            TopologicalCounter::new_ram().fill_counts(cdawg);
+        }
+        let entropies = state_entropies(cdawg);
+        if !counts_filled {
+            cdawg.reset_counts();
+        }
+
+        let mut entropy_histogram = Vec::new();
+        for entropy in entropies.iter() {
+            let bin = *entropy as usize;
+            if bin >= entropy_histogram.len() {
+                entropy_histogram.resize(bin + 1, 0);
+            }
+            entropy_histogram[bin] += 1;
+        }
+        let held_out = &self.config.held_out;
+        DetailedStats {
+            node_growth: stats.n_nodes.saturating_sub(last_nodes) as f64 / n_new,
+            edge_growth: stats.n_edges.saturating_sub(last_edges) as f64 / n_new,
+            entropy_mean: entropies.iter().sum::<f64>() / entropies.len().max(1) as f64,
+            entropy_histogram,
+            held_out_suffix_length: (!held_out.is_empty())
+                .then(|| mean_suffix_length(cdawg, held_out)),
+        }
+    }
+
+    /// Call after the `n_tokens`-th token is added. If the schedule fires, saves and returns stats.
+    pub fn observe<W, Ix, Mb>(
+        &mut self,
+        cdawg: &mut Cdawg<W, Ix, Mb, T>,
+        n_tokens: usize,
+    ) -> Result<Option<BuildStats>>
+    where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Ix: IndexType + Serialize + for<'de> Deserialize<'de>,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    {
+        if !self.config.schedule.should_eval(n_tokens) {
+            return Ok(None);
+        }
+        self.finish(cdawg, n_tokens).map(Some)
+    }
+
+    /// Save and return stats for the finished CDAWG, regardless of the schedule.
+    pub fn finish<W, Ix, Mb>(
+        &mut self,
+        cdawg: &mut Cdawg<W, Ix, Mb, T>,
+        n_tokens: usize,
+    ) -> Result<BuildStats>
+    where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Ix: IndexType + Serialize + for<'de> Deserialize<'de>,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    {
+        let stats = self.compute(cdawg, n_tokens);
+        if let Some(ref path) = self.config.path {
+            stats.append_to_jsonl(path)?;
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_variables)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_observe_cocoa() {
+        let (c, o, a) = (0, 1, 2);
+        let tokens = [c, o, c, o, a, u16::MAX];
+        let train_vec = Rc::new(RefCell::new(Vec::<u16>::new()));
+        let mut cdawg: Cdawg = Cdawg::new(train_vec.clone());
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stats.jsonl");
+        let config = StatsConfig::new(EvalSchedule::linear(2), 6).with_path(&path);
+        let mut tracker = StatsTracker::new(config);
+
+        let mut observed = Vec::new();
+        let (mut state, mut start) = (cdawg.get_source(), 1);
+        for (idx, token) in tokens.iter().enumerate() {
+            train_vec.borrow_mut().push(*token);
+            (state, start) = cdawg.update(state, start, idx + 1);
+            if *token == u16::MAX {
+                (state, start) = cdawg.end_document(idx + 1, 0);
+            }
+            if let Some(stats) = tracker.observe(&mut cdawg, idx + 1).unwrap() {
+                observed.push(stats.n_tokens);
+            }
+        }
+        let stats = tracker.finish(&mut cdawg, tokens.len()).unwrap();
+        assert_eq!(observed, vec![2, 4, 6]);
+        assert_eq!(stats.n_nodes, cdawg.node_count());
+        assert_eq!(stats.get_tokens_per_byte(), 1.);
+
+        assert!(stats.detail.is_none());
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 4);
+    }
+
+    #[test]
+    fn test_detailed_stats() {
+        let (c, o, a) = (0, 1, 2);
+        let tokens = vec![c, o, c, o, a, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        cdawg.build();
+
+        let config = StatsConfig::new(EvalSchedule::linear(2), 6).with_held_out(vec![c, o, a]);
+        let mut tracker = StatsTracker::new(config);
+        let stats = tracker.finish(&mut cdawg, tokens.len()).unwrap();
+        let detail = stats.detail.unwrap();
+        assert_eq!(detail.node_growth, stats.n_nodes as f64 / 6.);
+        // "co" is followed by "c" and "a" once each, and the source by four distinct tokens.
+        assert_eq!(detail.entropy_histogram.iter().sum::<usize>(), 2);
+        assert!(detail.entropy_mean > 1.);
+        // Counts are only filled while computing entropies.
+        assert_eq!(cdawg.get_count(cdawg.get_source()), 0);
+        // "c", "co" and "coa" are matched in full.
+        assert_eq!(detail.held_out_suffix_length, Some(2.));
+
+        // Growth is measured since the last stats.
+        let detail = tracker.compute(&mut cdawg, 12).detail.unwrap();
+        assert_eq!((detail.node_growth, detail.edge_growth), (0., 0.));
+    }
+}
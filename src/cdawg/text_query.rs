@@ -0,0 +1,126 @@
+// String queries against a CDAWG, bundled with the tokenizer the corpus was built with.
+//
+// Queries are tokenized without special tokens, since they can match anywhere in a document.
+// Continuations are decoded one token at a time. The reserved end-of-document token has no text, so
+// it is left out, but special tokens in the vocabulary (e.g., an EOS separator) are kept.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::inenaga::Cdawg;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::graph::indexing::IndexType;
+use crate::memory_backing::MemoryBacking;
+use crate::tokenize::Detokenize;
+use crate::weight::Weight;
+
+pub struct TextQueryEngine<W, Ix, Mb, T>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+{
+    cdawg: Cdawg<W, Ix, Mb, T>,
+    tokenizer: Box<dyn Detokenize<T>>,
+}
+
+impl<W, Ix, Mb, T> TextQueryEngine<W, Ix, Mb, T>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    /// Wrap `cdawg`, whose counts must be filled, and the tokenizer it was built with.
+    pub fn new(cdawg: Cdawg<W, Ix, Mb, T>, tokenizer: Box<dyn Detokenize<T>>) -> Self {
+        Self { cdawg, tokenizer }
+    }
+
+    pub fn get_cdawg(&self) -> &Cdawg<W, Ix, Mb, T> {
+        &self.cdawg
+    }
+
+    pub fn tokenize(&mut self, text: &str) -> Result<Vec<T>> {
+        self.tokenizer.tokenize_query(text)
+    }
+
+    /// Count the occurrences of `text` in the corpus.
+    pub fn count_str(&mut self, text: &str) -> Result<usize> {
+        let query = self.tokenize(text)?;
+        Ok(self.cdawg.get_ngram_count(&query))
+    }
+
+    /// Decoded next tokens after the longest suffix of `text` that occurs, with their
+    /// probabilities, most likely first.
+    pub fn next_token_strs(&mut self, text: &str) -> Result<Vec<(String, f64)>> {
+        let query = self.tokenize(text)?;
+        Ok(get_next_token_strs(&self.cdawg, &query, &*self.tokenizer))
+    }
+}
+
+/// Decoded next tokens after the longest suffix of `query` that occurs in `cdawg`, with their
+/// probabilities, most likely first. Tokens that `decoder` can't decode are left out.
+pub fn get_next_token_strs<W, Ix, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    query: &[T],
+    decoder: &dyn Detokenize<T>,
+) -> Vec<(String, f64)>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    let mut cs = cdawg.get_initial();
+    for token in query {
+        cs = cdawg.transition_and_count(cs, *token);
+    }
+    let mut next_tokens: Vec<_> = cdawg
+        .get_next_tokens(cs)
+        .into_iter()
+        .filter(|(token, _)| *token != T::end())
+        .filter_map(|(token, prob)| Some((decoder.decode(token)?, prob)))
+        .collect();
+    next_tokens.sort_by(|a, b| b.1.total_cmp(&a.1));
+    next_tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use crate::tokenize::{TokenIndex, Tokenize};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_text_query_engine() {
+        let mut index: TokenIndex<u16> = TokenIndex::new();
+        let text = "the cat sat on the mat";
+        index.build(text);
+        let mut tokens = index.tokenize(text);
+        tokens.push(u16::MAX);
+
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        let mut engine = TextQueryEngine::new(cdawg, Box::new(index));
+
+        assert_eq!(engine.count_str("the").unwrap(), 2);
+        assert_eq!(engine.count_str("sat on the").unwrap(), 1);
+        assert_eq!(engine.count_str("the dog").unwrap(), 0);
+
+        let next = engine.next_token_strs("the").unwrap();
+        let next_strs: Vec<_> = next.iter().map(|(token, _)| token.as_str()).collect();
+        assert_eq!(next_strs.len(), 2);
+        assert!(next_strs.contains(&"cat") && next_strs.contains(&"mat"));
+        assert_eq!(next[0].1, 0.5);
+
+        // "mat" is only followed by the end of the document, which has no text.
+        assert!(engine.next_token_strs("the mat").unwrap().is_empty());
+    }
+}
@@ -1,9 +1,42 @@
 // A simplified interface for accessing tokens compared to VecBacking.
 
+use anyhow::Result;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
 
 use crate::memory_backing::DiskVec;
+use crate::tokenize::end::End;
+
+/// Token types a CDAWG can be built on. The max value, `End::end()`, is reserved as the
+/// end-of-document token.
+pub trait CdawgToken:
+    Copy
+    + Ord
+    + Hash
+    + Debug
+    + Display
+    + Default
+    + Serialize
+    + DeserializeOwned
+    + End
+    + Into<u64>
+    + TryFrom<u64>
+    + 'static
+{
+    /// Name of the type, as passed to `--utype` and recorded in the metadata.
+    const NAME: &'static str;
+}
+
+impl CdawgToken for u16 {
+    const NAME: &'static str = "u16";
+}
+
+impl CdawgToken for u32 {
+    const NAME: &'static str = "u32";
+}
 
 pub trait TokenBacking<T> {
     fn len(&self) -> usize;
@@ -15,6 +48,11 @@ pub trait TokenBacking<T> {
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    // Write pushed tokens through to disk, for backings that store them in files.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl<T> TokenBacking<T> for Vec<T>
@@ -46,6 +84,10 @@ where
         DiskVec::get(self, index).unwrap()
     }
 
+    fn flush(&self) -> Result<()> {
+        DiskVec::flush(self)
+    }
+
     fn push(&mut self, value: T) {
         let _ = DiskVec::push(self, &value);
     }
@@ -0,0 +1,53 @@
+// Documents deleted from a built CDAWG, saved in its metadata.
+//
+// Deleting a document leaves its tokens and states in place, since removing them would mean
+// rebuilding. Instead, its occurrences are subtracted from the counts, and its end-of-document
+// position is recorded here so that its sink is skipped when locating matches or attributing them
+// to documents. Like `DocSpans`, documents are keyed by that position, since IDs need not be unique.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstones {
+    ends: BTreeSet<usize>, // Positions of the end-of-document tokens (0-indexed).
+}
+
+impl Tombstones {
+    /// Mark the document whose end-of-document token is at position `end` (0-indexed) as deleted.
+    /// Returns false if it already was.
+    pub fn insert(&mut self, end: usize) -> bool {
+        self.ends.insert(end)
+    }
+
+    pub fn contains(&self, end: usize) -> bool {
+        self.ends.contains(&end)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert() {
+        let mut tombstones = Tombstones::default();
+        assert!(tombstones.insert(4));
+        assert!(!tombstones.insert(4));
+        assert!(tombstones.contains(4));
+        assert!(!tombstones.contains(2));
+        assert_eq!(tombstones.len(), 1);
+
+        let json = serde_json::to_string(&tombstones).unwrap();
+        let loaded: Tombstones = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, tombstones);
+    }
+}
@@ -0,0 +1,185 @@
+// The `k` most frequent substrings of length `n` of the corpus, with their counts.
+//
+// Every substring of the corpus spells a unique path from the source. Partway through an edge, a
+// string can only be extended one way, so its count is the count of the edge's target. A DFS that
+// stops at depth `n` thus finds each length-n substring once, with its count. Counts never increase
+// along a path, so subtrees whose count can't reach the `k`th best found so far are pruned, and
+// children are visited most frequent first so that good candidates are found early. Paths through
+// an end-of-document token are skipped, since they span documents.
+//
+// Ties are broken by the tokens, smallest first, so the result doesn't depend on the edge order.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::{min, Reverse};
+use std::collections::BinaryHeap;
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::inenaga::Cdawg;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::graph::indexing::{IndexType, NodeIndex};
+use crate::graph::EdgeRef;
+use crate::memory_backing::MemoryBacking;
+use crate::metrics;
+use crate::weight::Weight;
+
+// Min-heap of the best substrings so far, whose top is the first to be evicted: the least frequent,
+// and among those, the largest tokens.
+type Best<T> = BinaryHeap<Reverse<(usize, Reverse<Vec<T>>)>>;
+
+/// The `k` most frequent substrings of length `n` in `cdawg`, whose counts must be filled, most
+/// frequent first.
+pub fn top_substrings<W, Ix, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    n: usize,
+    k: usize,
+) -> Vec<(Vec<T>, usize)>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    let _timer = metrics::time_query();
+    if n == 0 || k == 0 {
+        return Vec::new();
+    }
+    let mut best: Best<T> = BinaryHeap::with_capacity(k + 1);
+    let mut stack: Vec<(NodeIndex<Ix>, usize, (usize, usize))> = Vec::new(); // State, depth, label.
+    let mut path: Vec<T> = Vec::new();
+    push_children(cdawg, cdawg.get_source(), 0, 0, &mut stack);
+
+    while let Some((state, depth, (start, end))) = stack.pop() {
+        let count = cdawg.get_count(state);
+        if best.len() == k && count < min_count(&best) {
+            continue; // The bound rose since this was pushed.
+        }
+        path.truncate(depth);
+        let stop = min(end, start - 1 + n - depth);
+        let label = (start - 1..stop).map(|idx| cdawg.get_token(idx));
+        path.extend(label);
+        if path[depth..]
+            .iter()
+            .any(|&token| cdawg.is_document_end(token))
+        {
+            continue;
+        }
+        if path.len() < n {
+            let threshold = if best.len() == k { min_count(&best) } else { 0 };
+            push_children(cdawg, state, path.len(), threshold, &mut stack);
+            continue;
+        }
+        best.push(Reverse((count, Reverse(path.clone()))));
+        if best.len() > k {
+            best.pop();
+        }
+    }
+
+    best.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((count, Reverse(tokens)))| (tokens, count))
+        .collect()
+}
+
+fn min_count<T: Ord>(best: &Best<T>) -> usize {
+    best.peek().map_or(0, |Reverse((count, _))| *count)
+}
+
+// Push the children of `state` counted at least `threshold` times, so the most frequent is popped
+// first.
+fn push_children<W, Ix, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    state: NodeIndex<Ix>,
+    depth: usize,
+    threshold: usize,
+    stack: &mut Vec<(NodeIndex<Ix>, usize, (usize, usize))>,
+) where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+    Mb::EdgeRef: Copy,
+{
+    let mut children: Vec<_> = cdawg
+        .get_graph()
+        .edges(state)
+        .map(|edge| (edge.get_target(), edge.get_weight()))
+        .filter(|&(target, _)| target != state && cdawg.get_count(target) >= threshold)
+        .map(|(target, weight)| (target, cdawg.get_span(weight, target)))
+        .collect();
+    children.sort_by_key(|&(target, _)| cdawg.get_count(target));
+    stack.extend(
+        children
+            .into_iter()
+            .map(|(target, span)| (target, depth, span)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    fn build(tokens: Vec<u16>) -> Cdawg {
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+        cdawg
+    }
+
+    // Count every n-gram within documents directly.
+    fn brute_force(tokens: &[u16], n: usize) -> HashMap<Vec<u16>, usize> {
+        let mut counts = HashMap::new();
+        for doc in tokens.split(|&token| token == u16::MAX) {
+            for ngram in doc.windows(n) {
+                *counts.entry(ngram.to_vec()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn test_top_substrings_abcabcaba() {
+        let (a, b, c) = (0, 1, 2);
+        let cdawg = build(vec![a, b, c, a, b, c, a, b, a, u16::MAX]);
+        assert_eq!(
+            top_substrings(&cdawg, 1, 2),
+            vec![(vec![a], 4), (vec![b], 3)]
+        );
+        assert_eq!(
+            top_substrings(&cdawg, 2, 10),
+            vec![
+                (vec![a, b], 3),
+                (vec![b, c], 2),
+                (vec![c, a], 2),
+                (vec![b, a], 1)
+            ]
+        );
+        assert_eq!(top_substrings(&cdawg, 5, 1), vec![(vec![a, b, c, a, b], 2)]);
+        assert_eq!(top_substrings(&cdawg, 10, 1), vec![]);
+        assert_eq!(top_substrings(&cdawg, 0, 1), vec![]);
+    }
+
+    #[test]
+    fn test_top_substrings_across_documents() {
+        let sep = u16::MAX;
+        let tokens = vec![1, 2, 3, 1, 2, sep, 2, 3, 1, 2, 3, sep, 3, 1, sep];
+        let cdawg = build(tokens.clone());
+        for n in 1..5 {
+            let expected = brute_force(&tokens, n);
+            let top = top_substrings(&cdawg, n, 100);
+            assert_eq!(top.len(), expected.len());
+            for (ngram, count) in top.iter() {
+                assert_eq!(expected[ngram], *count);
+            }
+            assert!(top
+                .windows(2)
+                .all(|w| (Reverse(w[0].1), &w[0].0) < (Reverse(w[1].1), &w[1].0)));
+            assert_eq!(top_substrings(&cdawg, n, 2), top[..2.min(top.len())]);
+        }
+    }
+}
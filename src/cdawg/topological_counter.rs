@@ -1,12 +1,18 @@
 use anyhow::Result;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 
 use serde::{Deserialize, Serialize};
 
 use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::domain_counts::{DomainCounts, DomainLayout};
 use crate::cdawg::inenaga::Cdawg;
 use crate::cdawg::stack::Stack;
+use crate::cdawg::token_backing::CdawgToken;
 use crate::graph::indexing::{IndexType, NodeIndex};
+use crate::graph::EdgeRef;
 use crate::memory_backing::{DiskVec, MemoryBacking};
 use crate::weight::Weight;
 
@@ -38,6 +44,61 @@ where
     pub fn new_ram() -> Self {
         Self { stack: Vec::new() }
     }
+
+    /// Like `fill_counts`, but sums counts on `n_threads` threads. Graph backings aren't thread-safe,
+    /// so the edges are first read into RAM, which takes memory proportional to the graph. States
+    /// are then counted in reverse topological order: a state is ready once all of its children
+    /// have been counted, and threads take ready states from a shared queue.
+    pub fn fill_counts_parallel<W, Mb, T>(
+        &mut self,
+        cdawg: &mut Cdawg<W, Ix, Mb, T>,
+        n_threads: usize,
+    ) where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        T: CdawgToken,
+    {
+        let dag = CountDag::new(cdawg);
+        let counts = dag.count(n_threads);
+        for (state, count) in counts.into_iter().enumerate() {
+            if !dag.fixed[state] {
+                let idx = NodeIndex::new(state);
+                cdawg.set_count(idx, count);
+                cdawg.set_terminal(idx, dag.terminal[state]);
+            }
+        }
+    }
+
+    /// Fill the document frequency of every state, i.e. how many documents its strings occur in.
+    /// Like `fill_counts_parallel`, this first reads the edges into RAM. Documents are counted by
+    /// walking back from their sinks, so this takes time proportional to the sum of the frequencies.
+    pub fn fill_doc_frequencies<W, Mb, T>(&mut self, cdawg: &mut Cdawg<W, Ix, Mb, T>)
+    where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        T: CdawgToken,
+    {
+        let doc_freqs = count_doc_frequencies(cdawg);
+        cdawg.set_doc_frequencies(doc_freqs);
+    }
+
+    /// Fill the count of every state in each domain, i.e. in the documents labelled with each ID.
+    /// Up to `max_exact` domains are counted exactly, and more are sketched. Like
+    /// `fill_counts_parallel`, this first reads the edges into RAM, and it keeps a row of counts for
+    /// every state in RAM too.
+    pub fn fill_domain_counts<W, Mb, T>(
+        &mut self,
+        cdawg: &mut Cdawg<W, Ix, Mb, T>,
+        max_exact: usize,
+    ) where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        T: CdawgToken,
+    {
+        let layout = DomainLayout::new(get_domains(cdawg), max_exact);
+        let domain_counts = count_domains(cdawg, layout);
+        cdawg.set_domain_counts(domain_counts);
+    }
 }
 
 impl<Ix> TopologicalCounter<DiskVec<StackOp<Ix>>>
@@ -51,12 +112,13 @@ where
 }
 
 impl<Sb> TopologicalCounter<Sb> {
-    /// DFS implementation of graph traversal.
-    pub fn fill_counts<Ix, W, Mb>(&mut self, cdawg: &mut Cdawg<W, Ix, Mb>)
+    /// DFS implementation of graph traversal. Also marks states with end-of-document edges as terminal.
+    pub fn fill_counts<Ix, W, Mb, T>(&mut self, cdawg: &mut Cdawg<W, Ix, Mb, T>)
     where
         Ix: IndexType + Serialize + for<'de> Deserialize<'de>,
         W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
         Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        T: CdawgToken,
         Sb: Stack<StackOp<Ix>>,
     {
         self.stack.push(StackOp::open(cdawg.get_source()));
@@ -74,15 +136,260 @@ impl<Sb> TopologicalCounter<Sb> {
                 }
             } else {
                 // Closing! Set counts appropriately after all children have been handled.
-                let neighbors: Vec<_> = cdawg.get_graph().neighbors(op.state).collect();
+                let edges: Vec<_> = cdawg
+                    .get_graph()
+                    .edges(op.state)
+                    .map(|edge| (edge.get_target(), edge.get_weight()))
+                    .collect();
                 let mut count = 0;
-                for next_state in neighbors {
+                let mut terminal = false;
+                for (next_state, weight) in edges {
+                    // A deleted document's sink counts no occurrences.
+                    if next_state == op.state && cdawg.is_deleted_loop(weight) {
+                        continue;
+                    }
                     count += cdawg.get_count(next_state);
+                    // Skip the self-loop that labels each sink with its document.
+                    terminal |= next_state != op.state && cdawg.is_sentinel_edge(weight);
                 }
                 cdawg.set_count(op.state, count);
+                cdawg.set_terminal(op.state, terminal);
+            }
+        }
+    }
+}
+
+/// The number of documents whose sinks are reachable from each state. A sink is labelled with its
+/// document by a self-loop, and deleted documents are skipped.
+pub(crate) fn count_doc_frequencies<Ix, W, Mb, T>(cdawg: &Cdawg<W, Ix, Mb, T>) -> Vec<usize>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+{
+    let dag = CountDag::new(cdawg);
+    let n_states = cdawg.node_count();
+    let mut doc_freqs = vec![0; n_states];
+    // The last sink that each state was reached from, so it is only counted once per sink.
+    let mut reached_from = vec![usize::MAX; n_states];
+    let mut stack = Vec::new();
+    for sink in 0..n_states {
+        let idx = NodeIndex::new(sink);
+        let n_docs = cdawg
+            .get_graph()
+            .edges(idx)
+            .filter(|edge| edge.get_target() == idx && !cdawg.is_deleted_loop(edge.get_weight()))
+            .count();
+        if n_docs == 0 {
+            continue;
+        }
+        reached_from[sink] = sink;
+        stack.push(sink);
+        while let Some(state) = stack.pop() {
+            doc_freqs[state] += n_docs;
+            for &parent in dag.get_parents(state) {
+                if reached_from[parent] != sink {
+                    reached_from[parent] = sink;
+                    stack.push(parent);
+                }
             }
         }
     }
+    doc_freqs
+}
+
+// The domains of the live documents, which label the self-loops of their sinks.
+fn get_domains<Ix, W, Mb, T>(cdawg: &Cdawg<W, Ix, Mb, T>) -> Vec<usize>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+{
+    let mut domains = Vec::new();
+    for state in 0..cdawg.node_count() {
+        let idx = NodeIndex::new(state);
+        for edge in cdawg.get_graph().edges(idx) {
+            if edge.get_target() == idx && !cdawg.is_deleted_loop(edge.get_weight()) {
+                domains.push(edge.get_weight().get_span().1);
+            }
+        }
+    }
+    domains
+}
+
+/// The count of each state in each domain of `layout`. Like the total counts, a sink counts each of
+/// its live documents once, in that document's domain, and other states sum their children.
+pub(crate) fn count_domains<Ix, W, Mb, T>(
+    cdawg: &Cdawg<W, Ix, Mb, T>,
+    layout: DomainLayout,
+) -> DomainCounts
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    T: CdawgToken,
+{
+    let dag = CountDag::new(cdawg);
+    let n_states = cdawg.node_count();
+    let row_len = layout.row_len();
+    let mut values = vec![0; n_states * row_len];
+    // Number of children of each state that haven't been counted yet.
+    let mut pending: Vec<usize> = (0..n_states)
+        .map(|state| dag.get_children(state).len())
+        .collect();
+    let mut ready: Vec<usize> = (0..n_states).filter(|state| pending[*state] == 0).collect();
+    while let Some(state) = ready.pop() {
+        let row_start = state * row_len;
+        let idx = NodeIndex::new(state);
+        for edge in cdawg.get_graph().edges(idx) {
+            if edge.get_target() == idx && !cdawg.is_deleted_loop(edge.get_weight()) {
+                for cell in layout.cells(edge.get_weight().get_span().1) {
+                    values[row_start + cell] += 1;
+                }
+            }
+        }
+        for &child in dag.get_children(state) {
+            for cell in 0..row_len {
+                let count = values[child * row_len + cell];
+                values[row_start + cell] += count;
+            }
+        }
+        for &parent in dag.get_parents(state) {
+            pending[parent] -= 1;
+            if pending[parent] == 0 {
+                ready.push(parent);
+            }
+        }
+    }
+    DomainCounts::new(layout, values)
+}
+
+// The edges of a CDAWG in RAM, as compressed adjacency lists in both directions. Self-loops are left
+// out. States that already have counts, which are sinks while building, are fixed.
+struct CountDag {
+    child_starts: Vec<usize>,
+    children: Vec<usize>,
+    parent_starts: Vec<usize>,
+    parents: Vec<usize>,
+    initial_counts: Vec<usize>,
+    fixed: Vec<bool>,
+    terminal: Vec<bool>,
+}
+
+impl CountDag {
+    fn new<Ix, W, Mb, T>(cdawg: &Cdawg<W, Ix, Mb, T>) -> Self
+    where
+        Ix: IndexType,
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        T: CdawgToken,
+    {
+        let n_states = cdawg.node_count();
+        let mut child_starts = Vec::with_capacity(n_states + 1);
+        let mut children = Vec::new();
+        let mut n_parents = vec![0; n_states];
+        let mut initial_counts = Vec::with_capacity(n_states);
+        let mut terminal = Vec::with_capacity(n_states);
+        for state in 0..n_states {
+            let idx = NodeIndex::new(state);
+            child_starts.push(children.len());
+            initial_counts.push(cdawg.get_count(idx));
+            let mut is_terminal = false;
+            for edge in cdawg.get_graph().edges(idx) {
+                let next_state = edge.get_target().index();
+                if next_state != state {
+                    children.push(next_state);
+                    n_parents[next_state] += 1;
+                    is_terminal |= cdawg.is_sentinel_edge(edge.get_weight());
+                }
+            }
+            terminal.push(is_terminal);
+        }
+        child_starts.push(children.len());
+
+        let mut parent_starts = Vec::with_capacity(n_states + 1);
+        parent_starts.push(0);
+        for n in n_parents.iter() {
+            parent_starts.push(parent_starts.last().unwrap() + n);
+        }
+        let mut next_parent = parent_starts.clone();
+        let mut parents = vec![0; children.len()];
+        for state in 0..n_states {
+            for &child in children[child_starts[state]..child_starts[state + 1]].iter() {
+                parents[next_parent[child]] = state;
+                next_parent[child] += 1;
+            }
+        }
+
+        let fixed = initial_counts.iter().map(|count| *count > 0).collect();
+        Self {
+            child_starts,
+            children,
+            parent_starts,
+            parents,
+            initial_counts,
+            fixed,
+            terminal,
+        }
+    }
+
+    fn get_children(&self, state: usize) -> &[usize] {
+        &self.children[self.child_starts[state]..self.child_starts[state + 1]]
+    }
+
+    fn get_parents(&self, state: usize) -> &[usize] {
+        &self.parents[self.parent_starts[state]..self.parent_starts[state + 1]]
+    }
+
+    fn count(&self, n_threads: usize) -> Vec<usize> {
+        let n_states = self.initial_counts.len();
+        let counts: Vec<AtomicUsize> = self
+            .initial_counts
+            .iter()
+            .map(|count| AtomicUsize::new(*count))
+            .collect();
+        // Number of children of each state that haven't been counted yet.
+        let pending: Vec<AtomicUsize> = (0..n_states)
+            .map(|state| AtomicUsize::new(self.get_children(state).len()))
+            .collect();
+        let ready: Vec<usize> = (0..n_states)
+            .filter(|state| self.fixed[*state] || self.get_children(*state).is_empty())
+            .collect();
+        let ready = Mutex::new(ready);
+        let n_done = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            for _ in 0..n_threads.max(1) {
+                scope.spawn(|| {
+                    while n_done.load(Ordering::Acquire) < n_states {
+                        let Some(state) = ready.lock().unwrap().pop() else {
+                            thread::yield_now();
+                            continue;
+                        };
+                        if !self.fixed[state] {
+                            let count = self
+                                .get_children(state)
+                                .iter()
+                                .map(|child| counts[*child].load(Ordering::Acquire))
+                                .sum();
+                            counts[state].store(count, Ordering::Release);
+                        }
+                        for &parent in self.get_parents(state).iter() {
+                            if pending[parent].fetch_sub(1, Ordering::AcqRel) == 1
+                                && !self.fixed[parent]
+                            {
+                                ready.lock().unwrap().push(parent);
+                            }
+                        }
+                        n_done.fetch_add(1, Ordering::Release);
+                    }
+                });
+            }
+        });
+        counts.into_iter().map(AtomicUsize::into_inner).collect()
+    }
 }
 
 #[cfg(test)]
@@ -158,4 +465,57 @@ mod tests {
         assert_eq!(cdawg.get_count(NodeIndex::new(4)), 1);
         assert_eq!(cdawg.get_count(NodeIndex::new(5)), 3);
     }
+
+    #[test]
+    fn test_terminal_multidoc() {
+        let (a, b) = (0, 1);
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![
+            a,
+            b,
+            u16::MAX,
+            b,
+            u16::MAX,
+            a,
+            u16::MAX,
+        ])));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+
+        // The empty string ends all three documents.
+        assert!(cdawg.is_terminal(cdawg.get_source()));
+        assert_eq!(cdawg.terminal_count(cdawg.get_source()), 3);
+
+        // "b" ends two documents, "a" ends one.
+        let cs = cdawg.transition_and_count(cdawg.get_initial(), b);
+        assert_eq!(cdawg.get_suffix_terminal_count(cs), 2);
+        let cs = cdawg.transition_and_count(cdawg.get_initial(), a);
+        assert_eq!(cdawg.get_suffix_terminal_count(cs), 1);
+    }
+
+    #[test]
+    fn test_counts_parallel() {
+        let (a, b, c) = (0, 1, 2);
+        let corpora = vec![
+            vec![a, b, c, a, b, c, a, b, a, u16::MAX],
+            vec![a, b, u16::MAX, b, u16::MAX, a, u16::MAX],
+        ];
+        for tokens in corpora {
+            let tokens = Rc::new(RefCell::new(tokens));
+            let mut expected: Cdawg = Cdawg::new(tokens.clone());
+            expected.build();
+            TopologicalCounter::new_ram().fill_counts(&mut expected);
+
+            for n_threads in [1, 4] {
+                let mut cdawg: Cdawg = Cdawg::new(tokens.clone());
+                cdawg.build();
+                TopologicalCounter::new_ram().fill_counts_parallel(&mut cdawg, n_threads);
+                for state in 0..cdawg.node_count() {
+                    let state = NodeIndex::new(state);
+                    assert_eq!(cdawg.get_count(state), expected.get_count(state));
+                    assert_eq!(cdawg.is_terminal(state), expected.is_terminal(state));
+                }
+            }
+        }
+    }
 }
@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
 use crate::cdawg::inenaga::Cdawg;
 use crate::cdawg::stack::Stack;
+use crate::cdawg::token_backing::CdawgToken;
 use crate::graph::indexing::{IndexType, NodeIndex};
 use crate::memory_backing::{DiskVec, MemoryBacking};
 use crate::weight::Weight;
@@ -45,11 +46,12 @@ where
 
 impl<Sb> TraverseArity<Sb> {
     /// DFS implementation of graph traversal.
-    pub fn traverse_arity<Ix, W, Mb>(&mut self, cdawg: &mut Cdawg<W, Ix, Mb>) -> Vec<usize>
+    pub fn traverse_arity<Ix, W, Mb, T>(&mut self, cdawg: &mut Cdawg<W, Ix, Mb, T>) -> Vec<usize>
     where
         Ix: IndexType + Serialize + for<'de> Deserialize<'de>,
         W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
         Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        T: CdawgToken,
         Sb: Stack<usize>,
     {
         let mut arities = Vec::with_capacity(self.visited.len());
@@ -0,0 +1,141 @@
+// Driver for the `batch-count` subcommand: a high-throughput offline engine for looking up the
+// counts of many n-grams in a built CDAWG.
+//
+// Queries are streamed from a file and partitioned across worker threads by their first token, so
+// each worker sees a slice of the vocabulary and revisits the same parts of the graph. Each worker
+// opens its own handle on the index (the underlying files are mmapped, so the pages are shared) and
+// writes its results to its own shard file.
+
+use anyhow::{bail, Result};
+use clap::Args;
+use kdam::{tqdm, BarExt};
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread;
+use std::time::Instant;
+
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+// A batch of (line number, n-gram) queries sent to a worker.
+type Batch = Vec<(usize, Vec<u16>)>;
+
+#[derive(Args, Debug)]
+pub struct BatchCountArgs {
+    /// Path to the disk CDAWG.
+    #[arg(long)]
+    index_path: String,
+
+    /// Path to the token DiskVec that the CDAWG was built on.
+    #[arg(long)]
+    index_tokens_path: String,
+
+    /// File with one query per line, given as space-separated token IDs.
+    #[arg(long)]
+    queries_path: String,
+
+    /// Directory to write `shard-<worker>.tsv` files with `<line number>\t<count>` rows.
+    #[arg(long)]
+    output_dir: String,
+
+    /// Number of worker threads. Defaults to the number of available cores.
+    #[arg(long)]
+    num_threads: Option<usize>,
+
+    /// Number of queries sent to a worker at a time.
+    #[arg(long, default_value_t = 4096)]
+    batch_size: usize,
+}
+
+pub fn run(args: &BatchCountArgs) -> Result<()> {
+    let num_threads = match args.num_threads {
+        Some(n) => n,
+        None => thread::available_parallelism()?.get(),
+    };
+    if num_threads == 0 || args.batch_size == 0 {
+        bail!("--num-threads and --batch-size must be positive");
+    }
+    fs::create_dir_all(&args.output_dir)?;
+    let start_time = Instant::now();
+
+    let n_queries = thread::scope(|scope| {
+        let mut senders = Vec::with_capacity(num_threads);
+        let mut workers = Vec::with_capacity(num_threads);
+        for worker in 0..num_threads {
+            // Bounded, so memory use doesn't grow if reading outpaces the workers.
+            let (sender, receiver) = sync_channel::<Batch>(4);
+            senders.push(sender);
+            workers.push(scope.spawn(move || count_worker(args, worker, receiver)));
+        }
+
+        let mut batches: Vec<Batch> = vec![Vec::new(); num_threads];
+        let mut pbar = tqdm!();
+        let mut n_queries = 0;
+        let reader = BufReader::new(File::open(&args.queries_path)?);
+        for (line_idx, line) in reader.lines().enumerate() {
+            let ngram = line?
+                .split_whitespace()
+                .map(|token| token.parse::<u16>())
+                .collect::<Result<Vec<_>, _>>()?;
+            let worker = ngram
+                .first()
+                .map_or(0, |token| *token as usize % num_threads);
+            batches[worker].push((line_idx, ngram));
+            if batches[worker].len() == args.batch_size {
+                let batch = std::mem::take(&mut batches[worker]);
+                // A send only fails if the worker exited early, and join() reports why.
+                if senders[worker].send(batch).is_err() {
+                    break;
+                }
+            }
+            n_queries += 1;
+            if n_queries % args.batch_size == 0 {
+                let _ = pbar.update(args.batch_size);
+            }
+        }
+        for (sender, batch) in senders.into_iter().zip(batches) {
+            let _ = sender.send(batch);
+        }
+
+        for worker in workers {
+            worker.join().unwrap()?;
+        }
+        Ok::<_, anyhow::Error>(n_queries)
+    })?;
+
+    let elapsed = start_time.elapsed().as_secs_f64();
+    println!();
+    println!("  # queries: {}", n_queries);
+    println!("  elapsed: {:.2}s", elapsed);
+    println!("  queries/s: {:.0}", n_queries as f64 / elapsed);
+    println!("Wrote {} shards to {}", num_threads, args.output_dir);
+    Ok(())
+}
+
+fn count_worker(args: &BatchCountArgs, worker: usize, receiver: Receiver<Batch>) -> Result<()> {
+    let tokens: DiskVec<u16> = DiskVec::load(&args.index_tokens_path)?;
+    let cdawg: Cdawg<DefaultWeight, DefaultIx, Mb> = Cdawg::load(
+        Rc::new(RefCell::new(tokens)),
+        args.index_path.clone(),
+        CacheConfig::none(),
+    )?;
+
+    let shard_path = Path::new(&args.output_dir).join(format!("shard-{:05}.tsv", worker));
+    let mut writer = BufWriter::new(File::create(shard_path)?);
+    for batch in receiver {
+        for (line_idx, ngram) in batch {
+            writeln!(writer, "{}\t{}", line_idx, cdawg.get_ngram_count(&ngram))?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+// Driver for the `contains` subcommand.
+
+use anyhow::Result;
+use clap::Args;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::containment::check_containment;
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+#[derive(Args, Debug)]
+pub struct ContainsArgs {
+    /// Path to the disk CDAWG for the (big) corpus A.
+    #[arg(long)]
+    index_path: String,
+
+    /// Path to the token DiskVec that the CDAWG for A was built on.
+    #[arg(long)]
+    index_tokens_path: String,
+
+    /// Path to the token DiskVec for the (small) corpus B.
+    #[arg(long)]
+    query_tokens_path: String,
+
+    /// Maximum number of missing substrings to report.
+    #[arg(long, default_value_t = 10)]
+    max_counterexamples: usize,
+
+    /// Print the report as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+pub fn run(args: &ContainsArgs) -> Result<()> {
+    let tokens: DiskVec<u16> = DiskVec::load(&args.index_tokens_path)?;
+    let tokens_rc = Rc::new(RefCell::new(tokens));
+    let cdawg: Cdawg<DefaultWeight, DefaultIx, Mb> =
+        Cdawg::load(tokens_rc, args.index_path.clone(), CacheConfig::none())?;
+    let query: DiskVec<u16> = DiskVec::load(&args.query_tokens_path)?;
+
+    let report = check_containment(&cdawg, &query, args.max_counterexamples);
+    if args.json {
+        println!("{}", serde_json::to_string(&report)?);
+        return Ok(());
+    }
+
+    println!("  # tokens checked: {}", report.n_tokens);
+    println!("  # missing: {}", report.n_missing);
+    if report.is_contained() {
+        println!("Every substring of B occurs in A!");
+    }
+    for (start, end) in report.counterexamples.iter() {
+        let span: Vec<u16> = (*start..*end)
+            .map(|idx| query.get(idx))
+            .collect::<Result<_>>()?;
+        println!("  missing [{}, {}): {:?}", start, end, span);
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+// Driver for the `convert` subcommand, which turns a built index into another kind of index over the
+// same tokens without rebuilding it. For now this is only `--from dawg --to cdawg`, which compresses
+// a DAWG into a disk CDAWG, given the token vector that the DAWG was built on (with end-of-document
+// tokens, as saved by `--train-vec-path`). See cdawg::from_dawg for which DAWGs can be converted.
+//
+// DAWGs are loaded as in `diff`. The build manifest of the DAWG, if any, is copied next to the
+// CDAWG, so that queries can decode with its tokenizer.
+
+use anyhow::{anyhow, bail, Result};
+use clap::{Args, ValueEnum};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::diff::DawgFile;
+use rusty_dawg::build_manifest::BuildManifest;
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::token_backing::{CdawgToken, TokenBacking};
+use rusty_dawg::cdawg::{Cdawg, TopologicalCounter};
+use rusty_dawg::graph::avl_graph::binary::BinaryLabel;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::io::save::Save;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::tokenize::token_type::TokenType;
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexKind {
+    /// A DAWG: a disk directory, or a file saved from a RAM DAWG.
+    Dawg,
+    /// A disk CDAWG directory.
+    Cdawg,
+}
+
+#[derive(Args, Debug)]
+pub struct ConvertArgs {
+    /// Kind of index at `index_path`.
+    #[arg(long, value_enum)]
+    from: IndexKind,
+
+    /// Kind of index to write to `output_path`.
+    #[arg(long, value_enum)]
+    to: IndexKind,
+
+    /// Index to convert.
+    #[arg(long)]
+    index_path: String,
+
+    /// Token DiskVec that the index was built on, with an end-of-document token after each
+    /// document.
+    #[arg(long)]
+    tokens_path: String,
+
+    /// Number of tokens in `tokens_path` that the index was built on. Defaults to the tokens up to
+    /// the last end-of-document token, since token vectors are allocated for `--n-tokens` up front.
+    #[arg(long)]
+    n_tokens: Option<usize>,
+
+    /// Directory to write the converted index to.
+    #[arg(long)]
+    output_path: String,
+
+    /// Reference `tokens_path` from the CDAWG metadata, so it can be loaded without passing it.
+    #[arg(long)]
+    share_tokens: bool,
+
+    /// Token type of the index and tokens: `u16` or `u32`.
+    #[arg(long, default_value = "u16")]
+    utype: TokenType,
+}
+
+pub fn run(args: &ConvertArgs) -> Result<()> {
+    match (args.from, args.to, args.utype) {
+        (IndexKind::Dawg, IndexKind::Cdawg, TokenType::U16) => dawg_to_cdawg::<u16>(args),
+        (IndexKind::Dawg, IndexKind::Cdawg, TokenType::U32) => dawg_to_cdawg::<u32>(args),
+        (IndexKind::Dawg, IndexKind::Cdawg, utype) => {
+            bail!("CDAWGs can't be built on {} tokens", utype)
+        }
+        _ => bail!("Only --from dawg --to cdawg is supported"),
+    }
+}
+
+fn dawg_to_cdawg<T>(args: &ConvertArgs) -> Result<()>
+where
+    T: CdawgToken + BinaryLabel,
+{
+    let tokens: DiskVec<T> = DiskVec::load(&args.tokens_path)?;
+    let n_tokens = match args.n_tokens {
+        Some(n_tokens) => n_tokens,
+        None => get_written_len(&tokens),
+    };
+    println!("# tokens: {}", n_tokens);
+    let tokens: Rc<RefCell<dyn TokenBacking<T>>> = Rc::new(RefCell::new(tokens));
+    let mb: Mb = DiskBacking::new(&args.output_path);
+    let cache_config = CacheConfig::none();
+    println!("Converting DAWG...");
+    let mut cdawg: Cdawg<DefaultWeight, DefaultIx, Mb, T> =
+        match DawgFile::<T>::load(&args.index_path)? {
+            DawgFile::Ram(dawg) => Cdawg::from_dawg(&dawg, tokens, n_tokens, mb, cache_config)?,
+            DawgFile::Disk(dawg) => Cdawg::from_dawg(&dawg, tokens, n_tokens, mb, cache_config)?,
+        };
+    println!("Filling counts...");
+    TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+    if args.share_tokens {
+        cdawg.share_tokens(&args.tokens_path);
+    }
+
+    cdawg
+        .save(&args.output_path)
+        .map_err(|err| anyhow!("{}", err))?;
+    if let Some(manifest) = BuildManifest::load_next_to(&args.index_path)? {
+        manifest.save_next_to(&args.output_path)?;
+    }
+    println!(
+        "Wrote a CDAWG with {} nodes and {} edges to {}",
+        cdawg.node_count(),
+        cdawg.edge_count(),
+        args.output_path
+    );
+    Ok(())
+}
+
+// The length of `tokens` up to its last end-of-document token, or all of it if there is none.
+fn get_written_len<T: CdawgToken>(tokens: &DiskVec<T>) -> usize {
+    (0..tokens.len())
+        .rev()
+        .find(|&idx| TokenBacking::get(tokens, idx) == T::end())
+        .map_or(tokens.len(), |idx| idx + 1)
+}
@@ -0,0 +1,114 @@
+// Driver for the `convert-utype` subcommand, which rewrites a token vector or the edge labels of a
+// disk DAWG to a narrower or wider token type. Conversion is chunked and can be resumed by running
+// the same command again after an interruption.
+
+use anyhow::{bail, Result};
+use clap::{Args, ValueEnum};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use rusty_dawg::graph::avl_graph::edge::Edge;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::memory_backing::{convert_disk_vec, DiskBacking};
+use rusty_dawg::tokenize::token_type::TokenType;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ConvertKind {
+    /// A token DiskVec, e.g. the `train_vec_path` of a CDAWG.
+    Tokens,
+    /// A disk DAWG directory. Node weights are copied and edge labels are converted.
+    Dawg,
+}
+
+#[derive(Args, Debug)]
+pub struct ConvertUtypeArgs {
+    /// What `input_path` contains.
+    #[arg(long, value_enum)]
+    kind: ConvertKind,
+
+    /// Token DiskVec or disk DAWG directory to convert.
+    #[arg(long)]
+    input_path: String,
+
+    /// Where to write the converted copy.
+    #[arg(long)]
+    output_path: String,
+
+    /// Token type of the input: `u16`, `u32`, or `usize`.
+    #[arg(long)]
+    from: TokenType,
+
+    /// Token type to convert to: `u16`, `u32`, or `usize`.
+    #[arg(long)]
+    to: TokenType,
+
+    /// Number of items to convert between progress checkpoints.
+    #[arg(long, default_value_t = 1 << 20)]
+    chunk_size: usize,
+}
+
+pub fn run(args: &ConvertUtypeArgs) -> Result<()> {
+    if args.from == args.to {
+        bail!("Input is already {}", args.to);
+    }
+    match (args.from, args.to) {
+        (TokenType::U16, TokenType::U32) => convert::<u16, u32>(args),
+        (TokenType::U16, TokenType::Usize) => convert::<u16, usize>(args),
+        (TokenType::U32, TokenType::U16) => convert::<u32, u16>(args),
+        (TokenType::U32, TokenType::Usize) => convert::<u32, usize>(args),
+        (TokenType::Usize, TokenType::U16) => convert::<usize, u16>(args),
+        (TokenType::Usize, TokenType::U32) => convert::<usize, u32>(args),
+        _ => unreachable!(),
+    }
+}
+
+fn convert<A, B>(args: &ConvertUtypeArgs) -> Result<()>
+where
+    A: Copy + Serialize + DeserializeOwned + Default,
+    B: Copy + Serialize + TryFrom<u64>,
+    u64: TryFrom<A>,
+{
+    let convert_token = |idx: usize, token: A| -> Result<B> {
+        let Ok(token) = u64::try_from(token) else {
+            bail!("Token at index {} does not fit in 64 bits", idx);
+        };
+        match args.from.convert_token(token, args.to) {
+            Ok(token) => Ok(B::try_from(token).ok().unwrap()),
+            Err(err) => bail!("Can't convert item {}: {}", idx, err),
+        }
+    };
+
+    let input = Path::new(&args.input_path);
+    let output = Path::new(&args.output_path);
+    let n_items = match args.kind {
+        ConvertKind::Tokens => convert_disk_vec(input, output, args.chunk_size, convert_token)?,
+        ConvertKind::Dawg => {
+            let input_mb: DiskBacking<(), A, DefaultIx> = DiskBacking::new(input);
+            let output_mb: DiskBacking<(), B, DefaultIx> = DiskBacking::new(output);
+            // Node weights don't depend on the token type.
+            fs::copy(input_mb.get_nodes_path(), output_mb.get_nodes_path())?;
+            convert_disk_vec(
+                &input_mb.get_edges_path(),
+                &output_mb.get_edges_path(),
+                args.chunk_size,
+                |idx, edge: Edge<A, DefaultIx>| {
+                    Ok(Edge {
+                        weight: convert_token(idx, edge.weight)?,
+                        target: edge.target,
+                        left: edge.left,
+                        right: edge.right,
+                        balance_factor: edge.balance_factor,
+                    })
+                },
+            )?
+        }
+    };
+    println!(
+        "Converted {} items from {} to {} at {}",
+        n_items, args.from, args.to, args.output_path
+    );
+    Ok(())
+}
@@ -0,0 +1,156 @@
+// Driver for the `count` subcommand: look up one raw string query in a built CDAWG, for quick
+// sanity checks from the command line.
+//
+// The query is tokenized with the tokenizer recorded in the index manifest, unless --tokenizer is
+// passed. Alternatively, token IDs from another tokenizer can be passed with --query-ids, and are
+// remapped to the index's vocabulary by retokenizing or with an alignment file.
+
+use anyhow::{bail, Result};
+use clap::Args;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rusty_dawg::build_manifest::BuildManifest;
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::error::RustyDawgError;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::tokenize::{PretrainedTokenizer, TokenRemapper};
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+#[derive(Args, Debug)]
+pub struct CountArgs {
+    /// Path to the disk CDAWG.
+    #[arg(long)]
+    index: String,
+
+    /// Path to the token DiskVec that the CDAWG was built on. Not needed if the CDAWG references
+    /// shared tokens.
+    #[arg(long)]
+    index_tokens_path: Option<String>,
+
+    /// Text to look up.
+    #[arg(long)]
+    query: Option<String>,
+
+    /// Token IDs to look up instead of text, separated by whitespace, from `--source-tokenizer` or
+    /// the tokens of `--alignment`. They are remapped to the index's vocabulary.
+    #[arg(long)]
+    query_ids: Option<String>,
+
+    /// Pretrained tokenizer that `--query-ids` come from. They are decoded and retokenized with the
+    /// index's tokenizer.
+    #[arg(long)]
+    source_tokenizer: Option<String>,
+
+    /// JSON file mapping each token ID of `--query-ids` to a list of the index's token IDs.
+    #[arg(long)]
+    alignment: Option<String>,
+
+    /// Pretrained tokenizer to use instead of the one in the index manifest.
+    #[arg(long)]
+    tokenizer: Option<String>,
+
+    /// Print the result as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct CountReport {
+    tokens: Vec<u16>,
+    count: usize,     // Occurrences of the whole query.
+    max_length: u64,  // Length of the longest substring of the query that occurs.
+    max_count: usize, // Occurrences of that substring.
+}
+
+pub fn run(args: &CountArgs) -> Result<()> {
+    let tokens = tokenize(args)?;
+    let cdawg: Cdawg<DefaultWeight, DefaultIx, Mb> = match args.index_tokens_path {
+        Some(ref path) => {
+            let index_tokens: DiskVec<u16> = DiskVec::load(path)?;
+            Cdawg::load(
+                Rc::new(RefCell::new(index_tokens)),
+                args.index.clone(),
+                CacheConfig::none(),
+            )?
+        }
+        None => Cdawg::load_shared(args.index.clone(), CacheConfig::none())?,
+    };
+
+    let (mut max_length, mut max_count) = (0, 0);
+    let mut cs = cdawg.get_initial();
+    for token in tokens.iter() {
+        cs = cdawg.transition_and_count(cs, *token);
+        if cs.length > max_length {
+            max_length = cs.length;
+            max_count = cdawg.get_suffix_count(cs);
+        }
+    }
+    let report = CountReport {
+        count: cdawg.get_ngram_count(&tokens),
+        tokens,
+        max_length,
+        max_count,
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string(&report)?);
+        return Ok(());
+    }
+    println!("  tokens: {:?}", report.tokens);
+    println!("  count: {}", report.count);
+    println!(
+        "  max matched length: {} / {} (count {})",
+        report.max_length,
+        report.tokens.len(),
+        report.max_count
+    );
+    Ok(())
+}
+
+fn tokenize(args: &CountArgs) -> Result<Vec<u16>> {
+    let query = match (&args.query, &args.query_ids) {
+        (Some(query), None) => query,
+        (None, Some(query_ids)) => return remap(args, query_ids),
+        _ => bail!("Pass exactly one of --query and --query-ids"),
+    };
+    let name = match args.tokenizer {
+        Some(ref name) => name.clone(),
+        None => match BuildManifest::load_next_to(&args.index)? {
+            Some(manifest) => manifest.tokenizer,
+            None => bail!(
+                "{} has no manifest recording its tokenizer; pass --tokenizer",
+                args.index
+            ),
+        },
+    };
+    let tokenizer = PretrainedTokenizer::try_new(&name)?;
+    let (ids, _) = tokenizer.encode_with_offsets(query)?;
+    ids.into_iter()
+        .map(|id| match u16::try_from(id) {
+            Ok(id) if id != u16::MAX => Ok(id),
+            _ => bail!("Token ID {} exceeds u16", id),
+        })
+        .collect()
+}
+
+fn remap(args: &CountArgs, query_ids: &str) -> Result<Vec<u16>> {
+    let remapper = match (&args.source_tokenizer, &args.alignment) {
+        (Some(source), None) => TokenRemapper::for_index(source, &args.index)?,
+        (None, Some(path)) => TokenRemapper::load_alignment(path)?,
+        _ => bail!("--query-ids needs exactly one of --source-tokenizer and --alignment"),
+    };
+    let ids = query_ids
+        .split_whitespace()
+        .map(|id| {
+            id.parse()
+                .map_err(|_| RustyDawgError::InvalidToken(id.to_string()).into())
+        })
+        .collect::<Result<Vec<u32>>>()?;
+    remapper.remap(&ids)
+}
@@ -0,0 +1,155 @@
+// Driver for the `diff` subcommand, which compares two built (C)DAWGs structurally and reports
+// where they first differ, e.g. to check that a RAM build and a disk build of the same corpus agree.
+//
+// CDAWGs are recognized by their metadata, and compared from their sources. Anything else is loaded
+// as a DAWG: a directory as a disk DAWG, and a file as a DAWG saved from RAM.
+
+use anyhow::{anyhow, bail, Result};
+use clap::Args;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::path::Path;
+
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::metadata::CdawgMetadata;
+use rusty_dawg::dawg::Dawg;
+use rusty_dawg::graph::avl_graph::binary::BinaryLabel;
+use rusty_dawg::graph::avl_graph::AvlGraph;
+use rusty_dawg::graph::indexing::{DefaultIx, IndexType, NodeIndex};
+use rusty_dawg::io::load::Load;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, MemoryBacking};
+use rusty_dawg::tokenize::token_type::TokenType;
+use rusty_dawg::weight::{DefaultWeight, Weight, Weight64};
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// First (C)DAWG: a disk directory, or a file saved from a RAM DAWG.
+    a: String,
+
+    /// Second (C)DAWG, in either format.
+    b: String,
+
+    /// Token type of DAWG edges: `u16`, `u32`, or `usize`. Ignored for CDAWGs.
+    #[arg(long, default_value = "u16")]
+    utype: TokenType,
+}
+
+pub fn run(args: &DiffArgs) -> Result<()> {
+    match (load_metadata(&args.a)?, load_metadata(&args.b)?) {
+        (Some(a), Some(b)) => {
+            if a.index_width != b.index_width {
+                bail!("The CDAWGs were built with different index widths");
+            }
+            match a.index_width {
+                Some(64) => diff_cdawgs::<Weight64, u64>(args, &a, &b),
+                _ => diff_cdawgs::<DefaultWeight, DefaultIx>(args, &a, &b),
+            }
+        }
+        (None, None) => match args.utype {
+            TokenType::U16 => diff_dawgs::<u16>(args),
+            TokenType::U32 => diff_dawgs::<u32>(args),
+            TokenType::Usize => diff_dawgs::<usize>(args),
+        },
+        _ => bail!("Can't compare a CDAWG with a DAWG"),
+    }
+}
+
+pub(super) fn load_metadata(path: &str) -> Result<Option<CdawgMetadata>> {
+    let metadata_path = Path::new(path).join("metadata.json");
+    if !metadata_path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(CdawgMetadata::load_json(metadata_path)?))
+}
+
+fn diff_cdawgs<W, Ix>(args: &DiffArgs, a: &CdawgMetadata, b: &CdawgMetadata) -> Result<()>
+where
+    W: Weight + Copy + Serialize + DeserializeOwned + Default,
+    Ix: IndexType + Serialize + DeserializeOwned,
+{
+    type Graph<W, Ix> =
+        AvlGraph<W, CdawgEdgeWeight<Ix>, Ix, DiskBacking<W, CdawgEdgeWeight<Ix>, Ix>>;
+    let graph_a: Graph<W, Ix> = AvlGraph::load(&args.a, CacheConfig::none())?;
+    let graph_b: Graph<W, Ix> = AvlGraph::load(&args.b, CacheConfig::none())?;
+    report(
+        (&graph_a, NodeIndex::new(a.source)),
+        (&graph_b, NodeIndex::new(b.source)),
+    )
+}
+
+fn diff_dawgs<E>(args: &DiffArgs) -> Result<()>
+where
+    E: Eq + Ord + Copy + Debug + Serialize + DeserializeOwned + Default + BinaryLabel,
+{
+    let dawg_a = DawgFile::<E>::load(&args.a)?;
+    let dawg_b = DawgFile::<E>::load(&args.b)?;
+    match (&dawg_a, &dawg_b) {
+        (DawgFile::Ram(a), DawgFile::Ram(b)) => report(root(a), root(b)),
+        (DawgFile::Ram(a), DawgFile::Disk(b)) => report(root(a), root(b)),
+        (DawgFile::Disk(a), DawgFile::Ram(b)) => report(root(a), root(b)),
+        (DawgFile::Disk(a), DawgFile::Disk(b)) => report(root(a), root(b)),
+    }
+}
+
+// A DAWG loaded from either format. Their backings differ, so they have different types.
+pub(super) enum DawgFile<E>
+where
+    E: Eq + Copy + Debug + Serialize + DeserializeOwned + Default,
+{
+    Ram(Dawg<E, DefaultWeight>),
+    Disk(Dawg<E, DefaultWeight, DefaultIx, DiskBacking<DefaultWeight, E, DefaultIx>>),
+}
+
+impl<E> DawgFile<E>
+where
+    E: Eq + Ord + Copy + Debug + Serialize + DeserializeOwned + Default + BinaryLabel,
+{
+    pub(super) fn load(path: &str) -> Result<Self> {
+        let cache_config = CacheConfig::none();
+        let dawg = if Path::new(path).is_dir() {
+            DawgFile::Disk(Load::load(path, cache_config).map_err(|err| anyhow!("{}", err))?)
+        } else {
+            DawgFile::Ram(Load::load(path, cache_config).map_err(|err| anyhow!("{}", err))?)
+        };
+        Ok(dawg)
+    }
+}
+
+// The graph of a DAWG and its initial state.
+#[allow(clippy::type_complexity)]
+fn root<E, Mb>(
+    dawg: &Dawg<E, DefaultWeight, DefaultIx, Mb>,
+) -> (
+    &AvlGraph<DefaultWeight, E, DefaultIx, Mb>,
+    NodeIndex<DefaultIx>,
+)
+where
+    E: Eq + Ord + Copy + Debug + Serialize + DeserializeOwned,
+    Mb: MemoryBacking<DefaultWeight, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    (dawg.get_graph(), dawg.get_initial())
+}
+
+fn report<N, E, Ix, Mb1, Mb2>(
+    (a, root_a): (&AvlGraph<N, E, Ix, Mb1>, NodeIndex<Ix>),
+    (b, root_b): (&AvlGraph<N, E, Ix, Mb2>, NodeIndex<Ix>),
+) -> Result<()>
+where
+    N: Weight,
+    E: Copy + Ord + Debug,
+    Ix: IndexType,
+    Mb1: MemoryBacking<N, E, Ix>,
+    Mb1::EdgeRef: Copy,
+    Mb2: MemoryBacking<N, E, Ix>,
+    Mb2::EdgeRef: Copy,
+{
+    match a.first_divergence(root_a, b, root_b) {
+        Some(divergence) => bail!("First divergence: {}", divergence),
+        None => {
+            println!("No differences found");
+            Ok(())
+        }
+    }
+}
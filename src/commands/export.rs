@@ -0,0 +1,216 @@
+// Driver for the `export` subcommand, which writes the states and edges of a built (C)DAWG as two
+// tables, nodes and edges, for analysis in pandas or other dataframe libraries.
+//
+// Nodes have columns id, length, count and failure, which is null for the source. DAWG edges have
+// columns src, dst and token. CDAWG edges have src, dst, and the 0-indexed span [start, end) of
+// their label in the token vector. (C)DAWGs are recognized as in `diff`.
+
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::diff::{load_metadata, DawgFile};
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::graph::avl_graph::binary::BinaryLabel;
+use rusty_dawg::graph::avl_graph::AvlGraph;
+use rusty_dawg::graph::indexing::{DefaultIx, EdgeIndex, IndexType, NodeIndex};
+use rusty_dawg::graph::{EdgeRef, NodeRef};
+use rusty_dawg::io::arrow::ArrowWriter;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, MemoryBacking};
+use rusty_dawg::tokenize::token_type::TokenType;
+use rusty_dawg::weight::{DefaultWeight, Weight, Weight64};
+
+const NODE_FIELDS: [(&str, bool); 4] = [
+    ("id", false),
+    ("length", false),
+    ("count", false),
+    ("failure", true),
+];
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    Arrow,
+    Csv,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// (C)DAWG to export: a disk directory, or a file saved from a RAM DAWG.
+    #[arg(long)]
+    index_path: String,
+
+    /// Directory to write nodes.arrow and edges.arrow (or .csv) to. Created if missing.
+    #[arg(long)]
+    output_dir: String,
+
+    #[arg(long, value_enum, default_value = "arrow")]
+    format: ExportFormat,
+
+    /// Token type of DAWG edges: `u16`, `u32`, or `usize`. Ignored for CDAWGs.
+    #[arg(long, default_value = "u16")]
+    utype: TokenType,
+}
+
+pub fn run(args: &ExportArgs) -> Result<()> {
+    fs::create_dir_all(&args.output_dir)?;
+    match load_metadata(&args.index_path)? {
+        Some(metadata) => match metadata.index_width {
+            Some(64) => export_cdawg::<Weight64, u64>(args, metadata.end_position),
+            _ => export_cdawg::<DefaultWeight, DefaultIx>(args, metadata.end_position),
+        },
+        None => match args.utype {
+            TokenType::U16 => export_dawg::<u16>(args),
+            TokenType::U32 => export_dawg::<u32>(args),
+            TokenType::Usize => export_dawg::<usize>(args),
+        },
+    }
+}
+
+fn export_cdawg<W, Ix>(args: &ExportArgs, end_position: usize) -> Result<()>
+where
+    W: Weight + Copy + Serialize + DeserializeOwned + Default,
+    Ix: IndexType + Serialize + DeserializeOwned,
+{
+    type Graph<W, Ix> =
+        AvlGraph<W, CdawgEdgeWeight<Ix>, Ix, DiskBacking<W, CdawgEdgeWeight<Ix>, Ix>>;
+    let graph: Graph<W, Ix> = AvlGraph::load(&args.index_path, CacheConfig::none())?;
+    // Resolve open ends like `Cdawg::get_span`, from the self-loop at a finished document's sink.
+    let get_span = |weight: CdawgEdgeWeight<Ix>, target: NodeIndex<Ix>| {
+        let (start, end) = weight.get_span();
+        if end < Ix::max_value().index() {
+            return vec![Some(start as u64), Some(end as u64)];
+        }
+        let edge_idx = graph.get_node(target).get_first_edge();
+        let end = if edge_idx == EdgeIndex::end() {
+            end_position
+        } else {
+            graph.get_edge(edge_idx).get_weight().get_span().0 + 1
+        };
+        vec![Some(start as u64), Some(end as u64)]
+    };
+    export_graph(args, &graph, &["start", "end"], get_span)
+}
+
+fn export_dawg<E>(args: &ExportArgs) -> Result<()>
+where
+    E: Eq
+        + Ord
+        + Copy
+        + Debug
+        + Serialize
+        + DeserializeOwned
+        + Default
+        + BinaryLabel
+        + TryInto<u64>,
+{
+    let get_token = |token: E, _| vec![token.try_into().ok()];
+    match DawgFile::<E>::load(&args.index_path)? {
+        DawgFile::Ram(dawg) => export_graph(args, dawg.get_graph(), &["token"], get_token),
+        DawgFile::Disk(dawg) => export_graph(args, dawg.get_graph(), &["token"], get_token),
+    }
+}
+
+// Write the nodes table, then the edges table with `label_fields` computed by `get_label`.
+fn export_graph<N, E, Ix, Mb, F>(
+    args: &ExportArgs,
+    graph: &AvlGraph<N, E, Ix, Mb>,
+    label_fields: &[&str],
+    get_label: F,
+) -> Result<()>
+where
+    N: Weight + Clone,
+    E: Copy + Debug,
+    Ix: IndexType,
+    Mb: MemoryBacking<N, E, Ix>,
+    F: Fn(E, NodeIndex<Ix>) -> Vec<Option<u64>>,
+{
+    let mut nodes = TableWriter::create(args, "nodes", &NODE_FIELDS)?;
+    for idx in 0..graph.node_count() {
+        let weight = graph.get_node(NodeIndex::new(idx)).get_weight();
+        nodes.push(&[
+            Some(idx as u64),
+            Some(weight.get_length()),
+            Some(weight.get_count() as u64),
+            weight.get_failure().map(|q| q.index() as u64),
+        ])?;
+    }
+    let n_nodes = nodes.finish()?;
+
+    let mut edge_fields = vec![("src", false), ("dst", false)];
+    edge_fields.extend(label_fields.iter().map(|name| (*name, false)));
+    let mut edges = TableWriter::create(args, "edges", &edge_fields)?;
+    for idx in 0..graph.node_count() {
+        for edge in graph.edges(NodeIndex::new(idx)) {
+            let target = edge.get_target();
+            let mut row = vec![Some(idx as u64), Some(target.index() as u64)];
+            row.extend(get_label(edge.get_weight(), target));
+            edges.push(&row)?;
+        }
+    }
+    let n_edges = edges.finish()?;
+    println!(
+        "Wrote {} nodes and {} edges to {}",
+        n_nodes, n_edges, args.output_dir
+    );
+    Ok(())
+}
+
+enum TableWriter {
+    Arrow(ArrowWriter),
+    Csv(BufWriter<File>, usize),
+}
+
+impl TableWriter {
+    fn create(args: &ExportArgs, name: &str, fields: &[(&str, bool)]) -> Result<Self> {
+        let dir = Path::new(&args.output_dir);
+        Ok(match args.format {
+            ExportFormat::Arrow => TableWriter::Arrow(ArrowWriter::create(
+                dir.join(name).with_extension("arrow"),
+                fields,
+            )?),
+            ExportFormat::Csv => {
+                let mut writer =
+                    BufWriter::new(File::create(dir.join(name).with_extension("csv"))?);
+                let names: Vec<_> = fields.iter().map(|(name, _)| *name).collect();
+                writeln!(writer, "{}", names.join(","))?;
+                TableWriter::Csv(writer, 0)
+            }
+        })
+    }
+
+    // Nulls are left empty in CSVs.
+    fn push(&mut self, row: &[Option<u64>]) -> Result<()> {
+        match self {
+            TableWriter::Arrow(writer) => writer.push(row),
+            TableWriter::Csv(writer, len) => {
+                *len += 1;
+                let values: Vec<_> = row
+                    .iter()
+                    .map(|value| value.map_or(String::new(), |value| value.to_string()))
+                    .collect();
+                writeln!(writer, "{}", values.join(","))?;
+                Ok(())
+            }
+        }
+    }
+
+    // Returns the number of rows written.
+    fn finish(self) -> Result<usize> {
+        match self {
+            TableWriter::Arrow(writer) => {
+                let len = writer.len();
+                writer.finish()?;
+                Ok(len)
+            }
+            TableWriter::Csv(mut writer, len) => {
+                writer.flush()?;
+                Ok(len)
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+// Driver for the `export-matches` subcommand: the matched length and count at every position of a
+// query corpus B, against the corpus A indexed by a CDAWG, written as .npy arrays for downstream ML
+// tooling (e.g. memorization detection).
+//
+// The output directory gets three arrays of u64s. `lengths.npy` and `counts.npy` are aligned with
+// the tokens of B, skipping document separators, and document i spans
+// `offsets[i]..offsets[i + 1]` of them.
+
+use anyhow::Result;
+use clap::Args;
+use kdam::{tqdm, BarExt};
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::overlap::{document_spans, match_document};
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::io::npy::NpyWriter;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+#[derive(Args, Debug)]
+pub struct ExportMatchesArgs {
+    /// Path to the disk CDAWG for corpus A.
+    #[arg(long)]
+    index_path: String,
+
+    /// Path to the token DiskVec that the CDAWG for A was built on.
+    #[arg(long)]
+    index_tokens_path: String,
+
+    /// Path to the token DiskVec for corpus B, with documents separated by u16::MAX.
+    #[arg(long)]
+    query_tokens_path: String,
+
+    /// Directory to write `lengths.npy`, `counts.npy` and `offsets.npy` to.
+    #[arg(long)]
+    output_dir: String,
+}
+
+pub fn run(args: &ExportMatchesArgs) -> Result<()> {
+    let tokens: DiskVec<u16> = DiskVec::load(&args.index_tokens_path)?;
+    let cdawg: Cdawg<DefaultWeight, DefaultIx, Mb> = Cdawg::load(
+        Rc::new(RefCell::new(tokens)),
+        args.index_path.clone(),
+        CacheConfig::none(),
+    )?;
+    let query: DiskVec<u16> = DiskVec::load(&args.query_tokens_path)?;
+    let spans = document_spans(&query);
+    println!("# documents: {}", spans.len());
+
+    let output_dir = Path::new(&args.output_dir);
+    fs::create_dir_all(output_dir)?;
+    let mut lengths = NpyWriter::create(output_dir.join("lengths.npy"))?;
+    let mut counts = NpyWriter::create(output_dir.join("counts.npy"))?;
+    let mut offsets = NpyWriter::create(output_dir.join("offsets.npy"))?;
+    offsets.push(0)?;
+    let mut pbar = tqdm!(total = spans.len());
+    for span in spans {
+        let (doc_lengths, doc_counts) = match_document(&cdawg, &query, span);
+        lengths.extend(doc_lengths)?;
+        counts.extend(doc_counts.into_iter().map(|count| count as u64))?;
+        offsets.push(lengths.len() as u64)?;
+        let _ = pbar.update(1);
+    }
+    println!();
+    println!("Wrote {} positions to {}", lengths.len(), args.output_dir);
+    lengths.finish()?;
+    counts.finish()?;
+    offsets.finish()
+}
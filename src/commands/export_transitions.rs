@@ -0,0 +1,74 @@
+// Driver for the `export-transitions` subcommand, which writes the transitions of the most frequent
+// states of a built DAWG as a sparse CSR matrix, for training neural approximations of the
+// automaton. See dawg::transitions for the arrays written. DAWGs are loaded as in `diff`.
+
+use anyhow::Result;
+use clap::Args;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+
+use super::diff::DawgFile;
+use rusty_dawg::graph::avl_graph::binary::BinaryLabel;
+use rusty_dawg::tokenize::end::End;
+use rusty_dawg::tokenize::token_type::TokenType;
+
+#[derive(Args, Debug)]
+pub struct ExportTransitionsArgs {
+    /// DAWG to export: a disk directory, or a file saved from a RAM DAWG.
+    #[arg(long)]
+    index_path: String,
+
+    /// Directory to write the .npy arrays to. Created if missing.
+    #[arg(long)]
+    output_dir: String,
+
+    /// Number of states to export, taking those with the highest counts.
+    #[arg(long, default_value_t = 100000)]
+    max_states: usize,
+
+    /// Number of columns. Defaults to one more than the largest token exported.
+    #[arg(long)]
+    vocab_size: Option<usize>,
+
+    /// Token type of DAWG edges: `u16`, `u32`, or `usize`.
+    #[arg(long, default_value = "u16")]
+    utype: TokenType,
+}
+
+pub fn run(args: &ExportTransitionsArgs) -> Result<()> {
+    match args.utype {
+        TokenType::U16 => export::<u16>(args),
+        TokenType::U32 => export::<u32>(args),
+        TokenType::Usize => export::<usize>(args),
+    }
+}
+
+fn export<E>(args: &ExportTransitionsArgs) -> Result<()>
+where
+    E: Eq
+        + Ord
+        + Copy
+        + Debug
+        + Serialize
+        + DeserializeOwned
+        + Default
+        + BinaryLabel
+        + End
+        + TryInto<u64>,
+{
+    let matrix = match DawgFile::<E>::load(&args.index_path)? {
+        DawgFile::Ram(dawg) => dawg.transition_matrix(args.max_states, args.vocab_size),
+        DawgFile::Disk(dawg) => dawg.transition_matrix(args.max_states, args.vocab_size),
+    };
+    matrix.save(&args.output_dir)?;
+    let (n_rows, n_cols) = matrix.shape();
+    println!(
+        "Wrote a {} x {} matrix with {} transitions to {}",
+        n_rows,
+        n_cols,
+        matrix.indices.len(),
+        args.output_dir
+    );
+    Ok(())
+}
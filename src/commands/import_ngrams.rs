@@ -0,0 +1,52 @@
+// Driver for the `import-ngrams` subcommand.
+
+use anyhow::Result;
+use clap::Args;
+
+use rusty_dawg::dawg::import::{read_ngram_counts, NgramFormat};
+use rusty_dawg::dawg::Dawg;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::io::Save;
+use rusty_dawg::memory_backing::RamBacking;
+use rusty_dawg::tokenize::{load_tokenizer, Tokenize};
+use rusty_dawg::weight::DefaultWeight;
+
+#[derive(Args, Debug)]
+pub struct ImportNgramsArgs {
+    /// Path to the n-gram count table.
+    #[arg(long)]
+    ngrams_path: String,
+
+    /// Format of the table: `tsv` (`<n-gram>\t<count>`) or `arpa`.
+    #[arg(long, default_value = "tsv")]
+    format: NgramFormat,
+
+    /// Where to save the resulting DAWG.
+    #[arg(long)]
+    save_path: String,
+
+    /// Skip n-grams with more than this many tokens.
+    #[arg(long)]
+    max_n: Option<usize>,
+
+    /// Tokenizer to use. This can be `whitespace`, `null`, `bytes`, `chars`, or any huggingface
+    /// tokenizer.
+    #[arg(long, default_value = "whitespace")]
+    tokenizer: String,
+}
+
+pub fn run(args: &ImportNgramsArgs) -> Result<()> {
+    let mut index: Box<dyn Tokenize<u16>> = load_tokenizer(&args.tokenizer)?;
+
+    let counts = read_ngram_counts(&args.ngrams_path, args.format, index.as_mut(), args.max_n)?;
+    println!("# n-grams: {}", counts.len());
+    let mb: RamBacking<DefaultWeight, u16, DefaultIx> = RamBacking::default();
+    let dawg: Dawg<u16, DefaultWeight> = Dawg::from_ngram_counts(mb, &counts);
+    println!("  # states: {}", dawg.node_count());
+    println!("  # edges: {}", dawg.edge_count());
+
+    dawg.save(&args.save_path)
+        .map_err(|err| anyhow::anyhow!("{}", err))?;
+    println!("Successfully saved DAWG to {}!", &args.save_path);
+    Ok(())
+}
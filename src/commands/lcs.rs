@@ -0,0 +1,81 @@
+// Driver for the `lcs` subcommand: the longest common substring between a query corpus B and the
+// corpus A indexed by a CDAWG, and optionally the matched length at every position of B as a .npy
+// array aligned with its tokens.
+
+use anyhow::Result;
+use clap::Args;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::lcs::{longest_common_substring, overlap_profile};
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::io::npy::NpyWriter;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+#[derive(Args, Debug)]
+pub struct LcsArgs {
+    /// Path to the disk CDAWG for corpus A.
+    #[arg(long)]
+    index_path: String,
+
+    /// Path to the token DiskVec that the CDAWG for A was built on.
+    #[arg(long)]
+    index_tokens_path: String,
+
+    /// Path to the token DiskVec for corpus B, with documents separated by u16::MAX.
+    #[arg(long)]
+    query_tokens_path: String,
+
+    /// Where to write the matched length at each position of B, as a .npy array of u64s.
+    #[arg(long)]
+    profile_path: Option<String>,
+
+    /// Print the result as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+pub fn run(args: &LcsArgs) -> Result<()> {
+    let tokens: DiskVec<u16> = DiskVec::load(&args.index_tokens_path)?;
+    let cdawg: Cdawg<DefaultWeight, DefaultIx, Mb> = Cdawg::load(
+        Rc::new(RefCell::new(tokens)),
+        args.index_path.clone(),
+        CacheConfig::none(),
+    )?;
+    let query: DiskVec<u16> = DiskVec::load(&args.query_tokens_path)?;
+
+    let lcs = match args.profile_path {
+        Some(ref profile_path) => {
+            let (lcs, profile) = overlap_profile(&cdawg, &query);
+            let mut writer = NpyWriter::create(profile_path)?;
+            writer.extend(profile)?;
+            writer.finish()?;
+            lcs
+        }
+        None => longest_common_substring(&cdawg, &query),
+    };
+    if args.json {
+        println!("{}", serde_json::to_string(&lcs)?);
+        return Ok(());
+    }
+
+    if lcs.is_empty() {
+        println!("No token of B occurs in A!");
+    }
+    println!("  length: {}", lcs.len());
+    println!("  span: [{}, {})", lcs.start, lcs.end);
+    println!("  count: {}", lcs.count);
+    let span: Vec<u16> = (lcs.start..lcs.end)
+        .map(|idx| query.get(idx))
+        .collect::<Result<_>>()?;
+    println!("  tokens: {:?}", span);
+    if let Some(ref profile_path) = args.profile_path {
+        println!("Wrote {} positions to {}", query.len(), profile_path);
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+// Subcommands of the rusty-dawg binary for working with (C)DAWGs that have already been built.
+// Building itself is still driven by the top-level flags in main.rs.
+
+mod batch_count;
+mod contains;
+mod convert;
+mod convert_utype;
+mod count;
+mod diff;
+mod export;
+mod export_matches;
+mod export_transitions;
+mod import_ngrams;
+mod lcs;
+mod novelty;
+mod overlap;
+mod serve;
+mod top_substrings;
+mod validate_counts;
+mod verify;
+
+use anyhow::Result;
+use clap::Subcommand;
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Look up the counts of many n-grams in a built CDAWG using parallel workers.
+    BatchCount(batch_count::BatchCountArgs),
+    /// Check that every substring of a token stream occurs in a built CDAWG.
+    Contains(contains::ContainsArgs),
+    /// Convert a built DAWG into a CDAWG over the same tokens without rebuilding it.
+    Convert(convert::ConvertArgs),
+    /// Rewrite a token vector or disk DAWG edge labels to a narrower or wider token type.
+    ConvertUtype(convert_utype::ConvertUtypeArgs),
+    /// Print the count and longest match of one text query in a built CDAWG.
+    Count(count::CountArgs),
+    /// Compare two built (C)DAWGs structurally and report the first difference.
+    Diff(diff::DiffArgs),
+    /// Write the states and edges of a built (C)DAWG as Arrow or CSV tables.
+    Export(export::ExportArgs),
+    /// Write the matched length and count at every position of a token stream as .npy arrays.
+    ExportMatches(export_matches::ExportMatchesArgs),
+    /// Write the transitions of the most frequent states of a built DAWG as a sparse CSR matrix.
+    ExportTransitions(export_transitions::ExportTransitionsArgs),
+    /// Build a DAWG-compatible n-gram trie from an ARPA or TSV n-gram count table.
+    ImportNgrams(import_ngrams::ImportNgramsArgs),
+    /// Find the longest substring of a token stream that occurs in a built CDAWG.
+    Lcs(lcs::LcsArgs),
+    /// Report the fraction of n-grams in a token stream that don't occur in a built CDAWG.
+    Novelty(novelty::NoveltyArgs),
+    /// Score each document of a token stream by its longest substring shared with a built CDAWG.
+    Overlap(overlap::OverlapArgs),
+    /// Answer count, next-token, suffix length and locate queries on a built CDAWG over HTTP.
+    Serve(serve::ServeArgs),
+    /// Report the most frequent substrings of a given length in a built CDAWG.
+    TopSubstrings(top_substrings::TopSubstringsArgs),
+    /// Compare sampled n-gram counts between a CDAWG and a suffix array built over its tokens.
+    ValidateCounts(validate_counts::ValidateCountsArgs),
+    /// Check the failures, transitions and counts of a built DAWG, and optionally repair counts.
+    Verify(verify::VerifyArgs),
+}
+
+impl Command {
+    pub fn run(&self) -> Result<()> {
+        match self {
+            Command::BatchCount(args) => batch_count::run(args),
+            Command::Contains(args) => contains::run(args),
+            Command::Convert(args) => convert::run(args),
+            Command::ConvertUtype(args) => convert_utype::run(args),
+            Command::Count(args) => count::run(args),
+            Command::Diff(args) => diff::run(args),
+            Command::Export(args) => export::run(args),
+            Command::ExportMatches(args) => export_matches::run(args),
+            Command::ExportTransitions(args) => export_transitions::run(args),
+            Command::ImportNgrams(args) => import_ngrams::run(args),
+            Command::Lcs(args) => lcs::run(args),
+            Command::Novelty(args) => novelty::run(args),
+            Command::Overlap(args) => overlap::run(args),
+            Command::Serve(args) => serve::run(args),
+            Command::TopSubstrings(args) => top_substrings::run(args),
+            Command::ValidateCounts(args) => validate_counts::run(args),
+            Command::Verify(args) => verify::run(args),
+        }
+    }
+}
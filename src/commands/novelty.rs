@@ -0,0 +1,105 @@
+// Driver for the `novelty` subcommand: for n = 1..N, the fraction of n-grams in a query corpus B,
+// e.g. LM generations, that don't occur in the corpus A indexed by a CDAWG.
+
+use anyhow::{bail, Result};
+use clap::{Args, ValueEnum};
+use serde_json::json;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::rc::Rc;
+
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::novelty::ngram_novelty;
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum NoveltyFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct NoveltyArgs {
+    /// Path to the disk CDAWG for corpus A.
+    #[arg(long)]
+    index_path: String,
+
+    /// Path to the token DiskVec that the CDAWG for A was built on.
+    #[arg(long)]
+    index_tokens_path: String,
+
+    /// Path to the token DiskVec for corpus B, with documents separated by u16::MAX.
+    #[arg(long)]
+    query_tokens_path: String,
+
+    /// Largest n to report.
+    #[arg(long, default_value_t = 10)]
+    max_n: usize,
+
+    /// Where to write the report. Printed to stdout if not set.
+    #[arg(long)]
+    output_path: Option<String>,
+
+    #[arg(long, value_enum, default_value = "json")]
+    format: NoveltyFormat,
+}
+
+pub fn run(args: &NoveltyArgs) -> Result<()> {
+    if args.max_n == 0 {
+        bail!("--max-n must be positive");
+    }
+    let tokens: DiskVec<u16> = DiskVec::load(&args.index_tokens_path)?;
+    let cdawg: Cdawg<DefaultWeight, DefaultIx, Mb> = Cdawg::load(
+        Rc::new(RefCell::new(tokens)),
+        args.index_path.clone(),
+        CacheConfig::none(),
+    )?;
+    let query: DiskVec<u16> = DiskVec::load(&args.query_tokens_path)?;
+    let report = ngram_novelty(&cdawg, &query, args.max_n);
+
+    let mut writer: Box<dyn Write> = match args.output_path {
+        Some(ref output_path) => Box::new(BufWriter::new(File::create(output_path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+    match args.format {
+        NoveltyFormat::Csv => {
+            writeln!(writer, "n,n_ngrams,n_novel,n_duplicated,novel_fraction")?;
+            for ngram in report.ngrams.iter() {
+                writeln!(
+                    writer,
+                    "{},{},{},{},{}",
+                    ngram.n,
+                    ngram.n_ngrams,
+                    ngram.n_novel,
+                    ngram.n_duplicated(),
+                    ngram.novel_fraction()
+                )?;
+            }
+        }
+        NoveltyFormat::Json => {
+            let ngrams: Vec<_> = report
+                .ngrams
+                .iter()
+                .map(|ngram| {
+                    json!({
+                        "n": ngram.n,
+                        "n_ngrams": ngram.n_ngrams,
+                        "n_novel": ngram.n_novel,
+                        "n_duplicated": ngram.n_duplicated(),
+                        "novel_fraction": ngram.novel_fraction(),
+                    })
+                })
+                .collect();
+            let report = json!({"n_tokens": report.n_tokens, "ngrams": ngrams});
+            writeln!(writer, "{}", serde_json::to_string_pretty(&report)?)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+// Driver for the `overlap` subcommand: for each document in a query corpus B, the longest substring
+// shared with the corpus A indexed by a CDAWG, and its count in A.
+
+use anyhow::{bail, Result};
+use clap::{Args, ValueEnum};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::rc::Rc;
+use std::thread;
+
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::overlap::{document_spans, score_document, DocumentOverlap};
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum OverlapFormat {
+    Csv,
+    Jsonl,
+}
+
+#[derive(Args, Debug)]
+pub struct OverlapArgs {
+    /// Path to the disk CDAWG for corpus A.
+    #[arg(long)]
+    index_path: String,
+
+    /// Path to the token DiskVec that the CDAWG for A was built on.
+    #[arg(long)]
+    index_tokens_path: String,
+
+    /// Path to the token DiskVec for corpus B, with documents separated by u16::MAX.
+    #[arg(long)]
+    query_tokens_path: String,
+
+    /// Where to write one row of statistics per document in B.
+    #[arg(long)]
+    output_path: String,
+
+    #[arg(long, value_enum, default_value = "jsonl")]
+    format: OverlapFormat,
+
+    /// Number of worker threads. Each one opens its own handle on the index. Defaults to the
+    /// number of available cores.
+    #[arg(long)]
+    num_threads: Option<usize>,
+}
+
+pub fn run(args: &OverlapArgs) -> Result<()> {
+    let query: DiskVec<u16> = DiskVec::load(&args.query_tokens_path)?;
+    let spans = document_spans(&query);
+    let num_threads = match args.num_threads {
+        Some(n) => n,
+        None => thread::available_parallelism()?.get(),
+    };
+    if num_threads == 0 {
+        bail!("--num-threads must be positive");
+    }
+    println!("# documents: {}", spans.len());
+    println!("# threads: {}", num_threads);
+
+    // CDAWG handles aren't Send, so each worker loads its own and takes every num_threads-th
+    // document.
+    let mut overlaps: Vec<DocumentOverlap> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..num_threads)
+            .map(|worker| {
+                let spans = &spans;
+                scope.spawn(move || score_worker(args, spans, worker, num_threads))
+            })
+            .collect();
+        let mut overlaps = Vec::with_capacity(spans.len());
+        for worker in workers {
+            overlaps.extend(worker.join().unwrap()?);
+        }
+        Ok::<_, anyhow::Error>(overlaps)
+    })?;
+    overlaps.sort_by_key(|overlap| overlap.doc_idx);
+
+    let mut writer = BufWriter::new(File::create(&args.output_path)?);
+    if let OverlapFormat::Csv = args.format {
+        writeln!(
+            writer,
+            "doc_idx,start,n_tokens,max_length,max_count,match_end"
+        )?;
+    }
+    for overlap in overlaps.iter() {
+        match args.format {
+            OverlapFormat::Csv => writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                overlap.doc_idx,
+                overlap.start,
+                overlap.n_tokens,
+                overlap.max_length,
+                overlap.max_count,
+                overlap.match_end
+            )?,
+            OverlapFormat::Jsonl => writeln!(writer, "{}", serde_json::to_string(overlap)?)?,
+        }
+    }
+    writer.flush()?;
+    println!("Wrote {} rows to {}", overlaps.len(), args.output_path);
+    Ok(())
+}
+
+fn score_worker(
+    args: &OverlapArgs,
+    spans: &[(usize, usize)],
+    worker: usize,
+    num_threads: usize,
+) -> Result<Vec<DocumentOverlap>> {
+    let tokens: DiskVec<u16> = DiskVec::load(&args.index_tokens_path)?;
+    let cdawg: Cdawg<DefaultWeight, DefaultIx, Mb> = Cdawg::load(
+        Rc::new(RefCell::new(tokens)),
+        args.index_path.clone(),
+        CacheConfig::none(),
+    )?;
+    let query: DiskVec<u16> = DiskVec::load(&args.query_tokens_path)?;
+    Ok(spans
+        .iter()
+        .enumerate()
+        .skip(worker)
+        .step_by(num_threads)
+        .map(|(doc_idx, span)| score_document(&cdawg, &query, doc_idx, *span))
+        .collect())
+}
@@ -0,0 +1,365 @@
+// Driver for the `serve` subcommand: answer n-gram queries against a built CDAWG over HTTP, so the
+// index can be used as a standalone infini-gram service.
+//
+// Each endpoint takes a GET query string with either `tokens` (comma-separated token IDs) or `text`
+// (tokenized with the tokenizer recorded in the index manifest, or --tokenizer), and responds with
+// JSON:
+//
+//   /count          occurrences of the whole query
+//   /next_tokens    distribution over the next token after the longest matched suffix
+//   /suffix_length  length and count of the longest suffix of the query that occurs
+//   /locate         corpus offsets of up to `max_results` occurrences of the query
+//
+// /metrics takes no query and returns query, cache and disk-read metrics in the Prometheus text
+// format, for scraping.
+//
+// The CDAWG isn't thread-safe, so connections are handled one at a time. The server speaks just
+// enough HTTP/1.1 for curl and requests, and closes each connection after responding.
+
+use anyhow::{anyhow, bail, Result};
+use clap::Args;
+use serde::Serialize;
+use serde_json::json;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::rc::Rc;
+
+use rusty_dawg::build_manifest::BuildManifest;
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::cdawg_state::CdawgState;
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::metrics;
+use rusty_dawg::tokenize::PretrainedTokenizer;
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Path to the disk CDAWG.
+    #[arg(long)]
+    index_path: String,
+
+    /// Path to the token DiskVec that the CDAWG was built on. Not needed if the CDAWG references
+    /// shared tokens.
+    #[arg(long)]
+    index_tokens_path: Option<String>,
+
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+
+    /// Pretrained tokenizer for `text` queries, instead of the one in the index manifest.
+    #[arg(long)]
+    tokenizer: Option<String>,
+
+    /// Upper bound on `max_results` for /locate.
+    #[arg(long, default_value_t = 1000)]
+    max_locate_results: usize,
+}
+
+// A parsed GET request.
+struct Request {
+    path: String,
+    params: HashMap<String, String>,
+}
+
+// A response body, JSON for queries and plain text for /metrics.
+enum Body {
+    Json(serde_json::Value),
+    Text(String),
+}
+
+#[derive(Serialize)]
+struct NextToken {
+    token: u16,
+    prob: f64,
+}
+
+struct Server {
+    cdawg: Cdawg<DefaultWeight, DefaultIx, Mb>,
+    tokenizer: Option<PretrainedTokenizer>,
+    max_locate_results: usize,
+}
+
+pub fn run(args: &ServeArgs) -> Result<()> {
+    let cdawg = match args.index_tokens_path {
+        Some(ref path) => {
+            let index_tokens: DiskVec<u16> = DiskVec::load(path)?;
+            Cdawg::load(
+                Rc::new(RefCell::new(index_tokens)),
+                args.index_path.clone(),
+                CacheConfig::none(),
+            )?
+        }
+        None => Cdawg::load_shared(args.index_path.clone(), CacheConfig::none())?,
+    };
+    let tokenizer_name = match args.tokenizer {
+        Some(ref name) => Some(name.clone()),
+        None => BuildManifest::load_next_to(&args.index_path)?.map(|manifest| manifest.tokenizer),
+    };
+    let tokenizer = match tokenizer_name {
+        Some(name) => Some(PretrainedTokenizer::try_new(&name)?),
+        None => None,
+    };
+    let server = Server {
+        cdawg,
+        tokenizer,
+        max_locate_results: args.max_locate_results,
+    };
+
+    metrics::enable();
+    let listener = TcpListener::bind((args.host.as_str(), args.port))?;
+    println!("Serving {} on {}", args.index_path, listener.local_addr()?);
+    for stream in listener.incoming() {
+        // A bad connection shouldn't take the server down.
+        if let Err(err) = stream
+            .map_err(anyhow::Error::from)
+            .and_then(|s| server.handle(s))
+        {
+            eprintln!("Error handling connection: {}", err);
+        }
+    }
+    Ok(())
+}
+
+impl Server {
+    fn handle(&self, mut stream: TcpStream) -> Result<()> {
+        let (status, body) = match read_request(&stream) {
+            Ok(request) => match self.respond(&request) {
+                Ok(Some(body)) => ("200 OK", body),
+                Ok(None) => (
+                    "404 Not Found",
+                    Body::Json(json!({ "error": format!("Unknown endpoint {}", request.path) })),
+                ),
+                Err(err) => (
+                    "400 Bad Request",
+                    Body::Json(json!({ "error": err.to_string() })),
+                ),
+            },
+            Err(err) => (
+                "400 Bad Request",
+                Body::Json(json!({ "error": err.to_string() })),
+            ),
+        };
+        let (content_type, body) = match body {
+            Body::Json(value) => ("application/json", serde_json::to_string(&value)?),
+            Body::Text(text) => ("text/plain; version=0.0.4", text),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        )?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    // The body for `request`, or None if the endpoint doesn't exist.
+    fn respond(&self, request: &Request) -> Result<Option<Body>> {
+        let body = match request.path.as_str() {
+            "/count" => {
+                let tokens = self.get_tokens(request)?;
+                json!({ "count": self.cdawg.get_ngram_count(&tokens), "tokens": tokens })
+            }
+            "/next_tokens" => {
+                let tokens = self.get_tokens(request)?;
+                let cs = self.match_suffix(&tokens);
+                let next_tokens: Vec<_> = self
+                    .cdawg
+                    .get_next_tokens(cs)
+                    .into_iter()
+                    .map(|(token, prob)| NextToken { token, prob })
+                    .collect();
+                json!({
+                    "tokens": tokens,
+                    "suffix_length": cs.length,
+                    "next_tokens": next_tokens,
+                })
+            }
+            "/suffix_length" => {
+                let tokens = self.get_tokens(request)?;
+                let cs = self.match_suffix(&tokens);
+                json!({
+                    "tokens": tokens,
+                    "suffix_length": cs.length,
+                    "count": self.cdawg.get_suffix_count(cs),
+                })
+            }
+            "/locate" => {
+                let tokens = self.get_tokens(request)?;
+                let max_results = match request.params.get("max_results") {
+                    Some(value) => value.parse()?,
+                    None => 10,
+                };
+                let max_results = usize::min(max_results, self.max_locate_results);
+                let positions = if tokens.is_empty() || max_results == 0 {
+                    Vec::new()
+                } else {
+                    self.cdawg.locate(&tokens, max_results)
+                };
+                json!({ "tokens": tokens, "positions": positions })
+            }
+            "/metrics" => return Ok(Some(Body::Text(metrics::snapshot().to_prometheus()))),
+            _ => return Ok(None),
+        };
+        Ok(Some(Body::Json(body)))
+    }
+
+    // Run the query through the CDAWG, ending at its longest suffix that occurs.
+    fn match_suffix(&self, tokens: &[u16]) -> CdawgState<DefaultIx> {
+        let mut cs = self.cdawg.get_initial();
+        for token in tokens.iter() {
+            cs = self.cdawg.transition_and_count(cs, *token);
+        }
+        if cs.target.is_none() {
+            // The last token never occurs, so back off to the empty suffix.
+            cs = self.cdawg.get_initial();
+        }
+        cs
+    }
+
+    fn get_tokens(&self, request: &Request) -> Result<Vec<u16>> {
+        if let Some(tokens) = request.params.get("tokens") {
+            return tokens
+                .split(',')
+                .filter(|token| !token.is_empty())
+                .map(|token| match token.trim().parse::<u16>() {
+                    Ok(id) if id != u16::MAX => Ok(id),
+                    _ => bail!("Invalid token ID {:?}", token),
+                })
+                .collect();
+        }
+        let Some(text) = request.params.get("text") else {
+            bail!("Pass the query as `tokens` or `text`");
+        };
+        let Some(ref tokenizer) = self.tokenizer else {
+            bail!("The index has no manifest recording its tokenizer; pass `tokens`");
+        };
+        let (ids, _) = tokenizer.encode_with_offsets(text)?;
+        ids.into_iter()
+            .map(|id| match u16::try_from(id) {
+                Ok(id) if id != u16::MAX => Ok(id),
+                _ => bail!("Token ID {} exceeds u16", id),
+            })
+            .collect()
+    }
+}
+
+// Read the request line and headers, ignoring any body.
+fn read_request(stream: &TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        bail!("Malformed request line {:?}", line.trim_end());
+    };
+    if method != "GET" {
+        bail!("Only GET is supported");
+    }
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut params = HashMap::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params.insert(percent_decode(key)?, percent_decode(value)?);
+    }
+    Ok(Request {
+        path: path.to_string(),
+        params,
+    })
+}
+
+// Decode a URL-encoded query string component, where `+` is a space.
+fn percent_decode(text: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut iter = text.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [iter.next(), iter.next()];
+                let [Some(hi), Some(lo)] = hex else {
+                    bail!("Truncated escape in {:?}", text);
+                };
+                let hex = std::str::from_utf8(&[hi, lo])?.to_string();
+                bytes.push(
+                    u8::from_str_radix(&hex, 16)
+                        .map_err(|_| anyhow!("Invalid escape %{} in {:?}", hex, text))?,
+                );
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_dawg::cdawg::TopologicalCounter;
+    use rusty_dawg::io::Save;
+    use std::io::Read;
+    use std::thread;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_metrics_endpoint() {
+        let tokens = vec![0, 1, 0, 1, 2, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        let tmp_dir = tempdir().unwrap();
+        cdawg.save(tmp_dir.path().to_str().unwrap()).unwrap();
+        let server = Server {
+            cdawg: Cdawg::load(
+                Rc::new(RefCell::new(tokens)),
+                tmp_dir.path(),
+                CacheConfig::none(),
+            )
+            .unwrap(),
+            tokenizer: None,
+            max_locate_results: 10,
+        };
+
+        metrics::enable();
+        let count = Request {
+            path: "/count".to_string(),
+            params: HashMap::from([("tokens".to_string(), "0,1".to_string())]),
+        };
+        assert!(server.respond(&count).unwrap().is_some());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        let (stream, _) = listener.accept().unwrap();
+        server.handle(stream).unwrap();
+        let response = client.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Content-Type: text/plain"));
+        assert!(response.contains("rusty_dawg_query_seconds_count 1\n"));
+    }
+}
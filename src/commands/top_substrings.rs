@@ -0,0 +1,115 @@
+// Driver for the `top-substrings` subcommand: report the k most frequent substrings of length n in a
+// built CDAWG, decoded with the tokenizer recorded in the index manifest (or --tokenizer).
+//
+// Substrings are printed as token IDs when there is no tokenizer to decode them with, e.g. for
+// indices built with the whitespace tokenizer, whose vocabulary isn't saved.
+
+use anyhow::Result;
+use clap::Args;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rusty_dawg::build_manifest::BuildManifest;
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::tokenize::{ByteTokenizer, CharTokenizer, Detokenize, PretrainedTokenizer};
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+#[derive(Args, Debug)]
+pub struct TopSubstringsArgs {
+    /// Path to the disk CDAWG.
+    #[arg(long)]
+    index: String,
+
+    /// Path to the token DiskVec that the CDAWG was built on. Not needed if the CDAWG references
+    /// shared tokens.
+    #[arg(long)]
+    index_tokens_path: Option<String>,
+
+    /// Length of the substrings, in tokens.
+    #[arg(short)]
+    n: usize,
+
+    /// Number of substrings to report.
+    #[arg(short, default_value_t = 10)]
+    k: usize,
+
+    /// Tokenizer to decode with instead of the one in the index manifest.
+    #[arg(long)]
+    tokenizer: Option<String>,
+
+    /// Print the result as JSON lines.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct Substring {
+    tokens: Vec<u16>,
+    text: Option<String>,
+    count: usize,
+}
+
+pub fn run(args: &TopSubstringsArgs) -> Result<()> {
+    let decoder = get_decoder(args)?;
+    let cdawg: Cdawg<DefaultWeight, DefaultIx, Mb> = match args.index_tokens_path {
+        Some(ref path) => {
+            let index_tokens: DiskVec<u16> = DiskVec::load(path)?;
+            Cdawg::load(
+                Rc::new(RefCell::new(index_tokens)),
+                args.index.clone(),
+                CacheConfig::none(),
+            )?
+        }
+        None => Cdawg::load_shared(args.index.clone(), CacheConfig::none())?,
+    };
+
+    for (rank, (tokens, count)) in cdawg.top_substrings(args.n, args.k).into_iter().enumerate() {
+        let text = decoder.as_ref().map(|decoder| {
+            tokens
+                .iter()
+                .filter_map(|token| decoder.decode(*token))
+                .collect::<String>()
+        });
+        let substring = Substring {
+            tokens,
+            text,
+            count,
+        };
+        if args.json {
+            println!("{}", serde_json::to_string(&substring)?);
+            continue;
+        }
+        match substring.text {
+            Some(text) => println!("{:>4}. {:>10}  {:?}", rank + 1, substring.count, text),
+            None => println!(
+                "{:>4}. {:>10}  {:?}",
+                rank + 1,
+                substring.count,
+                substring.tokens
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn get_decoder(args: &TopSubstringsArgs) -> Result<Option<Box<dyn Detokenize<u16>>>> {
+    let name = match args.tokenizer {
+        Some(ref name) => name.clone(),
+        None => match BuildManifest::load_next_to(&args.index)? {
+            Some(manifest) => manifest.tokenizer,
+            None => return Ok(None),
+        },
+    };
+    Ok(match name.as_str() {
+        "bytes" => Some(Box::new(ByteTokenizer::new())),
+        "chars" => Some(Box::new(CharTokenizer::new())),
+        "whitespace" | "null" => None,
+        name => Some(Box::new(PretrainedTokenizer::try_new(name)?)),
+    })
+}
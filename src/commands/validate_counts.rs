@@ -0,0 +1,102 @@
+// Driver for the `validate-counts` subcommand: spot-check the counts of a built CDAWG against a
+// suffix array over the same tokens.
+//
+// N-grams are sampled from the token vector itself, so every sample occurs at least once. Samples
+// that cross a document boundary are skipped.
+
+use anyhow::{bail, Result};
+use clap::Args;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rusty_dawg::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use rusty_dawg::cdawg::sampling::XorShiftRng;
+use rusty_dawg::cdawg::token_backing::TokenBacking;
+use rusty_dawg::cdawg::Cdawg;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use rusty_dawg::suffix_array::SuffixArray;
+use rusty_dawg::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+#[derive(Args, Debug)]
+pub struct ValidateCountsArgs {
+    /// Path to the disk CDAWG.
+    #[arg(long)]
+    index_path: String,
+
+    /// Path to the token DiskVec that the CDAWG was built on.
+    #[arg(long)]
+    index_tokens_path: String,
+
+    /// Number of n-grams to sample.
+    #[arg(long, default_value_t = 1000)]
+    n_samples: usize,
+
+    /// Maximum length of a sampled n-gram.
+    #[arg(long, default_value_t = 10)]
+    max_n: usize,
+
+    /// Seed for sampling.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Maximum number of mismatches to print.
+    #[arg(long, default_value_t = 10)]
+    max_mismatches: usize,
+}
+
+pub fn run(args: &ValidateCountsArgs) -> Result<()> {
+    if args.max_n == 0 {
+        bail!("--max-n must be positive");
+    }
+    let tokens: DiskVec<u16> = DiskVec::load(&args.index_tokens_path)?;
+    let tokens_rc = Rc::new(RefCell::new(tokens));
+    let cdawg: Cdawg<DefaultWeight, DefaultIx, Mb> = Cdawg::load(
+        tokens_rc.clone(),
+        args.index_path.clone(),
+        CacheConfig::none(),
+    )?;
+
+    let tokens = tokens_rc.borrow();
+    let n_tokens = TokenBacking::len(&*tokens);
+    if n_tokens == 0 {
+        bail!("Token vector is empty");
+    }
+    println!("Building suffix array over {} tokens...", n_tokens);
+    let sa = SuffixArray::new(&*tokens);
+
+    let mut rng = XorShiftRng::new(args.seed);
+    let mut n_checked = 0;
+    let mut n_mismatches = 0;
+    for _ in 0..args.n_samples {
+        let start = rng.next_u64() as usize % n_tokens;
+        let n = 1 + rng.next_u64() as usize % args.max_n;
+        let end = usize::min(start + n, n_tokens);
+        let ngram: Vec<u16> = (start..end)
+            .map(|idx| TokenBacking::get(&*tokens, idx))
+            .collect();
+        if ngram.contains(&u16::MAX) {
+            continue;
+        }
+
+        n_checked += 1;
+        let expected = sa.count(&*tokens, &ngram);
+        let count = cdawg.get_ngram_count(&ngram);
+        if count != expected {
+            if n_mismatches < args.max_mismatches {
+                println!("  {:?}: CDAWG {}, suffix array {}", ngram, count, expected);
+            }
+            n_mismatches += 1;
+        }
+    }
+
+    println!("  # checked: {}", n_checked);
+    println!("  # mismatches: {}", n_mismatches);
+    if n_mismatches > 0 {
+        bail!("{} of {} counts didn't match", n_mismatches, n_checked);
+    }
+    println!("All counts match!");
+    Ok(())
+}
@@ -0,0 +1,167 @@
+// Driver for the `verify` subcommand, which checks the invariants of a built DAWG (see
+// dawg::verify) and optionally repairs its counts. Exits with an error if violations remain. For a
+// CDAWG, it checks the hash of the shared tokens it references, which loading skips. With
+// `--checksums`, it also checks the checksums of the `.vec` files, which loading skips too.
+//
+// A directory is loaded as a disk DAWG, whose repaired counts are written in place, and a file as a
+// DAWG saved from RAM, which is saved again after repairing.
+
+use anyhow::{anyhow, bail, Result};
+use clap::Args;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::diff::{load_metadata, DawgFile};
+use rusty_dawg::cdawg::shared_tokens::SharedTokens;
+use rusty_dawg::cdawg::token_backing::CdawgToken;
+use rusty_dawg::dawg::Dawg;
+use rusty_dawg::graph::avl_graph::binary::BinaryLabel;
+use rusty_dawg::graph::indexing::DefaultIx;
+use rusty_dawg::io::Save;
+use rusty_dawg::memory_backing::vec_backing::verify_checksum;
+use rusty_dawg::memory_backing::{CacheConfig, DiskBacking, DiskVec, MemoryBacking};
+use rusty_dawg::tokenize::token_type::TokenType;
+use rusty_dawg::weight::DefaultWeight;
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// DAWG to check: a disk directory, or a file saved from a RAM DAWG. A CDAWG directory has its
+    /// shared tokens checked.
+    path: String,
+
+    /// Raise counts that are less than the total of the states that fail to them.
+    #[arg(long)]
+    repair: bool,
+
+    /// Also check the checksums of the `.vec` files in a disk directory and of shared tokens.
+    #[arg(long)]
+    checksums: bool,
+
+    /// Token type of DAWG edges: `u16`, `u32`, or `usize`.
+    #[arg(long, default_value = "u16")]
+    utype: TokenType,
+}
+
+pub fn run(args: &VerifyArgs) -> Result<()> {
+    if args.checksums {
+        verify_checksums(Path::new(&args.path))?;
+    }
+    if let Some(metadata) = load_metadata(&args.path)? {
+        let Some(shared_tokens) = metadata.tokens else {
+            if args.checksums {
+                return Ok(());
+            }
+            bail!(
+                "{} is a CDAWG without shared tokens, so there is nothing to verify",
+                args.path
+            );
+        };
+        let shared_tokens = shared_tokens.resolve(&args.path);
+        if args.checksums {
+            verify_checksum(&shared_tokens.path)?;
+        }
+        // CDAWGs record their token type, so --utype isn't needed.
+        if metadata.utype.as_deref() == Some(u32::NAME) {
+            verify_tokens::<u32>(&shared_tokens)?;
+        } else {
+            verify_tokens::<u16>(&shared_tokens)?;
+        }
+        println!("Shared tokens {:?} match the index", shared_tokens.path);
+        return Ok(());
+    }
+    match args.utype {
+        TokenType::U16 => verify_file::<u16>(args),
+        TokenType::U32 => verify_file::<u32>(args),
+        TokenType::Usize => verify_file::<usize>(args),
+    }
+}
+
+// Files saved from a RAM DAWG aren't DiskVecs, so only directories have checksums to check.
+fn verify_checksums(path: &Path) -> Result<()> {
+    if !path.is_dir() {
+        return Ok(());
+    }
+    let mut vec_paths: Vec<PathBuf> = fs::read_dir(path)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<_>>()?;
+    vec_paths.retain(|path| path.extension().is_some_and(|ext| ext == "vec"));
+    vec_paths.sort();
+    for vec_path in vec_paths.iter() {
+        verify_checksum(vec_path)?;
+    }
+    println!("Checked the checksums of {} files", vec_paths.len());
+    Ok(())
+}
+
+fn verify_tokens<T>(shared_tokens: &SharedTokens) -> Result<()>
+where
+    T: CdawgToken + Serialize + DeserializeOwned + Default,
+{
+    let tokens: DiskVec<T> = DiskVec::load(&shared_tokens.path)?;
+    shared_tokens.verify(&tokens)
+}
+
+fn verify_file<E>(args: &VerifyArgs) -> Result<()>
+where
+    E: Eq + Ord + Copy + Debug + Serialize + DeserializeOwned + Default + BinaryLabel,
+{
+    // Disk DAWGs are only opened for writing to repair them, so that checking never changes files.
+    if args.repair && Path::new(&args.path).is_dir() {
+        let mut dawg: Dawg<E, DefaultWeight, DefaultIx, DiskBacking<DefaultWeight, E, DefaultIx>> =
+            Dawg::open_mut(&args.path, CacheConfig::none())?;
+        if verify(args, &mut dawg)? {
+            dawg.flush()?;
+        }
+        return Ok(());
+    }
+    match DawgFile::<E>::load(&args.path)? {
+        DawgFile::Ram(mut dawg) => {
+            if verify(args, &mut dawg)? {
+                dawg.save(&args.path).map_err(|err| anyhow!("{}", err))?;
+            }
+        }
+        DawgFile::Disk(mut dawg) => {
+            verify(args, &mut dawg)?;
+        }
+    }
+    Ok(())
+}
+
+// Returns whether counts were repaired, so that the DAWG needs saving.
+fn verify<E, Mb>(
+    args: &VerifyArgs,
+    dawg: &mut Dawg<E, DefaultWeight, DefaultIx, Mb>,
+) -> Result<bool>
+where
+    E: Eq + Ord + Copy + Debug + Serialize + DeserializeOwned,
+    Mb: MemoryBacking<DefaultWeight, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    let report = dawg.verify();
+    println!(
+        "Checked {} states: {} violations, {} of them counts",
+        report.n_states, report.n_violations, report.n_count_violations
+    );
+    for violation in report.violations.iter() {
+        println!("  {}", violation);
+    }
+    if report.violations.len() < report.n_violations {
+        println!(
+            "  ... and {} more",
+            report.n_violations - report.violations.len()
+        );
+    }
+    if report.is_ok() {
+        return Ok(false);
+    }
+    if !args.repair {
+        bail!("Found {} violations", report.n_violations);
+    }
+
+    let n_repaired = dawg.repair_counts()?;
+    println!("Repaired the counts of {} states", n_repaired);
+    Ok(true)
+}
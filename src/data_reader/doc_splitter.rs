@@ -0,0 +1,160 @@
+// Splitting of very long documents into overlapping pseudo-documents.
+//
+// A single huge document makes document-level provenance useless, so documents longer than
+// `max_tokens` are cut into pieces of at most `max_tokens` tokens, where consecutive pieces share
+// `overlap` tokens. Each piece is indexed as its own document with a fresh ID. If a docs sidecar is
+// configured, one JSON line per piece records which source document and token range it came from,
+// so provenance can be reassembled.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DocPiece {
+    pub doc_id: usize,        // ID the piece is indexed under.
+    pub source_doc_id: usize, // ID of the document it was cut from.
+    pub start: usize,         // First token of the piece in the source document.
+    pub end: usize,           // End (exclusive) of the piece in the source document.
+}
+
+impl DocPiece {
+    /// A document that isn't split.
+    pub fn whole(doc_id: usize, n_tokens: usize) -> Self {
+        Self {
+            doc_id,
+            source_doc_id: doc_id,
+            start: 0,
+            end: n_tokens,
+        }
+    }
+}
+
+pub struct DocSplitter {
+    max_tokens: usize,
+    overlap: usize,
+    next_id: usize,
+    sidecar: Option<BufWriter<File>>,
+}
+
+impl DocSplitter {
+    pub fn new(max_tokens: usize, overlap: usize) -> Result<Self> {
+        if overlap >= max_tokens {
+            bail!(
+                "Overlap ({}) must be less than the maximum document length ({})",
+                overlap,
+                max_tokens
+            );
+        }
+        Ok(Self {
+            max_tokens,
+            overlap,
+            next_id: 0,
+            sidecar: None,
+        })
+    }
+
+    /// Write a JSON line for every piece to `path`.
+    pub fn with_sidecar<P: AsRef<Path>>(mut self, path: P) -> Result<Self> {
+        self.sidecar = Some(BufWriter::new(File::create(path)?));
+        Ok(self)
+    }
+
+    /// [start, end) spans of the pieces of a document with `n_tokens` tokens.
+    pub fn spans(&self, n_tokens: usize) -> Vec<(usize, usize)> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+        loop {
+            let end = usize::min(start + self.max_tokens, n_tokens);
+            spans.push((start, end));
+            if end == n_tokens {
+                return spans;
+            }
+            start += self.max_tokens - self.overlap;
+        }
+    }
+
+    /// Split the document `source_doc_id` with `n_tokens` tokens, assigning IDs to the pieces
+    /// consecutively across documents and recording them in the sidecar.
+    pub fn split(&mut self, source_doc_id: usize, n_tokens: usize) -> Result<Vec<DocPiece>> {
+        let mut pieces = Vec::new();
+        for (start, end) in self.spans(n_tokens) {
+            let piece = DocPiece {
+                doc_id: self.next_id,
+                source_doc_id,
+                start,
+                end,
+            };
+            self.next_id += 1;
+            if let Some(ref mut sidecar) = self.sidecar {
+                writeln!(sidecar, "{}", serde_json::to_string(&piece)?)?;
+            }
+            pieces.push(piece);
+        }
+        Ok(pieces)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(ref mut sidecar) = self.sidecar {
+            sidecar.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the pieces recorded in a docs sidecar.
+pub fn load_sidecar<P: AsRef<Path>>(path: P) -> Result<Vec<DocPiece>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut pieces = Vec::new();
+    for line in reader.lines() {
+        pieces.push(serde_json::from_str(&line?)?);
+    }
+    Ok(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_spans() {
+        let splitter = DocSplitter::new(4, 1).unwrap();
+        assert_eq!(splitter.spans(3), vec![(0, 3)]);
+        assert_eq!(splitter.spans(4), vec![(0, 4)]);
+        assert_eq!(splitter.spans(10), vec![(0, 4), (3, 7), (6, 10)]);
+        assert_eq!(splitter.spans(0), vec![(0, 0)]);
+        assert!(DocSplitter::new(4, 4).is_err());
+    }
+
+    #[test]
+    fn test_split_with_sidecar() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("docs.jsonl");
+        let mut splitter = DocSplitter::new(3, 0).unwrap().with_sidecar(&path).unwrap();
+        let first = splitter.split(0, 5).unwrap();
+        let second = splitter.split(1, 2).unwrap();
+        splitter.flush().unwrap();
+
+        let ids: Vec<_> = first
+            .iter()
+            .chain(second.iter())
+            .map(|p| p.doc_id)
+            .collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+        let pieces = load_sidecar(&path).unwrap();
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(
+            pieces[1],
+            DocPiece {
+                doc_id: 1,
+                source_doc_id: 0,
+                start: 3,
+                end: 5
+            }
+        );
+        assert_eq!(pieces[2].source_doc_id, 1);
+    }
+}
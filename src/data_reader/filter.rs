@@ -0,0 +1,174 @@
+// Predicates on JSON documents, applied by the JSONL/Pile readers before tokenization so that an
+// index can be built over a region of interest of a corpus.
+//
+// Filters are written on the command line as:
+//   * `field=value`: the field equals `value` (as a string, or the JSON rendering of a non-string),
+//   * `field~regex`: the field is a string matching `regex`,
+//   * `field>=start`, `field<end`: the field is a string that sorts in [start, end). This is meant for
+//     ISO-8601 dates/times, which compare correctly as strings.
+// Fields can be nested with dots, e.g. `meta.pile_set_name=Github`.
+
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub enum DocumentFilter {
+    Equals { field: String, value: String },
+    Matches { field: String, regex: Regex },
+    AtLeast { field: String, value: String },
+    Before { field: String, value: String },
+}
+
+impl DocumentFilter {
+    pub fn accepts(&self, blob: &Value) -> bool {
+        match self {
+            DocumentFilter::Equals { field, value } => match lookup(blob, field) {
+                Some(Value::String(s)) => s == value,
+                Some(other) => other.to_string().as_str() == value.as_str(),
+                None => false,
+            },
+            DocumentFilter::Matches { field, regex } => match lookup(blob, field) {
+                Some(Value::String(s)) => regex.is_match(s),
+                _ => false,
+            },
+            DocumentFilter::AtLeast { field, value } => match lookup(blob, field) {
+                Some(Value::String(s)) => s.as_str() >= value.as_str(),
+                _ => false,
+            },
+            DocumentFilter::Before { field, value } => match lookup(blob, field) {
+                Some(Value::String(s)) => s.as_str() < value.as_str(),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Whether a document passes every filter.
+pub fn accepts_all(filters: &[DocumentFilter], blob: &Value) -> bool {
+    filters.iter().all(|filter| filter.accepts(blob))
+}
+
+/// Follow a dotted path of object keys, e.g. `meta.pile_set_name`.
+pub(crate) fn lookup<'a>(blob: &'a Value, field: &str) -> Option<&'a Value> {
+    field
+        .split('.')
+        .try_fold(blob, |value, key| value.as_object()?.get(key))
+}
+
+impl FromStr for DocumentFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // Split at the first operator, so values and regexes may contain operator characters.
+        if let Some(idx) = s.find(['=', '~', '<', '>']) {
+            let field = s[..idx].to_string();
+            let rest = &s[idx..];
+            if let Some(value) = rest.strip_prefix(">=") {
+                let value = value.to_string();
+                return Ok(DocumentFilter::AtLeast { field, value });
+            }
+            // Reject the other comparisons, rather than reading `a<=b` as `a` before `=b`.
+            if rest.starts_with("<=") || rest.starts_with('>') {
+                bail!(
+                    "Unsupported operator in filter {:?}: only =, ~, >= and < are supported",
+                    s
+                );
+            }
+            if let Some(value) = rest.strip_prefix('<') {
+                let value = value.to_string();
+                return Ok(DocumentFilter::Before { field, value });
+            }
+            if let Some(pattern) = rest.strip_prefix('~') {
+                let regex = Regex::new(pattern)?;
+                return Ok(DocumentFilter::Matches { field, regex });
+            }
+            if let Some(value) = rest.strip_prefix('=') {
+                let value = value.to_string();
+                return Ok(DocumentFilter::Equals { field, value });
+            }
+        }
+        bail!(
+            "Invalid filter {:?}: expected field=value, field~regex, field>=start, or field<end",
+            s
+        )
+    }
+}
+
+impl fmt::Display for DocumentFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentFilter::Equals { field, value } => write!(f, "{}={}", field, value),
+            DocumentFilter::Matches { field, regex } => write!(f, "{}~{}", field, regex.as_str()),
+            DocumentFilter::AtLeast { field, value } => write!(f, "{}>={}", field, value),
+            DocumentFilter::Before { field, value } => write!(f, "{}<{}", field, value),
+        }
+    }
+}
+
+// Serialize in the command-line syntax, e.g. for recording provenance in a build manifest.
+impl Serialize for DocumentFilter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for DocumentFilter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_accept() {
+        let blob = json!({
+            "text": "fn main() {}",
+            "meta": {"pile_set_name": "Github", "stars": 5},
+            "date": "2020-06-01",
+        });
+
+        let filter: DocumentFilter = "meta.pile_set_name=Github".parse().unwrap();
+        assert!(filter.accepts(&blob));
+        let filter: DocumentFilter = "meta.pile_set_name=ArXiv".parse().unwrap();
+        assert!(!filter.accepts(&blob));
+        let filter: DocumentFilter = "meta.stars=5".parse().unwrap();
+        assert!(filter.accepts(&blob));
+        let filter: DocumentFilter = "text~^fn ".parse().unwrap();
+        assert!(filter.accepts(&blob));
+        let filter: DocumentFilter = "missing=x".parse().unwrap();
+        assert!(!filter.accepts(&blob));
+
+        let range: Vec<DocumentFilter> = vec![
+            "date>=2020-01-01".parse().unwrap(),
+            "date<2021-01-01".parse().unwrap(),
+        ];
+        assert!(accepts_all(&range, &blob));
+        let range: Vec<DocumentFilter> = vec!["date>=2020-07-01".parse().unwrap()];
+        assert!(!accepts_all(&range, &blob));
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for s in [
+            "a.b=c=d",
+            "url~^https?://[a-z]+=",
+            "date>=2020",
+            "date<2021",
+        ] {
+            let filter: DocumentFilter = s.parse().unwrap();
+            assert_eq!(filter.to_string(), s);
+        }
+        assert!("no-operator".parse::<DocumentFilter>().is_err());
+        assert!("date<=2021".parse::<DocumentFilter>().is_err());
+        assert!("date>2021".parse::<DocumentFilter>().is_err());
+    }
+}
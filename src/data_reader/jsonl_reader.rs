@@ -1,14 +1,23 @@
 use anyhow::Result;
 use serde_json::Value;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::data_reader::buf_reader::BufReader;
+use crate::data_reader::filter::{accepts_all, lookup, DocumentFilter};
+use crate::error::RustyDawgError;
 
-/// Untyped JSONL reader when text/domain are stored as unembedded keys.
+/// Untyped JSONL reader for corpora that store the text, and optionally a domain, under
+/// configurable keys. Keys can be nested with dots, e.g. `metadata.text`.
+///
+/// If there is a domain key, each document's ID is the ID of its domain, like with the Pile reader.
+/// Domains are numbered in order of first appearance.
 pub struct JsonlReader {
     buf_reader: BufReader,
     text_key: String,
     domain_key: Option<String>,
+    filters: Vec<DocumentFilter>,
+    domains: HashMap<String, usize>,
 }
 
 impl JsonlReader {
@@ -22,28 +31,145 @@ impl JsonlReader {
             buf_reader,
             text_key,
             domain_key,
+            filters: Vec::new(),
+            domains: HashMap::new(),
         })
     }
+
+    /// Skip documents that don't pass all of `filters`.
+    pub fn with_filters(mut self, filters: Vec<DocumentFilter>) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Names of the domains read so far, indexed by ID. Non-string domains are rendered as JSON.
+    pub fn get_domain_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.domains.len()];
+        for (name, id) in self.domains.iter() {
+            names[*id] = name.clone();
+        }
+        names
+    }
+
+    fn get_domain_id(&mut self, blob: &Value, domain_key: &str) -> Result<usize> {
+        let name = match lookup(blob, domain_key) {
+            Some(Value::String(name)) => name.clone(),
+            Some(other) => other.to_string(),
+            None => {
+                let msg = format!("no domain at key {}", domain_key);
+                return Err(RustyDawgError::MalformedDocument(msg).into());
+            }
+        };
+        let n_domains = self.domains.len();
+        Ok(*self.domains.entry(name).or_insert(n_domains))
+    }
+
+    // None if the document is filtered out.
+    fn parse(&mut self, line: &str) -> Result<Option<(usize, Rc<String>)>> {
+        let blob: Value = serde_json::from_str(line)
+            .map_err(|err| RustyDawgError::MalformedDocument(err.to_string()))?;
+        if !accepts_all(&self.filters, &blob) {
+            return Ok(None);
+        }
+        let text = match lookup(&blob, &self.text_key) {
+            Some(Value::String(text)) => Rc::new(text.to_string()),
+            _ => {
+                let msg = format!("no text at key {}", self.text_key);
+                return Err(RustyDawgError::MalformedDocument(msg).into());
+            }
+        };
+        let doc_id = match self.domain_key.clone() {
+            Some(domain_key) => self.get_domain_id(&blob, &domain_key)?,
+            None => 0,
+        };
+        Ok(Some((doc_id, text)))
+    }
 }
 
 impl Iterator for JsonlReader {
-    type Item = (usize, Rc<String>);
-
-    fn next(&mut self) -> Option<(usize, Rc<String>)> {
-        let opt_line = self.buf_reader.next();
-        match opt_line {
-            Some(line) => {
-                let blob: Value = serde_json::from_str(line.unwrap().as_str()).unwrap();
-                let text = blob[self.text_key.as_str()].as_str().unwrap();
-                let text_rc = Rc::new(text.to_string());
-                let doc_id = match self.domain_key.as_ref() {
-                    // FIXME: the key is actually a string. remove this or make a hashmap
-                    Some(dkey) => blob[dkey].as_u64().unwrap(),
-                    None => 0,
-                };
-                Some((doc_id as usize, text_rc))
+    type Item = Result<(usize, Rc<String>)>;
+
+    fn next(&mut self) -> Option<Result<(usize, Rc<String>)>> {
+        loop {
+            let line = match self.buf_reader.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            if let Some(doc) = self.parse(&line).transpose() {
+                return Some(doc);
             }
-            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_nested_keys() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("train.jsonl.gz");
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        for line in [
+            r#"{"metadata": {"text": "hello world", "source": "web"}}"#,
+            r#"{"metadata": {"text": "fn main() {}", "source": "code"}}"#,
+            r#"{"metadata": {"text": "goodbye", "source": "web"}}"#,
+        ] {
+            writeln!(encoder, "{}", line).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let mut reader = JsonlReader::new(
+            &path,
+            "metadata.text".to_string(),
+            Some("metadata.source".to_string()),
+        )
+        .unwrap();
+        let docs: Vec<(usize, String)> = reader
+            .by_ref()
+            .map(|doc| doc.map(|(doc_id, text)| (doc_id, text.to_string())))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            docs,
+            vec![
+                (0, "hello world".to_string()),
+                (1, "fn main() {}".to_string()),
+                (0, "goodbye".to_string()),
+            ]
+        );
+        assert_eq!(reader.get_domain_names(), vec!["web", "code"]);
+
+        let reader = JsonlReader::new(&path, "metadata.text".to_string(), None).unwrap();
+        assert!(reader.map(|doc| doc.unwrap().0).all(|doc_id| doc_id == 0));
+    }
+
+    #[test]
+    fn test_malformed_documents() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("train.jsonl.gz");
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        for line in [r#"{"text": "hello"}"#, r#"{"body": "world"}"#, "{"] {
+            writeln!(encoder, "{}", line).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let reader = JsonlReader::new(&path, "text".to_string(), None).unwrap();
+        let docs: Vec<_> = reader.collect();
+        assert_eq!(docs.len(), 3);
+        assert_eq!(*docs[0].as_ref().unwrap().1, "hello");
+        for doc in &docs[1..] {
+            let err = doc.as_ref().unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<RustyDawgError>(),
+                Some(RustyDawgError::MalformedDocument(_))
+            ));
         }
     }
 }
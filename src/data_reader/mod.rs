@@ -1,12 +1,20 @@
+use anyhow::Result;
 use std::rc::Rc;
 
 mod buf_reader;
+pub mod doc_splitter;
+pub(crate) mod filter;
 mod jsonl_reader;
+mod multi_file;
 mod pile_reader;
 mod txt_reader;
 
-pub type DataReader = dyn Iterator<Item = (usize, Rc<String>)>;
+/// Documents with their IDs. Errors reading or parsing a document are yielded rather than panicking.
+pub type DataReader = dyn Iterator<Item = Result<(usize, Rc<String>)>>;
 
+pub use self::doc_splitter::{DocPiece, DocSplitter};
+pub use self::filter::DocumentFilter;
 pub use self::jsonl_reader::JsonlReader;
+pub use self::multi_file::{expand_train_path, FileDocs, FileDocsLog, MultiFileReader};
 pub use self::pile_reader::PileReader;
 pub use self::txt_reader::TxtReader;
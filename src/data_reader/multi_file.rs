@@ -0,0 +1,259 @@
+// Reading documents from several training files in turn, so that `--train-path` can be a file, a
+// directory, or a glob such as `data/*.jsonl.gz`.
+//
+// Files are read in sorted order, so that builds are deterministic. The range of documents read
+// from each file is recorded, and saved in the build manifest, so that document IDs and positions
+// can be traced back to the file they came from.
+
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::data_reader::DataReader;
+
+/// The documents read from one training file, as a range [start, end) of their positions in the
+/// order all documents were read. With `offset_ids`, these are also their IDs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileDocs {
+    pub path: String,
+    pub docs: (usize, usize),
+}
+
+pub type FileDocsLog = Rc<RefCell<Vec<FileDocs>>>;
+
+type OpenFile = dyn Fn(&Path) -> Result<Box<DataReader>>;
+
+/// The files that a training path stands for: the regular files in a directory (not recursively),
+/// the files matching a glob in its last component, or else the path itself. Sorted by path.
+pub fn expand_train_path(train_path: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(train_path);
+    let (dir, pattern) = if path.is_dir() {
+        (path, None)
+    } else {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        if !name.contains(['*', '?', '[']) {
+            return Ok(vec![path.to_path_buf()]);
+        }
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        (dir, Some(glob_to_regex(name)?))
+    };
+
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let matches = match pattern {
+            Some(ref pattern) => pattern.is_match(&entry.file_name().to_string_lossy()),
+            None => true,
+        };
+        if matches {
+            paths.push(entry.path());
+        }
+    }
+    if paths.is_empty() {
+        bail!("No training files found at {}", train_path);
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// Supports `*`, `?`, and character classes like `[a-c]` or `[!a-c]`.
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            '[' => {
+                pattern.push('[');
+                if chars.next_if_eq(&'!').is_some() {
+                    pattern.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        pattern.push('\\');
+                    }
+                    pattern.push(c);
+                }
+                pattern.push(']');
+            }
+            c => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    pattern.push('$');
+    Ok(Regex::new(&pattern)?)
+}
+
+/// Reads the documents of each file in turn, opening each with `open` once the last is finished.
+pub struct MultiFileReader {
+    paths: VecDeque<PathBuf>,
+    open: Box<OpenFile>,
+    current: Option<(PathBuf, Box<DataReader>)>,
+    offset_ids: bool,
+    file_start: usize, // Documents read before the current file.
+    n_docs: usize,
+    files: FileDocsLog,
+}
+
+impl MultiFileReader {
+    pub fn new<F>(paths: Vec<PathBuf>, open: F) -> Self
+    where
+        F: Fn(&Path) -> Result<Box<DataReader>> + 'static,
+    {
+        Self {
+            paths: paths.into(),
+            open: Box::new(open),
+            current: None,
+            offset_ids: false,
+            file_start: 0,
+            n_docs: 0,
+            files: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Add the number of documents in earlier files to each ID, for readers whose IDs count the
+    /// documents in a file. Other readers' IDs, like domains, are kept as they are.
+    pub fn with_offset_ids(mut self) -> Self {
+        self.offset_ids = true;
+        self
+    }
+
+    /// Record each file in `files` once it is finished, so all of them once the reader is exhausted.
+    pub fn with_log(mut self, files: FileDocsLog) -> Self {
+        files.borrow_mut().clear();
+        self.files = files;
+        self
+    }
+
+    fn finish_file(&mut self, path: PathBuf) {
+        self.files.borrow_mut().push(FileDocs {
+            path: path.to_string_lossy().into_owned(),
+            docs: (self.file_start, self.n_docs),
+        });
+        self.file_start = self.n_docs;
+    }
+}
+
+impl Iterator for MultiFileReader {
+    type Item = Result<(usize, Rc<String>)>;
+
+    fn next(&mut self) -> Option<Result<(usize, Rc<String>)>> {
+        loop {
+            if let Some((_, ref mut reader)) = self.current {
+                if let Some(item) = reader.next() {
+                    let (doc_id, doc) = match item {
+                        Ok(item) => item,
+                        Err(err) => return Some(Err(err)),
+                    };
+                    self.n_docs += 1;
+                    let doc_id = if self.offset_ids {
+                        self.file_start + doc_id
+                    } else {
+                        doc_id
+                    };
+                    return Some(Ok((doc_id, doc)));
+                }
+                let (path, _) = self.current.take().unwrap();
+                self.finish_file(path);
+            }
+            let path = self.paths.pop_front()?;
+            match (self.open)(&path) {
+                Ok(reader) => self.current = Some((path, reader)),
+                Err(err) => return Some(Err(err.context(format!("Could not open {:?}", path)))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_reader::TxtReader;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_expand_train_path() {
+        let dir = tempdir().unwrap();
+        for name in ["b.txt", "a.txt", "c.jsonl"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        fs::create_dir(dir.path().join("d.txt")).unwrap();
+
+        let names = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+        let dir_path = dir.path().to_str().unwrap();
+        assert_eq!(
+            names(expand_train_path(dir_path).unwrap()),
+            vec!["a.txt", "b.txt", "c.jsonl"]
+        );
+        let glob = format!("{}/*.txt", dir_path);
+        assert_eq!(
+            names(expand_train_path(&glob).unwrap()),
+            vec!["a.txt", "b.txt"]
+        );
+        let glob = format!("{}/[!a]*", dir_path);
+        assert_eq!(
+            names(expand_train_path(&glob).unwrap()),
+            vec!["b.txt", "c.jsonl"]
+        );
+        let file = format!("{}/a.txt", dir_path);
+        assert_eq!(names(expand_train_path(&file).unwrap()), vec!["a.txt"]);
+        assert!(expand_train_path(&format!("{}/*.gz", dir_path)).is_err());
+    }
+
+    #[test]
+    fn test_multi_file_reader() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "x|y").unwrap();
+        fs::write(dir.path().join("b.txt"), "").unwrap();
+        fs::write(dir.path().join("c.txt"), "z").unwrap();
+        let paths = expand_train_path(dir.path().to_str().unwrap()).unwrap();
+        let files = FileDocsLog::default();
+        let reader = MultiFileReader::new(paths, |path| {
+            let file = File::open(path)?;
+            let buf_size = file.metadata()?.len() as usize;
+            let reader = TxtReader::new(file, buf_size, Some("|".to_string()));
+            Ok(Box::new(reader) as Box<DataReader>)
+        })
+        .with_offset_ids()
+        .with_log(files.clone());
+
+        let docs: Vec<_> = reader
+            .map(|doc| doc.map(|(id, doc)| (id, doc.to_string())))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            docs,
+            vec![
+                (0, "x".to_string()),
+                (1, "y".to_string()),
+                (2, "z".to_string())
+            ]
+        );
+        let ranges: Vec<_> = files.borrow().iter().map(|file| file.docs).collect();
+        assert_eq!(ranges, vec![(0, 2), (2, 2), (2, 3)]);
+        assert!(files.borrow()[2].path.ends_with("c.txt"));
+    }
+}
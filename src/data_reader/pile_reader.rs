@@ -4,10 +4,13 @@ use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::data_reader::buf_reader::BufReader;
+use crate::data_reader::filter::{accepts_all, DocumentFilter};
+use crate::error::RustyDawgError;
 
 pub struct PileReader {
     buf_reader: BufReader,
     split_map: HashMap<String, usize>,
+    filters: Vec<DocumentFilter>,
 }
 
 fn get_pile_map() -> HashMap<String, usize> {
@@ -62,23 +65,48 @@ impl PileReader {
         Ok(Self {
             buf_reader,
             split_map,
+            filters: Vec::new(),
         })
     }
+
+    /// Skip documents that don't pass all of `filters`, e.g. `meta.pile_set_name=Github`.
+    pub fn with_filters(mut self, filters: Vec<DocumentFilter>) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    // None if the document is filtered out.
+    fn parse(&self, line: &str) -> Result<Option<(usize, Rc<String>)>> {
+        let value: serde_json::Value = serde_json::from_str(line)
+            .map_err(|err| RustyDawgError::MalformedDocument(err.to_string()))?;
+        if !accepts_all(&self.filters, &value) {
+            return Ok(None);
+        }
+        let blob: PileDocument = serde_json::from_value(value)
+            .map_err(|err| RustyDawgError::MalformedDocument(err.to_string()))?;
+        let doc_id = match self.split_map.get(&blob.meta.pile_set_name) {
+            Some(doc_id) => *doc_id,
+            None => {
+                let msg = format!("unknown pile_set_name {}", blob.meta.pile_set_name);
+                return Err(RustyDawgError::MalformedDocument(msg).into());
+            }
+        };
+        Ok(Some((doc_id, Rc::new(blob.text))))
+    }
 }
 
 impl Iterator for PileReader {
-    type Item = (usize, Rc<String>);
+    type Item = Result<(usize, Rc<String>)>;
 
-    fn next(&mut self) -> Option<(usize, Rc<String>)> {
-        let opt_line = self.buf_reader.next();
-        match opt_line {
-            Some(line) => {
-                let blob: PileDocument = serde_json::from_str(line.unwrap().as_str()).unwrap();
-                let doc_id = *self.split_map.get(&blob.meta.pile_set_name).unwrap();
-                let doc_text = Rc::new(blob.text);
-                Some((doc_id, doc_text))
+    fn next(&mut self) -> Option<Result<(usize, Rc<String>)>> {
+        loop {
+            let line = match self.buf_reader.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            if let Some(doc) = self.parse(&line).transpose() {
+                return Some(doc);
             }
-            None => None,
         }
     }
 }
@@ -28,7 +28,7 @@ impl TxtReader {
 
     // Returned value represents whether anything was read.
     pub fn refill_buffer(&mut self) -> Result<bool> {
-        let n_bytes_read = self.buf_reader.read(&mut self.buffer).unwrap();
+        let n_bytes_read = self.buf_reader.read(&mut self.buffer)?;
         if n_bytes_read == 0 {
             return Ok(false);
         }
@@ -49,16 +49,19 @@ impl TxtReader {
 }
 
 impl Iterator for TxtReader {
-    type Item = (usize, Rc<String>);
+    type Item = Result<(usize, Rc<String>)>;
 
-    fn next(&mut self) -> Option<(usize, Rc<String>)> {
-        if !self.docs.is_empty() || self.refill_buffer().unwrap() {
-            let doc = self.docs.pop_front().unwrap();
-            let counter = self.counter;
-            self.counter += 1;
-            Some((counter, doc.clone()))
-        } else {
-            None
+    fn next(&mut self) -> Option<Result<(usize, Rc<String>)>> {
+        if self.docs.is_empty() {
+            match self.refill_buffer() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => return Some(Err(err)),
+            }
         }
+        let doc = self.docs.pop_front()?;
+        let counter = self.counter;
+        self.counter += 1;
+        Some(Ok((counter, doc)))
     }
 }
@@ -0,0 +1,155 @@
+// Versioned binary save format for RAM DAWGs, written and read as a stream so that saving doesn't
+// need a second copy of the graph in memory, as serializing with bincode does.
+//
+// The file is a header followed by the node and edge sections (see graph::avl_graph::binary):
+//
+//   magic (8) | version (4) | label width (1) | index width (1) | node count (8) | edge count (8) |
+//   initial state (8) | max length (8, u64::MAX if none) | nodes... | edges...
+//
+// All integers are little-endian.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::mem::size_of;
+
+use super::Dawg;
+use crate::graph::avl_graph::binary::{read_uint, write_uint, BinaryLabel};
+use crate::graph::avl_graph::AvlGraph;
+use crate::graph::indexing::{DefaultIx, NodeIndex};
+use crate::weight::Weight;
+
+/// First bytes of every file in this format. Older bincode files never start with these.
+pub const MAGIC: &[u8; 8] = b"RDAWGBIN";
+
+/// Bumped whenever the layout of the header or records changes.
+pub const VERSION: u32 = 1;
+
+impl<E, W> Dawg<E, W>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + BinaryLabel + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+{
+    /// Write the DAWG in the binary format. The writer is buffered internally.
+    pub fn write_to(&self, writer: impl Write) -> Result<()> {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(MAGIC)?;
+        write_uint(&mut writer, VERSION as u64, 4)?;
+        write_uint(&mut writer, E::WIDTH as u64, 1)?;
+        write_uint(&mut writer, size_of::<DefaultIx>() as u64, 1)?;
+        write_uint(&mut writer, self.dawg.node_count() as u64, 8)?;
+        write_uint(&mut writer, self.dawg.edge_count() as u64, 8)?;
+        write_uint(&mut writer, self.initial.index() as u64, 8)?;
+        write_uint(&mut writer, self.max_length.unwrap_or(u64::MAX), 8)?;
+        self.dawg.write_nodes(&mut writer)?;
+        self.dawg.write_edges(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read a DAWG written by `write_to`. The reader is buffered internally.
+    pub fn read_from(reader: impl Read) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("Not a binary DAWG file");
+        }
+        let version = read_uint(&mut reader, 4)? as u32;
+        if version != VERSION {
+            bail!(
+                "Unsupported binary DAWG version {} (expected {})",
+                version,
+                VERSION
+            );
+        }
+        let label_width = read_uint(&mut reader, 1)? as usize;
+        if label_width != E::WIDTH {
+            bail!(
+                "DAWG has {}-byte edge labels, but the token type has {} bytes",
+                label_width,
+                E::WIDTH
+            );
+        }
+        let index_width = read_uint(&mut reader, 1)? as usize;
+        if index_width != size_of::<DefaultIx>() {
+            bail!("Unsupported index width of {} bytes", index_width);
+        }
+
+        let n_nodes = read_uint(&mut reader, 8)? as usize;
+        let n_edges = read_uint(&mut reader, 8)? as usize;
+        let initial = NodeIndex::new(read_uint(&mut reader, 8)? as usize);
+        let max_length = Some(read_uint(&mut reader, 8)?).filter(|&length| length != u64::MAX);
+        let dawg = AvlGraph::read_sections(&mut reader, n_nodes, n_edges)?;
+        Ok(Self {
+            dawg,
+            initial,
+            max_length,
+            window: VecDeque::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::load::Load;
+    use crate::io::Save;
+    use crate::memory_backing::CacheConfig;
+    use crate::weight::DefaultWeight;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_read() {
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        dawg.build(&['a', 'b', 'c', 'a', 'b']);
+        let mut bytes = Vec::new();
+        dawg.write_to(&mut bytes).unwrap();
+        assert_eq!(&bytes[..8], MAGIC);
+
+        let read: Dawg<char, DefaultWeight> = Dawg::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(read.node_count(), dawg.node_count());
+        assert_eq!(read.get_initial(), dawg.get_initial());
+        assert!(dawg.get_graph().isomorphic_eq(read.get_graph()));
+        assert_eq!(read.get_max_factor_length("zcab".chars().collect()), 3);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+        dawg.build(&[0, 1, 0]);
+        let mut bytes = Vec::new();
+        dawg.write_to(&mut bytes).unwrap();
+
+        // Wrong token type.
+        assert!(Dawg::<u32, DefaultWeight>::read_from(bytes.as_slice()).is_err());
+        // Truncated.
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(Dawg::<u16, DefaultWeight>::read_from(truncated).is_err());
+        // Unknown version.
+        bytes[8] = 99;
+        let err = Dawg::<u16, DefaultWeight>::read_from(bytes.as_slice()).err();
+        assert!(err.unwrap().to_string().contains("version 99"));
+    }
+
+    #[cfg(feature = "bincode-compat")]
+    #[test]
+    fn test_load_bincode_compat() {
+        let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+        dawg.build(&[0, 1, 2, 0, 1]);
+        let tmp_dir = tempdir().unwrap();
+        let binary_path = tmp_dir.path().join("binary.dawg");
+        let bincode_path = tmp_dir.path().join("bincode.dawg");
+        dawg.save(binary_path.to_str().unwrap()).unwrap();
+        let file = std::fs::File::create(&bincode_path).unwrap();
+        bincode::serialize_into(file, &dawg).unwrap();
+
+        for path in [binary_path, bincode_path] {
+            let loaded: Dawg<u16, DefaultWeight> =
+                Load::load(path.to_str().unwrap(), CacheConfig::none()).unwrap();
+            assert!(dawg.get_graph().isomorphic_eq(loaded.get_graph()));
+        }
+    }
+}
@@ -0,0 +1,251 @@
+// Import external n-gram count tables into a DAWG.
+//
+// The imported graph is an n-gram trie with failure links pointing to the longest suffix of each
+// n-gram that is also in the table. This answers the same queries as a DAWG built from text
+// (transition, transition_and_count, get_count, ...) so downstream code only targets one
+// interface. Counts are stored in node weights.
+//
+// Two formats are supported:
+//   * TSV: one `<n-gram>\t<count>` per line, with tokens in the n-gram separated by spaces.
+//   * ARPA: the usual `\data\` / `\N-grams:` sections. ARPA files store log-probabilities rather
+//     than counts, so every listed n-gram gets a count of 1.
+
+use anyhow::{bail, Result};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+
+use crate::dawg::Dawg;
+use crate::graph::indexing::{DefaultIx, NodeIndex};
+use crate::memory_backing::MemoryBacking;
+use crate::serde::{Deserialize, Serialize};
+use crate::tokenize::Tokenize;
+use crate::weight::Weight;
+
+use crate::graph::avl_graph::node::{NodeMutRef, NodeRef};
+use crate::graph::EdgeRef;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NgramFormat {
+    Tsv,
+    Arpa,
+}
+
+impl FromStr for NgramFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "tsv" => Ok(NgramFormat::Tsv),
+            "arpa" => Ok(NgramFormat::Arpa),
+            _ => bail!("Unknown n-gram format: {}", s),
+        }
+    }
+}
+
+/// Read an n-gram count table as (n-gram, count) pairs. N-grams longer than `max_n` are skipped.
+pub fn read_ngram_counts<E>(
+    path: &str,
+    format: NgramFormat,
+    tokenizer: &mut dyn Tokenize<E>,
+    max_n: Option<usize>,
+) -> Result<Vec<(Vec<E>, usize)>>
+where
+    E: Eq + Serialize + Copy + Debug,
+{
+    let reader = BufReader::new(fs::File::open(path)?);
+    let lines = reader.lines().collect::<std::io::Result<Vec<_>>>()?;
+    let pairs = match format {
+        NgramFormat::Tsv => parse_tsv(&lines)?,
+        NgramFormat::Arpa => parse_arpa(&lines)?,
+    };
+
+    let mut counts = Vec::with_capacity(pairs.len());
+    for (text, count) in pairs {
+        tokenizer.try_build(text)?;
+        let ngram = tokenizer.try_tokenize(text)?;
+        if ngram.is_empty() || max_n.is_some_and(|n| ngram.len() > n) {
+            continue;
+        }
+        counts.push((ngram, count));
+    }
+    Ok(counts)
+}
+
+fn parse_tsv(lines: &[String]) -> Result<Vec<(&str, usize)>> {
+    let mut pairs = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.rsplit_once('\t') {
+            Some((ngram, count)) => pairs.push((ngram, count.trim().parse()?)),
+            None => bail!("Line {} is not of the form <n-gram>\\t<count>", idx + 1),
+        }
+    }
+    Ok(pairs)
+}
+
+fn parse_arpa(lines: &[String]) -> Result<Vec<(&str, usize)>> {
+    let mut pairs = Vec::new();
+    let mut in_ngrams = false;
+    for line in lines.iter() {
+        let line = line.trim();
+        if line.starts_with('\\') {
+            // Section headers look like \data\, \1-grams:, ..., \end\.
+            in_ngrams = line.ends_with("-grams:");
+            continue;
+        }
+        if !in_ngrams || line.is_empty() {
+            continue;
+        }
+        // Format: <log10 prob>\t<n-gram>[\t<log10 backoff>]
+        let mut fields = line.split('\t');
+        match (fields.next(), fields.next()) {
+            (Some(_), Some(ngram)) => pairs.push((ngram, 1)),
+            _ => bail!("Malformed ARPA line: {}", line),
+        }
+    }
+    Ok(pairs)
+}
+
+impl<E, W, Mb> Dawg<E, W, DefaultIx, Mb>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    /// Build a DAWG-compatible n-gram trie from (n-gram, count) pairs.
+    pub fn from_ngram_counts(mb: Mb, counts: &[(Vec<E>, usize)]) -> Self {
+        let mut dawg = Self::new_mb(mb, None);
+        let mut total = 0;
+        for (ngram, count) in counts.iter() {
+            dawg.add_ngram(ngram, *count);
+            if ngram.len() == 1 {
+                total += count;
+            }
+        }
+        // The empty string occurs once per unigram occurrence.
+        if total > 0 {
+            dawg.dawg.get_node_mut(dawg.initial).set_count(total);
+        }
+        dawg.compute_ngram_failures();
+        dawg
+    }
+
+    /// Add an n-gram to the trie, adding `count` to its current count.
+    pub fn add_ngram(&mut self, ngram: &[E], count: usize) -> NodeIndex {
+        let mut state = self.initial;
+        for (idx, token) in ngram.iter().enumerate() {
+            state = match self.transition(state, *token, false) {
+                Some(next_state) => next_state,
+                None => {
+                    let new = self.dawg.add_node(W::new(idx as u64 + 1, None, 0));
+                    self.dawg.add_balanced_edge(state, new, *token);
+                    new
+                }
+            };
+        }
+        let old_count = self.get_node(state).get_count();
+        self.dawg.get_node_mut(state).set_count(old_count + count);
+        state
+    }
+
+    // Point each trie node at the node for its longest proper suffix, as in Aho-Corasick.
+    fn compute_ngram_failures(&mut self) {
+        let mut queue: VecDeque<NodeIndex> = VecDeque::new();
+        queue.push_back(self.initial);
+        while let Some(state) = queue.pop_front() {
+            let edges: Vec<_> = self
+                .dawg
+                .edges(state)
+                .map(|edge| (edge.get_weight(), edge.get_target()))
+                .collect();
+            for (token, next_state) in edges {
+                let failure = if state == self.initial {
+                    self.initial
+                } else {
+                    let mut opt_fail = self.get_node(state).get_failure();
+                    loop {
+                        match opt_fail {
+                            Some(fail) => {
+                                if let Some(target) = self.transition(fail, token, false) {
+                                    break target;
+                                }
+                                opt_fail = self.get_node(fail).get_failure();
+                            }
+                            None => break self.initial,
+                        }
+                    }
+                };
+                self.dawg
+                    .get_node_mut(next_state)
+                    .set_failure(Some(failure));
+                queue.push_back(next_state);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::dawg::Dawg;
+    use crate::memory_backing::RamBacking;
+    use crate::tokenize::TokenIndex;
+    use crate::weight::DefaultWeight;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    type Mb = RamBacking<DefaultWeight, usize, DefaultIx>;
+
+    #[test]
+    fn test_from_ngram_counts() {
+        let counts = vec![
+            (vec![0], 3),
+            (vec![1], 2),
+            (vec![0, 1], 2),
+            (vec![1, 0], 1),
+            (vec![0, 1, 0], 1),
+        ];
+        let dawg: Dawg<usize, DefaultWeight> =
+            Dawg::from_ngram_counts(Mb::default(), counts.as_slice());
+        assert_eq!(dawg.get_node(dawg.get_initial()).get_count(), 5);
+
+        let q01 = dawg.transition(dawg.get_initial(), 0, false).unwrap();
+        let q01 = dawg.transition(q01, 1, false).unwrap();
+        assert_eq!(dawg.get_node(q01).get_count(), 2);
+
+        // [0, 1] + 1 matches [0, 1, 0]; a second 1 fails back to [0] and then matches [0, 1].
+        let (q, length) = dawg.transition_and_count(q01, 0, 2);
+        assert_eq!(length, 3);
+        let (q, length) = dawg.transition_and_count(q.unwrap(), 1, length);
+        assert_eq!(length, 2);
+        assert_eq!(q, Some(q01));
+    }
+
+    #[test]
+    fn test_read_ngram_counts() {
+        let mut tsv = NamedTempFile::new().unwrap();
+        writeln!(tsv, "the\t4\nthe cat\t2\nthe cat sat\t1").unwrap();
+        let mut arpa = NamedTempFile::new().unwrap();
+        writeln!(
+            arpa,
+            "\\data\\\nngram 1=2\nngram 2=1\n\n\\1-grams:\n-1.0\tthe\t-0.5\n-1.2\tcat\n\n\\2-grams:\n-0.3\tthe cat\n\n\\end\\"
+        )
+        .unwrap();
+
+        let mut index: TokenIndex<usize> = TokenIndex::new();
+        let path = tsv.path().to_str().unwrap();
+        let counts = read_ngram_counts(path, NgramFormat::Tsv, &mut index, Some(2)).unwrap();
+        assert_eq!(counts, vec![(vec![3], 4), (vec![3, 4], 2)]);
+
+        let path = arpa.path().to_str().unwrap();
+        let counts = read_ngram_counts(path, NgramFormat::Arpa, &mut index, None).unwrap();
+        assert_eq!(counts, vec![(vec![3], 1), (vec![4], 1), (vec![3, 4], 1)]);
+    }
+}
@@ -4,25 +4,40 @@
 // https://github.com/viking-sudo-rm/knn-transformers/blob/master/src/suffix_dfa_builder.py
 //
 
+pub mod binary; // Streaming binary save format for RAM DAWGs.
+pub mod import;
+mod no_failures;
+pub mod parallel; // Sharded builds on multiple threads.
+mod prune;
 mod serde;
+pub mod snapshot; // Consistent reads of a RAM DAWG while it is being built.
+pub mod transitions; // Sparse transition matrices for downstream ML.
+pub mod verify; // Invariant checks on a built DAWG, and repairing inconsistent counts.
 
 use crate::serde::{Deserialize, Serialize};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::cmp::max;
 use std::cmp::{Eq, Ord};
-use std::collections::LinkedList;
+use std::collections::{LinkedList, VecDeque};
 use std::fmt::Debug;
+use std::io::Write;
 use std::path::Path;
 
 use crate::graph::avl_graph::AvlGraph;
+use crate::graph::dot;
 use crate::graph::indexing::NodeIndex;
-use crate::weight::{DefaultWeight, Weight};
+use crate::weight::Weight;
 
 use crate::graph::indexing::{DefaultIx, IndexType};
-use crate::memory_backing::{CacheConfig, DiskBacking, MemoryBacking, RamBacking};
+use crate::memory_backing::{
+    CacheConfig, ColumnarBacking, DiskBacking, MemoryBacking, MmapBacking, RamBacking, TierPolicy,
+    TieredBacking,
+};
+use crate::metrics;
 use crate::serde::de::DeserializeOwned; // The global serde, not the submodule
 
 use crate::graph::avl_graph::node::{NodeMutRef, NodeRef};
+use crate::graph::EdgeRef;
 
 pub struct Dawg<E, W, Ix = DefaultIx, Mb = RamBacking<W, E, Ix>>
 where
@@ -32,6 +47,7 @@ where
     dawg: AvlGraph<W, E, Ix, Mb>,
     initial: NodeIndex<Ix>,
     max_length: Option<u64>,
+    window: VecDeque<E>, // With max_length, the last tokens of the document being built.
 }
 
 impl<E, W> Dawg<E, W>
@@ -43,6 +59,36 @@ where
         let mb: RamBacking<W, E, DefaultIx> = RamBacking::default();
         Self::new_mb(mb, None)
     }
+
+    /// Record the states and edges changed by each document, so the last one can be rolled back.
+    pub fn enable_rollback(&mut self) {
+        self.dawg.enable_journal();
+    }
+
+    /// Undo everything added since the last `end_document`, or since `enable_rollback` if no
+    /// document has ended. If called right after `end_document`, undoes that whole document.
+    /// Building can then restart from `(get_initial(), 0)`.
+    pub fn rollback_last_document(&mut self) -> Result<()> {
+        if !self.dawg.rollback() {
+            bail!("Rollback is not enabled; call enable_rollback before building");
+        }
+        Ok(())
+    }
+}
+
+impl<E, W> Clone for Dawg<E, W>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            dawg: self.dawg.clone(),
+            initial: self.initial,
+            max_length: self.max_length,
+            window: self.window.clone(),
+        }
+    }
 }
 
 impl<E, W> Default for Dawg<E, W>
@@ -69,6 +115,88 @@ where
             dawg,
             initial: NodeIndex::new(0), // FIXME: Assumes that the initial state was numbered as 0.
             max_length: None, // FIXME: Doesn't matter after building, but could load from config.
+            window: VecDeque::new(),
+        })
+    }
+
+    /// Reopen a disk DAWG so that its states can be changed in place, e.g. to repair counts. Call
+    /// `flush` to write the changes.
+    pub fn open_mut<P: AsRef<Path> + Clone + std::fmt::Debug>(
+        path: P,
+        cache_config: CacheConfig,
+    ) -> Result<Self> {
+        let (n_nodes, n_edges) = {
+            let graph: AvlGraph<W, E, DefaultIx, DiskBacking<W, E, DefaultIx>> =
+                AvlGraph::load(path.clone(), CacheConfig::none())?;
+            (graph.node_count(), graph.edge_count())
+        };
+        let dawg = AvlGraph::open_mut(path, n_nodes, n_edges, cache_config)?;
+        Ok(Self {
+            dawg,
+            initial: NodeIndex::new(0),
+            max_length: None,
+            window: VecDeque::new(),
+        })
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.dawg.flush()
+    }
+}
+
+impl<E, W> Dawg<E, W, DefaultIx, ColumnarBacking<W, E, DefaultIx>>
+where
+    E: Eq + Ord + Copy + Debug + Serialize + DeserializeOwned + Default,
+    W: Weight + Clone + Serialize + DeserializeOwned,
+{
+    pub fn load_columnar<P: AsRef<Path> + Clone + std::fmt::Debug>(
+        path: P,
+        cache_config: CacheConfig,
+    ) -> Result<Self> {
+        let dawg = AvlGraph::load_columnar(path, cache_config)?;
+        Ok(Self {
+            dawg,
+            initial: NodeIndex::new(0), // FIXME: Assumes that the initial state was numbered as 0.
+            max_length: None,
+            window: VecDeque::new(),
+        })
+    }
+}
+
+impl<E, W> Dawg<E, W, DefaultIx, MmapBacking<W, E, DefaultIx>>
+where
+    E: Eq + Ord + Copy + Debug + Serialize + DeserializeOwned + Default + 'static,
+    W: Weight + Copy + Clone + Serialize + DeserializeOwned + Default + 'static,
+{
+    /// Map a DAWG saved on disk into memory, read-only.
+    pub fn load_mmap<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let dawg = AvlGraph::load_mmap(path)?;
+        Ok(Self {
+            dawg,
+            initial: NodeIndex::new(0), // FIXME: Assumes that the initial state was numbered as 0.
+            max_length: None,
+            window: VecDeque::new(),
+        })
+    }
+}
+
+impl<E, W> Dawg<E, W, DefaultIx, TieredBacking<W, E, DefaultIx>>
+where
+    E: Eq + Ord + Copy + Debug + Serialize + DeserializeOwned + Default,
+    W: Weight + Copy + Clone + Serialize + DeserializeOwned + Default,
+{
+    /// Load a DAWG saved on disk, with the states selected by `policy` pinned in RAM.
+    pub fn load_tiered<P: AsRef<Path> + Clone + std::fmt::Debug>(
+        path: P,
+        policy: TierPolicy,
+        cache_config: CacheConfig,
+    ) -> Result<Self> {
+        let dawg = AvlGraph::load_tiered(path, policy, cache_config)?;
+        Ok(Self {
+            dawg,
+            initial: NodeIndex::new(0), // FIXME: Assumes that the initial state was numbered as 0.
+            max_length: None,
+            window: VecDeque::new(),
         })
     }
 }
@@ -88,6 +216,7 @@ where
             dawg,
             initial,
             max_length,
+            window: VecDeque::new(),
         }
     }
 
@@ -106,6 +235,7 @@ where
             dawg,
             initial,
             max_length,
+            window: VecDeque::new(),
         }
     }
 
@@ -117,20 +247,45 @@ where
         }
     }
 
-    pub fn extend(&mut self, token: E, mut last: NodeIndex, mut length: u64) -> (NodeIndex, u64) {
-        // If we hit maximum length, fail once, then extend (doesn't need to be recursive!)
-        if self.max_length.is_some() && (length == self.max_length.unwrap()) {
-            if let Some(phi) = self.get_node(last).get_failure() {
-                last = phi;
-                length = self.get_node(phi).get_length();
+    /// Like `build`, but calls `callback` with the number of tokens added every `every` tokens.
+    /// Stops early if the callback fails.
+    pub fn build_with_callback<F>(
+        &mut self,
+        text: &[E],
+        every: usize,
+        mut callback: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Self, usize) -> Result<()>,
+    {
+        let mut last = self.initial;
+        let mut length = 0;
+        for (idx, token) in text.iter().enumerate() {
+            (last, length) = self.extend(*token, last, length);
+            if every != 0 && (idx + 1) % every == 0 {
+                callback(self, idx + 1)?;
             }
         }
+        Ok(())
+    }
+
+    pub fn extend(&mut self, token: E, mut last: NodeIndex, mut length: u64) -> (NodeIndex, u64) {
+        if let Some(max_length) = self.max_length {
+            (last, length) = self.shorten_to_max_length(last, length, max_length);
+            self.window.push_back(token);
+        }
 
-        // With max length or multiple documents, the transition sometimes already exists.
-        let next_new = self.transition(last, token, false);
-        if let Some(next_q) = next_new {
-            self.dawg.get_node_mut(next_q).increment_count();
-            return (next_q, length + 1);
+        // With max length or multiple documents, the transition sometimes already exists. If it
+        // isn't solid, its target also holds longer strings that don't end here, so split off the
+        // ones that do.
+        if let Some(next_state) = self.transition(last, token, false) {
+            let next_state = if self.is_solid(last, next_state) {
+                next_state
+            } else {
+                self.split(last, next_state, token)
+            };
+            self.increment_counts(next_state);
+            return (next_state, length + 1);
         }
 
         let new = self
@@ -159,63 +314,116 @@ where
             None => self.dawg.get_node_mut(new).set_failure(Some(self.initial)),
 
             // Found a failure state to fail to.
-            Some(mut state) => {
+            Some(state) => {
                 let next_state = opt_next_state.unwrap();
-                if self.get_node(state).get_length() + 1 == self.get_node(next_state).get_length() {
+                if self.is_solid(state, next_state) {
                     // Fail to an existing state.
                     self.dawg.get_node_mut(new).set_failure(Some(next_state));
                 } else {
-                    let clone = self.dawg.add_node(W::split(
-                        &self.get_node(state).get_weight(),
-                        &self.get_node(next_state).get_weight(),
-                    ));
-                    // let edges: Vec<_> = self
-                    //     .dawg
-                    //     .edges(next_state)
-                    //     .map(|edge| (edge.get_target(), edge.get_weight()))
-                    //     .collect();
-                    // for (target, weight) in edges {
-                    //     self.dawg.add_balanced_edge(clone, target, weight);
-                    // }
-                    self.dawg.clone_edges(next_state, clone);
+                    let clone = self.split(state, next_state, token);
                     self.dawg.get_node_mut(new).set_failure(Some(clone));
-                    self.dawg.get_node_mut(next_state).set_failure(Some(clone));
-
-                    // Reroute edges along failure chain.
-                    let mut next_state_ = next_state;
-                    loop {
-                        if next_state_ == next_state {
-                            self.dawg.reroute_edge(state, clone, token);
-                        } else {
-                            self.dawg.add_balanced_edge(state, clone, token);
-                        }
-
-                        match self.get_node(state).get_failure() {
-                            None => break,
-                            Some(q) => {
-                                state = q;
-                            }
-                        }
-                        if let Some(value) = self.transition(state, token, false) {
-                            next_state_ = value;
-                            if next_state_ != next_state {
-                                break;
-                            }
-                        }
-                    }
                 }
             }
         }
 
-        // Increment counts of all suffixes along the failure path.
-        let mut opt_ptr = Some(new);
-        while opt_ptr.is_some() {
-            let ptr = opt_ptr.unwrap();
+        self.increment_counts(new);
+        (new, length + 1)
+    }
+
+    // If last is max_length long, move to the state of its suffix one token shorter, so that
+    // extending it stays within max_length. That suffix is the failure of last if it is the longest
+    // string of its state. Otherwise, it only occurs where last does, so walk it from the initial
+    // state and split it off from last.
+    fn shorten_to_max_length(
+        &mut self,
+        last: NodeIndex,
+        length: u64,
+        max_length: u64,
+    ) -> (NodeIndex, u64) {
+        // Building restarted from (last, length), e.g. at the start of a document.
+        while self.window.len() as u64 > length {
+            self.window.pop_front();
+        }
+        if length != max_length {
+            return (last, length);
+        }
+        let Some(failure) = self.get_node(last).get_failure() else {
+            return (last, length);
+        };
+        self.window.pop_front();
+        if self.window.len() as u64 != length - 1 {
+            // The window doesn't hold last, so fall back to its failure.
+            self.window.clear();
+            return (failure, self.get_node(failure).get_length());
+        }
+        if self.get_node(failure).get_length() + 1 == length {
+            return (failure, length - 1);
+        }
+        let suffix: Vec<E> = self.window.iter().copied().collect();
+        let mut state = self.initial;
+        for token in suffix {
+            let next_state = self.transition(state, token, false).unwrap();
+            state = if self.is_solid(state, next_state) {
+                next_state
+            } else {
+                self.split(state, next_state, token)
+            };
+        }
+        (state, length - 1)
+    }
+
+    // Whether the transition from state to next_state is solid, i.e., the longest string of
+    // next_state is the longest string of state followed by one token.
+    fn is_solid(&self, state: NodeIndex, next_state: NodeIndex) -> bool {
+        self.get_node(state).get_length() + 1 == self.get_node(next_state).get_length()
+    }
+
+    // Split the strings of next_state up to the longest string of state followed by token into a
+    // clone, and reroute the transitions on token from state and its failure path to it.
+    fn split(&mut self, mut state: NodeIndex, next_state: NodeIndex, token: E) -> NodeIndex {
+        let clone = self.dawg.add_node(W::split(
+            &self.get_node(state).get_weight(),
+            &self.get_node(next_state).get_weight(),
+        ));
+        self.dawg.clone_edges(next_state, clone);
+        // The clone occurs wherever next_state does, so it ends the same documents.
+        if self.get_node(next_state).is_terminal() {
+            self.dawg.get_node_mut(clone).set_terminal(true);
+        }
+        self.dawg.get_node_mut(next_state).set_failure(Some(clone));
+
+        // Reroute edges along failure chain.
+        let mut next_state_ = next_state;
+        loop {
+            if next_state_ == next_state {
+                self.dawg.reroute_edge(state, clone, token);
+            } else {
+                self.dawg.add_balanced_edge(state, clone, token);
+            }
+
+            match self.get_node(state).get_failure() {
+                None => break,
+                Some(q) => {
+                    state = q;
+                }
+            }
+            if let Some(value) = self.transition(state, token, false) {
+                next_state_ = value;
+                if next_state_ != next_state {
+                    break;
+                }
+            }
+        }
+        clone
+    }
+
+    // Increment counts of all suffixes along the failure path.
+    fn increment_counts(&mut self, state: NodeIndex) {
+        let mut opt_ptr = Some(state);
+        while let Some(ptr) = opt_ptr {
             self.dawg.get_node_mut(ptr).increment_count();
             opt_ptr = self.get_node(ptr).get_failure();
         }
-
-        (new, length + 1)
     }
 
     pub fn end_document(
@@ -224,6 +432,13 @@ where
         doc_id_token: E,
         doc_id: u64,
     ) -> (NodeIndex, u64) {
+        // Every state on the failure path of last contains a suffix of the document.
+        let mut opt_ptr = Some(last);
+        while let Some(ptr) = opt_ptr {
+            self.dawg.get_node_mut(ptr).set_terminal(true);
+            opt_ptr = self.get_node(ptr).get_failure();
+        }
+
         loop {
             match self.transition(last, doc_id_token, false) {
                 Some(doc_state) => {
@@ -237,9 +452,15 @@ where
                 }
             }
         }
+        self.dawg.checkpoint();
         (self.get_initial(), 0)
     }
 
+    /// Whether some string in the state is the suffix of a document.
+    pub fn is_terminal(&self, state: NodeIndex) -> bool {
+        self.get_node(state).is_terminal()
+    }
+
     // Set the lengths field to store min factor length instead of max factor length.
     pub fn recompute_lengths(&mut self) {
         self._zero_lengths(self.initial);
@@ -318,6 +539,12 @@ where
             return (next_state, length + 1);
         }
 
+        // Without stored failures, every failed transition would seem to start over from the
+        // initial state. Graphs that may lack them should use `try_transition_and_count`.
+        debug_assert!(
+            W::has_failures(),
+            "This DAWG was stored without failure links"
+        );
         let fail_state = self.get_node(state).get_failure();
         match fail_state {
             Some(q) => {
@@ -332,6 +559,7 @@ where
 
     // Return the length of the largest substring of query that appears in the corpus.
     pub fn get_max_factor_length(&self, query: Vec<E>) -> u64 {
+        let _timer = metrics::time_query();
         let mut opt_state;
         let mut state = self.initial;
         let mut length = 0;
@@ -344,7 +572,22 @@ where
         max_length
     }
 
-    // TODO: Can build full substring vector for query.
+    /// Length of the longest suffix that occurs in the corpus at each position of `query`, i.e.,
+    /// its matching statistics. The maximum is `get_max_factor_length`.
+    pub fn get_suffix_length_profile(&self, query: &[E]) -> Vec<u64> {
+        let _timer = metrics::time_query();
+        let mut state = self.initial;
+        let mut length = 0;
+        query
+            .iter()
+            .map(|token| {
+                let opt_state;
+                (opt_state, length) = self.transition_and_count(state, *token, length);
+                state = opt_state.unwrap();
+                length
+            })
+            .collect()
+    }
 
     pub fn get_node(&self, state: NodeIndex) -> Mb::NodeRef {
         self.dawg.get_node(state)
@@ -373,14 +616,43 @@ where
         max_ratio
     }
 
+    /// The underlying graph, whose layout may change. Prefer `walker()` for custom traversals.
     pub fn get_graph(&self) -> &AvlGraph<W, E, DefaultIx, Mb> {
         &self.dawg
     }
+
+    /// Render the DAWG in GraphViz's DOT language, labelling edges with `token_decoder`.
+    pub fn to_dot<Wr, D>(&self, writer: &mut Wr, token_decoder: D) -> Result<()>
+    where
+        Wr: Write,
+        D: Fn(E) -> String,
+    {
+        dot::write_header(writer)?;
+        for idx in 0..self.node_count() {
+            let state = NodeIndex::new(idx);
+            let length = self.get_node(state).get_length();
+            dot::write_state(writer, idx, length, self.get_node(state).get_count())?;
+        }
+        for idx in 0..self.node_count() {
+            let state = NodeIndex::new(idx);
+            for edge in self.dawg.edges(state) {
+                let label = token_decoder(edge.get_weight());
+                dot::write_edge(writer, idx, edge.get_target().index(), &label)?;
+            }
+            if let Some(failure) = self.get_node(state).get_failure() {
+                dot::write_failure(writer, idx, failure.index())?;
+            }
+        }
+        dot::write_footer(writer)
+    }
 }
 
-// pyo3 requires that types implement Send
-unsafe impl<Mb> Send for Dawg<u16, DefaultWeight, DefaultIx, Mb> where
-    Mb: MemoryBacking<DefaultWeight, u16, DefaultIx>
+// pyo3 requires that types implement Send. Sharded builds also send RAM DAWGs across threads.
+unsafe impl<E, W, Mb> Send for Dawg<E, W, DefaultIx, Mb>
+where
+    E: Send,
+    W: Send,
+    Mb: MemoryBacking<W, E, DefaultIx>,
 {
 }
 
@@ -388,6 +660,7 @@ unsafe impl<Mb> Send for Dawg<u16, DefaultWeight, DefaultIx, Mb> where
 #[allow(unused_imports)]
 mod tests {
     use super::*;
+    use crate::weight::DefaultWeight;
     use bincode::{deserialize_from, serialize_into};
     use std::convert::TryInto;
     use std::fs::File;
@@ -431,6 +704,11 @@ mod tests {
         assert_eq!(dawg.get_max_factor_length("ca".chars().collect()), 2);
         assert_eq!(dawg.get_max_factor_length("z".chars().collect()), 0);
         assert_eq!(dawg.get_max_factor_length("zzbcazz".chars().collect()), 3);
+        let query: Vec<char> = "zzbcazz".chars().collect();
+        assert_eq!(
+            dawg.get_suffix_length_profile(&query),
+            vec![0, 0, 1, 2, 3, 0, 0]
+        );
 
         assert_eq!(dawg.dawg.get_node(NodeIndex::new(0)).get_count(), 6);
         assert_eq!(dawg.dawg.get_node(NodeIndex::new(1)).get_count(), 2);
@@ -497,6 +775,125 @@ mod tests {
         assert_eq!(dawg.dawg.get_node(NodeIndex::new(4)).get_count(), 2);
     }
 
+    #[test]
+    fn test_build_with_callback() {
+        let chars: Vec<char> = "abcab".chars().collect();
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        let mut calls = Vec::new();
+        dawg.build_with_callback(&chars, 2, |dawg, n_tokens| {
+            calls.push((n_tokens, dawg.node_count()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(calls, vec![(2, 3), (4, 5)]);
+
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        let result = dawg.build_with_callback(&chars, 1, |_, _| anyhow::bail!("stop"));
+        assert!(result.is_err());
+        assert_eq!(dawg.node_count(), 2);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        dawg.build(&['a', 'b']);
+        let mut out = Vec::new();
+        dawg.to_dot(&mut out, |token| token.to_string()).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(dot.starts_with("digraph {"));
+        assert!(dot.contains("q0 -> q1 [label=\"a\"];"));
+        assert!(dot.contains("q1 -> q2 [label=\"b\"];"));
+        assert!(dot.contains("q0 -> q2 [label=\"b\"];"));
+        assert!(dot.contains("q2 -> q0 [style=dashed"));
+        assert!(dot.trim_end().ends_with('}'));
+    }
+
+    #[test]
+    fn test_build_abcab_columnar() {
+        let chars: Vec<char> = "abcab".chars().collect();
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        dawg.build(&chars);
+
+        let tmp_dir = tempdir().unwrap();
+        type Mb = ColumnarBacking<DefaultWeight, char, DefaultIx>;
+        let mb: Mb = ColumnarBacking::new(tmp_dir.path());
+        let mut columnar_dawg: Dawg<char, DefaultWeight, DefaultIx, Mb> = Dawg::new_mb(mb, None);
+        columnar_dawg.build(&chars);
+
+        assert_eq!(columnar_dawg.node_count(), dawg.node_count());
+        for idx in 0..dawg.node_count() {
+            let state = NodeIndex::new(idx);
+            let (node, columnar_node) = (dawg.get_node(state), columnar_dawg.get_node(state));
+            assert_eq!(columnar_node.get_length(), node.get_length());
+            let (node, columnar_node) = (dawg.get_node(state), columnar_dawg.get_node(state));
+            assert_eq!(columnar_node.get_count(), node.get_count());
+            let (node, columnar_node) = (dawg.get_node(state), columnar_dawg.get_node(state));
+            assert_eq!(columnar_node.get_failure(), node.get_failure());
+        }
+        let query: Vec<char> = "bcbab".chars().collect();
+        assert_eq!(
+            columnar_dawg.get_max_factor_length(query.clone()),
+            dawg.get_max_factor_length(query)
+        );
+    }
+
+    #[test]
+    fn test_load_tiered() {
+        let chars: Vec<char> = "abcabcaab".chars().collect();
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        dawg.build(&chars);
+
+        let tmp_dir = tempdir().unwrap();
+        type DiskMb = DiskBacking<DefaultWeight, char, DefaultIx>;
+        let mb: DiskMb = DiskBacking::new(tmp_dir.path());
+        let mut disk_dawg: Dawg<char, DefaultWeight, DefaultIx, DiskMb> = Dawg::new_mb(mb, None);
+        disk_dawg.build(&chars);
+        let n_nodes = disk_dawg.node_count();
+        drop(disk_dawg);
+
+        type Mb = TieredBacking<DefaultWeight, char, DefaultIx>;
+        for policy in [TierPolicy::MinCount(3), TierPolicy::MaxDepth(1)] {
+            let tiered: Dawg<char, DefaultWeight, DefaultIx, Mb> =
+                Dawg::load_tiered(tmp_dir.path(), policy, CacheConfig::none()).unwrap();
+            let (n_pinned, _) = tiered.get_graph().n_pinned();
+            assert!(n_pinned > 0 && n_pinned < n_nodes);
+            for query in ["ab", "ca", "bcaab", "cc"] {
+                let query: Vec<char> = query.chars().collect();
+                assert_eq!(tiered.get_ngram_count(&query), dawg.get_ngram_count(&query));
+                assert_eq!(
+                    tiered.get_max_factor_length(query.clone()),
+                    dawg.get_max_factor_length(query)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_mmap() {
+        let chars: Vec<char> = "abcabcaab".chars().collect();
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        dawg.build(&chars);
+
+        let tmp_dir = tempdir().unwrap();
+        type DiskMb = DiskBacking<DefaultWeight, char, DefaultIx>;
+        let mb: DiskMb = DiskBacking::new(tmp_dir.path());
+        let mut disk_dawg: Dawg<char, DefaultWeight, DefaultIx, DiskMb> = Dawg::new_mb(mb, None);
+        disk_dawg.build(&chars);
+        drop(disk_dawg);
+
+        type Mb = MmapBacking<DefaultWeight, char, DefaultIx>;
+        let mapped: Dawg<char, DefaultWeight, DefaultIx, Mb> =
+            Dawg::load_mmap(tmp_dir.path()).unwrap();
+        for query in ["ab", "ca", "bcaab", "cc"] {
+            let query: Vec<char> = query.chars().collect();
+            assert_eq!(mapped.get_ngram_count(&query), dawg.get_ngram_count(&query));
+            assert_eq!(
+                mapped.get_max_factor_length(query.clone()),
+                dawg.get_max_factor_length(query)
+            );
+        }
+    }
+
     #[test]
     fn test_build_brown_ram_disk() {
         let corpus = "Communication
@@ -576,6 +973,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rollback_last_document() {
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        assert!(dawg.rollback_last_document().is_err());
+        dawg.enable_rollback();
+
+        let mut last = dawg.get_initial();
+        let mut length = 0;
+        for token in "abb".chars() {
+            (last, length) = dawg.extend(token, last, length);
+        }
+        (last, length) = dawg.end_document(last, '$', 0);
+        let (n_nodes, n_edges) = (dawg.node_count(), dawg.edge_count());
+
+        for token in "bac".chars() {
+            (last, length) = dawg.extend(token, last, length);
+        }
+        dawg.end_document(last, '$', 1);
+        dawg.rollback_last_document().unwrap();
+
+        let mut expected: Dawg<char, DefaultWeight> = Dawg::new();
+        let mut last = expected.get_initial();
+        let mut length = 0;
+        for token in "abb".chars() {
+            (last, length) = expected.extend(token, last, length);
+        }
+        expected.end_document(last, '$', 0);
+
+        assert_eq!((dawg.node_count(), dawg.edge_count()), (n_nodes, n_edges));
+        for ngram in ["a", "ab", "abb", "b", "bb", "ba", "c", "ac"] {
+            let ngram: Vec<_> = ngram.chars().collect();
+            assert_eq!(
+                dawg.get_ngram_count(&ngram),
+                expected.get_ngram_count(&ngram)
+            );
+        }
+        let query: Vec<_> = "bbac".chars().collect();
+        assert_eq!(
+            dawg.get_max_factor_length(query.clone()),
+            expected.get_max_factor_length(query)
+        );
+    }
+
     #[test]
     pub fn test_multiple_docs() {
         let docs: Vec<&str> = vec!["abb", "aca"];
@@ -607,6 +1047,11 @@ mod tests {
         let doc_abb = dawg.transition(q3_abb, doc_id_token, false).unwrap();
         assert_eq!(dawg.get_node(doc_abb).get_length(), 0); // Document ID 0
 
+        // Terminal states contain document suffixes.
+        assert!(dawg.is_terminal(q1));
+        assert!(!dawg.is_terminal(q2_abb));
+        assert!(dawg.is_terminal(q3_abb));
+
         // Branch of aca.
         let q2_aca = dawg.transition(q1, 'c', false).unwrap();
         assert_eq!(dawg.get_node(q2_aca).get_length(), 2);
@@ -623,4 +1068,62 @@ mod tests {
         assert_eq!(dawg.transition(q2_abb, 'a', false), None);
         assert_eq!(dawg.transition(q2_aca, 'b', false), None);
     }
+
+    // Build DAWGs on random documents over a small alphabet, and check their counts against
+    // brute-force n-gram counts.
+    fn check_counts_random_documents(max_length: Option<u64>) {
+        use crate::cdawg::sampling::XorShiftRng;
+        use std::collections::HashMap;
+
+        let mut rng = XorShiftRng::new(7);
+        for _ in 0..100 {
+            let docs: Vec<Vec<char>> = (0..1 + rng.next_u64() % 4)
+                .map(|_| {
+                    (0..rng.next_u64() % 12)
+                        .map(|_| ['a', 'b', 'c'][(rng.next_u64() % 3) as usize])
+                        .collect()
+                })
+                .collect();
+            let mut dawg: Dawg<char, DefaultWeight> =
+                Dawg::new_mb(RamBacking::default(), max_length);
+            for (doc_id, doc) in docs.iter().enumerate() {
+                let (mut last, mut length) = (dawg.get_initial(), 0);
+                for token in doc.iter() {
+                    (last, length) = dawg.extend(*token, last, length);
+                }
+                dawg.end_document(last, '$', doc_id as u64);
+            }
+            assert!(dawg.verify().is_ok(), "{:?}", docs);
+
+            let mut counts: HashMap<&[char], usize> = HashMap::new();
+            for doc in docs.iter() {
+                for n in 1..=max_length.map_or(doc.len(), |n| n as usize) {
+                    for ngram in doc.windows(n) {
+                        *counts.entry(ngram).or_insert(0) += 1;
+                    }
+                }
+            }
+            for (ngram, count) in counts {
+                assert_eq!(
+                    dawg.get_ngram_count(ngram),
+                    count,
+                    "{:?} in {:?}",
+                    ngram,
+                    docs
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_counts_random_documents() {
+        check_counts_random_documents(None);
+    }
+
+    #[test]
+    fn test_counts_random_documents_max_length() {
+        for max_length in [1, 2, 3, 5] {
+            check_counts_random_documents(Some(max_length));
+        }
+    }
 }
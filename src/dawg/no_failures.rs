@@ -0,0 +1,155 @@
+// Read-only DAWGs without failure links.
+//
+// When a graph is only used for exact lookups (e.g., counting n-grams), failure links are never
+// followed, so they can be dropped when the graph is copied into its final layout. Queries that
+// need failures are exposed through `try_*` methods that error if the layout doesn't store them.
+
+use anyhow::{bail, Result};
+use std::fmt::Debug;
+
+use crate::dawg::Dawg;
+use crate::graph::indexing::{DefaultIx, NodeIndex};
+use crate::graph::EdgeRef;
+use crate::memory_backing::MemoryBacking;
+use crate::serde::{Deserialize, Serialize};
+use crate::weight::Weight;
+
+use crate::graph::avl_graph::node::{NodeMutRef, NodeRef};
+
+impl<E, W, Mb> Dawg<E, W, DefaultIx, Mb>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    /// Copy into `mb` using a node weight that may drop failure links, e.g. `WeightNoFailure`.
+    /// Node indices are preserved.
+    pub fn without_failures<W2, Mb2>(&self, mb: Mb2) -> Dawg<E, W2, DefaultIx, Mb2>
+    where
+        W2: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb2: MemoryBacking<W2, E, DefaultIx>,
+        Mb2::EdgeRef: Copy,
+    {
+        let mut dawg = Dawg::new_mb(mb, self.max_length);
+        for idx in 0..self.node_count() {
+            let old_state = NodeIndex::new(idx);
+            // new_mb already added the initial state, which is numbered 0.
+            let state = if idx == self.initial.index() {
+                dawg.initial
+            } else {
+                dawg.dawg.add_node(W2::new(0, None, 0))
+            };
+            let node = self.get_node(old_state);
+            dawg.dawg.get_node_mut(state).set_length(node.get_length());
+            let node = self.get_node(old_state);
+            dawg.dawg.get_node_mut(state).set_count(node.get_count());
+            let node = self.get_node(old_state);
+            dawg.dawg
+                .get_node_mut(state)
+                .set_terminal(node.is_terminal());
+        }
+        for idx in 0..self.node_count() {
+            let state = NodeIndex::new(idx);
+            for edge in self.dawg.edges(state) {
+                dawg.dawg
+                    .add_balanced_edge(state, edge.get_target(), edge.get_weight());
+            }
+        }
+        dawg
+    }
+
+    /// Whether this graph stores failure links.
+    pub fn has_failures(&self) -> bool {
+        W::has_failures()
+    }
+
+    fn check_failures(&self) -> Result<()> {
+        if !self.has_failures() {
+            bail!(
+                "This DAWG was stored without failure links, so only exact lookups are supported"
+            );
+        }
+        Ok(())
+    }
+
+    /// Count the occurrences of `ngram` by exact lookup. Doesn't need failure links.
+    pub fn get_ngram_count(&self, ngram: &[E]) -> usize {
+        let mut state = self.initial;
+        for token in ngram.iter() {
+            match self.transition(state, *token, false) {
+                Some(next_state) => state = next_state,
+                None => return 0,
+            }
+        }
+        self.get_node(state).get_count()
+    }
+
+    /// Like `transition_and_count`, but errors if failure links aren't stored.
+    pub fn try_transition_and_count(
+        &self,
+        state: NodeIndex,
+        token: E,
+        length: u64,
+    ) -> Result<(Option<NodeIndex>, u64)> {
+        self.check_failures()?;
+        Ok(self.transition_and_count(state, token, length))
+    }
+
+    /// Like `get_suffix_length_profile`, but errors if failure links aren't stored.
+    pub fn try_get_suffix_length_profile(&self, query: &[E]) -> Result<Vec<u64>> {
+        self.check_failures()?;
+        Ok(self.get_suffix_length_profile(query))
+    }
+
+    /// Like `get_max_factor_length`, but errors if failure links aren't stored.
+    pub fn try_get_max_factor_length(&self, query: Vec<E>) -> Result<u64> {
+        self.check_failures()?;
+        Ok(self.get_max_factor_length(query))
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::memory_backing::RamBacking;
+    use crate::weight::{DefaultWeight, WeightNoFailure};
+
+    #[test]
+    fn test_without_failures() {
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        dawg.build(&"abcab".chars().collect::<Vec<_>>());
+        let compact: Dawg<char, WeightNoFailure> = dawg.without_failures(RamBacking::default());
+
+        assert_eq!(compact.node_count(), dawg.node_count());
+        assert_eq!(compact.edge_count(), dawg.edge_count());
+        assert!(!compact.has_failures());
+        for ngram in ["ab", "b", "abc", "ca", "ba"] {
+            let ngram: Vec<_> = ngram.chars().collect();
+            assert_eq!(
+                compact.get_ngram_count(&ngram),
+                dawg.get_ngram_count(&ngram)
+            );
+        }
+
+        let query: Vec<_> = "bcb".chars().collect();
+        assert_eq!(dawg.try_get_max_factor_length(query.clone()).unwrap(), 2);
+        assert_eq!(
+            dawg.try_get_suffix_length_profile(&query).unwrap(),
+            vec![1, 2, 1]
+        );
+        assert!(compact.try_get_suffix_length_profile(&query).is_err());
+        assert!(compact.try_get_max_factor_length(query).is_err());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "without failure links")]
+    fn test_unchecked_query_without_failures() {
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        dawg.build(&"abcab".chars().collect::<Vec<_>>());
+        let compact: Dawg<char, WeightNoFailure> = dawg.without_failures(RamBacking::default());
+        compact.get_max_factor_length("bcb".chars().collect());
+    }
+}
@@ -0,0 +1,415 @@
+// Parallel DAWG construction: documents are split across shards, each shard DAWG is built on its
+// own thread, and the shards are merged into one DAWG.
+//
+// Merging is a product construction. A state of the merged DAWG is reached by some string w, and
+// corresponds to the tuple of states that w reaches in each shard (or None where w doesn't occur).
+// Since the shards index disjoint sets of positions, two strings have the same end positions in
+// the union iff they reach the same tuple, so the reachable tuples are exactly the merged states.
+// The count of a state is the sum over its shards. Lengths and failures can't be read off the
+// shards, so they are recomputed from the merged graph: the length of a state is its longest path
+// from the initial state, and failures follow the usual suffix automaton rule along solid edges.
+//
+// Document ID nodes hang off the graph by edges labelled with `doc_id_token` (see
+// `Dawg::end_document`). They are copied from each shard rather than merged.
+//
+// Shards are built in RAM, so the corpus must fit in RAM across the shards. Building with a
+// maximum state length isn't supported.
+
+use anyhow::{anyhow, bail, Result};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Debug;
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::dawg::Dawg;
+use crate::graph::avl_graph::edge::EdgeRef;
+use crate::graph::avl_graph::node::{NodeMutRef, NodeRef};
+use crate::graph::indexing::{DefaultIx, NodeIndex};
+use crate::memory_backing::MemoryBacking;
+use crate::serde::{Deserialize, Serialize};
+use crate::weight::Weight;
+
+// A document ID and its tokens.
+type Document<E> = (u64, Vec<E>);
+
+/// Builds shard DAWGs on worker threads as documents are added, then merges them.
+pub struct ShardedBuilder<E, W>
+where
+    E: Copy,
+    W: Weight + Clone,
+{
+    senders: Vec<SyncSender<Document<E>>>,
+    workers: Vec<JoinHandle<Dawg<E, W>>>,
+    doc_id_token: E,
+    n_docs: usize,
+}
+
+impl<E, W> ShardedBuilder<E, W>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug + Send + 'static,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone + Send + 'static,
+{
+    pub fn new(n_threads: usize, doc_id_token: E) -> Result<Self> {
+        if n_threads == 0 {
+            bail!("Sharded builds need at least one thread");
+        }
+        let mut senders = Vec::with_capacity(n_threads);
+        let mut workers = Vec::with_capacity(n_threads);
+        for _ in 0..n_threads {
+            // Bounded, so memory use doesn't grow if reading outpaces the workers.
+            let (sender, receiver) = sync_channel::<Document<E>>(16);
+            senders.push(sender);
+            workers.push(thread::spawn(move || {
+                let mut dawg: Dawg<E, W> = Dawg::new();
+                for (doc_id, tokens) in receiver {
+                    let (mut last, mut length) = (dawg.get_initial(), 0);
+                    for token in tokens {
+                        (last, length) = dawg.extend(token, last, length);
+                    }
+                    dawg.end_document(last, doc_id_token, doc_id);
+                }
+                dawg
+            }));
+        }
+        Ok(Self {
+            senders,
+            workers,
+            doc_id_token,
+            n_docs: 0,
+        })
+    }
+
+    /// Add a document to the next shard, in round-robin order.
+    pub fn add_document(&mut self, doc_id: u64, tokens: Vec<E>) -> Result<()> {
+        let shard = self.n_docs % self.senders.len();
+        self.n_docs += 1;
+        self.senders[shard]
+            .send((doc_id, tokens))
+            .map_err(|_| anyhow!("Worker for shard {} exited early", shard))
+    }
+
+    /// Wait for the shards to finish, and merge them into a DAWG stored in `mb`.
+    pub fn finish<Mb>(self, mb: Mb) -> Result<Dawg<E, W, DefaultIx, Mb>>
+    where
+        Mb: MemoryBacking<W, E, DefaultIx>,
+        Mb::EdgeRef: Copy,
+    {
+        drop(self.senders);
+        let mut shards = Vec::with_capacity(self.workers.len());
+        for worker in self.workers {
+            let shard = worker
+                .join()
+                .map_err(|_| anyhow!("Worker panicked while building a shard"))?;
+            shards.push(shard);
+        }
+        Ok(merge(&shards, mb, Some(self.doc_id_token)))
+    }
+}
+
+/// Build a DAWG over `docs` with `n_threads` shards, where document i gets ID i.
+pub fn build_sharded<E, W, Mb>(
+    docs: &[Vec<E>],
+    n_threads: usize,
+    doc_id_token: E,
+    mb: Mb,
+) -> Result<Dawg<E, W, DefaultIx, Mb>>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug + Send + 'static,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone + Send + 'static,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    let mut builder = ShardedBuilder::new(n_threads, doc_id_token)?;
+    for (doc_id, doc) in docs.iter().enumerate() {
+        builder.add_document(doc_id as u64, doc.clone())?;
+    }
+    builder.finish(mb)
+}
+
+/// Merge DAWGs built over disjoint documents into one DAWG stored in `mb`. Edges labelled with
+/// `doc_id_token` are treated as document ID edges.
+pub fn merge<E, W, Mb>(
+    shards: &[Dawg<E, W>],
+    mb: Mb,
+    doc_id_token: Option<E>,
+) -> Dawg<E, W, DefaultIx, Mb>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    let mut merged: Dawg<E, W, DefaultIx, Mb> = Dawg::new_mb(mb, None);
+    let initial = merged.get_initial();
+    // Each shard counts the empty string once on top of its tokens.
+    let initial_count: usize = shards
+        .iter()
+        .map(|shard| shard.get_node(shard.get_initial()).get_count())
+        .sum();
+    let initial_count = initial_count + 1 - usize::max(shards.len(), 1);
+    merged.dawg.get_node_mut(initial).set_count(initial_count);
+
+    let initial_tuple: Vec<Option<NodeIndex>> = shards
+        .iter()
+        .map(|shard| Some(shard.get_initial()))
+        .collect();
+    let mut states: HashMap<Vec<Option<NodeIndex>>, NodeIndex> = HashMap::new();
+    states.insert(initial_tuple.clone(), initial);
+    let mut is_doc = vec![false];
+    let mut queue = VecDeque::from([(initial_tuple, initial)]);
+    while let Some((tuple, state)) = queue.pop_front() {
+        let terminal = shards
+            .iter()
+            .zip(tuple.iter())
+            .any(|(shard, q)| q.is_some_and(|q| shard.is_terminal(q)));
+        merged.dawg.get_node_mut(state).set_terminal(terminal);
+
+        // Group the transitions of all shards by token.
+        let mut targets: BTreeMap<E, Vec<Option<NodeIndex>>> = BTreeMap::new();
+        for (shard_idx, (shard, q)) in shards.iter().zip(tuple.iter()).enumerate() {
+            let Some(q) = q else { continue };
+            for edge in shard.get_graph().edges(*q) {
+                if Some(edge.get_weight()) == doc_id_token {
+                    continue;
+                }
+                let target = targets
+                    .entry(edge.get_weight())
+                    .or_insert_with(|| vec![None; shards.len()]);
+                target[shard_idx] = Some(edge.get_target());
+            }
+        }
+
+        for (token, target) in targets {
+            let next_state = match states.get(&target) {
+                Some(next_state) => *next_state,
+                None => {
+                    let count = shards
+                        .iter()
+                        .zip(target.iter())
+                        .map(|(shard, q)| q.map_or(0, |q| shard.get_node(q).get_count()))
+                        .sum();
+                    let next_state = merged.dawg.add_node(W::new(0, None, count));
+                    is_doc.push(false);
+                    states.insert(target.clone(), next_state);
+                    queue.push_back((target, next_state));
+                    next_state
+                }
+            };
+            merged.dawg.add_balanced_edge(state, next_state, token);
+        }
+
+        // Chain the document ID nodes of all shards after each other.
+        if let Some(doc_id_token) = doc_id_token {
+            let mut last = state;
+            for (shard, q) in shards.iter().zip(tuple.iter()) {
+                let mut doc = q.and_then(|q| shard.transition(q, doc_id_token, false));
+                while let Some(doc_state) = doc {
+                    let doc_id = shard.get_node(doc_state).get_length();
+                    let copy = merged.dawg.add_node(W::new(doc_id, None, 0));
+                    is_doc.push(true);
+                    merged.dawg.add_balanced_edge(last, copy, doc_id_token);
+                    last = copy;
+                    doc = shard.transition(doc_state, doc_id_token, false);
+                }
+            }
+        }
+    }
+
+    set_lengths_and_failures(&mut merged, &is_doc, doc_id_token);
+    merged
+}
+
+// Lengths are longest paths from the initial state, which are found in topological order. Then,
+// in order of length, each state fails to the target of its solid incoming edge's token from the
+// first state on the source's failure path that has that transition.
+fn set_lengths_and_failures<E, W, Mb>(
+    dawg: &mut Dawg<E, W, DefaultIx, Mb>,
+    is_doc: &[bool],
+    doc_id_token: Option<E>,
+) where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    let n_states = dawg.node_count();
+    let out_edges = |dawg: &Dawg<E, W, DefaultIx, Mb>, state: usize| -> Vec<(E, NodeIndex)> {
+        dawg.get_graph()
+            .edges(NodeIndex::new(state))
+            .filter(|edge| Some(edge.get_weight()) != doc_id_token)
+            .map(|edge| (edge.get_weight(), edge.get_target()))
+            .collect()
+    };
+
+    let mut in_degrees = vec![0; n_states];
+    for state in (0..n_states).filter(|state| !is_doc[*state]) {
+        for (_, target) in out_edges(dawg, state) {
+            in_degrees[target.index()] += 1;
+        }
+    }
+    let mut lengths = vec![0; n_states];
+    let mut solid_parents: Vec<Option<(NodeIndex, E)>> = vec![None; n_states];
+    let mut ready = vec![dawg.get_initial().index()];
+    while let Some(state) = ready.pop() {
+        for (token, target) in out_edges(dawg, state) {
+            let target = target.index();
+            if lengths[state] + 1 > lengths[target] {
+                lengths[target] = lengths[state] + 1;
+                solid_parents[target] = Some((NodeIndex::new(state), token));
+            }
+            in_degrees[target] -= 1;
+            if in_degrees[target] == 0 {
+                ready.push(target);
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..n_states).filter(|state| !is_doc[*state]).collect();
+    order.sort_by_key(|state| lengths[*state]);
+    for state in order {
+        dawg.dawg
+            .get_node_mut(NodeIndex::new(state))
+            .set_length(lengths[state]);
+        let Some((parent, token)) = solid_parents[state] else {
+            continue; // The initial state.
+        };
+        let mut failure = dawg.get_node(parent).get_failure();
+        let target = loop {
+            match failure {
+                // The transition can lead back to this state when it also holds shorter
+                // suffixes, in which case the failure is further up the chain.
+                Some(q) => match dawg.transition(q, token, false) {
+                    Some(target) if lengths[target.index()] < lengths[state] => break target,
+                    _ => failure = dawg.get_node(q).get_failure(),
+                },
+                None => break dawg.get_initial(),
+            }
+        };
+        dawg.dawg
+            .get_node_mut(NodeIndex::new(state))
+            .set_failure(Some(target));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_backing::RamBacking;
+    use crate::weight::DefaultWeight;
+
+    fn build_sequential(docs: &[Vec<char>], doc_id_token: char) -> Dawg<char, DefaultWeight> {
+        let mut dawg = Dawg::new();
+        for (doc_id, doc) in docs.iter().enumerate() {
+            let (mut last, mut length) = (dawg.get_initial(), 0);
+            for token in doc.iter() {
+                (last, length) = dawg.extend(*token, last, length);
+            }
+            dawg.end_document(last, doc_id_token, doc_id as u64);
+        }
+        dawg
+    }
+
+    fn count_occurrences(docs: &[Vec<char>], ngram: &[char]) -> usize {
+        docs.iter()
+            .map(|doc| doc.windows(ngram.len()).filter(|w| *w == ngram).count())
+            .sum()
+    }
+
+    fn max_factor_length(docs: &[Vec<char>], query: &[char]) -> u64 {
+        (1..=query.len())
+            .rev()
+            .find(|n| {
+                query
+                    .windows(*n)
+                    .any(|ngram| count_occurrences(docs, ngram) > 0)
+            })
+            .unwrap_or(0) as u64
+    }
+
+    #[test]
+    fn test_build_sharded_matches_sequential() {
+        let docs: Vec<Vec<char>> = ["abcab", "bcaab", "xab", "yab", "cccab", "b"]
+            .iter()
+            .map(|doc| doc.chars().collect())
+            .collect();
+        let queries: Vec<Vec<char>> = ["abcaab", "zzab", "yabca", "ccab", "abq"]
+            .iter()
+            .map(|query| query.chars().collect())
+            .collect();
+
+        let sequential = build_sequential(&docs, '$');
+        let n_tokens: usize = docs.iter().map(|doc| doc.len()).sum();
+        for n_threads in 1..=4 {
+            let sharded: Dawg<char, DefaultWeight> =
+                build_sharded(&docs, n_threads, '$', RamBacking::default()).unwrap();
+            assert!(sharded.verify().is_ok());
+            assert_eq!(
+                sharded.get_node(sharded.get_initial()).get_count(),
+                n_tokens + 1
+            );
+            for doc in docs.iter() {
+                for start in 0..doc.len() {
+                    for end in start + 1..=doc.len() {
+                        let ngram = &doc[start..end];
+                        assert_eq!(
+                            sharded.get_ngram_count(ngram),
+                            sequential.get_ngram_count(ngram),
+                            "{:?} with {} threads",
+                            ngram,
+                            n_threads
+                        );
+                        assert_eq!(
+                            sharded.get_ngram_count(ngram),
+                            count_occurrences(&docs, ngram)
+                        );
+                    }
+                }
+            }
+            for query in queries.iter() {
+                assert_eq!(
+                    sharded.get_max_factor_length(query.clone()),
+                    max_factor_length(&docs, query)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_lengths_and_failures() {
+        // "ab" reaches states with longest strings "xab" and "yab" in the two shards, but only
+        // "ab" itself occurs in both.
+        let shards = vec![
+            build_sequential(&["xab".chars().collect()], '$'),
+            build_sequential(&["yab".chars().collect()], '$'),
+        ];
+        let merged: Dawg<char, DefaultWeight> = merge(&shards, RamBacking::default(), Some('$'));
+        let ab = merged
+            .transition(merged.get_initial(), 'a', false)
+            .and_then(|q| merged.transition(q, 'b', false))
+            .unwrap();
+        assert_eq!(merged.get_node(ab).get_length(), 2);
+        assert_eq!(merged.get_node(ab).get_count(), 2);
+        // "b" is only ever preceded by "a", so it shares the state of "ab".
+        let b = merged.transition(merged.get_initial(), 'b', false).unwrap();
+        assert_eq!(b, ab);
+        assert_eq!(
+            merged.get_node(ab).get_failure(),
+            Some(merged.get_initial())
+        );
+        assert!(merged.is_terminal(ab));
+        assert!(merged.verify().is_ok());
+    }
+
+    #[test]
+    fn test_build_sharded_failure_to_shorter_state() {
+        let docs: Vec<Vec<char>> = ["xab", "yab"]
+            .iter()
+            .map(|doc| doc.chars().collect())
+            .collect();
+        let sharded: Dawg<char, DefaultWeight> =
+            build_sharded(&docs, 2, '$', RamBacking::default()).unwrap();
+        assert!(sharded.verify().is_ok());
+        let query: Vec<char> = "abq".chars().collect();
+        assert_eq!(sharded.get_max_factor_length(query), 2);
+    }
+}
@@ -0,0 +1,101 @@
+// Pruning a DAWG down to the substrings that occur at least `min_count` times.
+//
+// Counts never increase along a transition, and never decrease along a failure link, since a
+// state's failure represents suffixes of its strings. So the states that are kept are exactly those
+// reachable from the initial state through other kept states, and their failures are kept too. The
+// pruned graph is the subgraph on the kept states, with nodes renumbered and failures remapped.
+
+use std::fmt::Debug;
+
+use crate::dawg::Dawg;
+use crate::graph::indexing::{DefaultIx, NodeIndex};
+use crate::graph::EdgeRef;
+use crate::memory_backing::{MemoryBacking, RamBacking};
+use crate::serde::{Deserialize, Serialize};
+use crate::weight::Weight;
+
+use crate::graph::avl_graph::node::{NodeMutRef, NodeRef};
+
+impl<E, W, Mb> Dawg<E, W, DefaultIx, Mb>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    /// Copy the states with count at least `min_count` into a new DAWG in RAM. The initial state is
+    /// always kept.
+    pub fn prune_by_count(&self, min_count: usize) -> Dawg<E, W> {
+        let mut pruned: Dawg<E, W> = Dawg::new_mb(RamBacking::default(), self.max_length);
+        let new_states: Vec<Option<NodeIndex>> = (0..self.node_count())
+            .map(|idx| {
+                let state = NodeIndex::new(idx);
+                if state == self.initial {
+                    Some(pruned.initial)
+                } else if self.get_node(state).get_count() >= min_count {
+                    Some(pruned.dawg.add_node(W::new(0, None, 0)))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for (idx, new_state) in new_states.iter().enumerate() {
+            let Some(new_state) = *new_state else {
+                continue;
+            };
+            let state = NodeIndex::new(idx);
+            let node = self.dawg.get_node(state).get_weight();
+            let failure = node
+                .get_failure()
+                .and_then(|failure| new_states[failure.index()]);
+            pruned
+                .dawg
+                .get_node_mut(new_state)
+                .set_length(node.get_length());
+            pruned.dawg.get_node_mut(new_state).set_failure(failure);
+            pruned
+                .dawg
+                .get_node_mut(new_state)
+                .set_count(node.get_count());
+            pruned
+                .dawg
+                .get_node_mut(new_state)
+                .set_terminal(node.is_terminal());
+            for edge in self.dawg.edges(state) {
+                if let Some(new_target) = new_states[edge.get_target().index()] {
+                    pruned
+                        .dawg
+                        .add_balanced_edge(new_state, new_target, edge.get_weight());
+                }
+            }
+        }
+        pruned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weight::DefaultWeight;
+
+    #[test]
+    fn test_prune_by_count() {
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        dawg.build(&"abcabcaba".chars().collect::<Vec<_>>());
+        let pruned = dawg.prune_by_count(3);
+
+        assert!(pruned.node_count() < dawg.node_count());
+        for ngram in ["a", "b", "ab", "ba", "c"] {
+            let ngram: Vec<_> = ngram.chars().collect();
+            let count = dawg.get_ngram_count(&ngram);
+            let expected = if count >= 3 { count } else { 0 };
+            assert_eq!(pruned.get_ngram_count(&ngram), expected);
+        }
+
+        // Failure links still land on the longest frequent suffix.
+        let query: Vec<_> = "cab".chars().collect();
+        assert_eq!(pruned.get_max_factor_length(query), 2);
+        assert_eq!(dawg.prune_by_count(1).node_count(), dawg.node_count());
+    }
+}
@@ -8,6 +8,7 @@ use crate::graph::indexing::{IndexType, NodeIndex};
 use crate::memory_backing::MemoryBacking;
 use serde::de::{SeqAccess, Visitor};
 
+use std::collections::VecDeque;
 use std::marker::PhantomData;
 
 impl<E, W, Ix, Mb> Serialize for Dawg<E, W, Ix, Mb>
@@ -84,6 +85,7 @@ where
             dawg,
             initial,
             max_length,
+            window: VecDeque::new(),
         })
     }
 }
@@ -0,0 +1,200 @@
+// Reading a RAM DAWG while it is still being built.
+//
+// The builder owns the DAWG and mutates it in place, so readers never look at it directly. Instead,
+// the builder publishes immutable copies at document boundaries, and readers query the latest copy.
+//
+// Guarantees:
+//   * A snapshot is the DAWG exactly as it was after some call to `end_document`, so it never
+//     contains part of a document, and all counts in it are consistent with each other.
+//   * Snapshots are published in order: `epoch` and `n_docs` never decrease for a reader.
+//   * A snapshot stays valid for as long as a reader holds it, regardless of later publishes.
+//   * Snapshots lag the builder by up to `every` documents.
+// Publishing copies the whole graph, so `every` should be large enough to amortize that.
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use crate::dawg::Dawg;
+use crate::serde::{Deserialize, Serialize};
+use crate::weight::Weight;
+
+pub struct DawgSnapshot<E, W>
+where
+    E: Copy,
+    W: Weight + Clone,
+{
+    pub epoch: usize,  // Number of snapshots published before this one.
+    pub n_docs: usize, // Number of documents in the snapshot.
+    pub dawg: Arc<Dawg<E, W>>,
+}
+
+impl<E, W> Clone for DawgSnapshot<E, W>
+where
+    E: Copy,
+    W: Weight + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            epoch: self.epoch,
+            n_docs: self.n_docs,
+            dawg: self.dawg.clone(),
+        }
+    }
+}
+
+/// Held by the builder to publish snapshots.
+pub struct SnapshotPublisher<E, W>
+where
+    E: Copy,
+    W: Weight + Clone,
+{
+    latest: Arc<Mutex<DawgSnapshot<E, W>>>,
+    every: usize,
+    n_docs: usize,
+    epoch: usize,
+}
+
+/// Held by readers to get the latest snapshot. Can be cloned and sent to other threads.
+pub struct SnapshotReader<E, W>
+where
+    E: Copy,
+    W: Weight + Clone,
+{
+    latest: Arc<Mutex<DawgSnapshot<E, W>>>,
+}
+
+impl<E, W> Clone for SnapshotReader<E, W>
+where
+    E: Copy,
+    W: Weight + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            latest: self.latest.clone(),
+        }
+    }
+}
+
+/// Create a publisher and reader for `dawg`, which is published as epoch 0. Afterwards, a snapshot
+/// is published every `every` documents.
+pub fn snapshots<E, W>(
+    dawg: &Dawg<E, W>,
+    every: usize,
+) -> (SnapshotPublisher<E, W>, SnapshotReader<E, W>)
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+{
+    let latest = Arc::new(Mutex::new(DawgSnapshot {
+        epoch: 0,
+        n_docs: 0,
+        dawg: Arc::new(dawg.clone()),
+    }));
+    let publisher = SnapshotPublisher {
+        latest: latest.clone(),
+        every: usize::max(every, 1),
+        n_docs: 0,
+        epoch: 0,
+    };
+    (publisher, SnapshotReader { latest })
+}
+
+impl<E, W> SnapshotPublisher<E, W>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+{
+    /// Call right after `dawg.end_document`. Publishes a snapshot if one is due.
+    pub fn end_document(&mut self, dawg: &Dawg<E, W>) {
+        self.n_docs += 1;
+        if self.n_docs.is_multiple_of(self.every) {
+            self.publish(dawg);
+        }
+    }
+
+    /// Publish a snapshot now, e.g. when the build is finished. Must only be called at a document
+    /// boundary.
+    pub fn publish(&mut self, dawg: &Dawg<E, W>) {
+        // Copy before taking the lock, so readers aren't blocked during the copy.
+        let dawg = Arc::new(dawg.clone());
+        self.epoch += 1;
+        let mut latest = self.latest.lock().unwrap();
+        *latest = DawgSnapshot {
+            epoch: self.epoch,
+            n_docs: self.n_docs,
+            dawg,
+        };
+    }
+}
+
+impl<E, W> SnapshotReader<E, W>
+where
+    E: Copy,
+    W: Weight + Clone,
+{
+    pub fn latest(&self) -> DawgSnapshot<E, W> {
+        self.latest.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weight::DefaultWeight;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    fn build(docs: &[&str]) -> Dawg<char, DefaultWeight> {
+        let mut dawg = Dawg::new();
+        let (mut last, mut length) = (dawg.get_initial(), 0);
+        for (doc_id, doc) in docs.iter().enumerate() {
+            for token in doc.chars() {
+                (last, length) = dawg.extend(token, last, length);
+            }
+            (last, length) = dawg.end_document(last, '$', doc_id as u64);
+        }
+        dawg
+    }
+
+    #[test]
+    fn test_concurrent_reads() {
+        let docs = ["abab", "bab", "aab", "ba", "abba", "b"];
+        let ngram: Vec<char> = "ab".chars().collect();
+        let expected: Vec<usize> = (0..=docs.len())
+            .map(|n_docs| build(&docs[..n_docs]).get_ngram_count(&ngram))
+            .collect();
+
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        let (mut publisher, reader) = snapshots(&dawg, 2);
+        let done = Arc::new(AtomicBool::new(false));
+
+        let reader_done = done.clone();
+        let reader_expected = expected.clone();
+        let handle = thread::spawn(move || {
+            let mut last_epoch = 0;
+            loop {
+                let finished = reader_done.load(Ordering::SeqCst);
+                let snapshot = reader.latest();
+                assert!(snapshot.epoch >= last_epoch);
+                assert_eq!(snapshot.n_docs % 2, 0);
+                let count = snapshot.dawg.get_ngram_count(&ngram);
+                assert_eq!(count, reader_expected[snapshot.n_docs]);
+                last_epoch = snapshot.epoch;
+                if finished {
+                    return snapshot.n_docs;
+                }
+            }
+        });
+
+        let (mut last, mut length) = (dawg.get_initial(), 0);
+        for (doc_id, doc) in docs.iter().enumerate() {
+            for token in doc.chars() {
+                (last, length) = dawg.extend(token, last, length);
+            }
+            (last, length) = dawg.end_document(last, '$', doc_id as u64);
+            publisher.end_document(&dawg);
+        }
+        done.store(true, Ordering::SeqCst);
+        assert_eq!(handle.join().unwrap(), docs.len());
+    }
+}
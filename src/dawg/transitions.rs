@@ -0,0 +1,180 @@
+// Export of the transition structure of a DAWG as a sparse state x vocab matrix in CSR form, for
+// training neural approximations of the automaton.
+//
+// Rows are the `max_states` states with the highest counts, in decreasing order of count (ties by
+// state ID), and `states[row]` is the ID of the state in each row. Row r has its transitions in
+// `indptr[r]..indptr[r + 1]` of `indices` (tokens, sorted), `next_states` (the IDs of the states they
+// lead to, which may not have a row of their own) and `counts` (the counts of those states). So
+// `counts / state_counts[row]` is the next-token distribution of each row. Edges into the state for a
+// finished document are skipped, since the end-of-document token isn't part of the vocabulary.
+//
+// `save` writes each array as a .npy file of u64s, so that e.g.
+// `scipy.sparse.csr_matrix((next_states, indices, indptr), shape=shape)` loads them.
+
+use anyhow::Result;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt::Debug;
+use std::fs;
+use std::path::Path;
+
+use crate::dawg::Dawg;
+use crate::graph::indexing::{DefaultIx, NodeIndex};
+use crate::graph::EdgeRef;
+use crate::io::npy::NpyWriter;
+use crate::memory_backing::MemoryBacking;
+use crate::serde::{Deserialize, Serialize};
+use crate::tokenize::end::End;
+use crate::weight::Weight;
+
+use crate::graph::avl_graph::node::NodeRef;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransitionMatrix {
+    pub states: Vec<u64>,
+    pub state_counts: Vec<u64>,
+    pub indptr: Vec<u64>,
+    pub indices: Vec<u64>,
+    pub next_states: Vec<u64>,
+    pub counts: Vec<u64>,
+    pub vocab_size: usize,
+}
+
+impl TransitionMatrix {
+    pub fn shape(&self) -> (usize, usize) {
+        (self.states.len(), self.vocab_size)
+    }
+
+    /// Write each array to `<dir>/<name>.npy`, along with `shape.npy`. Creates `dir` if missing.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let (n_rows, n_cols) = self.shape();
+        let arrays: [(&str, &[u64]); 7] = [
+            ("states", &self.states),
+            ("state_counts", &self.state_counts),
+            ("indptr", &self.indptr),
+            ("indices", &self.indices),
+            ("next_states", &self.next_states),
+            ("counts", &self.counts),
+            ("shape", &[n_rows as u64, n_cols as u64]),
+        ];
+        for (name, values) in arrays {
+            let mut writer = NpyWriter::create(dir.join(name).with_extension("npy"))?;
+            writer.extend(values.iter().copied())?;
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+impl<E, W, Mb> Dawg<E, W, DefaultIx, Mb>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug + End + TryInto<u64>,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    /// The transitions of the `max_states` most frequent states. The vocab size is one more than the
+    /// largest token exported, unless `vocab_size` is given.
+    pub fn transition_matrix(
+        &self,
+        max_states: usize,
+        vocab_size: Option<usize>,
+    ) -> TransitionMatrix {
+        // Keep the most frequent states seen so far in a min-heap, so memory is O(max_states).
+        let mut heap = BinaryHeap::with_capacity(max_states + 1);
+        for idx in 0..self.node_count() {
+            let count = self.get_node(NodeIndex::new(idx)).get_count();
+            heap.push(Reverse((count, Reverse(idx))));
+            if heap.len() > max_states {
+                heap.pop();
+            }
+        }
+        // The heap isn't in order of count.
+        let mut top: Vec<_> = heap
+            .into_iter()
+            .map(|Reverse((count, Reverse(idx)))| (count, idx))
+            .collect();
+        top.sort_by_key(|&(count, idx)| (Reverse(count), idx));
+
+        let mut matrix = TransitionMatrix {
+            indptr: vec![0],
+            ..Default::default()
+        };
+        let mut max_token = None;
+        for (count, idx) in top {
+            let mut edges: Vec<_> = self
+                .dawg
+                .edges(NodeIndex::new(idx))
+                .filter(|edge| edge.get_weight() != E::end())
+                .map(|edge| (edge.get_weight(), edge.get_target()))
+                .collect();
+            edges.sort_by_key(|&(token, _)| token);
+            for (token, target) in edges {
+                let Ok(token) = token.try_into() else {
+                    continue;
+                };
+                max_token = max_token.max(Some(token));
+                matrix.indices.push(token);
+                matrix.next_states.push(target.index() as u64);
+                matrix.counts.push(self.get_node(target).get_count() as u64);
+            }
+            matrix.states.push(idx as u64);
+            matrix.state_counts.push(count as u64);
+            matrix.indptr.push(matrix.indices.len() as u64);
+        }
+        matrix.vocab_size =
+            vocab_size.unwrap_or_else(|| max_token.map_or(0, |token| token as usize + 1));
+        matrix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weight::DefaultWeight;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_transition_matrix() {
+        // "abab" over tokens a = 1, b = 2.
+        let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+        dawg.build(&[1, 2, 1, 2]);
+        let matrix = dawg.transition_matrix(3, None);
+        assert_eq!(matrix.shape(), (3, 3));
+
+        let initial = dawg.get_initial();
+        let a = dawg.transition(initial, 1, false).unwrap();
+        let b = dawg.transition(initial, 2, false).unwrap();
+        assert!(matrix.states.contains(&(a.index() as u64)));
+        assert!(matrix.states.contains(&(b.index() as u64)));
+        assert!(matrix.state_counts.windows(2).all(|w| w[0] >= w[1]));
+
+        // Every row lists its transitions in token order.
+        let row = matrix
+            .states
+            .iter()
+            .position(|&state| state == initial.index() as u64)
+            .unwrap();
+        let span = matrix.indptr[row] as usize..matrix.indptr[row + 1] as usize;
+        assert_eq!(matrix.indices[span.clone()], [1, 2]);
+        assert_eq!(
+            matrix.next_states[span.clone()],
+            [a.index() as u64, b.index() as u64]
+        );
+        assert_eq!(matrix.counts[span], [2, 2]);
+        assert_eq!(*matrix.indptr.last().unwrap(), matrix.indices.len() as u64);
+
+        assert_eq!(dawg.transition_matrix(1, Some(10)).shape(), (1, 10));
+        assert_eq!(
+            dawg.transition_matrix(100, None).states.len(),
+            dawg.node_count()
+        );
+
+        let dir = tempdir().unwrap();
+        matrix.save(dir.path()).unwrap();
+        let shape = fs::read(dir.path().join("shape.npy")).unwrap();
+        assert_eq!(shape.len(), 128 + 16);
+    }
+}
@@ -0,0 +1,330 @@
+// Checking the invariants of a built DAWG, for when a build with `max_length` or several documents
+// is suspected of leaving failures or counts inconsistent.
+//
+// Failures must lead to strictly shorter states, and transitions to strictly longer ones. Each
+// state has at most one transition per token, and looking the token up in its edge tree finds it.
+// Since the states that fail to a state occur at disjoint subsets of its end positions, a state's
+// count is at least the sum of their counts. Lengths are assumed to be the longest in each state,
+// as built, and not the shortest ones set by `recompute_lengths`.
+//
+// Only states reachable from the initial state are checked, since disk DAWGs are allocated with
+// more nodes than they use. States at the end of a document's marker path have no failure and a
+// count of zero, and are skipped. Other states without failures are reported.
+
+use anyhow::{bail, Result};
+use std::fmt;
+use std::fmt::Debug;
+
+use crate::dawg::Dawg;
+use crate::graph::indexing::{DefaultIx, NodeIndex};
+use crate::graph::EdgeRef;
+use crate::memory_backing::MemoryBacking;
+use crate::serde::{Deserialize, Serialize};
+use crate::weight::Weight;
+
+use crate::graph::avl_graph::node::{NodeMutRef, NodeRef};
+
+// Only the first violations are kept, but all of them are counted.
+const MAX_REPORTED: usize = 100;
+
+#[derive(Debug, PartialEq)]
+pub enum Violation<E> {
+    /// A state other than the initial state or a document marker has no failure.
+    MissingFailure { state: usize },
+    /// A failure or transition points past the last state.
+    OutOfRange { state: usize, target: usize },
+    /// A failure leads to a state that is at least as long.
+    FailureLength { state: usize, lengths: (u64, u64) },
+    /// A transition leads to a state that is no longer.
+    TransitionLength {
+        state: usize,
+        token: E,
+        lengths: (u64, u64),
+    },
+    /// A state has two transitions on `token`.
+    Duplicate { state: usize, token: E },
+    /// Looking up `token` in the state's edge tree doesn't find its transition.
+    Unreachable { state: usize, token: E },
+    /// A state's count is less than the sum of the counts of the states that fail to it.
+    Count {
+        state: usize,
+        count: usize,
+        child_count: usize,
+    },
+}
+
+impl<E: Debug> fmt::Display for Violation<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::MissingFailure { state } => write!(f, "state {} has no failure", state),
+            Violation::OutOfRange { state, target } => write!(
+                f,
+                "state {} points to state {}, which doesn't exist",
+                state, target
+            ),
+            Violation::FailureLength { state, lengths } => write!(
+                f,
+                "state {} has length {} but fails to a state of length {}",
+                state, lengths.0, lengths.1
+            ),
+            Violation::TransitionLength {
+                state,
+                token,
+                lengths,
+            } => write!(
+                f,
+                "state {} has length {} but its transition on {:?} has length {}",
+                state, lengths.0, token, lengths.1
+            ),
+            Violation::Duplicate { state, token } => {
+                write!(f, "state {} has several transitions on {:?}", state, token)
+            }
+            Violation::Unreachable { state, token } => write!(
+                f,
+                "state {} has a transition on {:?} that lookups don't find",
+                state, token
+            ),
+            Violation::Count {
+                state,
+                count,
+                child_count,
+            } => write!(
+                f,
+                "state {} has count {} but the states that fail to it have {}",
+                state, count, child_count
+            ),
+        }
+    }
+}
+
+pub struct VerifyReport<E> {
+    pub n_states: usize, // Reachable ones.
+    pub n_violations: usize,
+    pub n_count_violations: usize,
+    pub violations: Vec<Violation<E>>, // The first MAX_REPORTED.
+}
+
+impl<E> VerifyReport<E> {
+    pub fn is_ok(&self) -> bool {
+        self.n_violations == 0
+    }
+
+    /// Whether `repair_counts` can fix every violation.
+    pub fn is_repairable(&self) -> bool {
+        self.n_violations == self.n_count_violations
+    }
+
+    fn push(&mut self, violation: Violation<E>) {
+        if let Violation::Count { .. } = violation {
+            self.n_count_violations += 1;
+        }
+        self.n_violations += 1;
+        if self.violations.len() < MAX_REPORTED {
+            self.violations.push(violation);
+        }
+    }
+}
+
+impl<E, W, Mb> Dawg<E, W, DefaultIx, Mb>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    /// Check every state and transition against the invariants above.
+    pub fn verify(&self) -> VerifyReport<E> {
+        let n_states = self.node_count();
+        let reachable = self.reachable_states();
+        let mut report = VerifyReport {
+            n_states: reachable.iter().filter(|seen| **seen).count(),
+            n_violations: 0,
+            n_count_violations: 0,
+            violations: Vec::new(),
+        };
+        let mut child_counts = vec![0; n_states];
+        for idx in (0..n_states).filter(|idx| reachable[*idx]) {
+            let state = NodeIndex::new(idx);
+            let node = self.get_node(state).get_weight();
+            let length = node.get_length();
+            if W::has_failures() && state != self.initial {
+                match node.get_failure() {
+                    None if node.get_count() > 0 => {
+                        report.push(Violation::MissingFailure { state: idx })
+                    }
+                    None => {}
+                    Some(failure) if failure.index() >= n_states => {
+                        report.push(Violation::OutOfRange {
+                            state: idx,
+                            target: failure.index(),
+                        })
+                    }
+                    Some(failure) => {
+                        let failure_length =
+                            self.get_node(NodeIndex::new(failure.index())).get_length();
+                        if failure_length >= length {
+                            report.push(Violation::FailureLength {
+                                state: idx,
+                                lengths: (length, failure_length),
+                            });
+                        }
+                        child_counts[failure.index()] += node.get_count();
+                    }
+                }
+            }
+
+            let mut tokens = Vec::new();
+            for edge in self.dawg.edges(state) {
+                let (token, target) = (edge.get_weight(), edge.get_target());
+                tokens.push(token);
+                if target.index() >= n_states {
+                    report.push(Violation::OutOfRange {
+                        state: idx,
+                        target: target.index(),
+                    });
+                    continue;
+                }
+                if self.dawg.edge_target(state, token) != Some(target) {
+                    report.push(Violation::Unreachable { state: idx, token });
+                }
+                let target_length = self.get_node(target).get_length();
+                if !self.is_document_marker(target) && target_length <= length {
+                    report.push(Violation::TransitionLength {
+                        state: idx,
+                        token,
+                        lengths: (length, target_length),
+                    });
+                }
+            }
+            tokens.sort();
+            for pair in tokens.windows(2).filter(|pair| pair[0] == pair[1]) {
+                report.push(Violation::Duplicate {
+                    state: idx,
+                    token: pair[0],
+                });
+            }
+        }
+
+        if W::has_failures() {
+            for (idx, child_count) in child_counts.into_iter().enumerate() {
+                let count = self.get_node(NodeIndex::new(idx)).get_count();
+                if count < child_count {
+                    report.push(Violation::Count {
+                        state: idx,
+                        count,
+                        child_count,
+                    });
+                }
+            }
+        }
+        report
+    }
+
+    /// Raise each count that is less than the sum of the counts of the states that fail to it, from
+    /// the longest states down. Returns the number of counts changed. Fails if the failures are
+    /// inconsistent, since then there's no order to repair them in.
+    pub fn repair_counts(&mut self) -> Result<usize> {
+        let report = self.verify();
+        if !report.is_repairable() {
+            bail!(
+                "Can't repair counts with {} other violations",
+                report.n_violations - report.n_count_violations
+            );
+        }
+
+        let reachable = self.reachable_states();
+        let mut states: Vec<NodeIndex> = (0..self.node_count())
+            .filter(|idx| reachable[*idx])
+            .map(NodeIndex::new)
+            .collect();
+        states.sort_by_key(|state| std::cmp::Reverse(self.get_node(*state).get_length()));
+        let mut child_counts = vec![0; states.len()];
+        let mut n_repaired = 0;
+        for state in states {
+            let count = self.get_node(state).get_count();
+            let child_count = child_counts[state.index()];
+            if count < child_count {
+                self.dawg.get_node_mut(state).set_count(child_count);
+                n_repaired += 1;
+            }
+            if let Some(failure) = self.get_node(state).get_failure() {
+                child_counts[failure.index()] += count.max(child_count);
+            }
+        }
+        Ok(n_repaired)
+    }
+
+    // Depth-first search over transitions, ignoring targets past the last state.
+    fn reachable_states(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.node_count()];
+        reachable[self.initial.index()] = true;
+        let mut stack = vec![self.initial];
+        while let Some(state) = stack.pop() {
+            for target in self.dawg.neighbors(state) {
+                if target.index() < reachable.len() && !reachable[target.index()] {
+                    reachable[target.index()] = true;
+                    stack.push(target);
+                }
+            }
+        }
+        reachable
+    }
+
+    // The states that `end_document` adds, which have no failure and are never counted.
+    fn is_document_marker(&self, state: NodeIndex) -> bool {
+        let node = self.get_node(state).get_weight();
+        state != self.initial && node.get_failure().is_none() && node.get_count() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weight::DefaultWeight;
+
+    #[test]
+    fn test_verify_built() {
+        let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+        let (mut last, mut length) = (dawg.get_initial(), 0);
+        for token in [0, 1, 2, 0, 1, 1, 2, 0] {
+            (last, length) = dawg.extend(token, last, length);
+        }
+        dawg.end_document(last, u16::MAX, 0);
+        let report = dawg.verify();
+        assert!(report.is_ok(), "{}", report.violations[0]);
+        assert_eq!(report.n_states, dawg.node_count());
+    }
+
+    #[test]
+    fn test_verify_and_repair_counts() {
+        let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+        dawg.build(&[0, 1, 0, 0, 1]);
+        let state = dawg.transition(dawg.get_initial(), 0, false).unwrap();
+        dawg.dawg.get_node_mut(state).set_count(0);
+        dawg.dawg.get_node_mut(dawg.get_initial()).set_count(0);
+
+        let report = dawg.verify();
+        assert_eq!(report.n_violations, 2);
+        assert!(report.is_repairable());
+        assert_eq!(dawg.repair_counts().unwrap(), 2);
+        assert!(dawg.verify().is_ok());
+        assert_eq!(dawg.get_node(state).get_count(), 2);
+    }
+
+    #[test]
+    fn test_verify_failure_length() {
+        let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+        dawg.build(&[0, 1, 2]);
+        let state = dawg.transition(dawg.get_initial(), 2, false).unwrap();
+        let failure = dawg.get_node(state).get_failure().unwrap();
+        dawg.dawg.get_node_mut(failure).set_length(5);
+
+        let report = dawg.verify();
+        assert!(report.violations.contains(&Violation::FailureLength {
+            state: state.index(),
+            lengths: (3, 5),
+        }));
+        assert!(!report.is_repairable());
+        assert!(dawg.repair_counts().is_err());
+    }
+}
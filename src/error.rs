@@ -0,0 +1,64 @@
+// Errors caused by bad input, as opposed to broken invariants, which still panic. Library functions
+// return `anyhow::Result`, so these reach callers wrapped in an `anyhow::Error`, and can be told
+// apart with `err.downcast_ref::<RustyDawgError>()`. The Python bindings map them to exceptions.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RustyDawgError {
+    #[error("Invalid utype: {0} (expected auto, u16, u32, or usize)")]
+    InvalidTokenType(String),
+
+    #[error("utype {token_type} can only represent {n_ids} token IDs but the tokenizer has {vocab_size}")]
+    VocabTooLarge {
+        token_type: String,
+        n_ids: usize,
+        vocab_size: usize,
+    },
+
+    #[error(
+        "Token ID {0} doesn't fit in the token type, whose max value is reserved. Rebuild with a \
+         larger --utype, or --utype auto"
+    )]
+    TokenOverflow(u64),
+
+    #[error("Invalid token {0:?}: the null tokenizer expects integer token IDs")]
+    InvalidToken(String),
+
+    #[error("{0}")]
+    Tokenizer(String), // A pretrained tokenizer failed to load or encode.
+
+    #[error("Can't remap token {0} to the index's vocabulary: {1}")]
+    Unmappable(u64, String),
+
+    #[error("No such file or directory: {0:?}")]
+    MissingFile(PathBuf),
+
+    #[error("Malformed document: {0}")]
+    MalformedDocument(String),
+}
+
+/// Fail with `MissingFile` unless something exists at `path`.
+pub fn check_exists(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Err(RustyDawgError::MissingFile(path.to_path_buf()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downcast() {
+        let err = check_exists(Path::new("/no/such/index")).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RustyDawgError>(),
+            Some(RustyDawgError::MissingFile(_))
+        ));
+        assert!(check_exists(Path::new(".")).is_ok());
+    }
+}
@@ -0,0 +1,109 @@
+// When to run evaluations (DAWG) or compute statistics (CDAWG) during a build.
+//
+// Evaluating every fixed number of tokens makes early evaluations too frequent and late ones too
+// sparse relative to how the graph changes, so evaluations can also be spaced logarithmically in
+// the number of tokens, or by wall-clock time.
+
+use anyhow::{bail, Result};
+use std::time::{Duration, Instant};
+
+pub enum EvalSchedule {
+    Never,
+    Linear { every: usize },
+    // Thresholds are in increasing order, and next points at the first one not yet reached.
+    LogSpaced { thresholds: Vec<usize>, next: usize },
+    WallClock { period: Duration, last: Instant },
+}
+
+impl EvalSchedule {
+    /// Evaluate every `every` tokens, or never if `every` is 0.
+    pub fn linear(every: usize) -> Self {
+        if every == 0 {
+            return EvalSchedule::Never;
+        }
+        EvalSchedule::Linear { every }
+    }
+
+    /// About `n_evals` evaluations at token counts spaced geometrically between 1 and `n_tokens`.
+    pub fn log_spaced(n_tokens: usize, n_evals: usize) -> Self {
+        let mut thresholds: Vec<usize> = (1..=n_evals)
+            .map(|k| (n_tokens as f64).powf(k as f64 / n_evals as f64).round() as usize)
+            .collect();
+        thresholds.dedup();
+        EvalSchedule::LogSpaced {
+            thresholds,
+            next: 0,
+        }
+    }
+
+    /// Evaluate whenever at least `secs` seconds have passed since the last evaluation.
+    pub fn wall_clock(secs: f64) -> Result<Self> {
+        if secs <= 0. {
+            bail!("Evaluation period must be positive, got {}s", secs);
+        }
+        Ok(EvalSchedule::WallClock {
+            period: Duration::from_secs_f64(secs),
+            last: Instant::now(),
+        })
+    }
+
+    /// Whether to evaluate after `n_tokens` tokens have been added.
+    pub fn should_eval(&mut self, n_tokens: usize) -> bool {
+        match self {
+            EvalSchedule::Never => false,
+            EvalSchedule::Linear { every } => n_tokens != 0 && n_tokens.is_multiple_of(*every),
+            EvalSchedule::LogSpaced { thresholds, next } => {
+                if *next < thresholds.len() && n_tokens >= thresholds[*next] {
+                    while *next < thresholds.len() && n_tokens >= thresholds[*next] {
+                        *next += 1;
+                    }
+                    return true;
+                }
+                false
+            }
+            EvalSchedule::WallClock { period, last } => {
+                if last.elapsed() >= *period {
+                    *last = Instant::now();
+                    return true;
+                }
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_points(schedule: &mut EvalSchedule, n_tokens: usize) -> Vec<usize> {
+        (0..=n_tokens)
+            .filter(|idx| schedule.should_eval(*idx))
+            .collect()
+    }
+
+    #[test]
+    fn test_linear() {
+        assert_eq!(eval_points(&mut EvalSchedule::linear(4), 10), vec![4, 8]);
+        assert!(eval_points(&mut EvalSchedule::linear(0), 10).is_empty());
+    }
+
+    #[test]
+    fn test_log_spaced() {
+        let mut schedule = EvalSchedule::log_spaced(10000, 4);
+        assert_eq!(
+            eval_points(&mut schedule, 10000),
+            vec![10, 100, 1000, 10000]
+        );
+        // Duplicate thresholds for small counts are merged.
+        let mut schedule = EvalSchedule::log_spaced(4, 8);
+        assert_eq!(eval_points(&mut schedule, 4), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_wall_clock() {
+        assert!(EvalSchedule::wall_clock(0.).is_err());
+        let mut schedule = EvalSchedule::wall_clock(3600.).unwrap();
+        assert!(!schedule.should_eval(1));
+    }
+}
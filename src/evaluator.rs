@@ -1,16 +1,22 @@
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::cdawg::Cdawg;
 use crate::dawg::Dawg;
-use crate::graph::indexing::DefaultIx;
+use crate::graph::indexing::{DefaultIx, IndexType};
 use crate::memory_backing::MemoryBacking;
 use crate::stat_utils::get_entropy;
 use crate::weight::Weight;
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 use std::cmp::max;
 use std::cmp::Ord;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fmt::Debug;
 use std::fs;
 use std::io::Write;
 use std::marker::Copy;
+use std::str::FromStr;
 
 use crate::graph::avl_graph::node::NodeRef;
 
@@ -38,12 +44,31 @@ where
         self.metrics.get_mut(&key).expect("Unknown metric")
     }
 
-    pub fn to_json(&self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn to_json(&self, file_path: &str) -> anyhow::Result<()> {
         let json_data = serde_json::to_string(self)?;
         let mut file = fs::File::create(file_path)?;
         file.write_all(json_data.as_bytes())?;
         Ok(())
     }
+
+    /// Index and value of each metric at the latest evaluation, or None before the first one.
+    pub fn last_metrics(&self) -> Option<(usize, BTreeMap<String, f64>)> {
+        let idx = *self.indices.last()?;
+        let metrics = self
+            .metrics
+            .iter()
+            .map(|(key, values)| (key.clone(), values[values.len() - 1]))
+            .collect();
+        Some((idx, metrics))
+    }
+
+    /// Aggregate each metric over all the evaluations so far.
+    pub fn summarize(&self) -> BTreeMap<String, MetricSummary> {
+        self.metrics
+            .iter()
+            .filter_map(|(key, values)| Some((key.clone(), MetricSummary::new(values)?)))
+            .collect()
+    }
 }
 
 // TODO: Generic case
@@ -78,6 +103,51 @@ where
     where
         W: Weight + Serialize + for<'a> Deserialize<'a> + Clone,
         Mb: MemoryBacking<W, E, DefaultIx>,
+    {
+        let mut state = dawg.get_initial();
+        let mut length = 0;
+        let test = self.test;
+        let positions = test.iter().map(|token| {
+            let opt_state;
+            (opt_state, length) = dawg.transition_and_count(state, *token, length);
+            state = opt_state.unwrap();
+            let count = if state.index() != 0 {
+                dawg.get_node(state).get_count()
+            } else {
+                0
+            };
+            (length, count, get_entropy::<E, W, Mb>(dawg, state))
+        });
+        self.record(idx, dawg.node_count(), dawg.edge_count(), positions);
+    }
+
+    /// Like `evaluate`, but for a CDAWG, whose counts must be filled.
+    pub fn evaluate_cdawg<W, Ix, Mb>(&mut self, cdawg: &Cdawg<W, Ix, Mb, E>, idx: usize)
+    where
+        Ix: IndexType,
+        W: Weight + Serialize + for<'a> Deserialize<'a> + Clone,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        Mb::EdgeRef: Copy,
+        E: CdawgToken,
+    {
+        let mut cs = cdawg.get_initial();
+        let test = self.test;
+        let positions = test.iter().map(|token| {
+            cs = cdawg.transition_and_count(cs, *token);
+            let count = if cs.length > 0 {
+                cdawg.get_suffix_count(cs)
+            } else {
+                0
+            };
+            (cs.length, count, cdawg.get_entropy(cs))
+        });
+        self.record(idx, cdawg.node_count(), cdawg.edge_count(), positions);
+    }
+
+    // Record one evaluation from the suffix length, count and entropy at each test position.
+    fn record<I>(&mut self, idx: usize, n_nodes: usize, n_edges: usize, positions: I)
+    where
+        I: Iterator<Item = (u64, usize, f64)>,
     {
         let mut num_tokens = 0;
         let mut cum_length = 0;
@@ -85,20 +155,13 @@ where
         let mut cum_entropy = 0.;
         let mut max_length = 0;
 
-        let mut opt_state;
-        let mut state = dawg.get_initial();
-        let mut length = 0;
-
         for length in 0..self.max_length + 1 {
             self.get_mut(format!("length{}_count", length)).push(0.);
         }
         self.get_mut("length+_count".to_string()).push(0.);
         let it = self.metrics.get("length+_count").unwrap().len() - 1;
 
-        for token_ptr in self.test.iter() {
-            let token = *token_ptr;
-            (opt_state, length) = dawg.transition_and_count(state, token, length);
-            state = opt_state.unwrap();
+        for (length, count, entropy) in positions {
             cum_length += length;
             max_length = max(max_length, length);
             if length <= self.max_length {
@@ -106,19 +169,16 @@ where
             } else {
                 self.get_mut("length+_count".to_string())[it] += 1.;
             }
-            if state.index() != 0 {
-                cum_count += dawg.get_node(state).get_count();
-                // cum_count += counts[state.index()];
-            }
-            cum_entropy += get_entropy::<E, W, Mb>(dawg, state);
+            cum_count += count;
+            cum_entropy += entropy;
             num_tokens += 1;
         }
 
         self.indices.push(idx);
         self.get_mut("states_per_token".to_string())
-            .push((dawg.node_count() as f64) / (idx as f64));
+            .push((n_nodes as f64) / (idx as f64));
         self.get_mut("edges_per_token".to_string())
-            .push((dawg.edge_count() as f64) / (idx as f64));
+            .push((n_edges as f64) / (idx as f64));
         self.get_mut("suffix_lengths".to_string())
             .push((cum_length as f64) / (num_tokens as f64));
         self.get_mut("max_suffix_lengths".to_string())
@@ -130,15 +190,233 @@ where
     }
 }
 
+/// Evaluates on several named test sets at once, tracking separate metric curves for each.
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct MultiEvaluator<'a, E>
+where
+    E: Eq + serde::Serialize + Copy + Debug,
+{
+    evaluators: BTreeMap<String, Evaluator<'a, E>>,
+}
+
+impl<'a, E> MultiEvaluator<'a, E>
+where
+    E: Eq + Ord + serde::Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+{
+    pub fn new(tests: &'a [(String, Vec<E>)], max_length: u64) -> Self {
+        let mut evaluators = BTreeMap::new();
+        for (name, test) in tests.iter() {
+            evaluators.insert(name.clone(), Evaluator::new(test, max_length));
+        }
+        Self { evaluators }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.evaluators.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> &Evaluator<'a, E> {
+        self.evaluators.get(name).expect("Unknown test set")
+    }
+
+    pub fn evaluate<W, Mb>(&mut self, dawg: &Dawg<E, W, DefaultIx, Mb>, idx: usize)
+    where
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, E, DefaultIx>,
+    {
+        for evaluator in self.evaluators.values_mut() {
+            evaluator.evaluate(dawg, idx);
+        }
+    }
+
+    pub fn evaluate_cdawg<W, Ix, Mb>(&mut self, cdawg: &Cdawg<W, Ix, Mb, E>, idx: usize)
+    where
+        Ix: IndexType,
+        W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+        Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+        Mb::EdgeRef: Copy,
+        E: CdawgToken,
+    {
+        for evaluator in self.evaluators.values_mut() {
+            evaluator.evaluate_cdawg(cdawg, idx);
+        }
+    }
+
+    /// Write every metric curve. A single test set is written on its own, as older versions did.
+    pub fn to_json(&self, file_path: &str) -> anyhow::Result<()> {
+        let json_data = match self.evaluators.values().next() {
+            Some(evaluator) if self.evaluators.len() == 1 => serde_json::to_string(evaluator)?,
+            _ => serde_json::to_string(self)?,
+        };
+        let mut file = fs::File::create(file_path)?;
+        file.write_all(json_data.as_bytes())?;
+        Ok(())
+    }
+
+    /// Metrics of every test set at the latest evaluation, or None before the first one.
+    pub fn last_record(&self) -> Option<EvalRecord> {
+        let mut index = 0;
+        let mut test_sets = BTreeMap::new();
+        for (name, evaluator) in self.evaluators.iter() {
+            let (idx, metrics) = evaluator.last_metrics()?;
+            index = idx;
+            test_sets.insert(name.clone(), metrics);
+        }
+        Some(EvalRecord { index, test_sets })
+    }
+
+    pub fn summarize(&self) -> EvalSummary {
+        let n_evals = self
+            .evaluators
+            .values()
+            .next()
+            .map_or(0, |e| e.indices.len());
+        let test_sets = self
+            .evaluators
+            .iter()
+            .map(|(name, evaluator)| (name.clone(), evaluator.summarize()))
+            .collect();
+        EvalSummary { n_evals, test_sets }
+    }
+}
+
+/// Metrics of each test set at one evaluation, written as a line of the JSONL results.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvalRecord {
+    pub index: usize,
+    pub test_sets: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+/// Aggregates of one metric over all the evaluations in a build.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MetricSummary {
+    pub last: f64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl MetricSummary {
+    fn new(values: &[f64]) -> Option<Self> {
+        let last = *values.last()?;
+        Some(Self {
+            last,
+            mean: values.iter().sum::<f64>() / (values.len() as f64),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+}
+
+/// Summary of a build's evaluations, written by `ResultsWriter::finish`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvalSummary {
+    pub n_evals: usize,
+    pub test_sets: BTreeMap<String, BTreeMap<String, MetricSummary>>,
+}
+
+/// Format of the results file written during a build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResultsFormat {
+    /// Append a record per evaluation, and write a summary next to the results when finished.
+    #[default]
+    Jsonl,
+    /// Rewrite every metric curve at each evaluation, as older versions did.
+    Json,
+}
+
+impl fmt::Display for ResultsFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResultsFormat::Jsonl => write!(f, "jsonl"),
+            ResultsFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl FromStr for ResultsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "jsonl" => Ok(ResultsFormat::Jsonl),
+            "json" => Ok(ResultsFormat::Json),
+            _ => bail!("Invalid results format: {} (expected jsonl or json)", s),
+        }
+    }
+}
+
+/// Writes evaluation results as a build goes. In the JSONL format, each evaluation only appends a
+/// line, so writing doesn't slow down as the metric curves grow.
+pub struct ResultsWriter {
+    path: String,
+    format: ResultsFormat,
+    file: Option<fs::File>,
+}
+
+impl ResultsWriter {
+    /// Start a results file at `path`, replacing any results already there.
+    pub fn create(path: &str, format: ResultsFormat) -> Result<Self> {
+        let file = match format {
+            ResultsFormat::Jsonl => Some(fs::File::create(path)?),
+            ResultsFormat::Json => None,
+        };
+        Ok(Self {
+            path: path.to_string(),
+            format,
+            file,
+        })
+    }
+
+    /// Path the summary is written to in the JSONL format.
+    pub fn summary_path(path: &str) -> String {
+        format!("{}.summary.json", path)
+    }
+
+    /// Write the latest evaluation.
+    pub fn write<E>(&mut self, evaluator: &MultiEvaluator<E>) -> Result<()>
+    where
+        E: Eq + Ord + serde::Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    {
+        let Some(ref mut file) = self.file else {
+            return evaluator.to_json(&self.path);
+        };
+        if let Some(record) = evaluator.last_record() {
+            writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        }
+        Ok(())
+    }
+
+    /// Write the summary of all evaluations, in the JSONL format.
+    pub fn finish<E>(self, evaluator: &MultiEvaluator<E>) -> Result<()>
+    where
+        E: Eq + Ord + serde::Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    {
+        if self.format == ResultsFormat::Jsonl {
+            let json_data = serde_json::to_string(&evaluator.summarize())?;
+            fs::write(Self::summary_path(&self.path), json_data)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 #[allow(unused_imports)]
 mod tests {
+    use crate::cdawg::{Cdawg, TopologicalCounter};
     use crate::dawg::Dawg;
-    use crate::evaluator::Evaluator;
+    use crate::evaluator::{
+        EvalRecord, EvalSummary, Evaluator, MultiEvaluator, ResultsFormat, ResultsWriter,
+    };
     use crate::graph::indexing::DefaultIx;
     use crate::memory_backing::RamBacking;
     use crate::tokenize::{TokenIndex, Tokenize};
     use crate::weight::weight40::DefaultWeight;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+    use tempfile::tempdir;
 
     #[test]
     fn test_timeseries_short() {
@@ -195,4 +473,116 @@ mod tests {
         assert_eq!(*evaluator.get("suffix_lengths"), vec![1., 5. / 3.]);
         assert_eq!(*evaluator.get("suffix_counts"), vec![1., 4. / 3.]);
     }
+
+    #[test]
+    fn test_evaluate_cdawg() {
+        let (a, b, c) = (0, 1, 2);
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![a, b, b, u16::MAX])));
+        cdawg.build();
+        let mut counter = TopologicalCounter::new_ram();
+        counter.fill_counts(&mut cdawg);
+
+        let test = vec![a, b, c, b];
+        let mut evaluator: Evaluator<u16> = Evaluator::new(&test, 3);
+        evaluator.evaluate_cdawg(&cdawg, 4);
+        assert_eq!(*evaluator.get("suffix_lengths"), vec![4. / 4.]);
+        assert_eq!(*evaluator.get("max_suffix_lengths"), vec![2.]);
+        assert_eq!(*evaluator.get("length0_count"), vec![1.]);
+        assert_eq!(*evaluator.get("length1_count"), vec![2.]);
+        assert_eq!(*evaluator.get("length2_count"), vec![1.]);
+        // a and ab occur once, and b twice.
+        assert_eq!(*evaluator.get("suffix_counts"), vec![4. / 4.]);
+    }
+
+    #[test]
+    fn test_multiple_test_sets() {
+        let train_tokens = ["a", "b", "b"];
+        let test1_tokens = ["a", "b", "c"];
+        let test2_tokens = ["a", "a", "a"];
+
+        let mut index: TokenIndex<u16> = TokenIndex::new();
+        let train: Vec<_> = train_tokens.iter().map(|x| index.add(x)).collect();
+        let tests = vec![
+            (
+                "test1".to_string(),
+                test1_tokens.iter().map(|x| index.index(x)).collect(),
+            ),
+            (
+                "test2".to_string(),
+                test2_tokens.iter().map(|x| index.index(x)).collect(),
+            ),
+        ];
+
+        let mut evaluator: MultiEvaluator<u16> = MultiEvaluator::new(&tests, 3);
+        let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+        let mut last = dawg.get_initial();
+        let mut length = 0;
+        for (idx, token) in train.iter().enumerate() {
+            (last, length) = dawg.extend(*token, last, length);
+            evaluator.evaluate(&dawg, idx);
+        }
+        assert_eq!(
+            *evaluator.get("test1").get("suffix_lengths"),
+            vec![1. / 3., 1., 1.]
+        );
+        assert_eq!(
+            *evaluator.get("test2").get("suffix_lengths"),
+            vec![1., 1., 1.]
+        );
+    }
+
+    #[test]
+    fn test_results_writer() {
+        let train_tokens = ["a", "b", "b"];
+        let test_tokens = ["a", "b", "c"];
+
+        let mut index: TokenIndex<u16> = TokenIndex::new();
+        let train: Vec<_> = train_tokens.iter().map(|x| index.add(x)).collect();
+        let tests = vec![(
+            "test".to_string(),
+            test_tokens.iter().map(|x| index.index(x)).collect(),
+        )];
+
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("results.jsonl");
+        let path = path.to_str().unwrap();
+        let mut writer = ResultsWriter::create(path, ResultsFormat::Jsonl).unwrap();
+        let mut evaluator: MultiEvaluator<u16> = MultiEvaluator::new(&tests, 3);
+        let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+        let mut last = dawg.get_initial();
+        let mut length = 0;
+        for (idx, token) in train.iter().enumerate() {
+            (last, length) = dawg.extend(*token, last, length);
+            evaluator.evaluate(&dawg, idx + 1);
+            writer.write(&evaluator).unwrap();
+        }
+        writer.finish(&evaluator).unwrap();
+
+        let records: Vec<EvalRecord> = fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let indices: Vec<_> = records.iter().map(|record| record.index).collect();
+        assert_eq!(indices, vec![1, 2, 3]);
+        assert_eq!(records[0].test_sets["test"]["suffix_lengths"], 1. / 3.);
+        assert_eq!(records[2].test_sets["test"]["length2_count"], 1.);
+
+        let summary_path = ResultsWriter::summary_path(path);
+        let summary: EvalSummary =
+            serde_json::from_str(&fs::read_to_string(summary_path).unwrap()).unwrap();
+        assert_eq!(summary.n_evals, 3);
+        let suffix_lengths = summary.test_sets["test"]["suffix_lengths"];
+        assert_eq!(suffix_lengths.last, 1.);
+        assert_eq!(suffix_lengths.min, 1. / 3.);
+        assert_eq!(suffix_lengths.max, 1.);
+        assert!((suffix_lengths.mean - 7. / 9.).abs() < 1e-9);
+
+        // The old format rewrites every metric curve, and is unchanged for a single test set.
+        let mut writer = ResultsWriter::create(path, ResultsFormat::Json).unwrap();
+        writer.write(&evaluator).unwrap();
+        let blob: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(blob["indices"], serde_json::json!([1, 2, 3]));
+    }
 }
@@ -0,0 +1,326 @@
+// Read-only C API for querying a disk CDAWG from other languages, built with the `ffi` feature. The
+// declarations are in include/rusty_dawg.h, which is generated from this file by cbindgen (see
+// `make header`).
+//
+// An index is opened into an opaque `RustyDawgIndex` handle wrapping a `FrozenCdawg`, so it is
+// copied into RAM and can be queried from many threads at once. Queries take arrays of u16 token IDs,
+// and a query that doesn't occur has count 0. Opening fails by returning NULL, and a query on a NULL
+// index, or one that panics, returns 0. Either way, `rusty_dawg_last_error` describes why. Panics
+// are caught rather than unwinding into the caller.
+//
+// Every function is `unsafe`, since it trusts the caller's pointers and lengths.
+
+use anyhow::{anyhow, Result};
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::rc::Rc;
+use std::slice;
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::cdawg_state::CdawgState;
+use crate::cdawg::frozen::FrozenCdawg;
+use crate::cdawg::Cdawg;
+use crate::graph::indexing::DefaultIx;
+use crate::memory_backing::{CacheConfig, DiskBacking, DiskVec};
+use crate::weight::DefaultWeight;
+
+type Mb = DiskBacking<DefaultWeight, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A CDAWG opened for querying.
+pub struct RustyDawgIndex {
+    cdawg: FrozenCdawg,
+}
+
+/// Open the disk CDAWG at `index_path`, with the token vector it was built on at `tokens_path`, and
+/// copy it into RAM. `tokens_path` may be NULL if the CDAWG references shared tokens. Returns NULL
+/// on failure. The index must be closed with `rusty_dawg_close_index`.
+///
+/// # Safety
+///
+/// `index_path` must be a NUL-terminated string, and so must `tokens_path` unless it is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_dawg_open_index(
+    index_path: *const c_char,
+    tokens_path: *const c_char,
+) -> *mut RustyDawgIndex {
+    clear_last_error();
+    match panic::catch_unwind(|| open_index(index_path, tokens_path)) {
+        Ok(Ok(cdawg)) => Box::into_raw(Box::new(RustyDawgIndex { cdawg })),
+        Ok(Err(err)) => {
+            set_last_error(format!("{:#}", err));
+            ptr::null_mut()
+        }
+        Err(payload) => {
+            set_last_error(panic_message(payload));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free an index returned by `rusty_dawg_open_index`. Does nothing if `index` is NULL.
+///
+/// # Safety
+///
+/// `index` must have come from `rusty_dawg_open_index` and not have been closed already.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_dawg_close_index(index: *mut RustyDawgIndex) {
+    if !index.is_null() {
+        drop(Box::from_raw(index));
+    }
+}
+
+/// The reason the last call on this thread failed, or NULL if it succeeded. The string is owned by
+/// the library and stays valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn rusty_dawg_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.as_ptr())
+    })
+}
+
+/// The number of occurrences of the `n_tokens` tokens at `tokens` in the corpus. Returns 0 on
+/// failure.
+///
+/// # Safety
+///
+/// `index` must be an open index or NULL, and `tokens` must point to `n_tokens` tokens, or may be
+/// NULL if `n_tokens` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_dawg_count(
+    index: *const RustyDawgIndex,
+    tokens: *const u16,
+    n_tokens: usize,
+) -> u64 {
+    query(index, |index| {
+        index.cdawg.get_ngram_count(to_slice(tokens, n_tokens)) as u64
+    })
+}
+
+/// The length of the longest suffix of the query that occurs in the corpus. Returns 0 on failure.
+///
+/// # Safety
+///
+/// Same as `rusty_dawg_count`.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_dawg_suffix_len(
+    index: *const RustyDawgIndex,
+    tokens: *const u16,
+    n_tokens: usize,
+) -> u64 {
+    query(index, |index| {
+        index.match_suffix(to_slice(tokens, n_tokens)).length
+    })
+}
+
+/// The distribution over the token following the longest suffix of the query that occurs in the
+/// corpus. Writes up to `capacity` tokens and their probabilities to `out_tokens` and `out_probs`,
+/// most likely first, and returns the total number of next tokens, which may exceed `capacity`.
+/// Returns 0 on failure.
+///
+/// # Safety
+///
+/// Same as `rusty_dawg_count`. `out_tokens` and `out_probs` must each have room for `capacity`
+/// items, or may be NULL if `capacity` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_dawg_next_tokens(
+    index: *const RustyDawgIndex,
+    tokens: *const u16,
+    n_tokens: usize,
+    out_tokens: *mut u16,
+    out_probs: *mut f64,
+    capacity: usize,
+) -> usize {
+    query(index, |index| {
+        let cs = index.match_suffix(to_slice(tokens, n_tokens));
+        let mut next_tokens = index.cdawg.get_next_tokens(cs);
+        next_tokens.sort_by(|(token1, prob1), (token2, prob2)| {
+            prob2.total_cmp(prob1).then(token1.cmp(token2))
+        });
+        for (idx, (token, prob)) in next_tokens.iter().take(capacity).enumerate() {
+            *out_tokens.add(idx) = *token;
+            *out_probs.add(idx) = *prob;
+        }
+        next_tokens.len()
+    })
+}
+
+impl RustyDawgIndex {
+    // The state of the longest suffix of `query` that occurs, which is empty after a token that
+    // never occurs.
+    fn match_suffix(&self, query: &[u16]) -> CdawgState<DefaultIx> {
+        let mut cs = self.cdawg.get_initial();
+        for token in query.iter() {
+            cs = self.cdawg.transition_and_count(cs, *token);
+        }
+        match cs.target {
+            Some(_) => cs,
+            None => self.cdawg.get_initial(),
+        }
+    }
+}
+
+// Run `run` on `index`, or return 0 and set the last error if `index` is NULL or `run` panics. The
+// index is read-only, so a panic can't leave it half-changed.
+unsafe fn query<R: Default>(
+    index: *const RustyDawgIndex,
+    run: impl FnOnce(&RustyDawgIndex) -> R,
+) -> R {
+    clear_last_error();
+    if index.is_null() {
+        set_last_error("Index is NULL".to_string());
+        return R::default();
+    }
+    let index = &*index;
+    panic::catch_unwind(AssertUnwindSafe(|| run(index))).unwrap_or_else(|payload| {
+        set_last_error(panic_message(payload));
+        R::default()
+    })
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    };
+    format!("Panicked: {}", message)
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+unsafe fn open_index(index_path: *const c_char, tokens_path: *const c_char) -> Result<FrozenCdawg> {
+    let index_path = to_str(index_path)?;
+    let load = || -> Result<Cdawg<DefaultWeight, DefaultIx, Mb>> {
+        if tokens_path.is_null() {
+            return Cdawg::load_shared(index_path, CacheConfig::none());
+        }
+        let tokens: DiskVec<u16> = DiskVec::load(to_str(tokens_path)?)?;
+        Cdawg::load(
+            Rc::new(RefCell::new(tokens)),
+            index_path,
+            CacheConfig::none(),
+        )
+    };
+    let cdawg = load().map_err(|err| err.context(format!("Could not open {:?}", index_path)))?;
+    Ok(FrozenCdawg::new(&cdawg))
+}
+
+unsafe fn to_str<'a>(path: *const c_char) -> Result<&'a str> {
+    if path.is_null() {
+        return Err(anyhow!("Path is NULL"));
+    }
+    CStr::from_ptr(path)
+        .to_str()
+        .map_err(|_| anyhow!("Path {:?} isn't valid UTF-8", CStr::from_ptr(path)))
+}
+
+unsafe fn to_slice<'a>(tokens: *const u16, n_tokens: usize) -> &'a [u16] {
+    if n_tokens == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(tokens, n_tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use crate::io::Save;
+    use tempfile::tempdir;
+
+    fn c_path(path: &std::path::Path) -> CString {
+        CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_queries() {
+        let tokens = vec![0, 1, 2, 0, 1, u16::MAX, 1, 2, 3, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens.clone())));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        let tmp_dir = tempdir().unwrap();
+        let index_path = tmp_dir.path().join("cdawg");
+        let tokens_path = tmp_dir.path().join("tokens.vec");
+        cdawg.save(index_path.to_str().unwrap()).unwrap();
+        DiskVec::from_vec(&tokens, &tokens_path).unwrap();
+
+        unsafe {
+            let index =
+                rusty_dawg_open_index(c_path(&index_path).as_ptr(), c_path(&tokens_path).as_ptr());
+            assert!(!index.is_null());
+            assert_eq!(rusty_dawg_count(index, [0, 1].as_ptr(), 2), 2);
+            assert_eq!(rusty_dawg_count(index, [2, 2].as_ptr(), 2), 0);
+            let n_empty = cdawg.get_ngram_count(&[]) as u64;
+            assert_eq!(rusty_dawg_count(index, ptr::null(), 0), n_empty);
+            assert_eq!(rusty_dawg_suffix_len(index, [1, 2, 0].as_ptr(), 3), 3);
+            assert_eq!(rusty_dawg_suffix_len(index, [1, 2, 0, 3].as_ptr(), 4), 1);
+            assert_eq!(rusty_dawg_suffix_len(index, [4].as_ptr(), 1), 0);
+
+            // After [1, 2], 0 and 3 are equally likely, so they come in token order.
+            let (mut next, mut probs) = ([0u16; 1], [0f64; 1]);
+            let n_next = rusty_dawg_next_tokens(
+                index,
+                [1, 2].as_ptr(),
+                2,
+                next.as_mut_ptr(),
+                probs.as_mut_ptr(),
+                1,
+            );
+            assert_eq!(n_next, 2);
+            assert_eq!((next, probs), ([0], [0.5]));
+            let n_next =
+                rusty_dawg_next_tokens(index, [3].as_ptr(), 1, ptr::null_mut(), ptr::null_mut(), 0);
+            assert_eq!(n_next, 1);
+            rusty_dawg_close_index(index);
+
+            assert!(rusty_dawg_last_error().is_null());
+
+            let missing = tmp_dir.path().join("missing");
+            let index = rusty_dawg_open_index(c_path(&missing).as_ptr(), ptr::null());
+            assert!(index.is_null());
+            assert!(!rusty_dawg_last_error().is_null());
+
+            assert_eq!(rusty_dawg_count(ptr::null(), [0].as_ptr(), 1), 0);
+            let error = CStr::from_ptr(rusty_dawg_last_error());
+            assert_eq!(error.to_str().unwrap(), "Index is NULL");
+        }
+    }
+
+    #[test]
+    fn test_header_declares_functions() {
+        let header = include_str!("../include/rusty_dawg.h");
+        for name in [
+            "rusty_dawg_open_index",
+            "rusty_dawg_close_index",
+            "rusty_dawg_last_error",
+            "rusty_dawg_count",
+            "rusty_dawg_suffix_len",
+            "rusty_dawg_next_tokens",
+        ] {
+            assert!(
+                header.contains(&format!("{}(", name)),
+                "{} not in header",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,188 @@
+// Serde-free binary encoding of the nodes and edges of a graph in RAM.
+//
+// Every node and edge is a fixed-width little-endian record, so the sections are written and read
+// one item at a time through a buffered stream. Unlike bincode, nothing but the graph itself is held
+// in memory. Indices take as many bytes as the index type (5 for DefaultIx), and `end()` indices,
+// which also stand for missing failures, are written as they are.
+//
+// Node record: length (8), failure (index), count (8), terminal (1), first edge (index).
+// Edge record: label (BinaryLabel::WIDTH), target (index), left (index), right (index), balance (1).
+
+use anyhow::{bail, Result};
+use std::fmt::Debug;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+use super::{AvlGraph, Edge, EdgeRef, Node, NodeRef};
+use crate::graph::indexing::{EdgeIndex, IndexType, NodeIndex};
+use crate::memory_backing::{MemoryBacking, RamBacking, VecBacking, VecCacheConfig};
+use crate::weight::Weight;
+
+/// Edge labels that can be written as fixed-width little-endian integers.
+pub trait BinaryLabel: Copy {
+    /// Number of bytes each label takes.
+    const WIDTH: usize;
+
+    fn to_bits(self) -> u64;
+
+    /// None if `bits` isn't a valid label.
+    fn from_bits(bits: u64) -> Option<Self>;
+}
+
+macro_rules! binary_label_int {
+    ($ty:ty) => {
+        impl BinaryLabel for $ty {
+            const WIDTH: usize = size_of::<$ty>();
+
+            fn to_bits(self) -> u64 {
+                self as u64
+            }
+
+            fn from_bits(bits: u64) -> Option<Self> {
+                <$ty>::try_from(bits).ok()
+            }
+        }
+    };
+}
+
+binary_label_int!(u8);
+binary_label_int!(u16);
+binary_label_int!(u32);
+binary_label_int!(u64);
+binary_label_int!(usize);
+
+impl BinaryLabel for char {
+    const WIDTH: usize = 4;
+
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+
+    fn from_bits(bits: u64) -> Option<Self> {
+        char::from_u32(u32::try_from(bits).ok()?)
+    }
+}
+
+pub(crate) fn write_uint<Wr: Write>(writer: &mut Wr, value: u64, width: usize) -> Result<()> {
+    writer.write_all(&value.to_le_bytes()[..width])?;
+    Ok(())
+}
+
+pub(crate) fn read_uint<R: Read>(reader: &mut R, width: usize) -> Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes[..width])?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+impl<N, E, Ix> AvlGraph<N, E, Ix>
+where
+    E: BinaryLabel + Debug,
+    Ix: IndexType,
+    N: Weight + Clone,
+{
+    /// Write every node record, in index order.
+    pub fn write_nodes<Wr: Write>(&self, writer: &mut Wr) -> Result<()> {
+        let width = size_of::<Ix>();
+        let end = NodeIndex::<Ix>::end().index() as u64;
+        for idx in 0..self.nodes.len() {
+            let node = self.nodes.index(idx);
+            let failure = node.get_failure().map_or(end, |phi| phi.index() as u64);
+            write_uint(writer, node.get_length(), 8)?;
+            write_uint(writer, failure, width)?;
+            write_uint(writer, node.get_count() as u64, 8)?;
+            write_uint(writer, node.is_terminal() as u64, 1)?;
+            write_uint(writer, node.get_first_edge().index() as u64, width)?;
+        }
+        Ok(())
+    }
+
+    /// Write every edge record, in index order.
+    pub fn write_edges<Wr: Write>(&self, writer: &mut Wr) -> Result<()> {
+        let width = size_of::<Ix>();
+        for idx in 0..self.edges.len() {
+            let edge = self.edges.index(idx);
+            write_uint(writer, edge.get_weight().to_bits(), E::WIDTH)?;
+            write_uint(writer, edge.get_target().index() as u64, width)?;
+            write_uint(writer, edge.get_left().index() as u64, width)?;
+            write_uint(writer, edge.get_right().index() as u64, width)?;
+            write_uint(writer, edge.get_balance_factor() as u8 as u64, 1)?;
+        }
+        Ok(())
+    }
+
+    /// Read `n_nodes` node records followed by `n_edges` edge records.
+    pub fn read_sections<R: Read>(reader: &mut R, n_nodes: usize, n_edges: usize) -> Result<Self> {
+        let width = size_of::<Ix>();
+        let end = NodeIndex::<Ix>::end().index() as u64;
+        let mb: RamBacking<N, E, Ix> = RamBacking::default();
+        let mut nodes = mb.new_node_vec(Some(n_nodes), VecCacheConfig::default());
+        for _ in 0..n_nodes {
+            let length = read_uint(reader, 8)?;
+            let failure = read_uint(reader, width)?;
+            let count = read_uint(reader, 8)?;
+            let terminal = read_uint(reader, 1)? != 0;
+            let first_edge = read_uint(reader, width)?;
+            let failure = (failure != end).then(|| NodeIndex::new(failure as usize));
+            let mut weight = N::new(length, failure, count as usize);
+            weight.set_terminal(terminal);
+            nodes.push(Node {
+                weight,
+                first_edge: EdgeIndex::new(first_edge as usize),
+            });
+        }
+
+        let mut edges = mb.new_edge_vec(Some(n_edges), VecCacheConfig::default());
+        for _ in 0..n_edges {
+            let bits = read_uint(reader, E::WIDTH)?;
+            let Some(weight) = E::from_bits(bits) else {
+                bail!("Invalid edge label {}", bits);
+            };
+            edges.push(Edge {
+                weight,
+                target: NodeIndex::new(read_uint(reader, width)? as usize),
+                left: EdgeIndex::new(read_uint(reader, width)? as usize),
+                right: EdgeIndex::new(read_uint(reader, width)? as usize),
+                balance_factor: read_uint(reader, 1)? as u8 as i8,
+            });
+        }
+
+        Ok(AvlGraph {
+            nodes,
+            edges,
+            marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weight::DefaultWeight;
+
+    #[test]
+    fn test_sections_round_trip() {
+        type Graph = AvlGraph<DefaultWeight, u16>;
+        let mut graph = Graph::new();
+        let root = graph.add_node(DefaultWeight::new(0, None, 3));
+        for weight in [2, 0, 1] {
+            let leaf = graph.add_node(DefaultWeight::new(1, Some(NodeIndex::new(0)), 1));
+            graph.add_balanced_edge(root, leaf, weight);
+        }
+
+        let mut bytes = Vec::new();
+        graph.write_nodes(&mut bytes).unwrap();
+        graph.write_edges(&mut bytes).unwrap();
+        // Nodes take 8 + 5 + 8 + 1 + 5 bytes, and edges 2 + 5 * 3 + 1.
+        assert_eq!(bytes.len(), 4 * 27 + 3 * 18);
+
+        let read = Graph::read_sections(&mut bytes.as_slice(), 4, 3).unwrap();
+        assert!(graph.isomorphic_eq(&read));
+        assert_eq!(
+            read.get_node(root).get_first_edge(),
+            graph.get_node(root).get_first_edge()
+        );
+        assert_eq!(read.edge_target(root, 1), Some(NodeIndex::new(3)));
+        assert_eq!(read.get_node(root).get_failure(), None);
+    }
+}
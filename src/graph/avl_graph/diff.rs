@@ -0,0 +1,260 @@
+// Structural comparison of two graphs, e.g. the same corpus built in RAM and on disk.
+//
+// Node indices depend on the order that states were created in, and the shape of each AVL edge tree
+// on the order that edges were inserted in, so neither can be compared directly. Instead, both
+// graphs are walked breadth-first from their roots, visiting the edges of each state sorted by
+// weight, and the states reached at the same position are paired up. Failure links are compared
+// through this pairing once every reachable state has been paired.
+//
+// Only states reachable from the roots are compared. Node and edge counts can't be compared up
+// front, since a disk graph loaded from its files reports its preallocated capacity as its size.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use super::AvlGraph;
+use crate::graph::indexing::{IndexType, NodeIndex};
+use crate::graph::{EdgeRef, NodeRef};
+use crate::memory_backing::MemoryBacking;
+use crate::weight::Weight;
+
+/// The first difference found between two graphs. `path` is the edge weights from the root to the
+/// state where the graphs differ.
+#[derive(Debug, PartialEq)]
+pub enum Divergence<E> {
+    /// The states at `path` have different outgoing edge weights.
+    Edges {
+        path: Vec<E>,
+        weights: (Vec<E>, Vec<E>),
+    },
+    /// The edges at the end of `path` lead to states that were already paired with others.
+    Target { path: Vec<E> },
+    /// The states at `path` differ in `field`.
+    Node {
+        path: Vec<E>,
+        field: &'static str,
+        values: (String, String),
+    },
+}
+
+impl<E: fmt::Debug> fmt::Display for Divergence<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Edges { path, weights } => write!(
+                f,
+                "edges differ at {:?}: {:?} vs {:?}",
+                path, weights.0, weights.1
+            ),
+            Divergence::Target { path } => write!(f, "edge targets differ at {:?}", path),
+            Divergence::Node {
+                path,
+                field,
+                values,
+            } => write!(
+                f,
+                "{} differs at {:?}: {} vs {}",
+                field, path, values.0, values.1
+            ),
+        }
+    }
+}
+
+impl<N, E, Ix, Mb> AvlGraph<N, E, Ix, Mb>
+where
+    Mb: MemoryBacking<N, E, Ix>,
+    Mb::EdgeRef: Copy,
+    E: Copy + Ord + fmt::Debug,
+    N: Weight,
+    Ix: IndexType,
+{
+    /// Whether `other` has the same structure, weights, counts and failures as this graph, starting
+    /// from node 0 (the initial state of a DAWG, or the source of a CDAWG) in both.
+    pub fn isomorphic_eq<Mb2>(&self, other: &AvlGraph<N, E, Ix, Mb2>) -> bool
+    where
+        Mb2: MemoryBacking<N, E, Ix>,
+        Mb2::EdgeRef: Copy,
+    {
+        let root = NodeIndex::new(0);
+        self.first_divergence(root, other, root).is_none()
+    }
+
+    /// Compare with `other` by a canonical breadth-first search from `root` and `other_root`, and
+    /// return the first difference found, if any.
+    pub fn first_divergence<Mb2>(
+        &self,
+        root: NodeIndex<Ix>,
+        other: &AvlGraph<N, E, Ix, Mb2>,
+        other_root: NodeIndex<Ix>,
+    ) -> Option<Divergence<E>>
+    where
+        Mb2: MemoryBacking<N, E, Ix>,
+        Mb2::EdgeRef: Copy,
+    {
+        let mut pairs = HashMap::new();
+        let mut reverse_pairs = HashMap::new();
+        // For building paths, the parent of each state and the weight of the edge to it.
+        let mut parents: HashMap<NodeIndex<Ix>, (NodeIndex<Ix>, E)> = HashMap::new();
+        let path = |parents: &HashMap<_, _>, mut state| {
+            let mut path = Vec::new();
+            while let Some(&(parent, weight)) = parents.get(&state) {
+                path.push(weight);
+                state = parent;
+            }
+            path.reverse();
+            path
+        };
+
+        pairs.insert(root, other_root);
+        reverse_pairs.insert(other_root, root);
+        let mut order = Vec::new();
+        let mut queue = VecDeque::from([(root, other_root)]);
+        while let Some((a, b)) = queue.pop_front() {
+            order.push(a);
+            let (length_a, length_b) = (
+                self.get_node(a).get_length(),
+                other.get_node(b).get_length(),
+            );
+            let (count_a, count_b) = (self.get_node(a).get_count(), other.get_node(b).get_count());
+            let (terminal_a, terminal_b) = (
+                self.get_node(a).is_terminal(),
+                other.get_node(b).is_terminal(),
+            );
+            let fields = [
+                ("length", length_a as usize, length_b as usize),
+                ("count", count_a, count_b),
+                ("terminal", terminal_a as usize, terminal_b as usize),
+            ];
+            for (field, value_a, value_b) in fields {
+                if value_a != value_b {
+                    return Some(Divergence::Node {
+                        path: path(&parents, a),
+                        field,
+                        values: (value_a.to_string(), value_b.to_string()),
+                    });
+                }
+            }
+
+            let mut edges_a: Vec<_> = self
+                .edges(a)
+                .map(|edge| (edge.get_weight(), edge.get_target()))
+                .collect();
+            let mut edges_b: Vec<_> = other
+                .edges(b)
+                .map(|edge| (edge.get_weight(), edge.get_target()))
+                .collect();
+            edges_a.sort_by_key(|(weight, _)| *weight);
+            edges_b.sort_by_key(|(weight, _)| *weight);
+            let weights_a: Vec<_> = edges_a.iter().map(|(weight, _)| *weight).collect();
+            let weights_b: Vec<_> = edges_b.iter().map(|(weight, _)| *weight).collect();
+            if weights_a != weights_b {
+                return Some(Divergence::Edges {
+                    path: path(&parents, a),
+                    weights: (weights_a, weights_b),
+                });
+            }
+
+            for ((weight, target_a), (_, target_b)) in edges_a.into_iter().zip(edges_b) {
+                match (pairs.get(&target_a), reverse_pairs.get(&target_b)) {
+                    (None, None) => {
+                        pairs.insert(target_a, target_b);
+                        reverse_pairs.insert(target_b, target_a);
+                        parents.insert(target_a, (a, weight));
+                        queue.push_back((target_a, target_b));
+                    }
+                    (Some(&paired), _) if paired == target_b => {}
+                    _ => {
+                        let mut path = path(&parents, a);
+                        path.push(weight);
+                        return Some(Divergence::Target { path });
+                    }
+                }
+            }
+        }
+
+        for a in order {
+            let b = pairs[&a];
+            let failure_a = self.get_node(a).get_failure();
+            let failure_b = other.get_node(b).get_failure();
+            let paired = failure_a.map(|failure| pairs.get(&failure).copied());
+            if paired != failure_b.map(Some) {
+                return Some(Divergence::Node {
+                    path: path(&parents, a),
+                    field: "failure",
+                    values: (format!("{:?}", failure_a), format!("{:?}", failure_b)),
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dawg::Dawg;
+    use crate::graph::avl_graph::NodeMutRef;
+    use crate::graph::indexing::DefaultIx;
+    use crate::memory_backing::{CacheConfig, DiskBacking};
+    use crate::weight::DefaultWeight;
+    use tempfile::tempdir;
+
+    type Graph = AvlGraph<DefaultWeight, u16>;
+
+    // A root with edges to two leaves, inserted in the given order.
+    fn get_graph(weights: [u16; 2]) -> Graph {
+        let mut graph = Graph::new();
+        let root = graph.add_node(DefaultWeight::new(0, None, 2));
+        for weight in weights {
+            let leaf = graph.add_node(DefaultWeight::new(1, Some(NodeIndex::new(0)), 1));
+            graph.add_balanced_edge(root, leaf, weight);
+        }
+        graph
+    }
+
+    #[test]
+    fn test_isomorphic_eq() {
+        // Node indices differ, but the graphs have the same structure.
+        let graph = get_graph([0, 1]);
+        assert!(graph.isomorphic_eq(&get_graph([1, 0])));
+        assert!(!graph.isomorphic_eq(&get_graph([0, 2])));
+    }
+
+    #[test]
+    fn test_first_divergence() {
+        let graph = get_graph([0, 1]);
+        let mut other = get_graph([1, 0]);
+        other.get_node_mut(NodeIndex::new(2)).set_count(3);
+        let root = NodeIndex::new(0);
+        let divergence = graph.first_divergence(root, &other, root).unwrap();
+        assert_eq!(
+            divergence,
+            Divergence::Node {
+                path: vec![0],
+                field: "count",
+                values: ("1".to_string(), "3".to_string()),
+            }
+        );
+        assert_eq!(divergence.to_string(), "count differs at [0]: 1 vs 3");
+
+        let other = get_graph([0, 2]);
+        assert_eq!(
+            graph.first_divergence(root, &other, root),
+            Some(Divergence::Edges {
+                path: vec![],
+                weights: (vec![0, 1], vec![0, 2]),
+            })
+        );
+    }
+
+    #[test]
+    fn test_ram_and_disk_dawg() {
+        let tokens: [u16; 8] = [0, 1, 0, 1, 2, 0, 1, 0];
+        let mut ram: Dawg<u16, DefaultWeight> = Dawg::new();
+        let tmp_dir = tempdir().unwrap();
+        let mb: DiskBacking<DefaultWeight, u16, DefaultIx> = DiskBacking::new(tmp_dir.path());
+        let mut disk = Dawg::with_capacity_mb(mb, None, 16, 32, CacheConfig::none());
+        ram.build(&tokens);
+        disk.build(&tokens);
+        assert!(ram.get_graph().isomorphic_eq(disk.get_graph()));
+    }
+}
@@ -4,6 +4,7 @@
 // Edges stored in AVL tree: // https://stackoverflow.com/questions/7211806/how-to-implement-insertion-for-avl-tree-without-parent-pointer
 
 use crate::comparator::Comparator;
+use crate::error::check_exists;
 use anyhow::Result;
 use std::clone::Clone;
 use std::cmp::{Eq, Ord, Ordering};
@@ -14,13 +15,17 @@ use std::marker::PhantomData;
 use crate::serde::de::DeserializeOwned;
 use crate::serde::Serialize;
 use std::cmp::{max, min};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
 
 use crate::graph::indexing::{DefaultIx, EdgeIndex, IndexType, NodeIndex};
-use crate::memory_backing::{CacheConfig, DiskVec};
+use crate::memory_backing::{CacheConfig, CacheStats, DiskVec, VecCacheConfig};
 use crate::weight::Weight;
 
+pub mod binary;
 mod comparator;
+pub mod diff;
 pub mod edge;
 pub mod node;
 mod serde;
@@ -29,6 +34,9 @@ use self::comparator::DEFAULT_CMP;
 pub use self::edge::{Edge, EdgeMutRef, EdgeRef};
 pub use self::node::{Node, NodeMutRef, NodeRef};
 
+use crate::memory_backing::columnar_backing::{ColumnarBacking, NodeColumns};
+use crate::memory_backing::mmap_backing::{MmapBacking, MmapVec};
+use crate::memory_backing::tiered_backing::{TierPolicy, TieredBacking, TieredVec};
 use crate::memory_backing::{disk_backing, DiskBacking, MemoryBacking};
 use crate::memory_backing::{RamBacking, VecBacking};
 
@@ -61,10 +69,37 @@ where
         Ix: Serialize + DeserializeOwned + Default,
     {
         let mb: DiskBacking<N, E, Ix> = DiskBacking::new(path);
-        let _ = DiskVec::from_vec(&self.nodes, mb.get_nodes_path());
-        let _ = DiskVec::from_vec(&self.edges, mb.get_edges_path());
+        DiskVec::from_vec(&self.nodes, mb.get_nodes_path())?;
+        DiskVec::from_vec(&self.edges, mb.get_edges_path())?;
         Ok(())
     }
+
+    /// Start journaling changes so that `rollback` can undo them.
+    pub fn enable_journal(&mut self) {
+        self.nodes.enable();
+        self.edges.enable();
+    }
+
+    /// Undo all changes since the last checkpoint. Returns false if journaling isn't enabled.
+    pub fn rollback(&mut self) -> bool {
+        self.nodes.rollback() && self.edges.rollback()
+    }
+}
+
+// The copy doesn't journal changes, even if the original does.
+impl<N, E, Ix> Clone for AvlGraph<N, E, Ix>
+where
+    E: Copy + Debug,
+    Ix: IndexType,
+    N: Weight + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            edges: self.edges.clone(),
+            marker: PhantomData,
+        }
+    }
 }
 
 impl<N, E, Ix> AvlGraph<N, E, Ix, DiskBacking<N, E, Ix>>
@@ -77,18 +112,168 @@ where
         path: P,
         cache_config: CacheConfig,
     ) -> Result<Self> {
+        check_exists(path.as_ref())?;
         let mb: DiskBacking<N, E, Ix> = DiskBacking::new(path);
         // FIXME: This can be refactored to call a method in Mb.
         let nodes =
-            disk_backing::vec::Vec::load(mb.get_nodes_path(), cache_config.node_cache_size)?;
+            disk_backing::vec::Vec::load(mb.get_nodes_path(), cache_config.get_node_cache())?;
         let edges =
-            disk_backing::vec::Vec::load(mb.get_edges_path(), cache_config.edge_cache_size)?;
+            disk_backing::vec::Vec::load(mb.get_edges_path(), cache_config.get_edge_cache())?;
         Ok(Self {
             nodes,
             edges,
             marker: PhantomData,
         })
     }
+
+    /// Reopen a graph saved on disk so that more nodes and edges can be added. Files written
+    /// during a disk build keep their preallocated size, so the true sizes are passed in.
+    pub fn open_mut<P: AsRef<Path> + Clone + std::fmt::Debug>(
+        path: P,
+        n_nodes: usize,
+        n_edges: usize,
+        cache_config: CacheConfig,
+    ) -> Result<Self> {
+        let mb: DiskBacking<N, E, Ix> = DiskBacking::new(path);
+        let nodes = disk_backing::vec::Vec::open_mut(
+            mb.get_nodes_path(),
+            n_nodes,
+            cache_config.get_node_cache(),
+        )?;
+        let edges = disk_backing::vec::Vec::open_mut(
+            mb.get_edges_path(),
+            n_edges,
+            cache_config.get_edge_cache(),
+        )?;
+        Ok(Self {
+            nodes,
+            edges,
+            marker: PhantomData,
+        })
+    }
+
+    /// Hits and misses of the node and edge caches, for tuning the cache size and policy.
+    pub fn get_cache_stats(&self) -> (CacheStats, CacheStats) {
+        (self.nodes.get_cache_stats(), self.edges.get_cache_stats())
+    }
+}
+
+impl<N, E, Ix> AvlGraph<N, E, Ix, MmapBacking<N, E, Ix>>
+where
+    E: Copy + Debug + Serialize + DeserializeOwned + Default + 'static,
+    N: Weight + Copy + Clone + Serialize + DeserializeOwned + Default + 'static,
+    Ix: IndexType + Serialize + DeserializeOwned + 'static,
+{
+    /// Map a graph saved on disk into memory, read-only. The mapped files are written next to the
+    /// graph the first time, and again if it has changed since.
+    pub fn load_mmap<P: AsRef<Path>>(path: P) -> Result<Self> {
+        check_exists(path.as_ref())?;
+        let mb: MmapBacking<N, E, Ix> = MmapBacking::new(path);
+        mb.convert()?;
+        Ok(Self {
+            nodes: MmapVec::load(mb.get_nodes_path())?,
+            edges: MmapVec::load(mb.get_edges_path())?,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<N, E, Ix> AvlGraph<N, E, Ix, ColumnarBacking<N, E, Ix>>
+where
+    E: Copy + Debug + Serialize + DeserializeOwned + Default,
+    N: Weight + Clone,
+    Ix: IndexType + Serialize + DeserializeOwned,
+{
+    pub fn load_columnar<P: AsRef<Path> + Clone + std::fmt::Debug>(
+        path: P,
+        cache_config: CacheConfig,
+    ) -> Result<Self> {
+        check_exists(path.as_ref())?;
+        let mb: ColumnarBacking<N, E, Ix> = ColumnarBacking::new(path);
+        let nodes = NodeColumns::load(&mb, cache_config.get_node_cache())?;
+        let edges =
+            disk_backing::vec::Vec::load(mb.get_edges_path(), cache_config.get_edge_cache())?;
+        Ok(Self {
+            nodes,
+            edges,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<N, E, Ix> AvlGraph<N, E, Ix, TieredBacking<N, E, Ix>>
+where
+    E: Copy + Debug + Serialize + DeserializeOwned + Default,
+    N: Weight + Copy + Clone + Serialize + DeserializeOwned + Default,
+    Ix: IndexType + Serialize + DeserializeOwned,
+{
+    /// Load a graph saved on disk, pinning the states selected by `policy` and their outgoing
+    /// edges in RAM.
+    pub fn load_tiered<P: AsRef<Path> + Clone + std::fmt::Debug>(
+        path: P,
+        policy: TierPolicy,
+        cache_config: CacheConfig,
+    ) -> Result<Self> {
+        check_exists(path.as_ref())?;
+        let mb: TieredBacking<N, E, Ix> = TieredBacking::new(path);
+        let nodes = TieredVec::load(mb.get_nodes_path(), cache_config.get_node_cache())?;
+        let edges = TieredVec::load(mb.get_edges_path(), cache_config.get_edge_cache())?;
+        let mut graph = Self {
+            nodes,
+            edges,
+            marker: PhantomData,
+        };
+        graph.pin(policy);
+        Ok(graph)
+    }
+
+    fn pin(&mut self, policy: TierPolicy) {
+        // The initial state is always numbered 0.
+        let states: Vec<NodeIndex<Ix>> = match policy {
+            TierPolicy::MinCount(min_count) => (0..self.node_count())
+                .map(NodeIndex::new)
+                .filter(|state| self.get_node(*state).get_count() >= min_count)
+                .collect(),
+            TierPolicy::MaxDepth(max_depth) => {
+                let mut depths = HashMap::from([(NodeIndex::new(0), 0)]);
+                let mut queue = VecDeque::from([NodeIndex::new(0)]);
+                while let Some(state) = queue.pop_front() {
+                    let depth = depths[&state];
+                    if depth == max_depth {
+                        continue;
+                    }
+                    for next_state in self.neighbors(state) {
+                        if let Entry::Vacant(entry) = depths.entry(next_state) {
+                            entry.insert(depth + 1);
+                            queue.push_back(next_state);
+                        }
+                    }
+                }
+                depths.into_keys().collect()
+            }
+        };
+
+        let mut edges = Vec::new();
+        for state in states.iter() {
+            let mut stack = vec![self.get_node(*state).get_first_edge()];
+            while let Some(edge_idx) = stack.pop() {
+                if edge_idx == EdgeIndex::end() {
+                    continue;
+                }
+                edges.push(edge_idx.index());
+                let edge = self.get_edge(edge_idx);
+                stack.push(edge.get_left());
+                stack.push(edge.get_right());
+            }
+        }
+        self.nodes.pin(states.iter().map(|state| state.index()));
+        self.edges.pin(edges);
+    }
+
+    /// Number of states and edges pinned in RAM.
+    pub fn n_pinned(&self) -> (usize, usize) {
+        (self.nodes.n_pinned(), self.edges.n_pinned())
+    }
 }
 
 impl<N, E, Ix, Mb> AvlGraph<N, E, Ix, Mb>
@@ -98,8 +283,8 @@ where
     Ix: IndexType,
 {
     pub fn new_mb(mb: Mb) -> Self {
-        let nodes = mb.new_node_vec(None, 0);
-        let edges = mb.new_edge_vec(None, 0);
+        let nodes = mb.new_node_vec(None, VecCacheConfig::default());
+        let edges = mb.new_edge_vec(None, VecCacheConfig::default());
         AvlGraph {
             nodes,
             edges,
@@ -113,8 +298,8 @@ where
         n_edges: usize,
         cache_config: CacheConfig,
     ) -> Self {
-        let nodes = mb.new_node_vec(Some(n_nodes), cache_config.node_cache_size);
-        let edges = mb.new_edge_vec(Some(n_edges), cache_config.edge_cache_size);
+        let nodes = mb.new_node_vec(Some(n_nodes), cache_config.get_node_cache());
+        let edges = mb.new_edge_vec(Some(n_edges), cache_config.get_edge_cache());
         AvlGraph {
             nodes,
             edges,
@@ -130,6 +315,12 @@ where
     N: Weight,
     Ix: IndexType,
 {
+    /// Mark a state to roll back to. A no-op unless the backing is journaled.
+    pub fn checkpoint(&mut self) {
+        self.nodes.checkpoint();
+        self.edges.checkpoint();
+    }
+
     pub fn add_node(&mut self, weight: N) -> NodeIndex<Ix> {
         let node = Node::new(weight);
         let node_idx = NodeIndex::new(self.nodes.len());
@@ -204,6 +395,30 @@ where
     }
 
     // First result is either where weight was found or end; second is node above that (where to insert).
+    // Read ahead the top `depth` levels of the AVL tree rooted at `root`, one batch per level, so
+    // the search below doesn't wait on the disk once for every edge it visits.
+    fn prefetch_edge_tree(&self, root: EdgeIndex<Ix>, depth: usize) {
+        let mut level = vec![root];
+        for d in 0..depth {
+            let indices: Vec<usize> = level.iter().map(|edge| edge.index()).collect();
+            self.edges.prefetch(&indices);
+            if d + 1 == depth {
+                break;
+            }
+            level = level
+                .iter()
+                .flat_map(|edge| {
+                    let edge = self.edges.index(edge.index());
+                    [edge.get_left(), edge.get_right()]
+                })
+                .filter(|&edge| edge != EdgeIndex::end())
+                .collect();
+            if level.is_empty() {
+                break;
+            }
+        }
+    }
+
     fn binary_search(
         &self,
         edge: EdgeIndex<Ix>,
@@ -427,6 +642,7 @@ where
         if first_edge == EdgeIndex::end() {
             return None;
         }
+        self.prefetch_edge_tree(first_edge, self.edges.get_prefetch_depth());
         let (e, _last_e) = self.binary_search(first_edge, EdgeIndex::end(), weight, cmp);
         if e == EdgeIndex::end() {
             return None;
@@ -456,11 +672,17 @@ where
         self.edges.len()
     }
 
-    pub fn neighbors(&self, node: NodeIndex<Ix>) -> Neighbors<N, E, Ix, Mb> {
+    /// Write changes to nodes and edges through to disk, for backings that store them in files.
+    pub fn flush(&self) -> Result<()> {
+        self.nodes.flush()?;
+        self.edges.flush()
+    }
+
+    pub fn neighbors(&self, node: NodeIndex<Ix>) -> Neighbors<'_, N, E, Ix, Mb> {
         Neighbors::new(self, node)
     }
 
-    pub fn edges(&self, edges: NodeIndex<Ix>) -> Edges<N, E, Ix, Mb> {
+    pub fn edges(&self, edges: NodeIndex<Ix>) -> Edges<'_, N, E, Ix, Mb> {
         Edges::new(self, edges)
     }
 
@@ -529,6 +751,7 @@ where
     }
 
     // DONT USE THIS, here for legacy test reasons
+    #[cfg(test)]
     fn add_edge(&mut self, a: NodeIndex<Ix>, b: NodeIndex<Ix>, weight: E) -> Option<EdgeIndex<Ix>> {
         let edge = Edge::new(weight, b);
         let edge_idx = EdgeIndex::new(self.edges.len());
@@ -655,7 +878,7 @@ where
 mod tests {
     use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
     use crate::cdawg::comparator::CdawgComparator;
-    use crate::graph::avl_graph::edge::EdgeRef;
+    use crate::graph::avl_graph::edge::{EdgeMutRef, EdgeRef};
     use crate::graph::avl_graph::node::{NodeMutRef, NodeRef};
     use crate::graph::avl_graph::AvlGraph;
     use crate::graph::indexing::{DefaultIx, EdgeIndex, IndexType, NodeIndex};
@@ -687,11 +910,11 @@ mod tests {
         let e3 = graph.add_edge(q0, q1, 2).unwrap();
         let e4 = graph.add_edge(q0, q1, 4).unwrap();
 
-        graph.edges[root.index()].balance_factor = -1;
-        graph.edges[e1.index()].balance_factor = 0;
-        graph.edges[e2.index()].balance_factor = 0;
-        graph.edges[e3.index()].balance_factor = 0;
-        graph.edges[e4.index()].balance_factor = 0;
+        graph.get_edge_mut(root).set_balance_factor(-1);
+        graph.get_edge_mut(e1).set_balance_factor(0);
+        graph.get_edge_mut(e2).set_balance_factor(0);
+        graph.get_edge_mut(e3).set_balance_factor(0);
+        graph.get_edge_mut(e4).set_balance_factor(0);
 
         root = graph.rotate_from_right(root);
 
@@ -720,11 +943,11 @@ mod tests {
         let e3 = graph.add_edge(q0, q1, 0).unwrap();
         let e4 = graph.add_edge(q0, q1, 2).unwrap();
 
-        graph.edges[root.index()].balance_factor = 1;
-        graph.edges[e1.index()].balance_factor = 0;
-        graph.edges[e2.index()].balance_factor = 0;
-        graph.edges[e3.index()].balance_factor = 0;
-        graph.edges[e4.index()].balance_factor = 0;
+        graph.get_edge_mut(root).set_balance_factor(1);
+        graph.get_edge_mut(e1).set_balance_factor(0);
+        graph.get_edge_mut(e2).set_balance_factor(0);
+        graph.get_edge_mut(e3).set_balance_factor(0);
+        graph.get_edge_mut(e4).set_balance_factor(0);
 
         root = graph.rotate_from_left(root);
 
@@ -749,10 +972,10 @@ mod tests {
         let source = graph.add_node(weight);
         let sink = graph.add_node(weight);
 
-        let cmp0 = CdawgComparator::new_with_token(tokens.clone(), 10);
+        let cmp0: CdawgComparator = CdawgComparator::new_with_token(tokens.clone(), 10);
         graph.add_balanced_edge_cmp(source, sink, CdawgEdgeWeight::new(0, 2), Box::new(cmp0));
 
-        let cmp1 = CdawgComparator::new_with_token(tokens.clone(), 11);
+        let cmp1: CdawgComparator = CdawgComparator::new_with_token(tokens.clone(), 11);
         graph.add_balanced_edge_cmp(source, sink, CdawgEdgeWeight::new(1, 2), Box::new(cmp1));
         let edge1 = graph.get_edge(graph.get_node(source).get_first_edge());
         assert_eq!(edge1.get_weight().get_span(), (0, 2));
@@ -893,4 +1116,68 @@ mod tests {
         graph.get_node_mut(idx0).set_length(1);
         assert_eq!(graph.get_node(idx0).get_length(), 1);
     }
+
+    #[test]
+    fn test_save_to_disk_fault_detected_at_load() {
+        use crate::memory_backing::vec_backing::fault::{self, FaultPoint};
+        use crate::memory_backing::{CacheConfig, DiskBacking};
+        use tempfile::tempdir;
+
+        type DiskGraph =
+            AvlGraph<DefaultWeight, u32, DefaultIx, DiskBacking<DefaultWeight, u32, DefaultIx>>;
+        let weight = DefaultWeight::new(0, None, 0);
+        let mut graph: AvlGraph<DefaultWeight, u32> = AvlGraph::new();
+        let q0 = graph.add_node(weight);
+        let q1 = graph.add_node(weight);
+        for idx in 0..4 {
+            graph.add_balanced_edge(q0, q1, idx);
+        }
+
+        // The nodes are saved, but writing the edges fails partway through.
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("graph");
+        fault::inject(FaultPoint::Write, 2 + 2);
+        assert!(graph.save_to_disk(&path).is_err());
+        fault::clear();
+        assert!(DiskGraph::load(&path, CacheConfig::none()).is_err());
+
+        let path = tmp_dir.path().join("graph2");
+        graph.save_to_disk(&path).unwrap();
+        let loaded = DiskGraph::load(&path, CacheConfig::none()).unwrap();
+        assert_eq!(loaded.node_count(), 2);
+        assert_eq!(loaded.edge_count(), 4);
+        assert_eq!(loaded.edge_target(q0, 3), Some(q1));
+    }
+
+    #[test]
+    fn test_get_edge_by_weight_with_prefetch() {
+        use crate::memory_backing::{CacheConfig, DiskBacking};
+        use tempfile::tempdir;
+
+        type DiskGraph =
+            AvlGraph<DefaultWeight, u32, DefaultIx, DiskBacking<DefaultWeight, u32, DefaultIx>>;
+        let weight = DefaultWeight::new(0, None, 0);
+        let mut graph: AvlGraph<DefaultWeight, u32> = AvlGraph::new();
+        let q0 = graph.add_node(weight);
+        let q1 = graph.add_node(weight);
+        for idx in 0..20 {
+            graph.add_balanced_edge(q0, q1, idx);
+        }
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("graph");
+        graph.save_to_disk(&path).unwrap();
+
+        // Prefetching deeper than the tree is fine, and doesn't change what is found.
+        for depth in [1, 3, 10] {
+            let config = CacheConfig::new(4, 4).with_prefetch_depth(depth);
+            let loaded = DiskGraph::load(&path, config).unwrap();
+            for idx in 0..20 {
+                assert_eq!(
+                    loaded.get_edge_by_weight(q0, idx),
+                    graph.get_edge_by_weight(q0, idx)
+                );
+            }
+            assert_eq!(loaded.get_edge_by_weight(q0, 20), None);
+        }
+    }
 }
@@ -47,6 +47,7 @@ pub trait NodeRef<N, Ix> {
     fn get_length(self) -> u64;
     fn get_failure(self) -> Option<NodeIndex<Ix>>;
     fn get_count(self) -> usize;
+    fn is_terminal(&self) -> bool;
     fn get_first_edge(self) -> EdgeIndex<Ix>;
 }
 
@@ -79,6 +80,10 @@ where
         self.weight.get_count()
     }
 
+    fn is_terminal(&self) -> bool {
+        self.weight.is_terminal()
+    }
+
     fn get_first_edge(self) -> EdgeIndex<Ix> {
         self.first_edge
     }
@@ -119,6 +124,11 @@ where
         unsafe { (*self).weight.get_count() }
     }
 
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn is_terminal(&self) -> bool {
+        unsafe { (**self).weight.is_terminal() }
+    }
+
     #[allow(clippy::not_unsafe_ptr_arg_deref)]
     fn get_first_edge(self) -> EdgeIndex<Ix> {
         unsafe { (*self).first_edge }
@@ -130,6 +140,7 @@ pub trait NodeMutRef<Ix> {
     fn set_failure(self, state: Option<NodeIndex<Ix>>);
     fn increment_count(self);
     fn set_count(self, count: usize);
+    fn set_terminal(self, terminal: bool);
     fn set_first_edge(self, first_edge: EdgeIndex<Ix>);
 }
 
@@ -170,6 +181,13 @@ where
         }
     }
 
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn set_terminal(self, terminal: bool) {
+        unsafe {
+            (*self).weight.set_terminal(terminal);
+        }
+    }
+
     #[allow(clippy::not_unsafe_ptr_arg_deref)]
     fn set_first_edge(self, first_edge: EdgeIndex<Ix>) {
         unsafe {
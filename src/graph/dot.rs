@@ -0,0 +1,87 @@
+// Helpers for rendering automata in GraphViz's DOT language, so small graphs can be debugged
+// visually (e.g. `dot -Tpng graph.dot -o graph.png`).
+//
+// States are labelled with their index, length and count. Failure links are drawn dashed and don't
+// affect the layout.
+
+use anyhow::Result;
+use std::io::Write;
+
+pub fn write_header<Wr: Write>(writer: &mut Wr) -> Result<()> {
+    writeln!(writer, "digraph {{")?;
+    writeln!(writer, "  rankdir=LR;")?;
+    writeln!(writer, "  node [shape=circle];")?;
+    Ok(())
+}
+
+pub fn write_footer<Wr: Write>(writer: &mut Wr) -> Result<()> {
+    writeln!(writer, "}}")?;
+    Ok(())
+}
+
+pub fn write_state<Wr: Write>(
+    writer: &mut Wr,
+    state: usize,
+    length: u64,
+    count: usize,
+) -> Result<()> {
+    writeln!(
+        writer,
+        "  q{} [label=\"q{}\\nlen={}\\ncount={}\"];",
+        state, state, length, count
+    )?;
+    Ok(())
+}
+
+pub fn write_edge<Wr: Write>(
+    writer: &mut Wr,
+    source: usize,
+    target: usize,
+    label: &str,
+) -> Result<()> {
+    writeln!(
+        writer,
+        "  q{} -> q{} [label=\"{}\"];",
+        source,
+        target,
+        escape(label)
+    )?;
+    Ok(())
+}
+
+pub fn write_failure<Wr: Write>(writer: &mut Wr, source: usize, target: usize) -> Result<()> {
+    writeln!(
+        writer,
+        "  q{} -> q{} [style=dashed, color=gray, constraint=false];",
+        source, target
+    )?;
+    Ok(())
+}
+
+fn escape(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_edge_escapes() {
+        let mut out = Vec::new();
+        write_edge(&mut out, 0, 1, "say \"hi\"\n").unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "  q0 -> q1 [label=\"say \\\"hi\\\"\\n\"];\n"
+        );
+    }
+}
@@ -1,7 +1,9 @@
 // See https://docs.rs/petgraph/0.4.13/src/petgraph/graph_impl/mod.rs.html
 
+use anyhow::{bail, Result};
 use std::fmt;
 use std::hash::Hash;
+use std::mem::size_of;
 
 use serde::{Deserialize, Serialize};
 
@@ -115,6 +117,21 @@ unsafe impl IndexType for Index40 {
     }
 }
 
+unsafe impl IndexType for u64 {
+    #[inline(always)]
+    fn new(x: usize) -> Self {
+        x as u64
+    }
+    #[inline(always)]
+    fn index(&self) -> usize {
+        *self as usize
+    }
+    #[inline(always)]
+    fn max_value() -> Self {
+        u64::MAX
+    }
+}
+
 unsafe impl IndexType for u32 {
     #[inline(always)]
     fn new(x: usize) -> Self {
@@ -160,6 +177,50 @@ unsafe impl IndexType for u8 {
     }
 }
 
+/// Number of bits in an index of type `Ix`.
+pub fn index_bits<Ix: IndexType>() -> usize {
+    8 * size_of::<Ix>()
+}
+
+/// Width of the node and edge indices of a graph (`--index-width`). The default 40 bits holds about
+/// 1.1T nodes or edges; 64 bits removes the limit, at the cost of larger nodes and edges.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IndexWidth {
+    Bits40,
+    Bits64,
+}
+
+impl IndexWidth {
+    pub fn new(bits: usize) -> Result<Self> {
+        match bits {
+            40 => Ok(IndexWidth::Bits40),
+            64 => Ok(IndexWidth::Bits64),
+            _ => bail!("Invalid index width: {} (expected 40 or 64)", bits),
+        }
+    }
+
+    pub fn bits(&self) -> usize {
+        match self {
+            IndexWidth::Bits40 => index_bits::<DefaultIx>(),
+            IndexWidth::Bits64 => index_bits::<u64>(),
+        }
+    }
+
+    /// Largest index, which is reserved as a null index.
+    pub fn max_index(&self) -> usize {
+        match self {
+            IndexWidth::Bits40 => DefaultIx::max_value().index(),
+            IndexWidth::Bits64 => <u64 as IndexType>::max_value().index(),
+        }
+    }
+}
+
+impl fmt::Display for IndexWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-bit", self.bits())
+    }
+}
+
 /// Node identifier.
 #[derive(Copy, Clone, Default, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
 pub struct NodeIndex<Ix = DefaultIx>(Ix);
@@ -1,4 +1,5 @@
 pub mod avl_graph;
+pub(crate) mod dot; // Rendering automata in GraphViz, for debugging.
 #[allow(dead_code)]
 pub mod indexing;
 
@@ -0,0 +1,253 @@
+// gRPC server for remote queries on a `FrozenCdawg`, built with the `grpc` feature. The service is
+// defined in proto/cdawg.proto.
+//
+// Unary queries only walk a few states, so they are answered on the async runtime. Batched queries
+// run on a blocking thread and stream each response as soon as it's computed, so clients can start
+// consuming results before the whole batch is done.
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use crate::cdawg::cdawg_state::CdawgState;
+use crate::cdawg::frozen::FrozenCdawg;
+use crate::graph::indexing::DefaultIx;
+
+pub mod proto {
+    tonic::include_proto!("rusty_dawg");
+}
+
+use proto::cdawg_service_server::{CdawgService, CdawgServiceServer};
+use proto::{
+    BatchCountRequest, BatchMatchRequest, BatchNextTokensRequest, CountRequest, CountResponse,
+    MatchRequest, MatchResponse, NextToken, NextTokensRequest, NextTokensResponse,
+};
+
+type ResponseStream<R> = Pin<Box<dyn Stream<Item = Result<R, Status>> + Send>>;
+
+// Responses computed ahead of a slow client in batched queries.
+const STREAM_BUFFER: usize = 64;
+
+#[derive(Clone)]
+pub struct CdawgGrpcService {
+    cdawg: FrozenCdawg,
+}
+
+impl CdawgGrpcService {
+    pub fn new(cdawg: FrozenCdawg) -> Self {
+        Self { cdawg }
+    }
+
+    pub fn into_server(self) -> CdawgServiceServer<Self> {
+        CdawgServiceServer::new(self)
+    }
+
+    fn get_count(&self, request: &CountRequest) -> Result<CountResponse, Status> {
+        let tokens = to_tokens(&request.tokens)?;
+        Ok(CountResponse {
+            count: self.cdawg.get_ngram_count(&tokens) as u64,
+        })
+    }
+
+    fn get_next_tokens(&self, request: &NextTokensRequest) -> Result<NextTokensResponse, Status> {
+        let mut cs = self.cdawg.get_initial();
+        for token in to_tokens(&request.tokens)? {
+            cs = self.cdawg.transition_and_count(cs, token);
+        }
+        let cs = self.back_off(cs);
+        let next_tokens = self
+            .cdawg
+            .get_next_tokens(cs)
+            .into_iter()
+            .map(|(token, prob)| NextToken {
+                token: token as u32,
+                prob,
+            })
+            .collect();
+        Ok(NextTokensResponse {
+            suffix_length: cs.length,
+            next_tokens,
+        })
+    }
+
+    fn get_matches(&self, request: &MatchRequest) -> Result<MatchResponse, Status> {
+        let mut response = MatchResponse::default();
+        let mut cs = self.cdawg.get_initial();
+        for token in to_tokens(&request.tokens)? {
+            cs = self.cdawg.transition_and_count(cs, token);
+            let matched = self.back_off(cs);
+            response.suffix_lengths.push(matched.length);
+            response
+                .suffix_counts
+                .push(self.cdawg.get_suffix_count(matched) as u64);
+        }
+        Ok(response)
+    }
+
+    // After a token that never occurs, the longest matching suffix is empty.
+    fn back_off(&self, cs: CdawgState<DefaultIx>) -> CdawgState<DefaultIx> {
+        match cs.target {
+            Some(_) => cs,
+            None => self.cdawg.get_initial(),
+        }
+    }
+
+    // Answer `requests` one at a time on a blocking thread, streaming the responses in order.
+    fn stream<Q, R, F>(&self, requests: Vec<Q>, answer: F) -> ResponseStream<R>
+    where
+        Q: Send + 'static,
+        R: Send + 'static,
+        F: Fn(&Self, &Q) -> Result<R, Status> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+        let service = self.clone();
+        tokio::task::spawn_blocking(move || {
+            for request in requests.iter() {
+                // The client hung up.
+                if sender.blocking_send(answer(&service, request)).is_err() {
+                    break;
+                }
+            }
+        });
+        Box::pin(ReceiverStream::new(receiver))
+    }
+}
+
+#[tonic::async_trait]
+impl CdawgService for CdawgGrpcService {
+    type BatchCountStream = ResponseStream<CountResponse>;
+    type BatchNextTokensStream = ResponseStream<NextTokensResponse>;
+    type BatchMatchStream = ResponseStream<MatchResponse>;
+
+    async fn count(
+        &self,
+        request: Request<CountRequest>,
+    ) -> Result<Response<CountResponse>, Status> {
+        self.get_count(request.get_ref()).map(Response::new)
+    }
+
+    async fn next_tokens(
+        &self,
+        request: Request<NextTokensRequest>,
+    ) -> Result<Response<NextTokensResponse>, Status> {
+        self.get_next_tokens(request.get_ref()).map(Response::new)
+    }
+
+    async fn r#match(
+        &self,
+        request: Request<MatchRequest>,
+    ) -> Result<Response<MatchResponse>, Status> {
+        self.get_matches(request.get_ref()).map(Response::new)
+    }
+
+    async fn batch_count(
+        &self,
+        request: Request<BatchCountRequest>,
+    ) -> Result<Response<Self::BatchCountStream>, Status> {
+        let requests = request.into_inner().requests;
+        Ok(Response::new(self.stream(requests, Self::get_count)))
+    }
+
+    async fn batch_next_tokens(
+        &self,
+        request: Request<BatchNextTokensRequest>,
+    ) -> Result<Response<Self::BatchNextTokensStream>, Status> {
+        let requests = request.into_inner().requests;
+        Ok(Response::new(self.stream(requests, Self::get_next_tokens)))
+    }
+
+    async fn batch_match(
+        &self,
+        request: Request<BatchMatchRequest>,
+    ) -> Result<Response<Self::BatchMatchStream>, Status> {
+        let requests = request.into_inner().requests;
+        Ok(Response::new(self.stream(requests, Self::get_matches)))
+    }
+}
+
+/// Serve queries on `cdawg` at `addr` until the server fails.
+pub async fn serve(cdawg: FrozenCdawg, addr: SocketAddr) -> anyhow::Result<()> {
+    Server::builder()
+        .add_service(CdawgGrpcService::new(cdawg).into_server())
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+// Token IDs are u32 on the wire.
+fn to_tokens(tokens: &[u32]) -> Result<Vec<u16>, Status> {
+    tokens
+        .iter()
+        .map(|&token| match u16::try_from(token) {
+            Ok(token) if token != u16::MAX => Ok(token),
+            _ => Err(Status::invalid_argument(format!(
+                "Token ID {} exceeds u16",
+                token
+            ))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::{Cdawg, TopologicalCounter};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use tokio_stream::StreamExt;
+
+    fn get_service() -> CdawgGrpcService {
+        let tokens = vec![0, 1, 2, 0, 1, u16::MAX, 1, 2, 3, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+        CdawgGrpcService::new(FrozenCdawg::new(&cdawg))
+    }
+
+    #[test]
+    fn test_unary_queries() {
+        let service = get_service();
+        let count = service.get_count(&CountRequest { tokens: vec![0, 1] });
+        assert_eq!(count.unwrap().count, 2);
+        let count = service.get_count(&CountRequest {
+            tokens: vec![70000],
+        });
+        assert_eq!(count.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let matches = service
+            .get_matches(&MatchRequest {
+                tokens: vec![1, 2, 0, 3],
+            })
+            .unwrap();
+        assert_eq!(matches.suffix_lengths, vec![1, 2, 3, 1]);
+        assert_eq!(matches.suffix_counts, vec![3, 2, 1, 1]);
+
+        let next_tokens = service
+            .get_next_tokens(&NextTokensRequest { tokens: vec![1, 2] })
+            .unwrap();
+        assert_eq!(next_tokens.suffix_length, 2);
+        let mut next: Vec<_> = next_tokens.next_tokens.iter().map(|n| n.token).collect();
+        next.sort();
+        assert_eq!(next, vec![0, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_batch_count_streams_in_order() {
+        let service = get_service();
+        let requests = vec![
+            CountRequest { tokens: vec![1] },
+            CountRequest { tokens: vec![3] },
+            CountRequest { tokens: vec![2, 2] },
+        ];
+        let counts: Vec<_> = service
+            .stream(requests, CdawgGrpcService::get_count)
+            .map(|response| response.unwrap().count)
+            .collect()
+            .await;
+        assert_eq!(counts, vec![3, 1, 0]);
+    }
+}
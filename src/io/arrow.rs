@@ -0,0 +1,414 @@
+// Streaming writer for tables in the Arrow IPC file format (also known as Feather V2), so exports
+// can be read with `pyarrow.ipc.open_file` or `pandas.read_feather` without an Arrow dependency.
+//
+// Only what the exporters need is supported: every column holds u64s, and may have nulls. Rows are
+// buffered and written as a record batch every `BATCH_LEN` rows, and `finish` writes the footer that
+// indexes the batches. Arrow's metadata is FlatBuffers, which `Fb` encodes by hand.
+
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const MAGIC: &[u8] = b"ARROW1";
+const CONTINUATION: u32 = 0xFFFFFFFF;
+const BATCH_LEN: usize = 1 << 16;
+
+// Enum values from Arrow's Schema.fbs and Message.fbs.
+const METADATA_V5: i16 = 4;
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_RECORD_BATCH: u8 = 3;
+const TYPE_INT: u8 = 2;
+const BLOCK_SIZE: usize = 24; // struct Block { offset: long; metaDataLength: int; bodyLength: long; }
+
+pub struct ArrowWriter {
+    writer: BufWriter<File>,
+    position: usize, // Bytes written so far.
+    schema: Fb,
+    nullable: Vec<bool>,
+    columns: Vec<Vec<Option<u64>>>, // Rows of the batch being buffered.
+    blocks: Vec<u8>,                // Footer entries locating the batches written so far.
+    len: usize,
+}
+
+impl ArrowWriter {
+    /// Create an Arrow file with one u64 column for each `(name, nullable)` pair.
+    pub fn create<P: AsRef<Path>>(path: P, fields: &[(&str, bool)]) -> Result<Self> {
+        let schema = Fb::Table(vec![
+            None, // Little-endian, the default.
+            Some(Fb::Tables(
+                fields
+                    .iter()
+                    .map(|(name, nullable)| field(name, *nullable))
+                    .collect(),
+            )),
+        ]);
+        let mut writer = Self {
+            writer: BufWriter::new(File::create(path)?),
+            position: 0,
+            schema,
+            nullable: fields.iter().map(|(_, nullable)| *nullable).collect(),
+            columns: vec![Vec::new(); fields.len()],
+            blocks: Vec::new(),
+            len: 0,
+        };
+        writer.write(&[MAGIC, &[0, 0]].concat())?;
+        let schema = message(HEADER_SCHEMA, writer.schema.clone(), 0);
+        writer.write_message(&schema, &[])?;
+        Ok(writer)
+    }
+
+    /// Add a row, with one value for each column.
+    pub fn push(&mut self, row: &[Option<u64>]) -> Result<()> {
+        if row.len() != self.columns.len() {
+            bail!(
+                "Row has {} values, but the table has {} columns",
+                row.len(),
+                self.columns.len()
+            );
+        }
+        for ((column, nullable), value) in self.columns.iter_mut().zip(&self.nullable).zip(row) {
+            if value.is_none() && !nullable {
+                bail!("Null in a non-nullable column");
+            }
+            column.push(*value);
+        }
+        self.len += 1;
+        if self.columns[0].len() == BATCH_LEN {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Write the last batch and the footer.
+    pub fn finish(mut self) -> Result<()> {
+        if self
+            .columns
+            .first()
+            .is_some_and(|column| !column.is_empty())
+        {
+            self.write_batch()?;
+        }
+        // End-of-stream marker.
+        self.write(&[CONTINUATION.to_le_bytes(), 0u32.to_le_bytes()].concat())?;
+        let footer = Fb::Table(vec![
+            Some(Fb::I16(METADATA_V5)),
+            Some(self.schema.clone()),
+            Some(Fb::Structs(BLOCK_SIZE, Vec::new())), // No dictionaries.
+            Some(Fb::Structs(BLOCK_SIZE, std::mem::take(&mut self.blocks))),
+        ])
+        .encode();
+        self.write(&footer)?;
+        self.write(&(footer.len() as i32).to_le_bytes())?;
+        self.write(MAGIC)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<()> {
+        let len = self.columns[0].len();
+        let mut nodes = Vec::new();
+        let mut buffers = Vec::new();
+        let mut body = Vec::new();
+        for column in self.columns.iter_mut() {
+            let null_count = column.iter().filter(|value| value.is_none()).count();
+            nodes.extend(pack(&[len as i64, null_count as i64]));
+            // A validity bitmap, which can be left out if there are no nulls, then the values.
+            let mut validity = Vec::new();
+            if null_count > 0 {
+                validity = vec![0u8; len.div_ceil(8)];
+                for (idx, value) in column.iter().enumerate() {
+                    if value.is_some() {
+                        validity[idx / 8] |= 1 << (idx % 8);
+                    }
+                }
+            }
+            let values: Vec<u8> = column
+                .iter()
+                .flat_map(|value| value.unwrap_or(0).to_le_bytes())
+                .collect();
+            for buffer in [validity, values] {
+                buffers.extend(pack(&[body.len() as i64, buffer.len() as i64]));
+                body.extend(buffer);
+                pad(&mut body, 8);
+            }
+            column.clear();
+        }
+        let batch = Fb::Table(vec![
+            Some(Fb::I64(len as i64)),
+            Some(Fb::Structs(16, nodes)), // struct FieldNode { length: long; null_count: long; }
+            Some(Fb::Structs(16, buffers)), // struct Buffer { offset: long; length: long; }
+        ]);
+        let batch = message(HEADER_RECORD_BATCH, batch, body.len());
+        self.write_message(&batch, &body)
+    }
+
+    // Write an encapsulated message, recording a footer entry for record batches.
+    fn write_message(&mut self, metadata: &[u8], body: &[u8]) -> Result<()> {
+        let offset = self.position;
+        self.write(&CONTINUATION.to_le_bytes())?;
+        self.write(&(metadata.len() as i32).to_le_bytes())?;
+        self.write(metadata)?;
+        self.write(body)?;
+        if !body.is_empty() {
+            self.blocks.extend(pack(&[offset as i64]));
+            self.blocks.extend(
+                (metadata.len() as i32 + 8)
+                    .to_le_bytes()
+                    .into_iter()
+                    .chain([0; 4]),
+            );
+            self.blocks.extend(pack(&[body.len() as i64]));
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        self.position += bytes.len();
+        Ok(())
+    }
+}
+
+// A non-nested u64 field of a schema.
+fn field(name: &str, nullable: bool) -> Fb {
+    Fb::Table(vec![
+        Some(Fb::Str(name.to_string())),
+        Some(Fb::Bool(nullable)),
+        Some(Fb::U8(TYPE_INT)),
+        Some(Fb::Table(vec![Some(Fb::I32(64)), Some(Fb::Bool(false))])),
+        None,                     // Not dictionary-encoded.
+        Some(Fb::Tables(vec![])), // No children, which readers expect to be present.
+    ])
+}
+
+fn message(header_type: u8, header: Fb, body_len: usize) -> Vec<u8> {
+    Fb::Table(vec![
+        Some(Fb::I16(METADATA_V5)),
+        Some(Fb::U8(header_type)),
+        Some(header),
+        Some(Fb::I64(body_len as i64)),
+    ])
+    .encode()
+}
+
+fn pack(values: &[i64]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn pad(bytes: &mut Vec<u8>, align: usize) {
+    bytes.resize(bytes.len().next_multiple_of(align), 0);
+}
+
+// A FlatBuffers value, as far as Arrow's metadata needs them. Tables list their fields by slot,
+// with None for fields left at their default, and unions take two slots: the type, then the value.
+#[derive(Clone)]
+enum Fb {
+    U8(u8),
+    Bool(bool),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Str(String),
+    Table(Vec<Option<Fb>>),
+    Tables(Vec<Fb>),
+    Structs(usize, Vec<u8>), // Vector of 8-byte aligned structs of some size, concatenated.
+}
+
+impl Fb {
+    // Encode a root table, padded to 8 bytes as Arrow requires. Unlike the reference builder, this
+    // writes front to back: each table is preceded by its vtable and followed by its children.
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![0; 4];
+        let root = self.write(&mut buf);
+        buf[..4].copy_from_slice(&(root as u32).to_le_bytes());
+        pad(&mut buf, 8);
+        buf
+    }
+
+    // Size of the value inline in a table: scalars are stored there, anything else is an offset.
+    fn inline_size(&self) -> usize {
+        match self {
+            Fb::U8(_) | Fb::Bool(_) => 1,
+            Fb::I16(_) => 2,
+            Fb::I32(_) => 4,
+            Fb::I64(_) => 8,
+            _ => 4,
+        }
+    }
+
+    fn scalar_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            Fb::U8(value) => Some(vec![*value]),
+            Fb::Bool(value) => Some(vec![*value as u8]),
+            Fb::I16(value) => Some(value.to_le_bytes().to_vec()),
+            Fb::I32(value) => Some(value.to_le_bytes().to_vec()),
+            Fb::I64(value) => Some(value.to_le_bytes().to_vec()),
+            _ => None,
+        }
+    }
+
+    // Append a non-scalar value, returning the position that offsets to it point to.
+    fn write(&self, buf: &mut Vec<u8>) -> usize {
+        match self {
+            Fb::Table(fields) => {
+                // Fields follow the vtable offset, each aligned to its size. The table itself is
+                // 8-byte aligned, so they are aligned in the buffer too.
+                let mut slots = Vec::with_capacity(fields.len());
+                let mut size: usize = 4;
+                for field in fields.iter() {
+                    match field {
+                        Some(value) => {
+                            size = size.next_multiple_of(value.inline_size());
+                            slots.push(size);
+                            size += value.inline_size();
+                        }
+                        None => slots.push(0),
+                    }
+                }
+                pad(buf, 2);
+                let vtable = buf.len();
+                buf.extend((2 * (2 + fields.len()) as u16).to_le_bytes());
+                buf.extend((size as u16).to_le_bytes());
+                for slot in slots.iter() {
+                    buf.extend((*slot as u16).to_le_bytes());
+                }
+                pad(buf, 8);
+                let table = buf.len();
+                buf.extend(((table - vtable) as i32).to_le_bytes());
+                buf.resize(table + size, 0);
+                for (field, slot) in fields.iter().zip(slots) {
+                    let Some(value) = field else {
+                        continue;
+                    };
+                    let pos = table + slot;
+                    let bytes = value.scalar_bytes().unwrap_or_else(|| {
+                        let child = value.write(buf);
+                        ((child - pos) as u32).to_le_bytes().to_vec()
+                    });
+                    buf[pos..pos + bytes.len()].copy_from_slice(&bytes);
+                }
+                table
+            }
+            Fb::Str(value) => {
+                pad(buf, 4);
+                let pos = buf.len();
+                buf.extend((value.len() as u32).to_le_bytes());
+                buf.extend(value.as_bytes());
+                buf.push(0);
+                pos
+            }
+            Fb::Tables(tables) => {
+                pad(buf, 4);
+                let pos = buf.len();
+                buf.extend((tables.len() as u32).to_le_bytes());
+                let offsets = buf.len();
+                buf.resize(offsets + 4 * tables.len(), 0);
+                for (idx, table) in tables.iter().enumerate() {
+                    let slot = offsets + 4 * idx;
+                    let child = table.write(buf);
+                    buf[slot..slot + 4].copy_from_slice(&((child - slot) as u32).to_le_bytes());
+                }
+                pos
+            }
+            Fb::Structs(size, bytes) => {
+                // The length comes right before the structs, which must be 8-byte aligned.
+                pad(buf, 8);
+                buf.extend([0; 4]);
+                let pos = buf.len();
+                buf.extend(((bytes.len() / size) as u32).to_le_bytes());
+                buf.extend(bytes);
+                pos
+            }
+            _ => unreachable!("scalars are stored inline"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, UInt64Array};
+    use arrow_ipc::reader::FileReader;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_arrow_writer() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("table.arrow");
+        let mut writer = ArrowWriter::create(&path, &[("id", false), ("failure", true)]).unwrap();
+        writer.push(&[Some(0), None]).unwrap();
+        writer.push(&[Some(1), Some(0)]).unwrap();
+        assert!(writer.push(&[None, Some(0)]).is_err());
+        assert!(writer.push(&[Some(2)]).is_err());
+        assert_eq!(writer.len(), 2);
+        writer.finish().unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..8], b"ARROW1\0\0");
+        assert_eq!(&bytes[bytes.len() - 6..], MAGIC);
+        let footer_len =
+            i32::from_le_bytes(bytes[bytes.len() - 10..bytes.len() - 6].try_into().unwrap());
+        let footer_start = bytes.len() - 10 - footer_len as usize;
+        assert_eq!(footer_start % 8, 0);
+        assert_eq!(
+            &bytes[footer_start - 8..footer_start],
+            &[255, 255, 255, 255, 0, 0, 0, 0]
+        );
+        // The id column is written contiguously, and the failure column after its bitmap.
+        let ids: Vec<u8> = [0u64, 1].iter().flat_map(|x| x.to_le_bytes()).collect();
+        assert!(bytes.windows(16).any(|window| window == ids));
+    }
+
+    #[test]
+    fn test_arrow_writer_read_back() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("table.arrow");
+        // Enough rows for a second, partial batch.
+        let rows: Vec<[Option<u64>; 2]> = (0..BATCH_LEN as u64 + 3)
+            .map(|id| [Some(id), if id % 3 == 0 { None } else { Some(id / 2) }])
+            .collect();
+        let mut writer = ArrowWriter::create(&path, &[("id", false), ("failure", true)]).unwrap();
+        for row in rows.iter() {
+            writer.push(row).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        let schema = reader.schema();
+        let fields: Vec<_> = schema
+            .fields()
+            .iter()
+            .map(|field| (field.name().clone(), field.is_nullable()))
+            .collect();
+        assert_eq!(
+            fields,
+            [("id".to_string(), false), ("failure".to_string(), true)]
+        );
+        let mut read_rows = Vec::new();
+        for batch in reader {
+            let batch = batch.unwrap();
+            let columns: Vec<&UInt64Array> = batch
+                .columns()
+                .iter()
+                .map(|column| column.as_any().downcast_ref::<UInt64Array>().unwrap())
+                .collect();
+            for i in 0..batch.num_rows() {
+                read_rows
+                    .push([0, 1].map(|j| columns[j].is_valid(i).then_some(columns[j].value(i))));
+            }
+        }
+        assert_eq!(read_rows, rows);
+    }
+}
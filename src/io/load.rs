@@ -1,13 +1,15 @@
+use crate::dawg::binary::MAGIC;
 use crate::dawg::Dawg;
+use crate::graph::avl_graph::binary::BinaryLabel;
 use crate::graph::indexing::DefaultIx;
 use crate::weight::Weight;
-use bincode::deserialize_from;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::cmp::Eq;
 use std::error::Error;
 use std::fmt::Debug;
 use std::fs;
+use std::io::{Read, Seek, SeekFrom};
 
 use crate::memory_backing::{CacheConfig, DiskBacking};
 
@@ -17,18 +19,35 @@ pub trait Load {
         Self: Sized;
 }
 
-// load_path should be a file with a serialized Dawg object.
+// load_path should be a file saved from a RAM DAWG. Files saved with bincode by older versions
+// are still loaded with the bincode-compat feature, which is on by default.
 impl<E, W> Load for Dawg<E, W>
 where
-    E: Eq + Copy + Debug + for<'de> Deserialize<'de>,
+    E: Eq + Ord + Copy + Debug + Serialize + for<'de> Deserialize<'de> + BinaryLabel,
     W: Weight + Copy + Serialize + for<'a> Deserialize<'a> + Clone,
 {
     fn load(load_path: &str, _cache_config: CacheConfig) -> Result<Self, Box<dyn Error>> {
-        let file = fs::OpenOptions::new().read(true).open(load_path)?;
-        Ok(deserialize_from(&file)?)
+        let mut file = fs::OpenOptions::new().read(true).open(load_path)?;
+        let mut magic = [0; 8];
+        let is_binary = file.read_exact(&mut magic).is_ok() && &magic == MAGIC;
+        file.seek(SeekFrom::Start(0))?;
+        if is_binary {
+            return Ok(Dawg::read_from(file)?);
+        }
+        load_bincode(file)
     }
 }
 
+#[cfg(feature = "bincode-compat")]
+fn load_bincode<T: DeserializeOwned>(file: fs::File) -> Result<T, Box<dyn Error>> {
+    Ok(bincode::deserialize_from(std::io::BufReader::new(file))?)
+}
+
+#[cfg(not(feature = "bincode-compat"))]
+fn load_bincode<T>(_file: fs::File) -> Result<T, Box<dyn Error>> {
+    Err("Not a binary DAWG file; files saved with bincode need the bincode-compat feature".into())
+}
+
 // load_path should be a directory containing two nodes.vec and edges.vec.
 impl<E, W> Load for Dawg<E, W, DefaultIx, DiskBacking<W, E, DefaultIx>>
 where
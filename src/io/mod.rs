@@ -1,3 +1,5 @@
+pub mod arrow; // Arrow IPC tables of u64s, for pandas and other dataframe libraries.
 pub mod load;
+pub mod npy; // Streaming .npy arrays for downstream tooling.
 pub mod save;
 pub use self::save::Save;
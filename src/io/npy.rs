@@ -0,0 +1,99 @@
+// Streaming writer for 1D arrays in NumPy's .npy format (version 1.0), so results can be loaded
+// with `numpy.load` without going through Python while they are computed.
+//
+// The length isn't known until the array is finished, so a fixed-size header is reserved up front
+// and filled in by `finish`.
+
+use anyhow::Result;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+const HEADER_LEN: usize = 128; // Including the magic string, as NumPy recommends a multiple of 64.
+
+pub struct NpyWriter {
+    writer: BufWriter<File>,
+    len: usize,
+}
+
+impl NpyWriter {
+    /// Create a .npy file holding little-endian u64s.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&[b' '; HEADER_LEN])?;
+        Ok(Self { writer, len: 0 })
+    }
+
+    pub fn push(&mut self, value: u64) -> Result<()> {
+        self.writer.write_all(&value.to_le_bytes())?;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn extend<I: IntoIterator<Item = u64>>(&mut self, values: I) -> Result<()> {
+        for value in values {
+            self.push(value)?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Write the header now that the length is known.
+    pub fn finish(mut self) -> Result<()> {
+        let header = header(self.len);
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&header)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn header(len: usize) -> Vec<u8> {
+    let dict = format!(
+        "{{'descr': '<u8', 'fortran_order': False, 'shape': ({},), }}",
+        len
+    );
+    let mut header = MAGIC.to_vec();
+    let dict_len = HEADER_LEN - MAGIC.len() - 2;
+    header.extend_from_slice(&(dict_len as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    // Pad with spaces and end with a newline, as NumPy does.
+    header.resize(HEADER_LEN - 1, b' ');
+    header.push(b'\n');
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_npy_writer() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lengths.npy");
+        let mut writer = NpyWriter::create(&path).unwrap();
+        writer.extend([3, 1, 4]).unwrap();
+        assert_eq!(writer.len(), 3);
+        writer.finish().unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), HEADER_LEN + 3 * 8);
+        assert_eq!(&bytes[..MAGIC.len()], MAGIC);
+        let header = std::str::from_utf8(&bytes[10..HEADER_LEN]).unwrap();
+        assert!(header.starts_with("{'descr': '<u8', 'fortran_order': False, 'shape': (3,), }"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(
+            u64::from_le_bytes(bytes[HEADER_LEN + 8..HEADER_LEN + 16].try_into().unwrap()),
+            1
+        );
+    }
+}
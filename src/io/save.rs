@@ -1,7 +1,9 @@
 use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::token_backing::CdawgToken;
 use crate::cdawg::Cdawg;
 use crate::dawg::Dawg;
-use crate::graph::indexing::DefaultIx;
+use crate::graph::avl_graph::binary::BinaryLabel;
+use crate::graph::indexing::{DefaultIx, IndexType};
 use crate::memory_backing::{DiskBacking, RamBacking};
 use crate::weight::Weight;
 use serde::de::DeserializeOwned;
@@ -12,15 +14,14 @@ use serde::{Deserialize, Serialize};
 use std::cmp::Eq;
 use std::fmt::Debug;
 
-use bincode::serialize_into;
-
 pub trait Save {
     fn save(&self, save_path: &str) -> Result<(), Box<dyn Error>>;
 }
 
+// RAM DAWGs are saved in the binary format of dawg::binary.
 impl<E, W> Save for Dawg<E, W, DefaultIx, RamBacking<W, E, DefaultIx>>
 where
-    E: Eq + Copy + Debug + Serialize,
+    E: Eq + Ord + Copy + Debug + Serialize + for<'de> Deserialize<'de> + BinaryLabel,
     W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
 {
     fn save(&self, save_path: &str) -> Result<(), Box<dyn Error>> {
@@ -29,7 +30,7 @@ where
             .create(true)
             .truncate(true)
             .open(save_path)?;
-        serialize_into(&save_file, &self)?;
+        self.write_to(save_file)?;
         Ok(())
     }
 }
@@ -45,20 +46,24 @@ where
     }
 }
 
-impl<W> Save for Cdawg<W, DefaultIx, DiskBacking<W, CdawgEdgeWeight<DefaultIx>, DefaultIx>>
+impl<W, Ix, T> Save for Cdawg<W, Ix, DiskBacking<W, CdawgEdgeWeight<Ix>, Ix>, T>
 where
+    T: CdawgToken,
+    Ix: IndexType + Serialize + for<'de> Deserialize<'de>,
     W: Weight + Copy + Serialize + for<'de> Deserialize<'de> + Clone + Default,
-    CdawgEdgeWeight<DefaultIx>: Serialize + for<'de> Deserialize<'de>,
+    CdawgEdgeWeight<Ix>: Serialize + for<'de> Deserialize<'de>,
 {
     fn save(&self, save_path: &str) -> Result<(), Box<dyn Error>> {
         Ok(Cdawg::save_metadata(self, save_path)?)
     }
 }
 
-impl<W> Save for Cdawg<W, DefaultIx, RamBacking<W, CdawgEdgeWeight<DefaultIx>, DefaultIx>>
+impl<W, Ix, T> Save for Cdawg<W, Ix, RamBacking<W, CdawgEdgeWeight<Ix>, Ix>, T>
 where
+    T: CdawgToken,
+    Ix: IndexType + Serialize + for<'de> Deserialize<'de>,
     W: Weight + Serialize + for<'de> Deserialize<'de> + Clone + Default,
-    CdawgEdgeWeight<DefaultIx>: Serialize + for<'de> Deserialize<'de>,
+    CdawgEdgeWeight<Ix>: Serialize + for<'de> Deserialize<'de>,
 {
     fn save(&self, save_path: &str) -> Result<(), Box<dyn Error>> {
         // unimplemented!("Can't yet save CDAWGs on RAM");
@@ -1,3 +1,7 @@
+//! Build and query DAWGs and CDAWGs (compacted DAWGs) over large corpora.
+//!
+//! Most users only need the types in [`prelude`].
+
 extern crate anyhow;
 extern crate bincode;
 extern crate bitvec;
@@ -12,17 +16,32 @@ extern crate serde;
 extern crate serde_json;
 extern crate substring;
 extern crate tempfile;
+extern crate thiserror;
 extern crate tokenizers;
 extern crate unicode_segmentation;
 
+pub mod build_manifest;
+pub mod build_observer;
 pub mod build_stats;
+pub mod builder;
 pub mod cdawg;
 pub mod data_reader;
 pub mod dawg;
+pub mod error;
+pub mod eval_schedule;
 pub mod evaluator;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod graph;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod io;
 pub mod memory_backing;
-pub mod stat_utils;
+pub mod metrics;
+pub mod prelude;
+pub(crate) mod stat_utils;
+pub mod stats;
+pub mod suffix_array;
 pub mod tokenize;
+pub mod walker;
 pub mod weight;
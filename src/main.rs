@@ -12,21 +12,13 @@ extern crate serde;
 extern crate serde_json;
 extern crate substring;
 extern crate tempfile;
+extern crate thiserror;
 extern crate tokenizers;
 extern crate unicode_segmentation;
 
+mod backend;
 mod build_cdawg;
-mod build_stats;
-mod cdawg;
-mod data_reader;
-mod dawg;
-mod evaluator;
-mod graph;
-mod io;
-mod memory_backing;
-mod stat_utils;
-mod tokenize;
-mod weight;
+mod commands;
 
 use serde::{Deserialize, Serialize};
 use std::cmp::min;
@@ -34,29 +26,45 @@ use std::cmp::Ord;
 use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::fmt::Debug;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Instant;
 
-use io::Save;
+use rusty_dawg::io::Save;
 
 use clap::Parser;
 use std::fs;
 use std::mem::size_of;
 
-use kdam::{tqdm, BarExt};
-
-use crate::build_cdawg::build_cdawg;
-use crate::dawg::Dawg;
-use crate::evaluator::Evaluator;
-
-use crate::graph::avl_graph::edge::Edge;
-use crate::graph::avl_graph::node::Node;
-use crate::graph::indexing::DefaultIx;
-use crate::memory_backing::{CacheConfig, DiskBacking, MemoryBacking, RamBacking};
-
-use crate::data_reader::{DataReader, PileReader, TxtReader};
-
-use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
-use crate::tokenize::{NullTokenIndex, PretrainedTokenizer, TokenIndex, Tokenize};
-use crate::weight::DefaultWeight;
+use crate::backend::Backend;
+use crate::commands::Command;
+use rusty_dawg::build_observer::{BuildObserver, CsvMetricsObserver, ProgressBarObserver};
+use rusty_dawg::build_stats::BuildStats;
+use rusty_dawg::cdawg::domain_counts::DEFAULT_MAX_EXACT_DOMAINS;
+use rusty_dawg::dawg::parallel::ShardedBuilder;
+use rusty_dawg::dawg::Dawg;
+use rusty_dawg::eval_schedule::EvalSchedule;
+use rusty_dawg::evaluator::{MultiEvaluator, ResultsFormat, ResultsWriter};
+
+use rusty_dawg::graph::avl_graph::binary::BinaryLabel;
+use rusty_dawg::graph::avl_graph::edge::Edge;
+use rusty_dawg::graph::avl_graph::node::Node;
+use rusty_dawg::graph::indexing::{DefaultIx, IndexWidth};
+use rusty_dawg::memory_backing::{CacheConfig, CachePolicy, MemoryBacking, MemoryBudget};
+
+use rusty_dawg::build_manifest::BuildManifest;
+use rusty_dawg::data_reader::{
+    expand_train_path, DataReader, DocPiece, DocSplitter, DocumentFilter, FileDocsLog, JsonlReader,
+    MultiFileReader, PileReader, TxtReader,
+};
+use rusty_dawg::error::check_exists;
+
+use rusty_dawg::tokenize::char_level::N_BYTES;
+use rusty_dawg::tokenize::token_type::{scan_vocab_size, select_token_type, validate_index_width};
+use rusty_dawg::tokenize::{
+    load_tokenizer, DocumentSeparator, PretrainedTokenizer, Tokenize, BUILTIN_TOKENIZERS,
+};
+use rusty_dawg::weight::{CountMode, DefaultWeight, Weight, WeightType};
 
 // Node and edge weight types.
 type N = DefaultWeight;
@@ -67,13 +75,22 @@ author = "William Merrill <willm@nyu.edu>",
 version, about, long_about = None,
 )]
 pub struct Args {
-    /// Path to corpus DAWG is built on.
-    #[arg(long)]
-    train_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// Path to evaluation data. Optional.
+    /// Path to corpus DAWG is built on: a file, a directory of files, or a glob like
+    /// `data/*.jsonl.gz`. Files are read in sorted order. Required unless a subcommand is given.
     #[arg(long, default_value = "")]
-    test_path: String,
+    train_path: String,
+
+    // Documents read from each training file, filled in by the data reader for the manifest.
+    #[arg(skip)]
+    train_files: FileDocsLog,
+
+    /// Path to evaluation data. Optional. Can be passed multiple times to track metrics on
+    /// several test sets at once.
+    #[arg(long)]
+    test_path: Vec<String>,
 
     /// Where DAWG is saved. If saving to disk, will be treated as a directory; if
     /// serializing a RAM data structure, will be treated as a file.
@@ -84,8 +101,15 @@ pub struct Args {
     #[arg(long, default_value = "")]
     results_path: String,
 
-    /// Tokenizer to use. This can be `whitespace` or any huggingface tokenizer, e.g.,
-    /// `gpt2`, `bert-base-uncased`, etc.
+    /// Format of `results_path`: `jsonl` appends a record per evaluation and writes aggregates to
+    /// `<results_path>.summary.json` when the build finishes; `json` rewrites all the results at
+    /// each evaluation.
+    #[arg(long, default_value = "jsonl")]
+    results_format: ResultsFormat,
+
+    /// Tokenizer to use. This can be `whitespace`, `null` (whitespace-separated token IDs), `bytes`,
+    /// `chars`, or any huggingface tokenizer, e.g., `gpt2`, `bert-base-uncased`, etc. `bytes` and
+    /// `chars` index each UTF-8 byte or Unicode character as a token.
     #[arg(long, default_value = "gpt2")]
     tokenizer: String,
 
@@ -94,11 +118,28 @@ pub struct Args {
     #[arg(long, default_value = "txt")]
     data_reader: String,
 
-    /// Datatype used to represent tokens in a DAWG (no effect for CDAWG). Can be
-    /// `u16`, `u32`, or `usize`.
-    #[arg(long, default_value = "u16")]
+    /// Datatype used to represent tokens in a DAWG. Can be `auto`, `u16`, `u32`, or
+    /// `usize`. `auto` picks the smallest type that fits the tokenizer's vocabulary. CDAWGs
+    /// support `u16` and `u32`.
+    #[arg(long, default_value = "auto")]
     utype: String,
 
+    /// Bits per node and edge index: `40` (up to about 1.1T nodes or edges) or `64`. 64-bit
+    /// indices are only supported for CDAWGs, and make nodes and edges larger.
+    #[arg(long, default_value_t = 40)]
+    index_width: usize,
+
+    /// How node weights count: `exact`, or `approx` for 16-bit Morris counters that take 3B less
+    /// per node, with a relative standard error of about 1.6%. Only 40-bit indices are supported.
+    #[arg(long, default_value = "exact")]
+    count_mode: CountMode,
+
+    /// Node weight: `32` (32-bit lengths and failures, no counts, for lookup-only graphs), `40` or
+    /// `64`. Defaults to the one for `index_width`. Can't be combined with `--count-mode approx`,
+    /// which picks the approximate-count weight.
+    #[arg(long)]
+    weight_type: Option<WeightType>,
+
     /// Truncate evaluation data to this many tokens.
     #[arg(long, default_value_t = 0)]
     truncate_test: usize,
@@ -107,6 +148,16 @@ pub struct Args {
     #[arg(long, default_value_t = 0)]
     n_eval: usize,
 
+    /// Space the `n_eval` evaluations (or CDAWG statistics) logarithmically in the number of
+    /// tokens instead of evenly.
+    #[arg(long)]
+    eval_log_spaced: bool,
+
+    /// Evaluate (or compute CDAWG statistics) every this many seconds of wall-clock time,
+    /// instead of by token count.
+    #[arg(long)]
+    eval_every_secs: Option<f64>,
+
     /// Maximum suffix length to track when computing evaluation metrics.
     #[arg(long, default_value_t = 10)]
     max_length: u64,
@@ -120,10 +171,28 @@ pub struct Args {
     #[arg(long)]
     disk_path: Option<String>,
 
+    /// Only index documents matching this filter, e.g. `meta.pile_set_name=Github`,
+    /// `url~wikipedia\.org`, or `date>=2020-01-01`. Can be passed multiple times. Requires the
+    /// `pile` or `jsonl` data reader. Filters are recorded in the saved manifest.
+    #[arg(long)]
+    filter: Vec<DocumentFilter>,
+
     /// Token used to split documents when `data_reader` is `txt`.
     #[arg(long)]
     split_token: Option<String>,
 
+    /// Split documents longer than this many tokens into pseudo-documents.
+    #[arg(long)]
+    max_doc_tokens: Option<usize>,
+
+    /// Number of tokens shared by consecutive pieces of a split document.
+    #[arg(long, default_value_t = 0)]
+    doc_overlap: usize,
+
+    /// JSONL file recording the source document and token range of each pseudo-document.
+    #[arg(long)]
+    docs_path: Option<String>,
+
     /// Estimate of the number of nodes to allocate, expressed as a ratio of the
     /// estimated total number of tokens (`n_tokens`).
     #[arg(long, default_value_t = 2.)]
@@ -142,15 +211,35 @@ pub struct Args {
     #[arg(long, default_value_t = 0)]
     cache_size: usize,
 
+    /// RAM for caching a disk build, e.g. `32GB` or `512MiB`, instead of `cache_size`. The node and
+    /// edge cache sizes are derived from it, after setting aside the token cache and a margin.
+    #[arg(long, conflicts_with = "cache_size")]
+    memory_budget: Option<MemoryBudget>,
+
+    /// How the cache picks states and edges to keep: `lru`, `slru` (segmented LRU), or `pinned:N` to
+    /// also keep the first N of each, which are near the source, in RAM.
+    #[arg(long, default_value = "lru")]
+    cache_policy: CachePolicy,
+
+    /// Levels of each state's AVL tree of edges to read ahead in one batch when searching it on
+    /// disk. 0 disables prefetching.
+    #[arg(long, default_value_t = 0)]
+    prefetch_depth: usize,
+
     /// Amount of input to read, in bytes, at a time while consuming file.
     /// Defaults to 10 GB.
     #[arg(long, default_value_t = 10_000_000_000)]
     buf_size: usize,
 
-    /// Don't add document boundaries between adjacent documents.
+    /// Don't add document boundaries between adjacent documents. Same as `--doc-separator none`.
     #[arg(long, short, action)]
     single_string: bool,
 
+    /// How documents are separated: `reserved` ends each document with a reserved token, so matches
+    /// never span documents; `eos` appends the tokenizer's EOS token; `none` adds nothing.
+    #[arg(long, default_value = "reserved")]
+    doc_separator: String,
+
     // CDAWG args.
     /// Build CDAWG instead of DAWG.
     #[arg(long, short, action)]
@@ -160,7 +249,47 @@ pub struct Args {
     #[arg(long)]
     train_vec_path: Option<String>,
 
-    /// Number of tokens to wait before computing CDAWG statistics.
+    /// Number of tokens from `train_vec_path` to cache in RAM while building a CDAWG. 0 disables
+    /// the cache.
+    #[arg(long, default_value_t = 0)]
+    token_cache_size: usize,
+
+    /// Number of consecutive tokens read and cached together.
+    #[arg(long, default_value_t = 1024)]
+    token_block_size: usize,
+
+    /// Store `train_vec_path` compressed, in frames of this many tokens. Decompressed frames are
+    /// cached in RAM, up to `token_cache_size` tokens but at least one frame.
+    #[arg(long)]
+    token_frame_size: Option<usize>,
+
+    /// Reopen the CDAWG previously built on disk at `disk_path` and add the documents in
+    /// `train_path` to it. `train_vec_path` must be the tokens it was built on, unless they are
+    /// shared.
+    #[arg(long)]
+    resume: bool,
+
+    /// Checkpoint a CDAWG being built on disk at `disk_path` after the first document to end once
+    /// this many tokens have been added since the last checkpoint.
+    #[arg(long)]
+    checkpoint_every: Option<usize>,
+
+    /// Like `resume`, but first roll the CDAWG back to its last checkpoint and skip the documents
+    /// in `train_path` that it already contains.
+    #[arg(long)]
+    resume_from_checkpoint: bool,
+
+    /// Reference `train_vec_path` from the CDAWG metadata, with a hash to validate it on load, so
+    /// several indices can share one token vector.
+    #[arg(long)]
+    share_tokens: bool,
+
+    /// Save the token span of each document in the CDAWG metadata, so matches can be attributed
+    /// to the documents they occur in.
+    #[arg(long)]
+    doc_spans: bool,
+
+    /// Number of tokens to wait before computing (C)DAWG statistics.
     #[arg(long)]
     stats_threshold: Option<usize>,
 
@@ -168,6 +297,29 @@ pub struct Args {
     #[arg(long)]
     stats_path: Option<String>,
 
+    /// Also compute the distribution of next-token entropies over states and the growth in nodes
+    /// and edges with each CDAWG statistic. Counts are filled temporarily to compute entropies.
+    #[arg(long)]
+    detailed_stats: bool,
+
+    /// Text file to measure the average matched suffix length on with each CDAWG statistic.
+    /// Implies --detailed-stats.
+    #[arg(long)]
+    stats_held_out: Option<String>,
+
+    /// Path to a CSV file that (C)DAWG statistics are written to as they are computed.
+    #[arg(long)]
+    metrics_csv: Option<String>,
+
+    /// Print statistics of the built DAWG. `good-turing` prints the count-of-counts and
+    /// Good-Turing estimates for n-grams up to `max_length`.
+    #[arg(long)]
+    stats: Option<String>,
+
+    /// Number of tokens between snapshot markers saved in the CDAWG metadata.
+    #[arg(long)]
+    snapshot_every: Option<usize>,
+
     /// DiskVec path to use while traversing graph.
     #[arg(long)]
     count_path: Option<String>,
@@ -176,109 +328,254 @@ pub struct Args {
     #[arg(long)]
     no_counts: bool,
 
+    /// Also fill the number of documents each CDAWG state occurs in, for document frequency counts.
+    /// They are saved next to the graph as `doc_freqs.vec`.
+    #[arg(long)]
+    doc_frequencies: bool,
+
+    /// Also fill the count of each CDAWG state in each domain, with the Pile reader or the JSONL
+    /// reader with a domain key. They are saved next to the graph as `domain_counts.vec`.
+    #[arg(long)]
+    domain_counts: bool,
+
+    /// With `domain_counts`, count up to this many domains exactly, and sketch the counts of more.
+    #[arg(long, default_value_t = DEFAULT_MAX_EXACT_DOMAINS)]
+    max_exact_domains: usize,
+
+    /// After building, only keep the substrings that occur at least this many times. The pruned
+    /// graph is copied into RAM and saved to `save_path`.
+    #[arg(long)]
+    prune_min_count: Option<usize>,
+
     /// Build DAWG in RAM instead of on disk.
     #[arg(long)]
     ram: bool,
-    // FIXME: Below is causing issues, for whatever reason.
-    // Special arguments for JsonReader (not used for Pile).
-    // #[arg(long, default_value = "text")]
-    // jsonl_text_key: String,
-    // #[arg(long, default_value = "split")]
-    // jsonl_domain_key: String,
+
+    /// Build a DAWG on this many threads, each indexing a shard of the documents in RAM, and merge
+    /// the shards at the end. Doesn't support evaluation or `max_state_length`. For a CDAWG, fill the
+    /// counts on this many threads instead, which reads the whole graph into RAM.
+    #[arg(long, default_value_t = 1)]
+    num_threads: usize,
+
+    /// Key of the text of each document for the `jsonl` data reader. Keys can be nested with dots,
+    /// e.g. `metadata.text`.
+    #[arg(long, default_value = "text")]
+    jsonl_text_key: String,
+
+    /// Key of the domain of each document for the `jsonl` data reader, if any. Domains are used as
+    /// document IDs and numbered in order of first appearance.
+    #[arg(long)]
+    jsonl_domain_key: Option<String>,
 }
 
 impl Args {
-    pub fn get_cache_config(&self) -> CacheConfig {
-        // TODO: Generalize CacheConfig to store size info as well?
-        let nodes_ratio = self.nodes_ratio / (self.nodes_ratio + self.edges_ratio);
-        let edges_ratio = self.edges_ratio / (self.nodes_ratio + self.edges_ratio);
-        CacheConfig {
-            node_cache_size: (nodes_ratio * (self.cache_size as f64)).ceil() as usize,
-            edge_cache_size: (edges_ratio * (self.cache_size as f64)).ceil() as usize,
+    /// Schedule for evaluations or statistics. `every` is the number of tokens between them when
+    /// neither wall-clock nor log-spaced scheduling is requested.
+    pub fn get_eval_schedule(&self, every: usize) -> anyhow::Result<EvalSchedule> {
+        if self.eval_every_secs.is_some() && self.eval_log_spaced {
+            anyhow::bail!("--eval-every-secs and --eval-log-spaced are mutually exclusive");
+        }
+        if let Some(secs) = self.eval_every_secs {
+            return EvalSchedule::wall_clock(secs);
         }
+        if self.eval_log_spaced {
+            if self.n_eval == 0 {
+                anyhow::bail!("--eval-log-spaced requires --n-eval");
+            }
+            return Ok(EvalSchedule::log_spaced(self.n_tokens, self.n_eval));
+        }
+        Ok(EvalSchedule::linear(every))
     }
-}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+    pub fn get_doc_splitter(&self) -> anyhow::Result<Option<DocSplitter>> {
+        let max_doc_tokens = match self.max_doc_tokens {
+            Some(max_doc_tokens) => max_doc_tokens,
+            None if self.docs_path.is_some() => {
+                anyhow::bail!("--docs-path requires --max-doc-tokens")
+            }
+            None => return Ok(None),
+        };
+        let mut splitter = DocSplitter::new(max_doc_tokens, self.doc_overlap)?;
+        if let Some(ref docs_path) = self.docs_path {
+            splitter = splitter.with_sidecar(docs_path)?;
+        }
+        Ok(Some(splitter))
+    }
 
-    if args.cdawg {
-        return match args.disk_path.clone() {
-            Some(path) => {
-                if args.ram {
-                    println!("Building CDAWG in RAM but saving on disk...");
-                    type Mb = RamBacking<N, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
-                    let mb = Mb::default();
-                    return Ok(build_cdawg::<Mb>(args, mb)?);
+    /// Reader over the documents in the files of `train_path`, as selected by `data_reader`. The
+    /// documents read from each file are recorded in `train_files`.
+    pub fn get_data_reader(&self) -> anyhow::Result<Box<DataReader>> {
+        let structured = self.data_reader == "pile" || self.data_reader == "jsonl";
+        if !structured && !self.filter.is_empty() {
+            anyhow::bail!("--filter requires the pile or jsonl data reader");
+        }
+        let paths = expand_train_path(&self.train_path)?;
+        if paths.len() > 1 && self.data_reader == "jsonl" && self.jsonl_domain_key.is_some() {
+            anyhow::bail!("--jsonl-domain-key numbers domains per file, so it needs a single file");
+        }
+
+        let data_reader = self.data_reader.clone();
+        let filters = self.filter.clone();
+        let text_key = self.jsonl_text_key.clone();
+        let domain_key = self.jsonl_domain_key.clone();
+        let split_token = self.split_token.clone();
+        let max_buf_size = self.buf_size;
+        let open = move |path: &Path| -> anyhow::Result<Box<DataReader>> {
+            check_exists(path)?;
+            Ok(match data_reader.as_str() {
+                "pile" => Box::new(PileReader::new(path)?.with_filters(filters.clone())),
+                "jsonl" => Box::new(
+                    JsonlReader::new(path, text_key.clone(), domain_key.clone())?
+                        .with_filters(filters.clone()),
+                ),
+                _ => {
+                    let train_file = fs::File::open(path)?;
+                    let n_bytes = train_file.metadata()?.len();
+                    let buf_size: usize = min(n_bytes.try_into().unwrap(), max_buf_size);
+                    Box::new(TxtReader::new(train_file, buf_size, split_token.clone()))
                 }
-                println!("Building CDAWG on disk...");
-                type Mb = DiskBacking<N, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
-                let mb = Mb::new(path);
-                Ok(build_cdawg::<Mb>(args, mb)?)
-            }
-            None => {
-                println!("Building CDAWG in RAM...");
-                type Mb = RamBacking<N, CdawgEdgeWeight<DefaultIx>, DefaultIx>;
-                let mb = Mb::default();
-                Ok(build_cdawg::<Mb>(args, mb)?)
-            }
+            })
         };
+        let reader = MultiFileReader::new(paths, open).with_log(self.train_files.clone());
+        // The text reader numbers documents, so keep counting across files. Domains are kept.
+        Ok(if structured {
+            Box::new(reader)
+        } else {
+            Box::new(reader.with_offset_ids())
+        })
     }
 
-    match args.disk_path.clone() {
-        Some(path) => println!("DAWG on disk: {}", path),
-        None => println!("DAWG in RAM"),
-    };
+    /// Total size of the files of `train_path`, in bytes.
+    pub fn get_train_bytes(&self) -> anyhow::Result<u64> {
+        let mut n_bytes = 0;
+        for path in expand_train_path(&self.train_path)? {
+            n_bytes += fs::metadata(path)?.len();
+        }
+        Ok(n_bytes)
+    }
 
-    // Messy, but it works.
-    if args.utype == "u16" {
-        type E = u16;
-        match args.disk_path.clone() {
-            Some(path) => {
-                type Mb = DiskBacking<N, E, DefaultIx>;
-                let mb = Mb::new(path);
-                run_rusty_dawg::<E, Mb>(args, mb)
-            }
-            None => {
-                type Mb = RamBacking<N, E, DefaultIx>;
-                let mb = Mb::default();
-                run_rusty_dawg::<E, Mb>(args, mb)
-            }
+    pub fn get_separator(&self) -> anyhow::Result<DocumentSeparator> {
+        if self.single_string {
+            return Ok(DocumentSeparator::Concatenate);
         }
-    } else if args.utype == "u32" {
-        type E = u32;
-        match args.disk_path.clone() {
-            Some(path) => {
-                type Mb = DiskBacking<N, E, DefaultIx>;
-                let mb = Mb::new(path);
-                run_rusty_dawg::<E, Mb>(args, mb)
-            }
-            None => {
-                type Mb = RamBacking<N, E, DefaultIx>;
-                let mb = Mb::default();
-                run_rusty_dawg::<E, Mb>(args, mb)
-            }
+        let eos_id = match (self.doc_separator.as_str(), self.tokenizer.as_str()) {
+            ("eos", name) if BUILTIN_TOKENIZERS.contains(&name) => None,
+            ("eos", name) => PretrainedTokenizer::try_new(name)?.eos_id(),
+            _ => None,
+        };
+        DocumentSeparator::parse(&self.doc_separator, eos_id)
+    }
+
+    pub fn get_manifest(&self, separator: DocumentSeparator) -> anyhow::Result<BuildManifest> {
+        let vocab_hash = match self.tokenizer.as_str() {
+            name if BUILTIN_TOKENIZERS.contains(&name) => None,
+            name => Some(PretrainedTokenizer::try_new(name)?.vocab_hash()),
+        };
+        Ok(BuildManifest {
+            train_path: self.train_path.clone(),
+            data_reader: self.data_reader.clone(),
+            tokenizer: self.tokenizer.clone(),
+            vocab_hash,
+            filters: self.filter.clone(),
+            separator,
+            files: self.train_files.borrow().clone(),
+        })
+    }
+
+    /// Observers for the build: a progress bar, and a CSV metrics logger if `metrics_csv` is set.
+    pub fn get_build_observer(&self) -> anyhow::Result<Vec<Box<dyn BuildObserver>>> {
+        let mut observers: Vec<Box<dyn BuildObserver>> =
+            vec![Box::new(ProgressBarObserver::new(self.n_tokens))];
+        if let Some(ref path) = self.metrics_csv {
+            observers.push(Box::new(CsvMetricsObserver::create(path)?));
         }
-    } else if args.utype == "usize" {
-        type E = usize;
-        match args.disk_path.clone() {
-            Some(path) => {
-                type Mb = DiskBacking<N, E, DefaultIx>;
-                let mb = Mb::new(path);
-                run_rusty_dawg::<E, Mb>(args, mb)
+        Ok(observers)
+    }
+
+    /// Writer for evaluation results, if `results_path` is set.
+    pub fn get_results_writer(&self) -> anyhow::Result<Option<ResultsWriter>> {
+        if self.results_path.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(ResultsWriter::create(
+            &self.results_path,
+            self.results_format,
+        )?))
+    }
+
+    /// Cache sizes for a graph with nodes `N` and edges `E` indexed by `Ix`. With `memory_budget`,
+    /// they are derived from the budget after setting aside `token_cache_bytes`, and printed.
+    pub fn get_cache_config<N, E, Ix>(
+        &self,
+        token_cache_bytes: usize,
+    ) -> anyhow::Result<CacheConfig> {
+        let (node_cache_size, edge_cache_size) = match self.memory_budget {
+            Some(budget) => {
+                let plan = budget.plan::<N, E, Ix>(
+                    self.nodes_ratio,
+                    self.edges_ratio,
+                    self.cache_policy,
+                    token_cache_bytes,
+                )?;
+                println!("{}", plan);
+                (plan.node_cache_size, plan.edge_cache_size)
             }
             None => {
-                type Mb = RamBacking<N, E, DefaultIx>;
-                let mb = Mb::default();
-                run_rusty_dawg::<E, Mb>(args, mb)
+                let nodes_ratio = self.nodes_ratio / (self.nodes_ratio + self.edges_ratio);
+                let edges_ratio = self.edges_ratio / (self.nodes_ratio + self.edges_ratio);
+                (
+                    (nodes_ratio * (self.cache_size as f64)).ceil() as usize,
+                    (edges_ratio * (self.cache_size as f64)).ceil() as usize,
+                )
             }
-        }
-    } else {
-        panic!("Invalid usize type: {}", args.utype);
+        };
+        Ok(CacheConfig {
+            node_cache_size,
+            edge_cache_size,
+            policy: self.cache_policy,
+            prefetch_depth: self.prefetch_depth,
+        })
     }
 }
 
-fn run_rusty_dawg<E, Mb>(args: Args, mb: Mb) -> Result<(), Box<dyn std::error::Error>>
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    if let Some(command) = &args.command {
+        return Ok(command.run()?);
+    }
+    if args.train_path.is_empty() {
+        return Err("--train-path is required to build a (C)DAWG".into());
+    }
+
+    // Fail fast if the token type or index width can't represent the corpus.
+    let vocab_size = match args.tokenizer.as_str() {
+        "bytes" => Some(N_BYTES),
+        "whitespace" | "null" | "chars" => None,
+        name => Some(PretrainedTokenizer::try_new(name)?.vocab_size()),
+    };
+    let vocab_size = match vocab_size {
+        None if args.utype == "auto" => {
+            // Build the vocabulary in a first pass, so the token type can't overflow.
+            println!("Scanning {} for the vocabulary size...", args.train_path);
+            let texts = args
+                .get_data_reader()?
+                .map(|doc| doc.map(|(_, text)| Rc::unwrap_or_clone(text)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            scan_vocab_size(texts, &args.tokenizer)?
+        }
+        vocab_size => vocab_size,
+    };
+    let token_type = select_token_type(&args.utype, vocab_size)?;
+    let n_nodes = (args.nodes_ratio * (args.n_tokens as f64)).ceil() as usize;
+    let n_edges = (args.edges_ratio * (args.n_tokens as f64)).ceil() as usize;
+    let index_width = IndexWidth::new(args.index_width)?;
+    validate_index_width(index_width, args.n_tokens, n_nodes, n_edges)?;
+    println!("Token type: {}", token_type);
+
+    Backend::select(&args, token_type, index_width)?.run(args)
+}
+
+pub fn run_rusty_dawg<E, W, Mb>(args: Args, mb: Mb) -> Result<(), Box<dyn std::error::Error>>
 where
     E: Eq
         + Ord
@@ -288,95 +585,129 @@ where
         + Debug
         + TryInto<usize>
         + TryFrom<usize>
+        + Send
         + 'static
         + TryInto<u32>
         + TryFrom<u32>
-        + tokenize::end::End,
+        + rusty_dawg::tokenize::end::End
+        + BinaryLabel,
     usize: TryFrom<E>,
     u64: TryFrom<E>,
-    Mb: MemoryBacking<N, E, DefaultIx>,
+    W: Weight + Copy + Serialize + for<'a> Deserialize<'a> + Clone + Default + Send + 'static,
+    Mb: MemoryBacking<W, E, DefaultIx>,
     <E as TryFrom<usize>>::Error: Debug,
-    Dawg<E, N, DefaultIx, Mb>: io::Save,
+    Dawg<E, W, DefaultIx, Mb>: rusty_dawg::io::Save,
 {
     println!("sizeof(Ix) {}B", size_of::<DefaultIx>());
-    println!("sizeof(N) {}B", size_of::<N>());
+    println!("sizeof(W) {}B", size_of::<W>());
     println!("sizeof(E) {}B", size_of::<E>());
-    println!("sizeof(Node): {}B", size_of::<Node<N, DefaultIx>>());
+    println!("sizeof(Node): {}B", size_of::<Node<W, DefaultIx>>());
     println!("sizeof(Edge): {}B", size_of::<Edge<E, DefaultIx>>());
 
-    let mut index: Box<dyn Tokenize<E>> = if args.tokenizer == "whitespace" {
-        Box::new(TokenIndex::new())
-    } else if args.tokenizer == "null" {
-        Box::new(NullTokenIndex::new())
-    } else {
-        Box::new(PretrainedTokenizer::new(&args.tokenizer))
-    };
+    let mut index: Box<dyn Tokenize<E>> = load_tokenizer(&args.tokenizer)?;
 
-    let train_file = fs::File::open(args.train_path.as_str())?;
-    let n_bytes = train_file.metadata().unwrap().len();
-    let eval_threshold = if args.n_eval == 0 {
-        0
-    } else {
-        args.n_tokens / args.n_eval
-    };
-    let buf_size: usize = min(n_bytes.try_into().unwrap(), args.buf_size);
-    let reader: Box<DataReader> = if args.data_reader == "pile" {
-        Box::new(PileReader::new(args.train_path.clone()).unwrap())
-    } else {
-        Box::new(TxtReader::new(
-            train_file,
-            buf_size,
-            args.split_token.clone(),
-        ))
-    };
+    let n_bytes = args.get_train_bytes()?;
+    let eval_threshold = args.n_tokens.checked_div(args.n_eval).unwrap_or(0);
+    let mut eval_schedule = args.get_eval_schedule(eval_threshold)?;
+    let reader = args.get_data_reader()?;
 
-    let test_raw: String = if args.test_path.is_empty() {
-        "".to_string()
-    } else {
-        let path = args.test_path.as_str();
-        fs::read_to_string(path).unwrap_or_else(|_| panic!("Could not load test from {}", path))
-    };
-    index.build(&test_raw); // Either the tokenizer must be pretrained or test must contain all tokens!
-    let doc_id_token = E::try_from(index.get_count()).unwrap(); // The token used to store document IDs.
-    let mut test: Vec<E> = index.tokenize(&test_raw);
-    let old_test_len = test.len();
-    if args.truncate_test > 0 {
-        test = test[0..args.truncate_test].to_vec();
+    let mut tests_raw: Vec<(String, String)> = Vec::new();
+    for path in args.test_path.iter() {
+        if tests_raw.iter().any(|(other, _)| other == path) {
+            return Err(format!("Test path {} was passed more than once", path).into());
+        }
+        let test_raw = fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("Could not load test from {}", path));
+        index.try_build(&test_raw)?; // Either the tokenizer must be pretrained or test must contain all tokens!
+        tests_raw.push((path.clone(), test_raw));
     }
-    let mut evaluator = Evaluator::new(&test, args.max_length);
-    println!("#(test): {}/{}", test.len(), old_test_len);
+    let separator = args.get_separator()?;
+    let separator_token: Option<E> = separator.get_token()?;
+    let doc_id_token = E::end(); // The token used to store document IDs.
+    let mut tests: Vec<(String, Vec<E>)> = Vec::new();
+    for (path, test_raw) in tests_raw.iter() {
+        let mut test: Vec<E> = index.try_tokenize(test_raw)?;
+        let old_test_len = test.len();
+        if args.truncate_test > 0 && test.len() > args.truncate_test {
+            test = test[0..args.truncate_test].to_vec();
+        }
+        println!("#(test) {}: {}/{}", path, test.len(), old_test_len);
+        tests.push((path.clone(), test));
+    }
+    let mut evaluator = MultiEvaluator::new(&tests, args.max_length);
+    let mut results_writer = args.get_results_writer()?;
 
     let n_nodes = (args.nodes_ratio * (args.n_tokens as f64)).ceil() as usize;
     let n_edges = (args.edges_ratio * (args.n_tokens as f64)).ceil() as usize;
-    let cache_config = args.get_cache_config();
+    let cache_config =
+        args.get_cache_config::<Node<W, DefaultIx>, Edge<E, DefaultIx>, DefaultIx>(0)?;
     let max_length: Option<u64> = if !args.max_state_length.is_negative() {
         Some(args.max_state_length.try_into().unwrap())
     } else {
         None
     };
 
-    let mut dawg: Dawg<E, N, DefaultIx, Mb> =
-        Dawg::with_capacity_mb(mb, max_length, n_nodes, n_edges, cache_config);
-
+    let mut splitter = args.get_doc_splitter()?;
+    let mut observer = args.get_build_observer()?;
+    let mut stats_schedule = args.get_eval_schedule(args.stats_threshold.unwrap_or(0))?;
+    let start_time = Instant::now();
     let mut idx = 0;
-    let mut last = dawg.get_initial();
-    let mut length = 0;
-    let mut pbar = tqdm!(total = args.n_tokens);
-    for (doc_id, doc) in reader {
-        let tokens = index.tokenize(doc.as_str());
-        for token in &tokens {
-            (last, length) = dawg.extend(*token, last, length);
-            if eval_threshold != 0 && idx % eval_threshold == 0 && idx != 0 {
-                println!("Evaluating...");
-                evaluator.evaluate(&dawg, idx);
-                if !args.results_path.is_empty() {
-                    evaluator.to_json(&args.results_path)?;
+    let mut n_docs = 0;
+    let dawg: Dawg<E, W, DefaultIx, Mb> = if args.num_threads > 1 {
+        if max_length.is_some() {
+            return Err("--max-state-length is not supported with --num-threads".into());
+        }
+        if !tests.is_empty() {
+            return Err("Evaluation is not supported with --num-threads".into());
+        }
+        let mut builder = ShardedBuilder::<E, W>::new(args.num_threads, doc_id_token)?;
+        for_each_piece(reader, &mut *index, &mut splitter, |piece_id, tokens| {
+            builder.add_document(piece_id, tokens.to_vec())?;
+            idx += tokens.len();
+            n_docs += 1;
+            observer.on_token(idx)?;
+            observer.on_document(n_docs, idx)?;
+            Ok(())
+        })?;
+        println!("Merging {} shards...", args.num_threads);
+        builder.finish(mb)?
+    } else {
+        let mut dawg = Dawg::with_capacity_mb(mb, max_length, n_nodes, n_edges, cache_config);
+        let mut last = dawg.get_initial();
+        let mut length = 0;
+        for_each_piece(reader, &mut *index, &mut splitter, |piece_id, tokens| {
+            for token in tokens {
+                (last, length) = dawg.extend(*token, last, length);
+                if eval_schedule.should_eval(idx) {
+                    println!("Evaluating...");
+                    evaluator.evaluate(&dawg, idx);
+                    if let Some(ref mut writer) = results_writer {
+                        writer.write(&evaluator)?;
+                    }
+                }
+                idx += 1;
+                observer.on_token(idx)?;
+                if stats_schedule.should_eval(idx) {
+                    let elapsed_time = start_time.elapsed().as_secs_f32();
+                    let stats = BuildStats::from_dawg(&dawg, idx, n_bytes, elapsed_time);
+                    observer.on_node_stats(&stats)?;
                 }
             }
-            idx += 1;
-            let _ = pbar.update(1);
-        }
-        (last, length) = dawg.end_document(last, doc_id_token, doc_id.try_into().unwrap());
+            if separator.ends_documents() {
+                (last, length) = dawg.end_document(last, doc_id_token, piece_id);
+            } else if let Some(token) = separator_token {
+                (last, length) = dawg.extend(token, last, length);
+            }
+            n_docs += 1;
+            observer.on_document(n_docs, idx)?;
+            Ok(())
+        })?;
+        dawg
+    };
+    let stats = BuildStats::from_dawg(&dawg, idx, n_bytes, start_time.elapsed().as_secs_f32());
+    observer.on_node_stats(&stats)?;
+    if let Some(writer) = results_writer {
+        writer.finish(&evaluator)?;
     }
 
     eprintln!();
@@ -397,11 +728,87 @@ where
         dawg.edge_count()
     );
     println!("  Balance ratio: {}", dawg.balance_ratio(1));
+    println!("  Counts: {}", stats.describe_counts());
+    if args.stats.as_deref() == Some("good-turing") {
+        print_good_turing(&dawg, args.max_length);
+    }
 
     if !args.save_path.is_empty() {
         println!("Saving DAWG...");
-        dawg.save(&args.save_path)?;
+        match args.prune_min_count {
+            Some(min_count) => {
+                let pruned = dawg.prune_by_count(min_count);
+                println!(
+                    "  Pruned to count >= {}: nodes={}, edges={}",
+                    min_count,
+                    pruned.node_count(),
+                    pruned.edge_count()
+                );
+                pruned.save(&args.save_path)?;
+            }
+            None => dawg.save(&args.save_path)?,
+        }
+        args.get_manifest(separator)?
+            .save_next_to(&args.save_path)?;
         println!("Successfully saved DAWG to {}!", &args.save_path);
     }
     Ok(())
 }
+
+// Print the count-of-counts and Good-Turing estimates for n-grams up to `max_n`.
+fn print_good_turing<E, W, Mb>(dawg: &Dawg<E, W, DefaultIx, Mb>, max_n: u64)
+where
+    E: Eq + Ord + Serialize + for<'a> Deserialize<'a> + Copy + Debug,
+    W: Weight + Serialize + for<'a> Deserialize<'a> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    println!("Good-Turing statistics:");
+    for (idx, counts) in rusty_dawg::stats::count_of_counts_by_length(dawg, max_n)
+        .iter()
+        .enumerate()
+    {
+        println!(
+            "  n={}: types={} N1={} N2={} N3={} r*(1)={:.3} r*(2)={:.3} coverage={:.4}",
+            idx + 1,
+            counts.n_types(),
+            counts.get(1),
+            counts.get(2),
+            counts.get(3),
+            counts.adjusted_count(1),
+            counts.adjusted_count(2),
+            counts.coverage()
+        );
+    }
+}
+
+// Tokenize each document, split it into pieces, and call `f` with the ID and tokens of each piece.
+fn for_each_piece<E, F>(
+    reader: Box<DataReader>,
+    index: &mut dyn Tokenize<E>,
+    splitter: &mut Option<DocSplitter>,
+    mut f: F,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    E: Eq + Serialize + Copy + Debug,
+    F: FnMut(u64, &[E]) -> Result<(), Box<dyn std::error::Error>>,
+{
+    for doc in reader {
+        let (doc_id, doc) = doc?;
+        let tokens = index.try_tokenize(doc.as_str())?;
+        let pieces = match splitter {
+            Some(ref mut splitter) => splitter.split(doc_id, tokens.len())?,
+            None => vec![DocPiece::whole(doc_id, tokens.len())],
+        };
+        for piece in pieces {
+            f(
+                piece.doc_id.try_into().unwrap(),
+                &tokens[piece.start..piece.end],
+            )?;
+        }
+    }
+    if let Some(ref mut splitter) = splitter {
+        splitter.flush()?;
+    }
+    Ok(())
+}
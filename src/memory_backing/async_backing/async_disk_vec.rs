@@ -0,0 +1,143 @@
+use anyhow::{bail, Result};
+use bincode::Options;
+use memmap2::MmapOptions;
+use serde::de::DeserializeOwned;
+use std::fs::File;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::memory_backing::vec_backing::format;
+use crate::metrics;
+
+/// A read-only `DiskVec<T>` whose reads are awaited instead of blocking. Cloning it is cheap.
+pub struct AsyncDiskVec<T> {
+    file: Arc<File>,
+    item_size: usize,
+    offset: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+// Derived Clone would require T to be Clone.
+impl<T> Clone for AsyncDiskVec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            file: self.file.clone(),
+            item_size: self.item_size,
+            offset: self.offset,
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> AsyncDiskVec<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    /// Open a file saved by `DiskVec`, checking its header like `DiskVec::load`.
+    pub fn load<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Result<Self> {
+        let item_size = std::mem::size_of::<T>();
+        let file = File::options().read(true).open(&path)?;
+        let (offset, len) = {
+            let mmap = unsafe { MmapOptions::new().map(&file)? };
+            format::check_or_legacy(path.as_ref(), &mmap, item_size, false)?
+        };
+        Ok(Self {
+            file: Arc::new(file),
+            item_size,
+            offset,
+            len,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the item at the given index.
+    pub async fn get(&self, index: usize) -> Result<T> {
+        let mut items = self.get_many(vec![index]).await?;
+        Ok(items.pop().unwrap())
+    }
+
+    /// Get the items at the given indices, in order, in one trip to the blocking pool.
+    pub async fn get_many(&self, indices: Vec<usize>) -> Result<Vec<T>> {
+        if let Some(index) = indices.iter().find(|&&index| index >= self.len) {
+            bail!(
+                "index {} out of bounds for AsyncDiskVec of size {}",
+                index,
+                self.len
+            );
+        }
+        let file = self.file.clone();
+        let (item_size, offset) = (self.item_size, self.offset);
+        tokio::task::spawn_blocking(move || {
+            let mut bytes = vec![0; item_size];
+            indices
+                .iter()
+                .map(|&index| {
+                    read_exact_at(&file, &mut bytes, (offset + index * item_size) as u64)?;
+                    metrics::record_disk_read(item_size);
+                    Ok(bincode::DefaultOptions::new()
+                        .with_fixint_encoding()
+                        .allow_trailing_bytes()
+                        .deserialize::<T>(&bytes)?)
+                })
+                .collect()
+        })
+        .await?
+    }
+}
+
+// Positioned reads don't move a shared cursor, so concurrent reads of one file don't race.
+#[cfg(unix)]
+fn read_exact_at(file: &File, bytes: &mut [u8], offset: u64) -> std::io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(bytes, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut bytes: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !bytes.is_empty() {
+        match file.seek_read(bytes, offset)? {
+            0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            n => {
+                bytes = &mut bytes[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_backing::DiskVec;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_get_many() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("vec.bin");
+        let vec: Vec<u64> = (0..100).map(|x| x * x).collect();
+        DiskVec::from_vec(&vec, &path).unwrap();
+
+        let async_vec: AsyncDiskVec<u64> = AsyncDiskVec::load(&path).unwrap();
+        assert_eq!(async_vec.len(), 100);
+        assert_eq!(async_vec.get(7).await.unwrap(), 49);
+        assert_eq!(
+            async_vec.get_many(vec![99, 0, 10]).await.unwrap(),
+            vec![9801, 0, 100]
+        );
+        assert!(async_vec.get(100).await.is_err());
+    }
+}
@@ -0,0 +1,10 @@
+// Read-only async access to DiskVec files, built with the `async` feature.
+//
+// A `DiskVec` reads through an mmap, so a page fault blocks whichever thread touches it, and on an
+// async runtime every other task scheduled on that worker stalls with it. `AsyncDiskVec` instead
+// reads items with positioned file reads on tokio's blocking pool, so a server can keep thousands of
+// queries in flight. Nothing can be written through it; build and save with the other backings.
+
+mod async_disk_vec;
+
+pub use self::async_disk_vec::AsyncDiskVec;
@@ -0,0 +1,81 @@
+// Disk backing with nodes stored as a struct of arrays.
+//
+// Like `DiskBacking`, but each node field lives in its own file (lengths.vec, counts.vec,
+// failures.vec, first_edges.vec), so traversals that only need some fields read less memory. Edges
+// are stored as in `DiskBacking`.
+
+mod node_columns;
+
+use crate::graph::avl_graph::edge::Edge;
+
+use crate::graph::indexing::IndexType;
+use crate::memory_backing::disk_backing::disk_mut_refs::DiskEdgeMutRef;
+use crate::memory_backing::disk_backing::vec::Vec;
+use crate::memory_backing::{MemoryBacking, VecCacheConfig};
+use crate::weight::Weight;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::create_dir_all;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+pub use self::node_columns::{ColumnarNodeMutRef, ColumnarNodeRef, NodeColumns};
+
+#[derive(Clone)]
+pub struct ColumnarBacking<N, E, Ix> {
+    dir_path: Box<Path>,
+    marker: PhantomData<(N, E, Ix)>,
+}
+
+impl<N, E, Ix> ColumnarBacking<N, E, Ix> {
+    pub fn new<P: AsRef<Path> + Clone + std::fmt::Debug>(dir_path: P) -> Self {
+        create_dir_all(dir_path.clone()).unwrap();
+        Self {
+            dir_path: Box::from(dir_path.as_ref()),
+            marker: PhantomData,
+        }
+    }
+
+    pub fn get_lengths_path(&self) -> PathBuf {
+        self.dir_path.join("lengths.vec")
+    }
+
+    pub fn get_counts_path(&self) -> PathBuf {
+        self.dir_path.join("counts.vec")
+    }
+
+    pub fn get_failures_path(&self) -> PathBuf {
+        self.dir_path.join("failures.vec")
+    }
+
+    pub fn get_first_edges_path(&self) -> PathBuf {
+        self.dir_path.join("first_edges.vec")
+    }
+
+    pub fn get_edges_path(&self) -> PathBuf {
+        self.dir_path.join("edges.vec")
+    }
+}
+
+impl<N, E, Ix> MemoryBacking<N, E, Ix> for ColumnarBacking<N, E, Ix>
+where
+    Ix: IndexType + Copy + Serialize + DeserializeOwned,
+    N: Weight + Clone,
+    E: Copy + Serialize + DeserializeOwned + Default,
+{
+    type NodeRef = ColumnarNodeRef<N, Ix>;
+    type EdgeRef = Edge<E, Ix>;
+    type NodeMutRef = ColumnarNodeMutRef<Ix>;
+    type EdgeMutRef = DiskEdgeMutRef<E, Ix>;
+
+    type VecN = NodeColumns<N, Ix>;
+    type VecE = Vec<Edge<E, Ix>>;
+
+    fn new_node_vec(&self, capacity: Option<usize>, cache: VecCacheConfig) -> Self::VecN {
+        NodeColumns::new(self, capacity.unwrap_or(8), cache).unwrap()
+    }
+
+    fn new_edge_vec(&self, capacity: Option<usize>, cache: VecCacheConfig) -> Self::VecE {
+        Vec::new(self.get_edges_path(), capacity.unwrap_or(8), cache).unwrap()
+    }
+}
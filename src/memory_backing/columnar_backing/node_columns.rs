@@ -0,0 +1,267 @@
+// Nodes stored as separate columns on disk, one file per field.
+//
+// Lookups only read the columns they need: transitions read `first_edges`, and scoring reads
+// `counts`. The terminal flag is packed into the top bit of the length.
+
+use anyhow::Result;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::graph::avl_graph::node::{Node, NodeMutRef, NodeRef};
+use crate::graph::indexing::{EdgeIndex, IndexType, NodeIndex};
+use crate::memory_backing::{CachedDiskVec, VecBacking, VecCacheConfig};
+use crate::weight::Weight;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::ColumnarBacking;
+
+const TERMINAL_BIT: u64 = 1 << 63;
+
+pub struct Columns<Ix>
+where
+    Ix: IndexType,
+{
+    lengths: CachedDiskVec<u64>,
+    counts: CachedDiskVec<u64>,
+    failures: CachedDiskVec<NodeIndex<Ix>>,
+    first_edges: CachedDiskVec<EdgeIndex<Ix>>,
+}
+
+impl<Ix> Columns<Ix>
+where
+    Ix: IndexType + Serialize + DeserializeOwned,
+{
+    fn get_length(&mut self, index: usize) -> u64 {
+        self.lengths.get(index).unwrap() & !TERMINAL_BIT
+    }
+
+    fn is_terminal(&mut self, index: usize) -> bool {
+        self.lengths.get(index).unwrap() & TERMINAL_BIT != 0
+    }
+
+    fn get_count(&mut self, index: usize) -> usize {
+        self.counts.get(index).unwrap() as usize
+    }
+
+    fn get_failure(&mut self, index: usize) -> Option<NodeIndex<Ix>> {
+        let failure = self.failures.get(index).unwrap();
+        if failure == NodeIndex::end() {
+            None
+        } else {
+            Some(failure)
+        }
+    }
+
+    fn get_first_edge(&mut self, index: usize) -> EdgeIndex<Ix> {
+        self.first_edges.get(index).unwrap()
+    }
+
+    fn set_length(&mut self, index: usize, length: u64) {
+        let terminal = self.lengths.get(index).unwrap() & TERMINAL_BIT;
+        let _ = self.lengths.set(index, &(length | terminal));
+    }
+
+    fn set_terminal(&mut self, index: usize, terminal: bool) {
+        let length = self.get_length(index);
+        let bit = if terminal { TERMINAL_BIT } else { 0 };
+        let _ = self.lengths.set(index, &(length | bit));
+    }
+}
+
+pub struct NodeColumns<N, Ix>
+where
+    Ix: IndexType,
+{
+    columns: Rc<RefCell<Columns<Ix>>>,
+    marker: PhantomData<N>,
+}
+
+impl<N, Ix> NodeColumns<N, Ix>
+where
+    Ix: IndexType + Serialize + DeserializeOwned,
+{
+    pub fn new<E>(
+        mb: &ColumnarBacking<N, E, Ix>,
+        capacity: usize,
+        cache: impl Into<VecCacheConfig>,
+    ) -> Result<Self> {
+        let cache = cache.into();
+        let columns = Columns {
+            lengths: CachedDiskVec::new(mb.get_lengths_path(), capacity, cache)?,
+            counts: CachedDiskVec::new(mb.get_counts_path(), capacity, cache)?,
+            failures: CachedDiskVec::new(mb.get_failures_path(), capacity, cache)?,
+            first_edges: CachedDiskVec::new(mb.get_first_edges_path(), capacity, cache)?,
+        };
+        Ok(Self::from_columns(columns))
+    }
+
+    pub fn load<E>(
+        mb: &ColumnarBacking<N, E, Ix>,
+        cache: impl Into<VecCacheConfig>,
+    ) -> Result<Self> {
+        let cache = cache.into();
+        let columns = Columns {
+            lengths: CachedDiskVec::load(mb.get_lengths_path(), cache)?,
+            counts: CachedDiskVec::load(mb.get_counts_path(), cache)?,
+            failures: CachedDiskVec::load(mb.get_failures_path(), cache)?,
+            first_edges: CachedDiskVec::load(mb.get_first_edges_path(), cache)?,
+        };
+        Ok(Self::from_columns(columns))
+    }
+
+    fn from_columns(columns: Columns<Ix>) -> Self {
+        Self {
+            columns: Rc::new(RefCell::new(columns)),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<N, Ix> VecBacking<Node<N, Ix>> for NodeColumns<N, Ix>
+where
+    Ix: IndexType + Serialize + DeserializeOwned,
+    N: Weight,
+{
+    type TRef = ColumnarNodeRef<N, Ix>;
+    type TMutRef = ColumnarNodeMutRef<Ix>;
+
+    fn len(&self) -> usize {
+        self.columns.borrow().lengths.len()
+    }
+
+    fn push(&mut self, node: Node<N, Ix>) {
+        let mut columns = self.columns.borrow_mut();
+        let terminal = if node.weight.is_terminal() {
+            TERMINAL_BIT
+        } else {
+            0
+        };
+        let failure = match node.weight.get_failure() {
+            Some(phi) => NodeIndex::new(phi.index()),
+            None => NodeIndex::end(),
+        };
+        let _ = columns.lengths.push(&(node.weight.get_length() | terminal));
+        let _ = columns.counts.push(&(node.weight.get_count() as u64));
+        let _ = columns.failures.push(&failure);
+        let _ = columns.first_edges.push(&node.first_edge);
+    }
+
+    fn index(&self, index: usize) -> Self::TRef {
+        ColumnarNodeRef {
+            columns: self.columns.clone(),
+            index,
+            marker: PhantomData,
+        }
+    }
+
+    fn index_mut(&mut self, index: usize) -> Self::TMutRef {
+        ColumnarNodeMutRef {
+            columns: self.columns.clone(),
+            index,
+        }
+    }
+}
+
+/// Reads single fields of a node from their columns.
+pub struct ColumnarNodeRef<N, Ix>
+where
+    Ix: IndexType,
+{
+    columns: Rc<RefCell<Columns<Ix>>>,
+    index: usize,
+    marker: PhantomData<N>,
+}
+
+impl<N, Ix> NodeRef<N, Ix> for ColumnarNodeRef<N, Ix>
+where
+    Ix: IndexType + Serialize + DeserializeOwned,
+    N: Weight,
+{
+    fn get_weight(self) -> N
+    where
+        N: Clone,
+    {
+        let mut columns = self.columns.borrow_mut();
+        let failure = columns
+            .get_failure(self.index)
+            .map(|phi| NodeIndex::new(phi.index()));
+        let mut weight = N::new(
+            columns.get_length(self.index),
+            failure,
+            columns.get_count(self.index),
+        );
+        weight.set_terminal(columns.is_terminal(self.index));
+        weight
+    }
+
+    fn get_length(self) -> u64 {
+        self.columns.borrow_mut().get_length(self.index)
+    }
+
+    fn get_failure(self) -> Option<NodeIndex<Ix>> {
+        self.columns.borrow_mut().get_failure(self.index)
+    }
+
+    fn get_count(self) -> usize {
+        self.columns.borrow_mut().get_count(self.index)
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.columns.borrow_mut().is_terminal(self.index)
+    }
+
+    fn get_first_edge(self) -> EdgeIndex<Ix> {
+        self.columns.borrow_mut().get_first_edge(self.index)
+    }
+}
+
+/// Writes single fields of a node to their columns.
+pub struct ColumnarNodeMutRef<Ix>
+where
+    Ix: IndexType,
+{
+    columns: Rc<RefCell<Columns<Ix>>>,
+    index: usize,
+}
+
+impl<Ix> NodeMutRef<Ix> for ColumnarNodeMutRef<Ix>
+where
+    Ix: IndexType + Serialize + DeserializeOwned,
+{
+    fn set_length(self, length: u64) {
+        self.columns.borrow_mut().set_length(self.index, length);
+    }
+
+    fn set_failure(self, state: Option<NodeIndex<Ix>>) {
+        let failure = state.unwrap_or(NodeIndex::end());
+        let _ = self.columns.borrow_mut().failures.set(self.index, &failure);
+    }
+
+    fn increment_count(self) {
+        let mut columns = self.columns.borrow_mut();
+        let count = columns.counts.get(self.index).unwrap();
+        let _ = columns.counts.set(self.index, &(count + 1));
+    }
+
+    fn set_count(self, count: usize) {
+        let _ = self
+            .columns
+            .borrow_mut()
+            .counts
+            .set(self.index, &(count as u64));
+    }
+
+    fn set_terminal(self, terminal: bool) {
+        self.columns.borrow_mut().set_terminal(self.index, terminal);
+    }
+
+    fn set_first_edge(self, first_edge: EdgeIndex<Ix>) {
+        let _ = self
+            .columns
+            .borrow_mut()
+            .first_edges
+            .set(self.index, &first_edge);
+    }
+}
@@ -1,11 +1,11 @@
-mod disk_mut_refs;
-pub mod vec; // Implement VecBacking for DiskVec and DiskVecItem // Raw implementation of DiskVec data structure.
+pub(crate) mod disk_mut_refs;
+pub(crate) mod vec; // Implement VecBacking for DiskVec and DiskVecItem // Raw implementation of DiskVec data structure.
 
 use crate::graph::avl_graph::edge::Edge;
 use crate::graph::avl_graph::node::Node;
 
 use crate::graph::indexing::{EdgeIndex, IndexType, NodeIndex};
-use crate::memory_backing::MemoryBacking;
+use crate::memory_backing::{MemoryBacking, VecCacheConfig};
 use crate::weight::Weight;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -57,19 +57,19 @@ where
 
     // The disk-backed implementations of new_node_vec and new_edge_vec should pass file_path when they construct a new Vector.
 
-    fn new_node_vec(&self, capacity: Option<usize>, cache_size: usize) -> Self::VecN {
+    fn new_node_vec(&self, capacity: Option<usize>, cache: VecCacheConfig) -> Self::VecN {
         let path = self.get_nodes_path();
         match capacity {
-            Some(n) => Vec::new(path, n, cache_size).unwrap(),
-            None => Vec::new(path, 8, cache_size).unwrap(),
+            Some(n) => Vec::new(path, n, cache).unwrap(),
+            None => Vec::new(path, 8, cache).unwrap(),
         }
     }
 
-    fn new_edge_vec(&self, capacity: Option<usize>, cache_size: usize) -> Self::VecE {
+    fn new_edge_vec(&self, capacity: Option<usize>, cache: VecCacheConfig) -> Self::VecE {
         let path = self.get_edges_path();
         match capacity {
-            Some(n) => Vec::new(path, n, cache_size).unwrap(),
-            None => Vec::new(path, 8, cache_size).unwrap(),
+            Some(n) => Vec::new(path, n, cache).unwrap(),
+            None => Vec::new(path, 8, cache).unwrap(),
         }
     }
 }
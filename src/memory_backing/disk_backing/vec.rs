@@ -1,7 +1,7 @@
 // Implement the VecBacking interface for DiskVec.
 
 use super::disk_mut_refs::{DiskVecItem, MutRef};
-use crate::memory_backing::{CachedDiskVec, VecBacking};
+use crate::memory_backing::{CacheStats, CachedDiskVec, VecBacking, VecCacheConfig};
 use anyhow::Result;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -23,20 +23,38 @@ where
     pub fn new<P: AsRef<Path> + std::fmt::Debug>(
         path: P,
         capacity: usize,
-        cache_size: usize,
+        cache: impl Into<VecCacheConfig>,
     ) -> Result<Self> {
-        let disk_vec = CachedDiskVec::new(path, capacity, cache_size)?;
+        let disk_vec = CachedDiskVec::new(path, capacity, cache)?;
         Ok(Self {
             disk_vec: Rc::new(RefCell::new(disk_vec)),
         })
     }
 
-    pub fn load<P: AsRef<Path> + std::fmt::Debug>(path: P, cache_size: usize) -> Result<Self> {
-        let disk_vec = CachedDiskVec::load(path, cache_size)?;
+    pub fn load<P: AsRef<Path> + std::fmt::Debug>(
+        path: P,
+        cache: impl Into<VecCacheConfig>,
+    ) -> Result<Self> {
+        let disk_vec = CachedDiskVec::load(path, cache)?;
+        Ok(Self {
+            disk_vec: Rc::new(RefCell::new(disk_vec)),
+        })
+    }
+
+    pub fn open_mut<P: AsRef<Path> + std::fmt::Debug>(
+        path: P,
+        len: usize,
+        cache: impl Into<VecCacheConfig>,
+    ) -> Result<Self> {
+        let disk_vec = CachedDiskVec::open_mut(path, len, cache)?;
         Ok(Self {
             disk_vec: Rc::new(RefCell::new(disk_vec)),
         })
     }
+
+    pub fn get_cache_stats(&self) -> CacheStats {
+        self.disk_vec.borrow().get_cache_stats()
+    }
 }
 
 impl<T> VecBacking<T> for Vec<T>
@@ -61,6 +79,19 @@ where
     fn index_mut(&mut self, index: usize) -> T::MutRef {
         T::MutRef::new(self.disk_vec.clone(), index)
     }
+
+    fn flush(&self) -> Result<()> {
+        self.disk_vec.borrow().flush()
+    }
+
+    fn prefetch(&self, indices: &[usize]) {
+        // Readahead is only a hint, so failing to issue it isn't an error.
+        let _ = self.disk_vec.borrow().prefetch(indices);
+    }
+
+    fn get_prefetch_depth(&self) -> usize {
+        self.disk_vec.borrow().get_prefetch_depth()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,212 @@
+// Read-only vector of plain-old-data items mapped from a file in their in-memory layout.
+//
+// The file starts with a fixed-size header recording the item size, alignment and count, followed
+// by the items. Layouts are only guaranteed to match within one build, so loading fails if the size
+// or alignment differs from this build's.
+
+use anyhow::{bail, Result};
+use memmap2::{MmapMut, MmapOptions};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::path::{Path, PathBuf};
+
+use crate::memory_backing::{DiskVec, VecBacking};
+
+const MAGIC: &[u8; 8] = b"RDAWGPOD";
+const HEADER_LEN: usize = 64; // A multiple of the alignment of any item, so items stay aligned.
+
+pub struct MmapVec<T> {
+    mmap: MmapMut, // Private, so writes are never flushed to the file.
+    len: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T> MmapVec<T>
+where
+    T: Copy + 'static,
+{
+    /// Map the items written to `path` by `write`.
+    pub fn load<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Result<Self> {
+        let file = File::open(&path)?;
+        let mmap = unsafe { MmapOptions::new().map_copy(&file)? };
+        if mmap.len() < HEADER_LEN || &mmap[..MAGIC.len()] != MAGIC {
+            bail!("{path:?} is not a mapped vector file");
+        }
+        let (item_size, item_align, len) = (
+            read_header_field(&mmap, 0),
+            read_header_field(&mmap, 1),
+            read_header_field(&mmap, 2),
+        );
+        if item_size != size_of::<T>() || item_align != align_of::<T>() {
+            bail!(
+                "{path:?} holds items of size {} and alignment {}, but this build expects {} and {}",
+                item_size,
+                item_align,
+                size_of::<T>(),
+                align_of::<T>()
+            );
+        }
+        if mmap.len() != HEADER_LEN + len * item_size {
+            bail!("{path:?} is corrupt: expected {} items", len);
+        }
+        Ok(Self {
+            mmap,
+            len,
+            marker: PhantomData,
+        })
+    }
+
+    /// Write `len` items in their in-memory layout. The file is only moved to `path` once it is
+    /// complete.
+    pub fn write<P, I>(path: P, len: usize, items: I) -> Result<()>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = Result<T>>,
+    {
+        assert!(align_of::<T>() <= HEADER_LEN);
+        let tmp_path = get_tmp_path(path.as_ref());
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        file.set_len((HEADER_LEN + len * size_of::<T>()) as u64)?;
+        let mut mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+        mmap[..MAGIC.len()].copy_from_slice(MAGIC);
+        for (field, value) in [size_of::<T>(), align_of::<T>(), len]
+            .into_iter()
+            .enumerate()
+        {
+            let start = MAGIC.len() + 8 * field;
+            mmap[start..start + 8].copy_from_slice(&(value as u64).to_le_bytes());
+        }
+
+        let mut n_items = 0;
+        let data = unsafe { mmap.as_mut_ptr().add(HEADER_LEN) } as *mut T;
+        for item in items {
+            if n_items == len {
+                bail!("More than {} items were written", len);
+            }
+            unsafe { data.add(n_items).write(item?) };
+            n_items += 1;
+        }
+        if n_items != len {
+            bail!("Expected {} items, but only {} were written", len, n_items);
+        }
+        mmap.flush()?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Write the items of the DiskVec at `disk_path` to `path`, unless `path` is newer.
+    pub fn convert<P: AsRef<Path> + std::fmt::Debug>(disk_path: P, path: P) -> Result<()>
+    where
+        T: Serialize + DeserializeOwned + Default,
+    {
+        if is_newer(path.as_ref(), disk_path.as_ref())? {
+            return Ok(());
+        }
+        let disk_vec: DiskVec<T> = DiskVec::load(&disk_path)?;
+        Self::write(
+            path,
+            disk_vec.len(),
+            (0..disk_vec.len()).map(|idx| disk_vec.get(idx)),
+        )
+    }
+
+    fn as_ptr(&self) -> *const T {
+        unsafe { self.mmap.as_ptr().add(HEADER_LEN) as *const T }
+    }
+}
+
+impl<T> VecBacking<T> for MmapVec<T>
+where
+    T: Copy + 'static,
+{
+    type TRef = *const T;
+    type TMutRef = *mut T;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn push(&mut self, _item: T) {
+        panic!("MmapVec is read-only");
+    }
+
+    fn index(&self, index: usize) -> *const T {
+        assert!(index < self.len, "index {} out of bounds", index);
+        unsafe { self.as_ptr().add(index) }
+    }
+
+    fn index_mut(&mut self, index: usize) -> *mut T {
+        assert!(index < self.len, "index {} out of bounds", index);
+        unsafe { (self.mmap.as_mut_ptr().add(HEADER_LEN) as *mut T).add(index) }
+    }
+}
+
+fn read_header_field(mmap: &MmapMut, field: usize) -> usize {
+    let start = MAGIC.len() + 8 * field;
+    u64::from_le_bytes(mmap[start..start + 8].try_into().unwrap()) as usize
+}
+
+fn get_tmp_path(path: &Path) -> PathBuf {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    PathBuf::from(tmp_path)
+}
+
+// Whether `path` exists and was modified after `other`.
+fn is_newer(path: &Path, other: &Path) -> Result<bool> {
+    if !path.is_file() {
+        return Ok(false);
+    }
+    Ok(fs::metadata(path)?.modified()? >= fs::metadata(other)?.modified()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_load() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("items.pod");
+        let items: Vec<(u32, u16)> = vec![(1, 2), (3, 4), (5, 6)];
+        MmapVec::write(&path, items.len(), items.iter().copied().map(Ok)).unwrap();
+
+        let mut vec: MmapVec<(u32, u16)> = MmapVec::load(&path).unwrap();
+        assert_eq!(vec.len(), 3);
+        unsafe {
+            assert_eq!(*vec.index(1), (3, 4));
+            *vec.index_mut(1) = (7, 8);
+            assert_eq!(*vec.index(1), (7, 8));
+        }
+        // Writes stay private to the map.
+        let vec: MmapVec<(u32, u16)> = MmapVec::load(&path).unwrap();
+        unsafe { assert_eq!(*vec.index(1), (3, 4)) };
+
+        assert!(MmapVec::<u64>::load(&path).is_err());
+        assert!(MmapVec::write(&path, 4, items.iter().copied().map(Ok)).is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let tmp_dir = tempdir().unwrap();
+        let disk_path = tmp_dir.path().join("items.vec");
+        let path = tmp_dir.path().join("items.pod");
+        DiskVec::from_vec(&vec![10u64, 20, 30], &disk_path).unwrap();
+        MmapVec::<u64>::convert(&disk_path, &path).unwrap();
+
+        let vec: MmapVec<u64> = MmapVec::load(&path).unwrap();
+        let items: Vec<u64> = (0..vec.len())
+            .map(|idx| unsafe { *vec.index(idx) })
+            .collect();
+        assert_eq!(items, vec![10, 20, 30]);
+    }
+}
@@ -0,0 +1,80 @@
+// Read-only backing with nodes and edges mapped straight into memory, for serving queries.
+//
+// `DiskBacking` stores each item bincode-encoded, so every read deserializes a copy. This backing
+// maps `nodes.pod` and `edges.pod` instead, which hold the items in their in-memory layout, so a
+// reference is a pointer into the map as with `RamBacking`. The mapped files are written from the
+// `DiskBacking` files in the same directory when a graph is first loaded this way.
+
+mod mmap_vec;
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use crate::graph::avl_graph::edge::Edge;
+use crate::graph::avl_graph::node::Node;
+use crate::graph::indexing::IndexType;
+use crate::memory_backing::{DiskBacking, MemoryBacking, VecCacheConfig};
+use crate::weight::Weight;
+
+pub use self::mmap_vec::MmapVec;
+
+#[derive(Clone)]
+pub struct MmapBacking<N, E, Ix> {
+    dir_path: Box<Path>,
+    marker: PhantomData<(N, E, Ix)>,
+}
+
+impl<N, E, Ix> MmapBacking<N, E, Ix> {
+    pub fn new<P: AsRef<Path>>(dir_path: P) -> Self {
+        Self {
+            dir_path: Box::from(dir_path.as_ref()),
+            marker: PhantomData,
+        }
+    }
+
+    pub fn get_nodes_path(&self) -> PathBuf {
+        self.dir_path.join("nodes.pod")
+    }
+
+    pub fn get_edges_path(&self) -> PathBuf {
+        self.dir_path.join("edges.pod")
+    }
+
+    /// Write the mapped files from the `DiskBacking` files, unless they are already up to date.
+    pub fn convert(&self) -> Result<()>
+    where
+        Ix: IndexType + Serialize + DeserializeOwned + 'static,
+        N: Copy + Serialize + DeserializeOwned + Default + 'static,
+        E: Copy + Serialize + DeserializeOwned + Default + 'static,
+    {
+        let disk_mb: DiskBacking<N, E, Ix> = DiskBacking::new(self.dir_path.to_path_buf());
+        MmapVec::<Node<N, Ix>>::convert(disk_mb.get_nodes_path(), self.get_nodes_path())?;
+        MmapVec::<Edge<E, Ix>>::convert(disk_mb.get_edges_path(), self.get_edges_path())
+    }
+}
+
+impl<N, E, Ix> MemoryBacking<N, E, Ix> for MmapBacking<N, E, Ix>
+where
+    Ix: IndexType + Copy + 'static,
+    N: Weight + Copy + 'static,
+    E: Copy + 'static,
+{
+    type NodeRef = *const Node<N, Ix>;
+    type EdgeRef = *const Edge<E, Ix>;
+    type NodeMutRef = *mut Node<N, Ix>;
+    type EdgeMutRef = *mut Edge<E, Ix>;
+
+    type VecN = MmapVec<Node<N, Ix>>;
+    type VecE = MmapVec<Edge<E, Ix>>;
+
+    fn new_node_vec(&self, _capacity: Option<usize>, _cache: VecCacheConfig) -> Self::VecN {
+        panic!("MmapBacking is read-only; open a graph with AvlGraph::load_mmap");
+    }
+
+    fn new_edge_vec(&self, _capacity: Option<usize>, _cache: VecCacheConfig) -> Self::VecE {
+        panic!("MmapBacking is read-only; open a graph with AvlGraph::load_mmap");
+    }
+}
@@ -1,10 +1,25 @@
-pub mod disk_backing;
+#[cfg(feature = "async")]
+pub(crate) mod async_backing;
+pub mod columnar_backing;
+pub(crate) mod disk_backing;
+pub mod mmap_backing;
 pub mod ram_backing;
+pub mod tiered_backing;
 pub mod vec_backing;
 
+#[cfg(feature = "async")]
+pub use self::async_backing::AsyncDiskVec;
+pub use self::columnar_backing::ColumnarBacking;
 pub use self::disk_backing::DiskBacking;
+pub use self::mmap_backing::MmapBacking;
 pub use self::ram_backing::RamBacking;
-pub use self::vec_backing::{CacheConfig, CachedDiskVec, DiskVec};
+pub use self::tiered_backing::{TierPolicy, TieredBacking};
+pub use self::vec_backing::{
+    convert_disk_vec, CacheConfig, CachePlan, CachePolicy, CacheStats, CachedDiskVec, DiskVec,
+    MemoryBudget, VecCacheConfig,
+};
+
+use anyhow::Result;
 
 use crate::graph::avl_graph::edge::{Edge, EdgeMutRef, EdgeRef};
 use crate::graph::avl_graph::node::{Node, NodeMutRef, NodeRef};
@@ -24,9 +39,9 @@ where
     type VecN: VecBacking<Node<N, Ix>, TRef = Self::NodeRef, TMutRef = Self::NodeMutRef>;
     type VecE: VecBacking<Edge<E, Ix>, TRef = Self::EdgeRef, TMutRef = Self::EdgeMutRef>;
 
-    fn new_node_vec(&self, capacity: Option<usize>, cache_size: usize) -> Self::VecN;
+    fn new_node_vec(&self, capacity: Option<usize>, cache: VecCacheConfig) -> Self::VecN;
 
-    fn new_edge_vec(&self, capacity: Option<usize>, cache_size: usize) -> Self::VecE;
+    fn new_edge_vec(&self, capacity: Option<usize>, cache: VecCacheConfig) -> Self::VecE;
 }
 
 pub trait VecBacking<T> {
@@ -41,6 +56,22 @@ pub trait VecBacking<T> {
 
     fn index_mut(&mut self, index: usize) -> Self::TMutRef;
 
+    // Mark a state to roll back to, for backings that support it (see JournaledVec).
+    fn checkpoint(&mut self) {}
+
+    // Write changes through to disk, for backings that store items in files.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    // Hint that the items at these indices will be read soon, for backings that read from disk.
+    fn prefetch(&self, _indices: &[usize]) {}
+
+    // How many levels of an AVL edge tree to prefetch before searching it (0 to disable).
+    fn get_prefetch_depth(&self) -> usize {
+        0
+    }
+
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
@@ -0,0 +1,188 @@
+// A Vec that can roll back to its last checkpoint, used to undo the most recent document in RAM.
+//
+// Items pushed since the checkpoint are removed by truncating. Items that existed at the checkpoint
+// are copied into an append-only log the first time they are mutated, with a bit vector marking
+// which ones are already logged, and restored from it on rollback.
+//
+// Checkpoints are lazy: `checkpoint()` only takes effect at the next mutation. So right after a
+// document ends, rollback still undoes that whole document. Journaling is off until `enable()` is
+// called, and then costs one copy per distinct item mutated in each document.
+
+use bitvec::prelude::BitVec;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::Deref;
+
+use crate::memory_backing::VecBacking;
+
+pub struct JournaledVec<T> {
+    items: Vec<T>,
+    enabled: bool,
+    pending: bool,         // Whether a checkpoint was requested but not yet taken.
+    checkpoint_len: usize, // Number of items at the checkpoint.
+    log: Vec<(usize, T)>,  // Values at the checkpoint of items mutated since then.
+    logged: BitVec,        // Which items below checkpoint_len are in the log.
+}
+
+impl<T: Clone> JournaledVec<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(capacity),
+            enabled: false,
+            pending: false,
+            checkpoint_len: 0,
+            log: Vec::new(),
+            logged: BitVec::new(),
+        }
+    }
+
+    /// Start journaling from the current state.
+    pub fn enable(&mut self) {
+        self.enabled = true;
+        self.pending = true;
+    }
+
+    /// Mark the current state as the one `rollback` returns to.
+    pub fn checkpoint(&mut self) {
+        if self.enabled {
+            self.pending = true;
+        }
+    }
+
+    /// Undo all changes since the last checkpoint. Returns false if journaling isn't enabled.
+    pub fn rollback(&mut self) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        self.items.truncate(self.checkpoint_len);
+        for (idx, item) in self.log.drain(..).rev() {
+            self.items[idx] = item;
+            self.logged.set(idx, false);
+        }
+        true
+    }
+
+    fn before_mutation(&mut self) {
+        if self.pending {
+            for (idx, _) in self.log.iter() {
+                self.logged.set(*idx, false);
+            }
+            self.log.clear();
+            self.checkpoint_len = self.items.len();
+            if self.logged.len() < self.checkpoint_len {
+                self.logged.resize(self.checkpoint_len, false);
+            }
+            self.pending = false;
+        }
+    }
+}
+
+impl<T: Clone> Default for JournaledVec<T> {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+// Clones only copy the items, and don't journal.
+impl<T: Clone> Clone for JournaledVec<T> {
+    fn clone(&self) -> Self {
+        let mut vec = Self::with_capacity(self.items.len());
+        vec.items.extend_from_slice(&self.items);
+        vec
+    }
+}
+
+impl<T> Deref for JournaledVec<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.items
+    }
+}
+
+impl<T: Clone> VecBacking<T> for JournaledVec<T> {
+    fn checkpoint(&mut self) {
+        JournaledVec::checkpoint(self);
+    }
+
+    type TRef = *const T;
+    type TMutRef = *mut T;
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn push(&mut self, item: T) {
+        if self.enabled {
+            self.before_mutation();
+        }
+        self.items.push(item);
+    }
+
+    fn index(&self, index: usize) -> Self::TRef {
+        &self.items[index]
+    }
+
+    fn index_mut(&mut self, index: usize) -> Self::TMutRef {
+        if self.enabled {
+            self.before_mutation();
+            if index < self.checkpoint_len && !self.logged[index] {
+                self.logged.set(index, true);
+                self.log.push((index, self.items[index].clone()));
+            }
+        }
+        &mut self.items[index]
+    }
+}
+
+// Serialized exactly like a Vec, so saved graphs are unchanged. The journal is not saved.
+impl<T: Serialize> Serialize for JournaledVec<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.items.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for JournaledVec<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self {
+            items: Vec::deserialize(deserializer)?,
+            enabled: false,
+            pending: false,
+            checkpoint_len: 0,
+            log: Vec::new(),
+            logged: BitVec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollback() {
+        let mut vec: JournaledVec<u8> = JournaledVec::with_capacity(4);
+        vec.push(1);
+        vec.push(2);
+        assert!(!vec.rollback());
+
+        vec.enable();
+        unsafe {
+            *vec.index_mut(0) = 10;
+            *vec.index_mut(0) = 11;
+        }
+        vec.push(3);
+        vec.checkpoint();
+        // Rolling back right after a checkpoint still undoes the changes before it.
+        assert!(vec.rollback());
+        assert_eq!(*vec, vec![1, 2]);
+
+        vec.push(3);
+        vec.checkpoint();
+        unsafe {
+            *vec.index_mut(1) = 20;
+        }
+        vec.push(4);
+        assert!(vec.rollback());
+        assert_eq!(*vec, vec![1, 2, 3]);
+    }
+}
@@ -1,7 +1,10 @@
+mod journaled_vec;
 mod vec;
 
+pub use self::journaled_vec::JournaledVec;
+
 use crate::graph::indexing::IndexType;
-use crate::memory_backing::MemoryBacking;
+use crate::memory_backing::{MemoryBacking, VecCacheConfig};
 use crate::weight::Weight;
 use std::marker::PhantomData;
 
@@ -24,23 +27,17 @@ where
     type NodeMutRef = *mut Node<N, Ix>;
     type EdgeMutRef = *mut Edge<E, Ix>;
 
-    type VecN = Vec<Node<N, Ix>>;
-    type VecE = Vec<Edge<E, Ix>>;
+    type VecN = JournaledVec<Node<N, Ix>>;
+    type VecE = JournaledVec<Edge<E, Ix>>;
 
     // The disk-backed implementations of new_node_vec and new_edge_vec will presumably pass a file/path.
 
-    fn new_node_vec(&self, capacity: Option<usize>, _cache_size: usize) -> Self::VecN {
-        match capacity {
-            Some(n) => Vec::with_capacity(n),
-            None => Vec::new(),
-        }
+    fn new_node_vec(&self, capacity: Option<usize>, _cache: VecCacheConfig) -> Self::VecN {
+        JournaledVec::with_capacity(capacity.unwrap_or(0))
     }
 
-    fn new_edge_vec(&self, capacity: Option<usize>, _cache_size: usize) -> Self::VecE {
-        match capacity {
-            Some(n) => Vec::with_capacity(n),
-            None => Vec::new(),
-        }
+    fn new_edge_vec(&self, capacity: Option<usize>, _cache: VecCacheConfig) -> Self::VecE {
+        JournaledVec::with_capacity(capacity.unwrap_or(0))
     }
 }
 
@@ -0,0 +1,77 @@
+// Disk backing with frequently queried states pinned in RAM.
+//
+// Files are laid out as in `DiskBacking`, so an index built on disk can be reopened tiered. On
+// load, the states chosen by a `TierPolicy` and their outgoing edges are copied into RAM; all other
+// reads go to disk through the usual LRU caches.
+
+mod tiered_vec;
+
+use crate::graph::avl_graph::edge::Edge;
+use crate::graph::avl_graph::node::Node;
+
+use crate::graph::indexing::IndexType;
+use crate::memory_backing::{MemoryBacking, VecCacheConfig};
+use crate::weight::Weight;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::create_dir_all;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+pub use self::tiered_vec::{TieredEdgeMutRef, TieredNodeMutRef, TieredVec};
+
+/// Which states to pin in RAM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TierPolicy {
+    /// States with at least this count.
+    MinCount(usize),
+    /// States at most this many transitions from the initial state.
+    MaxDepth(usize),
+}
+
+#[derive(Clone)]
+pub struct TieredBacking<N, E, Ix> {
+    dir_path: Box<Path>,
+    marker: PhantomData<(N, E, Ix)>,
+}
+
+impl<N, E, Ix> TieredBacking<N, E, Ix> {
+    pub fn new<P: AsRef<Path> + Clone + std::fmt::Debug>(dir_path: P) -> Self {
+        create_dir_all(dir_path.clone()).unwrap();
+        Self {
+            dir_path: Box::from(dir_path.as_ref()),
+            marker: PhantomData,
+        }
+    }
+
+    pub fn get_nodes_path(&self) -> PathBuf {
+        self.dir_path.join("nodes.vec")
+    }
+
+    pub fn get_edges_path(&self) -> PathBuf {
+        self.dir_path.join("edges.vec")
+    }
+}
+
+impl<N, E, Ix> MemoryBacking<N, E, Ix> for TieredBacking<N, E, Ix>
+where
+    Ix: IndexType + Copy + Serialize + DeserializeOwned,
+    N: Weight + Serialize + DeserializeOwned + Default + Clone + Copy,
+    E: Copy + Serialize + DeserializeOwned + Default,
+{
+    type NodeRef = Node<N, Ix>;
+    type EdgeRef = Edge<E, Ix>;
+    type NodeMutRef = TieredNodeMutRef<N, Ix>;
+    type EdgeMutRef = TieredEdgeMutRef<E, Ix>;
+
+    type VecN = TieredVec<Node<N, Ix>>;
+    type VecE = TieredVec<Edge<E, Ix>>;
+
+    fn new_node_vec(&self, capacity: Option<usize>, cache: VecCacheConfig) -> Self::VecN {
+        TieredVec::new(self.get_nodes_path(), capacity.unwrap_or(8), cache).unwrap()
+    }
+
+    fn new_edge_vec(&self, capacity: Option<usize>, cache: VecCacheConfig) -> Self::VecE {
+        TieredVec::new(self.get_edges_path(), capacity.unwrap_or(8), cache).unwrap()
+    }
+}
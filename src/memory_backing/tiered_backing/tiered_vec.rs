@@ -0,0 +1,256 @@
+// A DiskVec with some items pinned in RAM.
+//
+// Reads of pinned items never touch the disk or the LRU cache. Writes go to the disk and, for
+// pinned items, to the RAM copy too, so both always agree.
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::graph::avl_graph::edge::{Edge, EdgeMutRef};
+use crate::graph::avl_graph::node::{Node, NodeMutRef};
+use crate::graph::indexing::{EdgeIndex, IndexType, NodeIndex};
+use crate::memory_backing::{CachedDiskVec, VecBacking, VecCacheConfig};
+use crate::weight::Weight;
+
+pub struct TieredStore<T> {
+    disk: CachedDiskVec<T>,
+    hot: HashMap<usize, T>,
+}
+
+impl<T> TieredStore<T>
+where
+    T: Serialize + DeserializeOwned + Default + Copy,
+{
+    fn get(&mut self, index: usize) -> T {
+        match self.hot.get(&index) {
+            Some(item) => *item,
+            None => self.disk.get(index).unwrap(),
+        }
+    }
+
+    fn modify<F: FnOnce(&mut T)>(&mut self, index: usize, f: F) {
+        let mut item = self.get(index);
+        f(&mut item);
+        let _ = self.disk.set(index, &item);
+        if let Some(hot) = self.hot.get_mut(&index) {
+            *hot = item;
+        }
+    }
+}
+
+pub struct TieredVec<T> {
+    store: Rc<RefCell<TieredStore<T>>>,
+}
+
+impl<T> TieredVec<T>
+where
+    T: Serialize + DeserializeOwned + Default + Copy,
+{
+    pub fn new<P: AsRef<Path> + std::fmt::Debug>(
+        path: P,
+        capacity: usize,
+        cache: impl Into<VecCacheConfig>,
+    ) -> Result<Self> {
+        let disk = CachedDiskVec::new(path, capacity, cache)?;
+        Ok(Self::from_disk(disk))
+    }
+
+    pub fn load<P: AsRef<Path> + std::fmt::Debug>(
+        path: P,
+        cache: impl Into<VecCacheConfig>,
+    ) -> Result<Self> {
+        let disk = CachedDiskVec::load(path, cache)?;
+        Ok(Self::from_disk(disk))
+    }
+
+    fn from_disk(disk: CachedDiskVec<T>) -> Self {
+        let store = TieredStore {
+            disk,
+            hot: HashMap::new(),
+        };
+        Self {
+            store: Rc::new(RefCell::new(store)),
+        }
+    }
+
+    /// Copy the items at `indices` into RAM.
+    pub fn pin<I: IntoIterator<Item = usize>>(&mut self, indices: I) {
+        let mut store = self.store.borrow_mut();
+        for index in indices {
+            let item = store.disk.get(index).unwrap();
+            store.hot.insert(index, item);
+        }
+    }
+
+    pub fn n_pinned(&self) -> usize {
+        self.store.borrow().hot.len()
+    }
+}
+
+pub trait TieredMutRef<T> {
+    fn new(store: Rc<RefCell<TieredStore<T>>>, index: usize) -> Self;
+}
+
+pub trait TieredItem: Sized {
+    type MutRef: TieredMutRef<Self>;
+}
+
+impl<N, Ix> TieredItem for Node<N, Ix> {
+    type MutRef = TieredNodeMutRef<N, Ix>;
+}
+
+impl<E, Ix> TieredItem for Edge<E, Ix> {
+    type MutRef = TieredEdgeMutRef<E, Ix>;
+}
+
+impl<T> VecBacking<T> for TieredVec<T>
+where
+    T: TieredItem + Serialize + DeserializeOwned + Default + Copy,
+{
+    type TRef = T;
+    type TMutRef = T::MutRef;
+
+    fn len(&self) -> usize {
+        self.store.borrow().disk.len()
+    }
+
+    fn push(&mut self, item: T) {
+        let _ = self.store.borrow_mut().disk.push(&item);
+    }
+
+    fn index(&self, index: usize) -> T {
+        self.store.borrow_mut().get(index)
+    }
+
+    fn index_mut(&mut self, index: usize) -> T::MutRef {
+        T::MutRef::new(self.store.clone(), index)
+    }
+}
+
+pub struct TieredNodeMutRef<N, Ix> {
+    store: Rc<RefCell<TieredStore<Node<N, Ix>>>>,
+    index: usize,
+}
+
+impl<N, Ix> TieredMutRef<Node<N, Ix>> for TieredNodeMutRef<N, Ix> {
+    fn new(store: Rc<RefCell<TieredStore<Node<N, Ix>>>>, index: usize) -> Self {
+        Self { store, index }
+    }
+}
+
+impl<N, Ix> NodeMutRef<Ix> for TieredNodeMutRef<N, Ix>
+where
+    Ix: IndexType,
+    N: Weight,
+    Node<N, Ix>: Serialize + DeserializeOwned + Default + Copy,
+{
+    fn set_length(self, length: u64) {
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |node| node.weight.set_length(length));
+    }
+
+    fn set_failure(self, state: Option<NodeIndex<Ix>>) {
+        // Handle potential mismatch in Ix.
+        let fail_state = state.map(|phi| NodeIndex::new(phi.index()));
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |node| node.weight.set_failure(fail_state));
+    }
+
+    fn increment_count(self) {
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |node| node.weight.increment_count());
+    }
+
+    fn set_count(self, count: usize) {
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |node| node.weight.set_count(count));
+    }
+
+    fn set_terminal(self, terminal: bool) {
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |node| node.weight.set_terminal(terminal));
+    }
+
+    fn set_first_edge(self, first_edge: EdgeIndex<Ix>) {
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |node| node.first_edge = first_edge);
+    }
+}
+
+pub struct TieredEdgeMutRef<E, Ix> {
+    store: Rc<RefCell<TieredStore<Edge<E, Ix>>>>,
+    index: usize,
+}
+
+impl<E, Ix> TieredMutRef<Edge<E, Ix>> for TieredEdgeMutRef<E, Ix> {
+    fn new(store: Rc<RefCell<TieredStore<Edge<E, Ix>>>>, index: usize) -> Self {
+        Self { store, index }
+    }
+}
+
+impl<E, Ix> EdgeMutRef<E, Ix> for TieredEdgeMutRef<E, Ix>
+where
+    Ix: IndexType + Copy,
+    Edge<E, Ix>: Serialize + DeserializeOwned + Default + Copy,
+    E: Copy,
+{
+    fn set_weight(self, weight: E) {
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |edge| edge.weight = weight);
+    }
+
+    fn set_target(self, target: NodeIndex<Ix>) {
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |edge| edge.target = target);
+    }
+
+    fn set_left(self, left: EdgeIndex<Ix>) {
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |edge| edge.left = left);
+    }
+
+    fn set_right(self, right: EdgeIndex<Ix>) {
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |edge| edge.right = right);
+    }
+
+    fn set_balance_factor(self, bf: i8) {
+        let mut store = self.store.borrow_mut();
+        store.modify(self.index, |edge| edge.balance_factor = bf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::avl_graph::node::NodeRef;
+    use crate::weight::DefaultWeight;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_pin_and_write_through() {
+        type T = Node<DefaultWeight, crate::graph::indexing::DefaultIx>;
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("nodes.vec");
+        let mut vec: TieredVec<T> = TieredVec::new(&path, 4, 0).unwrap();
+        for length in 0..3 {
+            vec.push(Node::new(DefaultWeight::new(length, None, 0)));
+        }
+        vec.pin([1]);
+        assert_eq!(vec.n_pinned(), 1);
+        assert_eq!(vec.index(1).get_length(), 1);
+
+        // Writes to a pinned item reach both copies.
+        vec.index_mut(1).set_count(5);
+        vec.index_mut(2).set_count(7);
+        assert_eq!(vec.index(1).get_count(), 5);
+        assert_eq!(vec.index(2).get_count(), 7);
+        let disk = &mut vec.store.borrow_mut().disk;
+        assert_eq!(disk.get(1).unwrap().get_count(), 5);
+    }
+}
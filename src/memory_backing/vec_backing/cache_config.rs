@@ -1,6 +1,10 @@
+use super::cache_policy::CachePolicy;
+
 pub struct CacheConfig {
     pub node_cache_size: usize,
     pub edge_cache_size: usize,
+    pub policy: CachePolicy,   // Shared by the node and edge caches.
+    pub prefetch_depth: usize, // Levels of each AVL edge tree to read ahead when searching it.
 }
 
 impl CacheConfig {
@@ -8,10 +12,57 @@ impl CacheConfig {
         Self {
             node_cache_size,
             edge_cache_size,
+            policy: CachePolicy::default(),
+            prefetch_depth: 0,
         }
     }
 
     pub fn none() -> Self {
         Self::new(0, 0)
     }
+
+    pub fn with_policy(mut self, policy: CachePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_prefetch_depth(mut self, prefetch_depth: usize) -> Self {
+        self.prefetch_depth = prefetch_depth;
+        self
+    }
+
+    pub fn get_node_cache(&self) -> VecCacheConfig {
+        VecCacheConfig::new(self.node_cache_size, self.policy)
+    }
+
+    pub fn get_edge_cache(&self) -> VecCacheConfig {
+        VecCacheConfig {
+            prefetch_depth: self.prefetch_depth,
+            ..VecCacheConfig::new(self.edge_cache_size, self.policy)
+        }
+    }
+}
+
+/// Size and policy of the cache of one CachedDiskVec. A bare size means an LRU of that size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VecCacheConfig {
+    pub size: usize,
+    pub policy: CachePolicy,
+    pub prefetch_depth: usize, // Only used for edge vectors; see CacheConfig.
+}
+
+impl VecCacheConfig {
+    pub fn new(size: usize, policy: CachePolicy) -> Self {
+        Self {
+            size,
+            policy,
+            prefetch_depth: 0,
+        }
+    }
+}
+
+impl From<usize> for VecCacheConfig {
+    fn from(size: usize) -> Self {
+        Self::new(size, CachePolicy::Lru)
+    }
 }
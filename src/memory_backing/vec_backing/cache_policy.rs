@@ -0,0 +1,284 @@
+// Replacement policies for the RAM cache of a CachedDiskVec.
+//
+// Query workloads are heavily skewed towards the states and edges near the source, which plain LRU
+// evicts whenever a long query streams through. `Pinned` keeps the first items of the vector, which
+// are created first and so are near the source, out of the LRU altogether. `SegmentedLru` only
+// protects items that have been hit at least twice, so one-off accesses can't flush them.
+
+use anyhow::{bail, Error};
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+
+use crate::graph::indexing::IndexType;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Evict the least recently used item.
+    #[default]
+    Lru,
+    /// Never evict the first `n` items of the vector, on top of an LRU of the configured size.
+    Pinned(usize),
+    /// Items enter a probationary LRU segment, and move to a protected one (4/5 of the size) when
+    /// hit again. Items evicted from the protected segment get another chance in probation.
+    SegmentedLru,
+}
+
+impl FromStr for CachePolicy {
+    type Err = Error;
+
+    /// Parse `lru`, `slru` or `pinned:N`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lru" => Ok(CachePolicy::Lru),
+            "slru" => Ok(CachePolicy::SegmentedLru),
+            _ => match s.strip_prefix("pinned:") {
+                Some(n) => Ok(CachePolicy::Pinned(n.parse()?)),
+                None => bail!(
+                    "Unknown cache policy {:?}; expected lru, slru or pinned:N",
+                    s
+                ),
+            },
+        }
+    }
+}
+
+/// Hits and misses of a cache, for tuning its size and policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.;
+        }
+        self.hits as f64 / total as f64
+    }
+}
+
+// The cache itself, keyed by vector index.
+pub(crate) enum PolicyCache<K, V> {
+    Lru(LruCache<K, V>),
+    Pinned {
+        n_pinned: usize,
+        pinned: Vec<Option<V>>, // Filled the first time each pinned item is read.
+        lru: Option<LruCache<K, V>>,
+    },
+    SegmentedLru {
+        probation: LruCache<K, V>,
+        protected: Option<LruCache<K, V>>,
+    },
+}
+
+impl<K, V> PolicyCache<K, V>
+where
+    K: IndexType,
+    V: Copy,
+{
+    /// None if nothing would ever be cached.
+    pub fn new(size: usize, policy: CachePolicy) -> Option<Self> {
+        match policy {
+            CachePolicy::Lru => Some(PolicyCache::Lru(LruCache::new(NonZeroUsize::new(size)?))),
+            CachePolicy::Pinned(n_pinned) => {
+                let lru = NonZeroUsize::new(size).map(LruCache::new);
+                if n_pinned == 0 && lru.is_none() {
+                    return None;
+                }
+                Some(PolicyCache::Pinned {
+                    n_pinned,
+                    pinned: Vec::new(),
+                    lru,
+                })
+            }
+            CachePolicy::SegmentedLru => {
+                let n_protected = size * 4 / 5;
+                let probation = LruCache::new(NonZeroUsize::new(size - n_protected)?);
+                let protected = NonZeroUsize::new(n_protected).map(LruCache::new);
+                Some(PolicyCache::SegmentedLru {
+                    probation,
+                    protected,
+                })
+            }
+        }
+    }
+
+    pub fn get(&mut self, key: K) -> Option<V> {
+        match self {
+            PolicyCache::Lru(lru) => lru.get(&key).copied(),
+            PolicyCache::Pinned {
+                n_pinned,
+                pinned,
+                lru,
+            } => {
+                if key.index() < *n_pinned {
+                    pinned.get(key.index()).copied().flatten()
+                } else {
+                    lru.as_mut()?.get(&key).copied()
+                }
+            }
+            PolicyCache::SegmentedLru {
+                probation,
+                protected,
+            } => {
+                let Some(protected) = protected else {
+                    return probation.get(&key).copied();
+                };
+                if let Some(value) = protected.get(&key) {
+                    return Some(*value);
+                }
+                // A second hit promotes the item, demoting the least recent protected one.
+                let value = probation.pop(&key)?;
+                if let Some((demoted_key, demoted)) = protected.push(key, value) {
+                    probation.put(demoted_key, demoted);
+                }
+                Some(value)
+            }
+        }
+    }
+
+    pub fn put(&mut self, key: K, value: V) {
+        match self {
+            PolicyCache::Lru(lru) => {
+                lru.put(key, value);
+            }
+            PolicyCache::Pinned {
+                n_pinned,
+                pinned,
+                lru,
+            } => {
+                let index = key.index();
+                if index < *n_pinned {
+                    if pinned.len() <= index {
+                        pinned.resize(index + 1, None);
+                    }
+                    pinned[index] = Some(value);
+                } else if let Some(lru) = lru {
+                    lru.put(key, value);
+                }
+            }
+            PolicyCache::SegmentedLru { probation, .. } => {
+                probation.put(key, value);
+            }
+        }
+    }
+
+    /// Drop the item if it is cached, e.g. because it has been changed.
+    pub fn pop(&mut self, key: K) {
+        match self {
+            PolicyCache::Lru(lru) => {
+                lru.pop(&key);
+            }
+            PolicyCache::Pinned { pinned, lru, .. } => {
+                if let Some(value) = pinned.get_mut(key.index()) {
+                    *value = None;
+                }
+                if let Some(lru) = lru {
+                    lru.pop(&key);
+                }
+            }
+            PolicyCache::SegmentedLru {
+                probation,
+                protected,
+            } => {
+                probation.pop(&key);
+                if let Some(protected) = protected {
+                    protected.pop(&key);
+                }
+            }
+        }
+    }
+
+    /// Whether the item is cached, without counting as a use.
+    pub fn contains(&self, key: K) -> bool {
+        match self {
+            PolicyCache::Lru(lru) => lru.contains(&key),
+            PolicyCache::Pinned { pinned, lru, .. } => {
+                matches!(pinned.get(key.index()), Some(Some(_)))
+                    || lru.as_ref().is_some_and(|lru| lru.contains(&key))
+            }
+            PolicyCache::SegmentedLru {
+                probation,
+                protected,
+            } => {
+                probation.contains(&key)
+                    || protected
+                        .as_ref()
+                        .is_some_and(|protected| protected.contains(&key))
+            }
+        }
+    }
+
+    /// Number of items cached.
+    pub fn len(&self) -> usize {
+        match self {
+            PolicyCache::Lru(lru) => lru.len(),
+            PolicyCache::Pinned { pinned, lru, .. } => {
+                pinned.iter().filter(|value| value.is_some()).count()
+                    + lru.as_ref().map_or(0, |lru| lru.len())
+            }
+            PolicyCache::SegmentedLru {
+                probation,
+                protected,
+            } => probation.len() + protected.as_ref().map_or(0, |protected| protected.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!("lru".parse::<CachePolicy>().unwrap(), CachePolicy::Lru);
+        assert_eq!(
+            "slru".parse::<CachePolicy>().unwrap(),
+            CachePolicy::SegmentedLru
+        );
+        assert_eq!(
+            "pinned:8".parse::<CachePolicy>().unwrap(),
+            CachePolicy::Pinned(8)
+        );
+        assert!("pinned:x".parse::<CachePolicy>().is_err());
+        assert!("fifo".parse::<CachePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_pinned() {
+        let mut cache: PolicyCache<usize, u32> =
+            PolicyCache::new(1, CachePolicy::Pinned(2)).unwrap();
+        for key in 0..4 {
+            cache.put(key, key as u32);
+        }
+        // The pinned items survive, and only the last of the rest fits in the LRU.
+        assert_eq!(cache.get(0), Some(0));
+        assert_eq!(cache.get(1), Some(1));
+        assert_eq!(cache.get(2), None);
+        assert_eq!(cache.get(3), Some(3));
+        assert_eq!(cache.len(), 3);
+        assert!(cache.contains(0) && cache.contains(3) && !cache.contains(2));
+        cache.pop(1);
+        assert_eq!(cache.get(1), None);
+        assert!(PolicyCache::<usize, u32>::new(0, CachePolicy::Pinned(0)).is_none());
+    }
+
+    #[test]
+    fn test_segmented_lru() {
+        let mut cache: PolicyCache<usize, u32> =
+            PolicyCache::new(5, CachePolicy::SegmentedLru).unwrap();
+        cache.put(0, 0);
+        assert_eq!(cache.get(0), Some(0)); // Promoted to the protected segment.
+                                           // A scan through many other items only churns the probationary segment.
+        for key in 1..10 {
+            cache.put(key, key as u32);
+        }
+        assert_eq!(cache.get(0), Some(0));
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.get(9), Some(9));
+        assert_eq!(cache.len(), 2);
+    }
+}
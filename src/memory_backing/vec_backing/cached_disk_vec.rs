@@ -1,21 +1,24 @@
 use anyhow::Result;
-use lru::LruCache;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::num::NonZeroUsize;
 use std::path::Path;
 
+use super::cache_config::VecCacheConfig;
+use super::cache_policy::{CacheStats, PolicyCache};
 use super::DiskVec;
 use crate::graph::indexing::{DefaultIx, IndexType};
+use crate::metrics;
 
-/// A DiskVec where recently accessed entries are cached in RAM.
+/// A DiskVec where entries are cached in RAM, chosen by a `CachePolicy`.
 pub struct CachedDiskVec<T, Ix = DefaultIx>
 where
     T: Sized,
     Ix: IndexType,
 {
     vec: DiskVec<T>,
-    cache: Option<LruCache<Ix, T>>,
+    cache: Option<PolicyCache<Ix, T>>,
+    stats: CacheStats,
+    prefetch_depth: usize,
 }
 
 impl<T, Ix> CachedDiskVec<T, Ix>
@@ -23,47 +26,54 @@ where
     T: Serialize + DeserializeOwned + Default + Copy,
     Ix: IndexType,
 {
+    fn with_cache(vec: DiskVec<T>, cache: VecCacheConfig) -> Self {
+        Self {
+            vec,
+            cache: PolicyCache::new(cache.size, cache.policy),
+            stats: CacheStats::default(),
+            prefetch_depth: cache.prefetch_depth,
+        }
+    }
+
     /// Create a new mutable `DiskVec<T>` with the given file path.
     ///
     /// Fails if the corresponding file already exists.
     pub fn new<P: AsRef<Path> + std::fmt::Debug>(
         path: P,
         capacity: usize,
-        cache_size: usize,
+        cache: impl Into<VecCacheConfig>,
     ) -> Result<Self> {
         let vec = DiskVec::new(path, capacity)?;
-        let cache = if cache_size > 0 {
-            Some(LruCache::new(NonZeroUsize::new(cache_size).unwrap()))
-        } else {
-            None
-        };
-        Ok(Self { vec, cache })
+        Ok(Self::with_cache(vec, cache.into()))
     }
 
     /// Load a read-only `DiskVec<T>` from an existing file.
-    pub fn load<P: AsRef<Path> + std::fmt::Debug>(path: P, cache_size: usize) -> Result<Self> {
+    pub fn load<P: AsRef<Path> + std::fmt::Debug>(
+        path: P,
+        cache: impl Into<VecCacheConfig>,
+    ) -> Result<Self> {
         let vec = DiskVec::load(path)?;
-        let cache = if cache_size > 0 {
-            Some(LruCache::new(NonZeroUsize::new(cache_size).unwrap()))
-        } else {
-            None
-        };
-        Ok(Self { vec, cache })
+        Ok(Self::with_cache(vec, cache.into()))
+    }
+
+    /// Reopen an existing file as a mutable `DiskVec<T>` holding its first `len` items.
+    pub fn open_mut<P: AsRef<Path> + std::fmt::Debug>(
+        path: P,
+        len: usize,
+        cache: impl Into<VecCacheConfig>,
+    ) -> Result<Self> {
+        let vec = DiskVec::open_mut(path, len)?;
+        Ok(Self::with_cache(vec, cache.into()))
     }
 
     /// Turn a `Vec<T>` into a new `DiskVec<T>`.
     pub fn from_vec<P: AsRef<Path> + std::fmt::Debug>(
         vec: &Vec<T>,
         path: P,
-        cache_size: usize,
+        cache: impl Into<VecCacheConfig>,
     ) -> Result<Self> {
         let vec = DiskVec::from_vec(vec, path)?;
-        let cache = if cache_size > 0 {
-            Some(LruCache::new(NonZeroUsize::new(cache_size).unwrap()))
-        } else {
-            None
-        };
-        Ok(Self { vec, cache })
+        Ok(Self::with_cache(vec, cache.into()))
     }
 
     // /// Convert a writable `DiskVec<T>` into a read-only `DiskVec<T>`.
@@ -85,7 +95,7 @@ where
     fn pop_cache(&mut self, index: usize) {
         // See https://users.rust-lang.org/t/if-let-passing-a-mutable-ref-option-not-working/31300
         if let Some(cache) = self.cache.as_mut() {
-            cache.pop(&Ix::new(index));
+            cache.pop(Ix::new(index));
         }
     }
 
@@ -106,6 +116,11 @@ where
         Ok(())
     }
 
+    /// Write changes through to the file. Cached items are always clean, since `set` evicts them.
+    pub fn flush(&self) -> Result<()> {
+        self.vec.flush()
+    }
+
     /// The number of items in the `DiskVec`.
     pub fn len(&self) -> usize {
         self.vec.len()
@@ -120,9 +135,15 @@ where
     pub fn get(&mut self, index: usize) -> Result<T> {
         let idx = Ix::new(index);
         if let Some(cache) = self.cache.as_mut() {
-            match cache.get(&idx) {
-                Some(value) => Ok(*value),
+            match cache.get(idx) {
+                Some(value) => {
+                    metrics::record_cache_hit();
+                    self.stats.hits += 1;
+                    Ok(value)
+                }
                 None => {
+                    metrics::record_cache_miss();
+                    self.stats.misses += 1;
                     let value = self.vec.get(index)?;
                     cache.put(idx, value);
                     Ok(value)
@@ -140,11 +161,34 @@ where
             None => 0,
         }
     }
+
+    /// Hits and misses of the cache since the vector was opened.
+    pub fn get_cache_stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Read ahead the items at `indices` that aren't already cached (see `DiskVec::prefetch`).
+    pub fn prefetch(&self, indices: &[usize]) -> Result<usize> {
+        let mut uncached: Vec<usize> = match self.cache.as_ref() {
+            Some(cache) => indices
+                .iter()
+                .copied()
+                .filter(|&index| !cache.contains(Ix::new(index)))
+                .collect(),
+            None => indices.to_vec(),
+        };
+        self.vec.prefetch(&mut uncached)
+    }
+
+    pub fn get_prefetch_depth(&self) -> usize {
+        self.prefetch_depth
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::memory_backing::CachePolicy;
     use tempfile::tempdir;
 
     #[test]
@@ -163,7 +207,7 @@ mod tests {
 
         // Before "getting", cache is fully empty.
         for idx in 0..10 {
-            assert_eq!(vec.cache.as_mut().unwrap().get(&DefaultIx::new(idx)), None);
+            assert_eq!(vec.cache.as_mut().unwrap().get(DefaultIx::new(idx)), None);
         }
 
         // Check that "get" returns the right values.
@@ -173,13 +217,13 @@ mod tests {
 
         // Only the last five things should fit in the cache.
         for idx in 0..5 {
-            assert_eq!(vec.cache.as_mut().unwrap().get(&DefaultIx::new(idx)), None);
+            assert_eq!(vec.cache.as_mut().unwrap().get(DefaultIx::new(idx)), None);
         }
         for idx in 5..10 {
             let value = idx + 10;
             assert_eq!(
-                vec.cache.as_mut().unwrap().get(&DefaultIx::new(idx)),
-                Some(&value)
+                vec.cache.as_mut().unwrap().get(DefaultIx::new(idx)),
+                Some(value)
             );
         }
     }
@@ -204,7 +248,7 @@ mod tests {
         }
 
         for idx in 0..10 {
-            assert_eq!(vec.cache.as_mut().unwrap().get(&DefaultIx::new(idx)), None);
+            assert_eq!(vec.cache.as_mut().unwrap().get(DefaultIx::new(idx)), None);
         }
 
         for idx in 0..5 {
@@ -215,6 +259,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cache_stats_pinned() {
+        let tmp_dir = tempdir().unwrap();
+        let cache = VecCacheConfig::new(0, CachePolicy::Pinned(2));
+        let mut vec: CachedDiskVec<usize> =
+            CachedDiskVec::new(tmp_dir.path().join("vec.bin"), 10, cache).unwrap();
+        for idx in 0..4 {
+            vec.push(&idx).unwrap();
+        }
+        for _ in 0..2 {
+            for idx in 0..4 {
+                assert_eq!(vec.get(idx).unwrap(), idx);
+            }
+        }
+        // Only the pinned items are cached, so they are the only hits.
+        assert_eq!(vec.get_cache_stats(), CacheStats { hits: 2, misses: 6 });
+        assert_eq!(vec.get_cache_stats().hit_rate(), 0.25);
+        assert_eq!(vec.get_cache_len(), 2);
+    }
+
     #[test]
     fn test_empty_cache() {
         let tmp_dir = tempdir().unwrap();
@@ -0,0 +1,117 @@
+// Chunked, restartable conversion of one DiskVec into another with a different item type.
+//
+// Items are mapped one at a time and written in chunks, so memory use is bounded by the chunk
+// size. After each chunk, the number of items written so far is saved to `<output>.progress`. If
+// the conversion is interrupted, calling it again with the same arguments resumes from there. The
+// progress file is removed once the conversion finishes.
+
+use anyhow::{bail, Result};
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::memory_backing::vec_backing::format::{self, HEADER_LEN};
+use crate::memory_backing::DiskVec;
+
+fn get_progress_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".progress");
+    PathBuf::from(path)
+}
+
+/// Map every item of the DiskVec at `input` through `map`, writing the results to a new DiskVec at
+/// `output`. `map` also gets the index of each item, e.g. for error messages. Returns the number of
+/// items converted.
+pub fn convert_disk_vec<A, B, F>(
+    input: &Path,
+    output: &Path,
+    chunk_size: usize,
+    mut map: F,
+) -> Result<usize>
+where
+    A: Serialize + DeserializeOwned + Default,
+    B: Serialize,
+    F: FnMut(usize, A) -> Result<B>,
+{
+    let progress_path = get_progress_path(output);
+    let done: usize = if progress_path.exists() {
+        fs::read_to_string(&progress_path)?.trim().parse()?
+    } else if output.exists() {
+        bail!("{:?} already exists!", output);
+    } else {
+        0
+    };
+
+    let input_vec: DiskVec<A> = DiskVec::load(input)?;
+    let item_size = std::mem::size_of::<B>();
+    let mut file = File::options()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(output)?;
+    // Drop anything written after the last completed chunk.
+    if done == 0 {
+        file.write_all(&format::header(item_size, None))?;
+    }
+    file.set_len((HEADER_LEN + done * item_size) as u64)?;
+    file.seek(SeekFrom::End(0))?;
+
+    let options = bincode::DefaultOptions::new().with_fixint_encoding();
+    let mut buf = Vec::with_capacity(chunk_size * item_size);
+    let mut start = done;
+    while start < input_vec.len() {
+        let end = (start + chunk_size).min(input_vec.len());
+        buf.clear();
+        for idx in start..end {
+            let value = map(idx, input_vec.get(idx)?)?;
+            // Match the DiskVec layout: each item is padded to size_of::<B>().
+            let serialized = options.serialize(&value)?;
+            buf.extend_from_slice(&serialized);
+            buf.resize(buf.len() + item_size - serialized.len(), 0);
+        }
+        file.write_all(&buf)?;
+        file.sync_data()?;
+        fs::write(&progress_path, end.to_string())?;
+        start = end;
+    }
+
+    if progress_path.exists() {
+        fs::remove_file(&progress_path)?;
+    }
+    Ok(input_vec.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_convert_disk_vec_resume() {
+        let tmp_dir = tempdir().unwrap();
+        let input = tmp_dir.path().join("input.vec");
+        let output = tmp_dir.path().join("output.vec");
+        let values: Vec<u32> = (0..10).collect();
+        DiskVec::from_vec(&values, &input).unwrap();
+
+        // Fail partway through, after the first two chunks were written.
+        let result = convert_disk_vec(&input, &output, 4, |idx, value: u32| {
+            if idx == 9 {
+                bail!("interrupted");
+            }
+            Ok(value as u16)
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(get_progress_path(&output)).unwrap(), "8");
+
+        let n_items = convert_disk_vec(&input, &output, 4, |_, value: u32| Ok(value as u16));
+        assert_eq!(n_items.unwrap(), 10);
+        assert!(!get_progress_path(&output).exists());
+        let converted: DiskVec<u16> = DiskVec::load(&output).unwrap();
+        let converted: Vec<u16> = (0..10).map(|idx| converted.get(idx).unwrap()).collect();
+        assert_eq!(converted, (0..10).collect::<Vec<u16>>());
+    }
+}
@@ -1,6 +1,7 @@
-use std::fs::File;
+use std::borrow::Borrow;
+use std::fs::{self, File};
 use std::marker;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Result};
 use bincode::Options;
@@ -8,17 +9,33 @@ use memmap2::MmapOptions;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+use crate::memory_backing::vec_backing::fault::{self, FaultPoint};
+use crate::memory_backing::vec_backing::format::{self, HEADER_LEN};
+use crate::metrics;
+
+// Reads closer together than this are merged into one readahead request.
+const PREFETCH_GAP: usize = 4096;
+
 enum Mmap {
     Mmap(memmap2::Mmap),
     MmapMut(memmap2::MmapMut),
 }
 
-/// A vec-like data structure with limited functionality that's backed by a file on disk.
+// Saves are written to `<path>.tmp` and renamed into place once complete.
+fn get_tmp_path(path: &Path) -> PathBuf {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    PathBuf::from(tmp_path)
+}
+
+/// A vec-like data structure with limited functionality that's backed by a file on disk. The file
+/// starts with a header (see `format`) that is checked on load.
 pub struct DiskVec<T>
 where
     T: Sized,
 {
     item_size: usize,
+    offset: usize, // Where the items start: after the header, or at 0 in a file without one.
     capacity: usize,
     len: usize,
     mmap: Mmap,
@@ -44,10 +61,12 @@ where
             .create(true)
             .truncate(false)
             .open(&path)?;
-        file.set_len((capacity * item_size) as u64)?;
-        let mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+        file.set_len((HEADER_LEN + capacity * item_size) as u64)?;
+        let mut mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+        mmap[..HEADER_LEN].copy_from_slice(&format::header(item_size, None));
         Ok(Self {
             item_size,
+            offset: HEADER_LEN,
             capacity,
             len: 0,
             mmap: Mmap::MmapMut(mmap),
@@ -56,14 +75,29 @@ where
         })
     }
 
-    /// Load a read-only `DiskVec<T>` from an existing file.
+    /// Load a read-only `DiskVec<T>` from an existing file. Files without a header, as written by
+    /// older versions, are read as they are.
+    ///
+    /// Fails with a `format::FormatError` if the header doesn't match this version and item type,
+    /// or if the file was only partially written. The checksum isn't checked, since that reads the
+    /// whole file; use `load_verified` for that.
     pub fn load<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Result<Self> {
+        Self::load_checked(path, false)
+    }
+
+    /// Like `load`, but also fail with `FormatError::Corrupted` if the checksum doesn't match.
+    pub fn load_verified<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Result<Self> {
+        Self::load_checked(path, true)
+    }
+
+    fn load_checked<P: AsRef<Path> + std::fmt::Debug>(path: P, verify: bool) -> Result<Self> {
         let item_size = std::mem::size_of::<T>();
         let file = File::options().read(true).open(&path)?;
-        let len = (file.metadata()?.len() as usize) / item_size;
         let mmap = unsafe { MmapOptions::new().map(&file)? };
+        let (offset, len) = format::check_or_legacy(path.as_ref(), &mmap, item_size, verify)?;
         Ok(Self {
             item_size,
+            offset,
             capacity: len,
             len,
             mmap: Mmap::Mmap(mmap),
@@ -72,23 +106,96 @@ where
         })
     }
 
+    /// Reopen an existing file as a mutable `DiskVec<T>` holding its first `len` items, so that
+    /// more can be pushed. The rest of the file is treated as spare capacity.
+    pub fn open_mut<P: AsRef<Path> + std::fmt::Debug>(path: P, len: usize) -> Result<Self> {
+        let item_size = std::mem::size_of::<T>();
+        let file = File::options().read(true).write(true).open(&path)?;
+        let mut mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+        let capacity = format::check(path.as_ref(), &mmap, item_size, false)?;
+        if len > capacity {
+            bail!(
+                "{path:?} holds {} items, but {} were expected",
+                capacity,
+                len
+            );
+        }
+        // The checksum is stale once anything is written.
+        format::clear_checksum(&mut mmap[..HEADER_LEN]);
+        Ok(Self {
+            item_size,
+            offset: HEADER_LEN,
+            capacity,
+            len,
+            mmap: Mmap::MmapMut(mmap),
+            file,
+            _marker: marker::PhantomData::<T>,
+        })
+    }
+
+    /// Write a new read-only `DiskVec<T>` atomically: `fill` writes to a temporary file, which is
+    /// only moved to `path` once it is complete. A failed save leaves nothing at `path`.
+    fn save_atomic<P, F>(path: P, capacity: usize, fill: F) -> Result<Self>
+    where
+        P: AsRef<Path> + std::fmt::Debug,
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        if path.as_ref().is_file() {
+            bail!("{path:?} aleady exists!");
+        }
+        // Leftover from a save that was interrupted.
+        let tmp_path = get_tmp_path(path.as_ref());
+        if tmp_path.is_file() {
+            fs::remove_file(&tmp_path)?;
+        }
+        let mut disk_vec = Self::new(&tmp_path, capacity)?;
+        fill(&mut disk_vec)?;
+        let disk_vec = disk_vec.make_read_only()?;
+        fault::check(FaultPoint::Rename)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(disk_vec)
+    }
+
     /// Turn a `Vec<T>` into a new `DiskVec<T>`.
     pub fn from_vec<P: AsRef<Path> + std::fmt::Debug>(vec: &Vec<T>, path: P) -> Result<Self> {
-        let len = vec.len();
-        let mut disk_vec = Self::new(path, len)?;
-        for item in vec {
-            disk_vec.push(item)?;
-        }
-        disk_vec.make_read_only()
+        Self::from_iter(vec, vec.len(), path)
+    }
+
+    /// Stream items into a new read-only `DiskVec<T>` without collecting them in RAM first.
+    ///
+    /// `capacity` is only an estimate: the file grows geometrically if more items are yielded, and
+    /// is truncated to the number of items at the end.
+    pub fn from_iter<P, I>(iter: I, capacity: usize, path: P) -> Result<Self>
+    where
+        P: AsRef<Path> + std::fmt::Debug,
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        Self::save_atomic(path, capacity, |disk_vec| {
+            for item in iter {
+                if disk_vec.len == disk_vec.capacity {
+                    disk_vec.try_reserve(disk_vec.capacity.max(1))?;
+                }
+                disk_vec.push(item.borrow())?;
+            }
+            Ok(())
+        })
     }
 
-    /// Convert a writable `DiskVec<T>` into a read-only `DiskVec<T>`.
+    /// Convert a writable `DiskVec<T>` into a read-only `DiskVec<T>`, with a checksum of its items.
     pub fn make_read_only(mut self) -> Result<Self> {
+        let data_len = self.len * self.item_size;
         if self.len < self.capacity {
-            let new_file_len = self.len * self.item_size;
-            self.file.set_len(new_file_len as u64)?;
+            fault::check(FaultPoint::SetLen)?;
+            self.file.set_len((HEADER_LEN + data_len) as u64)?;
         }
-        if let Mmap::MmapMut(mmap) = self.mmap {
+        if let Mmap::MmapMut(mut mmap) = self.mmap {
+            let header = format::header(
+                self.item_size,
+                Some(&mmap[HEADER_LEN..HEADER_LEN + data_len]),
+            );
+            mmap[..HEADER_LEN].copy_from_slice(&header);
+            fault::check(FaultPoint::Flush)?;
             mmap.flush()?;
         }
         self.file.metadata()?.permissions().set_readonly(true);
@@ -98,7 +205,9 @@ where
 
     pub fn try_reserve(&mut self, additional: usize) -> Result<()> {
         let new_capacity = self.capacity + additional;
-        self.file.set_len((new_capacity * self.item_size) as u64)?;
+        fault::check(FaultPoint::SetLen)?;
+        self.file
+            .set_len((HEADER_LEN + new_capacity * self.item_size) as u64)?;
         self.mmap = Mmap::MmapMut(unsafe { MmapOptions::new().map_mut(&self.file)? });
         self.capacity = new_capacity;
         Ok(())
@@ -132,7 +241,8 @@ where
             if serialized.len() > self.item_size {
                 bail!("error inserting value into array, size of serialized item ({}) does not match expected size ({})!", serialized.len(), self.item_size);
             }
-            let start_idx = index * self.item_size;
+            fault::check(FaultPoint::Write)?;
+            let start_idx = self.offset + index * self.item_size;
             mmap[start_idx..(start_idx + serialized.len())].copy_from_slice(&serialized[..]);
         } else {
             bail!("this DiskVec is read only!");
@@ -152,6 +262,15 @@ where
         self._set(index, value)
     }
 
+    /// Write changes through to the file, so they survive a crash.
+    pub fn flush(&self) -> Result<()> {
+        if let Mmap::MmapMut(ref mmap) = self.mmap {
+            fault::check(FaultPoint::Flush)?;
+            mmap.flush()?;
+        }
+        Ok(())
+    }
+
     /// The number of items in the `DiskVec`.
     pub fn len(&self) -> usize {
         self.len
@@ -171,17 +290,57 @@ where
                 self.len
             );
         }
-        let start_index = index * self.item_size;
+        let start_index = self.offset + index * self.item_size;
         let bytes = match &self.mmap {
             Mmap::Mmap(mmap) => &mmap[start_index..(start_index + self.item_size)],
             Mmap::MmapMut(mmap) => &mmap[start_index..(start_index + self.item_size)],
         };
+        metrics::record_disk_read(self.item_size);
         let deserialized = bincode::DefaultOptions::new()
             .with_fixint_encoding()
             .allow_trailing_bytes()
             .deserialize::<T>(bytes)?;
         Ok(deserialized)
     }
+
+    /// Ask the OS to read the items at `indices` into the page cache ahead of `get`. Indices are
+    /// sorted in place and merged into runs of nearby items, which are requested together. Returns
+    /// the number of requests issued. Out-of-bounds indices are ignored.
+    pub fn prefetch(&self, indices: &mut [usize]) -> Result<usize> {
+        let runs = coalesce_runs(indices, self.len, self.item_size);
+        #[cfg(unix)]
+        for &(start, end) in runs.iter() {
+            let offset = self.offset + start;
+            let advice = memmap2::Advice::WillNeed;
+            match &self.mmap {
+                Mmap::Mmap(mmap) => mmap.advise_range(advice, offset, end - start)?,
+                Mmap::MmapMut(mmap) => mmap.advise_range(advice, offset, end - start)?,
+            }
+        }
+        Ok(runs.len())
+    }
+}
+
+/// Check the header and checksum of the DiskVec file at `path`, whatever its item type. Files that
+/// were never finished with `make_read_only` have no checksum, so only their header is checked.
+pub fn verify_checksum<P: AsRef<Path>>(path: P) -> Result<()> {
+    let file = File::options().read(true).open(&path)?;
+    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    Ok(format::verify(path.as_ref(), &mmap)?)
+}
+
+// Byte ranges, relative to the start of the items, covering the items at `indices`.
+fn coalesce_runs(indices: &mut [usize], len: usize, item_size: usize) -> Vec<(usize, usize)> {
+    indices.sort_unstable();
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for &index in indices.iter().filter(|&&index| index < len) {
+        let (start, end) = (index * item_size, (index + 1) * item_size);
+        match runs.last_mut() {
+            Some(run) if start <= run.1 + PREFETCH_GAP => run.1 = run.1.max(end),
+            _ => runs.push((start, end)),
+        }
+    }
+    runs
 }
 
 #[cfg(test)]
@@ -192,6 +351,7 @@ mod tests {
 
     use crate::graph::avl_graph::node::{Node, NodeRef};
     use crate::graph::indexing::{DefaultIx, NodeIndex};
+    use crate::memory_backing::vec_backing::format::FormatError;
     use crate::weight::{DefaultWeight, Weight};
 
     #[derive(Serialize, Deserialize, Default, Debug)]
@@ -238,6 +398,23 @@ mod tests {
         assert_eq!(disk_vec.get(0).unwrap().get_length(), 42);
     }
 
+    #[test]
+    fn test_prefetch() {
+        let tmp_dir = tempdir().unwrap();
+        let vec: Vec<u64> = (0..2048).collect();
+        let disk_vec = DiskVec::<u64>::from_vec(&vec, tmp_dir.path().join("vec.bin")).unwrap();
+
+        // Nearby items share a request, and distant or out-of-bounds ones don't.
+        let mut indices = vec![2000, 3, 0, 5000, 1, 600];
+        assert_eq!(
+            coalesce_runs(&mut indices, disk_vec.len(), 8),
+            vec![(0, 32), (4800, 4808), (16000, 16008)]
+        );
+        let mut indices = vec![2000, 3, 0, 5000, 1, 600];
+        assert_eq!(disk_vec.prefetch(&mut indices).unwrap(), 3);
+        assert_eq!(disk_vec.get(600).unwrap(), 600);
+    }
+
     #[test]
     fn test_from_vec() {
         let tmp_dir = tempdir().unwrap();
@@ -247,4 +424,144 @@ mod tests {
         assert_eq!(disk_vec.len(), 2);
         assert_eq!(disk_vec.get(1).unwrap().x, 2);
     }
+
+    #[test]
+    fn test_from_iter_grows() {
+        let tmp_dir = tempdir().unwrap();
+
+        let iter = (0..10).map(|x| Foo { x, y: x + 1 });
+        let disk_vec = DiskVec::<Foo>::from_iter(iter, 3, tmp_dir.path().join("vec.bin")).unwrap();
+        assert_eq!(disk_vec.len(), 10);
+        assert_eq!(disk_vec.get(9).unwrap().y, 10);
+    }
+
+    #[test]
+    fn test_from_vec_faults_are_atomic() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("vec.bin");
+        let vec: Vec<Foo> = (0..8).map(|x| Foo { x, y: x }).collect();
+
+        // A partial write, a failed truncate/flush, or a crash before the rename all leave nothing
+        // at the destination path.
+        for (point, n_ok) in [
+            (FaultPoint::Write, 5),
+            (FaultPoint::Flush, 0),
+            (FaultPoint::Rename, 0),
+        ] {
+            fault::inject(point, n_ok);
+            assert!(DiskVec::<Foo>::from_vec(&vec, &path).is_err());
+            fault::clear();
+            assert!(!path.exists());
+            assert!(DiskVec::<Foo>::load(&path).is_err());
+        }
+
+        // Retrying cleans up the leftover temporary file.
+        let disk_vec = DiskVec::<Foo>::from_vec(&vec, &path).unwrap();
+        assert_eq!(disk_vec.len(), 8);
+        assert!(!get_tmp_path(&path).exists());
+    }
+
+    #[test]
+    fn test_from_iter_set_len_fault() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("vec.bin");
+
+        // Growing the file fails after the first resize.
+        fault::inject(FaultPoint::SetLen, 1);
+        let iter = (0..10).map(|x| Foo { x, y: x });
+        assert!(DiskVec::<Foo>::from_iter(iter, 2, &path).is_err());
+        fault::clear();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_load_detects_truncated_file() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("vec.bin");
+        let vec = vec![Foo { x: 0, y: 1 }, Foo { x: 2, y: 3 }];
+        DiskVec::<Foo>::from_vec(&vec, &path).unwrap();
+
+        // Simulate a write that was cut off partway through an item.
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_len((HEADER_LEN + std::mem::size_of::<Foo>() + 3) as u64)
+            .unwrap();
+        let err = DiskVec::<Foo>::load(&path).err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<FormatError>(),
+            Some(FormatError::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn test_open_mut() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("vec.bin");
+        let mut disk_vec = DiskVec::<Foo>::new(&path, 4).unwrap();
+        disk_vec.push(&Foo { x: 0, y: 1 }).unwrap();
+        disk_vec.push(&Foo { x: 2, y: 3 }).unwrap();
+        drop(disk_vec);
+
+        // The file still has the capacity it was created with, so the length is passed in.
+        let mut disk_vec = DiskVec::<Foo>::open_mut(&path, 2).unwrap();
+        assert_eq!(disk_vec.len(), 2);
+        assert_eq!(disk_vec.get(1).unwrap().x, 2);
+        for x in 4..8 {
+            disk_vec.push(&Foo { x, y: x }).unwrap();
+        }
+        assert_eq!(disk_vec.len(), 6);
+        assert_eq!(disk_vec.get(5).unwrap().x, 7);
+
+        assert!(DiskVec::<Foo>::open_mut(&path, 100).is_err());
+    }
+
+    #[test]
+    fn test_load_detects_corruption() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("vec.bin");
+        let vec = vec![Foo { x: 0, y: 1 }, Foo { x: 2, y: 3 }];
+        DiskVec::<Foo>::from_vec(&vec, &path).unwrap();
+
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[HEADER_LEN] ^= 1;
+        fs::write(&path, &bytes).unwrap();
+        // Only the header is checked unless the checksum is asked for.
+        assert_eq!(DiskVec::<Foo>::load(&path).unwrap().len(), 2);
+        let err = DiskVec::<Foo>::load_verified(&path).err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<FormatError>(),
+            Some(FormatError::Corrupted { .. })
+        ));
+        assert!(verify_checksum(&path).is_err());
+
+        // Reopening for writing drops the checksum, and finishing the file writes a new one.
+        let disk_vec = DiskVec::<Foo>::open_mut(&path, 2).unwrap();
+        assert_eq!(disk_vec.get(0).unwrap().x, 1);
+        disk_vec.make_read_only().unwrap();
+        assert_eq!(DiskVec::<Foo>::load_verified(&path).unwrap().len(), 2);
+        assert!(verify_checksum(&path).is_ok());
+
+        // A file without a header, as written by older versions, is read as it is, but can't be
+        // reopened for writing.
+        let legacy = [0, 1, 2, 3].map(|x| Foo { x, y: x });
+        let legacy_bytes: Vec<u8> = legacy
+            .iter()
+            .flat_map(|foo| {
+                bincode::DefaultOptions::new()
+                    .with_fixint_encoding()
+                    .serialize(foo)
+                    .unwrap()
+            })
+            .collect();
+        fs::write(&path, &legacy_bytes).unwrap();
+        let disk_vec = DiskVec::<Foo>::load_verified(&path).unwrap();
+        assert_eq!(disk_vec.len(), 4);
+        assert_eq!(disk_vec.get(3).unwrap().y, 3);
+        let err = DiskVec::<Foo>::open_mut(&path, 4).err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<FormatError>(),
+            Some(FormatError::VersionMismatch { found: None, .. })
+        ));
+        fs::write(&path, &legacy_bytes[1..]).unwrap();
+        assert!(DiskVec::<Foo>::load(&path).is_err());
+    }
 }
@@ -0,0 +1,59 @@
+// Fault injection for DiskVec file operations, used to test what a crash or I/O error mid-save
+// leaves on disk.
+//
+// Tests arm a failure point with `inject(point, n)`: the first `n` operations at that point
+// succeed and the next one fails. Faults are thread-local, so tests running in parallel don't
+// interfere. Outside of tests, `check` is a no-op.
+
+#[cfg(test)]
+use anyhow::bail;
+use anyhow::Result;
+#[cfg(test)]
+use std::cell::RefCell;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultPoint {
+    Write,  // Writing one item into the mmap.
+    SetLen, // Resizing the file.
+    Flush,  // Flushing the mmap to disk.
+    Rename, // Moving a finished file into place.
+}
+
+#[cfg(test)]
+thread_local! {
+    static FAULTS: RefCell<Vec<(FaultPoint, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Fail the operation at `point` after `n_ok` successful ones.
+#[cfg(test)]
+pub fn inject(point: FaultPoint, n_ok: usize) {
+    FAULTS.with(|faults| faults.borrow_mut().push((point, n_ok)));
+}
+
+/// Disarm all failure points.
+#[cfg(test)]
+pub fn clear() {
+    FAULTS.with(|faults| faults.borrow_mut().clear());
+}
+
+#[cfg(test)]
+pub fn check(point: FaultPoint) -> Result<()> {
+    FAULTS.with(|faults| {
+        let mut faults = faults.borrow_mut();
+        let Some(idx) = faults.iter().position(|(p, _)| *p == point) else {
+            return Ok(());
+        };
+        if faults[idx].1 == 0 {
+            faults.remove(idx);
+            bail!("Injected fault at {:?}", point);
+        }
+        faults[idx].1 -= 1;
+        Ok(())
+    })
+}
+
+#[cfg(not(test))]
+#[inline]
+pub fn check(_point: FaultPoint) -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,292 @@
+// File header of a DiskVec, so that files written by another version of the crate, with another
+// item type, or only partially are rejected on load instead of being misread.
+//
+// The header is HEADER_LEN bytes, all little-endian:
+//
+//   magic (8) | version (4) | item size (4) | checksum (8) | flags (8)
+//
+// The checksum is an FNV-1a hash of the items. It is only kept up to date for files that are no
+// longer written to, i.e. ones finished with `make_read_only`, and the HAS_CHECKSUM flag says
+// whether it is. Reopening a file for writing clears the flag. Checking it takes a pass over the
+// whole file, so loading only checks the header, and the checksum is checked on request, e.g. by
+// `rusty-dawg verify --checksums`.
+//
+// Files written before headers were added (version 0) are read as items starting at offset 0, but
+// can't be reopened for writing.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub const MAGIC: &[u8; 8] = b"RDAWGVEC";
+pub const VERSION: u32 = 1;
+pub const HEADER_LEN: usize = 32;
+
+const HAS_CHECKSUM: u64 = 1;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Why a DiskVec file, or other saved file such as CDAWG metadata, can't be loaded. Returned inside
+/// an `anyhow::Error`, so callers that care can `downcast_ref::<FormatError>()`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FormatError {
+    /// Written by a different format version. `found` is None if the file has no header at all,
+    /// e.g. because it was written before headers were added.
+    VersionMismatch {
+        path: PathBuf,
+        found: Option<u32>,
+        expected: u32,
+    },
+    /// Holds items of a different size than the type it is being loaded as.
+    ItemSizeMismatch {
+        path: PathBuf,
+        found: usize,
+        expected: usize,
+    },
+    /// The items don't match the checksum in the header.
+    Corrupted { path: PathBuf },
+    /// Shorter than the header, or cut off partway through an item.
+    Truncated { path: PathBuf, len: u64 },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::VersionMismatch {
+                path,
+                found: None,
+                expected,
+            } => write!(
+                f,
+                "{:?} has no header; it was written by an older version and can only be loaded \
+                 read-only (expected format version {})",
+                path, expected
+            ),
+            FormatError::VersionMismatch {
+                path,
+                found: Some(found),
+                expected,
+            } => write!(
+                f,
+                "{:?} has format version {}, but this version reads {}",
+                path, found, expected
+            ),
+            FormatError::ItemSizeMismatch {
+                path,
+                found,
+                expected,
+            } => write!(
+                f,
+                "{:?} holds items of {} bytes, but {} were expected",
+                path, found, expected
+            ),
+            FormatError::Corrupted { path } => {
+                write!(f, "{:?} is corrupt: its checksum doesn't match", path)
+            }
+            FormatError::Truncated { path, len } => {
+                write!(
+                    f,
+                    "{:?} is truncated: {} bytes is not a whole file",
+                    path, len
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+pub fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// A header for items of `item_size` bytes, with the checksum of `items` if given.
+pub fn header(item_size: usize, items: Option<&[u8]>) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..8].copy_from_slice(MAGIC);
+    header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+    header[12..16].copy_from_slice(&(item_size as u32).to_le_bytes());
+    if let Some(items) = items {
+        header[16..24].copy_from_slice(&checksum(items).to_le_bytes());
+        header[24..32].copy_from_slice(&HAS_CHECKSUM.to_le_bytes());
+    }
+    header
+}
+
+/// Check the header of `bytes`, the whole contents of the file at `path`, and return the number of
+/// items after it. The checksum is only checked if `verify` is set.
+pub fn check(
+    path: &Path,
+    bytes: &[u8],
+    item_size: usize,
+    verify: bool,
+) -> Result<usize, FormatError> {
+    let path = path.to_path_buf();
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        if bytes.len() < HEADER_LEN && MAGIC.starts_with(&bytes[..bytes.len().min(MAGIC.len())]) {
+            return Err(FormatError::Truncated {
+                path,
+                len: bytes.len() as u64,
+            });
+        }
+        return Err(FormatError::VersionMismatch {
+            path,
+            found: None,
+            expected: VERSION,
+        });
+    }
+    if bytes.len() < HEADER_LEN {
+        return Err(FormatError::Truncated {
+            path,
+            len: bytes.len() as u64,
+        });
+    }
+
+    let field = |start: usize, end: usize| {
+        let mut buf = [0; 8];
+        buf[..end - start].copy_from_slice(&bytes[start..end]);
+        u64::from_le_bytes(buf)
+    };
+    let version = field(8, 12) as u32;
+    if version != VERSION {
+        return Err(FormatError::VersionMismatch {
+            path,
+            found: Some(version),
+            expected: VERSION,
+        });
+    }
+    let found_size = field(12, 16) as usize;
+    if found_size != item_size {
+        return Err(FormatError::ItemSizeMismatch {
+            path,
+            found: found_size,
+            expected: item_size,
+        });
+    }
+    let items = &bytes[HEADER_LEN..];
+    if !items.len().is_multiple_of(item_size) {
+        return Err(FormatError::Truncated {
+            path,
+            len: bytes.len() as u64,
+        });
+    }
+    if verify && field(24, 32) & HAS_CHECKSUM != 0 && checksum(items) != field(16, 24) {
+        return Err(FormatError::Corrupted { path });
+    }
+    Ok(items.len().checked_div(item_size).unwrap_or(0))
+}
+
+/// Like `check`, but read a file without a header, as written before headers were added, as items
+/// starting at offset 0. Returns the offset of the items and their number.
+pub fn check_or_legacy(
+    path: &Path,
+    bytes: &[u8],
+    item_size: usize,
+    verify: bool,
+) -> Result<(usize, usize), FormatError> {
+    match check(path, bytes, item_size, verify) {
+        Ok(len) => Ok((HEADER_LEN, len)),
+        Err(FormatError::VersionMismatch { found: None, .. }) => {
+            if !bytes.len().is_multiple_of(item_size) {
+                return Err(FormatError::Truncated {
+                    path: path.to_path_buf(),
+                    len: bytes.len() as u64,
+                });
+            }
+            Ok((0, bytes.len().checked_div(item_size).unwrap_or(0)))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Check the header and checksum of `bytes`, the whole contents of the file at `path`, whatever
+/// its item type.
+pub fn verify(path: &Path, bytes: &[u8]) -> Result<(), FormatError> {
+    let item_size = match bytes.get(12..16) {
+        Some(field) if bytes.starts_with(MAGIC) => {
+            u32::from_le_bytes(field.try_into().unwrap()) as usize
+        }
+        _ => 1,
+    };
+    check(path, bytes, item_size, true).map(|_| ())
+}
+
+/// Clear the checksum flag in `header`, before the items after it are changed.
+pub fn clear_checksum(header: &mut [u8]) {
+    header[16..32].fill(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let path = Path::new("vec.bin");
+        let items = [1, 2, 3, 4];
+        let mut bytes = header(2, Some(&items)).to_vec();
+        bytes.extend_from_slice(&items);
+        assert_eq!(check(path, &bytes, 2, true), Ok(2));
+        assert!(matches!(
+            check(path, &bytes, 4, true),
+            Err(FormatError::ItemSizeMismatch { found: 2, .. })
+        ));
+
+        let mut corrupt = bytes.clone();
+        corrupt[HEADER_LEN] = 9;
+        assert!(matches!(
+            check(path, &corrupt, 2, true),
+            Err(FormatError::Corrupted { .. })
+        ));
+        assert_eq!(check(path, &corrupt, 2, false), Ok(2));
+        clear_checksum(&mut corrupt[..HEADER_LEN]);
+        assert_eq!(check(path, &corrupt, 2, true), Ok(2));
+
+        assert!(matches!(
+            check(path, &bytes[..bytes.len() - 1], 2, true),
+            Err(FormatError::Truncated { .. })
+        ));
+        assert!(matches!(
+            check(path, &bytes[..10], 2, true),
+            Err(FormatError::Truncated { len: 10, .. })
+        ));
+
+        let mut newer = bytes.clone();
+        newer[8] = 2;
+        assert!(matches!(
+            check(path, &newer, 2, true),
+            Err(FormatError::VersionMismatch { found: Some(2), .. })
+        ));
+        // A file written before headers were added.
+        assert!(matches!(
+            check(path, &[0; 64], 2, true),
+            Err(FormatError::VersionMismatch { found: None, .. })
+        ));
+        assert_eq!(check_or_legacy(path, &[0; 64], 2, true), Ok((0, 32)));
+        assert_eq!(check_or_legacy(path, &bytes, 2, true), Ok((HEADER_LEN, 2)));
+        assert!(matches!(
+            check_or_legacy(path, &[0; 63], 2, true),
+            Err(FormatError::Truncated { len: 63, .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify() {
+        let path = Path::new("vec.bin");
+        let items = [1, 2, 3, 4];
+        let mut bytes = header(4, Some(&items)).to_vec();
+        bytes.extend_from_slice(&items);
+        assert_eq!(verify(path, &bytes), Ok(()));
+        bytes[HEADER_LEN] = 9;
+        assert!(matches!(
+            verify(path, &bytes),
+            Err(FormatError::Corrupted { .. })
+        ));
+        assert!(verify(path, &bytes[HEADER_LEN..]).is_err());
+    }
+}
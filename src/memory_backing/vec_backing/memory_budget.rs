@@ -0,0 +1,234 @@
+// Sizing the node and edge caches of a disk build from a memory budget in bytes, rather than a
+// number of cached items.
+//
+// Each cached item costs the size of the item, its key, and the bookkeeping of the LRU it lives in
+// (list pointers, a hash table slot and allocator padding). Before splitting the budget, a safety
+// margin is set aside for everything else the build allocates, along with the token cache and any
+// items pinned by `CachePolicy::Pinned`, which are kept on top of the LRU. The rest is split between
+// nodes and edges in the ratio of `nodes_ratio` to `edges_ratio`, as with `--cache-size`.
+
+use anyhow::{bail, Error, Result};
+use std::fmt;
+use std::mem::size_of;
+use std::str::FromStr;
+
+use super::cache_policy::CachePolicy;
+
+/// Fraction of the budget left for allocations other than the caches.
+pub const SAFETY_MARGIN: f64 = 0.1;
+
+/// Estimated bytes of LRU bookkeeping for each cached item, on top of the item and its key.
+pub const LRU_ENTRY_OVERHEAD: usize = 40;
+
+const UNITS: [(&str, usize); 13] = [
+    ("b", 1),
+    ("k", 1_000),
+    ("kb", 1_000),
+    ("kib", 1 << 10),
+    ("m", 1_000_000),
+    ("mb", 1_000_000),
+    ("mib", 1 << 20),
+    ("g", 1_000_000_000),
+    ("gb", 1_000_000_000),
+    ("gib", 1 << 30),
+    ("t", 1_000_000_000_000),
+    ("tb", 1_000_000_000_000),
+    ("tib", 1 << 40),
+];
+
+/// A number of bytes of RAM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryBudget(pub usize);
+
+impl FromStr for MemoryBudget {
+    type Err = Error;
+
+    /// Parse a number of bytes with an optional unit, e.g. `32GB`, `512MiB` or `1.5t`. Units are
+    /// case-insensitive: `KB`, `MB`, `GB` and `TB` (or just `K`, `M`, `G`, `T`) are powers of 1000,
+    /// and `KiB`, `MiB`, `GiB` and `TiB` are powers of 1024.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let unit = unit.trim().to_ascii_lowercase();
+        let scale = match UNITS.iter().find(|(name, _)| *name == unit) {
+            Some((_, scale)) => *scale,
+            None if unit.is_empty() => 1,
+            None => bail!("Unknown unit in memory budget {:?}; expected e.g. 32GB", s),
+        };
+        let number: f64 = match number.parse() {
+            Ok(number) => number,
+            Err(_) => bail!("Invalid memory budget {:?}; expected e.g. 32GB", s),
+        };
+        Ok(Self((number * scale as f64) as usize))
+    }
+}
+
+impl fmt::Display for MemoryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format_bytes(self.0))
+    }
+}
+
+/// Cache sizes derived from a memory budget, and the bytes each part takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CachePlan {
+    pub budget: usize,
+    pub node_cache_size: usize,
+    pub edge_cache_size: usize,
+    pub node_entry_bytes: usize, // Per cached node, including LRU bookkeeping.
+    pub edge_entry_bytes: usize,
+    pub pinned_bytes: usize,
+    pub token_cache_bytes: usize,
+    pub margin_bytes: usize,
+}
+
+impl CachePlan {
+    pub fn node_cache_bytes(&self) -> usize {
+        self.node_cache_size * self.node_entry_bytes
+    }
+
+    pub fn edge_cache_bytes(&self) -> usize {
+        self.edge_cache_size * self.edge_entry_bytes
+    }
+}
+
+impl fmt::Display for CachePlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Memory budget: {}", format_bytes(self.budget))?;
+        writeln!(
+            f,
+            "  node cache: {} nodes x {}B = {}",
+            self.node_cache_size,
+            self.node_entry_bytes,
+            format_bytes(self.node_cache_bytes())
+        )?;
+        writeln!(
+            f,
+            "  edge cache: {} edges x {}B = {}",
+            self.edge_cache_size,
+            self.edge_entry_bytes,
+            format_bytes(self.edge_cache_bytes())
+        )?;
+        if self.pinned_bytes > 0 {
+            writeln!(f, "  pinned: {}", format_bytes(self.pinned_bytes))?;
+        }
+        if self.token_cache_bytes > 0 {
+            writeln!(f, "  token cache: {}", format_bytes(self.token_cache_bytes))?;
+        }
+        write!(
+            f,
+            "  safety margin: {} ({:.0}%)",
+            format_bytes(self.margin_bytes),
+            SAFETY_MARGIN * 100.
+        )
+    }
+}
+
+impl MemoryBudget {
+    /// Split the budget between caches of nodes of type `N` and edges of type `E`, keyed by
+    /// indices of type `Ix`, after setting aside `token_cache_bytes` and the safety margin.
+    pub fn plan<N, E, Ix>(
+        &self,
+        nodes_ratio: f64,
+        edges_ratio: f64,
+        policy: CachePolicy,
+        token_cache_bytes: usize,
+    ) -> Result<CachePlan> {
+        let node_entry_bytes = size_of::<N>() + size_of::<Ix>() + LRU_ENTRY_OVERHEAD;
+        let edge_entry_bytes = size_of::<E>() + size_of::<Ix>() + LRU_ENTRY_OVERHEAD;
+        let pinned_bytes = match policy {
+            CachePolicy::Pinned(n) => n * (size_of::<Option<N>>() + size_of::<Option<E>>()),
+            _ => 0,
+        };
+        let margin_bytes = (SAFETY_MARGIN * self.0 as f64).ceil() as usize;
+        let reserved = margin_bytes + pinned_bytes + token_cache_bytes;
+        if reserved >= self.0 {
+            bail!(
+                "A memory budget of {} leaves nothing for the node and edge caches after {} for the \
+                 token cache, {} for pinned items and a {} safety margin",
+                self,
+                format_bytes(token_cache_bytes),
+                format_bytes(pinned_bytes),
+                format_bytes(margin_bytes)
+            );
+        }
+
+        // Cache nodes_ratio * x nodes and edges_ratio * x edges, for the largest x that fits.
+        let bytes_per_x =
+            nodes_ratio * node_entry_bytes as f64 + edges_ratio * edge_entry_bytes as f64;
+        let x = (self.0 - reserved) as f64 / bytes_per_x;
+        Ok(CachePlan {
+            budget: self.0,
+            node_cache_size: (nodes_ratio * x).floor() as usize,
+            edge_cache_size: (edges_ratio * x).floor() as usize,
+            node_entry_bytes,
+            edge_entry_bytes,
+            pinned_bytes,
+            token_cache_bytes,
+            margin_bytes,
+        })
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    if bytes < 1000 {
+        return format!("{}B", bytes);
+    }
+    let mut value = bytes as f64 / 1000.;
+    for unit in ["KB", "MB", "GB"] {
+        if value < 1000. {
+            return format!("{:.1}{}", value, unit);
+        }
+        value /= 1000.;
+    }
+    format!("{:.1}TB", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_budget() {
+        let parse = |s: &str| s.parse::<MemoryBudget>().unwrap().0;
+        assert_eq!(parse("32GB"), 32_000_000_000);
+        assert_eq!(parse("32g"), 32_000_000_000);
+        assert_eq!(parse("512MiB"), 512 << 20);
+        assert_eq!(parse("1.5 kb"), 1500);
+        assert_eq!(parse("4096"), 4096);
+        assert!("32 parsecs".parse::<MemoryBudget>().is_err());
+        assert!("GB".parse::<MemoryBudget>().is_err());
+        assert_eq!(MemoryBudget(32_000_000_000).to_string(), "32.0GB");
+        assert_eq!(MemoryBudget(512).to_string(), "512B");
+    }
+
+    #[test]
+    fn test_plan() {
+        let budget = MemoryBudget(1_000_000);
+        let plan = budget
+            .plan::<[u8; 20], [u8; 18], u32>(1., 2., CachePolicy::Lru, 100_000)
+            .unwrap();
+        assert_eq!(plan.node_entry_bytes, 64);
+        assert_eq!(plan.edge_entry_bytes, 62);
+        assert_eq!(plan.margin_bytes, 100_000);
+        // 800KB left for 64B + 2 * 62B per node.
+        assert_eq!(plan.node_cache_size, 4255);
+        assert_eq!(plan.edge_cache_size, 8510);
+        let used = plan.node_cache_bytes() + plan.edge_cache_bytes() + 200_000;
+        assert!(used <= budget.0);
+
+        // Pinned items come out of the budget too.
+        let pinned = budget
+            .plan::<[u8; 20], [u8; 18], u32>(1., 2., CachePolicy::Pinned(1000), 100_000)
+            .unwrap();
+        assert_eq!(pinned.pinned_bytes, 1000 * (21 + 19));
+        assert!(pinned.node_cache_size < plan.node_cache_size);
+
+        assert!(budget
+            .plan::<[u8; 20], [u8; 18], u32>(1., 2., CachePolicy::Lru, 900_000)
+            .is_err());
+    }
+}
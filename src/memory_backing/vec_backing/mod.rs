@@ -1,7 +1,15 @@
 mod cache_config;
+mod cache_policy;
 mod cached_disk_vec;
+mod convert;
 mod disk_vec;
+pub(crate) mod fault;
+pub mod format;
+mod memory_budget;
 
-pub use cache_config::CacheConfig;
+pub use cache_config::{CacheConfig, VecCacheConfig};
+pub use cache_policy::{CachePolicy, CacheStats};
 pub use cached_disk_vec::CachedDiskVec;
-pub use disk_vec::DiskVec;
+pub use convert::convert_disk_vec;
+pub use disk_vec::{verify_checksum, DiskVec};
+pub use memory_budget::{CachePlan, MemoryBudget};
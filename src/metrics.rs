@@ -0,0 +1,227 @@
+// Process-wide metrics for serving (C)DAWG queries.
+//
+// Counters are plain atomics so they can be bumped from the query and disk paths without threading
+// any state through. Collection is off by default and costs one relaxed load per event; call
+// `enable()` to turn it on. Metrics are pulled with `snapshot()`, which can be rendered in the
+// Prometheus text format for a `/metrics` endpoint.
+
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Instant;
+
+// Upper bounds (in seconds) of the query latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 10] =
+    [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+struct Metrics {
+    enabled: AtomicBool,
+    queries: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_micros: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    disk_bytes_read: AtomicU64,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+
+static METRICS: Metrics = Metrics {
+    enabled: AtomicBool::new(false),
+    queries: ZERO,
+    latency_buckets: [ZERO; LATENCY_BUCKETS.len()],
+    latency_micros: ZERO,
+    cache_hits: ZERO,
+    cache_misses: ZERO,
+    disk_bytes_read: ZERO,
+};
+
+pub fn enable() {
+    METRICS.enabled.store(true, Ordering::Relaxed);
+}
+
+pub fn disable() {
+    METRICS.enabled.store(false, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    METRICS.enabled.load(Ordering::Relaxed)
+}
+
+/// Zero all counters.
+pub fn reset() {
+    METRICS.queries.store(0, Ordering::Relaxed);
+    for bucket in METRICS.latency_buckets.iter() {
+        bucket.store(0, Ordering::Relaxed);
+    }
+    METRICS.latency_micros.store(0, Ordering::Relaxed);
+    METRICS.cache_hits.store(0, Ordering::Relaxed);
+    METRICS.cache_misses.store(0, Ordering::Relaxed);
+    METRICS.disk_bytes_read.store(0, Ordering::Relaxed);
+}
+
+pub fn record_cache_hit() {
+    if is_enabled() {
+        METRICS.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn record_cache_miss() {
+    if is_enabled() {
+        METRICS.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn record_disk_read(n_bytes: usize) {
+    if is_enabled() {
+        METRICS
+            .disk_bytes_read
+            .fetch_add(n_bytes as u64, Ordering::Relaxed);
+    }
+}
+
+pub fn record_query(seconds: f64) {
+    if !is_enabled() {
+        return;
+    }
+    METRICS.queries.fetch_add(1, Ordering::Relaxed);
+    METRICS
+        .latency_micros
+        .fetch_add((seconds * 1e6) as u64, Ordering::Relaxed);
+    if let Some(idx) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+        METRICS.latency_buckets[idx].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Records one query and its latency when dropped.
+pub struct QueryTimer {
+    start: Option<Instant>, // None when collection was off as the query started.
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            record_query(start.elapsed().as_secs_f64());
+        }
+    }
+}
+
+/// Time a query for as long as the returned guard is alive. Doesn't read the clock when
+/// collection is off.
+pub fn time_query() -> QueryTimer {
+    QueryTimer {
+        start: is_enabled().then(Instant::now),
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetricsSnapshot {
+    pub queries: u64,
+    pub latency_buckets: Vec<u64>, // Non-cumulative counts per bucket in LATENCY_BUCKETS.
+    pub latency_seconds_sum: f64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub disk_bytes_read: u64,
+}
+
+impl MetricsSnapshot {
+    pub fn cache_hit_rate(&self) -> f64 {
+        let total = self.cache_hits + self.cache_misses;
+        if total == 0 {
+            return 0.;
+        }
+        self.cache_hits as f64 / total as f64
+    }
+
+    /// Render in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# TYPE rusty_dawg_query_seconds histogram");
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.latency_buckets.iter()) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "rusty_dawg_query_seconds_bucket{{le=\"{}\"}} {}",
+                bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "rusty_dawg_query_seconds_bucket{{le=\"+Inf\"}} {}",
+            self.queries
+        );
+        let _ = writeln!(
+            out,
+            "rusty_dawg_query_seconds_sum {}",
+            self.latency_seconds_sum
+        );
+        let _ = writeln!(out, "rusty_dawg_query_seconds_count {}", self.queries);
+        for (name, value) in [
+            ("rusty_dawg_cache_hits_total", self.cache_hits),
+            ("rusty_dawg_cache_misses_total", self.cache_misses),
+            ("rusty_dawg_disk_bytes_read_total", self.disk_bytes_read),
+        ] {
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        out
+    }
+}
+
+pub fn snapshot() -> MetricsSnapshot {
+    MetricsSnapshot {
+        queries: METRICS.queries.load(Ordering::Relaxed),
+        latency_buckets: METRICS
+            .latency_buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect(),
+        latency_seconds_sum: METRICS.latency_micros.load(Ordering::Relaxed) as f64 / 1e6,
+        cache_hits: METRICS.cache_hits.load(Ordering::Relaxed),
+        cache_misses: METRICS.cache_misses.load(Ordering::Relaxed),
+        disk_bytes_read: METRICS.disk_bytes_read.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::frozen::FrozenCdawg;
+    use crate::cdawg::Cdawg;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_snapshot_and_prometheus() {
+        // Other tests may touch the global counters, so only check lower bounds.
+        enable();
+        record_query(0.002);
+        record_cache_hit();
+        record_cache_miss();
+        record_disk_read(16);
+
+        let snapshot = snapshot();
+        assert!(snapshot.queries >= 1);
+        assert!(snapshot.latency_buckets[3] >= 1);
+        assert!(snapshot.cache_hits >= 1);
+        assert!(snapshot.disk_bytes_read >= 16);
+
+        let text = snapshot.to_prometheus();
+        assert!(text.contains("rusty_dawg_query_seconds_bucket{le=\"0.005\"}"));
+        assert!(text.contains("rusty_dawg_disk_bytes_read_total"));
+    }
+
+    #[test]
+    fn test_cdawg_queries_counted() {
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(vec![0, 1, 0, u16::MAX])));
+        cdawg.build();
+        enable();
+        let before = snapshot().queries;
+        cdawg.get_ngram_count(&[0, 1]);
+        cdawg.get_suffix_length_profile(&[1, 0]);
+        FrozenCdawg::new(&cdawg).get_ngram_count(&[0]);
+        assert!(snapshot().queries >= before + 3);
+    }
+}
@@ -0,0 +1,50 @@
+//! The main user-facing types, so downstream code doesn't need to know the module layout:
+//!
+//! ```ignore
+//! use rusty_dawg::prelude::*;
+//!
+//! let tokens = Rc::new(RefCell::new(DiskVec::<u16>::load("tokens.vec")?));
+//! let cdawg: Cdawg<DefaultWeight, DefaultIx, DiskBacking<_, _, _>> =
+//!     Cdawg::load(tokens, "cdawg", CacheConfig::none())?;
+//! ```
+//!
+//! Anything not re-exported here is still reachable through its module, but is more likely to
+//! change between versions.
+
+// DAWGs and CDAWGs.
+pub use crate::cdawg::absolute_discounting::AbsoluteDiscounting;
+pub use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+pub use crate::cdawg::cdawg_state::CdawgState;
+pub use crate::cdawg::count_kind::CountKind;
+pub use crate::cdawg::frozen::FrozenCdawg;
+pub use crate::cdawg::text_query::TextQueryEngine;
+pub use crate::cdawg::token_backing::TokenBacking;
+pub use crate::cdawg::{Cdawg, TopologicalCounter};
+pub use crate::dawg::Dawg;
+
+// Custom traversals.
+pub use crate::walker::{Cursor, DawgPosition, Walk};
+
+// Building from a data reader.
+pub use crate::builder::{CdawgBuilder, DawgBuilder};
+
+// Graph internals needed to inspect states and edges.
+pub use crate::graph::indexing::{DefaultIx, EdgeIndex, IndexType, NodeIndex};
+pub use crate::graph::{EdgeRef, NodeRef};
+pub use crate::weight::{DefaultWeight, Weight};
+
+// Storage.
+pub use crate::memory_backing::{
+    CacheConfig, ColumnarBacking, DiskBacking, DiskVec, MemoryBacking, MmapBacking, RamBacking,
+    TierPolicy, TieredBacking,
+};
+
+// Saving and loading.
+pub use crate::io::load::Load;
+pub use crate::io::Save;
+
+// Tokenizers.
+pub use crate::tokenize::{Detokenize, NullTokenIndex, PretrainedTokenizer, TokenIndex, Tokenize};
+
+// Data readers.
+pub use crate::data_reader::{DataReader, DocumentFilter, JsonlReader, PileReader, TxtReader};
@@ -0,0 +1,157 @@
+// Count-of-counts statistics over the substrings indexed by a DAWG, with Good-Turing estimates.
+//
+// A state other than the initial one represents the substrings whose lengths are greater than the
+// length of its failure and at most its own length, and all of them have the state's count. So N_r,
+// the number of distinct substrings occurring exactly r times, is a sum over states, without
+// enumerating the substrings themselves.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use crate::dawg::Dawg;
+use crate::graph::avl_graph::node::NodeRef;
+use crate::graph::indexing::{DefaultIx, NodeIndex};
+use crate::memory_backing::MemoryBacking;
+use crate::weight::Weight;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CountOfCounts {
+    pub n_r: BTreeMap<usize, u64>, // Number of distinct substrings with each count r > 0.
+}
+
+impl CountOfCounts {
+    pub fn add(&mut self, count: usize, n_types: u64) {
+        if count > 0 && n_types > 0 {
+            *self.n_r.entry(count).or_insert(0) += n_types;
+        }
+    }
+
+    pub fn get(&self, count: usize) -> u64 {
+        self.n_r.get(&count).copied().unwrap_or(0)
+    }
+
+    /// Number of distinct substrings.
+    pub fn n_types(&self) -> u64 {
+        self.n_r.values().sum()
+    }
+
+    /// Number of substring occurrences, i.e., the sum of r * N_r.
+    pub fn n_tokens(&self) -> u64 {
+        self.n_r
+            .iter()
+            .map(|(count, n_types)| *count as u64 * n_types)
+            .sum()
+    }
+
+    /// Good-Turing adjusted count r* = (r + 1) N_{r+1} / N_r. Falls back to r where N_r or N_{r+1}
+    /// is 0, since the estimate is unusable there.
+    pub fn adjusted_count(&self, count: usize) -> f64 {
+        let (n_r, n_next) = (self.get(count), self.get(count + 1));
+        if n_r == 0 || n_next == 0 {
+            return count as f64;
+        }
+        (count + 1) as f64 * n_next as f64 / n_r as f64
+    }
+
+    /// Good-Turing estimate of the probability mass of unseen substrings, N_1 / N.
+    pub fn unseen_mass(&self) -> f64 {
+        match self.n_tokens() {
+            0 => 1.,
+            n_tokens => self.get(1) as f64 / n_tokens as f64,
+        }
+    }
+
+    /// Estimated fraction of occurrences covered by substrings already seen, 1 - N_1 / N.
+    pub fn coverage(&self) -> f64 {
+        1. - self.unseen_mass()
+    }
+}
+
+/// Count-of-counts over all distinct substrings of the corpus. Counts must be filled.
+pub fn count_of_counts<E, W, Mb>(dawg: &Dawg<E, W, DefaultIx, Mb>) -> CountOfCounts
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    let mut counts = CountOfCounts::default();
+    for_each_state(dawg, |count, min_length, max_length| {
+        counts.add(count, max_length - min_length);
+    });
+    counts
+}
+
+/// Count-of-counts of the n-grams of each length n from 1 to `max_n`, in order.
+pub fn count_of_counts_by_length<E, W, Mb>(
+    dawg: &Dawg<E, W, DefaultIx, Mb>,
+    max_n: u64,
+) -> Vec<CountOfCounts>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    let mut counts = vec![CountOfCounts::default(); max_n as usize];
+    for_each_state(dawg, |count, min_length, max_length| {
+        for n in (min_length + 1)..=min(max_length, max_n) {
+            counts[n as usize - 1].add(count, 1);
+        }
+    });
+    counts
+}
+
+// Call `f(count, failure length, length)` for every state other than the initial one.
+fn for_each_state<E, W, Mb, F>(dawg: &Dawg<E, W, DefaultIx, Mb>, mut f: F)
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+    F: FnMut(usize, u64, u64),
+{
+    for idx in 0..dawg.node_count() {
+        let state = NodeIndex::new(idx);
+        if state == dawg.get_initial() {
+            continue;
+        }
+        let failure_length = match dawg.get_node(state).get_failure() {
+            Some(failure) => dawg.get_node(failure).get_length(),
+            None => 0,
+        };
+        let count = dawg.get_node(state).get_count();
+        f(count, failure_length, dawg.get_node(state).get_length());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weight::DefaultWeight;
+
+    #[test]
+    fn test_count_of_counts() {
+        let mut dawg: Dawg<char, DefaultWeight> = Dawg::new();
+        dawg.build(&['a', 'b', 'a', 'b']);
+
+        // a, b and ab occur twice. ba, aba, bab and abab occur once.
+        let counts = count_of_counts(&dawg);
+        assert_eq!(counts.get(1), 4);
+        assert_eq!(counts.get(2), 3);
+        assert_eq!(counts.n_types(), 7);
+        assert_eq!(counts.n_tokens(), 10);
+        assert_eq!(counts.adjusted_count(1), 1.5);
+        assert_eq!(counts.adjusted_count(2), 2.);
+        assert_eq!(counts.unseen_mass(), 0.4);
+        assert!((counts.coverage() - 0.6).abs() < 1e-9);
+
+        let by_length = count_of_counts_by_length(&dawg, 3);
+        assert_eq!(by_length.len(), 3);
+        assert_eq!(by_length[0].n_r, BTreeMap::from([(2, 2)]));
+        assert_eq!(by_length[1].n_r, BTreeMap::from([(1, 1), (2, 1)]));
+        assert_eq!(by_length[2].n_r, BTreeMap::from([(1, 2)]));
+    }
+}
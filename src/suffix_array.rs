@@ -0,0 +1,261 @@
+// Suffix array over a token backing, used as an independent oracle for automaton counts.
+//
+// The array is built with SA-IS (Nong, Zhang & Chan, 2009), which runs in linear time. It takes a
+// usize per token on top of the working memory of the construction, so it is meant for
+// spot-checking counts, not for serving queries. Counting an n-gram takes two binary searches.
+
+use std::cmp::Ordering;
+
+use crate::cdawg::token_backing::TokenBacking;
+
+const EMPTY: usize = usize::MAX;
+
+pub struct SuffixArray {
+    sa: Vec<usize>,
+}
+
+impl SuffixArray {
+    pub fn new<B: TokenBacking<u16> + ?Sized>(tokens: &B) -> Self {
+        // Shift tokens up by one to make room for a sentinel that is smaller than every token.
+        let mut text: Vec<usize> = (0..tokens.len())
+            .map(|idx| tokens.get(idx) as usize + 1)
+            .collect();
+        text.push(0);
+        let mut sa = sais(&text, u16::MAX as usize + 2);
+        // The sentinel suffix always sorts first.
+        sa.remove(0);
+        Self { sa }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sa.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sa.is_empty()
+    }
+
+    /// Start of the `rank`-th smallest suffix.
+    pub fn get(&self, rank: usize) -> usize {
+        self.sa[rank]
+    }
+
+    /// Count the occurrences of `ngram` in `tokens`, which must be the tokens the array was built on.
+    pub fn count<B: TokenBacking<u16> + ?Sized>(&self, tokens: &B, ngram: &[u16]) -> usize {
+        let lower = self
+            .sa
+            .partition_point(|start| compare_prefix(tokens, *start, ngram) == Ordering::Less);
+        let upper = self
+            .sa
+            .partition_point(|start| compare_prefix(tokens, *start, ngram) != Ordering::Greater);
+        upper - lower
+    }
+}
+
+// Compare the first `ngram.len()` tokens of the suffix at `start` with `ngram`.
+fn compare_prefix<B: TokenBacking<u16> + ?Sized>(
+    tokens: &B,
+    start: usize,
+    ngram: &[u16],
+) -> Ordering {
+    for (offset, token) in ngram.iter().enumerate() {
+        if start + offset >= tokens.len() {
+            return Ordering::Less;
+        }
+        match tokens.get(start + offset).cmp(token) {
+            Ordering::Equal => continue,
+            order => return order,
+        }
+    }
+    Ordering::Equal
+}
+
+// Suffix array of `text`, whose symbols are below `alphabet_size` and whose last symbol is a unique
+// minimum.
+fn sais(text: &[usize], alphabet_size: usize) -> Vec<usize> {
+    let n = text.len();
+    if n == 1 {
+        return vec![0];
+    }
+
+    // A suffix is S-type if it is smaller than the next suffix, and L-type otherwise.
+    let mut is_s = vec![false; n];
+    is_s[n - 1] = true;
+    for idx in (0..n - 1).rev() {
+        is_s[idx] = text[idx] < text[idx + 1] || (text[idx] == text[idx + 1] && is_s[idx + 1]);
+    }
+    let is_lms = |idx: usize| idx > 0 && is_s[idx] && !is_s[idx - 1];
+
+    let mut bucket_sizes = vec![0; alphabet_size];
+    for symbol in text.iter() {
+        bucket_sizes[*symbol] += 1;
+    }
+
+    // Sort the LMS substrings by placing LMS suffixes at the ends of their buckets and inducing.
+    let mut sa = vec![EMPTY; n];
+    let mut tails = bucket_tails(&bucket_sizes);
+    for idx in (0..n).filter(|idx| is_lms(*idx)) {
+        tails[text[idx]] -= 1;
+        sa[tails[text[idx]]] = idx;
+    }
+    induce(text, &is_s, &bucket_sizes, &mut sa);
+
+    // Name the LMS substrings by their rank, giving equal substrings the same name.
+    let mut names = vec![EMPTY; n];
+    let mut name = 0;
+    let mut prev: Option<usize> = None;
+    for start in sa.iter().copied().filter(|idx| is_lms(*idx)) {
+        if let Some(prev) = prev {
+            if !lms_substrings_equal(text, &is_s, prev, start) {
+                name += 1;
+            }
+        }
+        names[start] = name;
+        prev = Some(start);
+    }
+    let lms_starts: Vec<usize> = (0..n).filter(|idx| is_lms(*idx)).collect();
+    let reduced: Vec<usize> = lms_starts.iter().map(|idx| names[*idx]).collect();
+
+    // Sort the LMS suffixes, recursing if the names don't determine the order.
+    let n_names = name + 1;
+    let reduced_sa = if n_names < reduced.len() {
+        sais(&reduced, n_names)
+    } else {
+        let mut reduced_sa = vec![0; reduced.len()];
+        for (idx, name) in reduced.iter().enumerate() {
+            reduced_sa[*name] = idx;
+        }
+        reduced_sa
+    };
+
+    // Induce the full suffix array from the sorted LMS suffixes.
+    sa.fill(EMPTY);
+    let mut tails = bucket_tails(&bucket_sizes);
+    for rank in reduced_sa.iter().rev() {
+        let idx = lms_starts[*rank];
+        tails[text[idx]] -= 1;
+        sa[tails[text[idx]]] = idx;
+    }
+    induce(text, &is_s, &bucket_sizes, &mut sa);
+    sa
+}
+
+fn bucket_heads(bucket_sizes: &[usize]) -> Vec<usize> {
+    let mut sum = 0;
+    bucket_sizes
+        .iter()
+        .map(|size| {
+            sum += size;
+            sum - size
+        })
+        .collect()
+}
+
+fn bucket_tails(bucket_sizes: &[usize]) -> Vec<usize> {
+    let mut sum = 0;
+    bucket_sizes
+        .iter()
+        .map(|size| {
+            sum += size;
+            sum
+        })
+        .collect()
+}
+
+// Place L-type suffixes left to right from the bucket heads, then S-type suffixes right to left
+// from the bucket tails.
+fn induce(text: &[usize], is_s: &[bool], bucket_sizes: &[usize], sa: &mut [usize]) {
+    let mut heads = bucket_heads(bucket_sizes);
+    for rank in 0..sa.len() {
+        if sa[rank] != EMPTY && sa[rank] > 0 && !is_s[sa[rank] - 1] {
+            let idx = sa[rank] - 1;
+            sa[heads[text[idx]]] = idx;
+            heads[text[idx]] += 1;
+        }
+    }
+
+    let mut tails = bucket_tails(bucket_sizes);
+    for rank in (0..sa.len()).rev() {
+        if sa[rank] != EMPTY && sa[rank] > 0 && is_s[sa[rank] - 1] {
+            let idx = sa[rank] - 1;
+            tails[text[idx]] -= 1;
+            sa[tails[text[idx]]] = idx;
+        }
+    }
+}
+
+fn lms_substrings_equal(text: &[usize], is_s: &[bool], a: usize, b: usize) -> bool {
+    let n = text.len();
+    // The sentinel is unique.
+    if a == n - 1 || b == n - 1 {
+        return a == b;
+    }
+    let is_lms = |idx: usize| idx > 0 && is_s[idx] && !is_s[idx - 1];
+    let mut offset = 0;
+    loop {
+        if text[a + offset] != text[b + offset] || is_s[a + offset] != is_s[b + offset] {
+            return false;
+        }
+        if offset > 0 {
+            match (is_lms(a + offset), is_lms(b + offset)) {
+                (true, true) => return true,
+                (false, false) => (),
+                _ => return false,
+            }
+        }
+        offset += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_sa(tokens: &[u16]) -> Vec<usize> {
+        let mut sa: Vec<usize> = (0..tokens.len()).collect();
+        sa.sort_by(|a, b| tokens[*a..].cmp(&tokens[*b..]));
+        sa
+    }
+
+    fn naive_count(tokens: &[u16], ngram: &[u16]) -> usize {
+        tokens
+            .windows(ngram.len())
+            .filter(|window| *window == ngram)
+            .count()
+    }
+
+    #[test]
+    fn test_sais_matches_naive() {
+        let texts: Vec<Vec<u16>> = vec![
+            vec![],
+            vec![3],
+            vec![1, 1, 1, 1],
+            vec![0, 1, 2, 0, 1, 0, 1, 2, 0],
+            // "mississippi"
+            vec![2, 1, 4, 4, 1, 4, 4, 1, 3, 3, 1],
+            vec![5, u16::MAX, 5, 0, u16::MAX, 5, 0],
+        ];
+        for tokens in texts {
+            let sa = SuffixArray::new(&tokens);
+            let ranks: Vec<usize> = (0..sa.len()).map(|rank| sa.get(rank)).collect();
+            assert_eq!(ranks, naive_sa(&tokens), "{:?}", tokens);
+        }
+    }
+
+    #[test]
+    fn test_count() {
+        let tokens: Vec<u16> = vec![0, 1, 0, 1, 2, u16::MAX, 1, 0, 1, u16::MAX];
+        let sa = SuffixArray::new(&tokens);
+        for ngram in [
+            vec![0],
+            vec![1],
+            vec![0, 1],
+            vec![1, 0, 1],
+            vec![1, 2],
+            vec![2, 1],
+            vec![1, u16::MAX],
+        ] {
+            assert_eq!(sa.count(&tokens, &ngram), naive_count(&tokens, &ngram));
+        }
+    }
+}
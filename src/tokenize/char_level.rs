@@ -0,0 +1,145 @@
+// Tokenizers for classic string indexing, without a vocabulary to build or download.
+//
+// * `ByteTokenizer` (`--tokenizer bytes`) makes each UTF-8 byte a token, whose ID is its value. All
+//   256 IDs fit in u16, below the reserved end-of-document token u16::MAX.
+// * `CharTokenizer` (`--tokenizer chars`) makes each Unicode character a token, whose ID is its code
+//   point. Text in the Basic Multilingual Plane fits in u16, since u16::MAX is a noncharacter, but
+//   other code points need u32. Like the null tokenizer, it counts the largest ID it has seen, so
+//   `--utype auto` can pick the token type by scanning the corpus.
+
+use anyhow::Result;
+use std::cmp::max;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+
+use crate::tokenize::end::End;
+use crate::tokenize::token_type::token_overflow;
+use crate::tokenize::{Detokenize, Tokenize};
+
+pub const N_BYTES: usize = 256;
+
+// Convert an ID to a token, failing if it doesn't fit or collides with the end-of-document token.
+fn to_token<E>(id: u32) -> Result<E>
+where
+    E: TryFrom<u32> + End + PartialEq,
+{
+    match E::try_from(id) {
+        Ok(token) if token != E::end() => Ok(token),
+        _ => Err(token_overflow(id as u64).into()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByteTokenizer;
+
+impl ByteTokenizer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<E> Tokenize<E> for ByteTokenizer
+where
+    E: Eq + serde::Serialize + Copy + Debug + TryFrom<u32> + End,
+{
+    fn try_build(&mut self, _text: &str) -> Result<()> {
+        // do nothing (every byte is already a token)
+        Ok(())
+    }
+
+    fn try_tokenize(&mut self, text: &str) -> Result<Vec<E>> {
+        text.bytes().map(|byte| to_token(byte as u32)).collect()
+    }
+
+    fn get_count(&self) -> usize {
+        N_BYTES
+    }
+}
+
+impl<E> Detokenize<E> for ByteTokenizer
+where
+    E: Eq + serde::Serialize + Copy + Debug + TryFrom<u32> + End + Into<u64>,
+{
+    // Bytes that aren't a whole character on their own are decoded as U+FFFD.
+    fn decode(&self, token: E) -> Option<String> {
+        let byte = u8::try_from(token.into()).ok()?;
+        Some(String::from_utf8_lossy(&[byte]).into_owned())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CharTokenizer {
+    pub count: usize,
+}
+
+impl CharTokenizer {
+    pub fn new() -> Self {
+        Self { count: 0 }
+    }
+}
+
+impl<E> Tokenize<E> for CharTokenizer
+where
+    E: Eq + serde::Serialize + Copy + Debug + TryFrom<u32> + End,
+{
+    fn try_build(&mut self, _text: &str) -> Result<()> {
+        // do nothing (every character is already a token)
+        Ok(())
+    }
+
+    fn try_tokenize(&mut self, text: &str) -> Result<Vec<E>> {
+        text.chars()
+            .map(|c| {
+                self.count = max(self.count, c as usize + 1);
+                to_token(c as u32)
+            })
+            .collect()
+    }
+
+    fn get_count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<E> Detokenize<E> for CharTokenizer
+where
+    E: Eq + serde::Serialize + Copy + Debug + TryFrom<u32> + End + Into<u64>,
+{
+    fn decode(&self, token: E) -> Option<String> {
+        let id = u32::try_from(token.into()).ok()?;
+        char::from_u32(id).map(String::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RustyDawgError;
+
+    #[test]
+    fn test_bytes() {
+        let mut tokenizer = ByteTokenizer::new();
+        let tokens: Vec<u16> = tokenizer.tokenize("añb");
+        assert_eq!(tokens, [97, 0xc3, 0xb1, 98]);
+        assert_eq!(Tokenize::<u16>::get_count(&tokenizer), 256);
+        assert_eq!(tokenizer.decode(97_u16), Some("a".to_string()));
+        assert_eq!(tokenizer.decode(0xc3_u16), Some("\u{fffd}".to_string()));
+        assert_eq!(tokenizer.decode(u16::MAX), None);
+    }
+
+    #[test]
+    fn test_chars() {
+        let mut tokenizer = CharTokenizer::new();
+        let tokens: Vec<u32> = tokenizer.tokenize("añ😀");
+        assert_eq!(tokens, [97, 0xf1, 0x1f600]);
+        assert_eq!(Tokenize::<u32>::get_count(&tokenizer), 0x1f601);
+        assert_eq!(tokenizer.decode(0xf1_u32), Some("ñ".to_string()));
+        assert_eq!(tokenizer.decode(0xd800_u32), None); // A surrogate isn't a character.
+
+        let err = Tokenize::<u16>::try_tokenize(&mut tokenizer, "a😀").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RustyDawgError>(),
+            Some(RustyDawgError::TokenOverflow(0x1f600))
+        ));
+    }
+}
@@ -1,20 +1,89 @@
+pub mod char_level; // Byte- and character-level tokenizers.
 pub mod end;
-pub mod null_token_index;
-pub mod pretrain_tokenizer;
-pub mod token_index;
+pub(crate) mod null_token_index;
+pub(crate) mod pretrain_tokenizer;
+pub(crate) mod remap; // Maps queries tokenized with another vocabulary onto an index's.
+pub(crate) mod separator; // How documents are separated in the indexed token stream.
+pub mod supertoken; // Merges k consecutive tokens into one symbol to shorten sequences.
+pub(crate) mod token_index;
+pub mod token_type;
 
+pub use self::char_level::{ByteTokenizer, CharTokenizer};
 pub use self::null_token_index::NullTokenIndex;
 pub use self::pretrain_tokenizer::PretrainedTokenizer;
+pub use self::remap::TokenRemapper;
+pub use self::separator::DocumentSeparator;
+pub use self::supertoken::SupertokenTokenizer;
 pub use self::token_index::TokenIndex;
+use anyhow::Result;
 use std::cmp::Eq;
+use std::convert::{TryFrom, TryInto};
 use std::fmt::Debug;
 use std::marker::Copy;
 
+use self::end::End;
+
+/// Tokenizers that are built in rather than loaded from huggingface. Their vocabularies are built
+/// from the corpus or fixed, rather than recorded with the index.
+pub const BUILTIN_TOKENIZERS: [&str; 4] = ["whitespace", "null", "bytes", "chars"];
+
+/// Tokenizer passed by name to `--tokenizer`: `whitespace`, `null`, `bytes`, `chars`, or a
+/// pretrained huggingface tokenizer.
+pub fn load_tokenizer<E>(name: &str) -> Result<Box<dyn Tokenize<E>>>
+where
+    E: Eq
+        + serde::Serialize
+        + Copy
+        + Debug
+        + TryInto<usize>
+        + TryFrom<usize>
+        + TryFrom<u32>
+        + End
+        + 'static,
+    usize: TryFrom<E>,
+{
+    Ok(match name {
+        "whitespace" => Box::new(TokenIndex::new()),
+        "null" => Box::new(NullTokenIndex::new()),
+        "bytes" => Box::new(ByteTokenizer::new()),
+        "chars" => Box::new(CharTokenizer::new()),
+        name => Box::new(PretrainedTokenizer::try_new(name)?),
+    })
+}
+
 pub trait Tokenize<E>
 where
     E: Eq + serde::Serialize + Copy + Debug,
 {
-    fn build(&mut self, text: &str);
-    fn tokenize(&mut self, text: &str) -> Vec<E>;
+    /// Add the tokens of `text` to the vocabulary, failing on tokens that can't be represented.
+    fn try_build(&mut self, text: &str) -> Result<()>;
+    /// Tokenize `text`, failing on tokens that can't be represented or parsed.
+    fn try_tokenize(&mut self, text: &str) -> Result<Vec<E>>;
     fn get_count(&self) -> usize;
+
+    // Like `try_build`, but panics on bad input.
+    fn build(&mut self, text: &str) {
+        self.try_build(text).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // Like `try_tokenize`, but panics on bad input.
+    fn tokenize(&mut self, text: &str) -> Vec<E> {
+        self.try_tokenize(text)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+/// Tokenizers that can also map tokens back to text, for answering string queries.
+pub trait Detokenize<E>: Tokenize<E>
+where
+    E: Eq + serde::Serialize + Copy + Debug,
+{
+    /// Text of a single token, or None if it isn't in the vocabulary.
+    fn decode(&self, token: E) -> Option<String>;
+
+    /// Tokenize a query. Unlike `tokenize`, this shouldn't add special tokens, since a query can
+    /// match anywhere in a document.
+    fn tokenize_query(&mut self, text: &str) -> Result<Vec<E>> {
+        self.try_tokenize(text)
+    }
 }
@@ -4,7 +4,11 @@ use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::fmt::Debug;
 
-use crate::tokenize::Tokenize;
+use anyhow::Result;
+
+use crate::error::RustyDawgError;
+use crate::tokenize::token_type::token_overflow;
+use crate::tokenize::{Detokenize, Tokenize};
 
 pub struct NullTokenIndex {
     pub count: usize,
@@ -21,25 +25,30 @@ impl NullTokenIndex {
         NullTokenIndex { count: 0 }
     }
 
-    fn index<E>(&self, token: &str) -> E
+    fn index<E>(&self, token: &str) -> Result<E>
     where
         E: Eq + serde::Serialize + Copy + Debug + TryInto<usize> + TryFrom<usize>,
         usize: TryFrom<E>,
     {
-        let n: usize = token.parse().unwrap();
-        n.try_into().unwrap_or_else(|_| panic!("Err!!!"))
-        // token.parse().unwrap()
+        let n: usize = token
+            .parse()
+            .map_err(|_| RustyDawgError::InvalidToken(token.to_string()))?;
+        // The max value of E is reserved for the end-of-document token.
+        if E::try_from(n + 1).is_err() {
+            return Err(token_overflow(n as u64).into());
+        }
+        Ok(n.try_into().unwrap_or_else(|_| panic!("Err!!!")))
     }
 
-    fn add<E>(&mut self, token: &str) -> E
+    fn add<E>(&mut self, token: &str) -> Result<E>
     where
         E: Eq + serde::Serialize + Copy + Debug + TryInto<usize> + TryFrom<usize>,
         usize: TryFrom<E>,
     {
-        let index = self.index(token);
+        let index = self.index(token)?;
         let index_usize = usize::try_from(index).unwrap_or_else(|_| panic!("Err!!!")) + 1;
         self.count = max(self.count, index_usize);
-        index
+        Ok(index)
     }
 }
 
@@ -48,13 +57,13 @@ where
     E: Eq + serde::Serialize + Copy + Debug + TryInto<usize> + TryFrom<usize>,
     usize: TryFrom<E>,
 {
-    fn tokenize(&mut self, text: &str) -> Vec<E> {
-        let tokenized_text: Vec<E> = text.split_whitespace().map(|x| self.add(x)).collect();
-        tokenized_text
+    fn try_tokenize(&mut self, text: &str) -> Result<Vec<E>> {
+        text.split_whitespace().map(|x| self.add(x)).collect()
     }
 
-    fn build(&mut self, _text: &str) {
+    fn try_build(&mut self, _text: &str) -> Result<()> {
         // do nothing (text is already tokenized)
+        Ok(())
     }
 
     fn get_count(&self) -> usize {
@@ -62,8 +71,20 @@ where
     }
 }
 
+impl<E> Detokenize<E> for NullTokenIndex
+where
+    E: Eq + serde::Serialize + Copy + Debug + TryInto<usize> + TryFrom<usize>,
+    usize: TryFrom<E>,
+{
+    // Tokens are written as their IDs.
+    fn decode(&self, token: E) -> Option<String> {
+        usize::try_from(token).ok().map(|id| id.to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::error::RustyDawgError;
     use crate::tokenize::{NullTokenIndex, Tokenize};
 
     #[test]
@@ -77,6 +98,28 @@ mod tests {
         assert_eq!(tokens, vec![1, 0, 0, 1]);
     }
 
+    #[test]
+    #[should_panic(expected = "Token ID 65535 doesn't fit")]
+    fn test_overflow() {
+        let mut token_index: Box<dyn Tokenize<u16>> = Box::new(NullTokenIndex::new());
+        token_index.tokenize("1 65535");
+    }
+
+    #[test]
+    fn test_try_tokenize() {
+        let mut token_index: Box<dyn Tokenize<u16>> = Box::new(NullTokenIndex::new());
+        let err = token_index.try_tokenize("1 65535").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RustyDawgError>(),
+            Some(RustyDawgError::TokenOverflow(65535))
+        ));
+        let err = token_index.try_tokenize("1 cat").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RustyDawgError>(),
+            Some(RustyDawgError::InvalidToken(_))
+        ));
+    }
+
     // #[test]
     // fn test_add() {
     //     let mut token_index: TokenIndex<u16> = TokenIndex::new();
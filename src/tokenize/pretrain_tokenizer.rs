@@ -1,4 +1,4 @@
-use anyhow::anyhow;
+use anyhow::Result;
 
 use std::convert::TryFrom;
 use std::convert::TryInto;
@@ -7,8 +7,11 @@ use std::fmt::Debug;
 use std::marker::Copy;
 use tokenizers::tokenizer::Tokenizer;
 
+use crate::cdawg::shared_tokens::{FNV_OFFSET, FNV_PRIME};
+use crate::error::RustyDawgError;
 use crate::tokenize::end::End;
-use crate::tokenize::Tokenize;
+use crate::tokenize::token_type::token_overflow;
+use crate::tokenize::{Detokenize, Tokenize};
 
 // pub(crate) fn tokenize(s: &str) -> impl Iterator<Item = &str> {
 //     s.split_word_bounds().filter(|w| {
@@ -29,14 +32,66 @@ pub struct PretrainedTokenizer {
 
 impl PretrainedTokenizer {
     pub fn new(name: &str) -> Self {
-        let tokenizer = Tokenizer::from_pretrained(name, None)
-            .map_err(|err| anyhow!("Failed to load pretrained tokenizer {} - {}", name, err))
-            .unwrap();
+        Self::try_new(name).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `new`, but fails if the tokenizer can't be loaded, e.g. if there is no such tokenizer.
+    pub fn try_new(name: &str) -> Result<Self> {
+        let tokenizer = Tokenizer::from_pretrained(name, None).map_err(|err| {
+            RustyDawgError::Tokenizer(format!(
+                "Failed to load pretrained tokenizer {} - {}",
+                name, err
+            ))
+        })?;
 
-        PretrainedTokenizer {
+        Ok(PretrainedTokenizer {
             tokenizer,
             add_eos: false,
+        })
+    }
+
+    /// Number of token IDs, including added special tokens.
+    pub fn vocab_size(&self) -> usize {
+        self.tokenizer.get_vocab_size(true)
+    }
+
+    /// FNV-1a hash of the vocabulary, including added special tokens, in ID order. Recorded in the
+    /// build manifest to check that a tokenizer loaded later by name still has the same vocabulary.
+    pub fn vocab_hash(&self) -> u64 {
+        let mut hash = FNV_OFFSET;
+        for id in 0..self.vocab_size() as u32 {
+            let token = self.tokenizer.id_to_token(id).unwrap_or_default();
+            // Terminate each token with a byte that can't occur in UTF-8.
+            for &byte in token.as_bytes().iter().chain(&[0xff]) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
         }
+        hash
+    }
+
+    /// ID of the token that marks the end of a document, if the vocabulary has one of the usual
+    /// spellings.
+    pub fn eos_id(&self) -> Option<u32> {
+        ["<|endoftext|>", "<|end_of_text|>", "</s>", "<eos>", "[SEP]"]
+            .iter()
+            .find_map(|token| self.tokenizer.token_to_id(token))
+    }
+
+    /// Tokenize a query, along with the character span of each token in `text`. Unlike `tokenize`, no
+    /// special tokens are added, since a query can match anywhere in a document.
+    #[allow(clippy::type_complexity)]
+    pub fn encode_with_offsets(
+        &self,
+        text: &str,
+    ) -> anyhow::Result<(Vec<u32>, Vec<(usize, usize)>)> {
+        let encoding = self
+            .tokenizer
+            .encode_char_offsets(text, false)
+            .map_err(|err| {
+                RustyDawgError::Tokenizer(format!("Failed to tokenize query - {}", err))
+            })?;
+        Ok((encoding.get_ids().to_vec(), encoding.get_offsets().to_vec()))
     }
 }
 
@@ -44,32 +99,30 @@ impl<E> Tokenize<E> for PretrainedTokenizer
 where
     E: Eq + serde::Serialize + Copy + Debug + TryFrom<u32> + End,
 {
-    fn build(&mut self, _text: &str) {
+    fn try_build(&mut self, _text: &str) -> Result<()> {
         // do nothing (pretrained tokenizer is already built)
+        Ok(())
     }
 
-    fn tokenize(&mut self, text: &str) -> Vec<E> {
-        // let tokenized_text: Vec<_> = text
-        //     .split_whitespace()
-        //     .map(|x| E::try_from(self.tokenizer.token_to_id(x)
-        //     .unwrap_or_default())
-        //     .unwrap_or_else(|_| panic!("Err!!!")))
-        //     .collect();
-        // tokenized_text
-        // self.tokenizer.encode(text, false).unwrap_or_else(|_| panic!("Err!!!"))
-        let output = self.tokenizer.encode(text, true);
-        let bindings = output.expect("REASON"); //.get_ids();
-        let ids = bindings.get_ids();
-        let mut converted_values: Vec<E> = ids
+    fn try_tokenize(&mut self, text: &str) -> Result<Vec<E>> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(|err| RustyDawgError::Tokenizer(format!("Failed to tokenize - {}", err)))?;
+        let mut converted_values: Vec<E> = encoding
+            .get_ids()
             .iter()
-            .map(|&num| num.try_into().unwrap_or_else(|_| panic!("Err!!!")))
-            .collect();
+            .map(|&num| match E::try_from(num) {
+                Ok(token) if token != E::end() => Ok(token),
+                _ => Err(token_overflow(num as u64)),
+            })
+            .collect::<std::result::Result<_, _>>()?;
 
         if self.add_eos {
             converted_values.push(E::end())
         }
 
-        converted_values
+        Ok(converted_values)
     }
 
     fn get_count(&self) -> usize {
@@ -77,9 +130,30 @@ where
     }
 }
 
+impl<E> Detokenize<E> for PretrainedTokenizer
+where
+    E: Eq + serde::Serialize + Copy + Debug + TryFrom<u32> + End + Into<u64>,
+{
+    fn decode(&self, token: E) -> Option<String> {
+        let id = u32::try_from(token.into()).ok()?;
+        if token == E::end() || id as usize >= self.vocab_size() {
+            return None;
+        }
+        // Special tokens are kept, so EOS is decoded as its spelling rather than dropped.
+        self.tokenizer.decode(&[id], false).ok()
+    }
+
+    fn tokenize_query(&mut self, text: &str) -> Result<Vec<E>> {
+        let (ids, _) = self.encode_with_offsets(text)?;
+        ids.into_iter()
+            .map(|id| id.try_into().map_err(|_| token_overflow(id as u64).into()))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::tokenize::{PretrainedTokenizer, Tokenize};
+    use crate::tokenize::{Detokenize, PretrainedTokenizer, Tokenize};
 
     #[test]
     fn test_gpt2_tokenizer() {
@@ -91,6 +165,28 @@ mod tests {
         assert_eq!(token_index.get_count(), 50257);
 
         assert_eq!(token_index.tokenize("hello world"), [31373, 995]);
+        assert_eq!(PretrainedTokenizer::new("gpt2").eos_id(), Some(50256));
+        assert_ne!(
+            PretrainedTokenizer::new("gpt2").vocab_hash(),
+            PretrainedTokenizer::new("bert-base-uncased").vocab_hash()
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut tokenizer = PretrainedTokenizer::new("gpt2");
+        tokenizer.add_eos = true;
+        let query: Vec<u16> = tokenizer.tokenize_query("hello world").unwrap();
+        assert_eq!(query, [31373, 995]);
+        let decoded: Vec<_> = [31373_u16, 995, 50256, u16::MAX]
+            .iter()
+            .map(|token| Detokenize::<u16>::decode(&tokenizer, *token))
+            .collect();
+        assert_eq!(
+            decoded,
+            [Some("hello"), Some(" world"), Some("<|endoftext|>"), None]
+                .map(|s| s.map(String::from))
+        );
     }
 
     #[test]
@@ -101,4 +197,14 @@ mod tests {
 
         assert_eq!(token_index.tokenize("hello world"), [31373, 995, u16::MAX]);
     }
+
+    #[test]
+    fn test_encode_with_offsets() {
+        let pt = PretrainedTokenizer::new("gpt2");
+        let (ids, offsets) = pt.encode_with_offsets("hello wörld").unwrap();
+        assert_eq!(ids.len(), offsets.len());
+        assert_eq!(ids[0], 31373);
+        assert_eq!(offsets[0], (0, 5));
+        assert_eq!(offsets.last().unwrap().1, 11);
+    }
 }
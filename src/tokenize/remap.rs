@@ -0,0 +1,166 @@
+// Remap queries tokenized with one vocabulary onto the vocabulary an index was built with, e.g. to
+// query a GPT-2 index with Llama tokens.
+//
+// * `Retokenize` decodes the query with its own tokenizer and encodes the text with the index's.
+//   The index's tokenizer is checked against the vocabulary hash in its manifest, if recorded, so a
+//   tokenizer that changed since the build isn't used silently.
+// * `Alignment` maps each query token to a sequence of index tokens with a precomputed table, loaded
+//   from a JSON object like `{"5": [17, 4], "6": [9]}`.
+//
+// Remapping fails with `RustyDawgError::Unmappable` rather than dropping tokens, since a query with
+// tokens missing would match different text.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use crate::build_manifest::BuildManifest;
+use crate::error::RustyDawgError;
+use crate::tokenize::end::End;
+use crate::tokenize::token_type::token_overflow;
+use crate::tokenize::PretrainedTokenizer;
+
+pub enum TokenRemapper {
+    Retokenize {
+        source: Box<PretrainedTokenizer>,
+        target: Box<PretrainedTokenizer>,
+    },
+    Alignment(HashMap<u32, Vec<u32>>),
+}
+
+impl TokenRemapper {
+    /// Remap tokens of the pretrained tokenizer `source` by retokenizing with `target`.
+    pub fn retokenize(source: PretrainedTokenizer, target: PretrainedTokenizer) -> Self {
+        Self::Retokenize {
+            source: Box::new(source),
+            target: Box::new(target),
+        }
+    }
+
+    /// Remap tokens of the pretrained tokenizer `source` onto the tokenizer recorded in the manifest
+    /// of the index at `index_path`.
+    pub fn for_index<P: AsRef<Path>>(source: &str, index_path: P) -> Result<Self> {
+        let index_path = index_path.as_ref();
+        let Some(manifest) = BuildManifest::load_next_to(index_path)? else {
+            let msg = format!("{:?} has no manifest recording its tokenizer", index_path);
+            return Err(RustyDawgError::Tokenizer(msg).into());
+        };
+        let target = PretrainedTokenizer::try_new(&manifest.tokenizer)?;
+        if let Some(vocab_hash) = manifest.vocab_hash {
+            if target.vocab_hash() != vocab_hash {
+                let msg = format!(
+                    "The vocabulary of {} has changed since the index was built",
+                    manifest.tokenizer
+                );
+                return Err(RustyDawgError::Tokenizer(msg).into());
+            }
+        }
+        Ok(Self::retokenize(
+            PretrainedTokenizer::try_new(source)?,
+            target,
+        ))
+    }
+
+    /// Load an alignment from a JSON object mapping each source token ID to its target token IDs.
+    pub fn load_alignment<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let blob: HashMap<String, Vec<u32>> = serde_json::from_str(&text)?;
+        let mut alignment = HashMap::with_capacity(blob.len());
+        for (source, target) in blob {
+            let source: u32 = source
+                .parse()
+                .map_err(|_| RustyDawgError::InvalidToken(source.clone()))?;
+            alignment.insert(source, target);
+        }
+        Ok(Self::Alignment(alignment))
+    }
+
+    /// Map source token IDs to tokens of the index, failing if any can't be mapped.
+    pub fn remap<E>(&self, tokens: &[u32]) -> Result<Vec<E>>
+    where
+        E: TryFrom<u32> + End + PartialEq,
+    {
+        let ids = match self {
+            Self::Retokenize { source, target } => {
+                if let Some(&token) = tokens
+                    .iter()
+                    .find(|&&token| token as usize >= source.vocab_size())
+                {
+                    return Err(unmappable(token, "not in the source vocabulary"));
+                }
+                let text = source
+                    .tokenizer
+                    .decode(tokens, false)
+                    .map_err(|err| unmappable(tokens[0], &err.to_string()))?;
+                let (ids, _) = target.encode_with_offsets(&text)?;
+                ids
+            }
+            Self::Alignment(alignment) => {
+                let mut ids = Vec::with_capacity(tokens.len());
+                for &token in tokens {
+                    match alignment.get(&token) {
+                        Some(targets) => ids.extend_from_slice(targets),
+                        None => return Err(unmappable(token, "not in the alignment")),
+                    }
+                }
+                ids
+            }
+        };
+        ids.into_iter()
+            .map(|id| match E::try_from(id) {
+                Ok(token) if token != E::end() => Ok(token),
+                _ => Err(token_overflow(id as u64).into()),
+            })
+            .collect()
+    }
+}
+
+fn unmappable(token: u32, reason: &str) -> anyhow::Error {
+    RustyDawgError::Unmappable(token as u64, reason.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_alignment() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("alignment.json");
+        fs::write(&path, r#"{"5": [17, 4], "6": [9], "7": [70000]}"#).unwrap();
+        let remapper = TokenRemapper::load_alignment(&path).unwrap();
+
+        let tokens: Vec<u16> = remapper.remap(&[5, 6, 5]).unwrap();
+        assert_eq!(tokens, [17, 4, 9, 17, 4]);
+
+        let err = remapper.remap::<u16>(&[5, 8]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RustyDawgError>(),
+            Some(RustyDawgError::Unmappable(8, _))
+        ));
+        let err = remapper.remap::<u16>(&[7]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RustyDawgError>(),
+            Some(RustyDawgError::TokenOverflow(70000))
+        ));
+        assert_eq!(remapper.remap::<u32>(&[7]).unwrap(), [70000]);
+
+        fs::write(&path, r#"{"cat": [1]}"#).unwrap();
+        assert!(TokenRemapper::load_alignment(&path).is_err());
+    }
+
+    #[test]
+    fn test_retokenize() {
+        let remapper = TokenRemapper::retokenize(
+            PretrainedTokenizer::new("bert-base-uncased"),
+            PretrainedTokenizer::new("gpt2"),
+        );
+        // "hello world" in BERT's vocabulary.
+        let tokens: Vec<u16> = remapper.remap(&[7592, 2088]).unwrap();
+        assert_eq!(tokens, [31373, 995]);
+        assert!(remapper.remap::<u16>(&[1_000_000]).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+// How documents are separated in the indexed token stream. The choice is made at build time and
+// recorded with the index, so queries know which tokens mark document boundaries.
+//
+// * `Reserved` ends each document with the reserved token `E::end()`, which no tokenizer produces.
+//   The CDAWG gives each document its own sink and the DAWG its own document ID state, so matches
+//   never span documents. This is the default, and what indices recorded without a choice used.
+// * `Eos(id)` appends the tokenizer's EOS token. It is indexed like any other token, so matches can
+//   span documents, as in infini-gram.
+// * `Concatenate` adds nothing between documents, so the corpus is indexed as one string.
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::tokenize::end::End;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentSeparator {
+    #[default]
+    Reserved,
+    Eos(u32),
+    #[serde(rename = "none")]
+    Concatenate,
+}
+
+impl DocumentSeparator {
+    /// Parse `reserved`, `eos` or `none`. `eos` needs the ID of the tokenizer's EOS token.
+    pub fn parse(name: &str, eos_id: Option<u32>) -> Result<Self> {
+        match name {
+            "reserved" => Ok(Self::Reserved),
+            "eos" => match eos_id {
+                Some(id) => Ok(Self::Eos(id)),
+                None => bail!("The tokenizer has no EOS token to separate documents with"),
+            },
+            "none" => Ok(Self::Concatenate),
+            _ => bail!(
+                "Unknown document separator {}: expected reserved, eos or none",
+                name
+            ),
+        }
+    }
+
+    /// Token added after each document, if any.
+    pub fn get_token<E: End + TryFrom<u32>>(&self) -> Result<Option<E>> {
+        match *self {
+            Self::Reserved => Ok(Some(E::end())),
+            Self::Eos(id) => E::try_from(id)
+                .map(Some)
+                .map_err(|_| anyhow!("EOS token {} doesn't fit in the token type", id)),
+            Self::Concatenate => Ok(None),
+        }
+    }
+
+    /// Whether the separator ends documents in the graph, rather than being indexed as a token.
+    pub fn ends_documents(&self) -> bool {
+        *self == Self::Reserved
+    }
+
+    /// Whether `token` marks a document boundary.
+    pub fn is_boundary<E: End + PartialEq + Into<u64>>(&self, token: E) -> bool {
+        match *self {
+            Self::Reserved => token == E::end(),
+            Self::Eos(id) => token.into() == id as u64,
+            Self::Concatenate => false,
+        }
+    }
+}
+
+impl fmt::Display for DocumentSeparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Reserved => write!(f, "reserved"),
+            Self::Eos(id) => write!(f, "eos ({})", id),
+            Self::Concatenate => write!(f, "none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_get_token() {
+        let separator = DocumentSeparator::parse("eos", Some(50256)).unwrap();
+        assert_eq!(separator, DocumentSeparator::Eos(50256));
+        assert_eq!(separator.get_token::<u16>().unwrap(), Some(50256));
+        assert!(!separator.ends_documents());
+        assert!(separator.is_boundary(50256u16));
+        assert!(!separator.is_boundary(u16::MAX));
+
+        let reserved = DocumentSeparator::parse("reserved", None).unwrap();
+        assert_eq!(reserved.get_token::<u16>().unwrap(), Some(u16::MAX));
+        assert!(reserved.is_boundary(u16::MAX));
+        let none = DocumentSeparator::parse("none", None).unwrap();
+        assert_eq!(none.get_token::<u16>().unwrap(), None);
+
+        assert!(DocumentSeparator::parse("eos", None).is_err());
+        assert!(DocumentSeparator::parse("bos", Some(0)).is_err());
+        assert!(DocumentSeparator::Eos(70000).get_token::<u16>().is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let json = serde_json::to_string(&DocumentSeparator::Eos(2)).unwrap();
+        assert_eq!(json, r#"{"eos":2}"#);
+        let none: DocumentSeparator = serde_json::from_str(r#""none""#).unwrap();
+        assert_eq!(none, DocumentSeparator::Concatenate);
+    }
+}
@@ -0,0 +1,262 @@
+// Supertokens: non-overlapping groups of k consecutive tokens, merged into one symbol.
+//
+// Indexing supertokens instead of tokens divides the sequence length by k. Each document is split
+// into chunks of k tokens starting at its first token; the last chunk may be shorter. A supertoken
+// packs up to 3 u16 tokens into a u64, along with the number of tokens in its top 16 bits.
+//
+// An occurrence of a query can start at any of the k positions within a chunk, so queries try every
+// alignment: the first `o` query tokens must end a chunk (the head), full chunks follow, and the
+// remaining tokens must start a chunk (the tail). The head and tail are matched against the edges
+// of the DAWG, and the results of all alignments are merged.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::dawg::Dawg;
+use crate::graph::avl_graph::edge::EdgeRef;
+use crate::graph::avl_graph::node::NodeRef;
+use crate::graph::indexing::{DefaultIx, NodeIndex};
+use crate::memory_backing::MemoryBacking;
+use crate::tokenize::Tokenize;
+use crate::weight::Weight;
+
+pub const MAX_K: usize = 3;
+const SLOT_BITS: usize = 16;
+const LEN_SHIFT: usize = 48;
+
+/// Pack up to `MAX_K` tokens into a supertoken.
+pub fn pack(tokens: &[u16]) -> u64 {
+    debug_assert!(tokens.len() <= MAX_K);
+    let mut supertoken = (tokens.len() as u64) << LEN_SHIFT;
+    for (slot, token) in tokens.iter().enumerate() {
+        supertoken |= (*token as u64) << (slot * SLOT_BITS);
+    }
+    supertoken
+}
+
+/// Unpack the tokens of a supertoken, or None if it isn't one (e.g., a document ID token).
+pub fn unpack(supertoken: u64) -> Option<Vec<u16>> {
+    let len = (supertoken >> LEN_SHIFT) as usize;
+    if len == 0 || len > MAX_K {
+        return None;
+    }
+    let tokens = (0..len)
+        .map(|slot| (supertoken >> (slot * SLOT_BITS)) as u16)
+        .collect();
+    Some(tokens)
+}
+
+/// Merge a document's tokens into supertokens of `k` tokens.
+pub fn merge(tokens: &[u16], k: usize) -> Vec<u64> {
+    tokens.chunks(k).map(pack).collect()
+}
+
+fn check_k(k: usize) -> Result<()> {
+    if k == 0 || k > MAX_K {
+        bail!(
+            "Supertokens must have between 1 and {} tokens, got {}",
+            MAX_K,
+            k
+        );
+    }
+    Ok(())
+}
+
+/// Tokenizer that merges the output of another tokenizer into supertokens. Each call to
+/// `tokenize` is treated as one document.
+pub struct SupertokenTokenizer {
+    inner: Box<dyn Tokenize<u16>>,
+    k: usize,
+}
+
+impl SupertokenTokenizer {
+    pub fn new(inner: Box<dyn Tokenize<u16>>, k: usize) -> Result<Self> {
+        check_k(k)?;
+        Ok(Self { inner, k })
+    }
+
+    pub fn get_k(&self) -> usize {
+        self.k
+    }
+}
+
+impl Tokenize<u64> for SupertokenTokenizer {
+    fn try_build(&mut self, text: &str) -> Result<()> {
+        self.inner.try_build(text)
+    }
+
+    fn try_tokenize(&mut self, text: &str) -> Result<Vec<u64>> {
+        Ok(merge(&self.inner.try_tokenize(text)?, self.k))
+    }
+
+    fn get_count(&self) -> usize {
+        self.inner.get_count()
+    }
+}
+
+// Whether `supertoken` has `tokens` at `start`. If `full`, it must also have exactly `k` tokens.
+fn matches_at(supertoken: u64, start: usize, tokens: &[u16], full: bool, k: usize) -> bool {
+    match unpack(supertoken) {
+        Some(slots) => {
+            (!full || slots.len() == k)
+                && slots.len() >= start + tokens.len()
+                && slots[start..start + tokens.len()] == *tokens
+        }
+        None => false,
+    }
+}
+
+fn matching_targets<W, Mb>(
+    dawg: &Dawg<u64, W, DefaultIx, Mb>,
+    state: NodeIndex,
+    start: usize,
+    tokens: &[u16],
+    full: bool,
+    k: usize,
+) -> Vec<NodeIndex>
+where
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, u64, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    dawg.get_graph()
+        .edges(state)
+        .filter(|edge| matches_at(edge.get_weight(), start, tokens, full, k))
+        .map(|edge| edge.get_target())
+        .collect()
+}
+
+/// Count the occurrences of a token n-gram in a DAWG built over supertokens of `k` tokens, summing
+/// over all alignments of the n-gram with the chunk boundaries.
+pub fn get_ngram_count<W, Mb>(
+    dawg: &Dawg<u64, W, DefaultIx, Mb>,
+    ngram: &[u16],
+    k: usize,
+) -> Result<usize>
+where
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, u64, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    check_k(k)?;
+    if ngram.is_empty() {
+        return Ok(dawg.get_node(dawg.get_initial()).get_count());
+    }
+
+    let mut count = 0;
+    // `offset` is the number of n-gram tokens in the chunk where the n-gram starts, if that chunk
+    // doesn't start with it.
+    for offset in 0..k {
+        let (mut states, mut rest) = if offset == 0 {
+            (vec![dawg.get_initial()], ngram)
+        } else {
+            let head_len = usize::min(offset, ngram.len());
+            let (head, rest) = ngram.split_at(head_len);
+            // If more tokens follow, the head must fill the rest of a full chunk.
+            let full = !rest.is_empty();
+            let targets = matching_targets(dawg, dawg.get_initial(), k - offset, head, full, k);
+            (targets, rest)
+        };
+
+        while rest.len() >= k {
+            let supertoken = pack(&rest[..k]);
+            states = states
+                .into_iter()
+                .filter_map(|state| dawg.transition(state, supertoken, false))
+                .collect();
+            rest = &rest[k..];
+        }
+
+        for state in states {
+            if rest.is_empty() {
+                count += dawg.get_node(state).get_count();
+            } else {
+                for target in matching_targets(dawg, state, 0, rest, false, k) {
+                    count += dawg.get_node(target).get_count();
+                }
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Longest substring of `query` made of whole chunks in some alignment that occurs in the DAWG,
+/// in tokens. This is a lower bound on the true longest match, which can extend up to `k - 1`
+/// more tokens on each side.
+pub fn get_max_factor_length<W, Mb>(
+    dawg: &Dawg<u64, W, DefaultIx, Mb>,
+    query: &[u16],
+    k: usize,
+) -> Result<usize>
+where
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, u64, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    check_k(k)?;
+    let mut max_length = 0;
+    for offset in 0..usize::min(k, query.len()) {
+        let supertokens: Vec<u64> = query[offset..].chunks_exact(k).map(pack).collect();
+        let length = dawg.get_max_factor_length(supertokens) as usize * k;
+        max_length = usize::max(max_length, length);
+    }
+    Ok(max_length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weight::DefaultWeight;
+
+    fn count_naive(corpus: &[Vec<u16>], ngram: &[u16]) -> usize {
+        corpus
+            .iter()
+            .map(|doc| doc.windows(ngram.len()).filter(|w| *w == ngram).count())
+            .sum()
+    }
+
+    #[test]
+    fn test_pack_unpack() {
+        assert_eq!(unpack(pack(&[1, 2])), Some(vec![1, 2]));
+        assert_eq!(unpack(pack(&[u16::MAX])), Some(vec![u16::MAX]));
+        assert_eq!(unpack(u64::MAX), None);
+        assert_eq!(merge(&[1, 2, 3], 2), vec![pack(&[1, 2]), pack(&[3])]);
+    }
+
+    #[test]
+    fn test_get_ngram_count() {
+        let corpus: Vec<Vec<u16>> = vec![vec![0, 1, 0, 1, 2, 3, 0], vec![1, 0, 1, 2]];
+        for k in 1..=MAX_K {
+            let mut dawg: Dawg<u64, DefaultWeight> = Dawg::new();
+            let (mut last, mut length) = (dawg.get_initial(), 0);
+            for (doc_id, doc) in corpus.iter().enumerate() {
+                for supertoken in merge(doc, k) {
+                    (last, length) = dawg.extend(supertoken, last, length);
+                }
+                (last, length) = dawg.end_document(last, u64::MAX, doc_id as u64);
+            }
+
+            for ngram in [
+                vec![0],
+                vec![0, 1],
+                vec![1, 0, 1],
+                vec![0, 1, 2],
+                vec![2, 3, 0],
+            ] {
+                let count = get_ngram_count(&dawg, &ngram, k).unwrap();
+                assert_eq!(count, count_naive(&corpus, &ngram), "{:?}, k={}", ngram, k);
+            }
+            assert_eq!(get_ngram_count(&dawg, &[3, 1], k).unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn test_get_max_factor_length() {
+        let mut dawg: Dawg<u64, DefaultWeight> = Dawg::new();
+        dawg.build(&merge(&[0, 1, 2, 3, 4, 5], 2));
+        // [2, 3, 4, 5] is only found with the second alignment of the query.
+        let query = [9, 2, 3, 4, 5, 9];
+        assert_eq!(get_max_factor_length(&dawg, &query, 2).unwrap(), 4);
+        assert!(get_max_factor_length(&dawg, &query, 4).is_err());
+    }
+}
@@ -1,4 +1,7 @@
-use crate::tokenize::Tokenize;
+use anyhow::Result;
+
+use crate::tokenize::token_type::token_overflow;
+use crate::tokenize::{Detokenize, Tokenize};
 
 use std::collections::HashMap;
 use std::convert::TryFrom;
@@ -60,10 +63,19 @@ where
     }
 
     pub fn add(&mut self, token: &str) -> E {
+        self.try_add(token).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `add`, but fails if the vocabulary outgrows the token type.
+    pub fn try_add(&mut self, token: &str) -> Result<E> {
         let token_string = token.to_string();
         match self.token_to_index.get(token) {
-            Some(ptr) => *ptr,
+            Some(ptr) => Ok(*ptr),
             None => {
+                // The max value of E is reserved for the end-of-document token.
+                if E::try_from(self.count + 1).is_err() {
+                    return Err(token_overflow(self.count as u64).into());
+                }
                 self.token_to_index.insert(
                     token_string,
                     (self.count).try_into().unwrap_or_else(|_| {
@@ -73,9 +85,9 @@ where
                 // TODO: Could optimize this to only store each string once.
                 self.index_to_token.push(token.to_string());
                 self.count += 1;
-                (self.count - 1)
+                Ok((self.count - 1)
                     .try_into()
-                    .unwrap_or_else(|_| panic!("Err!!!"))
+                    .unwrap_or_else(|_| panic!("Err!!!")))
             }
         }
     }
@@ -93,13 +105,16 @@ where
     E: Eq + serde::Serialize + Copy + Debug + TryInto<usize> + TryFrom<usize>,
     usize: TryFrom<E>,
 {
-    fn build(&mut self, text: &str) {
-        let _tokens: Vec<_> = text.split_whitespace().map(|x| self.add(x)).collect();
+    fn try_build(&mut self, text: &str) -> Result<()> {
+        for token in text.split_whitespace() {
+            self.try_add(token)?;
+        }
+        Ok(())
     }
 
-    fn tokenize(&mut self, text: &str) -> Vec<E> {
+    fn try_tokenize(&mut self, text: &str) -> Result<Vec<E>> {
         let tokenized_text: Vec<E> = text.split_whitespace().map(|x| self.index(x)).collect();
-        tokenized_text
+        Ok(tokenized_text)
     }
 
     fn get_count(&self) -> usize {
@@ -107,10 +122,31 @@ where
     }
 }
 
+impl<E> Detokenize<E> for TokenIndex<E>
+where
+    E: Eq + serde::Serialize + Copy + Debug + TryInto<usize> + TryFrom<usize>,
+    usize: TryFrom<E>,
+{
+    fn decode(&self, token: E) -> Option<String> {
+        let index = usize::try_from(token).ok()?;
+        self.index_to_token.get(index).cloned()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::tokenize::{TokenIndex, Tokenize};
 
+    #[test]
+    #[should_panic(expected = "Token ID 255 doesn't fit")]
+    fn test_overflow() {
+        let mut token_index: TokenIndex<u8> = TokenIndex::new();
+        let words: Vec<_> = (0..252).map(|idx| idx.to_string()).collect();
+        token_index.build(&words.join(" "));
+        assert_eq!(token_index.get_count(), 255);
+        token_index.build("overflow");
+    }
+
     #[test]
     fn test_build_tokenizer() {
         let mut token_index: TokenIndex<u16> = TokenIndex::new();
@@ -0,0 +1,232 @@
+// Choose and validate the integer type used to store tokens (`--utype`) and check that the corpus
+// fits in the node index width.
+
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::RustyDawgError;
+use crate::graph::indexing::IndexWidth;
+use crate::tokenize::{ByteTokenizer, CharTokenizer, NullTokenIndex, TokenIndex, Tokenize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TokenType {
+    U16,
+    U32,
+    Usize,
+}
+
+impl TokenType {
+    /// Number of distinct token IDs representable. The max value is reserved for end-of-document.
+    pub fn n_ids(&self) -> usize {
+        match self {
+            TokenType::U16 => u16::MAX as usize,
+            TokenType::U32 => u32::MAX as usize,
+            TokenType::Usize => usize::MAX,
+        }
+    }
+
+    pub fn fits(&self, vocab_size: usize) -> bool {
+        vocab_size <= self.n_ids()
+    }
+
+    /// Convert a token of this type to `to`, mapping the end-of-document token to its max value.
+    /// Fails if the token doesn't fit.
+    pub fn convert_token(&self, token: u64, to: TokenType) -> Result<u64> {
+        if token == self.n_ids() as u64 {
+            return Ok(to.n_ids() as u64);
+        }
+        if token >= to.n_ids() as u64 {
+            bail!("Token {} does not fit in {}", token, to);
+        }
+        Ok(token)
+    }
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TokenType::U16 => "u16",
+            TokenType::U32 => "u32",
+            TokenType::Usize => "usize",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for TokenType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "u16" => Ok(TokenType::U16),
+            "u32" => Ok(TokenType::U32),
+            "usize" => Ok(TokenType::Usize),
+            _ => Err(RustyDawgError::InvalidTokenType(s.to_string()).into()),
+        }
+    }
+}
+
+/// Resolve a `--utype` value. `auto` picks the smallest type that fits the vocabulary, so its size
+/// must be known, by scanning the corpus if necessary (see `scan_vocab_size`). An explicit type is
+/// checked against the vocabulary size if it is known.
+pub fn select_token_type(utype: &str, vocab_size: Option<usize>) -> Result<TokenType> {
+    if utype == "auto" {
+        let Some(size) = vocab_size else {
+            bail!("--utype auto needs the vocabulary size");
+        };
+        return Ok([TokenType::U16, TokenType::U32, TokenType::Usize]
+            .into_iter()
+            .find(|token_type| token_type.fits(size))
+            .unwrap());
+    }
+
+    let token_type: TokenType = utype.parse()?;
+    if let Some(size) = vocab_size {
+        if !token_type.fits(size) {
+            return Err(RustyDawgError::VocabTooLarge {
+                token_type: token_type.to_string(),
+                n_ids: token_type.n_ids(),
+                vocab_size: size,
+            }
+            .into());
+        }
+    }
+    Ok(token_type)
+}
+
+/// Size of the vocabulary that `tokenizer` (`whitespace`, `null`, `bytes` or `chars`) builds on
+/// `texts`, for picking a token type when it isn't known up front. Returns None for pretrained
+/// tokenizers.
+pub fn scan_vocab_size<I, S>(texts: I, tokenizer: &str) -> Result<Option<usize>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut index: Box<dyn Tokenize<usize>> = match tokenizer {
+        "whitespace" => Box::new(TokenIndex::new()),
+        "null" => Box::new(NullTokenIndex::new()),
+        "bytes" => Box::new(ByteTokenizer::new()),
+        "chars" => Box::new(CharTokenizer::new()),
+        _ => return Ok(None),
+    };
+    for text in texts {
+        // Token IDs are only collected by tokenizing, and words only by building.
+        if tokenizer != "whitespace" {
+            index.try_tokenize(text.as_ref())?;
+        } else {
+            index.try_build(text.as_ref())?;
+        }
+    }
+    Ok(Some(index.get_count()))
+}
+
+/// Error for a token ID that the token type can't hold. Tokenizers fail with it rather than wrapping
+/// the ID or letting it collide with the reserved end-of-document token and corrupt the graph.
+pub(crate) fn token_overflow(id: u64) -> RustyDawgError {
+    RustyDawgError::TokenOverflow(id)
+}
+
+/// Check that the estimated numbers of nodes and edges, and token positions, fit in the index width.
+pub fn validate_index_width(
+    width: IndexWidth,
+    n_tokens: usize,
+    n_nodes: usize,
+    n_edges: usize,
+) -> Result<()> {
+    // The max value is reserved as a null index.
+    let max_index = width.max_index();
+    for (name, value) in [("tokens", n_tokens), ("nodes", n_nodes), ("edges", n_edges)] {
+        if value >= max_index {
+            bail!(
+                "Estimated # {} ({}) does not fit in the {} index (max {}). Try --index-width 64",
+                name,
+                value,
+                width,
+                max_index - 1
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_auto() {
+        assert_eq!(
+            select_token_type("auto", Some(50257)).unwrap(),
+            TokenType::U16
+        );
+        assert_eq!(
+            select_token_type("auto", Some(128256)).unwrap(),
+            TokenType::U32
+        );
+        assert!(select_token_type("auto", None).is_err());
+    }
+
+    #[test]
+    fn test_select_explicit() {
+        assert_eq!(
+            select_token_type("usize", Some(50257)).unwrap(),
+            TokenType::Usize
+        );
+        assert!(select_token_type("u16", Some(128256)).is_err());
+        assert!(select_token_type("u8", None).is_err());
+    }
+
+    #[test]
+    fn test_convert_token() {
+        let eod = u32::MAX as u64;
+        assert_eq!(
+            TokenType::U32.convert_token(eod, TokenType::U16).unwrap(),
+            u16::MAX as u64
+        );
+        assert_eq!(
+            TokenType::U32.convert_token(42, TokenType::U16).unwrap(),
+            42
+        );
+        assert!(TokenType::U32
+            .convert_token(1 << 16, TokenType::U16)
+            .is_err());
+        assert_eq!(
+            TokenType::U16
+                .convert_token(u16::MAX as u64, TokenType::U32)
+                .unwrap(),
+            eod
+        );
+    }
+
+    #[test]
+    fn test_scan_vocab_size() {
+        let texts = ["the cat", "the dog sat"];
+        // Four words, plus <unk>, <bos> and <eos>.
+        assert_eq!(scan_vocab_size(texts, "whitespace").unwrap(), Some(7));
+        assert_eq!(
+            scan_vocab_size(["3 70000", "12"], "null").unwrap(),
+            Some(70001)
+        );
+        assert_eq!(scan_vocab_size(texts, "gpt2").unwrap(), None);
+        assert_eq!(scan_vocab_size(texts, "bytes").unwrap(), Some(256));
+        assert_eq!(scan_vocab_size(["ab", "c"], "chars").unwrap(), Some(100));
+        assert!(scan_vocab_size(["3 cat"], "null").is_err());
+
+        let vocab_size = scan_vocab_size(["3 70000"], "null").unwrap();
+        assert_eq!(
+            select_token_type("auto", vocab_size).unwrap(),
+            TokenType::U32
+        );
+    }
+
+    #[test]
+    fn test_validate_index_width() {
+        let width = IndexWidth::Bits40;
+        assert!(validate_index_width(width, 1 << 30, 1 << 31, 1 << 32).is_ok());
+        assert!(validate_index_width(width, 1 << 30, 1 << 31, 1 << 40).is_err());
+        let width = IndexWidth::new(64).unwrap();
+        assert!(validate_index_width(width, 1 << 30, 1 << 31, 1 << 40).is_ok());
+        assert!(IndexWidth::new(32).is_err());
+    }
+}
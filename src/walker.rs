@@ -0,0 +1,332 @@
+// The supported way to write custom traversals of a DAWG or CDAWG, without reaching into
+// `get_graph()`. A `Cursor` stands at a position in the automaton, i.e., the end of some matched
+// string, and can `enter` a token to extend the match or `fail` to the longest suffix in a smaller
+// state. Positions depend only on the automaton, not on how its graph is stored, so a traversal
+// works the same with any memory backing.
+//
+// In a DAWG, every position is a state. In a CDAWG, a position can also be partway along an edge,
+// where it has the count and id of the state at the end of the edge.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+use crate::cdawg::cdawg_edge_weight::CdawgEdgeWeight;
+use crate::cdawg::cdawg_state::CdawgState;
+use crate::cdawg::token_backing::CdawgToken;
+use crate::cdawg::Cdawg;
+use crate::dawg::Dawg;
+use crate::graph::indexing::{DefaultIx, IndexType, NodeIndex};
+use crate::graph::NodeRef;
+use crate::memory_backing::MemoryBacking;
+use crate::weight::Weight;
+
+/// An automaton that a `Cursor` can walk.
+pub trait Walk {
+    type Token: Copy;
+    type Position: Copy + Debug;
+
+    /// The position of the empty string.
+    fn start(&self) -> Self::Position;
+
+    /// The position after `token`, if the matched string followed by `token` occurs.
+    fn enter(&self, position: Self::Position, token: Self::Token) -> Option<Self::Position>;
+
+    /// The position of the longest suffix in a different state, or None at the start.
+    fn fail(&self, position: Self::Position) -> Option<Self::Position>;
+
+    /// Number of occurrences of the matched string.
+    fn count(&self, position: Self::Position) -> usize;
+
+    /// Length of the matched string.
+    fn length(&self, position: Self::Position) -> u64;
+
+    /// Index of the state the position is in, which is stable for a given automaton.
+    fn state_id(&self, position: Self::Position) -> usize;
+}
+
+/// A position in a DAWG: a state, and the length of the string matched to get there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DawgPosition<Ix = DefaultIx> {
+    pub state: NodeIndex<Ix>,
+    pub length: u64,
+}
+
+pub struct Cursor<'a, A: Walk> {
+    automaton: &'a A,
+    position: A::Position,
+}
+
+impl<'a, A: Walk> Cursor<'a, A> {
+    /// A cursor at the start of `automaton`.
+    pub fn new(automaton: &'a A) -> Self {
+        Self::at(automaton, automaton.start())
+    }
+
+    /// A cursor at a position saved with `position()`.
+    pub fn at(automaton: &'a A, position: A::Position) -> Self {
+        Self {
+            automaton,
+            position,
+        }
+    }
+
+    pub fn position(&self) -> A::Position {
+        self.position
+    }
+
+    pub fn reset(&mut self) {
+        self.position = self.automaton.start();
+    }
+
+    /// Extend the match by `token`. Returns false, without moving, if the extension doesn't occur.
+    pub fn enter(&mut self, token: A::Token) -> bool {
+        match self.automaton.enter(self.position, token) {
+            Some(position) => {
+                self.position = position;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Shorten the match to the longest suffix in a different state. Returns false at the start.
+    pub fn fail(&mut self) -> bool {
+        match self.automaton.fail(self.position) {
+            Some(position) => {
+                self.position = position;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Extend the match by `token`, failing first as many times as needed, like
+    /// `transition_and_count`. Ends at the start if no suffix can be extended.
+    pub fn enter_or_fail(&mut self, token: A::Token) {
+        while !self.enter(token) {
+            if !self.fail() {
+                return;
+            }
+        }
+    }
+
+    pub fn counts(&self) -> usize {
+        self.automaton.count(self.position)
+    }
+
+    pub fn length(&self) -> u64 {
+        self.automaton.length(self.position)
+    }
+
+    pub fn emit_state_id(&self) -> usize {
+        self.automaton.state_id(self.position)
+    }
+}
+
+impl<E, W, Mb> Walk for Dawg<E, W, DefaultIx, Mb>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    type Token = E;
+    type Position = DawgPosition;
+
+    fn start(&self) -> DawgPosition {
+        DawgPosition {
+            state: self.get_initial(),
+            length: 0,
+        }
+    }
+
+    fn enter(&self, position: DawgPosition, token: E) -> Option<DawgPosition> {
+        let state = self.transition(position.state, token, false)?;
+        Some(DawgPosition {
+            state,
+            length: position.length + 1,
+        })
+    }
+
+    fn fail(&self, position: DawgPosition) -> Option<DawgPosition> {
+        let state = self.get_node(position.state).get_failure()?;
+        Some(DawgPosition {
+            state,
+            length: self.get_node(state).get_length(),
+        })
+    }
+
+    fn count(&self, position: DawgPosition) -> usize {
+        self.get_node(position.state).get_count()
+    }
+
+    fn length(&self, position: DawgPosition) -> u64 {
+        position.length
+    }
+
+    fn state_id(&self, position: DawgPosition) -> usize {
+        position.state.index()
+    }
+}
+
+impl<E, W, Mb> Dawg<E, W, DefaultIx, Mb>
+where
+    E: Eq + Ord + Serialize + for<'de> Deserialize<'de> + Copy + Debug,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, E, DefaultIx>,
+    Mb::EdgeRef: Copy,
+{
+    /// A cursor at the initial state, for custom traversals.
+    pub fn walker(&self) -> Cursor<'_, Self> {
+        Cursor::new(self)
+    }
+}
+
+impl<W, Ix, Mb, T> Walk for Cdawg<W, Ix, Mb, T>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    Mb::EdgeRef: Copy,
+    T: CdawgToken,
+{
+    type Token = T;
+    type Position = CdawgState<Ix>;
+
+    fn start(&self) -> CdawgState<Ix> {
+        self.get_initial()
+    }
+
+    // The successful branches of `Cdawg::transition_and_count`.
+    fn enter(&self, cs: CdawgState<Ix>, token: T) -> Option<CdawgState<Ix>> {
+        let state = cs.target?;
+        if cs.start < cs.end {
+            // On an edge, the next token is fixed.
+            let mut next_cs = cs;
+            next_cs.start += 1;
+            next_cs.length += 1;
+            return (self.get_token(cs.start) == token).then_some(next_cs);
+        }
+        let edge_idx = self.get_edge_by_token(state, token)?;
+        let (start, end, target) = self.get_start_end_target(edge_idx);
+        Some(CdawgState {
+            state,
+            edge_start: start - 1,
+            start,
+            end,
+            target: Some(target),
+            length: cs.length + 1,
+        })
+    }
+
+    // The failing branches of `Cdawg::transition_and_count`.
+    fn fail(&self, cs: CdawgState<Ix>) -> Option<CdawgState<Ix>> {
+        if cs.length == 0 {
+            return None;
+        }
+        let fail_cs = if cs.start == cs.end {
+            self.implicitly_fail(cs.target?, (cs.end, cs.end))
+        } else {
+            self.implicitly_fail(cs.state, (cs.edge_start, cs.start))
+        };
+        // Failing out of the source leaves the null state, which is the empty string.
+        match fail_cs.target {
+            Some(_) => Some(fail_cs),
+            None => Some(self.get_initial()),
+        }
+    }
+
+    fn count(&self, cs: CdawgState<Ix>) -> usize {
+        self.get_suffix_count(cs)
+    }
+
+    fn length(&self, cs: CdawgState<Ix>) -> u64 {
+        cs.length
+    }
+
+    fn state_id(&self, cs: CdawgState<Ix>) -> usize {
+        cs.target.unwrap().index()
+    }
+}
+
+impl<W, Ix, Mb, T> Cdawg<W, Ix, Mb, T>
+where
+    Ix: IndexType,
+    W: Weight + Serialize + for<'de> Deserialize<'de> + Clone,
+    Mb: MemoryBacking<W, CdawgEdgeWeight<Ix>, Ix>,
+    Mb::EdgeRef: Copy,
+    T: CdawgToken,
+{
+    /// A cursor at the source, for custom traversals.
+    pub fn walker(&self) -> Cursor<'_, Self> {
+        Cursor::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdawg::TopologicalCounter;
+    use crate::weight::DefaultWeight;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_dawg_walker() {
+        let (a, b, c) = (0, 1, 2);
+        let mut dawg: Dawg<u16, DefaultWeight> = Dawg::new();
+        dawg.build(&[a, b, c, a, b]);
+        let mut cursor = dawg.walker();
+        assert!(cursor.enter(a) && cursor.enter(b));
+        assert_eq!((cursor.length(), cursor.counts()), (2, 2));
+        assert!(!cursor.enter(a));
+        // "ab" and "b" share a state, so failing goes back to the start.
+        assert!(cursor.fail());
+        assert_eq!(cursor.length(), 0);
+        assert!(cursor.enter(c));
+        assert_eq!(cursor.length(), 1);
+
+        // Following failures as needed agrees with the built-in query.
+        let query = [b, c, c, a, b, a];
+        let mut cursor = dawg.walker();
+        let lengths: Vec<_> = query
+            .iter()
+            .map(|token| {
+                cursor.enter_or_fail(*token);
+                cursor.length()
+            })
+            .collect();
+        assert_eq!(lengths, dawg.get_suffix_length_profile(&query));
+        cursor.reset();
+        assert!(!cursor.fail());
+    }
+
+    #[test]
+    fn test_cdawg_walker() {
+        let (a, b, c, d) = (0, 1, 2, 3);
+        let tokens = vec![a, b, c, a, b, u16::MAX, b, c, d, a, b, c, u16::MAX];
+        let mut cdawg: Cdawg = Cdawg::new(Rc::new(RefCell::new(tokens)));
+        cdawg.build();
+        TopologicalCounter::new_ram().fill_counts(&mut cdawg);
+
+        let mut cursor = cdawg.walker();
+        assert!(cursor.enter(a) && cursor.enter(b) && cursor.enter(c));
+        assert_eq!((cursor.length(), cursor.counts()), (3, 2));
+        assert!(!cursor.enter(c));
+        while cursor.fail() {
+            assert!(cursor.length() < 3);
+        }
+        assert_eq!(cursor.length(), 0);
+
+        let query = [b, c, a, b, d, a, b, c, c, d];
+        let mut cursor = cdawg.walker();
+        let mut cs = cdawg.get_initial();
+        for token in query {
+            cursor.enter_or_fail(token);
+            cs = cdawg.transition_and_count(cs, token);
+            assert_eq!(cursor.length(), cs.length);
+            assert_eq!(cursor.counts(), cdawg.get_suffix_count(cs));
+            assert_eq!(cursor.emit_state_id(), cs.target.unwrap().index());
+        }
+    }
+}
@@ -1,16 +1,60 @@
+pub use self::weight32::Weight32;
 pub use self::weight40::DefaultWeight;
+pub use self::weight64::Weight64;
+pub use self::weight_approx::WeightApprox;
+pub use self::weight_no_failure::WeightNoFailure;
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str::FromStr;
+
 use crate::graph::indexing::NodeIndex;
 
+// Weights take and return failures as full-width indices, so that they work in graphs with any
+// index type. Each weight stores them in however many bits it has.
+pub type FailureIndex = NodeIndex<usize>;
+
 pub trait Weight {
     fn get_length(&self) -> u64;
     fn set_length(&mut self, length: u64);
-    fn get_failure(&self) -> Option<NodeIndex>;
-    fn set_failure(&mut self, failure: Option<NodeIndex>);
+    fn get_failure(&self) -> Option<FailureIndex>;
+    fn set_failure(&mut self, failure: Option<FailureIndex>);
     fn increment_count(&mut self);
     fn get_count(&self) -> usize;
     fn set_count(&mut self, count: usize);
+    // Whether some string in the state is the suffix of a document.
+    fn is_terminal(&self) -> bool;
+    fn set_terminal(&mut self, terminal: bool);
+
+    // Name of the weight type, as passed to `--weight-type` and recorded in CDAWG metadata.
+    fn name() -> &'static str
+    where
+        Self: Sized;
+
+    // Whether counts are stored. If not, `get_count` always returns 0.
+    fn has_counts() -> bool
+    where
+        Self: Sized,
+    {
+        true
+    }
+
+    // Whether failure links are stored. If not, `get_failure` always returns None.
+    fn has_failures() -> bool
+    where
+        Self: Sized,
+    {
+        true
+    }
 
-    fn new(length: u64, failure: Option<NodeIndex>, count: usize) -> Self
+    // Relative standard error of counts, or 0 if they are exact.
+    fn count_error() -> f64
+    where
+        Self: Sized,
+    {
+        0.
+    }
+
+    fn new(length: u64, failure: Option<FailureIndex>, count: usize) -> Self
     where
         Self: Sized;
 
@@ -40,4 +84,88 @@ pub trait Weight {
     }
 }
 
-pub mod weight40;
+/// Whether node weights count exactly, or with approximate counters that take less space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CountMode {
+    #[default]
+    Exact,
+    Approx,
+}
+
+impl fmt::Display for CountMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CountMode::Exact => write!(f, "exact"),
+            CountMode::Approx => write!(f, "approx"),
+        }
+    }
+}
+
+impl FromStr for CountMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "exact" => Ok(CountMode::Exact),
+            "approx" => Ok(CountMode::Approx),
+            _ => bail!("Invalid count mode: {} (expected exact or approx)", s),
+        }
+    }
+}
+
+/// Node weight selected with `--weight-type`. Each is a `Weight` implementation whose `name` is the
+/// string it is parsed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeightType {
+    W32,    // Weight32: 32-bit lengths and failures, and no counts.
+    W40,    // DefaultWeight: 40-bit fields.
+    W64,    // Weight64: 64-bit fields.
+    Approx, // WeightApprox: 40-bit lengths and failures, and 16-bit approximate counts.
+}
+
+impl WeightType {
+    /// The weight used unless one is chosen: the one for the index width and count mode.
+    pub fn default_for(index_bits: usize, count_mode: CountMode) -> Self {
+        match (index_bits, count_mode) {
+            (_, CountMode::Approx) => WeightType::Approx,
+            (64, CountMode::Exact) => WeightType::W64,
+            _ => WeightType::W40,
+        }
+    }
+
+    pub fn has_counts(&self) -> bool {
+        *self != WeightType::W32
+    }
+}
+
+impl fmt::Display for WeightType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WeightType::W32 => Weight32::name(),
+            WeightType::W40 => DefaultWeight::name(),
+            WeightType::W64 => Weight64::name(),
+            WeightType::Approx => WeightApprox::name(),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for WeightType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "32" => Ok(WeightType::W32),
+            "40" => Ok(WeightType::W40),
+            "64" => Ok(WeightType::W64),
+            // Approximate counts are chosen with `--count-mode approx` instead.
+            _ => bail!("Invalid weight type: {} (expected 32, 40, or 64)", s),
+        }
+    }
+}
+
+pub(crate) mod weight32; // 32-bit lengths and failures without counts, for lookup-only graphs.
+pub(crate) mod weight40;
+pub(crate) mod weight64;
+pub(crate) mod weight_approx; // 16-bit Morris counters instead of exact counts.
+pub(crate) mod weight_no_failure;
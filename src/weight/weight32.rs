@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use std::clone::Clone;
+
+use crate::graph::indexing::NodeIndex;
+use crate::weight::{FailureIndex, Weight};
+
+// Lengths and failures in 32 bits each and no counts, so 8B per node, for graphs that only answer
+// membership and longest-match queries. The top bit of the length field stores the terminal flag,
+// so lengths are limited to 31 bits, and failures to states below u32::MAX. Counts are never stored,
+// so `get_count` is always 0.
+const TERMINAL_BIT: u32 = 1 << 31;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+pub struct Weight32 {
+    length: u32,
+    failure: u32,
+}
+
+impl Weight for Weight32 {
+    fn new(length: u64, failure: Option<FailureIndex>, _count: usize) -> Self {
+        let mut weight = Self {
+            length: length as u32,
+            failure: u32::MAX,
+        };
+        weight.set_failure(failure);
+        weight
+    }
+
+    fn name() -> &'static str {
+        "32"
+    }
+
+    fn has_counts() -> bool {
+        false
+    }
+
+    fn get_length(&self) -> u64 {
+        (self.length & !TERMINAL_BIT) as u64
+    }
+
+    fn set_length(&mut self, length: u64) {
+        self.length = length as u32 | (self.length & TERMINAL_BIT);
+    }
+
+    fn get_failure(&self) -> Option<FailureIndex> {
+        if self.failure == u32::MAX {
+            return None;
+        }
+        Some(NodeIndex::new(self.failure as usize))
+    }
+
+    fn set_failure(&mut self, failure: Option<FailureIndex>) {
+        self.failure = match failure {
+            Some(f) => f.index() as u32,
+            None => u32::MAX,
+        };
+    }
+
+    fn increment_count(&mut self) {}
+
+    fn get_count(&self) -> usize {
+        0
+    }
+
+    fn set_count(&mut self, _count: usize) {}
+
+    fn is_terminal(&self) -> bool {
+        self.length & TERMINAL_BIT != 0
+    }
+
+    fn set_terminal(&mut self, terminal: bool) {
+        let terminal = if terminal { TERMINAL_BIT } else { 0 };
+        self.length = (self.length & !TERMINAL_BIT) | terminal;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::size_of;
+
+    #[test]
+    fn test_weight32() {
+        let mut weight = Weight32::new(53, Some(FailureIndex::new(2)), 4);
+        assert_eq!(weight.get_length(), 53);
+        assert_eq!(weight.get_failure(), Some(FailureIndex::new(2)));
+        assert_eq!(weight.get_count(), 0);
+        weight.set_terminal(true);
+        assert!(weight.is_terminal());
+        weight.set_length(1 << 30);
+        assert_eq!(weight.get_length(), 1 << 30);
+        assert!(weight.is_terminal());
+        weight.set_failure(None);
+        assert_eq!(weight.get_failure(), None);
+        assert_eq!(size_of::<Weight32>(), 8);
+    }
+}
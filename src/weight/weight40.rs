@@ -2,10 +2,23 @@ use serde::{Deserialize, Serialize};
 use std::clone::Clone;
 
 use crate::graph::indexing::{DefaultIx, IndexType, NodeIndex};
-use crate::weight::Weight;
+use crate::weight::{FailureIndex, Weight};
 
 pub type DefaultWeight = WeightMinimal;
 
+// The top bit of the 40-bit length field stores the terminal flag, so lengths are limited to 39 bits.
+pub(crate) const TERMINAL_BIT: usize = 1 << 39;
+
+// Panic rather than let a length spill into the terminal flag.
+pub(crate) fn check_length(length: u64) -> usize {
+    assert!(
+        length < TERMINAL_BIT as u64,
+        "Length {} doesn't fit in 39 bits",
+        length
+    );
+    length as usize
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
 pub struct WeightMinimal {
     length: DefaultIx,
@@ -14,9 +27,9 @@ pub struct WeightMinimal {
 }
 
 impl Weight for WeightMinimal {
-    fn new(length: u64, failure: Option<NodeIndex>, count: usize) -> Self {
+    fn new(length: u64, failure: Option<FailureIndex>, count: usize) -> Self {
         Self {
-            length: DefaultIx::new(length as usize),
+            length: DefaultIx::new(check_length(length)),
             //length: length as DefaultIx,
             failure: match failure {
                 Some(f) => DefaultIx::new(f.index()),
@@ -26,22 +39,27 @@ impl Weight for WeightMinimal {
         }
     }
 
+    fn name() -> &'static str {
+        "40"
+    }
+
     fn get_length(&self) -> u64 {
-        self.length.index() as u64
+        (self.length.index() & !TERMINAL_BIT) as u64
     }
 
     fn set_length(&mut self, length: u64) {
-        self.length = DefaultIx::new(length as usize);
+        let terminal = self.length.index() & TERMINAL_BIT;
+        self.length = DefaultIx::new(check_length(length) | terminal);
     }
 
-    fn get_failure(&self) -> Option<NodeIndex> {
+    fn get_failure(&self) -> Option<FailureIndex> {
         if self.failure == DefaultIx::max_value() {
             return None;
         }
         Some(NodeIndex::new(self.failure.index()))
     }
 
-    fn set_failure(&mut self, failure: Option<NodeIndex>) {
+    fn set_failure(&mut self, failure: Option<FailureIndex>) {
         match failure {
             Some(f) => self.failure = DefaultIx::new(f.index()),
             None => self.failure = DefaultIx::max_value(),
@@ -59,6 +77,16 @@ impl Weight for WeightMinimal {
     fn set_count(&mut self, count: usize) {
         self.count = DefaultIx::new(count);
     }
+
+    fn is_terminal(&self) -> bool {
+        self.length.index() & TERMINAL_BIT != 0
+    }
+
+    fn set_terminal(&mut self, terminal: bool) {
+        let length = self.length.index() & !TERMINAL_BIT;
+        let terminal = if terminal { TERMINAL_BIT } else { 0 };
+        self.length = DefaultIx::new(length | terminal);
+    }
 }
 
 #[cfg(test)]
@@ -76,4 +104,25 @@ mod tests {
         let weight = WeightMinimal::new(1 << 35, None, 0);
         assert_eq!(weight.get_length(), 1 << 35);
     }
+
+    #[test]
+    fn test_terminal_weight40() {
+        let mut weight = WeightMinimal::new(53, None, 0);
+        assert!(!weight.is_terminal());
+        weight.set_terminal(true);
+        assert!(weight.is_terminal());
+        assert_eq!(weight.get_length(), 53);
+        weight.set_length(54);
+        assert!(weight.is_terminal());
+        assert_eq!(weight.get_length(), 54);
+        weight.set_terminal(false);
+        assert!(!weight.is_terminal());
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit in 39 bits")]
+    fn test_length_into_terminal_bit_weight40() {
+        let mut weight = WeightMinimal::new(53, None, 0);
+        weight.set_length(1 << 39);
+    }
 }
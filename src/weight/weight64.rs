@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use std::clone::Clone;
+
+use crate::graph::indexing::NodeIndex;
+use crate::weight::{FailureIndex, Weight};
+
+// Same as WeightMinimal, but with 64-bit fields, for graphs built with `--index-width 64`. The top
+// bit of the length field stores the terminal flag, so lengths are limited to 63 bits.
+const TERMINAL_BIT: u64 = 1 << 63;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+pub struct Weight64 {
+    length: u64,
+    failure: u64,
+    count: u64,
+}
+
+impl Weight for Weight64 {
+    fn new(length: u64, failure: Option<FailureIndex>, count: usize) -> Self {
+        let mut weight = Self {
+            length,
+            failure: u64::MAX,
+            count: count as u64,
+        };
+        weight.set_failure(failure);
+        weight
+    }
+
+    fn name() -> &'static str {
+        "64"
+    }
+
+    fn get_length(&self) -> u64 {
+        self.length & !TERMINAL_BIT
+    }
+
+    fn set_length(&mut self, length: u64) {
+        self.length = length | (self.length & TERMINAL_BIT);
+    }
+
+    fn get_failure(&self) -> Option<FailureIndex> {
+        if self.failure == u64::MAX {
+            return None;
+        }
+        Some(NodeIndex::new(self.failure as usize))
+    }
+
+    fn set_failure(&mut self, failure: Option<FailureIndex>) {
+        self.failure = match failure {
+            Some(f) => f.index() as u64,
+            None => u64::MAX,
+        };
+    }
+
+    fn increment_count(&mut self) {
+        self.count += 1;
+    }
+
+    fn get_count(&self) -> usize {
+        self.count as usize
+    }
+
+    fn set_count(&mut self, count: usize) {
+        self.count = count as u64;
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.length & TERMINAL_BIT != 0
+    }
+
+    fn set_terminal(&mut self, terminal: bool) {
+        let terminal = if terminal { TERMINAL_BIT } else { 0 };
+        self.length = self.get_length() | terminal;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weight64() {
+        // Past what fits in the 40-bit fields of WeightMinimal.
+        let failure = NodeIndex::new(1 << 45);
+        let mut weight = Weight64::new(1 << 41, Some(failure), 1 << 42);
+        assert_eq!(weight.get_length(), 1 << 41);
+        assert_eq!(weight.get_failure(), Some(failure));
+        assert_eq!(weight.get_count(), 1 << 42);
+
+        weight.set_terminal(true);
+        weight.set_length(54);
+        assert!(weight.is_terminal());
+        assert_eq!(weight.get_length(), 54);
+        weight.set_failure(None);
+        assert_eq!(weight.get_failure(), None);
+    }
+}
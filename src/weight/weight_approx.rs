@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::clone::Clone;
+
+use crate::cdawg::sampling::XorShiftRng;
+use crate::graph::indexing::{DefaultIx, IndexType, NodeIndex};
+use crate::weight::{FailureIndex, Weight};
+
+// Same as WeightMinimal, but with a 16-bit Morris counter in place of the 40-bit count, saving 3B
+// per node. The counter stores c, and estimates the count as B((1 + 1/B)^c - 1). Each increment
+// raises c with probability (1 + 1/B)^-c, which keeps the estimate unbiased with a relative standard
+// error of about 1/sqrt(2B). Counts that are set rather than incremented, like the ones filled in a
+// CDAWG, are rounded to the nearest c. That is exact up to about sqrt(B), off by at most one up to
+// about B, and within 1/(2B) of the count beyond that.
+//
+// With B = 2048, the error is about 1.6%, and counts up to about 1.6e17 fit in 16 bits.
+const MORRIS_BASE: f64 = 2048.;
+const TERMINAL_BIT: usize = 1 << 39;
+
+thread_local! {
+    // Seeded, so that builds are reproducible.
+    static RNG: RefCell<XorShiftRng> = RefCell::new(XorShiftRng::new(0));
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+pub struct WeightApprox {
+    length: DefaultIx,
+    failure: DefaultIx,
+    counter: u16,
+}
+
+fn estimate(counter: u16) -> usize {
+    (MORRIS_BASE * ((1. / MORRIS_BASE).ln_1p() * counter as f64).exp_m1()).round() as usize
+}
+
+fn to_counter(count: usize) -> u16 {
+    let counter = (count as f64 / MORRIS_BASE).ln_1p() / (1. / MORRIS_BASE).ln_1p();
+    counter.round().min(u16::MAX as f64) as u16
+}
+
+impl Weight for WeightApprox {
+    fn new(length: u64, failure: Option<FailureIndex>, count: usize) -> Self {
+        Self {
+            length: DefaultIx::new(length as usize),
+            failure: match failure {
+                Some(f) => DefaultIx::new(f.index()),
+                None => DefaultIx::max_value(),
+            },
+            counter: to_counter(count),
+        }
+    }
+
+    fn name() -> &'static str {
+        "approx"
+    }
+
+    fn count_error() -> f64 {
+        1. / (2. * MORRIS_BASE).sqrt()
+    }
+
+    fn get_length(&self) -> u64 {
+        (self.length.index() & !TERMINAL_BIT) as u64
+    }
+
+    fn set_length(&mut self, length: u64) {
+        let terminal = self.length.index() & TERMINAL_BIT;
+        self.length = DefaultIx::new(length as usize | terminal);
+    }
+
+    fn get_failure(&self) -> Option<FailureIndex> {
+        if self.failure == DefaultIx::max_value() {
+            return None;
+        }
+        Some(NodeIndex::new(self.failure.index()))
+    }
+
+    fn set_failure(&mut self, failure: Option<FailureIndex>) {
+        match failure {
+            Some(f) => self.failure = DefaultIx::new(f.index()),
+            None => self.failure = DefaultIx::max_value(),
+        }
+    }
+
+    fn increment_count(&mut self) {
+        if self.counter == u16::MAX {
+            return;
+        }
+        let p = (-(1. / MORRIS_BASE).ln_1p() * self.counter as f64).exp();
+        if RNG.with(|rng| rng.borrow_mut().next_f64()) < p {
+            self.counter += 1;
+        }
+    }
+
+    fn get_count(&self) -> usize {
+        estimate(self.counter)
+    }
+
+    fn set_count(&mut self, count: usize) {
+        self.counter = to_counter(count);
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.length.index() & TERMINAL_BIT != 0
+    }
+
+    fn set_terminal(&mut self, terminal: bool) {
+        let length = self.length.index() & !TERMINAL_BIT;
+        let terminal = if terminal { TERMINAL_BIT } else { 0 };
+        self.length = DefaultIx::new(length | terminal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::size_of;
+
+    #[test]
+    fn test_weight_approx() {
+        let mut weight = WeightApprox::new(53, Some(FailureIndex::new(2)), 4);
+        assert_eq!(weight.get_length(), 53);
+        assert_eq!(weight.get_failure(), Some(FailureIndex::new(2)));
+        assert_eq!(weight.get_count(), 4);
+        weight.set_terminal(true);
+        assert!(weight.is_terminal());
+        assert_eq!(weight.get_length(), 53);
+        assert_eq!(size_of::<WeightApprox>(), 12);
+    }
+
+    #[test]
+    fn test_set_count_approx() {
+        for count in [0, 1, 2, 40] {
+            assert_eq!(WeightApprox::new(0, None, count).get_count(), count);
+        }
+        assert!(WeightApprox::new(0, None, 800).get_count().abs_diff(800) <= 1);
+        for count in [1_000_000, 1 << 40] {
+            let estimate = WeightApprox::new(0, None, count).get_count() as f64;
+            assert!((estimate / count as f64 - 1.).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_increment_count_approx() {
+        let mut weight = WeightApprox::initial();
+        for _ in 0..100_000 {
+            weight.increment_count();
+        }
+        let error = (weight.get_count() as f64 / 100_000. - 1.).abs();
+        assert!(error < 5. * WeightApprox::count_error());
+    }
+}
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use std::clone::Clone;
+
+use crate::graph::indexing::{DefaultIx, IndexType};
+use crate::weight::weight40::{check_length, TERMINAL_BIT};
+use crate::weight::{FailureIndex, Weight};
+
+// Same as WeightMinimal, but without the failure field, saving 5B per node. Meant for read-only
+// graphs that only answer exact lookups; see `Dawg::without_failures`. Failures are never stored,
+// so this can't be used while building.
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+pub struct WeightNoFailure {
+    length: DefaultIx,
+    count: DefaultIx,
+}
+
+impl Weight for WeightNoFailure {
+    fn new(length: u64, _failure: Option<FailureIndex>, count: usize) -> Self {
+        Self {
+            length: DefaultIx::new(check_length(length)),
+            count: DefaultIx::new(count),
+        }
+    }
+
+    fn name() -> &'static str {
+        "no-failure"
+    }
+
+    fn has_failures() -> bool {
+        false
+    }
+
+    fn get_length(&self) -> u64 {
+        (self.length.index() & !TERMINAL_BIT) as u64
+    }
+
+    fn set_length(&mut self, length: u64) {
+        let terminal = self.length.index() & TERMINAL_BIT;
+        self.length = DefaultIx::new(check_length(length) | terminal);
+    }
+
+    fn get_failure(&self) -> Option<FailureIndex> {
+        None
+    }
+
+    fn set_failure(&mut self, _failure: Option<FailureIndex>) {}
+
+    fn increment_count(&mut self) {
+        self.count = DefaultIx::new(self.count.index() + 1);
+    }
+
+    fn get_count(&self) -> usize {
+        self.count.index()
+    }
+
+    fn set_count(&mut self, count: usize) {
+        self.count = DefaultIx::new(count);
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.length.index() & TERMINAL_BIT != 0
+    }
+
+    fn set_terminal(&mut self, terminal: bool) {
+        let length = self.length.index() & !TERMINAL_BIT;
+        let terminal = if terminal { TERMINAL_BIT } else { 0 };
+        self.length = DefaultIx::new(length | terminal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::size_of;
+
+    #[test]
+    fn test_weight_no_failure() {
+        let mut weight = WeightNoFailure::new(53, Some(FailureIndex::new(2)), 4);
+        assert_eq!(weight.get_length(), 53);
+        assert_eq!(weight.get_failure(), None);
+        assert_eq!(weight.get_count(), 4);
+        weight.set_terminal(true);
+        assert!(weight.is_terminal());
+        assert_eq!(weight.get_length(), 53);
+        assert_eq!(size_of::<WeightNoFailure>(), 10);
+    }
+}